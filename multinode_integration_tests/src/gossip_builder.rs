@@ -42,6 +42,8 @@ impl GossipBuilder {
                 rate_pack: node.rate_pack().clone(),
                 neighbors: BTreeSet::new(),
                 version: 0,
+                supports_compression: false,
+                relays: BTreeSet::new(),
             },
             node_addr_opt: match include_ip {
                 true => Some(node.node_addr()),
@@ -97,7 +99,11 @@ impl GossipBuilder {
                 }
             })
             .collect();
-        Gossip { node_records }
+        Gossip {
+            node_records,
+            version_summary_opt: None,
+            delta: false,
+        }
     }
 
     pub fn build_cores_package(self, from: &PublicKey, to: &PublicKey) -> IncipientCoresPackage {
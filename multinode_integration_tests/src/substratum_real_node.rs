@@ -6,7 +6,6 @@ use crate::substratum_node::PortSelector;
 use crate::substratum_node::SubstratumNode;
 use crate::substratum_node::SubstratumNodeUtils;
 use node_lib::sub_lib::accountant;
-use node_lib::sub_lib::accountant::TEMPORARY_CONSUMING_WALLET;
 use node_lib::sub_lib::cryptde::PublicKey;
 use node_lib::sub_lib::cryptde_null::CryptDENull;
 use node_lib::sub_lib::neighborhood::sentinel_ip_addr;
@@ -110,6 +109,9 @@ impl NodeStartupConfig {
         args.push("trace".to_string());
         args.push("--data_directory".to_string());
         args.push("/node_root/home".to_string());
+        // Every Node in the cluster shares one /24, so only whole addresses can tell them apart.
+        args.push("--ipv4_diversity_prefix_len".to_string());
+        args.push("32".to_string());
         args
     }
 
@@ -399,7 +401,10 @@ impl SubstratumRealNode {
             container_ip: ip_addr,
             node_reference,
             earning_wallet,
-            consuming_wallet: Some(TEMPORARY_CONSUMING_WALLET.clone()),
+            consuming_wallet: real_startup_config
+                .consuming_private_key
+                .as_ref()
+                .map(|key| Wallet::from_private_key(key).expect("Bad consuming private key")),
             rate_pack,
             root_dir,
         });
@@ -823,6 +828,8 @@ mod tests {
                 "trace",
                 "--data_directory",
                 "/node_root/home",
+                "--ipv4_diversity_prefix_len",
+                "32",
             ))
         );
     }
@@ -44,6 +44,8 @@ fn when_bootstrapping_from_a_node_then_the_node_sends_gossip_upon_startup() {
                 rate_pack: DEFAULT_RATE_PACK,
                 neighbors: BTreeSet::default(),
                 version: 0,
+                supports_compression: true,
+                relays: BTreeSet::default(),
             };
             let cryptde = CryptDENull::from(&node_ref.public_key);
             let signed_data = PlainData::from(serde_cbor::ser::to_vec(&inner).unwrap());
@@ -76,6 +76,8 @@ fn graph_connects_but_does_not_over_connect() {
         is_bootstrap_node: false,
         neighbors: vec_to_btset(vec![start_node.public_key().clone()]),
         version: 100, // to make the sample Node update its database and send out standard Gossip
+        supports_compression: false,
+        relays: vec_to_btset(vec![]),
     };
     let standard_gossip = Gossip {
         node_records: vec![
@@ -86,6 +88,8 @@ fn graph_connects_but_does_not_over_connect() {
             )),
             GossipNodeRecord::from(another_agr.clone()),
         ],
+        version_summary_opt: None,
+        delta: false,
     };
     let socket_addrs: Vec<SocketAddr> = start_node.node_addr().into();
     mock_node
@@ -8,9 +8,11 @@ use crate::sub_lib::accountant::ReportExitServiceConsumedMessage;
 use crate::sub_lib::accountant::ReportExitServiceProvidedMessage;
 use crate::sub_lib::accountant::ReportRoutingServiceConsumedMessage;
 use crate::sub_lib::accountant::ReportRoutingServiceProvidedMessage;
+use crate::sub_lib::accountant::SetPayableScanIntervalMessage;
 use crate::sub_lib::blockchain_bridge::ReportAccountsPayable;
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::peer_actors::BindMessage;
+use crate::sub_lib::ui_gateway::{UiEvent, UiEventMessage};
 use crate::sub_lib::utils::NODE_MAILBOX_CAPACITY;
 use crate::sub_lib::wallet::Wallet;
 use actix::Actor;
@@ -19,6 +21,7 @@ use actix::AsyncContext;
 use actix::Context;
 use actix::Handler;
 use actix::Recipient;
+use actix::SpawnHandle;
 use std::time::SystemTime;
 
 pub const PAYMENT_CURVE_MINIMUM_TIME: i64 = 86_400; // one day
@@ -32,6 +35,8 @@ pub struct Accountant {
     payable_dao: Box<PayableDao>,
     receivable_dao: Box<ReceivableDao>,
     report_accounts_payable_sub: Option<Recipient<ReportAccountsPayable>>,
+    ui_event_sub: Option<Recipient<UiEventMessage>>,
+    payable_scan_handle: Option<SpawnHandle>,
     logger: Logger,
 }
 
@@ -45,20 +50,30 @@ impl Handler<BindMessage> for Accountant {
     fn handle(&mut self, msg: BindMessage, ctx: &mut Self::Context) -> Self::Result {
         self.report_accounts_payable_sub =
             Some(msg.peer_actors.blockchain_bridge.report_accounts_payable);
+        self.ui_event_sub = Some(msg.peer_actors.ui_gateway.event_sub);
         ctx.set_mailbox_capacity(NODE_MAILBOX_CAPACITY);
-        ctx.run_interval(self.config.payable_scan_interval, |act, _ctx| {
-            act.logger.debug("Scanning for payables".to_string());
-            Accountant::scan_for_payables(
-                act.payable_dao.as_ref(),
-                act.report_accounts_payable_sub
-                    .as_ref()
-                    .expect("BlockchainBridge is unbound"),
-            );
-        });
+        self.schedule_payable_scans(ctx);
         self.logger.info(String::from("Accountant bound"));
     }
 }
 
+impl Handler<SetPayableScanIntervalMessage> for Accountant {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: SetPayableScanIntervalMessage,
+        ctx: &mut Self::Context,
+    ) -> Self::Result {
+        self.logger.info(format!(
+            "Payable scan interval changes from {:?} to {:?}",
+            self.config.payable_scan_interval, msg.payable_scan_interval
+        ));
+        self.config.payable_scan_interval = msg.payable_scan_interval;
+        self.schedule_payable_scans(ctx);
+    }
+}
+
 impl Handler<ReportRoutingServiceProvidedMessage> for Accountant {
     type Result = ();
 
@@ -154,6 +169,8 @@ impl Accountant {
             payable_dao,
             receivable_dao,
             report_accounts_payable_sub: None,
+            ui_event_sub: None,
+            payable_scan_handle: None,
             logger: Logger::new("Accountant"),
         }
     }
@@ -173,12 +190,34 @@ impl Accountant {
             report_exit_service_consumed: addr
                 .clone()
                 .recipient::<ReportExitServiceConsumedMessage>(),
+            set_payable_scan_interval: addr.clone().recipient::<SetPayableScanIntervalMessage>(),
         }
     }
 
+    // Replaces any scans already scheduled
+    fn schedule_payable_scans(&mut self, ctx: &mut Context<Accountant>) {
+        if let Some(handle) = self.payable_scan_handle.take() {
+            ctx.cancel_future(handle);
+        }
+        self.payable_scan_handle = Some(ctx.run_interval(
+            self.config.payable_scan_interval,
+            |act, _ctx| {
+                act.logger.debug("Scanning for payables".to_string());
+                Accountant::scan_for_payables(
+                    act.payable_dao.as_ref(),
+                    act.report_accounts_payable_sub
+                        .as_ref()
+                        .expect("BlockchainBridge is unbound"),
+                    act.ui_event_sub.as_ref().expect("UiGateway is unbound"),
+                );
+            },
+        ));
+    }
+
     fn scan_for_payables(
         payable_dao: &PayableDao,
         report_accounts_payable_sub: &Recipient<ReportAccountsPayable>,
+        ui_event_sub: &Recipient<UiEventMessage>,
     ) {
         let payables = payable_dao
             .non_pending_payables()
@@ -187,6 +226,15 @@ impl Accountant {
             .collect::<Vec<PayableAccount>>();
 
         if !payables.is_empty() {
+            payables.iter().for_each(|payable| {
+                // Watching UIs are told, but a busy UiGateway mustn't hold up payment.
+                let _ = ui_event_sub.try_send(UiEventMessage {
+                    event: UiEvent::PaymentRequested {
+                        wallet: payable.wallet_address.address.clone(),
+                        amount: payable.balance,
+                    },
+                });
+            });
             report_accounts_payable_sub
                 .try_send(ReportAccountsPayable { accounts: payables })
                 .expect("BlockchainBridge is dead");
@@ -265,6 +313,7 @@ pub mod tests {
     use crate::test_utils::recorder::Recorder;
     use actix::System;
     use std::cell::RefCell;
+    use std::sync::mpsc;
     use std::sync::Arc;
     use std::sync::Mutex;
     use std::thread;
@@ -394,6 +443,7 @@ pub mod tests {
     fn accountant_timer_triggers_scanning_for_payables() {
         init_test_logging();
         let (blockchain_bridge, blockchain_bridge_awaiter, _) = make_recorder();
+        let (tx, rx) = mpsc::channel();
         thread::spawn(move || {
             let system = System::new("accountant_timer_triggers_scanning_for_payables");
             let config = AccountantConfig {
@@ -417,7 +467,7 @@ pub mod tests {
             let receivable_dao = Box::new(ReceivableDaoMock::new());
             let subject = Accountant::new(config, payable_dao, receivable_dao);
             let peer_actors = peer_actors_builder()
-                .blockchain_bridge(blockchain_bridge)
+                .blockchain_bridge(blockchain_bridge.stop_after_count(2))
                 .build();
             let subject_addr: Addr<Accountant> = subject.start();
             let subject_subs = Accountant::make_subs_from(&subject_addr);
@@ -428,12 +478,67 @@ pub mod tests {
                 .unwrap();
 
             system.run();
+            tx.send(()).unwrap();
         });
 
         blockchain_bridge_awaiter.await_message_count(2);
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
         TestLogHandler::new().exists_log_containing("DEBUG: Accountant: Scanning for payables");
     }
 
+    #[test]
+    fn set_payable_scan_interval_message_reschedules_scanning_for_payables() {
+        init_test_logging();
+        let (blockchain_bridge, blockchain_bridge_awaiter, _) = make_recorder();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let system =
+                System::new("set_payable_scan_interval_message_reschedules_scanning_for_payables");
+            let config = AccountantConfig {
+                payable_scan_interval: Duration::from_secs(3600),
+            };
+            let now = to_time_t(&SystemTime::now());
+            let accounts = vec![PayableAccount {
+                wallet_address: Wallet::new("wallet0"),
+                balance: PAYMENT_CURVE_MINIMUM_BALANCE + 1,
+                last_paid_timestamp: from_time_t(now - PAYMENT_CURVE_TIME_INTERSECTION - 10),
+                pending_payment_transaction: None,
+            }];
+            let payable_dao = Box::new(
+                PayableDaoMock::new()
+                    .non_pending_payables_result(accounts.clone())
+                    .non_pending_payables_result(accounts),
+            );
+            let receivable_dao = Box::new(ReceivableDaoMock::new());
+            let subject = Accountant::new(config, payable_dao, receivable_dao);
+            let peer_actors = peer_actors_builder()
+                .blockchain_bridge(blockchain_bridge.stop_after_count(2))
+                .build();
+            let subject_addr: Addr<Accountant> = subject.start();
+            let subject_subs = Accountant::make_subs_from(&subject_addr);
+            subject_subs
+                .bind
+                .try_send(BindMessage { peer_actors })
+                .unwrap();
+
+            subject_subs
+                .set_payable_scan_interval
+                .try_send(SetPayableScanIntervalMessage {
+                    payable_scan_interval: Duration::from_millis(100),
+                })
+                .unwrap();
+
+            system.run();
+            tx.send(()).unwrap();
+        });
+
+        blockchain_bridge_awaiter.await_message_count(2);
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        TestLogHandler::new().exists_log_containing(
+            "INFO: Accountant: Payable scan interval changes from 3600s to 100ms",
+        );
+    }
+
     #[test]
     fn scan_for_payables_message_does_not_trigger_payment_for_balances_below_the_curve() {
         init_test_logging();
@@ -468,14 +573,18 @@ pub mod tests {
         );
         let subject_addr: Addr<Recorder> = blockchain_bridge.start();
         let report_accounts_payable_sub = subject_addr.recipient::<ReportAccountsPayable>();
+        let (ui_gateway, _, ui_gateway_recordings_arc) = make_recorder();
+        let ui_event_sub = ui_gateway.start().recipient::<UiEventMessage>();
 
-        Accountant::scan_for_payables(&payable_dao, &report_accounts_payable_sub);
+        Accountant::scan_for_payables(&payable_dao, &report_accounts_payable_sub, &ui_event_sub);
 
         System::current().stop_with_code(0);
         system.run();
 
         let blockchain_bridge_recordings = blockchain_bridge_recordings_arc.lock().unwrap();
         assert_eq!(blockchain_bridge_recordings.len(), 0);
+        let ui_gateway_recordings = ui_gateway_recordings_arc.lock().unwrap();
+        assert_eq!(ui_gateway_recordings.len(), 0);
     }
 
     #[test]
@@ -505,12 +614,25 @@ pub mod tests {
             System::new("scan_for_payables_message_triggers_payment_for_balances_over_the_curve");
         let subject_addr: Addr<Recorder> = blockchain_bridge.start();
         let report_accounts_payable_sub = subject_addr.recipient::<ReportAccountsPayable>();
+        let (ui_gateway, ui_gateway_awaiter, ui_gateway_recordings_arc) = make_recorder();
+        let ui_event_sub = ui_gateway.start().recipient::<UiEventMessage>();
 
-        Accountant::scan_for_payables(&payable_dao, &report_accounts_payable_sub);
+        Accountant::scan_for_payables(&payable_dao, &report_accounts_payable_sub, &ui_event_sub);
 
         System::current().stop_with_code(0);
         system.run();
 
+        ui_gateway_awaiter.await_message_count(2);
+        let ui_gateway_recordings = ui_gateway_recordings_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recordings.get_record::<UiEventMessage>(1),
+            &UiEventMessage {
+                event: UiEvent::PaymentRequested {
+                    wallet: "wallet1".to_string(),
+                    amount: PAYMENT_CURVE_BALANCE_INTERSECTION + 1,
+                }
+            }
+        );
         blockchain_bridge_awaiter.await_message_count(1);
         let blockchain_bridge_recordings = blockchain_bridge_recordings_arc.lock().unwrap();
         assert_eq!(
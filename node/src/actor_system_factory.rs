@@ -6,6 +6,7 @@ use super::discriminator::DiscriminatorFactory;
 use super::dispatcher::Dispatcher;
 use super::hopper::hopper::Hopper;
 use super::neighborhood::neighborhood::Neighborhood;
+use super::neighborhood::neighborhood_dao::NeighborhoodDaoReal;
 use super::proxy_client::proxy_client::ProxyClient;
 use super::proxy_server::proxy_server::ProxyServer;
 use super::stream_handler_pool::StreamHandlerPool;
@@ -31,6 +32,7 @@ use crate::sub_lib::hopper::HopperSubs;
 use crate::sub_lib::neighborhood::BootstrapNeighborhoodNowMessage;
 use crate::sub_lib::neighborhood::NeighborhoodConfig;
 use crate::sub_lib::neighborhood::NeighborhoodSubs;
+use crate::sub_lib::neighborhood::RouteConstraints;
 use crate::sub_lib::peer_actors::BindMessage;
 use crate::sub_lib::peer_actors::PeerActors;
 use crate::sub_lib::proxy_client::ProxyClientConfig;
@@ -81,8 +83,12 @@ impl ActorSystemFactoryReal {
         let db_initializer = DbInitializerReal::new();
         // make all the actors
         let (dispatcher_subs, pool_bind_sub) = actor_factory.make_and_start_dispatcher();
-        let proxy_server_subs = actor_factory
-            .make_and_start_proxy_server(cryptde, config.neighborhood_config.is_decentralized());
+        let proxy_server_subs = actor_factory.make_and_start_proxy_server(
+            cryptde,
+            config.neighborhood_config.is_decentralized(),
+            config.multipath,
+            config.route_constraints,
+        );
         let proxy_client_subs = actor_factory.make_and_start_proxy_client(ProxyClientConfig {
             cryptde,
             dns_servers: config.dns_servers,
@@ -95,8 +101,12 @@ impl ActorSystemFactoryReal {
             per_routing_service: config.neighborhood_config.rate_pack.routing_service_rate,
             per_routing_byte: config.neighborhood_config.rate_pack.routing_byte_rate,
         });
-        let neighborhood_subs =
-            actor_factory.make_and_start_neighborhood(cryptde, config.neighborhood_config);
+        let neighborhood_subs = actor_factory.make_and_start_neighborhood(
+            cryptde,
+            config.neighborhood_config,
+            &config.data_directory,
+            &db_initializer,
+        );
         let accountant_subs = actor_factory.make_and_start_accountant(
             config.accountant_config,
             &config.data_directory,
@@ -183,6 +193,7 @@ impl ActorSystemFactoryReal {
                 dispatcher_subs: dispatcher_subs.clone(),
                 stream_handler_pool_subs: stream_handler_pool_subs.clone(),
                 neighborhood_subs: neighborhood_subs.clone(),
+                hopper_subs: peer_actors.hopper.clone(),
             })
             .expect("Stream Handler Pool is dead");
         pool_bind_sub
@@ -190,6 +201,7 @@ impl ActorSystemFactoryReal {
                 dispatcher_subs,
                 stream_handler_pool_subs: stream_handler_pool_subs.clone(),
                 neighborhood_subs: neighborhood_subs.clone(),
+                hopper_subs: peer_actors.hopper.clone(),
             })
             .expect("Dispatcher is dead");
         peer_actors
@@ -209,12 +221,16 @@ pub trait ActorFactory: Send {
         &self,
         cryptde: &'static dyn CryptDE,
         is_decentralized: bool,
+        multipath: bool,
+        route_constraints: RouteConstraints,
     ) -> ProxyServerSubs;
     fn make_and_start_hopper(&self, config: HopperConfig) -> HopperSubs;
     fn make_and_start_neighborhood(
         &self,
         cryptde: &'static dyn CryptDE,
         config: NeighborhoodConfig,
+        data_directory: &PathBuf,
+        db_initializer: &dyn DbInitializer,
     ) -> NeighborhoodSubs;
     fn make_and_start_accountant(
         &self,
@@ -250,8 +266,11 @@ impl ActorFactory for ActorFactoryReal {
         &self,
         cryptde: &'static dyn CryptDE,
         is_decentralized: bool,
+        multipath: bool,
+        route_constraints: RouteConstraints,
     ) -> ProxyServerSubs {
-        let proxy_server = ProxyServer::new(cryptde, is_decentralized);
+        let proxy_server =
+            ProxyServer::new(cryptde, is_decentralized, multipath, route_constraints);
         let addr: Addr<ProxyServer> = proxy_server.start();
         ProxyServer::make_subs_from(&addr)
     }
@@ -266,8 +285,16 @@ impl ActorFactory for ActorFactoryReal {
         &self,
         cryptde: &'static dyn CryptDE,
         config: NeighborhoodConfig,
+        data_directory: &PathBuf,
+        db_initializer: &dyn DbInitializer,
     ) -> NeighborhoodSubs {
-        let neighborhood = Neighborhood::new(cryptde, config);
+        let neighborhood_dao = Box::new(NeighborhoodDaoReal::new(
+            db_initializer
+                .initialize(data_directory)
+                .expect("Failed to connect to database"),
+        ));
+        let mut neighborhood = Neighborhood::new(cryptde, config);
+        neighborhood.restore_from(neighborhood_dao);
         let addr: Addr<Neighborhood> = neighborhood.start();
         Neighborhood::make_subs_from(&addr)
     }
@@ -340,8 +367,10 @@ mod tests {
     use super::*;
     use crate::blockchain::blockchain_interface::TESTNET_CONTRACT_ADDRESS;
     use crate::bootstrapper::CRYPT_DE_OPT;
+    use crate::config_reload::ReloadableConfiguration;
     use crate::database::db_initializer::test_utils::{ConnectionWrapperMock, DbInitializerMock};
     use crate::database::db_initializer::InitializationError;
+    use crate::log_file::LogRotation;
     use crate::neighborhood::gossip::Gossip;
     use crate::stream_messages::AddStreamMsg;
     use crate::stream_messages::RemoveStreamMsg;
@@ -349,24 +378,40 @@ mod tests {
     use crate::sub_lib::accountant::ReportExitServiceProvidedMessage;
     use crate::sub_lib::accountant::ReportRoutingServiceConsumedMessage;
     use crate::sub_lib::accountant::ReportRoutingServiceProvidedMessage;
+    use crate::sub_lib::accountant::SetPayableScanIntervalMessage;
     use crate::sub_lib::blockchain_bridge::ReportAccountsPayable;
     use crate::sub_lib::crash_point::CrashPoint;
     use crate::sub_lib::cryptde::PlainData;
     use crate::sub_lib::dispatcher::InboundClientData;
     use crate::sub_lib::hopper::IncipientCoresPackage;
     use crate::sub_lib::hopper::{ExpiredCoresPackage, NoLookupIncipientCoresPackage};
+    use crate::sub_lib::hopper::{RouteFailure, UndeliverablePackageMessage};
+    use crate::sub_lib::logger::LogFormat;
+    use crate::sub_lib::neighborhood::sentinel_ip_addr;
+    use crate::sub_lib::neighborhood::DiversityConfig;
+    use crate::sub_lib::neighborhood::Heartbeat;
+    use crate::sub_lib::neighborhood::NeighborhoodGraphRequest;
     use crate::sub_lib::neighborhood::NodeQueryMessage;
+    use crate::sub_lib::neighborhood::RelayRequestMessage;
     use crate::sub_lib::neighborhood::RemoveNeighborMessage;
     use crate::sub_lib::neighborhood::RouteQueryMessage;
+    use crate::sub_lib::neighborhood::SetInitialNeighborsMessage;
+    use crate::sub_lib::neighborhood::SetRatePackMessage;
     use crate::sub_lib::neighborhood::{DispatcherNodeQueryMessage, NodeRecordMetadataMessage};
     use crate::sub_lib::proxy_client::{
-        ClientResponsePayload, DnsResolveFailure, InboundServerData,
+        ClientResponsePayload, DnsResolveFailure, InboundServerData, SetDnsServersMessage,
     };
+    use crate::sub_lib::proxy_server::ActiveStreamsRequest;
+    use crate::sub_lib::proxy_server::SetRouteConstraintsMessage;
     use crate::sub_lib::proxy_server::{AddReturnRouteMessage, ClientRequestPayload};
     use crate::sub_lib::stream_handler_pool::DispatcherNodeQueryResponse;
     use crate::sub_lib::stream_handler_pool::TransmitDataMsg;
+    use crate::sub_lib::ui_gateway::NodeConfiguration;
     use crate::sub_lib::ui_gateway::UiGatewayConfig;
-    use crate::sub_lib::ui_gateway::{FromUiMessage, UiCarrierMessage};
+    use crate::sub_lib::ui_gateway::UiTokens;
+    use crate::sub_lib::ui_gateway::{
+        ConnectionStateMessage, FromUiMessage, UiCarrierMessage, UiEventMessage,
+    };
     use crate::sub_lib::wallet::Wallet;
     use crate::test_utils::recorder::Recorder;
     use crate::test_utils::recorder::Recording;
@@ -417,12 +462,14 @@ mod tests {
             &self,
             cryptde: &'a dyn CryptDE,
             is_decentralized: bool,
+            multipath: bool,
+            route_constraints: RouteConstraints,
         ) -> ProxyServerSubs {
             self.parameters
                 .proxy_server_params
                 .lock()
                 .unwrap()
-                .get_or_insert((cryptde, is_decentralized));
+                .get_or_insert((cryptde, is_decentralized, multipath, route_constraints));
             let addr: Addr<Recorder> = ActorFactoryMock::start_recorder(&self.proxy_server);
             ProxyServerSubs {
                 bind: addr.clone().recipient::<BindMessage>(),
@@ -433,7 +480,12 @@ mod tests {
                 dns_failure_from_hopper: addr
                     .clone()
                     .recipient::<ExpiredCoresPackage<DnsResolveFailure>>(),
+                route_failure_from_hopper: addr
+                    .clone()
+                    .recipient::<ExpiredCoresPackage<RouteFailure>>(),
                 add_return_route: addr.clone().recipient::<AddReturnRouteMessage>(),
+                set_route_constraints: addr.clone().recipient::<SetRouteConstraintsMessage>(),
+                active_streams_request: addr.clone().recipient::<ActiveStreamsRequest>(),
             }
         }
 
@@ -451,6 +503,8 @@ mod tests {
                     .clone()
                     .recipient::<NoLookupIncipientCoresPackage>(),
                 from_dispatcher: addr.clone().recipient::<InboundClientData>(),
+                undeliverable_package: addr.clone().recipient::<UndeliverablePackageMessage>(),
+                set_rate_pack: addr.clone().recipient::<SetRatePackMessage>(),
             }
         }
 
@@ -458,6 +512,8 @@ mod tests {
             &self,
             cryptde: &'a dyn CryptDE,
             config: NeighborhoodConfig,
+            _data_directory: &PathBuf,
+            _db_initializer: &dyn DbInitializer,
         ) -> NeighborhoodSubs {
             self.parameters
                 .neighborhood_params
@@ -474,6 +530,11 @@ mod tests {
                 from_hopper: addr.clone().recipient::<ExpiredCoresPackage<Gossip>>(),
                 dispatcher_node_query: addr.clone().recipient::<DispatcherNodeQueryMessage>(),
                 remove_neighbor: addr.clone().recipient::<RemoveNeighborMessage>(),
+                heartbeat_from_hopper: addr.clone().recipient::<ExpiredCoresPackage<Heartbeat>>(),
+                from_relay_client: addr.clone().recipient::<RelayRequestMessage>(),
+                graph_request: addr.clone().recipient::<NeighborhoodGraphRequest>(),
+                set_rate_pack: addr.clone().recipient::<SetRatePackMessage>(),
+                set_initial_neighbors: addr.clone().recipient::<SetInitialNeighborsMessage>(),
             }
         }
 
@@ -503,6 +564,9 @@ mod tests {
                 report_exit_service_consumed: addr
                     .clone()
                     .recipient::<ReportExitServiceConsumedMessage>(),
+                set_payable_scan_interval: addr
+                    .clone()
+                    .recipient::<SetPayableScanIntervalMessage>(),
             }
         }
 
@@ -517,6 +581,8 @@ mod tests {
                 bind: addr.clone().recipient::<BindMessage>(),
                 ui_message_sub: addr.clone().recipient::<UiCarrierMessage>(),
                 from_ui_message_sub: addr.clone().recipient::<FromUiMessage>(),
+                connection_state_sub: addr.clone().recipient::<ConnectionStateMessage>(),
+                event_sub: addr.clone().recipient::<UiEventMessage>(),
             }
         }
 
@@ -548,6 +614,8 @@ mod tests {
                     .recipient::<ExpiredCoresPackage<ClientRequestPayload>>(),
                 inbound_server_data: addr.clone().recipient::<InboundServerData>(),
                 dns_resolve_failed: addr.clone().recipient::<DnsResolveFailure>(),
+                set_rate_pack: addr.clone().recipient::<SetRatePackMessage>(),
+                set_dns_servers: addr.clone().recipient::<SetDnsServersMessage>(),
             }
         }
 
@@ -583,7 +651,7 @@ mod tests {
     #[derive(Clone)]
     struct Parameters<'a> {
         proxy_client_params: Arc<Mutex<Option<(ProxyClientConfig)>>>,
-        proxy_server_params: Arc<Mutex<Option<(&'a dyn CryptDE, bool)>>>,
+        proxy_server_params: Arc<Mutex<Option<(&'a dyn CryptDE, bool, bool, RouteConstraints)>>>,
         hopper_params: Arc<Mutex<Option<HopperConfig>>>,
         neighborhood_params: Arc<Mutex<Option<(&'a dyn CryptDE, NeighborhoodConfig)>>>,
         accountant_params: Arc<Mutex<Option<(AccountantConfig, PathBuf)>>>,
@@ -711,6 +779,32 @@ mod tests {
         subject.make_and_start_accountant(config, &PathBuf::new(), &db_initializer_mock);
     }
 
+    #[test]
+    #[should_panic(expected = "Failed to connect to database: SqliteError(InvalidQuery)")]
+    fn failed_neighborhood_initialization_produces_panic() {
+        let db_initializer_mock = DbInitializerMock::new().initialize_result(Err(
+            InitializationError::SqliteError(rusqlite::Error::InvalidQuery),
+        ));
+        let subject = ActorFactoryReal {};
+        subject.make_and_start_neighborhood(
+            cryptde(),
+            NeighborhoodConfig {
+                neighbor_configs: vec![],
+                is_bootstrap_node: false,
+                local_ip_addr: sentinel_ip_addr(),
+                clandestine_port_list: vec![],
+                earning_wallet: Wallet::new("router"),
+                consuming_wallet: None,
+                rate_pack: rate_pack(100),
+                gossip_batch_window: Duration::from_millis(0),
+                diversity: DiversityConfig::default(),
+                behind_nat: false,
+            },
+            &PathBuf::new(),
+            &db_initializer_mock,
+        );
+    }
+
     #[test]
     #[should_panic(expected = "Invalid blockchain node URL")]
     fn invalid_blockchain_url_produces_panic() {
@@ -729,6 +823,9 @@ mod tests {
         let recordings = actor_factory.get_recordings();
         let config = BootstrapperConfig {
             log_level: LevelFilter::Off,
+            log_format: LogFormat::Text,
+            log_directory: PathBuf::new(),
+            log_rotation: LogRotation::default(),
             crash_point: CrashPoint::None,
             dns_servers: vec![],
             neighborhood_config: NeighborhoodConfig {
@@ -739,6 +836,9 @@ mod tests {
                 earning_wallet: Wallet::new("router"),
                 consuming_wallet: Some(Wallet::new("consumer")),
                 rate_pack: rate_pack(100),
+                gossip_batch_window: Duration::from_millis(0),
+                diversity: DiversityConfig::default(),
+                behind_nat: false,
             },
             accountant_config: AccountantConfig {
                 payable_scan_interval: Duration::from_secs(100),
@@ -747,6 +847,10 @@ mod tests {
             ui_gateway_config: UiGatewayConfig {
                 ui_port: 5335,
                 node_descriptor: String::from(""),
+                node_configuration: NodeConfiguration::default(),
+                ui_tokens: UiTokens::default(),
+                reload_args: vec![],
+                reloadable_configuration: ReloadableConfiguration::default(),
             },
             blockchain_bridge_config: BlockchainBridgeConfig {
                 blockchain_service_url: None,
@@ -755,6 +859,8 @@ mod tests {
             },
             clandestine_port_opt: None,
             data_directory: PathBuf::new(),
+            multipath: false,
+            route_constraints: RouteConstraints::default(),
         };
         let subject = ActorSystemFactoryReal {};
         unsafe {
@@ -786,6 +892,9 @@ mod tests {
         let parameters = actor_factory.make_parameters();
         let config = BootstrapperConfig {
             log_level: LevelFilter::Off,
+            log_format: LogFormat::Text,
+            log_directory: PathBuf::new(),
+            log_rotation: LogRotation::default(),
             crash_point: CrashPoint::None,
             dns_servers: vec![],
             neighborhood_config: NeighborhoodConfig {
@@ -796,6 +905,9 @@ mod tests {
                 earning_wallet: Wallet::new("router"),
                 consuming_wallet: Some(Wallet::new("consumer")),
                 rate_pack: rate_pack(100),
+                gossip_batch_window: Duration::from_millis(0),
+                diversity: DiversityConfig::default(),
+                behind_nat: false,
             },
             accountant_config: AccountantConfig {
                 payable_scan_interval: Duration::from_secs(100),
@@ -804,6 +916,10 @@ mod tests {
             ui_gateway_config: UiGatewayConfig {
                 ui_port: 5335,
                 node_descriptor: String::from("NODE-DESCRIPTOR"),
+                node_configuration: NodeConfiguration::default(),
+                ui_tokens: UiTokens::default(),
+                reload_args: vec![],
+                reloadable_configuration: ReloadableConfiguration::default(),
             },
             blockchain_bridge_config: BlockchainBridgeConfig {
                 blockchain_service_url: None,
@@ -812,6 +928,8 @@ mod tests {
             },
             clandestine_port_opt: None,
             data_directory: PathBuf::new(),
+            multipath: false,
+            route_constraints: RouteConstraints::default(),
         };
         let (tx, rx) = mpsc::channel();
         let system = System::new("SubstratumNode");
@@ -841,10 +959,12 @@ mod tests {
         assert_eq!(proxy_client_config.exit_service_rate, rate_pack_exit(100),);
         assert_eq!(proxy_client_config.exit_byte_rate, rate_pack_exit_byte(100),);
         assert_eq!(proxy_client_config.dns_servers, config.dns_servers);
-        let (actual_cryptde, actual_is_decentralized) =
+        let (actual_cryptde, actual_is_decentralized, actual_multipath, actual_route_constraints) =
             Parameters::get(parameters.proxy_server_params);
         check_cryptde(actual_cryptde);
         assert_eq!(actual_is_decentralized, false);
+        assert_eq!(actual_multipath, false);
+        assert_eq!(actual_route_constraints, config.route_constraints);
         let (cryptde, neighborhood_config) = Parameters::get(parameters.neighborhood_params);
         check_cryptde(cryptde);
         assert_eq!(neighborhood_config, config.neighborhood_config);
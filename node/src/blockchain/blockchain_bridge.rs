@@ -253,9 +253,7 @@ mod tests {
             amount: U256::from(21),
         }];
         let result = Box::new(ok(expected_results.clone()));
-        let wallet = Wallet {
-            address: "smelly".to_string(),
-        };
+        let wallet = Wallet::new("smelly");
         let blockchain_interface_mock =
             BlockchainInterfaceMock::default().retrieve_transactions_result(result);
         let retrieve_transactions_parameters = blockchain_interface_mock
@@ -5,6 +5,9 @@ use crate::actor_system_factory::ActorSystemFactory;
 use crate::actor_system_factory::ActorSystemFactoryReal;
 use crate::blockchain::blockchain_interface::TESTNET_CONTRACT_ADDRESS;
 use crate::config_dao::ConfigDaoReal;
+use crate::config_file::merge_config_file;
+use crate::config_file::CONFIG_FILE_NAME;
+use crate::config_reload::ReloadableConfiguration;
 use crate::configuration::{Configuration, PortConfiguration};
 use crate::crash_test_dummy::CrashTestDummy;
 use crate::database::db_initializer::{DbInitializer, DbInitializerReal};
@@ -13,6 +16,10 @@ use crate::json_discriminator_factory::JsonDiscriminatorFactory;
 use crate::listener_handler::ListenerHandler;
 use crate::listener_handler::ListenerHandlerFactory;
 use crate::listener_handler::ListenerHandlerFactoryReal;
+use crate::log_file::LogRotation;
+use crate::log_file::DEFAULT_LOG_RETENTION;
+use crate::log_file::DEFAULT_LOG_ROTATE_HOURS;
+use crate::log_file::DEFAULT_LOG_ROTATE_SIZE_MB;
 use crate::persistent_configuration::{
     PersistentConfiguration, PersistentConfigurationReal, LOWEST_USABLE_INSECURE_PORT,
 };
@@ -23,24 +30,42 @@ use crate::sub_lib::blockchain_bridge::BlockchainBridgeConfig;
 use crate::sub_lib::crash_point::CrashPoint;
 use crate::sub_lib::cryptde::CryptDE;
 use crate::sub_lib::cryptde_null::CryptDENull;
+use crate::sub_lib::ip_range::IpRange;
+use crate::sub_lib::logger::LogFormat;
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::main_tools::StdStreams;
+use crate::sub_lib::neighborhood::public_key_from_str;
+use crate::sub_lib::neighborhood::DiversityConfig;
 use crate::sub_lib::neighborhood::NeighborhoodConfig;
+use crate::sub_lib::neighborhood::RatePack;
+use crate::sub_lib::neighborhood::RouteConstraints;
+use crate::sub_lib::neighborhood::DEFAULT_GOSSIP_BATCH_WINDOW_MS;
+use crate::sub_lib::neighborhood::DEFAULT_IPV4_DIVERSITY_PREFIX_LEN;
+use crate::sub_lib::neighborhood::DEFAULT_IPV6_DIVERSITY_PREFIX_LEN;
+use crate::sub_lib::neighborhood::DEFAULT_MAX_NODES_PER_PREFIX;
 use crate::sub_lib::neighborhood::DEFAULT_RATE_PACK;
+use crate::sub_lib::neighborhood::MAX_RATE;
 use crate::sub_lib::neighborhood::{sentinel_ip_addr, NodeDescriptor};
 use crate::sub_lib::socket_server::SocketServer;
+use crate::sub_lib::ui_gateway::NodeConfiguration;
 use crate::sub_lib::ui_gateway::UiGatewayConfig;
+use crate::sub_lib::ui_gateway::UiTokens;
+use crate::sub_lib::ui_gateway::WalletAddresses;
 use crate::sub_lib::ui_gateway::DEFAULT_UI_PORT;
 use crate::sub_lib::wallet::Wallet;
+use crate::ui_gateway::ui_tokens::make_ui_tokens;
 use base64;
 use clap::{
     arg_enum, crate_authors, crate_description, crate_version, value_t, values_t, App, Arg,
+    ArgMatches, ErrorKind,
 };
 use dirs::data_dir;
 use futures::try_ready;
 use log::LevelFilter;
 use regex::Regex;
 use std::env;
+use std::env::temp_dir;
+use std::fs;
 use std::net::IpAddr;
 use std::net::Ipv4Addr;
 use std::net::SocketAddr;
@@ -75,6 +100,9 @@ impl Into<bool> for NodeType {
 #[derive(Clone)]
 pub struct BootstrapperConfig {
     pub log_level: LevelFilter,
+    pub log_format: LogFormat,
+    pub log_directory: PathBuf,
+    pub log_rotation: LogRotation,
     pub dns_servers: Vec<SocketAddr>,
     pub neighborhood_config: NeighborhoodConfig,
     pub accountant_config: AccountantConfig,
@@ -87,12 +115,17 @@ pub struct BootstrapperConfig {
     // to an unfortunate ownership and privilege situation for the database file.
     pub clandestine_port_opt: Option<u16>,
     pub data_directory: PathBuf,
+    pub multipath: bool,
+    pub route_constraints: RouteConstraints,
 }
 
 impl BootstrapperConfig {
     pub fn new() -> BootstrapperConfig {
         BootstrapperConfig {
             log_level: LevelFilter::Off,
+            log_format: LogFormat::Text,
+            log_directory: PathBuf::new(),
+            log_rotation: LogRotation::default(),
             dns_servers: vec![],
             neighborhood_config: NeighborhoodConfig {
                 neighbor_configs: vec![],
@@ -102,6 +135,9 @@ impl BootstrapperConfig {
                 earning_wallet: accountant::DEFAULT_EARNING_WALLET.clone(),
                 consuming_wallet: None,
                 rate_pack: DEFAULT_RATE_PACK.clone(),
+                gossip_batch_window: Duration::from_millis(DEFAULT_GOSSIP_BATCH_WINDOW_MS),
+                diversity: DiversityConfig::default(),
+                behind_nat: false,
             },
             accountant_config: AccountantConfig {
                 payable_scan_interval: Duration::from_secs(DEFAULT_PAYABLE_SCAN_INTERVAL),
@@ -111,6 +147,10 @@ impl BootstrapperConfig {
             ui_gateway_config: UiGatewayConfig {
                 ui_port: DEFAULT_UI_PORT,
                 node_descriptor: String::from(""),
+                node_configuration: NodeConfiguration::default(),
+                ui_tokens: UiTokens::default(),
+                reload_args: vec![],
+                reloadable_configuration: ReloadableConfiguration::default(),
             },
             blockchain_bridge_config: BlockchainBridgeConfig {
                 blockchain_service_url: None,
@@ -119,6 +159,8 @@ impl BootstrapperConfig {
             },
             clandestine_port_opt: None,
             data_directory: PathBuf::new(),
+            multipath: false,
+            route_constraints: RouteConstraints::default(),
         }
     }
 }
@@ -159,7 +201,15 @@ impl SocketServer for Bootstrapper {
         configuration.establish();
         let mut config = BootstrapperConfig::new();
         Bootstrapper::parse_args(args, &mut config);
-        logger_initializer.init(config.log_level);
+        // Taken before the environment is read, because a reload can't read it again
+        config.ui_gateway_config.reload_args = args.clone();
+        config.ui_gateway_config.reloadable_configuration = ReloadableConfiguration::from(&config);
+        logger_initializer.init(
+            config.log_level,
+            config.log_format,
+            &config.log_directory,
+            &config.log_rotation,
+        );
         Bootstrapper::parse_environment_variables(&mut config);
         self.config = Some(config);
         self.listener_handlers =
@@ -192,6 +242,17 @@ impl SocketServer for Bootstrapper {
             config.neighborhood_config.clandestine_port_list.clone(),
             streams,
         );
+        config.ui_gateway_config.node_configuration = Bootstrapper::node_configuration(config);
+        // Written after privilege drop, so that they belong to the user the Node runs as
+        config.ui_gateway_config.ui_tokens =
+            make_ui_tokens(&config.data_directory).unwrap_or_else(|e| {
+                panic!(
+                    "Could not write UI tokens to {:?}: {}",
+                    config.data_directory, e
+                )
+            });
+        Logger::new("Bootstrapper")
+            .info(format!("UI tokens written to {:?}", config.data_directory));
         let stream_handler_pool_subs = self.actor_system_factory.make_and_start_actors(
             self.config
                 .as_ref()
@@ -221,22 +282,57 @@ impl Bootstrapper {
     }
 
     fn parse_args(args: &Vec<String>, config: &mut BootstrapperConfig) {
+        Bootstrapper::try_parse_args(args, config).unwrap_or_else(|e| e.exit())
+    }
+
+    // Like parse_args, but hands back what's wrong with the arguments instead of exiting
+    pub fn try_parse_args(
+        args: &[String],
+        config: &mut BootstrapperConfig,
+    ) -> Result<(), clap::Error> {
         let default_ui_port_value = DEFAULT_UI_PORT.to_string();
         let default_earning_wallet_value = accountant::DEFAULT_EARNING_WALLET.clone().address;
         let default_crash_point_value = format!("{}", CrashPoint::None);
         let default_node_type_value = format!("{}", NodeType::Standard);
         let default_ip_value = sentinel_ip_addr().to_string();
         let default_data_dir_value = Bootstrapper::data_directory_default(&RealDirsWrapper {});
+        let default_gossip_batch_window_value = DEFAULT_GOSSIP_BATCH_WINDOW_MS.to_string();
+        let default_payable_scan_interval_value = DEFAULT_PAYABLE_SCAN_INTERVAL.to_string();
+        let default_ipv4_diversity_prefix_len_value = DEFAULT_IPV4_DIVERSITY_PREFIX_LEN.to_string();
+        let default_ipv6_diversity_prefix_len_value = DEFAULT_IPV6_DIVERSITY_PREFIX_LEN.to_string();
+        let default_max_nodes_per_prefix_value = DEFAULT_MAX_NODES_PER_PREFIX.to_string();
+        let default_routing_byte_rate_value = DEFAULT_RATE_PACK.routing_byte_rate.to_string();
+        let default_routing_service_rate_value = DEFAULT_RATE_PACK.routing_service_rate.to_string();
+        let default_exit_byte_rate_value = DEFAULT_RATE_PACK.exit_byte_rate.to_string();
+        let default_exit_service_rate_value = DEFAULT_RATE_PACK.exit_service_rate.to_string();
+        let default_log_dir_value = temp_dir().to_string_lossy().to_string();
+        let default_log_rotate_size_value = DEFAULT_LOG_ROTATE_SIZE_MB.to_string();
+        let default_log_rotate_hours_value = DEFAULT_LOG_ROTATE_HOURS.to_string();
+        let default_log_retention_value = DEFAULT_LOG_RETENTION.to_string();
+        let (args, file_private_key) = merge_config_file(args, &default_data_dir_value)
+            .map_err(|e| clap::Error::with_description(&e, ErrorKind::InvalidValue))?;
         let matches = App::new("SubstratumNode")
             .version(crate_version!())
             .author(crate_authors!("\n"))
             .about(crate_description!())
+            .arg(
+                Arg::with_name("behind_nat")
+                    .long("behind_nat")
+                    .overrides_with("no_behind_nat")
+                    .help("This Node can't accept inbound connections; have its neighbors relay to it"),
+            )
             .arg(
                 Arg::with_name("blockchain_service_url")
                     .long("blockchain_service_url")
                     .value_name("URL")
                     .takes_value(true),
             )
+            .arg(
+                Arg::with_name("check_config")
+                    .long("check_config")
+                    .alias("check-config")
+                    .help("Check the configuration, report on it, and exit without starting the Node"),
+            )
             .arg(
                 Arg::with_name("clandestine_port")
                     .long("clandestine_port")
@@ -245,6 +341,16 @@ impl Bootstrapper {
                     .validator(Bootstrapper::validate_clandestine_port)
                     .help("Must be between 1025 and 65535 [default: last used port]"),
             )
+            .arg(
+                Arg::with_name("config")
+                    .long("config")
+                    .value_name("FILE")
+                    .takes_value(true)
+                    .help(&format!(
+                        "Config file of 'option = value' lines; defaults to {} in the data directory",
+                        CONFIG_FILE_NAME
+                    )),
+            )
             .arg(
                 Arg::with_name("data_directory")
                     .long("data_directory")
@@ -261,6 +367,60 @@ impl Bootstrapper {
                     .use_delimiter(true)
                     .validator(Bootstrapper::validate_ip_address),
             )
+            .arg(
+                Arg::with_name("exclude_ip_ranges")
+                    .long("exclude_ip_ranges")
+                    .value_name("IP_RANGES")
+                    .takes_value(true)
+                    .use_delimiter(true)
+                    .validator(|s| IpRange::from_str(&s).map(|_| ()))
+                    .help("Never route through Nodes at these addresses, like 10.0.0.0/8,1.2.3.4"),
+            )
+            .arg(
+                Arg::with_name("exclude_nodes")
+                    .long("exclude_nodes")
+                    .value_name("PUBLIC_KEYS")
+                    .takes_value(true)
+                    .use_delimiter(true)
+                    .validator(|s| public_key_from_str(&s).map(|_| ()))
+                    .help("Never route through these Nodes"),
+            )
+            .arg(
+                Arg::with_name("exit_byte_rate")
+                    .long("exit_byte_rate")
+                    .value_name("RATE")
+                    .takes_value(true)
+                    .default_value(&default_exit_byte_rate_value)
+                    .validator(Bootstrapper::validate_rate)
+                    .help("What this Node charges per byte of traffic it exits"),
+            )
+            .arg(
+                Arg::with_name("exit_nodes")
+                    .long("exit_nodes")
+                    .value_name("PUBLIC_KEYS")
+                    .takes_value(true)
+                    .use_delimiter(true)
+                    .validator(|s| public_key_from_str(&s).map(|_| ()))
+                    .help("Exit only through one of these Nodes"),
+            )
+            .arg(
+                Arg::with_name("exit_service_rate")
+                    .long("exit_service_rate")
+                    .value_name("RATE")
+                    .takes_value(true)
+                    .default_value(&default_exit_service_rate_value)
+                    .validator(Bootstrapper::validate_rate)
+                    .help("What this Node charges per request or response it exits"),
+            )
+            .arg(
+                Arg::with_name("gossip_batch_window")
+                    .long("gossip_batch_window")
+                    .value_name("MILLISECONDS")
+                    .takes_value(true)
+                    .default_value(&default_gossip_batch_window_value)
+                    .validator(|s| s.parse::<u64>().map(|_| ()).map_err(|_| s))
+                    .help("How long to collect Neighborhood changes before Gossiping them; 0 Gossips each change immediately"),
+            )
             .arg(
                 Arg::with_name("ip")
                     .long("ip")
@@ -269,6 +429,24 @@ impl Bootstrapper {
                     .default_value(&default_ip_value)
                     .validator(Bootstrapper::validate_ip_address),
             )
+            .arg(
+                Arg::with_name("ipv4_diversity_prefix_len")
+                    .long("ipv4_diversity_prefix_len")
+                    .value_name("BITS")
+                    .takes_value(true)
+                    .default_value(&default_ipv4_diversity_prefix_len_value)
+                    .validator(|s| Bootstrapper::validate_prefix_len(s, 32))
+                    .help("No route may pass through two Nodes whose IPv4 addresses share this many leading bits"),
+            )
+            .arg(
+                Arg::with_name("ipv6_diversity_prefix_len")
+                    .long("ipv6_diversity_prefix_len")
+                    .value_name("BITS")
+                    .takes_value(true)
+                    .default_value(&default_ipv6_diversity_prefix_len_value)
+                    .validator(|s| Bootstrapper::validate_prefix_len(s, 128))
+                    .help("No route may pass through two Nodes whose IPv6 addresses share this many leading bits"),
+            )
             .arg(
                 Arg::with_name("log_level")
                     .long("log_level")
@@ -278,6 +456,65 @@ impl Bootstrapper {
                     .default_value("warn")
                     .case_insensitive(true),
             )
+            .arg(
+                Arg::with_name("log_format")
+                    .long("log_format")
+                    .value_name("FORMAT")
+                    .takes_value(true)
+                    .possible_values(&["text", "json"])
+                    .default_value("text")
+                    .case_insensitive(true)
+                    .help("json writes one JSON object per log line, for log pipelines to index"),
+            )
+            .arg(
+                Arg::with_name("log_dir")
+                    .long("log_dir")
+                    .value_name("DIRECTORY")
+                    .empty_values(false)
+                    .default_value(&default_log_dir_value),
+            )
+            .arg(
+                Arg::with_name("log_retention")
+                    .long("log_retention")
+                    .value_name("COUNT")
+                    .takes_value(true)
+                    .default_value(&default_log_retention_value)
+                    .validator(Bootstrapper::validate_positive_number)
+                    .help("How many rotated log files to keep"),
+            )
+            .arg(
+                Arg::with_name("log_rotate_hours")
+                    .long("log_rotate_hours")
+                    .value_name("HOURS")
+                    .takes_value(true)
+                    .default_value(&default_log_rotate_hours_value)
+                    .validator(Bootstrapper::validate_positive_number)
+                    .help("Start a fresh log file after this many hours"),
+            )
+            .arg(
+                Arg::with_name("log_rotate_size")
+                    .long("log_rotate_size")
+                    .value_name("MEGABYTES")
+                    .takes_value(true)
+                    .default_value(&default_log_rotate_size_value)
+                    .validator(Bootstrapper::validate_positive_number)
+                    .help("Start a fresh log file once the current one is this big"),
+            )
+            .arg(
+                Arg::with_name("max_nodes_per_prefix")
+                    .long("max_nodes_per_prefix")
+                    .value_name("COUNT")
+                    .takes_value(true)
+                    .default_value(&default_max_nodes_per_prefix_value)
+                    .validator(|s| s.parse::<usize>().map(|_| ()).map_err(|_| s))
+                    .help("Most Nodes to accept from Gossip whose IP addresses share a diversity prefix"),
+            )
+            .arg(
+                Arg::with_name("multipath")
+                    .long("multipath")
+                    .overrides_with("no_multipath")
+                    .help("Send each request over two disjoint routes to the same exit Node"),
+            )
             .arg(
                 Arg::with_name("neighbor")
                     .long("neighbor")
@@ -288,6 +525,27 @@ impl Bootstrapper {
                     .requires("ip")
                     .validator(|s| NodeDescriptor::from_str(&s).map(|_| ())),
             )
+            .arg(
+                Arg::with_name("neighbors_file")
+                    .long("neighbors_file")
+                    .value_name("PATH")
+                    .takes_value(true)
+                    .requires("ip")
+                    .validator(|s| Bootstrapper::read_neighbors_file(&s).map(|_| ()))
+                    .help("File of Node descriptors to debut to, one per line; lines starting with # are ignored"),
+            )
+            .arg(
+                Arg::with_name("no_behind_nat")
+                    .long("no_behind_nat")
+                    .overrides_with("behind_nat")
+                    .help("Turns off behind_nat if the config file sets it"),
+            )
+            .arg(
+                Arg::with_name("no_multipath")
+                    .long("no_multipath")
+                    .overrides_with("multipath")
+                    .help("Turns off multipath if the config file sets it"),
+            )
             .arg(
                 Arg::with_name("node_type")
                     .long("node_type")
@@ -297,6 +555,33 @@ impl Bootstrapper {
                     .default_value(&default_node_type_value)
                     .case_insensitive(true),
             )
+            .arg(
+                Arg::with_name("payable_scan_interval")
+                    .long("payable_scan_interval")
+                    .value_name("SECONDS")
+                    .takes_value(true)
+                    .default_value(&default_payable_scan_interval_value)
+                    .validator(Bootstrapper::validate_positive_number)
+                    .help("How often to look for debts that are due to be paid"),
+            )
+            .arg(
+                Arg::with_name("routing_byte_rate")
+                    .long("routing_byte_rate")
+                    .value_name("RATE")
+                    .takes_value(true)
+                    .default_value(&default_routing_byte_rate_value)
+                    .validator(Bootstrapper::validate_rate)
+                    .help("What this Node charges per byte of traffic it routes"),
+            )
+            .arg(
+                Arg::with_name("routing_service_rate")
+                    .long("routing_service_rate")
+                    .value_name("RATE")
+                    .takes_value(true)
+                    .default_value(&default_routing_service_rate_value)
+                    .validator(Bootstrapper::validate_rate)
+                    .help("What this Node charges per CORES package it routes"),
+            )
             .arg(
                 Arg::with_name("ui_port")
                     .long("ui_port")
@@ -332,7 +617,19 @@ impl Bootstrapper {
                     .hidden(true)
                     .help("Only used for testing"),
             )
-            .get_matches_from(args.iter());
+            .get_matches_from_safe(args.iter())?;
+
+        if let Some(ref key) = file_private_key {
+            if !Bootstrapper::is_valid_private_key(key) {
+                return Err(clap::Error::with_description(
+                    "consuming_private_key in the config file must be 64 hexadecimal digits",
+                    ErrorKind::InvalidValue,
+                ));
+            }
+        }
+        config.blockchain_bridge_config.consuming_private_key = file_private_key;
+
+        config.neighborhood_config.behind_nat = matches.is_present("behind_nat");
 
         config.blockchain_bridge_config.blockchain_service_url = matches
             .value_of("blockchain_service_url")
@@ -353,16 +650,67 @@ impl Bootstrapper {
             .map(|s| SocketAddr::from((IpAddr::from_str(s).expect("Internal Error"), 53)))
             .collect();
 
+        config.route_constraints = RouteConstraints::from_strs(
+            &Bootstrapper::values_or_empty(&matches, "exit_nodes"),
+            &Bootstrapper::values_or_empty(&matches, "exclude_nodes"),
+            &Bootstrapper::values_or_empty(&matches, "exclude_ip_ranges"),
+        )
+        .expect("Internal Error");
+
+        config.neighborhood_config.gossip_batch_window = Duration::from_millis(
+            value_t!(matches, "gossip_batch_window", u64).expect("Internal Error"),
+        );
+
         config.neighborhood_config.local_ip_addr =
             value_t!(matches, "ip", IpAddr).expect("Internal Error");
 
+        config.accountant_config.payable_scan_interval = Duration::from_secs(
+            value_t!(matches, "payable_scan_interval", u64).expect("Internal Error"),
+        );
+
+        config.neighborhood_config.diversity = DiversityConfig {
+            ipv4_prefix_len: value_t!(matches, "ipv4_diversity_prefix_len", u8)
+                .expect("Internal Error"),
+            ipv6_prefix_len: value_t!(matches, "ipv6_diversity_prefix_len", u8)
+                .expect("Internal Error"),
+            max_nodes_per_prefix: value_t!(matches, "max_nodes_per_prefix", usize)
+                .expect("Internal Error"),
+        };
+
+        config.neighborhood_config.rate_pack = RatePack {
+            routing_byte_rate: value_t!(matches, "routing_byte_rate", u64).expect("Internal Error"),
+            routing_service_rate: value_t!(matches, "routing_service_rate", u64)
+                .expect("Internal Error"),
+            exit_byte_rate: value_t!(matches, "exit_byte_rate", u64).expect("Internal Error"),
+            exit_service_rate: value_t!(matches, "exit_service_rate", u64).expect("Internal Error"),
+        };
+
         config.log_level = value_t!(matches, "log_level", LevelFilter).expect("Internal Error");
+        config.log_format = value_t!(matches, "log_format", LogFormat).expect("Internal Error");
+        config.log_directory = value_t!(matches, "log_dir", PathBuf).expect("Internal Error");
+        config.log_rotation = LogRotation {
+            max_size: value_t!(matches, "log_rotate_size", u64).expect("Internal Error")
+                * 1024
+                * 1024,
+            max_age: Duration::from_secs(
+                value_t!(matches, "log_rotate_hours", u64).expect("Internal Error") * 3600,
+            ),
+            retention: value_t!(matches, "log_retention", usize).expect("Internal Error"),
+        };
+
+        config.multipath = matches.is_present("multipath");
 
         config.neighborhood_config.neighbor_configs =
             match values_t!(matches, "neighbor", NodeDescriptor) {
                 Ok(neighbors) => neighbors,
                 Err(_) => vec![],
             };
+        if let Some(path) = matches.value_of("neighbors_file") {
+            config
+                .neighborhood_config
+                .neighbor_configs
+                .extend(Bootstrapper::read_neighbors_file(path).expect("Neighbors file changed"));
+        }
 
         config.neighborhood_config.is_bootstrap_node = value_t!(matches, "node_type", NodeType)
             .expect("Internal Error")
@@ -379,25 +727,43 @@ impl Bootstrapper {
 
         config.crash_point = value_t!(matches, "crash_point", CrashPoint).expect("Internal Error");
 
-        // TODO: In real life this should come from a command-line parameter
         config.neighborhood_config.consuming_wallet =
-            Some(accountant::TEMPORARY_CONSUMING_WALLET.clone());
+            Bootstrapper::consuming_wallet(&config.blockchain_bridge_config.consuming_private_key);
+        Ok(())
+    }
+
+    fn values_or_empty<'a>(matches: &'a ArgMatches, name: &str) -> Vec<&'a str> {
+        match matches.values_of(name) {
+            Some(values) => values.collect(),
+            None => vec![],
+        }
     }
 
     fn parse_environment_variables(config: &mut BootstrapperConfig) {
-        config.blockchain_bridge_config.consuming_private_key =
-            match env::var("CONSUMING_PRIVATE_KEY") {
-                Ok(key) => Bootstrapper::parse_private_key(key),
-                Err(_) => None,
-            };
+        if let Ok(key) = env::var("CONSUMING_PRIVATE_KEY") {
+            config.blockchain_bridge_config.consuming_private_key =
+                Bootstrapper::parse_private_key(key);
+            config.neighborhood_config.consuming_wallet = Bootstrapper::consuming_wallet(
+                &config.blockchain_bridge_config.consuming_private_key,
+            );
+        }
 
         env::remove_var("CONSUMING_PRIVATE_KEY");
     }
 
-    fn is_valid_private_key(key: &str) -> bool {
+    pub fn is_valid_private_key(key: &str) -> bool {
         Regex::new("^[0-9a-fA-F]{64}$")
             .expect("Failed to compile regular expression")
             .is_match(key)
+            && Wallet::from_private_key(key).is_ok()
+    }
+
+    // Without the private key, the Node can't prove to other Nodes that it may spend from the
+    // wallet, so it has no consuming wallet at all.
+    fn consuming_wallet(private_key_opt: &Option<String>) -> Option<Wallet> {
+        private_key_opt.as_ref().map(|key| {
+            Wallet::from_private_key(key).expect("Consuming private key was not validated")
+        })
     }
 
     fn parse_private_key(key: String) -> Option<String> {
@@ -407,6 +773,20 @@ impl Bootstrapper {
         Some(key)
     }
 
+    fn read_neighbors_file(path: &str) -> Result<Vec<NodeDescriptor>, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Can't read neighbors file {}: {}", path, e))?;
+        contents
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                NodeDescriptor::from_str(line)
+                    .map_err(|e| format!("Bad descriptor in neighbors file {}: {}", path, e))
+            })
+            .collect()
+    }
+
     fn validate_ethereum_address(address: String) -> Result<(), String> {
         match Regex::new("^0x[0-9a-fA-F]{40}$")
             .expect("Failed to compile regular expression")
@@ -424,6 +804,27 @@ impl Bootstrapper {
         }
     }
 
+    fn validate_prefix_len(prefix_len: String, max: u8) -> Result<(), String> {
+        match prefix_len.parse::<u8>() {
+            Ok(bits) if bits <= max => Ok(()),
+            _ => Err(prefix_len),
+        }
+    }
+
+    fn validate_positive_number(number: String) -> Result<(), String> {
+        match number.parse::<u64>() {
+            Ok(n) if n > 0 => Ok(()),
+            _ => Err(number),
+        }
+    }
+
+    fn validate_rate(rate: String) -> Result<(), String> {
+        match rate.parse::<u64>() {
+            Ok(n) if n <= MAX_RATE => Ok(()),
+            _ => Err(format!("{} is not a rate from 0 to {}", rate, MAX_RATE)),
+        }
+    }
+
     fn validate_ui_port(port: String) -> Result<(), String> {
         match str::parse::<u16>(&port) {
             Ok(port_number) if port_number < LOWEST_USABLE_INSECURE_PORT => Err(port),
@@ -458,6 +859,43 @@ impl Bootstrapper {
         cryptde
     }
 
+    fn node_configuration(config: &BootstrapperConfig) -> NodeConfiguration {
+        let neighborhood_config = &config.neighborhood_config;
+        NodeConfiguration {
+            is_decentralized: neighborhood_config.is_decentralized(),
+            is_bootstrap_node: neighborhood_config.is_bootstrap_node,
+            ip_address: neighborhood_config.local_ip_addr.to_string(),
+            clandestine_ports: neighborhood_config.clandestine_port_list.clone(),
+            neighbors: neighborhood_config
+                .neighbor_configs
+                .iter()
+                .map(|descriptor| descriptor.to_string())
+                .collect(),
+            dns_servers: config
+                .dns_servers
+                .iter()
+                .map(|server| server.to_string())
+                .collect(),
+            ui_port: config.ui_gateway_config.ui_port,
+            data_directory: config.data_directory.to_string_lossy().to_string(),
+            log_level: config.log_level.to_string(),
+            blockchain_service_url: config
+                .blockchain_bridge_config
+                .blockchain_service_url
+                .clone(),
+            multipath: config.multipath,
+            behind_nat: neighborhood_config.behind_nat,
+            rate_pack: neighborhood_config.rate_pack.clone(),
+            wallet_addresses: WalletAddresses {
+                earning: neighborhood_config.earning_wallet.address.clone(),
+                consuming: neighborhood_config
+                    .consuming_wallet
+                    .as_ref()
+                    .map(|wallet| wallet.address.clone()),
+            },
+        }
+    }
+
     fn report_local_descriptor(
         cryptde: &dyn CryptDE,
         ip_addr: IpAddr,
@@ -547,6 +985,7 @@ mod tests {
     use crate::sub_lib::cryptde::PublicKey;
     use crate::sub_lib::node_addr::NodeAddr;
     use crate::sub_lib::stream_connector::ConnectionInfo;
+    use crate::sub_lib::ui_gateway::{CONTROL_TOKEN_FILE, READ_ONLY_TOKEN_FILE};
     use crate::test_utils::logging::init_test_logging;
     use crate::test_utils::logging::TestLog;
     use crate::test_utils::logging::TestLogHandler;
@@ -805,6 +1244,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn validate_rate_accepts_zero_through_the_maximum() {
+        assert_eq!(Bootstrapper::validate_rate(String::from("0")), Ok(()));
+        assert_eq!(Bootstrapper::validate_rate(MAX_RATE.to_string()), Ok(()));
+        assert_eq!(
+            Bootstrapper::validate_rate((MAX_RATE + 1).to_string()),
+            Err(format!(
+                "{} is not a rate from 0 to {}",
+                MAX_RATE + 1,
+                MAX_RATE
+            ))
+        );
+        assert_eq!(
+            Bootstrapper::validate_rate(String::from("-3")),
+            Err(format!("-3 is not a rate from 0 to {}", MAX_RATE))
+        );
+    }
+
+    #[test]
+    fn validate_positive_number_rejects_zero_and_garbage() {
+        assert_eq!(
+            Bootstrapper::validate_positive_number(String::from("0")),
+            Err(String::from("0"))
+        );
+        assert_eq!(
+            Bootstrapper::validate_positive_number(String::from("-3")),
+            Err(String::from("-3"))
+        );
+        assert_eq!(
+            Bootstrapper::validate_positive_number(String::from("12")),
+            Ok(())
+        );
+    }
+
     #[test]
     fn validate_ip_address_given_invalid_input() {
         assert_eq!(
@@ -904,6 +1377,22 @@ mod tests {
         assert_eq!(String::from("mocked/path"), result);
     }
 
+    #[test]
+    fn validate_prefix_len_accepts_only_lengths_that_fit_the_address_family() {
+        assert_eq!(
+            Bootstrapper::validate_prefix_len(String::from("32"), 32),
+            Ok(())
+        );
+        assert_eq!(
+            Bootstrapper::validate_prefix_len(String::from("33"), 32),
+            Err(String::from("33"))
+        );
+        assert_eq!(
+            Bootstrapper::validate_prefix_len(String::from("booga"), 128),
+            Err(String::from("booga"))
+        );
+    }
+
     #[test]
     fn validate_clandestine_port_rejects_badly_formatted_port_number() {
         let result = Bootstrapper::validate_clandestine_port(String::from("booga"));
@@ -932,6 +1421,53 @@ mod tests {
         assert_eq!(Ok(()), result);
     }
 
+    #[test]
+    fn read_neighbors_file_skips_blank_lines_and_comments() {
+        let home_dir = ensure_node_home_directory_exists(
+            "bootstrapper",
+            "read_neighbors_file_skips_blank_lines_and_comments",
+        );
+        let path = home_dir.join("neighbors.txt");
+        fs::write(
+            &path,
+            "# bootstrap Nodes\nQmlsbA:1.2.3.4:1234,2345\n\n  VGVk:2.3.4.5:3456  \n",
+        )
+        .unwrap();
+
+        let result = Bootstrapper::read_neighbors_file(path.to_str().unwrap());
+
+        assert_eq!(
+            result,
+            Ok(vec![
+                NodeDescriptor::from_str("QmlsbA:1.2.3.4:1234,2345").unwrap(),
+                NodeDescriptor::from_str("VGVk:2.3.4.5:3456").unwrap(),
+            ])
+        );
+    }
+
+    #[test]
+    fn read_neighbors_file_complains_about_bad_descriptors_and_missing_files() {
+        let home_dir = ensure_node_home_directory_exists(
+            "bootstrapper",
+            "read_neighbors_file_complains_about_bad_descriptors_and_missing_files",
+        );
+        let path = home_dir.join("neighbors.txt");
+        fs::write(&path, "QmlsbA:1.2.3.4:1234\nbooga\n").unwrap();
+
+        let bad_result = Bootstrapper::read_neighbors_file(path.to_str().unwrap());
+        let missing_result =
+            Bootstrapper::read_neighbors_file(home_dir.join("nonexistent").to_str().unwrap());
+
+        assert!(bad_result
+            .err()
+            .unwrap()
+            .starts_with("Bad descriptor in neighbors file"));
+        assert!(missing_result
+            .err()
+            .unwrap()
+            .starts_with("Can't read neighbors file"));
+    }
+
     #[test]
     fn parse_args_creates_configurations() {
         let args: Vec<String> = vec![
@@ -958,6 +1494,42 @@ mod tests {
             "http://127.0.0.1:8545",
             "--log_level",
             "trace",
+            "--log_format",
+            "JSON",
+            "--log_dir",
+            "/var/log/substratum",
+            "--log_rotate_size",
+            "5",
+            "--log_rotate_hours",
+            "2",
+            "--log_retention",
+            "3",
+            "--multipath",
+            "--behind_nat",
+            "--gossip_batch_window",
+            "250",
+            "--exit_nodes",
+            "QmlsbA,VGVk",
+            "--exclude_nodes",
+            "Qm9vZ2E",
+            "--exclude_ip_ranges",
+            "10.0.0.0/8,1.2.3.4",
+            "--ipv4_diversity_prefix_len",
+            "16",
+            "--ipv6_diversity_prefix_len",
+            "32",
+            "--max_nodes_per_prefix",
+            "2",
+            "--routing_byte_rate",
+            "1",
+            "--routing_service_rate",
+            "2",
+            "--exit_byte_rate",
+            "3",
+            "--exit_service_rate",
+            "0",
+            "--payable_scan_interval",
+            "600",
         ]
         .into_iter()
         .map(String::from)
@@ -1014,7 +1586,55 @@ mod tests {
             config.blockchain_bridge_config.blockchain_service_url
         );
         assert_eq!(PathBuf::from("~/.booga"), config.data_directory,);
-        assert_eq!(Some(1234u16), config.clandestine_port_opt)
+        assert_eq!(Some(1234u16), config.clandestine_port_opt);
+        assert_eq!(true, config.multipath);
+        assert_eq!(true, config.neighborhood_config.behind_nat);
+        assert_eq!(
+            RatePack {
+                routing_byte_rate: 1,
+                routing_service_rate: 2,
+                exit_byte_rate: 3,
+                exit_service_rate: 0,
+            },
+            config.neighborhood_config.rate_pack
+        );
+        assert_eq!(LogFormat::Json, config.log_format);
+        assert_eq!(PathBuf::from("/var/log/substratum"), config.log_directory);
+        assert_eq!(
+            LogRotation {
+                max_size: 5 * 1024 * 1024,
+                max_age: Duration::from_secs(7200),
+                retention: 3,
+            },
+            config.log_rotation
+        );
+        assert_eq!(
+            Duration::from_millis(250),
+            config.neighborhood_config.gossip_batch_window
+        );
+        assert_eq!(
+            Duration::from_secs(600),
+            config.accountant_config.payable_scan_interval
+        );
+        assert_eq!(
+            RouteConstraints {
+                exit_keys: vec![PublicKey::new(b"Bill"), PublicKey::new(b"Ted")],
+                excluded_keys: vec![PublicKey::new(b"Booga")],
+                excluded_ip_ranges: vec![
+                    IpRange::from_str("10.0.0.0/8").unwrap(),
+                    IpRange::from_str("1.2.3.4").unwrap()
+                ],
+            },
+            config.route_constraints
+        );
+        assert_eq!(
+            DiversityConfig {
+                ipv4_prefix_len: 16,
+                ipv6_prefix_len: 32,
+                max_nodes_per_prefix: 2,
+            },
+            config.neighborhood_config.diversity
+        );
     }
 
     #[test]
@@ -1046,6 +1666,22 @@ mod tests {
         );
         assert_eq!(sentinel_ip_addr(), config.neighborhood_config.local_ip_addr,);
         assert_eq!(5333, config.ui_gateway_config.ui_port);
+        assert_eq!(false, config.multipath);
+        assert_eq!(false, config.neighborhood_config.behind_nat);
+        assert_eq!(DEFAULT_RATE_PACK, config.neighborhood_config.rate_pack);
+        assert_eq!(
+            Duration::from_millis(DEFAULT_GOSSIP_BATCH_WINDOW_MS),
+            config.neighborhood_config.gossip_batch_window
+        );
+        assert_eq!(
+            Duration::from_secs(DEFAULT_PAYABLE_SCAN_INTERVAL),
+            config.accountant_config.payable_scan_interval
+        );
+        assert_eq!(RouteConstraints::default(), config.route_constraints);
+        assert_eq!(
+            DiversityConfig::default(),
+            config.neighborhood_config.diversity
+        );
     }
 
     #[test]
@@ -1071,6 +1707,126 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_args_takes_options_the_command_line_leaves_out_from_the_config_file() {
+        let home_dir = ensure_node_home_directory_exists(
+            "bootstrapper",
+            "parse_args_takes_options_the_command_line_leaves_out_from_the_config_file",
+        );
+        fs::write(
+            home_dir.join("config.toml"),
+            r#"
+dns_servers = ["12.34.56.78", "23.45.67.89"]
+neighbor = [
+    "QmlsbA:1.2.3.4:1234,2345",
+    "VGVk:2.3.4.5:3456",
+]
+ui_port = 5335
+log_level = "info"
+multipath = true
+"#,
+        )
+        .unwrap();
+        let args: Vec<String> = vec![
+            "SubstratumNode",
+            "--data_directory",
+            home_dir.to_str().unwrap(),
+            "--log_level",
+            "debug",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        let mut config = BootstrapperConfig::new();
+
+        Bootstrapper::parse_args(&args, &mut config);
+
+        assert_eq!(
+            config.dns_servers,
+            vec![
+                SocketAddr::from_str("12.34.56.78:53").unwrap(),
+                SocketAddr::from_str("23.45.67.89:53").unwrap(),
+            ]
+        );
+        assert_eq!(config.neighborhood_config.neighbor_configs.len(), 2);
+        assert_eq!(config.ui_gateway_config.ui_port, 5335);
+        assert_eq!(config.log_level, LevelFilter::Debug);
+        assert_eq!(config.multipath, true);
+    }
+
+    #[test]
+    fn parse_args_lets_the_command_line_turn_off_flags_the_config_file_turns_on() {
+        let home_dir = ensure_node_home_directory_exists(
+            "bootstrapper",
+            "parse_args_lets_the_command_line_turn_off_flags_the_config_file_turns_on",
+        );
+        fs::write(
+            home_dir.join("config.toml"),
+            "dns_servers = \"12.34.56.78\"\nmultipath = true\nbehind_nat = true\n",
+        )
+        .unwrap();
+        let args: Vec<String> = vec![
+            "SubstratumNode",
+            "--data_directory",
+            home_dir.to_str().unwrap(),
+            "--no_multipath",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        let mut config = BootstrapperConfig::new();
+
+        Bootstrapper::parse_args(&args, &mut config);
+
+        assert_eq!(config.multipath, false);
+        assert_eq!(config.neighborhood_config.behind_nat, true);
+    }
+
+    #[test]
+    fn consuming_private_key_in_the_environment_beats_the_one_in_the_config_file() {
+        let home_dir = ensure_node_home_directory_exists(
+            "bootstrapper",
+            "consuming_private_key_in_the_environment_beats_the_one_in_the_config_file",
+        );
+        fs::write(
+            home_dir.join("node.toml"),
+            "dns_servers = \"12.34.56.78\"\nconsuming_private_key = \"cc46befe8d169b89db447bd725fc2368b12542113555302598430cb5d5c74ea9\"\n",
+        )
+        .unwrap();
+        let args: Vec<String> = vec![
+            "SubstratumNode",
+            "--config",
+            home_dir.join("node.toml").to_str().unwrap(),
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        let environment = ENVIRONMENT.lock().unwrap();
+        let mut from_file = BootstrapperConfig::new();
+        let mut from_environment = BootstrapperConfig::new();
+
+        environment.remove_var("CONSUMING_PRIVATE_KEY");
+        Bootstrapper::parse_args(&args, &mut from_file);
+        Bootstrapper::parse_environment_variables(&mut from_file);
+        Bootstrapper::parse_args(&args, &mut from_environment);
+        environment.set_var(
+            "CONSUMING_PRIVATE_KEY",
+            "9bc385849a4f9019a0acf7699da91422fdd0a3eb55ff4407e450f2c65e69a9f9",
+        );
+        Bootstrapper::parse_environment_variables(&mut from_environment);
+
+        assert_eq!(
+            from_file.blockchain_bridge_config.consuming_private_key,
+            Some("cc46befe8d169b89db447bd725fc2368b12542113555302598430cb5d5c74ea9".to_string())
+        );
+        assert_eq!(
+            from_environment
+                .blockchain_bridge_config
+                .consuming_private_key,
+            Some("9bc385849a4f9019a0acf7699da91422fdd0a3eb55ff4407e450f2c65e69a9f9".to_string())
+        );
+    }
+
     #[test]
     fn initialize_as_privileged_with_no_args_binds_http_and_tls_ports() {
         let _lock = INITIALIZATION.lock();
@@ -1168,6 +1924,46 @@ mod tests {
         assert_eq!(config.clandestine_discriminator_factories.is_empty(), true);
     }
 
+    #[test]
+    fn node_configuration_summarizes_the_configuration_without_secrets() {
+        let mut config = BootstrapperConfig::new();
+        config.log_level = LevelFilter::Debug;
+        config.dns_servers = vec![SocketAddr::from_str("1.1.1.1:53").unwrap()];
+        config.neighborhood_config.local_ip_addr = IpAddr::from_str("1.2.3.4").unwrap();
+        config.neighborhood_config.clandestine_port_list = vec![1234];
+        config.neighborhood_config.neighbor_configs =
+            vec![NodeDescriptor::from_str("QmlsbA:2.3.4.5:3456").unwrap()];
+        config.neighborhood_config.consuming_wallet = Some(Wallet::new("0xCONSUMING"));
+        config.blockchain_bridge_config.consuming_private_key = Some("SECRET".to_string());
+        config.data_directory = PathBuf::from("/home/booga");
+        config.multipath = true;
+
+        let result = Bootstrapper::node_configuration(&config);
+
+        assert_eq!(
+            result,
+            NodeConfiguration {
+                is_decentralized: true,
+                is_bootstrap_node: false,
+                ip_address: "1.2.3.4".to_string(),
+                clandestine_ports: vec![1234],
+                neighbors: vec!["QmlsbA:2.3.4.5:3456".to_string()],
+                dns_servers: vec!["1.1.1.1:53".to_string()],
+                ui_port: DEFAULT_UI_PORT,
+                data_directory: "/home/booga".to_string(),
+                log_level: "DEBUG".to_string(),
+                blockchain_service_url: None,
+                multipath: true,
+                behind_nat: false,
+                rate_pack: DEFAULT_RATE_PACK.clone(),
+                wallet_addresses: WalletAddresses {
+                    earning: accountant::DEFAULT_EARNING_WALLET.address.clone(),
+                    consuming: Some("0xCONSUMING".to_string()),
+                },
+            }
+        );
+    }
+
     #[test]
     fn initialize_as_unprivileged_passes_node_descriptor_to_ui_config() {
         let _lock = INITIALIZATION.lock();
@@ -1191,6 +1987,36 @@ mod tests {
         assert!(config.ui_gateway_config.node_descriptor.len() > 0);
     }
 
+    #[test]
+    fn initialize_as_unprivileged_writes_ui_tokens_and_passes_them_to_ui_config() {
+        let _lock = INITIALIZATION.lock();
+        let home_dir = ensure_node_home_directory_exists(
+            "bootstrapper",
+            "initialize_as_unprivileged_writes_ui_tokens_and_passes_them_to_ui_config",
+        );
+        let mut subject = BootstrapperBuilder::new()
+            .add_listener_handler(Box::new(
+                ListenerHandlerNull::new(vec![]).bind_port_result(Ok(())),
+            ))
+            .build();
+        let mut config = BootstrapperConfig::new();
+        config.clandestine_port_opt = Some(1234);
+        config.data_directory = home_dir.clone();
+        subject.config = Some(config);
+
+        subject.initialize_as_unprivileged(&mut FakeStreamHolder::new().streams());
+
+        let ui_tokens = subject.config.unwrap().ui_gateway_config.ui_tokens;
+        assert_eq!(
+            fs::read_to_string(home_dir.join(CONTROL_TOKEN_FILE)).unwrap(),
+            ui_tokens.control
+        );
+        assert_eq!(
+            fs::read_to_string(home_dir.join(READ_ONLY_TOKEN_FILE)).unwrap(),
+            ui_tokens.read_only
+        );
+    }
+
     #[test]
     fn initialize_as_privileged_with_clandestine_port_produces_expected_clandestine_discriminator_factories_vector(
     ) {
@@ -1226,6 +2052,10 @@ mod tests {
     fn initialize_as_privileged_stores_dns_servers_and_passes_them_to_actor_system_factory_for_proxy_client_in_initialize_as_unprivileged(
     ) {
         let _lock = INITIALIZATION.lock();
+        let home_dir = ensure_node_home_directory_exists(
+            "bootstrapper",
+            "initialize_as_privileged_stores_dns_servers_and_passes_them_to_actor_system_factory_for_proxy_client_in_initialize_as_unprivileged",
+        );
         let mut holder = FakeStreamHolder::new();
         let actor_system_factory = ActorSystemFactoryMock::new();
         let dns_servers_arc = actor_system_factory.dnss.clone();
@@ -1251,6 +2081,8 @@ mod tests {
                 String::from("1.2.3.4,2.3.4.5"),
                 String::from("--clandestine_port"),
                 String::from("1234"),
+                String::from("--data_directory"),
+                home_dir.to_string_lossy().to_string(),
             ],
             &mut log_initializer,
         );
@@ -1374,6 +2206,10 @@ mod tests {
     fn initialize_as_unprivileged_moves_streams_from_listener_handlers_to_stream_handler_pool() {
         let _lock = INITIALIZATION.lock();
         init_test_logging();
+        let home_dir = ensure_node_home_directory_exists(
+            "bootstrapper",
+            "initialize_as_unprivileged_moves_streams_from_listener_handlers_to_stream_handler_pool",
+        );
         let mut holder = FakeStreamHolder::new();
         let one_listener_handler = ListenerHandlerNull::new(vec![]).bind_port_result(Ok(()));
         let another_listener_handler = ListenerHandlerNull::new(vec![]).bind_port_result(Ok(()));
@@ -1385,6 +2221,8 @@ mod tests {
             String::from("222.222.222.222"),
             String::from("--clandestine_port"),
             String::from("1234"),
+            String::from("--data_directory"),
+            home_dir.to_string_lossy().to_string(),
         ];
         let actor_system_factory = ActorSystemFactoryMock::new();
         let mut subject = BootstrapperBuilder::new()
@@ -1410,6 +2248,13 @@ mod tests {
     #[test]
     fn bootstrapper_as_future_polls_listener_handler_futures() {
         let _lock = INITIALIZATION.lock();
+        let home_dir = ensure_node_home_directory_exists(
+            "bootstrapper",
+            "bootstrapper_as_future_polls_listener_handler_futures",
+        );
+        let mut cli_params = make_default_cli_params();
+        cli_params.push(String::from("--data_directory"));
+        cli_params.push(home_dir.to_string_lossy().to_string());
         let mut holder = FakeStreamHolder::new();
         let connection_info1 = ConnectionInfo {
             reader: Box::new(ReadHalfWrapperMock::new()),
@@ -1467,7 +2312,7 @@ mod tests {
 
         let mut log_initializer: Box<LoggerInitializerWrapper> =
             Box::new(LoggerInitializerWrapperMock::new());
-        subject.initialize_as_privileged(&make_default_cli_params(), &mut log_initializer);
+        subject.initialize_as_privileged(&cli_params, &mut log_initializer);
         subject.initialize_as_unprivileged(&mut holder.streams());
 
         thread::spawn(|| {
@@ -0,0 +1,581 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::bootstrapper::Bootstrapper;
+use crate::bootstrapper::BootstrapperConfig;
+use crate::database::db_initializer::ConnectionWrapperReal;
+use crate::database::db_initializer::DbInitializerReal;
+use crate::database::db_initializer::CURRENT_SCHEMA_VERSION;
+use crate::database::db_initializer::DATABASE_FILE;
+use crate::neighborhood::neighborhood_dao::NeighborhoodDao;
+use crate::neighborhood::neighborhood_dao::NeighborhoodDaoReal;
+use crate::persistent_configuration::HTTP_PORT;
+use crate::persistent_configuration::LOWEST_USABLE_INSECURE_PORT;
+use crate::persistent_configuration::TLS_PORT;
+use crate::sub_lib::main_tools::Command;
+use crate::sub_lib::main_tools::StdStreams;
+use crate::sub_lib::neighborhood::sentinel_ip_addr;
+use rusqlite::Connection;
+use rusqlite::OpenFlags;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::env;
+use std::io;
+use std::path::Path;
+
+pub const CHECK_CONFIG_OPTIONS: [&str; 2] = ["--check_config", "--check-config"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Finding {
+    Fine(String),
+    Problem(String),
+}
+
+// Checks everything about the configuration that can be checked without binding ports or starting
+// actors, so that mistakes show up in a report rather than as a panic partway through startup.
+pub struct ConfigChecker {}
+
+impl Command for ConfigChecker {
+    fn go(&mut self, streams: &mut StdStreams<'_>, args: &Vec<String>) -> u8 {
+        let findings = ConfigChecker::check(args, env::var("CONSUMING_PRIVATE_KEY").ok());
+        ConfigChecker::report(&findings, streams.stdout)
+    }
+}
+
+impl ConfigChecker {
+    pub fn new() -> ConfigChecker {
+        ConfigChecker {}
+    }
+
+    pub fn is_requested(args: &[String]) -> bool {
+        args.iter()
+            .any(|arg| CHECK_CONFIG_OPTIONS.contains(&arg.as_str()))
+    }
+
+    pub fn check(args: &[String], env_private_key: Option<String>) -> Vec<Finding> {
+        let mut config = BootstrapperConfig::new();
+        if let Err(e) = Bootstrapper::try_parse_args(args, &mut config) {
+            return vec![Finding::Problem(ConfigChecker::first_line(&e.message))];
+        }
+        let stored = DbInitializerReal::stored_configuration(&config.data_directory);
+        let mut findings = vec![Finding::Fine(
+            "Command line, config file and environment parse".to_string(),
+        )];
+        findings.push(ConfigChecker::check_private_key(env_private_key, &config));
+        findings.push(Finding::Fine(format!(
+            "Earning wallet is {}",
+            config.neighborhood_config.earning_wallet.address
+        )));
+        findings.extend(ConfigChecker::check_neighbors(&config));
+        findings.extend(ConfigChecker::check_ports(&config, &stored));
+        findings.push(ConfigChecker::check_directory(
+            "Data directory",
+            &config.data_directory,
+        ));
+        findings.push(ConfigChecker::check_directory(
+            "Log directory",
+            &config.log_directory,
+        ));
+        let database = ConfigChecker::check_database(&config.data_directory, &stored);
+        let database_is_current = database
+            == Finding::Fine(format!(
+                "Database schema version {} is current",
+                CURRENT_SCHEMA_VERSION
+            ));
+        findings.push(database);
+        if database_is_current {
+            findings.push(ConfigChecker::check_saved_node_records(
+                &config.data_directory,
+            ));
+        }
+        findings
+    }
+
+    pub fn report(findings: &[Finding], stdout: &mut dyn io::Write) -> u8 {
+        findings.iter().for_each(|finding| {
+            match finding {
+                Finding::Fine(msg) => writeln!(stdout, "  ok       {}", msg),
+                Finding::Problem(msg) => writeln!(stdout, "  PROBLEM  {}", msg),
+            }
+            .expect("Couldn't write report")
+        });
+        let problem_count = findings
+            .iter()
+            .filter(|finding| match finding {
+                Finding::Problem(_) => true,
+                Finding::Fine(_) => false,
+            })
+            .count();
+        match problem_count {
+            0 => {
+                writeln!(stdout, "Configuration is ready to use").expect("Couldn't write report");
+                0
+            }
+            n => {
+                writeln!(
+                    stdout,
+                    "Found {} problem{}; SubstratumNode would not start properly",
+                    n,
+                    if n == 1 { "" } else { "s" }
+                )
+                .expect("Couldn't write report");
+                1
+            }
+        }
+    }
+
+    fn first_line(message: &str) -> String {
+        let line = message.lines().next().unwrap_or("");
+        line.trim_start_matches("error: ").to_string()
+    }
+
+    fn check_private_key(env_private_key: Option<String>, config: &BootstrapperConfig) -> Finding {
+        match env_private_key {
+            Some(ref key) if !Bootstrapper::is_valid_private_key(key) => {
+                Finding::Problem("CONSUMING_PRIVATE_KEY must be 64 hexadecimal digits".to_string())
+            }
+            Some(_) => {
+                Finding::Fine("Consuming private key comes from CONSUMING_PRIVATE_KEY".to_string())
+            }
+            None if config
+                .blockchain_bridge_config
+                .consuming_private_key
+                .is_some() =>
+            {
+                Finding::Fine("Consuming private key comes from the config file".to_string())
+            }
+            None => Finding::Fine("No consuming private key is set".to_string()),
+        }
+    }
+
+    fn check_neighbors(config: &BootstrapperConfig) -> Vec<Finding> {
+        let neighborhood_config = &config.neighborhood_config;
+        let neighbors = &neighborhood_config.neighbor_configs;
+        let has_ip = neighborhood_config.local_ip_addr != sentinel_ip_addr();
+        let mut findings = vec![];
+        if neighborhood_config.is_bootstrap_node && !has_ip {
+            findings.push(Finding::Problem(
+                "A bootstrap Node needs --ip so that other Nodes can reach it".to_string(),
+            ))
+        }
+        if !neighbors.is_empty() && !has_ip {
+            findings.push(Finding::Problem(
+                "A Node with neighbors needs --ip so that they can reach it".to_string(),
+            ))
+        }
+        let mut public_keys = HashSet::new();
+        neighbors.iter().for_each(|neighbor| {
+            if neighbor.node_addr.ports().is_empty() {
+                findings.push(Finding::Problem(format!(
+                    "Neighbor {} has no clandestine ports",
+                    neighbor
+                )))
+            }
+            if !public_keys.insert(neighbor.public_key.clone()) {
+                findings.push(Finding::Problem(format!(
+                    "Neighbor {} appears more than once",
+                    neighbor.public_key
+                )))
+            }
+        });
+        if findings.is_empty() {
+            findings.push(Finding::Fine(match neighbors.len() {
+                0 if has_ip => "No neighbors".to_string(),
+                0 => "No neighbors and no --ip; this Node will run zero-hop".to_string(),
+                n => format!("{} neighbor{}", n, if n == 1 { "" } else { "s" }),
+            }))
+        }
+        findings
+    }
+
+    fn check_ports(
+        config: &BootstrapperConfig,
+        stored: &Result<Option<HashMap<String, String>>, String>,
+    ) -> Vec<Finding> {
+        let ui_port = config.ui_gateway_config.ui_port;
+        let stored_port = match stored {
+            Ok(Some(values)) => values.get("clandestine_port").cloned(),
+            _ => None,
+        };
+        let clandestine_port = match (config.clandestine_port_opt, stored_port) {
+            (Some(port), _) => port,
+            (None, None) => {
+                return vec![Finding::Fine(format!(
+                    "UI port is {}; a clandestine port will be chosen at startup",
+                    ui_port
+                ))]
+            }
+            (None, Some(port_str)) => match port_str.parse::<u16>() {
+                Ok(port) if port >= LOWEST_USABLE_INSECURE_PORT => port,
+                _ => {
+                    return vec![Finding::Problem(format!(
+                        "The database's clandestine port {} is not between {} and 65535",
+                        port_str, LOWEST_USABLE_INSECURE_PORT
+                    ))]
+                }
+            },
+        };
+        let mut findings = vec![];
+        if clandestine_port == ui_port {
+            findings.push(Finding::Problem(format!(
+                "The UI port and the clandestine port are both {}",
+                ui_port
+            )))
+        }
+        [HTTP_PORT, TLS_PORT]
+            .iter()
+            .filter(|port| **port == clandestine_port || **port == ui_port)
+            .for_each(|port| {
+                findings.push(Finding::Problem(format!(
+                    "Port {} is reserved for proxied HTTP and TLS traffic",
+                    port
+                )))
+            });
+        if findings.is_empty() {
+            findings.push(Finding::Fine(format!(
+                "UI port is {} and clandestine port is {}",
+                ui_port, clandestine_port
+            )))
+        }
+        findings
+    }
+
+    fn check_directory(description: &str, directory: &Path) -> Finding {
+        let directory = if directory.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            directory
+        };
+        if directory.is_dir() {
+            if is_writable(directory) {
+                Finding::Fine(format!("{} {:?} is writable", description, directory))
+            } else {
+                Finding::Problem(format!("{} {:?} is not writable", description, directory))
+            }
+        } else if directory.exists() {
+            Finding::Problem(format!(
+                "{} {:?} is not a directory",
+                description, directory
+            ))
+        } else {
+            match directory.ancestors().find(|ancestor| ancestor.is_dir()) {
+                Some(ancestor) if is_writable(ancestor) => Finding::Fine(format!(
+                    "{} {:?} doesn't exist yet but can be created",
+                    description, directory
+                )),
+                _ => Finding::Problem(format!(
+                    "{} {:?} doesn't exist and can't be created",
+                    description, directory
+                )),
+            }
+        }
+    }
+
+    fn check_database(
+        data_directory: &Path,
+        stored: &Result<Option<HashMap<String, String>>, String>,
+    ) -> Finding {
+        match stored {
+            Err(e) => Finding::Problem(e.clone()),
+            Ok(None) => Finding::Fine(format!(
+                "No database yet; {:?} will be created",
+                data_directory.join(DATABASE_FILE)
+            )),
+            Ok(Some(values)) => match values.get("schema_version") {
+                Some(version) if version == CURRENT_SCHEMA_VERSION => {
+                    Finding::Fine(format!("Database schema version {} is current", version))
+                }
+                Some(version) => Finding::Problem(format!(
+                    "Database schema version {} doesn't match this Node's {}; remove {:?} or choose another --data_directory",
+                    version,
+                    CURRENT_SCHEMA_VERSION,
+                    data_directory.join(DATABASE_FILE)
+                )),
+                None => Finding::Problem(format!(
+                    "Database {:?} has no schema version",
+                    data_directory.join(DATABASE_FILE)
+                )),
+            },
+        }
+    }
+
+    fn check_saved_node_records(data_directory: &Path) -> Finding {
+        let database_file_path = data_directory.join(DATABASE_FILE);
+        let conn = match Connection::open_with_flags(
+            &database_file_path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY,
+        ) {
+            Ok(conn) => conn,
+            Err(e) => {
+                return Finding::Problem(format!("Can't read {:?}: {}", database_file_path, e))
+            }
+        };
+        let dao = NeighborhoodDaoReal::new(Box::new(ConnectionWrapperReal::new(conn)));
+        match dao.node_records() {
+            Err(e) => Finding::Problem(format!(
+                "Can't read saved Node records in {:?}: {}",
+                database_file_path, e
+            )),
+            Ok(records) => {
+                let unreadable: Vec<String> = records
+                    .into_iter()
+                    .filter_map(|record| record.err())
+                    .collect();
+                if unreadable.is_empty() {
+                    Finding::Fine("Saved Node records are readable".to_string())
+                } else {
+                    Finding::Problem(format!(
+                        "{} saved Node record(s) in {:?} can't be read and will be discarded: {}",
+                        unreadable.len(),
+                        database_file_path,
+                        unreadable.join("; ")
+                    ))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_writable(directory: &Path) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    match CString::new(directory.as_os_str().as_bytes()) {
+        Ok(path) => unsafe { libc::access(path.as_ptr(), libc::W_OK | libc::X_OK) == 0 },
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_writable(directory: &Path) -> bool {
+    std::fs::metadata(directory)
+        .map(|metadata| !metadata.permissions().readonly())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::db_initializer::DbInitializer;
+    use crate::neighborhood::neighborhood_dao::StoredNodeRecord;
+    use crate::neighborhood::neighborhood_test_utils::make_node_record;
+    use crate::test_utils::test_utils::ensure_node_home_directory_exists;
+    use crate::test_utils::test_utils::FakeStreamHolder;
+    use rusqlite::NO_PARAMS;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn args_for(home_dir: &PathBuf, log_dir: &PathBuf, extra: Vec<&str>) -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            "SubstratumNode",
+            "--check_config",
+            "--dns_servers",
+            "1.1.1.1",
+            "--data_directory",
+            home_dir.to_str().unwrap(),
+            "--log_dir",
+            log_dir.to_str().unwrap(),
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        args.extend(extra.into_iter().map(String::from));
+        args
+    }
+
+    #[test]
+    fn is_requested_recognizes_both_spellings() {
+        let args = |arg: &str| vec!["SubstratumNode".to_string(), arg.to_string()];
+
+        assert!(ConfigChecker::is_requested(&args("--check_config")));
+        assert!(ConfigChecker::is_requested(&args("--check-config")));
+        assert!(!ConfigChecker::is_requested(&args("--config")));
+    }
+
+    #[test]
+    fn check_approves_a_usable_configuration() {
+        let home_dir = ensure_node_home_directory_exists(
+            "config_check",
+            "check_approves_a_usable_configuration",
+        );
+        let args = args_for(
+            &home_dir,
+            &home_dir,
+            vec![
+                "--ip",
+                "1.2.3.4",
+                "--clandestine_port",
+                "1234",
+                "--ui_port",
+                "5335",
+            ],
+        );
+
+        let result = ConfigChecker::check(&args, None);
+
+        assert_eq!(
+            result,
+            vec![
+                Finding::Fine("Command line, config file and environment parse".to_string()),
+                Finding::Fine("No consuming private key is set".to_string()),
+                Finding::Fine(format!(
+                    "Earning wallet is {}",
+                    BootstrapperConfig::new()
+                        .neighborhood_config
+                        .earning_wallet
+                        .address
+                )),
+                Finding::Fine("No neighbors".to_string()),
+                Finding::Fine("UI port is 5335 and clandestine port is 1234".to_string()),
+                Finding::Fine(format!("Data directory {:?} is writable", home_dir)),
+                Finding::Fine(format!("Log directory {:?} is writable", home_dir)),
+                Finding::Fine(format!(
+                    "No database yet; {:?} will be created",
+                    home_dir.join(DATABASE_FILE)
+                )),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_reports_a_parse_failure_alone() {
+        let home_dir = ensure_node_home_directory_exists(
+            "config_check",
+            "check_reports_a_parse_failure_alone",
+        );
+        let args = args_for(&home_dir, &home_dir, vec!["--ui_port", "80"]);
+
+        let result = ConfigChecker::check(&args, None);
+
+        assert_eq!(result.len(), 1);
+        match &result[0] {
+            Finding::Problem(msg) => assert!(msg.contains("--ui_port"), "{}", msg),
+            finding => panic!("Expected a problem, got {:?}", finding),
+        }
+    }
+
+    #[test]
+    fn check_finds_problems_that_would_stop_the_node() {
+        let home_dir = ensure_node_home_directory_exists(
+            "config_check",
+            "check_finds_problems_that_would_stop_the_node",
+        );
+        DbInitializerReal::new().initialize(&home_dir).unwrap();
+        {
+            let conn = Connection::open(home_dir.join(DATABASE_FILE)).unwrap();
+            conn.execute(
+                "update config set value = '0.0.1' where name = 'schema_version'",
+                NO_PARAMS,
+            )
+            .unwrap();
+            conn.execute(
+                "update config set value = '5335' where name = 'clandestine_port'",
+                NO_PARAMS,
+            )
+            .unwrap();
+        }
+        let log_file = home_dir.join("not_a_directory");
+        fs::write(&log_file, "").unwrap();
+        let args = args_for(
+            &home_dir,
+            &log_file,
+            vec!["--neighbor", "QmlsbA:1.2.3.4:1234", "--ui_port", "5335"],
+        );
+
+        let result = ConfigChecker::check(&args, Some("booga".to_string()));
+
+        let problems: Vec<String> = result
+            .into_iter()
+            .filter_map(|finding| match finding {
+                Finding::Problem(msg) => Some(msg),
+                Finding::Fine(_) => None,
+            })
+            .collect();
+        assert_eq!(
+            problems,
+            vec![
+                "CONSUMING_PRIVATE_KEY must be 64 hexadecimal digits".to_string(),
+                "A Node with neighbors needs --ip so that they can reach it".to_string(),
+                "The UI port and the clandestine port are both 5335".to_string(),
+                format!("Log directory {:?} is not a directory", log_file),
+                format!(
+                    "Database schema version 0.0.1 doesn't match this Node's {}; remove {:?} or choose another --data_directory",
+                    CURRENT_SCHEMA_VERSION,
+                    home_dir.join(DATABASE_FILE)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_reports_saved_node_records_that_cannot_be_read() {
+        let home_dir = ensure_node_home_directory_exists(
+            "config_check",
+            "check_reports_saved_node_records_that_cannot_be_read",
+        );
+        let dao = NeighborhoodDaoReal::new(DbInitializerReal::new().initialize(&home_dir).unwrap());
+        dao.replace_node_records(&[
+            StoredNodeRecord::new(&make_node_record(1234, true, false), true),
+            StoredNodeRecord::new(&make_node_record(2345, true, false), false),
+        ])
+        .unwrap();
+        {
+            let conn = Connection::open(home_dir.join(DATABASE_FILE)).unwrap();
+            conn.execute(
+                "update neighborhood_node set node_addr = 'booga' where root = 0",
+                NO_PARAMS,
+            )
+            .unwrap();
+        }
+        let args = args_for(&home_dir, &home_dir, vec![]);
+
+        let result = ConfigChecker::check(&args, None);
+
+        assert_eq!(
+            result.last(),
+            Some(&Finding::Problem(format!(
+                "1 saved Node record(s) in {:?} can't be read and will be discarded: Invalid node_addr 'booga'",
+                home_dir.join(DATABASE_FILE)
+            )))
+        );
+    }
+
+    #[test]
+    fn check_approves_readable_saved_node_records() {
+        let home_dir = ensure_node_home_directory_exists(
+            "config_check",
+            "check_approves_readable_saved_node_records",
+        );
+        DbInitializerReal::new().initialize(&home_dir).unwrap();
+        let args = args_for(&home_dir, &home_dir, vec![]);
+
+        let result = ConfigChecker::check(&args, None);
+
+        assert_eq!(
+            result.last(),
+            Some(&Finding::Fine(
+                "Saved Node records are readable".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn report_lists_findings_and_returns_status() {
+        let mut holder = FakeStreamHolder::new();
+
+        let good =
+            ConfigChecker::report(&[Finding::Fine("All good".to_string())], &mut holder.stdout);
+        let bad = ConfigChecker::report(
+            &[
+                Finding::Fine("This is fine".to_string()),
+                Finding::Problem("This isn't".to_string()),
+            ],
+            &mut holder.stdout,
+        );
+
+        assert_eq!(good, 0);
+        assert_eq!(bad, 1);
+        assert_eq!(
+            holder.stdout.get_string(),
+            "  ok       All good\nConfiguration is ready to use\n  ok       This is fine\n  PROBLEM  This isn't\nFound 1 problem; SubstratumNode would not start properly\n"
+        );
+    }
+}
@@ -0,0 +1,343 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use toml::Value;
+
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+// Options that are given once per value on the command line; arrays of other options are joined
+// into one comma-separated value.
+const REPEATABLE_OPTIONS: [&str; 1] = ["neighbor"];
+
+/// A config file: `option = value` lines in TOML syntax, where the options are the ones the
+/// command line takes, spelled the same way. A flag set to true in the file can be turned off
+/// on the command line with its `--no_` form.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct ConfigFile {
+    // Not a command-line option: on the command line it would show up in the process list.
+    pub consuming_private_key: Option<String>,
+    #[serde(flatten)]
+    pub entries: BTreeMap<String, Value>,
+}
+
+impl ConfigFile {
+    pub fn parse(contents: &str) -> Result<ConfigFile, String> {
+        // Parsed as a Value first, which unlike a flattened map refuses duplicate options.
+        let value: Value = toml::from_str(contents).map_err(|e| e.to_string())?;
+        let config_file: ConfigFile = value.try_into().map_err(|e| e.to_string())?;
+        if let Some((name, _)) = config_file
+            .entries
+            .iter()
+            .find(|(_, value)| value.is_table())
+        {
+            return Err(format!("'{}' is a table; tables aren't supported", name));
+        }
+        Ok(config_file)
+    }
+
+    /// The file's options as command-line arguments, leaving out the ones the command line
+    /// itself sets, since those take precedence.
+    pub fn args_not_in(&self, cli_args: &[String]) -> Result<Vec<String>, String> {
+        let on_command_line = option_names(cli_args);
+        let mut args = vec![];
+        for (name, value) in &self.entries {
+            if on_command_line.contains(name) {
+                continue;
+            }
+            let option = format!("--{}", name);
+            match value {
+                Value::Boolean(true) => args.push(option),
+                Value::Boolean(false) => (),
+                Value::Array(elements) => {
+                    let values = elements
+                        .iter()
+                        .map(|element| {
+                            scalar(element)
+                                .ok_or_else(|| format!("'{}' can't hold nested arrays", name))
+                        })
+                        .collect::<Result<Vec<String>, String>>()?;
+                    if REPEATABLE_OPTIONS.contains(&name.as_str()) {
+                        values.into_iter().for_each(|value| {
+                            args.push(option.clone());
+                            args.push(value);
+                        });
+                    } else if !values.is_empty() {
+                        args.push(option);
+                        args.push(values.join(","));
+                    }
+                }
+                scalar_value => {
+                    args.push(option);
+                    args.push(scalar(scalar_value).expect("Scalar disappeared"));
+                }
+            }
+        }
+        Ok(args)
+    }
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Integer(i) => Some(i.to_string()),
+        Value::Float(f) => Some(f.to_string()),
+        Value::Boolean(b) => Some(b.to_string()),
+        Value::Datetime(d) => Some(d.to_string()),
+        Value::Array(_) | Value::Table(_) => None,
+    }
+}
+
+/// Finds the config file, from --config or else in the data directory, and puts its options in
+/// front of the command-line arguments. Without --config, a missing file just means there are
+/// no options in it. Also returns the file's consuming private key, if it has one.
+pub fn merge_config_file(
+    args: &[String],
+    default_data_directory: &str,
+) -> Result<(Vec<String>, Option<String>), String> {
+    let (path, required) = match option_value(args, "config") {
+        Some(path) => (PathBuf::from(path), true),
+        None => (
+            PathBuf::from(
+                option_value(args, "data_directory")
+                    .unwrap_or_else(|| default_data_directory.to_string()),
+            )
+            .join(CONFIG_FILE_NAME),
+            false,
+        ),
+    };
+    let config_file = match fs::read_to_string(&path) {
+        Ok(contents) => ConfigFile::parse(&contents)
+            .map_err(|e| format!("Bad config file {}: {}", path.display(), e))?,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound && !required => ConfigFile::default(),
+        Err(e) => return Err(format!("Can't read config file {}: {}", path.display(), e)),
+    };
+    if config_file.entries.contains_key("config") {
+        return Err(format!(
+            "Bad config file {}: a config file can't name another one",
+            path.display()
+        ));
+    }
+    let cli_args = if args.is_empty() { args } else { &args[1..] };
+    let mut merged = args.iter().take(1).cloned().collect::<Vec<String>>();
+    merged.extend(config_file.args_not_in(cli_args)?);
+    merged.extend(cli_args.iter().cloned());
+    Ok((merged, config_file.consuming_private_key))
+}
+
+fn option_names(args: &[String]) -> HashSet<String> {
+    args.iter()
+        .filter(|arg| arg.starts_with("--"))
+        .map(|arg| arg[2..].split('=').next().unwrap_or("").to_string())
+        .collect()
+}
+
+fn option_value(args: &[String], name: &str) -> Option<String> {
+    let option = format!("--{}", name);
+    let prefix = format!("{}=", option);
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == &option {
+            return iter.next().cloned();
+        }
+        if arg.starts_with(&prefix) {
+            return Some(arg[prefix.len()..].to_string());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_utils::ensure_node_home_directory_exists;
+
+    fn strings(strs: &[&str]) -> Vec<String> {
+        strs.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_understands_the_toml_the_node_needs() {
+        let contents = r#"
+# SubstratumNode configuration
+dns_servers = ["1.1.1.1", "8.8.8.8"]  # two of them
+neighbor = [
+    "QmlsbA:1.2.3.4:1234,2345",   # Bill
+    "VGVk:2.3.4.5:3456",
+]
+ui_port = 5335
+multipath = true
+behind_nat = false
+wallet_address = "0xbDfeFf9A1f4A1bdF483d680046344316019C58CF"
+consuming_private_key = "with \"escapes\" \\ in it"
+"#;
+
+        let result = ConfigFile::parse(contents).unwrap();
+
+        assert_eq!(
+            result.entries,
+            vec![
+                (
+                    "dns_servers".to_string(),
+                    Value::Array(vec![
+                        Value::String("1.1.1.1".to_string()),
+                        Value::String("8.8.8.8".to_string()),
+                    ])
+                ),
+                (
+                    "neighbor".to_string(),
+                    Value::Array(vec![
+                        Value::String("QmlsbA:1.2.3.4:1234,2345".to_string()),
+                        Value::String("VGVk:2.3.4.5:3456".to_string()),
+                    ])
+                ),
+                ("ui_port".to_string(), Value::Integer(5335)),
+                ("multipath".to_string(), Value::Boolean(true)),
+                ("behind_nat".to_string(), Value::Boolean(false)),
+                (
+                    "wallet_address".to_string(),
+                    Value::String("0xbDfeFf9A1f4A1bdF483d680046344316019C58CF".to_string())
+                ),
+            ]
+            .into_iter()
+            .collect::<BTreeMap<String, Value>>()
+        );
+        assert_eq!(
+            result.consuming_private_key,
+            Some("with \"escapes\" \\ in it".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_reports_mistakes() {
+        assert_eq!(
+            ConfigFile::parse("ip = \"1.2.3.4\"\n\nlog_level = info\n"),
+            Err("invalid number at line 3".to_string())
+        );
+        assert_eq!(
+            ConfigFile::parse("ip \"1.2.3.4\""),
+            Err("expected an equals, found a string at line 1".to_string())
+        );
+        assert_eq!(
+            ConfigFile::parse("ip = \"1.2.3.4"),
+            Err("unterminated string at line 1".to_string())
+        );
+        assert_eq!(
+            ConfigFile::parse("[node]\nip = \"1.2.3.4\""),
+            Err("'node' is a table; tables aren't supported".to_string())
+        );
+        assert_eq!(
+            ConfigFile::parse("ip = \"1.2.3.4\"\nip = \"2.3.4.5\""),
+            Err("duplicate key: `ip`".to_string())
+        );
+        assert_eq!(
+            ConfigFile::parse("consuming_private_key = 1234"),
+            Err(
+                "invalid type: integer `1234`, expected a string for key `consuming_private_key`"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn args_not_in_leaves_out_options_the_command_line_sets() {
+        let subject = ConfigFile::parse(
+            r#"
+dns_servers = ["1.1.1.1", "8.8.8.8"]
+neighbor = ["QmlsbA:1.2.3.4:1234,2345", "VGVk:2.3.4.5:3456"]
+ip = "1.2.3.4"
+log_level = "info"
+multipath = true
+behind_nat = false
+consuming_private_key = "secret"
+"#,
+        )
+        .unwrap();
+
+        let result = subject
+            .args_not_in(&strings(&["--log_level", "trace", "--ip=2.3.4.5"]))
+            .unwrap();
+
+        assert_eq!(
+            result,
+            strings(&[
+                "--dns_servers",
+                "1.1.1.1,8.8.8.8",
+                "--multipath",
+                "--neighbor",
+                "QmlsbA:1.2.3.4:1234,2345",
+                "--neighbor",
+                "VGVk:2.3.4.5:3456",
+            ])
+        );
+        assert_eq!(subject.consuming_private_key, Some("secret".to_string()));
+    }
+
+    #[test]
+    fn merge_config_file_reads_the_data_directory_file_unless_config_names_another() {
+        let data_directory = ensure_node_home_directory_exists(
+            "config_file",
+            "merge_config_file_reads_the_data_directory_file_unless_config_names_another",
+        );
+        fs::write(
+            data_directory.join(CONFIG_FILE_NAME),
+            "log_level = \"info\"\nui_port = 5335\n",
+        )
+        .unwrap();
+        let other_file = data_directory.join("other.toml");
+        fs::write(&other_file, "log_level = \"debug\"\n").unwrap();
+        let data_dir_str = data_directory.to_str().unwrap();
+
+        let default_result = merge_config_file(
+            &strings(&["SubstratumNode", "--ui_port", "6000"]),
+            data_dir_str,
+        )
+        .unwrap();
+        let other_result = merge_config_file(
+            &strings(&["SubstratumNode", "--config", other_file.to_str().unwrap()]),
+            "/nonexistent",
+        )
+        .unwrap();
+
+        assert_eq!(
+            default_result,
+            (
+                strings(&["SubstratumNode", "--log_level", "info", "--ui_port", "6000"]),
+                None
+            )
+        );
+        assert_eq!(
+            other_result.0,
+            strings(&[
+                "SubstratumNode",
+                "--log_level",
+                "debug",
+                "--config",
+                other_file.to_str().unwrap(),
+            ])
+        );
+    }
+
+    #[test]
+    fn merge_config_file_tolerates_a_missing_default_file_but_not_a_missing_named_one() {
+        let args = strings(&["SubstratumNode", "--data_directory", "/nonexistent/dir"]);
+
+        let default_result = merge_config_file(&args, "/also/nonexistent");
+        let named_result = merge_config_file(
+            &strings(&["SubstratumNode", "--config", "/nonexistent/config.toml"]),
+            "/also/nonexistent",
+        );
+
+        assert_eq!(default_result, Ok((args, None)));
+        assert_eq!(
+            named_result
+                .err()
+                .unwrap()
+                .starts_with("Can't read config file /nonexistent/config.toml: "),
+            true
+        );
+    }
+}
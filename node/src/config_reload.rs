@@ -0,0 +1,346 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::bootstrapper::Bootstrapper;
+use crate::bootstrapper::BootstrapperConfig;
+use crate::sub_lib::neighborhood::sentinel_ip_addr;
+use crate::sub_lib::neighborhood::NodeDescriptor;
+use crate::sub_lib::neighborhood::RatePack;
+use log::LevelFilter;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+// What a configuration reload compares against: the parameters a running Node can change, and
+// everything else the command line and config file can set, which takes a restart to change.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReloadableConfiguration {
+    pub dns_servers: Vec<SocketAddr>,
+    pub log_level: LevelFilter,
+    pub neighbors: Vec<NodeDescriptor>,
+    pub rate_pack: RatePack,
+    pub payable_scan_interval: Duration,
+    pub local_ip_addr: IpAddr,
+    // Keyed by option name. The values are only ever compared, never shown: one is a private key.
+    pub fixed: BTreeMap<&'static str, String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigurationChange {
+    DnsServers(Vec<SocketAddr>),
+    LogLevel(LevelFilter),
+    Neighbors(Vec<NodeDescriptor>),
+    RatePack(RatePack),
+    PayableScanInterval(Duration),
+}
+
+impl ConfigurationChange {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConfigurationChange::DnsServers(_) => "dns_servers",
+            ConfigurationChange::LogLevel(_) => "log_level",
+            ConfigurationChange::Neighbors(_) => "neighbors",
+            ConfigurationChange::RatePack(_) => "rate_pack",
+            ConfigurationChange::PayableScanInterval(_) => "payable_scan_interval",
+        }
+    }
+}
+
+impl Default for ReloadableConfiguration {
+    fn default() -> Self {
+        ReloadableConfiguration {
+            dns_servers: vec![],
+            log_level: LevelFilter::Off,
+            neighbors: vec![],
+            rate_pack: RatePack::default(),
+            payable_scan_interval: Duration::from_secs(0),
+            local_ip_addr: sentinel_ip_addr(),
+            fixed: BTreeMap::new(),
+        }
+    }
+}
+
+impl<'a> From<&'a BootstrapperConfig> for ReloadableConfiguration {
+    fn from(config: &'a BootstrapperConfig) -> Self {
+        let neighborhood_config = &config.neighborhood_config;
+        let fixed: BTreeMap<&'static str, String> = vec![
+            ("behind_nat", format!("{}", neighborhood_config.behind_nat)),
+            (
+                "blockchain_service_url",
+                format!(
+                    "{:?}",
+                    config.blockchain_bridge_config.blockchain_service_url
+                ),
+            ),
+            (
+                "clandestine_port",
+                format!("{:?}", config.clandestine_port_opt),
+            ),
+            (
+                "consuming_private_key",
+                format!(
+                    "{:?}",
+                    config.blockchain_bridge_config.consuming_private_key
+                ),
+            ),
+            ("crash_point", format!("{:?}", config.crash_point)),
+            ("data_directory", format!("{:?}", config.data_directory)),
+            (
+                "exclude_ip_ranges",
+                format!("{:?}", config.route_constraints.excluded_ip_ranges),
+            ),
+            (
+                "exclude_nodes",
+                format!("{:?}", config.route_constraints.excluded_keys),
+            ),
+            (
+                "exit_nodes",
+                format!("{:?}", config.route_constraints.exit_keys),
+            ),
+            (
+                "gossip_batch_window",
+                format!("{:?}", neighborhood_config.gossip_batch_window),
+            ),
+            (
+                "ipv4_diversity_prefix_len",
+                format!("{}", neighborhood_config.diversity.ipv4_prefix_len),
+            ),
+            (
+                "ipv6_diversity_prefix_len",
+                format!("{}", neighborhood_config.diversity.ipv6_prefix_len),
+            ),
+            ("log_dir", format!("{:?}", config.log_directory)),
+            ("log_format", format!("{:?}", config.log_format)),
+            (
+                "log_retention",
+                format!("{}", config.log_rotation.retention),
+            ),
+            (
+                "log_rotate_hours",
+                format!("{:?}", config.log_rotation.max_age),
+            ),
+            (
+                "log_rotate_size",
+                format!("{}", config.log_rotation.max_size),
+            ),
+            (
+                "max_nodes_per_prefix",
+                format!("{}", neighborhood_config.diversity.max_nodes_per_prefix),
+            ),
+            ("multipath", format!("{}", config.multipath)),
+            (
+                "node_type",
+                format!("{}", neighborhood_config.is_bootstrap_node),
+            ),
+            ("ui_port", format!("{}", config.ui_gateway_config.ui_port)),
+            (
+                "wallet_address",
+                neighborhood_config.earning_wallet.address.clone(),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        ReloadableConfiguration {
+            dns_servers: config.dns_servers.clone(),
+            log_level: config.log_level,
+            neighbors: neighborhood_config.neighbor_configs.clone(),
+            rate_pack: neighborhood_config.rate_pack.clone(),
+            payable_scan_interval: config.accountant_config.payable_scan_interval,
+            local_ip_addr: neighborhood_config.local_ip_addr,
+            fixed,
+        }
+    }
+}
+
+impl ReloadableConfiguration {
+    // Reads the command line the Node started with, and the config file it names, again
+    pub fn read(args: &[String]) -> Result<ReloadableConfiguration, String> {
+        let mut config = BootstrapperConfig::new();
+        Bootstrapper::try_parse_args(args, &mut config).map_err(|e| {
+            e.message
+                .lines()
+                .next()
+                .unwrap_or("")
+                .trim_start_matches("error: ")
+                .to_string()
+        })?;
+        Ok(ReloadableConfiguration::from(&config))
+    }
+
+    // Either everything that changed can change live, or nothing changes at all.
+    pub fn changes_to(
+        &self,
+        new: &ReloadableConfiguration,
+    ) -> Result<Vec<ConfigurationChange>, String> {
+        let mut fixed_changes = self
+            .fixed
+            .iter()
+            .filter(|(name, value)| new.fixed.get(*name) != Some(value))
+            .map(|(name, _)| *name)
+            .collect::<Vec<&str>>();
+        if self.local_ip_addr != new.local_ip_addr {
+            fixed_changes.push("ip");
+            fixed_changes.sort();
+        }
+        if !fixed_changes.is_empty() {
+            return Err(format!(
+                "Can't change {} without restarting the Node; nothing was reloaded",
+                fixed_changes.join(", ")
+            ));
+        }
+        if !new.neighbors.is_empty() && new.local_ip_addr == sentinel_ip_addr() {
+            return Err(
+                "A Node without --ip can't have neighbors; nothing was reloaded".to_string(),
+            );
+        }
+        let mut changes = vec![];
+        if self.dns_servers != new.dns_servers {
+            changes.push(ConfigurationChange::DnsServers(new.dns_servers.clone()))
+        }
+        if self.log_level != new.log_level {
+            changes.push(ConfigurationChange::LogLevel(new.log_level))
+        }
+        if self.neighbors != new.neighbors {
+            changes.push(ConfigurationChange::Neighbors(new.neighbors.clone()))
+        }
+        if self.rate_pack != new.rate_pack {
+            changes.push(ConfigurationChange::RatePack(new.rate_pack.clone()))
+        }
+        if self.payable_scan_interval != new.payable_scan_interval {
+            changes.push(ConfigurationChange::PayableScanInterval(
+                new.payable_scan_interval,
+            ))
+        }
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_utils::ensure_node_home_directory_exists;
+    use crate::test_utils::test_utils::rate_pack;
+    use std::fs;
+    use std::str::FromStr;
+
+    fn args_with(data_directory: &str, extra: Vec<&str>) -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            "SubstratumNode",
+            "--dns_servers",
+            "1.1.1.1",
+            "--data_directory",
+            data_directory,
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        args.extend(extra.into_iter().map(String::from));
+        args
+    }
+
+    #[test]
+    fn read_picks_up_config_file_changes() {
+        let home_dir =
+            ensure_node_home_directory_exists("config_reload", "read_picks_up_config_file_changes");
+        let args = args_with(home_dir.to_str().unwrap(), vec!["--ip", "1.2.3.4"]);
+        let before = ReloadableConfiguration::read(&args).unwrap();
+        fs::write(
+            home_dir.join("config.toml"),
+            "dns_servers = \"8.8.8.8\"\nneighbor = [\"QmlsbA:2.3.4.5:3456\"]\nlog_level = \"debug\"\nrouting_byte_rate = 3\npayable_scan_interval = 60\n",
+        )
+        .unwrap();
+
+        let after = ReloadableConfiguration::read(&args).unwrap();
+
+        let changes = before.changes_to(&after).unwrap();
+        let mut new_rate_pack = before.rate_pack.clone();
+        new_rate_pack.routing_byte_rate = 3;
+        assert_eq!(
+            changes,
+            vec![
+                ConfigurationChange::LogLevel(LevelFilter::Debug),
+                ConfigurationChange::Neighbors(vec![NodeDescriptor::from_str(
+                    "QmlsbA:2.3.4.5:3456"
+                )
+                .unwrap()]),
+                ConfigurationChange::RatePack(new_rate_pack),
+                ConfigurationChange::PayableScanInterval(Duration::from_secs(60)),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_reports_unparseable_configuration() {
+        let home_dir = ensure_node_home_directory_exists(
+            "config_reload",
+            "read_reports_unparseable_configuration",
+        );
+        let args = args_with(home_dir.to_str().unwrap(), vec!["--ui_port", "80"]);
+
+        let result = ReloadableConfiguration::read(&args);
+
+        assert!(
+            result.as_ref().err().unwrap().contains("--ui_port"),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn changes_to_lists_live_changes_in_order() {
+        let before = ReloadableConfiguration {
+            local_ip_addr: IpAddr::from_str("1.2.3.4").unwrap(),
+            ..ReloadableConfiguration::default()
+        };
+        let after = ReloadableConfiguration {
+            dns_servers: vec![SocketAddr::from_str("8.8.8.8:53").unwrap()],
+            rate_pack: rate_pack(100),
+            ..before.clone()
+        };
+
+        let result = before.changes_to(&after);
+
+        assert_eq!(
+            result,
+            Ok(vec![
+                ConfigurationChange::DnsServers(vec![SocketAddr::from_str("8.8.8.8:53").unwrap()]),
+                ConfigurationChange::RatePack(rate_pack(100)),
+            ])
+        );
+        assert_eq!(before.changes_to(&before), Ok(vec![]));
+    }
+
+    #[test]
+    fn changes_to_rejects_everything_if_anything_needs_a_restart() {
+        let mut before = ReloadableConfiguration::default();
+        before.fixed.insert("ui_port", "5333".to_string());
+        before.fixed.insert("wallet_address", "0xAA".to_string());
+        let mut after = before.clone();
+        after.fixed.insert("ui_port", "5334".to_string());
+        after.fixed.insert("wallet_address", "0xBB".to_string());
+        after.local_ip_addr = IpAddr::from_str("1.2.3.4").unwrap();
+        after.rate_pack = rate_pack(100);
+
+        let result = before.changes_to(&after);
+
+        assert_eq!(
+            result,
+            Err("Can't change ip, ui_port, wallet_address without restarting the Node; nothing was reloaded".to_string())
+        );
+    }
+
+    #[test]
+    fn changes_to_rejects_neighbors_for_a_node_without_an_ip() {
+        let before = ReloadableConfiguration::default();
+        let after = ReloadableConfiguration {
+            neighbors: vec![NodeDescriptor::from_str("QmlsbA:2.3.4.5:3456").unwrap()],
+            ..before.clone()
+        };
+
+        let result = before.changes_to(&after);
+
+        assert_eq!(
+            result,
+            Err("A Node without --ip can't have neighbors; nothing was reloaded".to_string())
+        );
+    }
+}
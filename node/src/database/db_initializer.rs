@@ -10,13 +10,14 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 use std::fs;
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::path::Path;
 use std::path::PathBuf;
 use tokio::net::TcpListener;
 
 pub const DATABASE_FILE: &str = "node-data.db";
-pub const CURRENT_SCHEMA_VERSION: &str = "0.0.3";
+pub const CURRENT_SCHEMA_VERSION: &str = "0.0.4";
 
-pub trait ConnectionWrapper: Debug {
+pub trait ConnectionWrapper: Debug + Send {
     fn prepare(&self, query: &str) -> Result<Statement, rusqlite::Error>;
 }
 
@@ -61,7 +62,7 @@ impl DbInitializer for DbInitializerReal {
         match Connection::open_with_flags(database_file_path, flags) {
             Ok(conn) => {
                 let config = self.extract_configurations(&conn);
-                match self.check_version(config.get(&String::from("schema_version"))) {
+                match self.update_schema(&conn, config.get(&String::from("schema_version"))) {
                     Ok(_) => Ok(Box::new(ConnectionWrapperReal::new(conn))),
                     Err(e) => Err(e),
                 }
@@ -87,6 +88,36 @@ impl DbInitializerReal {
         DbInitializerReal {}
     }
 
+    // Reads the config table of an existing database without creating or changing anything.
+    // There's nothing to read if no Node has run with this data directory yet.
+    pub fn stored_configuration(
+        data_directory: &Path,
+    ) -> Result<Option<HashMap<String, String>>, String> {
+        let database_file_path = data_directory.join(DATABASE_FILE);
+        if !database_file_path.exists() {
+            return Ok(None);
+        }
+        let read_error = |e: Error| format!("Can't read {:?}: {}", database_file_path, e);
+        let conn =
+            Connection::open_with_flags(&database_file_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+                .map_err(read_error)?;
+        let mut stmt = conn
+            .prepare("select name, value from config")
+            .map_err(read_error)?;
+        let rows = stmt
+            .query_map(NO_PARAMS, |row| Ok((row.get(0), row.get(1))))
+            .map_err(read_error)?;
+        Ok(Some(
+            rows.filter_map(
+                |row: Result<(Result<String, Error>, Result<String, Error>), Error>| match row {
+                    Ok((Ok(name), Ok(value))) => Some((name, value)),
+                    _ => None,
+                },
+            )
+            .collect(),
+        ))
+    }
+
     fn create_data_directory_if_necessary(data_directory: &PathBuf) {
         match fs::read_dir(data_directory) {
             Ok(_) => (),
@@ -104,7 +135,8 @@ impl DbInitializerReal {
         self.create_config_table(conn)?;
         self.initialize_config(conn)?;
         self.create_payable_table(conn)?;
-        self.create_receivable_table(conn)
+        self.create_receivable_table(conn)?;
+        self.create_neighborhood_node_table(conn)
     }
 
     fn create_config_table(&self, conn: &Connection) -> Result<(), InitializationError> {
@@ -188,6 +220,21 @@ impl DbInitializerReal {
         Ok(())
     }
 
+    fn create_neighborhood_node_table(&self, conn: &Connection) -> Result<(), InitializationError> {
+        conn.execute(
+            "create table if not exists neighborhood_node (
+                signed_gossip blob not null,
+                signature blob not null,
+                node_addr text null,
+                desirable integer not null,
+                root integer not null
+            )",
+            NO_PARAMS,
+        )
+        .expect("Can't create neighborhood_node table");
+        Ok(())
+    }
+
     fn extract_configurations(&self, conn: &Connection) -> HashMap<String, String> {
         let mut stmt = conn.prepare("select name, value from config").unwrap();
         match stmt
@@ -201,6 +248,31 @@ impl DbInitializerReal {
         .collect::<HashMap<String, String>>()
     }
 
+    // Brings a database written by an earlier version of the Node up to CURRENT_SCHEMA_VERSION in
+    // place, keeping everything already in it.
+    fn update_schema(
+        &self,
+        conn: &Connection,
+        version: Option<&String>,
+    ) -> Result<(), InitializationError> {
+        match version.map(|v| v.as_str()) {
+            Some("0.0.3") => {
+                self.create_neighborhood_node_table(conn)?;
+                self.set_schema_version(conn)
+            }
+            _ => self.check_version(version),
+        }
+    }
+
+    fn set_schema_version(&self, conn: &Connection) -> Result<(), InitializationError> {
+        conn.execute(
+            "update config set value = ? where name = 'schema_version'",
+            &[CURRENT_SCHEMA_VERSION],
+        )
+        .map_err(InitializationError::SqliteError)?;
+        Ok(())
+    }
+
     fn check_version(&self, version: Option<&String>) -> Result<(), InitializationError> {
         match version {
             None => Err(InitializationError::IncompatibleVersion),
@@ -357,6 +429,11 @@ mod tests {
             .unwrap();
         let mut receivable_contents = stmt.query_map(NO_PARAMS, |_| Ok(42)).unwrap();
         assert!(receivable_contents.next().is_none());
+        let mut stmt = conn
+            .prepare("select signed_gossip, signature, node_addr, desirable, root from neighborhood_node")
+            .unwrap();
+        let mut neighborhood_node_contents = stmt.query_map(NO_PARAMS, |_| Ok(42)).unwrap();
+        assert!(neighborhood_node_contents.next().is_none());
     }
 
     #[test]
@@ -410,6 +487,79 @@ mod tests {
         assert!(config_contents.next().is_none());
     }
 
+    #[test]
+    fn existing_version_0_0_3_database_is_updated_in_place() {
+        let home_dir = ensure_node_home_directory_exists(
+            "db_initializer",
+            "existing_version_0_0_3_database_is_updated_in_place",
+        );
+        {
+            let mut flags = OpenFlags::empty();
+            flags.insert(OpenFlags::SQLITE_OPEN_READ_WRITE);
+            flags.insert(OpenFlags::SQLITE_OPEN_CREATE);
+            let conn = Connection::open_with_flags(&home_dir.join(DATABASE_FILE), flags).unwrap();
+            conn.execute_batch(
+                "create table config (name text not null, value text);
+                create unique index idx_config_name on config (name);
+                insert into config (name, value) values ('schema_version', '0.0.3');
+                insert into config (name, value) values ('clandestine_port', '4321');
+                insert into config (name, value) values ('seed', null);
+                create table payable (
+                    wallet_address text primary key,
+                    balance integer not null,
+                    last_paid_timestamp integer not null,
+                    pending_payment_transaction text null
+                );
+                insert into payable values ('0x1234', 42, 1000, null);
+                create table receivable (
+                    wallet_address text primary key,
+                    balance integer not null,
+                    last_received_timestamp integer not null
+                );
+                insert into receivable values ('0x2345', 24, 2000);",
+            )
+            .unwrap();
+        }
+        let subject = DbInitializerReal::new();
+
+        subject.initialize(&home_dir).unwrap();
+
+        let conn = Connection::open_with_flags(
+            &home_dir.join(DATABASE_FILE),
+            OpenFlags::SQLITE_OPEN_READ_ONLY,
+        )
+        .unwrap();
+        let config = DbInitializerReal::stored_configuration(&home_dir)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            config.get("schema_version"),
+            Some(&CURRENT_SCHEMA_VERSION.to_string())
+        );
+        assert_eq!(config.get("clandestine_port"), Some(&"4321".to_string()));
+        let payable_balance: i64 = conn
+            .query_row(
+                "select balance from payable where wallet_address = '0x1234'",
+                NO_PARAMS,
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(payable_balance, 42);
+        let receivable_balance: i64 = conn
+            .query_row(
+                "select balance from receivable where wallet_address = '0x2345'",
+                NO_PARAMS,
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(receivable_balance, 24);
+        let mut stmt = conn
+            .prepare("select signed_gossip, signature, node_addr, desirable, root from neighborhood_node")
+            .unwrap();
+        let mut neighborhood_node_contents = stmt.query_map(NO_PARAMS, |_| Ok(42)).unwrap();
+        assert!(neighborhood_node_contents.next().is_none());
+    }
+
     #[test]
     fn existing_database_with_no_version_is_rejected() {
         let home_dir = ensure_node_home_directory_exists(
@@ -464,6 +614,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn stored_configuration_reads_an_existing_database_and_creates_none() {
+        let home_dir = ensure_node_home_directory_exists(
+            "db_initializer",
+            "stored_configuration_reads_an_existing_database_and_creates_none",
+        );
+        let empty_dir = home_dir.join("empty");
+        fs::create_dir_all(&empty_dir).unwrap();
+        DbInitializerReal::new().initialize(&home_dir).unwrap();
+
+        let empty_result = DbInitializerReal::stored_configuration(&empty_dir);
+        let existing_result = DbInitializerReal::stored_configuration(&home_dir).unwrap();
+
+        assert_eq!(empty_result, Ok(None));
+        assert!(!empty_dir.join(DATABASE_FILE).exists());
+        let existing = existing_result.unwrap();
+        assert_eq!(
+            existing.get("schema_version"),
+            Some(&CURRENT_SCHEMA_VERSION.to_string())
+        );
+        assert!(existing.contains_key("clandestine_port"));
+    }
+
     #[test]
     fn choose_clandestine_port_chooses_different_unused_ports_each_time() {
         let _listeners = (0..10)
@@ -2,6 +2,7 @@
 use crate::stream_messages::PoolBindMessage;
 use crate::sub_lib::dispatcher::DispatcherSubs;
 use crate::sub_lib::dispatcher::InboundClientData;
+use crate::sub_lib::logger::LogFields;
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::peer_actors::BindMessage;
 use crate::sub_lib::stream_handler_pool::TransmitDataMsg;
@@ -65,11 +66,16 @@ impl Handler<TransmitDataMsg> for Dispatcher {
     type Result = ();
 
     fn handle(&mut self, msg: TransmitDataMsg, _ctx: &mut Self::Context) {
-        self.logger.debug(format!(
-            "Relaying {} bytes to StreamHandlerPool for {:?}",
-            msg.data.len(),
-            msg.endpoint
-        ));
+        self.logger.debug_with(
+            LogFields::new()
+                .bytes(msg.data.len())
+                .field("endpoint", format!("{:?}", msg.endpoint)),
+            format!(
+                "Relaying {} bytes to StreamHandlerPool for {:?}",
+                msg.data.len(),
+                msg.endpoint
+            ),
+        );
         self.to_stream
             .as_ref()
             .expect("StreamHandlerPool unbound in Dispatcher")
@@ -289,6 +295,7 @@ mod tests {
                 dispatcher_subs: peer_actors.dispatcher.clone(),
                 stream_handler_pool_subs,
                 neighborhood_subs: peer_actors.neighborhood.clone(),
+                hopper_subs: peer_actors.hopper.clone(),
             })
             .unwrap();
         subject_addr.try_send(BindMessage { peer_actors }).unwrap();
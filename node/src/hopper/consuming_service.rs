@@ -5,6 +5,7 @@ use crate::sub_lib::cryptde::CryptData;
 use crate::sub_lib::cryptde::{encodex, CryptDE};
 use crate::sub_lib::dispatcher::{Endpoint, InboundClientData};
 use crate::sub_lib::hopper::{IncipientCoresPackage, NoLookupIncipientCoresPackage};
+use crate::sub_lib::logger::LogFields;
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::stream_handler_pool::TransmitDataMsg;
 use actix::Recipient;
@@ -33,10 +34,15 @@ impl ConsumingService {
     }
 
     pub fn consume_no_lookup(&self, incipient_cores_package: NoLookupIncipientCoresPackage) {
-        self.logger.debug(format!(
-            "Received NoLookupIncipientCoresPackage with {}-byte payload",
-            incipient_cores_package.payload.len()
-        ));
+        self.logger.debug_with(
+            LogFields::new()
+                .public_key(&incipient_cores_package.public_key)
+                .bytes(incipient_cores_package.payload.len()),
+            format!(
+                "Received NoLookupIncipientCoresPackage with {}-byte payload",
+                incipient_cores_package.payload.len()
+            ),
+        );
         let target_key = incipient_cores_package.public_key.clone();
         let target_node_addr = incipient_cores_package.node_addr.clone();
         match LiveCoresPackage::from_no_lookup_incipient(incipient_cores_package, self.cryptde) {
@@ -65,10 +71,13 @@ impl ConsumingService {
     }
 
     pub fn consume(&self, incipient_cores_package: IncipientCoresPackage) {
-        self.logger.debug(format!(
-            "Received IncipientCoresPackage with {}-byte payload",
-            incipient_cores_package.payload.len()
-        ));
+        self.logger.debug_with(
+            LogFields::new().bytes(incipient_cores_package.payload.len()),
+            format!(
+                "Received IncipientCoresPackage with {}-byte payload",
+                incipient_cores_package.payload.len()
+            ),
+        );
         match LiveCoresPackage::from_incipient(incipient_cores_package, self.cryptde.borrow()) {
             Ok((live_package, next_hop)) => {
                 let encrypted_package =
@@ -98,10 +107,13 @@ impl ConsumingService {
             sequence_number: None,
             data: encrypted_package.into(),
         };
-        self.logger.debug(format!(
-            "Sending zero-hop InboundClientData with {}-byte payload back to Hopper",
-            ibcd.data.len()
-        ));
+        self.logger.debug_with(
+            LogFields::new().bytes(ibcd.data.len()),
+            format!(
+                "Sending zero-hop InboundClientData with {}-byte payload back to Hopper",
+                ibcd.data.len()
+            ),
+        );
         self.to_hopper.try_send(ibcd).expect("Hopper is dead");
     }
 
@@ -113,10 +125,15 @@ impl ConsumingService {
             sequence_number: None,
         };
 
-        self.logger.debug(format!(
-            "Sending TransmitDataMsg with {}-byte payload to Dispatcher",
-            transmit_msg.data.len()
-        ));
+        self.logger.debug_with(
+            LogFields::new()
+                .bytes(transmit_msg.data.len())
+                .field("endpoint", format!("{:?}", transmit_msg.endpoint)),
+            format!(
+                "Sending TransmitDataMsg with {}-byte payload to Dispatcher",
+                transmit_msg.data.len()
+            ),
+        );
         self.to_dispatcher
             .try_send(transmit_msg)
             .expect("Dispatcher is dead");
@@ -131,12 +148,13 @@ mod tests {
     use crate::sub_lib::node_addr::NodeAddr;
     use crate::sub_lib::route::Route;
     use crate::sub_lib::route::RouteSegment;
-    use crate::sub_lib::wallet::Wallet;
     use crate::test_utils::logging::init_test_logging;
     use crate::test_utils::logging::TestLogHandler;
     use crate::test_utils::recorder::make_recorder;
     use crate::test_utils::recorder::peer_actors_builder;
-    use crate::test_utils::test_utils::{cryptde, make_meaningless_message_type};
+    use crate::test_utils::test_utils::{
+        cryptde, make_meaningless_message_type, make_paying_wallet,
+    };
     use actix::System;
     use std::net::{IpAddr, Ipv4Addr};
     use std::str::FromStr;
@@ -209,7 +227,7 @@ mod tests {
     #[test]
     fn consume_converts_incipient_message_to_live_and_sends_to_dispatcher() {
         let cryptde = cryptde();
-        let consuming_wallet = Wallet::new("wallet");
+        let consuming_wallet = make_paying_wallet(b"wallet");
         let (dispatcher, _, dispatcher_recording_arc) = make_recorder();
         let destination_key = PublicKey::new(&[65, 65, 65]);
         let route = Route::one_way(
@@ -255,7 +273,7 @@ mod tests {
     #[test]
     fn consume_sends_zero_hop_incipient_directly_to_hopper() {
         let cryptde = cryptde();
-        let consuming_wallet = Wallet::new("wallet");
+        let consuming_wallet = make_paying_wallet(b"wallet");
         let (hopper, _, hopper_recording_arc) = make_recorder();
         let destination_key = cryptde.public_key();
         let route = Route::one_way(
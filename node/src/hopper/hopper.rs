@@ -6,7 +6,9 @@ use crate::sub_lib::cryptde::CryptDE;
 use crate::sub_lib::dispatcher::InboundClientData;
 use crate::sub_lib::hopper::HopperSubs;
 use crate::sub_lib::hopper::IncipientCoresPackage;
+use crate::sub_lib::hopper::UndeliverablePackageMessage;
 use crate::sub_lib::hopper::{HopperConfig, NoLookupIncipientCoresPackage};
+use crate::sub_lib::neighborhood::SetRatePackMessage;
 use crate::sub_lib::peer_actors::BindMessage;
 use crate::sub_lib::utils::NODE_MAILBOX_CAPACITY;
 use actix::Actor;
@@ -90,12 +92,39 @@ impl Handler<InboundClientData> for Hopper {
 
     fn handle(&mut self, msg: InboundClientData, _ctx: &mut Self::Context) -> Self::Result {
         self.routing_service
-            .as_ref()
+            .as_mut()
             .expect("Hopper unbound: no RoutingService")
             .route(msg);
     }
 }
 
+impl Handler<UndeliverablePackageMessage> for Hopper {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: UndeliverablePackageMessage,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        self.routing_service
+            .as_mut()
+            .expect("Hopper unbound: no RoutingService")
+            .report_undeliverable(msg);
+    }
+}
+
+impl Handler<SetRatePackMessage> for Hopper {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetRatePackMessage, _ctx: &mut Self::Context) -> Self::Result {
+        self.per_routing_service = msg.rate_pack.routing_service_rate;
+        self.per_routing_byte = msg.rate_pack.routing_byte_rate;
+        if let Some(routing_service) = self.routing_service.as_mut() {
+            routing_service.set_rates(self.per_routing_service, self.per_routing_byte);
+        }
+    }
+}
+
 impl Hopper {
     pub fn new(config: HopperConfig) -> Hopper {
         Hopper {
@@ -114,6 +143,8 @@ impl Hopper {
             from_hopper_client: addr.clone().recipient::<IncipientCoresPackage>(),
             from_hopper_client_no_lookup: addr.clone().recipient::<NoLookupIncipientCoresPackage>(),
             from_dispatcher: addr.clone().recipient::<InboundClientData>(),
+            undeliverable_package: addr.clone().recipient::<UndeliverablePackageMessage>(),
+            set_rate_pack: addr.clone().recipient::<SetRatePackMessage>(),
         }
     }
 }
@@ -128,9 +159,8 @@ mod tests {
     use crate::sub_lib::hopper::IncipientCoresPackage;
     use crate::sub_lib::route::Route;
     use crate::sub_lib::route::RouteSegment;
-    use crate::sub_lib::wallet::Wallet;
     use crate::test_utils::test_utils::{
-        cryptde, make_meaningless_message_type, route_to_proxy_client,
+        cryptde, make_meaningless_message_type, make_paying_wallet, route_to_proxy_client,
     };
     use actix::Actor;
     use actix::System;
@@ -184,7 +214,7 @@ mod tests {
     #[should_panic(expected = "Hopper unbound: no ConsumingService")]
     fn panics_if_consuming_service_is_unbound() {
         let cryptde = cryptde();
-        let consuming_wallet = Wallet::new("wallet");
+        let consuming_wallet = make_paying_wallet(b"wallet");
         let next_key = PublicKey::new(&[65, 65, 65]);
         let route = Route::one_way(
             RouteSegment::new(
@@ -15,11 +15,20 @@ use std::net::IpAddr;
 pub struct LiveCoresPackage {
     pub route: Route,
     pub payload: CryptData,
+    // The payload of a failure return is sealed by the originator when it makes the Route, and the
+    // Node reporting the failure doesn't know who the originator is, so it can't seal the reason
+    // in with it. The reason rides alongside instead, unencrypted and unauthenticated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_reason_opt: Option<String>,
 }
 
 impl LiveCoresPackage {
     pub fn new(route: Route, payload: CryptData) -> LiveCoresPackage {
-        LiveCoresPackage { route, payload }
+        LiveCoresPackage {
+            route,
+            payload,
+            failure_reason_opt: None,
+        }
     }
 
     pub fn to_next_live(
@@ -27,7 +36,11 @@ impl LiveCoresPackage {
         cryptde: &dyn CryptDE, // must be the CryptDE of the Node to which the top hop is encrypted
     ) -> Result<(LiveHop, LiveCoresPackage), RouteError> {
         let next_hop = self.route.shift(cryptde)?;
-        let next_live = LiveCoresPackage::new(self.route, self.payload);
+        let next_live = LiveCoresPackage {
+            route: self.route,
+            payload: self.payload,
+            failure_reason_opt: self.failure_reason_opt,
+        };
         Ok((next_hop, next_live))
     }
 
@@ -57,7 +70,14 @@ impl LiveCoresPackage {
             Ok(h) => h,
             Err(e) => return Err(format!("Could not decrypt next hop: {:?}", e)),
         };
-        Ok((LiveCoresPackage::new(route, incipient.payload), next_hop))
+        Ok((
+            LiveCoresPackage {
+                route,
+                payload: incipient.payload,
+                failure_reason_opt: incipient.failure_reason_opt,
+            },
+            next_hop,
+        ))
     }
 
     pub fn to_expired(
@@ -69,13 +89,31 @@ impl LiveCoresPackage {
             Err(e) => return Err(format!("{:?}", e)),
             Ok(hop) => hop,
         };
-        decodex::<MessageType>(cryptde, &self.payload).map(|decoded_payload| {
+        if let Some(ref payer) = top_hop.payer {
+            if !top_hop.payer_owns_secret_key(cryptde.public_key(), &self.route.hops[1..]) {
+                return Err(format!(
+                    "Payer's proof of ownership of consuming wallet {} is invalid",
+                    payer.wallet.address
+                ));
+            }
+        }
+        let LiveCoresPackage {
+            route,
+            payload,
+            failure_reason_opt,
+        } = self;
+        decodex::<MessageType>(cryptde, &payload).map(|mut decoded_payload| {
+            if let MessageType::RouteFailed(route_failure) = &mut decoded_payload {
+                if let Some(reason) = failure_reason_opt {
+                    route_failure.reason = reason;
+                }
+            }
             ExpiredCoresPackage::new(
                 immediate_neighbor_ip,
-                top_hop.consuming_wallet,
-                self.route,
+                top_hop.consuming_wallet(),
+                route,
                 decoded_payload,
-                self.payload.len(),
+                payload.len(),
             )
         })
     }
@@ -89,12 +127,13 @@ mod tests {
     use crate::sub_lib::cryptde_null::CryptDENull;
     use crate::sub_lib::dispatcher::Component;
     use crate::sub_lib::hopper::IncipientCoresPackage;
+    use crate::sub_lib::hopper::RouteFailure;
     use crate::sub_lib::node_addr::NodeAddr;
     use crate::sub_lib::route::Route;
     use crate::sub_lib::route::RouteSegment;
     use crate::sub_lib::wallet::Wallet;
     use crate::test_utils::test_utils::{
-        cryptde, make_meaningless_message_type, make_meaningless_route,
+        cryptde, make_meaningless_message_type, make_meaningless_route, make_paying_wallet,
     };
     use std::str::FromStr;
 
@@ -102,7 +141,7 @@ mod tests {
     fn live_cores_package_can_be_constructed_from_scratch() {
         let payload = CryptData::new(&[5, 6]);
         let cryptde = cryptde();
-        let consuming_wallet = Wallet::new("wallet");
+        let consuming_wallet = make_paying_wallet(b"wallet");
         let route = Route::one_way(
             RouteSegment::new(
                 vec![&PublicKey::new(&[1, 2]), &PublicKey::new(&[3, 4])],
@@ -130,35 +169,30 @@ mod tests {
         let encrypted_payload = cryptde
             .encode(&destination_key, &PlainData::new(&serialized_payload))
             .unwrap();
-        let consuming_wallet = Wallet::new("wallet");
+        let consuming_wallet = make_paying_wallet(b"wallet");
         let route = Route::one_way(
             RouteSegment::new(vec![&relay_key, &destination_key], Component::Neighborhood),
             cryptde,
-            Some(consuming_wallet),
+            Some(consuming_wallet.clone()),
         )
         .unwrap();
         let subject = LiveCoresPackage::new(route.clone(), encrypted_payload.clone());
 
         let (next_hop, next_pkg) = subject.to_next_live(&relay_cryptde).unwrap();
 
+        assert_eq!(next_hop.public_key, destination_key);
+        assert_eq!(next_hop.component, Component::Hopper);
+        assert_eq!(next_hop.consuming_wallet(), Some(consuming_wallet.clone()));
         assert_eq!(
-            next_hop,
-            LiveHop::new(
-                &destination_key,
-                Some(Wallet::new("wallet")),
-                Component::Hopper,
-            )
+            next_hop.payer_owns_secret_key(&relay_key, &next_pkg.route.hops),
+            true
         );
         assert_eq!(next_pkg.payload, encrypted_payload);
         let mut route = next_pkg.route.clone();
-        assert_eq!(
-            route.shift(&destination_cryptde).unwrap(),
-            LiveHop::new(
-                &PublicKey::new(&[]),
-                Some(Wallet::new("wallet")),
-                Component::Neighborhood,
-            )
-        );
+        let last_hop = route.shift(&destination_cryptde).unwrap();
+        assert_eq!(last_hop.public_key, PublicKey::new(&[]));
+        assert_eq!(last_hop.component, Component::Neighborhood);
+        assert_eq!(last_hop.consuming_wallet(), Some(consuming_wallet));
         assert_eq!(
             &route.hops[0].as_slice()[..8],
             &[52, 52, 52, 52, 52, 52, 52, 52]
@@ -218,7 +252,7 @@ mod tests {
     #[test]
     fn live_cores_package_can_be_constructed_from_incipient_cores_package() {
         let cryptde = cryptde();
-        let consuming_wallet = Wallet::new("wallet");
+        let consuming_wallet = make_paying_wallet(b"wallet");
         let key12 = cryptde.public_key();
         let key34 = PublicKey::new(&[3, 4]);
         let key56 = PublicKey::new(&[5, 6]);
@@ -234,14 +268,9 @@ mod tests {
             IncipientCoresPackage::new(cryptde, route.clone(), payload.clone(), &key56).unwrap();
         let (subject, next_stop) = LiveCoresPackage::from_incipient(incipient, cryptde).unwrap();
 
-        assert_eq!(
-            LiveHop {
-                public_key: key34,
-                consuming_wallet: Some(consuming_wallet),
-                component: Component::Hopper
-            },
-            next_stop
-        );
+        assert_eq!(next_stop, route.next_hop(cryptde).unwrap());
+        assert_eq!(next_stop.public_key, key34);
+        assert_eq!(next_stop.consuming_wallet(), Some(consuming_wallet));
         route.shift(cryptde).unwrap();
 
         assert_eq!(route, subject.route);
@@ -278,7 +307,7 @@ mod tests {
         let second_stop_cryptde = CryptDENull::from(&second_stop_key);
         let cryptde = cryptde();
         let encrypted_payload = encodex(cryptde, &first_stop_key, &payload).unwrap();
-        let consuming_wallet = Wallet::new("wallet");
+        let consuming_wallet = make_paying_wallet(b"wallet");
         let mut route = Route::round_trip(
             RouteSegment::new(vec![&relay_key, &first_stop_key], Component::Neighborhood),
             RouteSegment::new(
@@ -286,7 +315,7 @@ mod tests {
                 Component::ProxyServer,
             ),
             cryptde,
-            Some(consuming_wallet),
+            Some(consuming_wallet.clone()),
             1234,
         )
         .unwrap();
@@ -298,33 +327,30 @@ mod tests {
             .unwrap();
 
         assert_eq!(result.immediate_neighbor_ip, immediate_neighbor_ip);
-        assert_eq!(result.consuming_wallet, Some(Wallet::new("wallet")));
+        assert_eq!(result.consuming_wallet, Some(consuming_wallet.clone()));
         assert_eq!(result.payload, payload);
         let mut route = result.remaining_route.clone();
-        assert_eq!(
-            route.shift(&first_stop_cryptde).unwrap(),
-            LiveHop::new(
-                &relay_key,
-                Some(Wallet::new("wallet")),
-                Component::Neighborhood,
-            )
-        );
-        assert_eq!(
-            route.shift(&relay_cryptde).unwrap(),
-            LiveHop::new(
-                &second_stop_key,
-                Some(Wallet::new("wallet")),
-                Component::Hopper,
-            )
-        );
-        assert_eq!(
-            route.shift(&second_stop_cryptde).unwrap(),
-            LiveHop::new(
+        vec![
+            (&first_stop_cryptde, &relay_key, Component::Neighborhood),
+            (&relay_cryptde, &second_stop_key, Component::Hopper),
+            (
+                &second_stop_cryptde,
                 &PublicKey::new(&[]),
-                Some(Wallet::new("wallet")),
                 Component::ProxyServer,
-            )
-        );
+            ),
+        ]
+        .into_iter()
+        .for_each(|(owner_cryptde, next_key, component)| {
+            let hop = route.next_hop(owner_cryptde).unwrap();
+            assert_eq!(&hop.public_key, next_key);
+            assert_eq!(hop.component, component);
+            assert_eq!(hop.consuming_wallet(), Some(consuming_wallet.clone()));
+            assert_eq!(
+                hop.payer_owns_secret_key(owner_cryptde.public_key(), &route.hops[1..]),
+                true
+            );
+            route.shift(owner_cryptde).unwrap();
+        });
         assert_eq!(
             route.hops[0],
             crate::test_utils::test_utils::encrypt_return_route_id(1234, cryptde),
@@ -348,11 +374,93 @@ mod tests {
         assert_eq!(result, Err(format!("{:?}", RouteError::EmptyRoute)));
     }
 
+    #[test]
+    fn to_expired_complains_about_payer_without_proof_of_wallet_ownership() {
+        let cryptde = cryptde();
+        let mut hop = LiveHop::new(&PublicKey::new(b""), None, Component::ProxyClient);
+        hop.sign_payer(
+            &make_paying_wallet(b"impostor"),
+            &cryptde.public_key(),
+            0,
+            false,
+            None,
+        )
+        .unwrap();
+        hop.payer.as_mut().unwrap().wallet = Wallet::new("victim");
+        let hop = hop.encode(&cryptde.public_key(), cryptde).unwrap();
+        let subject = LiveCoresPackage::new(
+            Route { hops: vec![hop] },
+            encodex(
+                cryptde,
+                &cryptde.public_key(),
+                &make_meaningless_message_type(),
+            )
+            .unwrap(),
+        );
+
+        let result = subject.to_expired(IpAddr::from_str("1.2.3.4").unwrap(), cryptde);
+
+        assert_eq!(
+            result,
+            Err(String::from(
+                "Payer's proof of ownership of consuming wallet victim is invalid"
+            ))
+        );
+    }
+
+    #[test]
+    fn failure_reason_travels_with_the_package_and_is_put_into_the_route_failure_on_arrival() {
+        let relay_cryptde = CryptDENull::from(&PublicKey::new(&[1, 2]));
+        let middle_cryptde = CryptDENull::from(&PublicKey::new(&[7, 8]));
+        let originator_cryptde = CryptDENull::from(&PublicKey::new(&[3, 4]));
+        let route = Route::one_way(
+            RouteSegment::new(
+                vec![
+                    relay_cryptde.public_key(),
+                    middle_cryptde.public_key(),
+                    originator_cryptde.public_key(),
+                ],
+                Component::ProxyServer,
+            ),
+            &relay_cryptde,
+            None,
+        )
+        .unwrap();
+        let route_failure = RouteFailure {
+            reporting_key: relay_cryptde.public_key().clone(),
+            failed_key: PublicKey::new(&[5, 6]),
+            reason: String::new(),
+        };
+        let mut incipient = IncipientCoresPackage::new(
+            &relay_cryptde,
+            route,
+            MessageType::RouteFailed(route_failure.clone()),
+            originator_cryptde.public_key(),
+        )
+        .unwrap();
+        incipient.failure_reason_opt = Some("connection refused".to_string());
+        let (live, _) = LiveCoresPackage::from_incipient(incipient, &relay_cryptde).unwrap();
+
+        let (_, next_live) = live.to_next_live(&middle_cryptde).unwrap();
+        let expired = next_live
+            .to_expired(IpAddr::from_str("1.2.3.4").unwrap(), &originator_cryptde)
+            .unwrap();
+
+        assert_eq!(
+            expired.payload,
+            MessageType::RouteFailed(RouteFailure {
+                reason: "connection refused".to_string(),
+                ..route_failure
+            })
+        );
+    }
+
     #[test]
     fn live_cores_package_serialization_deserialization() {
         let original = LiveCoresPackage {
             route: make_meaningless_route(),
             payload: CryptData::new(&[1, 2, 3, 4]),
+            failure_reason_opt: Some("connection refused".to_string()),
         };
 
         let serialized = serde_cbor::ser::to_vec(&original).unwrap();
@@ -9,16 +9,39 @@ use crate::sub_lib::dispatcher::Component;
 use crate::sub_lib::dispatcher::Endpoint;
 use crate::sub_lib::dispatcher::InboundClientData;
 use crate::sub_lib::hop::LiveHop;
+use crate::sub_lib::hop::Payer;
 use crate::sub_lib::hopper::{ExpiredCoresPackage, HopperSubs, MessageType};
+use crate::sub_lib::hopper::{IncipientCoresPackage, UndeliverablePackageMessage};
+use crate::sub_lib::logger::LogFields;
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::neighborhood::NeighborhoodSubs;
+use crate::sub_lib::neighborhood::RelayRequestMessage;
 use crate::sub_lib::proxy_client::ProxyClientSubs;
 use crate::sub_lib::proxy_server::ProxyServerSubs;
 use crate::sub_lib::stream_handler_pool::TransmitDataMsg;
-use crate::sub_lib::wallet::Wallet;
+use crate::sub_lib::ttl_hashmap::TtlHashMap;
 use actix::Recipient;
+use lru_cache::LruCache;
 use std::borrow::Borrow;
-use std::net::{IpAddr, SocketAddr};
+use std::net::SocketAddr;
+use std::time::Duration;
+use std::time::Instant;
+
+// A replayed CORES package arriving after this long won't be recognized as a replay.
+pub const REPLAY_DETECTION_TTL: Duration = Duration::from_secs(120);
+
+// Caps the memory a flood of unique packages can take up; beyond this, the least recently seen
+// packages are forgotten first, even if they're younger than REPLAY_DETECTION_TTL.
+pub const REPLAY_DETECTION_CAPACITY: usize = 100_000;
+
+// Single-use Payer nonces are remembered, with the wallets that spent them, until this many newer
+// ones have been spent.
+pub const SPENT_NONCE_CAPACITY: usize = 100_000;
+
+// If the Dispatcher hasn't reported a relayed CORES package undeliverable by now, it never will.
+pub const FAILURE_RETURN_TTL: Duration = Duration::from_secs(60);
+
+type PackageDigest = [u8; sha1::DIGEST_LENGTH];
 
 pub struct RoutingServiceSubs {
     pub proxy_client_subs: ProxyClientSubs,
@@ -35,6 +58,9 @@ pub struct RoutingService {
     routing_service_subs: RoutingServiceSubs,
     per_routing_service: u64,
     per_routing_byte: u64,
+    seen_packages: LruCache<PackageDigest, Instant>,
+    spent_nonces: LruCache<(String, u64), ()>,
+    failure_returns: TtlHashMap<PackageDigest, IncipientCoresPackage>,
     logger: Logger,
 }
 
@@ -52,19 +78,31 @@ impl RoutingService {
             routing_service_subs,
             per_routing_service,
             per_routing_byte,
+            seen_packages: LruCache::new(REPLAY_DETECTION_CAPACITY),
+            spent_nonces: LruCache::new(SPENT_NONCE_CAPACITY),
+            failure_returns: TtlHashMap::new(FAILURE_RETURN_TTL),
             logger: Logger::new("RoutingService"),
         }
     }
 
-    pub fn route(&self, ibcd: InboundClientData) {
+    pub fn set_rates(&mut self, per_routing_service: u64, per_routing_byte: u64) {
+        self.per_routing_service = per_routing_service;
+        self.per_routing_byte = per_routing_byte;
+    }
+
+    pub fn route(&mut self, ibcd: InboundClientData) {
         let data_size = ibcd.data.len();
-        self.logger.debug(format!(
-            "Received {} bytes of InboundClientData ({}) from Dispatcher",
-            data_size, ibcd.peer_addr
-        ));
+        self.logger.debug_with(
+            LogFields::new().peer_addr(ibcd.peer_addr).bytes(data_size),
+            format!(
+                "Received {} bytes of InboundClientData ({}) from Dispatcher",
+                data_size, ibcd.peer_addr
+            ),
+        );
         let peer_addr = ibcd.peer_addr;
         let last_data = ibcd.last_data;
         let ibcd_but_data = ibcd.clone_but_data();
+        let digest = Self::digest(&ibcd.data[..]);
         let live_package = match self.decrypt_and_deserialize_lcp(ibcd) {
             Ok(package) => package,
             Err(_) => return, // log already written
@@ -79,29 +117,63 @@ impl RoutingService {
             }
         };
 
+        if self.is_replay(digest, next_hop.component) {
+            self.logger.warning(format!(
+                "Dropping replayed {}-byte CORES package from {} destined for {:?}",
+                data_size, peer_addr, next_hop.component
+            ));
+            return;
+        }
+
         if self.should_route_data(peer_addr, next_hop.component) {
-            self.route_data(
-                peer_addr.ip(),
-                next_hop,
-                live_package,
-                last_data,
-                &ibcd_but_data,
-            );
+            self.route_data(peer_addr, next_hop, live_package, last_data, &ibcd_but_data);
         }
     }
 
+    pub fn report_undeliverable(&mut self, msg: UndeliverablePackageMessage) {
+        let failure_return = match self.failure_returns.remove(&Self::digest(&msg.data[..])) {
+            Some(failure_return) => failure_return,
+            None => {
+                self.logger.debug_with(
+                    LogFields::new()
+                        .public_key(&msg.public_key)
+                        .bytes(msg.data.len()),
+                    format!(
+                        "No failure return for {} undeliverable bytes to {}",
+                        msg.data.len(),
+                        msg.public_key
+                    ),
+                );
+                return;
+            }
+        };
+        self.logger.warning(format!(
+            "Couldn't relay {}-byte CORES package to {}: {}; reporting route failure to originator",
+            msg.data.len(),
+            msg.public_key,
+            msg.reason
+        ));
+        let mut package = failure_return.as_ref().clone();
+        package.failure_reason_opt = Some(msg.reason);
+        self.routing_service_subs
+            .hopper_subs
+            .from_hopper_client
+            .try_send(package)
+            .expect("Hopper is dead");
+    }
+
     fn route_data(
-        &self,
-        sender_ip: IpAddr,
+        &mut self,
+        sender_addr: SocketAddr,
         next_hop: LiveHop,
         live_package: LiveCoresPackage,
         last_data: bool,
         ibcd_but_data: &InboundClientData,
     ) {
         if (next_hop.component == Component::Hopper) && (!self.is_destined_for_here(&next_hop)) {
-            self.route_data_externally(live_package, next_hop.consuming_wallet, last_data);
+            self.route_data_externally(live_package, next_hop, last_data);
         } else {
-            self.route_data_internally(next_hop.component, sender_ip, live_package, ibcd_but_data)
+            self.route_data_internally(next_hop, sender_addr, live_package, ibcd_but_data)
         }
     }
 
@@ -110,16 +182,16 @@ impl RoutingService {
     }
 
     fn route_data_internally(
-        &self,
-        component: Component,
-        immediate_neighbor_ip: IpAddr,
+        &mut self,
+        next_hop: LiveHop,
+        immediate_neighbor_addr: SocketAddr,
         live_package: LiveCoresPackage,
         ibcd_but_data: &InboundClientData,
     ) {
-        if component == Component::Hopper {
+        if next_hop.component == Component::Hopper {
             self.route_data_around_again(live_package, ibcd_but_data)
         } else {
-            self.route_data_to_peripheral_component(component, immediate_neighbor_ip, live_package)
+            self.route_data_to_peripheral_component(next_hop, immediate_neighbor_addr, live_package)
         }
     }
 
@@ -152,14 +224,15 @@ impl RoutingService {
     }
 
     fn route_data_to_peripheral_component(
-        &self,
-        component: Component,
-        immediate_neighbor_ip: IpAddr,
+        &mut self,
+        next_hop: LiveHop,
+        immediate_neighbor_addr: SocketAddr,
         live_package: LiveCoresPackage,
     ) {
+        let component = next_hop.component;
         let data_len = live_package.payload.len();
         let expired_package =
-            match live_package.to_expired(immediate_neighbor_ip, self.cryptde.borrow()) {
+            match live_package.to_expired(immediate_neighbor_addr.ip(), self.cryptde.borrow()) {
                 Ok(pkg) => pkg,
                 Err(e) => {
                     self.logger.error(format!(
@@ -169,6 +242,11 @@ impl RoutingService {
                     return;
                 }
             };
+        if let Some(ref payer) = next_hop.payer {
+            if !self.spend_nonce(payer) {
+                return;
+            }
+        }
         self.logger.trace(format!(
             "Forwarding ExpiredCoresPackage to {:?}: {:?}",
             component, expired_package
@@ -210,6 +288,18 @@ impl RoutingService {
                     expired_package.payload_len,
                 ))
                 .expect("Proxy Server is dead"),
+            (Component::ProxyServer, MessageType::RouteFailed(route_failure)) => self
+                .routing_service_subs
+                .proxy_server_subs
+                .route_failure_from_hopper
+                .try_send(ExpiredCoresPackage::new(
+                    expired_package.immediate_neighbor_ip,
+                    expired_package.consuming_wallet,
+                    expired_package.remaining_route,
+                    route_failure,
+                    expired_package.payload_len,
+                ))
+                .expect("Proxy Server is dead"),
             (Component::Neighborhood, MessageType::Gossip(gossip)) => self
                 .routing_service_subs
                 .neighborhood_subs
@@ -222,6 +312,27 @@ impl RoutingService {
                     expired_package.payload_len,
                 ))
                 .expect("Neighborhood is dead"),
+            (Component::Neighborhood, MessageType::Heartbeat(heartbeat)) => self
+                .routing_service_subs
+                .neighborhood_subs
+                .heartbeat_from_hopper
+                .try_send(ExpiredCoresPackage::new(
+                    expired_package.immediate_neighbor_ip,
+                    expired_package.consuming_wallet,
+                    expired_package.remaining_route,
+                    heartbeat,
+                    expired_package.payload_len,
+                ))
+                .expect("Neighborhood is dead"),
+            (Component::Neighborhood, MessageType::RelayRequest(relay_request)) => self
+                .routing_service_subs
+                .neighborhood_subs
+                .from_relay_client
+                .try_send(RelayRequestMessage {
+                    relay_request,
+                    client_addr: immediate_neighbor_addr,
+                })
+                .expect("Neighborhood is dead"),
             (destination, payload) => self.logger.error(format!(
                 "Attempt to send invalid combination {:?} to {:?}",
                 payload, destination
@@ -230,18 +341,30 @@ impl RoutingService {
     }
 
     fn route_data_externally(
-        &self,
+        &mut self,
         live_package: LiveCoresPackage,
-        consuming_wallet_opt: Option<Wallet>,
+        next_hop: LiveHop,
         last_data: bool,
     ) {
         let payload_size = live_package.payload.len();
-        match consuming_wallet_opt {
-            Some(consuming_wallet) => {
+        let proven = next_hop
+            .payer_owns_secret_key(self.cryptde.public_key(), &live_package.route.hops[1..]);
+        match next_hop.payer {
+            Some(ref payer) if !proven => {
+                self.logger.error(format!(
+                    "Refusing to route CORES package with {}-byte payload without proof of {} wallet ownership",
+                    payload_size, payer.wallet.address
+                ));
+                return;
+            }
+            Some(payer) => {
+                if !self.spend_nonce(&payer) {
+                    return;
+                }
                 self.routing_service_subs
                     .to_accountant_routing
                     .try_send(ReportRoutingServiceProvidedMessage {
-                        consuming_wallet,
+                        consuming_wallet: payer.wallet,
                         payload_size,
                         service_rate: self.per_routing_service,
                         byte_rate: self.per_routing_byte,
@@ -257,16 +380,29 @@ impl RoutingService {
             }
         }
 
+        let failure_return_opt = next_hop
+            .failure_return
+            .as_ref()
+            .and_then(|failure_return| failure_return.to_package(&live_package.route));
         let transmit_msg = match self.to_transmit_data_msg(live_package, last_data) {
             // crashpoint - need to figure out how to bubble up different kinds of errors, or just log and return
             Err(_) => unimplemented!(),
             Ok(m) => m,
         };
 
-        self.logger.debug(format!(
-            "Relaying {}-byte LiveCoresPackage Dispatcher inside a TransmitDataMsg",
-            transmit_msg.data.len()
-        ));
+        self.logger.debug_with(
+            LogFields::new()
+                .bytes(transmit_msg.data.len())
+                .field("endpoint", format!("{:?}", transmit_msg.endpoint)),
+            format!(
+                "Relaying {}-byte LiveCoresPackage Dispatcher inside a TransmitDataMsg",
+                transmit_msg.data.len()
+            ),
+        );
+        if let Some(failure_return) = failure_return_opt {
+            self.failure_returns
+                .insert(Self::digest(&transmit_msg.data[..]), failure_return);
+        }
         self.routing_service_subs
             .to_dispatcher
             .try_send(transmit_msg)
@@ -304,6 +440,44 @@ impl RoutingService {
         })
     }
 
+    // A Payer that isn't reusable can pay only once; after that, its package must be a replay.
+    // Nonces are chosen by each wallet, so two wallets may well use the same one.
+    fn spend_nonce(&mut self, payer: &Payer) -> bool {
+        if payer.reusable {
+            return true;
+        }
+        let key = (payer.wallet.address.clone(), payer.nonce);
+        if self.spent_nonces.contains_key(&key) {
+            self.logger.warning(format!(
+                "Refusing CORES package whose single-use nonce from wallet {} was already spent",
+                payer.wallet.address
+            ));
+            return false;
+        }
+        self.spent_nonces.insert(key, ());
+        true
+    }
+
+    fn digest(data: &[u8]) -> PackageDigest {
+        let mut hash = sha1::Sha1::new();
+        hash.update(data);
+        hash.digest().bytes()
+    }
+
+    // Gossip is versioned and idempotent, so only traffic that would cost somebody money is checked.
+    fn is_replay(&mut self, digest: PackageDigest, component: Component) -> bool {
+        if component == Component::Neighborhood {
+            return false;
+        }
+        let now = Instant::now();
+        let is_replay = match self.seen_packages.get_mut(&digest) {
+            Some(seen_at) => now.duration_since(*seen_at) < REPLAY_DETECTION_TTL,
+            None => false,
+        };
+        self.seen_packages.insert(digest, now);
+        is_replay
+    }
+
     fn should_route_data(&self, peer_addr: SocketAddr, component: Component) -> bool {
         if component == Component::Neighborhood {
             true
@@ -346,12 +520,14 @@ impl RoutingService {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::neighborhood::gossip::{Gossip, GossipBuilder};
+    use crate::neighborhood::gossip::{Gossip, GossipBuilder, GossipNodeRecord};
     use crate::sub_lib::accountant::ReportRoutingServiceProvidedMessage;
     use crate::sub_lib::cryptde::{encodex, PublicKey};
     use crate::sub_lib::cryptde_null::CryptDENull;
-    use crate::sub_lib::hopper::IncipientCoresPackage;
     use crate::sub_lib::hopper::MessageType;
+    use crate::sub_lib::hopper::RouteFailure;
+    use crate::sub_lib::neighborhood::Heartbeat;
+    use crate::sub_lib::neighborhood::RelayRequest;
     use crate::sub_lib::proxy_client::{ClientResponsePayload, DnsResolveFailure};
     use crate::sub_lib::proxy_server::ClientRequestPayload;
     use crate::sub_lib::route::Route;
@@ -361,14 +537,14 @@ mod tests {
     use crate::test_utils::logging::TestLogHandler;
     use crate::test_utils::recorder::make_recorder;
     use crate::test_utils::recorder::peer_actors_builder;
-    use crate::test_utils::test_utils::make_meaningless_message_type;
     use crate::test_utils::test_utils::route_to_proxy_server;
     use crate::test_utils::test_utils::{cryptde, make_request_payload};
+    use crate::test_utils::test_utils::{make_meaningless_message_type, make_paying_wallet};
     use crate::test_utils::test_utils::{make_meaningless_stream_key, route_to_proxy_client};
     use crate::test_utils::test_utils::{make_response_payload, rate_pack_routing};
     use crate::test_utils::test_utils::{rate_pack_routing_byte, route_from_proxy_client};
     use actix::System;
-    use std::net::SocketAddr;
+    use std::net::{IpAddr, SocketAddr};
     use std::str::FromStr;
 
     #[test]
@@ -400,7 +576,7 @@ mod tests {
 
         let system = System::new("dns_resolution_failures_are_reported_to_the_proxy_server");
         let peer_actors = peer_actors_builder().proxy_server(proxy_server).build();
-        let subject = RoutingService::new(
+        let mut subject = RoutingService::new(
             cryptde,
             false,
             RoutingServiceSubs {
@@ -426,6 +602,70 @@ mod tests {
         assert_eq!(dns_resolve_failure, message.payload);
     }
 
+    #[test]
+    fn route_failures_are_reported_to_the_proxy_server() {
+        let cryptde = cryptde();
+        let route = route_to_proxy_server(&cryptde.public_key(), cryptde);
+        let route_failure = RouteFailure {
+            reporting_key: PublicKey::new(b"relay"),
+            failed_key: PublicKey::new(b"exit"),
+            reason: String::new(),
+        };
+        let mut lcp = LiveCoresPackage::new(
+            route,
+            encodex(
+                cryptde,
+                &cryptde.public_key(),
+                &MessageType::RouteFailed(route_failure.clone()),
+            )
+            .unwrap(),
+        );
+        lcp.failure_reason_opt = Some("connection refused".to_string());
+        let data_enc = encodex(cryptde, &cryptde.public_key(), &lcp).unwrap();
+        let inbound_client_data = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            reception_port: None,
+            sequence_number: None,
+            last_data: false,
+            is_clandestine: false,
+            data: data_enc.into(),
+        };
+        let (proxy_server, proxy_server_awaiter, proxy_server_recording) = make_recorder();
+
+        let system = System::new("route_failures_are_reported_to_the_proxy_server");
+        let peer_actors = peer_actors_builder().proxy_server(proxy_server).build();
+        let mut subject = RoutingService::new(
+            cryptde,
+            false,
+            RoutingServiceSubs {
+                proxy_client_subs: peer_actors.proxy_client,
+                proxy_server_subs: peer_actors.proxy_server,
+                neighborhood_subs: peer_actors.neighborhood,
+                hopper_subs: peer_actors.hopper,
+                to_dispatcher: peer_actors.dispatcher.from_dispatcher_client,
+                to_accountant_routing: peer_actors.accountant.report_routing_service_provided,
+            },
+            100,
+            200,
+        );
+
+        subject.route(inbound_client_data);
+
+        System::current().stop();
+        system.run();
+
+        proxy_server_awaiter.await_message_count(1);
+        let recordings = proxy_server_recording.lock().unwrap();
+        let message = recordings.get_record::<ExpiredCoresPackage<RouteFailure>>(0);
+        assert_eq!(
+            RouteFailure {
+                reason: "connection refused".to_string(),
+                ..route_failure
+            },
+            message.payload
+        );
+    }
+
     #[test]
     fn logs_and_ignores_message_that_cannot_be_decoded() {
         init_test_logging();
@@ -445,7 +685,7 @@ mod tests {
             data: data_enc.into(),
         };
         let peer_actors = peer_actors_builder().build();
-        let subject = RoutingService::new(
+        let mut subject = RoutingService::new(
             cryptde,
             false,
             RoutingServiceSubs {
@@ -491,7 +731,7 @@ mod tests {
             data: data_enc.into(),
         };
         let peer_actors = peer_actors_builder().build();
-        let subject = RoutingService::new(
+        let mut subject = RoutingService::new(
             cryptde,
             false,
             RoutingServiceSubs {
@@ -535,7 +775,7 @@ mod tests {
 
         let system = System::new("converts_live_message_to_expired_for_proxy_client");
         let peer_actors = peer_actors_builder().proxy_client(component).build();
-        let subject = RoutingService::new(
+        let mut subject = RoutingService::new(
             cryptde,
             false,
             RoutingServiceSubs {
@@ -594,7 +834,7 @@ mod tests {
 
         let system = System::new("converts_live_message_to_expired_for_proxy_server");
         let peer_actors = peer_actors_builder().proxy_server(component).build();
-        let subject = RoutingService::new(
+        let mut subject = RoutingService::new(
             cryptde,
             false,
             RoutingServiceSubs {
@@ -662,7 +902,7 @@ mod tests {
 
         let system = System::new("converts_live_message_to_expired_for_neighborhood");
         let peer_actors = peer_actors_builder().neighborhood(component).build();
-        let subject = RoutingService::new(
+        let mut subject = RoutingService::new(
             cryptde,
             false,
             RoutingServiceSubs {
@@ -696,10 +936,141 @@ mod tests {
         assert_eq!(record.payload_len, expected_ecp.payload_len);
     }
 
+    #[test]
+    fn heartbeats_are_forwarded_to_the_neighborhood() {
+        let cryptde = cryptde();
+        let (neighborhood, _, neighborhood_recording_arc) = make_recorder();
+        let mut route = Route::one_way(
+            RouteSegment::new(
+                vec![&cryptde.public_key(), &cryptde.public_key()],
+                Component::Neighborhood,
+            ),
+            cryptde,
+            None,
+        )
+        .unwrap();
+        route.shift(cryptde).unwrap();
+        let lcp = LiveCoresPackage::new(
+            route,
+            encodex::<MessageType>(cryptde, &cryptde.public_key(), &Heartbeat::Ping(42).into())
+                .unwrap(),
+        );
+        let data_enc = encodex(cryptde, &cryptde.public_key(), &lcp).unwrap();
+        let inbound_client_data = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.3.2.4:5678").unwrap(),
+            reception_port: None,
+            last_data: false,
+            is_clandestine: true,
+            sequence_number: None,
+            data: data_enc.into(),
+        };
+
+        let system = System::new("heartbeats_are_forwarded_to_the_neighborhood");
+        let peer_actors = peer_actors_builder().neighborhood(neighborhood).build();
+        let mut subject = RoutingService::new(
+            cryptde,
+            false,
+            RoutingServiceSubs {
+                proxy_client_subs: peer_actors.proxy_client,
+                proxy_server_subs: peer_actors.proxy_server,
+                neighborhood_subs: peer_actors.neighborhood,
+                hopper_subs: peer_actors.hopper,
+                to_dispatcher: peer_actors.dispatcher.from_dispatcher_client,
+                to_accountant_routing: peer_actors.accountant.report_routing_service_provided,
+            },
+            0,
+            0,
+        );
+
+        subject.route(inbound_client_data);
+
+        System::current().stop();
+        system.run();
+        let neighborhood_recording = neighborhood_recording_arc.lock().unwrap();
+        let record = neighborhood_recording.get_record::<ExpiredCoresPackage<Heartbeat>>(0);
+        assert_eq!(
+            record.immediate_neighbor_ip,
+            IpAddr::from_str("1.3.2.4").unwrap()
+        );
+        assert_eq!(record.payload, Heartbeat::Ping(42));
+    }
+
+    #[test]
+    fn relay_requests_are_forwarded_to_the_neighborhood_with_the_client_address() {
+        let cryptde = cryptde();
+        let (neighborhood, _, neighborhood_recording_arc) = make_recorder();
+        let mut route = Route::one_way(
+            RouteSegment::new(
+                vec![&cryptde.public_key(), &cryptde.public_key()],
+                Component::Neighborhood,
+            ),
+            cryptde,
+            None,
+        )
+        .unwrap();
+        route.shift(cryptde).unwrap();
+        let relay_request = RelayRequest::Request {
+            node_record: GossipNodeRecord {
+                signed_data: PlainData::new(&[1, 2, 3]),
+                signature: CryptData::new(&[4, 5, 6]),
+                node_addr_opt: None,
+            },
+        };
+        let lcp = LiveCoresPackage::new(
+            route,
+            encodex::<MessageType>(
+                cryptde,
+                &cryptde.public_key(),
+                &relay_request.clone().into(),
+            )
+            .unwrap(),
+        );
+        let data_enc = encodex(cryptde, &cryptde.public_key(), &lcp).unwrap();
+        let inbound_client_data = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.3.2.4:5678").unwrap(),
+            reception_port: None,
+            last_data: false,
+            is_clandestine: true,
+            sequence_number: None,
+            data: data_enc.into(),
+        };
+
+        let system =
+            System::new("relay_requests_are_forwarded_to_the_neighborhood_with_the_client_address");
+        let peer_actors = peer_actors_builder().neighborhood(neighborhood).build();
+        let mut subject = RoutingService::new(
+            cryptde,
+            false,
+            RoutingServiceSubs {
+                proxy_client_subs: peer_actors.proxy_client,
+                proxy_server_subs: peer_actors.proxy_server,
+                neighborhood_subs: peer_actors.neighborhood,
+                hopper_subs: peer_actors.hopper,
+                to_dispatcher: peer_actors.dispatcher.from_dispatcher_client,
+                to_accountant_routing: peer_actors.accountant.report_routing_service_provided,
+            },
+            0,
+            0,
+        );
+
+        subject.route(inbound_client_data);
+
+        System::current().stop();
+        system.run();
+        let neighborhood_recording = neighborhood_recording_arc.lock().unwrap();
+        assert_eq!(
+            neighborhood_recording.get_record::<RelayRequestMessage>(0),
+            &RelayRequestMessage {
+                relay_request,
+                client_addr: SocketAddr::from_str("1.3.2.4:5678").unwrap(),
+            }
+        );
+    }
+
     #[test]
     fn passes_on_inbound_client_data_not_meant_for_this_node() {
         let cryptde = cryptde();
-        let consuming_wallet = Wallet::new("wallet");
+        let consuming_wallet = make_paying_wallet(b"wallet");
         let (dispatcher, _, dispatcher_recording_arc) = make_recorder();
         let (accountant, _, accountant_recording_arc) = make_recorder();
         let next_key = PublicKey::new(&[65, 65, 65]);
@@ -731,7 +1102,7 @@ mod tests {
             .dispatcher(dispatcher)
             .accountant(accountant)
             .build();
-        let subject = RoutingService::new(
+        let mut subject = RoutingService::new(
             cryptde,
             false,
             RoutingServiceSubs {
@@ -781,7 +1152,7 @@ mod tests {
     #[test]
     fn reprocesses_inbound_client_data_meant_for_this_node_and_destined_for_hopper() {
         let cryptde = cryptde();
-        let consuming_wallet = Wallet::new("wallet");
+        let consuming_wallet = make_paying_wallet(b"wallet");
         let (hopper, _, hopper_recording_arc) = make_recorder();
         let route = Route::one_way(
             RouteSegment::new(
@@ -812,7 +1183,7 @@ mod tests {
             "reprocesses_inbound_client_data_meant_for_this_node_and_destined_for_hopper",
         );
         let peer_actors = peer_actors_builder().hopper(hopper).build();
-        let subject = RoutingService::new(
+        let mut subject = RoutingService::new(
             cryptde,
             false,
             RoutingServiceSubs {
@@ -872,7 +1243,7 @@ mod tests {
         };
         let system = System::new("refuses_data_for_proxy_client_if_is_bootstrap_node");
         let peer_actors = peer_actors_builder().proxy_client(component).build();
-        let subject = RoutingService::new(
+        let mut subject = RoutingService::new(
             cryptde,
             true,
             RoutingServiceSubs {
@@ -921,7 +1292,7 @@ mod tests {
         };
         let system = System::new("refuses_data_for_proxy_server_if_is_bootstrap_node");
         let peer_actors = peer_actors_builder().proxy_server(component).build();
-        let subject = RoutingService::new(
+        let mut subject = RoutingService::new(
             cryptde,
             true,
             RoutingServiceSubs {
@@ -952,7 +1323,7 @@ mod tests {
         init_test_logging();
         let cryptde = cryptde();
         let (component, _, component_recording_arc) = make_recorder();
-        let consuming_wallet = Wallet::new("wallet");
+        let consuming_wallet = make_paying_wallet(b"wallet");
         let route = Route::one_way(
             RouteSegment::new(
                 vec![&cryptde.public_key(), &cryptde.public_key()],
@@ -979,7 +1350,7 @@ mod tests {
         };
         let system = System::new("refuses_data_for_hopper_if_is_bootstrap_node");
         let peer_actors = peer_actors_builder().hopper(component).build();
-        let subject = RoutingService::new(
+        let mut subject = RoutingService::new(
             cryptde,
             true,
             RoutingServiceSubs {
@@ -1037,7 +1408,7 @@ mod tests {
         };
         let system = System::new("accepts_data_for_neighborhood_if_is_bootstrap_node");
         let peer_actors = peer_actors_builder().neighborhood(neighborhood).build();
-        let subject = RoutingService::new(
+        let mut subject = RoutingService::new(
             cryptde,
             true,
             RoutingServiceSubs {
@@ -1111,7 +1482,7 @@ mod tests {
             .neighborhood(neighborhood)
             .dispatcher(dispatcher)
             .build();
-        let subject = RoutingService::new(
+        let mut subject = RoutingService::new(
             cryptde,
             false,
             RoutingServiceSubs {
@@ -1140,28 +1511,100 @@ mod tests {
     }
 
     #[test]
-    fn route_logs_and_ignores_inbound_client_data_that_doesnt_deserialize_properly() {
+    fn route_logs_and_ignores_cores_package_whose_payer_cannot_prove_wallet_ownership() {
         init_test_logging();
+        let cryptde = cryptde();
+        let destination_key = PublicKey::new(&[3, 4]);
+        let mut forged_hop = LiveHop::new(&destination_key, None, Component::Hopper);
+        forged_hop
+            .sign_payer(
+                &make_paying_wallet(b"impostor"),
+                &cryptde.public_key(),
+                0,
+                false,
+                None,
+            )
+            .unwrap();
+        forged_hop.payer.as_mut().unwrap().wallet = Wallet::new("victim");
+        let route = Route {
+            hops: vec![
+                forged_hop.encode(&cryptde.public_key(), cryptde).unwrap(),
+                LiveHop::new(&PublicKey::new(b""), None, Component::ProxyClient)
+                    .encode(&destination_key, cryptde)
+                    .unwrap(),
+            ],
+        };
+        let lcp = LiveCoresPackage::new(
+            route,
+            encodex(cryptde, &destination_key, &make_meaningless_message_type()).unwrap(),
+        );
+        let data_enc = encodex(cryptde, &cryptde.public_key(), &lcp).unwrap();
         let inbound_client_data = InboundClientData {
             peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
             reception_port: None,
             last_data: true,
             is_clandestine: true,
             sequence_number: None,
-            data: vec![],
+            data: data_enc.into(),
         };
-        let system = System::new("consume_logs_error_when_given_bad_input_data");
-        let (proxy_client, _, proxy_client_recording_arc) = make_recorder();
-        let (proxy_server, _, proxy_server_recording_arc) = make_recorder();
-        let (neighborhood, _, neighborhood_recording_arc) = make_recorder();
+        let system = System::new(
+            "route_logs_and_ignores_cores_package_whose_payer_cannot_prove_wallet_ownership",
+        );
+        let (accountant, _, accountant_recording_arc) = make_recorder();
         let (dispatcher, _, dispatcher_recording_arc) = make_recorder();
         let peer_actors = peer_actors_builder()
-            .proxy_client(proxy_client)
-            .proxy_server(proxy_server)
+            .accountant(accountant)
+            .dispatcher(dispatcher)
+            .build();
+        let mut subject = RoutingService::new(
+            cryptde,
+            false,
+            RoutingServiceSubs {
+                proxy_client_subs: peer_actors.proxy_client,
+                proxy_server_subs: peer_actors.proxy_server,
+                neighborhood_subs: peer_actors.neighborhood,
+                hopper_subs: peer_actors.hopper,
+                to_dispatcher: peer_actors.dispatcher.from_dispatcher_client,
+                to_accountant_routing: peer_actors.accountant.report_routing_service_provided,
+            },
+            100,
+            200,
+        );
+
+        subject.route(inbound_client_data);
+
+        System::current().stop_with_code(0);
+        system.run();
+        TestLogHandler::new().exists_log_matching(
+            "ERROR: RoutingService: Refusing to route CORES package with \\d+-byte payload without proof of victim wallet ownership",
+        );
+        assert_eq!(accountant_recording_arc.lock().unwrap().len(), 0);
+        assert_eq!(dispatcher_recording_arc.lock().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn route_logs_and_ignores_inbound_client_data_that_doesnt_deserialize_properly() {
+        init_test_logging();
+        let inbound_client_data = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            reception_port: None,
+            last_data: true,
+            is_clandestine: true,
+            sequence_number: None,
+            data: vec![],
+        };
+        let system = System::new("consume_logs_error_when_given_bad_input_data");
+        let (proxy_client, _, proxy_client_recording_arc) = make_recorder();
+        let (proxy_server, _, proxy_server_recording_arc) = make_recorder();
+        let (neighborhood, _, neighborhood_recording_arc) = make_recorder();
+        let (dispatcher, _, dispatcher_recording_arc) = make_recorder();
+        let peer_actors = peer_actors_builder()
+            .proxy_client(proxy_client)
+            .proxy_server(proxy_server)
             .neighborhood(neighborhood)
             .dispatcher(dispatcher)
             .build();
-        let subject = RoutingService::new(
+        let mut subject = RoutingService::new(
             cryptde(),
             false,
             RoutingServiceSubs {
@@ -1215,7 +1658,7 @@ mod tests {
             .neighborhood(neighborhood)
             .dispatcher(dispatcher)
             .build();
-        let subject = RoutingService::new(
+        let mut subject = RoutingService::new(
             cryptde,
             false,
             RoutingServiceSubs {
@@ -1242,4 +1685,411 @@ mod tests {
         assert_eq!(neighborhood_recording_arc.lock().unwrap().len(), 0);
         assert_eq!(dispatcher_recording_arc.lock().unwrap().len(), 0);
     }
+
+    #[test]
+    fn route_logs_and_drops_replayed_live_cores_package() {
+        init_test_logging();
+        let cryptde = cryptde();
+        let route = route_to_proxy_client(&cryptde.public_key(), cryptde);
+        let payload = make_request_payload(0, cryptde);
+        let lcp = LiveCoresPackage::new(
+            route,
+            encodex::<MessageType>(cryptde, &cryptde.public_key(), &payload.clone().into())
+                .unwrap(),
+        );
+        let data_enc = encodex(cryptde, &cryptde.public_key(), &lcp).unwrap();
+        let inbound_client_data = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            reception_port: None,
+            last_data: false,
+            is_clandestine: true,
+            sequence_number: None,
+            data: data_enc.into(),
+        };
+        let data_len = inbound_client_data.data.len();
+        let system = System::new("route_logs_and_drops_replayed_live_cores_package");
+        let (proxy_client, _, proxy_client_recording_arc) = make_recorder();
+        let peer_actors = peer_actors_builder().proxy_client(proxy_client).build();
+        let mut subject = RoutingService::new(
+            cryptde,
+            false,
+            RoutingServiceSubs {
+                proxy_client_subs: peer_actors.proxy_client,
+                proxy_server_subs: peer_actors.proxy_server,
+                neighborhood_subs: peer_actors.neighborhood,
+                hopper_subs: peer_actors.hopper,
+                to_dispatcher: peer_actors.dispatcher.from_dispatcher_client,
+                to_accountant_routing: peer_actors.accountant.report_routing_service_provided,
+            },
+            100,
+            200,
+        );
+
+        subject.route(inbound_client_data.clone());
+        subject.route(inbound_client_data);
+
+        System::current().stop_with_code(0);
+        system.run();
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: RoutingService: Dropping replayed {}-byte CORES package from 1.2.3.4:5678 destined for ProxyClient",
+            data_len
+        ));
+        assert_eq!(proxy_client_recording_arc.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn route_does_not_consider_repeated_gossip_a_replay() {
+        let cryptde = cryptde();
+        let mut route = Route::one_way(
+            RouteSegment::new(
+                vec![&cryptde.public_key(), &cryptde.public_key()],
+                Component::Neighborhood,
+            ),
+            cryptde,
+            None,
+        )
+        .unwrap();
+        route.shift(cryptde).unwrap();
+        let lcp = LiveCoresPackage::new(
+            route,
+            encodex::<MessageType>(
+                cryptde,
+                &cryptde.public_key(),
+                &GossipBuilder::empty().into(),
+            )
+            .unwrap(),
+        );
+        let data_enc = encodex(cryptde, &cryptde.public_key(), &lcp).unwrap();
+        let inbound_client_data = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.3.2.4:5678").unwrap(),
+            reception_port: None,
+            last_data: false,
+            is_clandestine: true,
+            sequence_number: None,
+            data: data_enc.into(),
+        };
+        let system = System::new("route_does_not_consider_repeated_gossip_a_replay");
+        let (neighborhood, _, neighborhood_recording_arc) = make_recorder();
+        let peer_actors = peer_actors_builder().neighborhood(neighborhood).build();
+        let mut subject = RoutingService::new(
+            cryptde,
+            false,
+            RoutingServiceSubs {
+                proxy_client_subs: peer_actors.proxy_client,
+                proxy_server_subs: peer_actors.proxy_server,
+                neighborhood_subs: peer_actors.neighborhood,
+                hopper_subs: peer_actors.hopper,
+                to_dispatcher: peer_actors.dispatcher.from_dispatcher_client,
+                to_accountant_routing: peer_actors.accountant.report_routing_service_provided,
+            },
+            0,
+            0,
+        );
+
+        subject.route(inbound_client_data.clone());
+        subject.route(inbound_client_data);
+
+        System::current().stop_with_code(0);
+        system.run();
+        assert_eq!(neighborhood_recording_arc.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn replay_detection_forgets_least_recently_seen_packages_when_full() {
+        let system = System::new("replay_detection_forgets_least_recently_seen_packages_when_full");
+        let peer_actors = peer_actors_builder().build();
+        let mut subject = RoutingService::new(
+            cryptde(),
+            false,
+            RoutingServiceSubs {
+                proxy_client_subs: peer_actors.proxy_client,
+                proxy_server_subs: peer_actors.proxy_server,
+                neighborhood_subs: peer_actors.neighborhood,
+                hopper_subs: peer_actors.hopper,
+                to_dispatcher: peer_actors.dispatcher.from_dispatcher_client,
+                to_accountant_routing: peer_actors.accountant.report_routing_service_provided,
+            },
+            0,
+            0,
+        );
+        subject.seen_packages = LruCache::new(2);
+        let first = RoutingService::digest(b"first");
+        let second = RoutingService::digest(b"second");
+        let third = RoutingService::digest(b"third");
+
+        assert_eq!(subject.is_replay(first, Component::ProxyClient), false);
+        assert_eq!(subject.is_replay(second, Component::ProxyClient), false);
+        assert_eq!(subject.is_replay(first, Component::ProxyClient), true);
+        assert_eq!(subject.is_replay(third, Component::ProxyClient), false);
+
+        assert_eq!(subject.seen_packages.len(), 2);
+        assert_eq!(subject.is_replay(first, Component::ProxyClient), true);
+        assert_eq!(subject.is_replay(second, Component::ProxyClient), false);
+        System::current().stop_with_code(0);
+        system.run();
+    }
+
+    fn make_payer(wallet_seed: &[u8], nonce: u64, reusable: bool) -> Payer {
+        let mut hop = LiveHop::new(&PublicKey::new(&[1, 2]), None, Component::Hopper);
+        hop.sign_payer(
+            &make_paying_wallet(wallet_seed),
+            &PublicKey::new(&[3, 4]),
+            nonce,
+            reusable,
+            None,
+        )
+        .unwrap();
+        hop.payer.unwrap()
+    }
+
+    #[test]
+    fn single_use_nonces_can_be_spent_only_once_per_wallet() {
+        init_test_logging();
+        let system = System::new("single_use_nonces_can_be_spent_only_once_per_wallet");
+        let peer_actors = peer_actors_builder().build();
+        let mut subject = RoutingService::new(
+            cryptde(),
+            false,
+            RoutingServiceSubs {
+                proxy_client_subs: peer_actors.proxy_client,
+                proxy_server_subs: peer_actors.proxy_server,
+                neighborhood_subs: peer_actors.neighborhood,
+                hopper_subs: peer_actors.hopper,
+                to_dispatcher: peer_actors.dispatcher.from_dispatcher_client,
+                to_accountant_routing: peer_actors.accountant.report_routing_service_provided,
+            },
+            0,
+            0,
+        );
+
+        assert_eq!(
+            subject.spend_nonce(&make_payer(b"wallet", 1234, false)),
+            true
+        );
+        assert_eq!(
+            subject.spend_nonce(&make_payer(b"wallet", 5678, false)),
+            true
+        );
+        assert_eq!(
+            subject.spend_nonce(&make_payer(b"wallet", 1234, false)),
+            false
+        );
+        assert_eq!(
+            subject.spend_nonce(&make_payer(b"wallet", 4321, true)),
+            true
+        );
+        assert_eq!(
+            subject.spend_nonce(&make_payer(b"wallet", 4321, true)),
+            true
+        );
+        assert_eq!(
+            subject.spend_nonce(&make_payer(b"other wallet", 1234, false)),
+            true
+        );
+
+        assert_eq!(subject.spent_nonces.len(), 3);
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: RoutingService: Refusing CORES package whose single-use nonce from wallet {} was already spent",
+            make_paying_wallet(b"wallet").address
+        ));
+        System::current().stop_with_code(0);
+        system.run();
+    }
+
+    #[test]
+    fn spent_nonces_are_forgotten_least_recent_first_when_full() {
+        let system = System::new("spent_nonces_are_forgotten_least_recent_first_when_full");
+        let peer_actors = peer_actors_builder().build();
+        let mut subject = RoutingService::new(
+            cryptde(),
+            false,
+            RoutingServiceSubs {
+                proxy_client_subs: peer_actors.proxy_client,
+                proxy_server_subs: peer_actors.proxy_server,
+                neighborhood_subs: peer_actors.neighborhood,
+                hopper_subs: peer_actors.hopper,
+                to_dispatcher: peer_actors.dispatcher.from_dispatcher_client,
+                to_accountant_routing: peer_actors.accountant.report_routing_service_provided,
+            },
+            0,
+            0,
+        );
+        subject.spent_nonces = LruCache::new(2);
+
+        assert_eq!(subject.spend_nonce(&make_payer(b"wallet", 1, false)), true);
+        assert_eq!(subject.spend_nonce(&make_payer(b"wallet", 2, false)), true);
+        assert_eq!(subject.spend_nonce(&make_payer(b"wallet", 3, false)), true);
+
+        assert_eq!(subject.spent_nonces.len(), 2);
+        assert_eq!(subject.spend_nonce(&make_payer(b"wallet", 1, false)), true);
+        assert_eq!(subject.spend_nonce(&make_payer(b"wallet", 3, false)), false);
+        System::current().stop_with_code(0);
+        system.run();
+    }
+
+    #[test]
+    fn replay_detection_ignores_packages_seen_longer_ago_than_the_ttl() {
+        let system = System::new("replay_detection_ignores_packages_seen_longer_ago_than_the_ttl");
+        let peer_actors = peer_actors_builder().build();
+        let mut subject = RoutingService::new(
+            cryptde(),
+            false,
+            RoutingServiceSubs {
+                proxy_client_subs: peer_actors.proxy_client,
+                proxy_server_subs: peer_actors.proxy_server,
+                neighborhood_subs: peer_actors.neighborhood,
+                hopper_subs: peer_actors.hopper,
+                to_dispatcher: peer_actors.dispatcher.from_dispatcher_client,
+                to_accountant_routing: peer_actors.accountant.report_routing_service_provided,
+            },
+            0,
+            0,
+        );
+        let digest = RoutingService::digest(b"package");
+        subject.seen_packages.insert(
+            digest,
+            Instant::now() - REPLAY_DETECTION_TTL - Duration::from_secs(1),
+        );
+
+        let result = subject.is_replay(digest, Component::ProxyClient);
+
+        assert_eq!(result, false);
+        assert_eq!(subject.is_replay(digest, Component::ProxyClient), true);
+        System::current().stop_with_code(0);
+        system.run();
+    }
+
+    #[test]
+    fn report_undeliverable_launches_failure_return_for_package_relayed_to_unreachable_node() {
+        init_test_logging();
+        let cryptde = cryptde();
+        let consuming_wallet = make_paying_wallet(b"wallet");
+        let originator_key = PublicKey::new(b"originator");
+        let originator_cryptde = CryptDENull::from(&originator_key);
+        let next_key = PublicKey::new(b"next");
+        let mut route = Route::round_trip(
+            RouteSegment::new(
+                vec![&originator_key, &cryptde.public_key(), &next_key],
+                Component::ProxyClient,
+            ),
+            RouteSegment::new(
+                vec![&next_key, &cryptde.public_key(), &originator_key],
+                Component::ProxyServer,
+            ),
+            &originator_cryptde,
+            Some(consuming_wallet),
+            1234,
+        )
+        .unwrap();
+        route.shift(&originator_cryptde).unwrap();
+        let failure_return = route
+            .next_hop(cryptde)
+            .unwrap()
+            .failure_return
+            .unwrap()
+            .to_package(&route)
+            .unwrap();
+        let payload = PlainData::new(&b"abcd"[..]);
+        let lcp = LiveCoresPackage::new(route, cryptde.encode(&next_key, &payload).unwrap());
+        let (_, next_lcp) = lcp.clone().to_next_live(cryptde).unwrap();
+        let relayed_data: Vec<u8> = encodex(cryptde, &next_key, &next_lcp).unwrap().into();
+        let data_enc = encodex(cryptde, &cryptde.public_key(), &lcp).unwrap();
+        let inbound_client_data = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            reception_port: None,
+            last_data: false,
+            is_clandestine: true,
+            sequence_number: None,
+            data: data_enc.into(),
+        };
+        let (hopper, _, hopper_recording_arc) = make_recorder();
+        let (dispatcher, _, dispatcher_recording_arc) = make_recorder();
+        let system = System::new(
+            "report_undeliverable_launches_failure_return_for_package_relayed_to_unreachable_node",
+        );
+        let peer_actors = peer_actors_builder()
+            .hopper(hopper)
+            .dispatcher(dispatcher)
+            .build();
+        let mut subject = RoutingService::new(
+            cryptde,
+            false,
+            RoutingServiceSubs {
+                proxy_client_subs: peer_actors.proxy_client,
+                proxy_server_subs: peer_actors.proxy_server,
+                neighborhood_subs: peer_actors.neighborhood,
+                hopper_subs: peer_actors.hopper,
+                to_dispatcher: peer_actors.dispatcher.from_dispatcher_client,
+                to_accountant_routing: peer_actors.accountant.report_routing_service_provided,
+            },
+            0,
+            0,
+        );
+
+        subject.route(inbound_client_data);
+        subject.report_undeliverable(UndeliverablePackageMessage {
+            public_key: next_key.clone(),
+            data: relayed_data.clone(),
+            reason: "connection refused".to_string(),
+        });
+        subject.report_undeliverable(UndeliverablePackageMessage {
+            public_key: next_key.clone(),
+            data: relayed_data.clone(),
+            reason: "connection refused".to_string(),
+        });
+
+        System::current().stop_with_code(0);
+        system.run();
+        let dispatcher_recording = dispatcher_recording_arc.lock().unwrap();
+        assert_eq!(
+            dispatcher_recording.get_record::<TransmitDataMsg>(0).data,
+            relayed_data
+        );
+        let hopper_recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(
+            hopper_recording.get_record::<IncipientCoresPackage>(0),
+            &IncipientCoresPackage {
+                failure_reason_opt: Some("connection refused".to_string()),
+                ..failure_return
+            }
+        );
+        assert_eq!(hopper_recording.len(), 1);
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: RoutingService: Couldn't relay {}-byte CORES package to {}: connection refused; reporting route failure to originator",
+            relayed_data.len(),
+            next_key
+        ));
+    }
+
+    #[test]
+    fn report_undeliverable_ignores_data_without_failure_return() {
+        let cryptde = cryptde();
+        let (hopper, _, hopper_recording_arc) = make_recorder();
+        let system = System::new("report_undeliverable_ignores_data_without_failure_return");
+        let peer_actors = peer_actors_builder().hopper(hopper).build();
+        let mut subject = RoutingService::new(
+            cryptde,
+            false,
+            RoutingServiceSubs {
+                proxy_client_subs: peer_actors.proxy_client,
+                proxy_server_subs: peer_actors.proxy_server,
+                neighborhood_subs: peer_actors.neighborhood,
+                hopper_subs: peer_actors.hopper,
+                to_dispatcher: peer_actors.dispatcher.from_dispatcher_client,
+                to_accountant_routing: peer_actors.accountant.report_routing_service_provided,
+            },
+            0,
+            0,
+        );
+
+        subject.report_undeliverable(UndeliverablePackageMessage {
+            public_key: PublicKey::new(b"next"),
+            data: b"never relayed".to_vec(),
+            reason: "connection refused".to_string(),
+        });
+
+        System::current().stop_with_code(0);
+        system.run();
+        assert_eq!(hopper_recording_arc.lock().unwrap().len(), 0);
+    }
 }
@@ -5,7 +5,10 @@ pub mod accountant;
 mod actor_system_factory;
 mod blockchain;
 mod bootstrapper;
+pub mod config_check;
 mod config_dao;
+mod config_file;
+pub mod config_reload;
 mod configuration;
 mod crash_test_dummy;
 pub mod database;
@@ -18,6 +21,7 @@ pub mod json_discriminator_factory;
 pub mod json_framer;
 pub mod json_masquerader;
 mod listener_handler;
+mod log_file;
 pub mod masquerader;
 pub mod neighborhood;
 mod null_masquerader;
@@ -0,0 +1,685 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::logger;
+use crate::sub_lib::logger::LogFormat;
+use chrono::format::strftime::StrftimeItems;
+use chrono::NaiveDateTime;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::Level;
+use log::LevelFilter;
+use log::Log;
+use log::Metadata;
+use log::Record;
+use std::collections::BTreeSet;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+pub const LOG_FILE_BASE_NAME: &str = "SubstratumNode";
+const LOG_FILE_SUFFIX: &str = ".log";
+const COMPRESSED_SUFFIX: &str = ".gz";
+
+pub const DEFAULT_LOG_ROTATE_SIZE_MB: u64 = 100;
+pub const DEFAULT_LOG_ROTATE_HOURS: u64 = 24;
+pub const DEFAULT_LOG_RETENTION: usize = 10;
+// After a rotation fails, logging goes on in the same file for this long before it's tried again.
+const ROTATION_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+type Compressor = Box<dyn Fn(PathBuf) + Send>;
+
+/// When the log file is set aside for a fresh one, and how many set-aside files are kept.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogRotation {
+    pub max_size: u64,
+    pub max_age: Duration,
+    pub retention: usize,
+}
+
+impl Default for LogRotation {
+    fn default() -> Self {
+        LogRotation {
+            max_size: DEFAULT_LOG_ROTATE_SIZE_MB * 1024 * 1024,
+            max_age: Duration::from_secs(DEFAULT_LOG_ROTATE_HOURS * 3600),
+            retention: DEFAULT_LOG_RETENTION,
+        }
+    }
+}
+
+/// The Node's log file. Rotation copies the file to an archive and truncates it rather than
+/// renaming it, because the file may have been created before the Node dropped privileges and
+/// only the handle we already have open can still write to it. The copy is a PendingRotation,
+/// so that it can be made without holding up other writers; lines written meanwhile are kept.
+pub struct RotatingLogFile {
+    directory: PathBuf,
+    file: File,
+    written: u64,
+    started_at: SystemTime,
+    rotation: LogRotation,
+    compressor: Compressor,
+    rotating: bool,
+    retry_rotation_at: Option<SystemTime>,
+}
+
+/// A rotation that's been started: the first `length` bytes of the log file are to be copied to
+/// `archive` before RotatingLogFile::finish_rotation is called.
+pub struct PendingRotation {
+    source: PathBuf,
+    archive: PathBuf,
+    length: u64,
+    started_at: SystemTime,
+}
+
+impl PendingRotation {
+    pub fn copy(&self) -> io::Result<()> {
+        let mut source = File::open(&self.source)?.take(self.length);
+        let mut archive = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&self.archive)?;
+        io::copy(&mut source, &mut archive)?;
+        Ok(())
+    }
+}
+
+impl RotatingLogFile {
+    // A log left over from an earlier run is archived rather than overwritten. If it can't be
+    // archived, it's kept and logging goes on in it, just as after a failed rotation.
+    pub fn open(directory: &Path, rotation: LogRotation) -> io::Result<RotatingLogFile> {
+        RotatingLogFile::open_with(
+            directory,
+            rotation,
+            Box::new(compress_in_background),
+            SystemTime::now(),
+        )
+    }
+
+    fn open_with(
+        directory: &Path,
+        rotation: LogRotation,
+        compressor: Compressor,
+        now: SystemTime,
+    ) -> io::Result<RotatingLogFile> {
+        fs::create_dir_all(directory)?;
+        let path = directory.join(format!("{}{}", LOG_FILE_BASE_NAME, LOG_FILE_SUFFIX));
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let mut log_file = RotatingLogFile {
+            directory: directory.to_path_buf(),
+            written: file.metadata()?.len(),
+            file,
+            started_at: now,
+            rotation,
+            compressor,
+            rotating: false,
+            retry_rotation_at: None,
+        };
+        if log_file.written > 0 {
+            let pending = log_file.begin_rotation(now)?;
+            let result = pending.copy();
+            if let Err(e) = log_file.finish_rotation(pending, result) {
+                eprintln!("Couldn't archive the log from an earlier run: {}", e);
+            }
+        }
+        Ok(log_file)
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.directory
+            .join(format!("{}{}", LOG_FILE_BASE_NAME, LOG_FILE_SUFFIX))
+    }
+
+    // The line is written in any case; if it's time to rotate, the caller is handed the copying
+    // to do, and must then call finish_rotation.
+    pub fn write_line(&mut self, line: &str) -> io::Result<Option<PendingRotation>> {
+        let now = SystemTime::now();
+        let pending_opt = if self.rotation_due(now) {
+            Some(self.begin_rotation(now)?)
+        } else {
+            None
+        };
+        if let Err(e) = self.write_raw(line) {
+            self.rotating = false;
+            return Err(e);
+        }
+        Ok(pending_opt)
+    }
+
+    // Whatever was written while the archive was being copied is moved to the start of the file.
+    pub fn finish_rotation(
+        &mut self,
+        pending: PendingRotation,
+        copy_result: io::Result<()>,
+    ) -> io::Result<()> {
+        self.rotating = false;
+        let tail = match copy_result.and_then(|_| self.read_from(pending.length)) {
+            Ok(tail) => tail,
+            Err(e) => {
+                let _ = fs::remove_file(&pending.archive);
+                self.retry_rotation_at = Some(pending.started_at + ROTATION_RETRY_INTERVAL);
+                return Err(e);
+            }
+        };
+        self.retry_rotation_at = None;
+        self.file.set_len(0)?;
+        self.written = 0;
+        self.file.write_all(&tail)?;
+        self.written = tail.len() as u64;
+        self.started_at = pending.started_at;
+        (self.compressor)(pending.archive);
+        self.remove_old_archives()
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+
+    fn rotation_due(&self, now: SystemTime) -> bool {
+        if self.written == 0 || self.rotating {
+            return false;
+        }
+        if let Some(retry_rotation_at) = self.retry_rotation_at {
+            if now < retry_rotation_at {
+                return false;
+            }
+        }
+        let age = now
+            .duration_since(self.started_at)
+            .unwrap_or_else(|_| Duration::from_secs(0));
+        self.written >= self.rotation.max_size || age >= self.rotation.max_age
+    }
+
+    fn begin_rotation(&mut self, now: SystemTime) -> io::Result<PendingRotation> {
+        self.file.flush()?;
+        self.rotating = true;
+        Ok(PendingRotation {
+            source: self.path(),
+            archive: self.unused_archive_path(now),
+            length: self.written,
+            started_at: now,
+        })
+    }
+
+    fn write_raw(&mut self, line: &str) -> io::Result<()> {
+        self.file.write_all(line.as_bytes())?;
+        self.file.write_all(b"\n")?;
+        self.written += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    fn read_from(&mut self, position: u64) -> io::Result<Vec<u8>> {
+        self.file.flush()?;
+        let mut data = vec![];
+        let mut source = File::open(self.path())?;
+        source.seek(SeekFrom::Start(position))?;
+        source.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn unused_archive_path(&self, now: SystemTime) -> PathBuf {
+        let mut time = now;
+        loop {
+            let stem = format!("{}-{}", LOG_FILE_BASE_NAME, archive_timestamp(&time));
+            let path = self.directory.join(format!("{}{}", stem, LOG_FILE_SUFFIX));
+            let compressed = self
+                .directory
+                .join(format!("{}{}{}", stem, LOG_FILE_SUFFIX, COMPRESSED_SUFFIX));
+            if !path.exists() && !compressed.exists() {
+                return path;
+            }
+            time += Duration::from_millis(1);
+        }
+    }
+
+    // An archive may be on disk compressed, uncompressed, or (while it's being compressed) both.
+    fn remove_old_archives(&self) -> io::Result<()> {
+        let prefix = format!("{}-", LOG_FILE_BASE_NAME);
+        let mut stems = BTreeSet::new();
+        for entry in fs::read_dir(&self.directory)? {
+            let file_name = entry?.file_name().to_string_lossy().to_string();
+            if !file_name.starts_with(&prefix) {
+                continue;
+            }
+            let file_name = file_name.trim_end_matches(COMPRESSED_SUFFIX);
+            if file_name.ends_with(LOG_FILE_SUFFIX) {
+                stems.insert(file_name.trim_end_matches(LOG_FILE_SUFFIX).to_string());
+            }
+        }
+        let excess = stems.len().saturating_sub(self.rotation.retention);
+        for stem in stems.iter().take(excess) {
+            for suffix in &[
+                LOG_FILE_SUFFIX.to_string(),
+                format!("{}{}", LOG_FILE_SUFFIX, COMPRESSED_SUFFIX),
+            ] {
+                if let Err(e) = fs::remove_file(self.directory.join(format!("{}{}", stem, suffix)))
+                {
+                    if e.kind() != io::ErrorKind::NotFound {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn archive_timestamp(time: &SystemTime) -> String {
+    let since_epoch = time
+        .duration_since(UNIX_EPOCH)
+        .expect("SystemTime before UNIX EPOCH!");
+    let naive_date_time =
+        NaiveDateTime::from_timestamp(since_epoch.as_secs() as i64, since_epoch.subsec_nanos());
+    naive_date_time
+        .format_with_items(StrftimeItems::new("%Y%m%d-%H%M%S%.3f"))
+        .to_string()
+}
+
+// Compression is a convenience: an archive that can't be compressed just stays uncompressed.
+fn compress_in_background(path: PathBuf) {
+    thread::spawn(move || {
+        let _ = compress(&path);
+    });
+}
+
+fn compress(path: &Path) -> io::Result<()> {
+    let compressed = PathBuf::from(format!("{}{}", path.to_string_lossy(), COMPRESSED_SUFFIX));
+    let result = File::open(path).and_then(|mut input| {
+        let mut encoder = GzEncoder::new(File::create(&compressed)?, Compression::default());
+        io::copy(&mut input, &mut encoder)?;
+        encoder.finish()?.sync_all()
+    });
+    match result {
+        Ok(_) => fs::remove_file(path),
+        Err(e) => {
+            let _ = fs::remove_file(&compressed);
+            Err(e)
+        }
+    }
+}
+
+/// The log backend: writes lines from the Node's own Loggers (which filter themselves) and
+/// lines from other crates at or above the configured level to the log file, and copies the
+/// ones that are Info or more severe to stderr.
+pub struct NodeLog {
+    level: LevelFilter,
+    format: LogFormat,
+    file: Mutex<RotatingLogFile>,
+}
+
+impl NodeLog {
+    pub fn new(level: LevelFilter, format: LogFormat, file: RotatingLogFile) -> NodeLog {
+        NodeLog {
+            level,
+            format,
+            file: Mutex::new(file),
+        }
+    }
+}
+
+impl Log for NodeLog {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target() == logger::LOG_TARGET || metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = logger::format_record(self.format, record);
+        if record.level() <= Level::Info {
+            eprintln!("{}", line);
+        }
+        let pending_opt = match self
+            .file
+            .lock()
+            .expect("Log file is poisoned")
+            .write_line(&line)
+        {
+            Ok(pending_opt) => pending_opt,
+            Err(e) => {
+                eprintln!("Couldn't write to log file: {}", e);
+                None
+            }
+        };
+        // Other threads go on logging while the archive is copied.
+        if let Some(pending) = pending_opt {
+            let copy_result = pending.copy();
+            if let Err(e) = self
+                .file
+                .lock()
+                .expect("Log file is poisoned")
+                .finish_rotation(pending, copy_result)
+            {
+                eprintln!("Couldn't rotate log file: {}", e);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        let _ = self.file.lock().expect("Log file is poisoned").flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_utils::ensure_node_home_directory_exists;
+    use flate2::read::GzDecoder;
+    use std::sync::Arc;
+
+    fn make_subject(
+        directory: &Path,
+        rotation: LogRotation,
+        compressed: &Arc<Mutex<Vec<PathBuf>>>,
+    ) -> RotatingLogFile {
+        let compressed = compressed.clone();
+        RotatingLogFile::open_with(
+            directory,
+            rotation,
+            Box::new(move |path| compressed.lock().unwrap().push(path)),
+            SystemTime::now(),
+        )
+        .unwrap()
+    }
+
+    fn write(subject: &mut RotatingLogFile, line: &str) {
+        if let Some(pending) = subject.write_line(line).unwrap() {
+            let copy_result = pending.copy();
+            subject.finish_rotation(pending, copy_result).unwrap();
+        }
+    }
+
+    fn archives(directory: &Path) -> Vec<String> {
+        let mut names = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|name| name.starts_with("SubstratumNode-"))
+            .collect::<Vec<String>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn log_file_rotates_when_it_gets_too_big_and_hands_the_archive_to_the_compressor() {
+        let directory = ensure_node_home_directory_exists(
+            "log_file",
+            "log_file_rotates_when_it_gets_too_big_and_hands_the_archive_to_the_compressor",
+        );
+        let compressed = Arc::new(Mutex::new(vec![]));
+        let mut subject = make_subject(
+            &directory,
+            LogRotation {
+                max_size: 10,
+                max_age: Duration::from_secs(3600),
+                retention: 5,
+            },
+            &compressed,
+        );
+
+        write(&mut subject, "first line");
+        write(&mut subject, "second line");
+
+        assert_eq!(
+            fs::read_to_string(subject.path()).unwrap(),
+            "second line\n".to_string()
+        );
+        let compressed = compressed.lock().unwrap();
+        assert_eq!(compressed.len(), 1);
+        assert_eq!(
+            fs::read_to_string(&compressed[0]).unwrap(),
+            "first line\n".to_string()
+        );
+        assert_eq!(archives(&directory).len(), 1);
+    }
+
+    #[test]
+    fn log_file_rotates_when_it_gets_too_old() {
+        let directory =
+            ensure_node_home_directory_exists("log_file", "log_file_rotates_when_it_gets_too_old");
+        let compressed = Arc::new(Mutex::new(vec![]));
+        let mut subject = make_subject(
+            &directory,
+            LogRotation {
+                max_size: 1_000_000,
+                max_age: Duration::from_secs(3600),
+                retention: 5,
+            },
+            &compressed,
+        );
+        write(&mut subject, "old line");
+        let young = subject.rotation_due(SystemTime::now());
+        subject.started_at -= Duration::from_secs(3601);
+
+        write(&mut subject, "new line");
+
+        assert_eq!(young, false);
+        assert_eq!(
+            fs::read_to_string(subject.path()).unwrap(),
+            "new line\n".to_string()
+        );
+        assert_eq!(compressed.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn lines_written_while_the_archive_is_being_copied_stay_in_the_log() {
+        let directory = ensure_node_home_directory_exists(
+            "log_file",
+            "lines_written_while_the_archive_is_being_copied_stay_in_the_log",
+        );
+        let compressed = Arc::new(Mutex::new(vec![]));
+        let mut subject = make_subject(
+            &directory,
+            LogRotation {
+                max_size: 10,
+                max_age: Duration::from_secs(3600),
+                retention: 5,
+            },
+            &compressed,
+        );
+        write(&mut subject, "first line");
+        let pending = subject.write_line("second line").unwrap().unwrap();
+
+        let meanwhile = subject.write_line("third line").unwrap();
+        let copy_result = pending.copy();
+        subject.finish_rotation(pending, copy_result).unwrap();
+
+        assert_eq!(meanwhile.is_none(), true);
+        assert_eq!(
+            fs::read_to_string(subject.path()).unwrap(),
+            "second line\nthird line\n".to_string()
+        );
+        assert_eq!(subject.written, 23);
+        let compressed = compressed.lock().unwrap();
+        assert_eq!(
+            fs::read_to_string(&compressed[0]).unwrap(),
+            "first line\n".to_string()
+        );
+    }
+
+    #[test]
+    fn failed_rotation_keeps_the_line_and_waits_before_trying_again() {
+        let directory = ensure_node_home_directory_exists(
+            "log_file",
+            "failed_rotation_keeps_the_line_and_waits_before_trying_again",
+        );
+        let compressed = Arc::new(Mutex::new(vec![]));
+        let mut subject = make_subject(
+            &directory,
+            LogRotation {
+                max_size: 10,
+                max_age: Duration::from_secs(3600),
+                retention: 5,
+            },
+            &compressed,
+        );
+        write(&mut subject, "first line");
+        // Nothing is left to copy the archive from.
+        fs::remove_file(subject.path()).unwrap();
+        let pending = subject.write_line("second line").unwrap().unwrap();
+        let started_at = pending.started_at;
+        let copy_result = pending.copy();
+
+        let result = subject.finish_rotation(pending, copy_result);
+
+        assert_eq!(result.is_err(), true);
+        assert_eq!(subject.written, 23);
+        assert_eq!(subject.rotation_due(started_at), false);
+        assert_eq!(
+            subject.rotation_due(started_at + ROTATION_RETRY_INTERVAL),
+            true
+        );
+        assert_eq!(archives(&directory), Vec::<String>::new());
+        assert_eq!(compressed.lock().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn compression_replaces_the_archive_with_a_gzipped_copy() {
+        let directory = ensure_node_home_directory_exists(
+            "log_file",
+            "compression_replaces_the_archive_with_a_gzipped_copy",
+        );
+        let archive = directory.join("SubstratumNode-20190101-000000.000.log");
+        fs::write(&archive, "archived line\n").unwrap();
+
+        compress(&archive).unwrap();
+
+        assert_eq!(archive.exists(), false);
+        let mut decompressed = String::new();
+        GzDecoder::new(
+            File::open(directory.join("SubstratumNode-20190101-000000.000.log.gz")).unwrap(),
+        )
+        .read_to_string(&mut decompressed)
+        .unwrap();
+        assert_eq!(decompressed, "archived line\n".to_string());
+    }
+
+    #[test]
+    fn opening_archives_the_log_from_an_earlier_run() {
+        let directory = ensure_node_home_directory_exists(
+            "log_file",
+            "opening_archives_the_log_from_an_earlier_run",
+        );
+        fs::write(directory.join("SubstratumNode.log"), "earlier run\n").unwrap();
+        let compressed = Arc::new(Mutex::new(vec![]));
+
+        let subject = make_subject(&directory, LogRotation::default(), &compressed);
+
+        assert_eq!(fs::read_to_string(subject.path()).unwrap(), String::new());
+        let compressed = compressed.lock().unwrap();
+        assert_eq!(
+            fs::read_to_string(&compressed[0]).unwrap(),
+            "earlier run\n".to_string()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn opening_keeps_the_log_from_an_earlier_run_if_it_cannot_be_archived() {
+        let directory = ensure_node_home_directory_exists(
+            "log_file",
+            "opening_keeps_the_log_from_an_earlier_run_if_it_cannot_be_archived",
+        );
+        fs::write(directory.join("SubstratumNode.log"), "earlier run\n").unwrap();
+        let now = SystemTime::now();
+        // A dangling link where the archive would go, so that it can't be created.
+        std::os::unix::fs::symlink(
+            directory.join("nowhere"),
+            directory.join(format!("SubstratumNode-{}.log", archive_timestamp(&now))),
+        )
+        .unwrap();
+        let compressed = Arc::new(Mutex::new(vec![]));
+        let inner_compressed = compressed.clone();
+
+        let mut subject = RotatingLogFile::open_with(
+            &directory,
+            LogRotation {
+                max_size: 10,
+                max_age: Duration::from_secs(3600),
+                retention: 5,
+            },
+            Box::new(move |path| inner_compressed.lock().unwrap().push(path)),
+            now,
+        )
+        .unwrap();
+        write(&mut subject, "this run");
+
+        assert_eq!(
+            fs::read_to_string(subject.path()).unwrap(),
+            "earlier run\nthis run\n".to_string()
+        );
+        assert_eq!(subject.rotation_due(now), false);
+        assert_eq!(subject.rotation_due(now + ROTATION_RETRY_INTERVAL), true);
+        assert_eq!(compressed.lock().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn only_the_newest_archives_are_kept_whether_compressed_or_not() {
+        let directory = ensure_node_home_directory_exists(
+            "log_file",
+            "only_the_newest_archives_are_kept_whether_compressed_or_not",
+        );
+        [
+            "SubstratumNode-20190101-000000.000.log.gz",
+            "SubstratumNode-20190102-000000.000.log.gz",
+            "SubstratumNode-20190103-000000.000.log",
+            "SubstratumNode-20190103-000000.000.log.gz",
+            "unrelated.log",
+        ]
+        .iter()
+        .for_each(|name| fs::write(directory.join(name), "").unwrap());
+        let compressed = Arc::new(Mutex::new(vec![]));
+        let mut subject = make_subject(
+            &directory,
+            LogRotation {
+                max_size: 1,
+                max_age: Duration::from_secs(3600),
+                retention: 2,
+            },
+            &compressed,
+        );
+
+        write(&mut subject, "one");
+        write(&mut subject, "two");
+
+        let newest = compressed.lock().unwrap()[0]
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        assert_eq!(
+            archives(&directory),
+            vec![
+                "SubstratumNode-20190103-000000.000.log".to_string(),
+                "SubstratumNode-20190103-000000.000.log.gz".to_string(),
+                newest,
+            ]
+        );
+        assert_eq!(directory.join("unrelated.log").exists(), true);
+    }
+
+    #[test]
+    fn archive_names_are_never_reused() {
+        let directory =
+            ensure_node_home_directory_exists("log_file", "archive_names_are_never_reused");
+        let compressed = Arc::new(Mutex::new(vec![]));
+        let subject = make_subject(&directory, LogRotation::default(), &compressed);
+        let now = SystemTime::now();
+        let first = subject.unused_archive_path(now);
+        fs::write(format!("{}.gz", first.to_string_lossy()), "").unwrap();
+
+        let second = subject.unused_archive_path(now);
+
+        assert_ne!(first, second);
+        assert_eq!(first < second, true);
+    }
+}
@@ -3,22 +3,28 @@
 use actix;
 use actix::System;
 use futures::future::Future;
+use node_lib::config_check::ConfigChecker;
 use node_lib::server_initializer::ServerInitializer;
 use node_lib::sub_lib::main_tools::Command;
 use node_lib::sub_lib::main_tools::StdStreams;
 use std::io;
 
 pub fn main() {
-    let system = System::new("main");
+    let args: Vec<String> = std::env::args().collect();
     let mut streams: StdStreams<'_> = StdStreams {
         stdin: &mut io::stdin(),
         stdout: &mut io::stdout(),
         stderr: &mut io::stderr(),
     };
+    if ConfigChecker::is_requested(&args) {
+        ::std::process::exit(ConfigChecker::new().go(&mut streams, &args) as i32);
+    }
+
+    let system = System::new("main");
 
     let mut server_initializer = ServerInitializer::new();
     let streams_ref: &mut StdStreams<'_> = &mut streams;
-    server_initializer.go(streams_ref, &std::env::args().collect());
+    server_initializer.go(streams_ref, &args);
 
     actix::spawn(server_initializer.map_err(|_| {
         System::current().stop();
@@ -10,6 +10,7 @@ use crate::sub_lib::cryptde::{CryptDE, CryptData, PlainData, PublicKey};
 use crate::sub_lib::hopper::MessageType;
 use crate::sub_lib::node_addr::NodeAddr;
 use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::collections::HashSet;
 use std::convert::{TryFrom, TryInto};
 use std::fmt::Debug;
@@ -102,6 +103,14 @@ impl GossipNodeRecord {
                     Vec::from_iter(nri.neighbors.clone().into_iter())
                 ));
                 human_readable.push_str(&format!("\n\t\tversion: {:?},", nri.version));
+                human_readable.push_str(&format!(
+                    "\n\t\tsupports_compression: {:?},",
+                    nri.supports_compression
+                ));
+                human_readable.push_str(&format!(
+                    "\n\t\trelays: {:?},",
+                    Vec::from_iter(nri.relays.clone().into_iter())
+                ));
                 human_readable.push_str("\n\t},");
             }
             Err(_e) => human_readable.push_str("\n\tinner: <non-deserializable>"),
@@ -117,6 +126,13 @@ impl GossipNodeRecord {
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Gossip {
     pub node_records: Vec<GossipNodeRecord>,
+    // The version of every record in the sender's database; only full standard Gossip carries it.
+    // Once we know a neighbor's summary, we send it only the records it doesn't have yet.
+    #[serde(default)]
+    pub version_summary_opt: Option<BTreeMap<PublicKey, u32>>,
+    // Standard Gossip holding only the records the target lacked; it carries no version summary.
+    #[serde(default)]
+    pub delta: bool,
 }
 
 impl Into<MessageType> for Gossip {
@@ -379,6 +395,8 @@ impl<'a> GossipBuilder<'a> {
             db,
             gossip: Gossip {
                 node_records: vec![],
+                version_summary_opt: None,
+                delta: false,
             },
             keys_so_far: HashSet::new(),
         }
@@ -387,6 +405,8 @@ impl<'a> GossipBuilder<'a> {
     pub fn empty() -> Gossip {
         Gossip {
             node_records: vec![],
+            version_summary_opt: None,
+            delta: false,
         }
     }
 
@@ -411,6 +431,23 @@ impl<'a> GossipBuilder<'a> {
         self
     }
 
+    pub fn version_summary(mut self) -> GossipBuilder<'a> {
+        self.gossip.version_summary_opt = Some(
+            self.db
+                .keys()
+                .into_iter()
+                .flat_map(|key| self.db.node_by_key(key))
+                .map(|node_record| (node_record.public_key().clone(), node_record.version()))
+                .collect(),
+        );
+        self
+    }
+
+    pub fn delta(mut self) -> GossipBuilder<'a> {
+        self.gossip.delta = true;
+        self
+    }
+
     pub fn build(self) -> Gossip {
         self.gossip
     }
@@ -568,10 +605,10 @@ mod tests {
 
         let expected = format!(
             "\nGossipNodeRecord {{{}{}{}{}\n}}",
-            "\n\tinner: NodeRecordInner {\n\t\tpublic_key: AQIDBA,\n\t\tnode_addr_opt: Some(1.2.3.4:[1234]),\n\t\tis_bootstrap_node: false,\n\t\tearning_wallet: Wallet { address: \"0x1234\" },\n\t\trate_pack: RatePack { routing_byte_rate: 1235, routing_service_rate: 1236, exit_byte_rate: 1237, exit_service_rate: 1238 },\n\t\tneighbors: [],\n\t\tversion: 2,\n\t},",
+            "\n\tinner: NodeRecordInner {\n\t\tpublic_key: AQIDBA,\n\t\tnode_addr_opt: Some(1.2.3.4:[1234]),\n\t\tis_bootstrap_node: false,\n\t\tearning_wallet: Wallet { address: \"0x1234\" },\n\t\trate_pack: RatePack { routing_byte_rate: 1235, routing_service_rate: 1236, exit_byte_rate: 1237, exit_service_rate: 1238 },\n\t\tneighbors: [],\n\t\tversion: 2,\n\t\tsupports_compression: true,\n\t\trelays: [],\n\t},",
             "\n\tnode_addr_opt: Some(1.2.3.4:[1234]),",
-            "\n\tsigned_data: PlainData { data: [166, 106, 112, 117, 98, 108, 105, 99, 95, 107, 101, 121, 68, 1, 2, 3, 4, 110, 101, 97, 114, 110, 105, 110, 103, 95, 119, 97, 108, 108, 101, 116, 161, 103, 97, 100, 100, 114, 101, 115, 115, 102, 48, 120, 49, 50, 51, 52, 105, 114, 97, 116, 101, 95, 112, 97, 99, 107, 164, 113, 114, 111, 117, 116, 105, 110, 103, 95, 98, 121, 116, 101, 95, 114, 97, 116, 101, 25, 4, 211, 116, 114, 111, 117, 116, 105, 110, 103, 95, 115, 101, 114, 118, 105, 99, 101, 95, 114, 97, 116, 101, 25, 4, 212, 110, 101, 120, 105, 116, 95, 98, 121, 116, 101, 95, 114, 97, 116, 101, 25, 4, 213, 113, 101, 120, 105, 116, 95, 115, 101, 114, 118, 105, 99, 101, 95, 114, 97, 116, 101, 25, 4, 214, 113, 105, 115, 95, 98, 111, 111, 116, 115, 116, 114, 97, 112, 95, 110, 111, 100, 101, 244, 105, 110, 101, 105, 103, 104, 98, 111, 114, 115, 128, 103, 118, 101, 114, 115, 105, 111, 110, 2] },",
-            "\n\tsignature: CryptData { data: [1, 2, 3, 4, 173, 202, 73, 13, 113, 107, 215, 175, 17, 86, 222, 134, 22, 141, 45, 123, 130, 82, 44, 226] },"
+            "\n\tsigned_data: PlainData { data: [168, 106, 112, 117, 98, 108, 105, 99, 95, 107, 101, 121, 68, 1, 2, 3, 4, 110, 101, 97, 114, 110, 105, 110, 103, 95, 119, 97, 108, 108, 101, 116, 161, 103, 97, 100, 100, 114, 101, 115, 115, 102, 48, 120, 49, 50, 51, 52, 105, 114, 97, 116, 101, 95, 112, 97, 99, 107, 164, 113, 114, 111, 117, 116, 105, 110, 103, 95, 98, 121, 116, 101, 95, 114, 97, 116, 101, 25, 4, 211, 116, 114, 111, 117, 116, 105, 110, 103, 95, 115, 101, 114, 118, 105, 99, 101, 95, 114, 97, 116, 101, 25, 4, 212, 110, 101, 120, 105, 116, 95, 98, 121, 116, 101, 95, 114, 97, 116, 101, 25, 4, 213, 113, 101, 120, 105, 116, 95, 115, 101, 114, 118, 105, 99, 101, 95, 114, 97, 116, 101, 25, 4, 214, 113, 105, 115, 95, 98, 111, 111, 116, 115, 116, 114, 97, 112, 95, 110, 111, 100, 101, 244, 105, 110, 101, 105, 103, 104, 98, 111, 114, 115, 128, 103, 118, 101, 114, 115, 105, 111, 110, 2, 116, 115, 117, 112, 112, 111, 114, 116, 115, 95, 99, 111, 109, 112, 114, 101, 115, 115, 105, 111, 110, 245, 102, 114, 101, 108, 97, 121, 115, 128] },",
+            "\n\tsignature: CryptData { data: [1, 2, 3, 4, 2, 93, 167, 168, 198, 27, 216, 121, 104, 100, 231, 75, 105, 27, 135, 9, 177, 123, 153, 53] },"
         );
 
         assert_eq!(expected, result);
@@ -623,6 +660,8 @@ mod tests {
                 GossipNodeRecord::from((&db, target_node.public_key(), true)),
                 neighbor_gnr,
             ],
+            version_summary_opt: None,
+            delta: false,
         };
 
         let result = gossip.to_dot_graph(&source_node, &target_node);
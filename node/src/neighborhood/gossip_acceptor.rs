@@ -3,7 +3,9 @@ use crate::neighborhood::neighborhood::AccessibleGossipRecord;
 use crate::neighborhood::neighborhood_database::{NeighborhoodDatabase, NeighborhoodDatabaseError};
 use crate::neighborhood::node_record::NodeRecord;
 use crate::sub_lib::cryptde::{CryptDE, PublicKey};
+use crate::sub_lib::logger::LogFields;
 use crate::sub_lib::logger::Logger;
+use crate::sub_lib::neighborhood::DiversityConfig;
 use crate::sub_lib::node_addr::NodeAddr;
 use std::collections::HashSet;
 use std::net::IpAddr;
@@ -22,10 +24,18 @@ pub trait GossipAcceptor: Send {
         agrs: Vec<AccessibleGossipRecord>,
         gossip_source: IpAddr,
     ) -> GossipAcceptanceResult;
+    // Gossip that carries a version summary is standard Gossip no matter how few records it has.
+    fn handle_standard(
+        &self,
+        database: &mut NeighborhoodDatabase,
+        agrs: Vec<AccessibleGossipRecord>,
+        gossip_source: IpAddr,
+    ) -> GossipAcceptanceResult;
 }
 
 pub struct GossipAcceptorReal<'a> {
     cryptde: &'a CryptDE,
+    diversity: DiversityConfig,
     logger: Logger,
 }
 
@@ -43,6 +53,15 @@ impl<'a> GossipAcceptor for GossipAcceptorReal<'a> {
             GossipType::Reject => GossipAcceptanceResult::Ignored,
         }
     }
+
+    fn handle_standard(
+        &self,
+        database: &mut NeighborhoodDatabase,
+        agrs: Vec<AccessibleGossipRecord>,
+        gossip_source: IpAddr,
+    ) -> GossipAcceptanceResult {
+        self.handle_standard_gossip(database, agrs, gossip_source)
+    }
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -54,10 +73,11 @@ enum GossipType {
 }
 
 impl<'a> GossipAcceptorReal<'a> {
-    pub fn new(cryptde: &'a CryptDE) -> GossipAcceptorReal {
+    pub fn new(cryptde: &'a CryptDE, diversity: DiversityConfig) -> GossipAcceptorReal {
         GossipAcceptorReal {
             logger: Logger::new("GossipAcceptor"),
             cryptde,
+            diversity,
         }
     }
 
@@ -261,15 +281,23 @@ impl<'a> GossipAcceptorReal<'a> {
             .node_addr_opt
             .clone()
             .expect("Debut Gossip should have been checked for NodeAddr");
+        if database.node_by_key(&public_key).is_none()
+            && self.prefix_is_full(database, &node_addr.ip_addr())
+        {
+            return Ok(GossipAcceptanceResult::Ignored);
+        }
         let debuting_node = NodeRecord::from(debuting_agr);
         let debut_node_key = match database.add_node(debuting_node) {
             Ok(key) => key,
             // TODO: We'll have to stop rejecting re-debuts if we're going to repair the network.
             Err(NeighborhoodDatabaseError::NodeKeyCollision(_)) => {
-                self.logger.warning(format!(
-                    "Ignored re-debut from Node {}, which is already in the database",
-                    public_key
-                ));
+                self.logger.warning_with(
+                    LogFields::new().public_key(&public_key),
+                    format!(
+                        "Ignored re-debut from Node {}, which is already in the database",
+                        public_key
+                    ),
+                );
                 return Ok(GossipAcceptanceResult::Ignored);
             }
             Err(e) => panic!(
@@ -319,6 +347,11 @@ impl<'a> GossipAcceptorReal<'a> {
                 Some(node_addr) => node_addr.ip_addr() == gossip_source,
             })
             .for_each(|agr| {
+                if let Some(node_addr) = &agr.node_addr_opt {
+                    if self.prefix_is_full(database, &node_addr.ip_addr()) {
+                        return;
+                    }
+                }
                 database
                     .add_node(NodeRecord::from(agr))
                     .expect("List of new Nodes contained existing Nodes");
@@ -326,6 +359,26 @@ impl<'a> GossipAcceptorReal<'a> {
         database.keys().len() != all_keys.len()
     }
 
+    // A crowd of Nodes from one IP prefix may be one operator trying to surround us.
+    fn prefix_is_full(&self, database: &NeighborhoodDatabase, ip_addr: &IpAddr) -> bool {
+        let (prefix, count) = database.nodes_in_prefix_of(ip_addr);
+        if count >= self.diversity.max_nodes_per_prefix {
+            self.logger.warning_with(
+                LogFields::new()
+                    .field("ip_addr", ip_addr.to_string())
+                    .field("prefix", prefix.to_string())
+                    .field("node_count", count),
+                format!(
+                    "Refusing Node at {}: already know {} Nodes in {}",
+                    ip_addr, count, prefix
+                ),
+            );
+            true
+        } else {
+            false
+        }
+    }
+
     fn identify_and_update_obsolete_nodes(
         &self,
         database: &mut NeighborhoodDatabase,
@@ -469,20 +522,21 @@ impl<'a> GossipAcceptorReal<'a> {
         database: &mut NeighborhoodDatabase,
         agr: &AccessibleGossipRecord,
     ) -> bool {
-        let existing_node_record = database
-            .node_by_key_mut(&agr.inner.public_key)
-            .expect("Node magically disappeared");
-        match (
-            existing_node_record.node_addr_opt(),
-            agr.node_addr_opt.clone(),
-        ) {
+        let existing_node_addr_opt = database
+            .node_by_key(&agr.inner.public_key)
+            .expect("Node magically disappeared")
+            .node_addr_opt();
+        match (existing_node_addr_opt, agr.node_addr_opt.clone()) {
             (None, Some(new_node_addr)) => {
-                existing_node_record
-                    .set_node_addr(&new_node_addr)
+                database
+                    .set_node_addr(&agr.inner.public_key, &new_node_addr)
                     .expect("Unexpected complaint about changing NodeAddr");
             }
             _ => (), // Maybe we eventually want to detect errors here and abort the change, returning false.
         }
+        let existing_node_record = database
+            .node_by_key_mut(&agr.inner.public_key)
+            .expect("Node magically disappeared");
         existing_node_record.inner = agr.inner.clone();
         existing_node_record.signed_gossip = agr.signed_gossip.clone();
         existing_node_record.signature = agr.signature.clone();
@@ -609,8 +663,10 @@ mod tests {
         let mut db = db_from_node(&just_a_node);
         let gossip = Gossip {
             node_records: vec![],
+            version_summary_opt: None,
+            delta: false,
         };
-        let subject = GossipAcceptorReal::new(cryptde());
+        let subject = GossipAcceptorReal::new(cryptde(), DiversityConfig::default());
 
         let result = subject.handle(
             &mut db,
@@ -627,7 +683,7 @@ mod tests {
         let root_node_cryptde = CryptDENull::from(&root_node.public_key());
         let mut dest_db = db_from_node(&root_node);
         let (gossip, debut_node, gossip_source) = make_debut(2345);
-        let subject = GossipAcceptorReal::new(&root_node_cryptde);
+        let subject = GossipAcceptorReal::new(&root_node_cryptde, DiversityConfig::default());
 
         let result = subject.handle(&mut dest_db, gossip.try_into().unwrap(), gossip_source);
 
@@ -657,7 +713,7 @@ mod tests {
             .resign();
         dest_db.node_by_key_mut(existing_node_key).unwrap().resign();
         let (gossip, debut_node, gossip_source) = make_debut(2345);
-        let subject = GossipAcceptorReal::new(&root_node_cryptde);
+        let subject = GossipAcceptorReal::new(&root_node_cryptde, DiversityConfig::default());
 
         let result = subject.handle(&mut dest_db, gossip.try_into().unwrap(), gossip_source);
 
@@ -721,7 +777,7 @@ mod tests {
             .resign();
 
         let (gossip, debut_node, gossip_source) = make_debut(2345);
-        let subject = GossipAcceptorReal::new(&root_node_cryptde);
+        let subject = GossipAcceptorReal::new(&root_node_cryptde, DiversityConfig::default());
 
         let result = subject.handle(&mut dest_db, gossip.try_into().unwrap(), gossip_source);
 
@@ -809,7 +865,7 @@ mod tests {
             .resign();
 
         let (gossip, debut_node, gossip_source) = make_debut(2345);
-        let subject = GossipAcceptorReal::new(&root_node_cryptde);
+        let subject = GossipAcceptorReal::new(&root_node_cryptde, DiversityConfig::default());
 
         let result = subject.handle(&mut dest_db, gossip.try_into().unwrap(), gossip_source);
 
@@ -895,7 +951,7 @@ mod tests {
             .resign();
 
         let (gossip, debut_node, gossip_source) = make_debut(2345);
-        let subject = GossipAcceptorReal::new(&root_node_cryptde);
+        let subject = GossipAcceptorReal::new(&root_node_cryptde, DiversityConfig::default());
 
         let result = subject.handle(&mut dest_db, gossip.try_into().unwrap(), gossip_source);
 
@@ -925,7 +981,7 @@ mod tests {
         let root_node_cryptde = CryptDENull::from(&root_node.public_key());
         let mut dest_db = db_from_node(&root_node);
         let (gossip, debut_node, gossip_source) = make_debut(2345);
-        let subject = GossipAcceptorReal::new(&root_node_cryptde);
+        let subject = GossipAcceptorReal::new(&root_node_cryptde, DiversityConfig::default());
 
         let result = subject.handle(&mut dest_db, gossip.try_into().unwrap(), gossip_source);
 
@@ -981,7 +1037,7 @@ mod tests {
         dest_db.node_by_key_mut(four_key).unwrap().resign();
         dest_db.node_by_key_mut(five_key).unwrap().resign();
         let (gossip, debut_node, gossip_source) = make_debut(8901);
-        let subject = GossipAcceptorReal::new(&root_node_cryptde);
+        let subject = GossipAcceptorReal::new(&root_node_cryptde, DiversityConfig::default());
         let mut root_node = dest_db.root().clone();
 
         let result = subject.handle(&mut dest_db, gossip.try_into().unwrap(), gossip_source);
@@ -1015,7 +1071,7 @@ mod tests {
             .unwrap();
         dest_db.add_arbitrary_full_neighbor(root_node.public_key(), existing_node_key);
         let (gossip, debut_node, gossip_source) = make_debut(3456);
-        let subject = GossipAcceptorReal::new(cryptde());
+        let subject = GossipAcceptorReal::new(cryptde(), DiversityConfig::default());
 
         let result = subject.handle(&mut dest_db, gossip.try_into().unwrap(), gossip_source);
 
@@ -1023,12 +1079,39 @@ mod tests {
         TestLogHandler::new ().exists_log_containing (format! ("WARN: GossipAcceptor: Ignored re-debut from Node {}, which is already in the database", debut_node.public_key()).as_str());
     }
 
+    #[test]
+    fn debut_from_a_crowded_ip_prefix_is_ignored() {
+        init_test_logging();
+        let root_node = make_node_record(1234, true, false);
+        let mut dest_db = db_from_node(&root_node);
+        let existing_node_key = &dest_db
+            .add_node(make_node_record(3456, true, false))
+            .unwrap();
+        dest_db.add_arbitrary_full_neighbor(root_node.public_key(), existing_node_key);
+        let (gossip, debut_node, gossip_source) = make_debut(3457);
+        let subject = GossipAcceptorReal::new(
+            cryptde(),
+            DiversityConfig {
+                max_nodes_per_prefix: 1,
+                ..DiversityConfig::default()
+            },
+        );
+
+        let result = subject.handle(&mut dest_db, gossip.try_into().unwrap(), gossip_source);
+
+        assert_eq!(GossipAcceptanceResult::Ignored, result);
+        assert_eq!(dest_db.node_by_key(debut_node.public_key()), None);
+        TestLogHandler::new().exists_log_containing(
+            "WARN: GossipAcceptor: Refusing Node at 3.4.5.7: already know 1 Nodes in 3.4.5.0/24",
+        );
+    }
+
     #[test]
     fn pass_is_properly_handled() {
         let root_node = make_node_record(1234, true, false);
         let mut db = db_from_node(&root_node);
         let (gossip, pass_target, gossip_source) = make_pass(2345);
-        let subject = GossipAcceptorReal::new(cryptde());
+        let subject = GossipAcceptorReal::new(cryptde(), DiversityConfig::default());
 
         let result = subject.handle(&mut db, gossip.try_into().unwrap(), gossip_source);
 
@@ -1114,7 +1197,7 @@ mod tests {
             .node(node_e.public_key(), true)
             .node(node_f.public_key(), true)
             .build();
-        let subject = GossipAcceptorReal::new(cryptde());
+        let subject = GossipAcceptorReal::new(cryptde(), DiversityConfig::default());
 
         let result = subject.handle(
             &mut dest_db,
@@ -1231,7 +1314,7 @@ mod tests {
             .node(src_node.public_key(), true)
             .node(third_node.public_key(), true)
             .build();
-        let subject = GossipAcceptorReal::new(&dest_node_cryptde);
+        let subject = GossipAcceptorReal::new(&dest_node_cryptde, DiversityConfig::default());
 
         let result = subject.handle(
             &mut dest_db,
@@ -1295,7 +1378,7 @@ mod tests {
             .node(current_node.public_key(), false)
             .node(obsolete_node.public_key(), false)
             .build();
-        let subject = GossipAcceptorReal::new(cryptde());
+        let subject = GossipAcceptorReal::new(cryptde(), DiversityConfig::default());
         let original_dest_db = dest_db.clone();
 
         let result = subject.handle(
@@ -1329,6 +1412,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn single_record_standard_gossip_is_not_mistaken_for_a_debut() {
+        let dest_root = make_node_record(1234, true, false);
+        let mut dest_db = db_from_node(&dest_root);
+        let src_root = make_node_record(2345, true, false);
+        dest_db.add_node(src_root.clone()).unwrap();
+        dest_db.add_arbitrary_full_neighbor(dest_root.public_key(), src_root.public_key());
+        let mut src_db = db_from_node(&src_root);
+        src_db.add_node(dest_root.clone()).unwrap();
+        src_db.add_arbitrary_full_neighbor(src_root.public_key(), dest_root.public_key());
+        src_db.root_mut().set_version(5);
+        src_db.resign_node(src_root.public_key());
+        let gossip = GossipBuilder::new(&src_db)
+            .node(src_root.public_key(), true)
+            .version_summary()
+            .build();
+        let subject = GossipAcceptorReal::new(cryptde(), DiversityConfig::default());
+
+        let result = subject.handle_standard(
+            &mut dest_db,
+            gossip.try_into().unwrap(),
+            src_root.node_addr_opt().unwrap().ip_addr(),
+        );
+
+        assert_eq!(GossipAcceptanceResult::Accepted(vec![]), result);
+        assert_eq!(
+            dest_db
+                .node_by_key(src_root.public_key())
+                .unwrap()
+                .version(),
+            5
+        );
+    }
+
     #[test]
     fn make_debuts_chooses_properly() {
         let root_node = make_node_record(1234, true, false);
@@ -1350,7 +1467,7 @@ mod tests {
         dest_db.add_node(existing_neighbor.clone()).unwrap();
         dest_db.add_arbitrary_half_neighbor(root_node.public_key(), existing_neighbor.public_key());
         dest_db.resign_node(root_node.public_key());
-        let subject = GossipAcceptorReal::new(cryptde());
+        let subject = GossipAcceptorReal::new(cryptde(), DiversityConfig::default());
 
         let result = subject.make_debuts(
             &dest_db,
@@ -1388,7 +1505,7 @@ mod tests {
         db.add_arbitrary_full_neighbor(root_node.public_key(), other_neighbor_3_key);
         db.add_arbitrary_full_neighbor(less_connected_neighbor_key, other_neighbor_1_key);
         db.add_arbitrary_full_neighbor(less_connected_neighbor_key, other_neighbor_2_key);
-        let subject = GossipAcceptorReal::new(cryptde());
+        let subject = GossipAcceptorReal::new(cryptde(), DiversityConfig::default());
 
         let result = subject.find_more_appropriate_neighbor(&db);
 
@@ -1411,7 +1528,7 @@ mod tests {
         db.add_arbitrary_full_neighbor(less_connected_neighbor_key, other_neighbor_1_key);
         db.add_arbitrary_full_neighbor(less_connected_neighbor_key, other_neighbor_2_key);
         db.add_arbitrary_full_neighbor(less_connected_neighbor_key, other_neighbor_3_key);
-        let subject = GossipAcceptorReal::new(cryptde());
+        let subject = GossipAcceptorReal::new(cryptde(), DiversityConfig::default());
 
         let result = subject.find_more_appropriate_neighbor(&db);
 
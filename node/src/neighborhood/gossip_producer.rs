@@ -4,9 +4,16 @@ use super::gossip::Gossip;
 use super::gossip::GossipBuilder;
 use super::neighborhood_database::NeighborhoodDatabase;
 use crate::sub_lib::cryptde::PublicKey;
+use std::collections::BTreeMap;
 
 pub trait GossipProducer: Send {
     fn produce(&self, database: &NeighborhoodDatabase, target: &PublicKey) -> Gossip;
+    fn produce_delta(
+        &self,
+        database: &NeighborhoodDatabase,
+        target: &PublicKey,
+        target_version_summary: &BTreeMap<PublicKey, u32>,
+    ) -> Option<Gossip>;
     fn produce_debut(&self, database: &NeighborhoodDatabase) -> Gossip;
 }
 
@@ -26,21 +33,52 @@ impl GossipProducer for GossipProducerReal {
             a Gossip message representing the current neighborhood for a target Node
     */
     fn produce(&self, database: &NeighborhoodDatabase, target: &PublicKey) -> Gossip {
-        let target_node_ref = database
-            .node_by_key(target)
-            .expect(format!("Target node {:?} not in NeighborhoodDatabase", target).as_str());
-        let builder = database
+        let keys = database
             .keys()
             .into_iter()
             .filter(|k| *k != target)
-            .flat_map(|k| database.node_by_key(k))
-            .fold(GossipBuilder::new(database), |so_far, node_record_ref| {
-                let reveal_node_addr = node_record_ref.public_key() == database.root().public_key()
-                    || target_node_ref.has_half_neighbor(node_record_ref.public_key());
-                so_far.node(node_record_ref.public_key(), reveal_node_addr)
-            });
-        let gossip = builder.build();
-        gossip
+            .collect();
+        Self::produce_from_keys(database, target, keys)
+            .version_summary()
+            .build()
+    }
+
+    /*
+        `produce_delta`
+            like `produce`, but leaves out every record that `target` already has at its current version, according
+            to the version summary it sent us with its last full standard Gossip; the result carries no version
+            summary of its own
+        returns:
+            None if `target` is already up to date; otherwise a Gossip message containing only what `target` lacks
+    */
+    fn produce_delta(
+        &self,
+        database: &NeighborhoodDatabase,
+        target: &PublicKey,
+        target_version_summary: &BTreeMap<PublicKey, u32>,
+    ) -> Option<Gossip> {
+        let keys: Vec<&PublicKey> = database
+            .keys()
+            .into_iter()
+            .filter(|k| *k != target)
+            .filter(
+                |k| match (target_version_summary.get(k), database.node_by_key(k)) {
+                    (Some(known_version), Some(node_record_ref)) => {
+                        node_record_ref.version() > *known_version
+                    }
+                    _ => true,
+                },
+            )
+            .collect();
+        if keys.is_empty() {
+            None
+        } else {
+            Some(
+                Self::produce_from_keys(database, target, keys)
+                    .delta()
+                    .build(),
+            )
+        }
     }
 
     fn produce_debut(&self, database: &NeighborhoodDatabase) -> Gossip {
@@ -54,6 +92,24 @@ impl GossipProducerReal {
     pub fn new() -> GossipProducerReal {
         GossipProducerReal {}
     }
+
+    fn produce_from_keys<'a>(
+        database: &'a NeighborhoodDatabase,
+        target: &PublicKey,
+        keys: Vec<&PublicKey>,
+    ) -> GossipBuilder<'a> {
+        let target_node_ref = database
+            .node_by_key(target)
+            .expect(format!("Target node {:?} not in NeighborhoodDatabase", target).as_str());
+        keys.into_iter().flat_map(|k| database.node_by_key(k)).fold(
+            GossipBuilder::new(database),
+            |so_far, node_record_ref| {
+                let reveal_node_addr = node_record_ref.public_key() == database.root().public_key()
+                    || target_node_ref.has_half_neighbor(node_record_ref.public_key());
+                so_far.node(node_record_ref.public_key(), reveal_node_addr)
+            },
+        )
+    }
 }
 
 #[cfg(test)]
@@ -155,6 +211,111 @@ mod tests {
         assert_eq!(expected_gossip_digests, actual_gossip_digests);
     }
 
+    #[test]
+    fn produce_includes_a_version_summary_of_the_whole_database() {
+        let root_node = make_node_record(1234, true, false);
+        let mut db = db_from_node(&root_node);
+        let target_key = &db.add_node(make_node_record(2345, true, false)).unwrap();
+        let other_key = &db.add_node(make_node_record(3456, true, false)).unwrap();
+        db.node_by_key_mut(other_key).unwrap().set_version(7);
+        let subject = GossipProducerReal::new();
+
+        let gossip = subject.produce(&db, target_key);
+
+        assert_eq!(gossip.delta, false);
+        let expected_summary: BTreeMap<PublicKey, u32> = vec![
+            (root_node.public_key().clone(), 0),
+            (target_key.clone(), 0),
+            (other_key.clone(), 7),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(gossip.version_summary_opt, Some(expected_summary));
+    }
+
+    #[test]
+    fn produce_delta_includes_only_records_the_target_lacks_or_has_old_versions_of() {
+        let root_node = make_node_record(1234, true, false);
+        let mut db = db_from_node(&root_node);
+        let target_key = &db.add_node(make_node_record(2345, true, false)).unwrap();
+        let current_key = &db.add_node(make_node_record(3456, true, false)).unwrap();
+        let updated_key = &db.add_node(make_node_record(4567, true, false)).unwrap();
+        let unknown_key = &db.add_node(make_node_record(5678, true, false)).unwrap();
+        db.node_by_key_mut(updated_key).unwrap().set_version(3);
+        let target_version_summary: BTreeMap<PublicKey, u32> = vec![
+            (root_node.public_key().clone(), 0),
+            (target_key.clone(), 0),
+            (current_key.clone(), 0),
+            (updated_key.clone(), 2),
+        ]
+        .into_iter()
+        .collect();
+        let subject = GossipProducerReal::new();
+
+        let gossip = subject
+            .produce_delta(&db, target_key, &target_version_summary)
+            .unwrap();
+
+        let keys = gossip
+            .node_records
+            .iter()
+            .map(|gnr| NodeRecordInner::try_from(gnr).unwrap().public_key)
+            .collect::<BTreeSet<PublicKey>>();
+        assert_eq!(
+            keys,
+            vec![updated_key.clone(), unknown_key.clone()]
+                .into_iter()
+                .collect::<BTreeSet<PublicKey>>()
+        );
+        assert_eq!(gossip.version_summary_opt, None);
+        assert_eq!(gossip.delta, true);
+    }
+
+    #[test]
+    fn produce_delta_produces_nothing_for_a_target_that_is_up_to_date() {
+        let root_node = make_node_record(1234, true, false);
+        let mut db = db_from_node(&root_node);
+        let target_key = &db.add_node(make_node_record(2345, true, false)).unwrap();
+        let subject = GossipProducerReal::new();
+        let target_version_summary = subject
+            .produce(&db, target_key)
+            .version_summary_opt
+            .unwrap();
+
+        let result = subject.produce_delta(&db, target_key, &target_version_summary);
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn delta_gossip_about_one_change_is_much_smaller_than_full_gossip() {
+        let root_node = make_node_record(1000, true, false);
+        let mut db = db_from_node(&root_node);
+        let target_key = &db.add_node(make_node_record(1001, true, false)).unwrap();
+        (1002..1100).for_each(|n| {
+            db.add_node(make_node_record(n, true, false)).unwrap();
+        });
+        let subject = GossipProducerReal::new();
+        let full_gossip = subject.produce(&db, target_key);
+        let target_version_summary = full_gossip.version_summary_opt.clone().unwrap();
+        let changed_key = make_node_record(1050, true, false).public_key().clone();
+        db.node_by_key_mut(&changed_key).unwrap().set_version(1);
+
+        let delta_gossip = subject
+            .produce_delta(&db, target_key, &target_version_summary)
+            .unwrap();
+
+        assert_eq!(delta_gossip.node_records.len(), 1);
+        let full_size = serde_cbor::ser::to_vec(&full_gossip).unwrap().len();
+        let delta_size = serde_cbor::ser::to_vec(&delta_gossip).unwrap().len();
+        assert!(
+            delta_size * 4 < full_size,
+            "Delta Gossip was {} bytes; full Gossip was {} bytes",
+            delta_size,
+            full_size
+        );
+    }
+
     #[test]
     fn produce_debut_creates_a_gossip_to_a_target_about_ourselves() {
         let our_node_record = make_node_record(7771, true, false);
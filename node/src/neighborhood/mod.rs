@@ -5,8 +5,10 @@ pub mod gossip;
 mod gossip_acceptor;
 mod gossip_producer;
 pub mod neighborhood;
+pub mod neighborhood_dao;
 pub mod neighborhood_database;
 pub mod node_record;
+mod rate_limiter;
 
 #[cfg(test)]
 pub mod neighborhood_test_utils;
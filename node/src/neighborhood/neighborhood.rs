@@ -5,25 +5,39 @@ use super::gossip_producer::GossipProducer;
 use super::gossip_producer::GossipProducerReal;
 use super::neighborhood_database::NeighborhoodDatabase;
 use super::node_record::NodeRecord;
+use super::rate_limiter::RateLimiter;
 use crate::neighborhood::gossip::{DotGossipEndpoint, Gossip, GossipNodeRecord};
 use crate::neighborhood::gossip_acceptor::GossipAcceptanceResult;
+use crate::neighborhood::neighborhood_dao::{NeighborhoodDao, StoredNodeRecord};
 use crate::neighborhood::node_record::NodeRecordInner;
+use crate::sub_lib::accountant::DEFAULT_EARNING_WALLET;
 use crate::sub_lib::cryptde::PublicKey;
 use crate::sub_lib::cryptde::{CryptDE, CryptData, PlainData};
 use crate::sub_lib::dispatcher::Component;
 use crate::sub_lib::hopper::{ExpiredCoresPackage, NoLookupIncipientCoresPackage};
 use crate::sub_lib::hopper::{IncipientCoresPackage, MessageType};
+use crate::sub_lib::logger::LogFields;
 use crate::sub_lib::logger::Logger;
+use crate::sub_lib::neighborhood::ConnectionState;
 use crate::sub_lib::neighborhood::DispatcherNodeQueryMessage;
+use crate::sub_lib::neighborhood::DiversityConfig;
 use crate::sub_lib::neighborhood::ExpectedService;
 use crate::sub_lib::neighborhood::ExpectedServices;
+use crate::sub_lib::neighborhood::Heartbeat;
 use crate::sub_lib::neighborhood::NeighborhoodConfig;
+use crate::sub_lib::neighborhood::NeighborhoodGraphRequest;
 use crate::sub_lib::neighborhood::NeighborhoodSubs;
 use crate::sub_lib::neighborhood::NodeQueryMessage;
 use crate::sub_lib::neighborhood::NodeQueryResponseMetadata;
+use crate::sub_lib::neighborhood::RatePack;
+use crate::sub_lib::neighborhood::RelayRequest;
+use crate::sub_lib::neighborhood::RelayRequestMessage;
 use crate::sub_lib::neighborhood::RemoveNeighborMessage;
+use crate::sub_lib::neighborhood::RouteConstraints;
 use crate::sub_lib::neighborhood::RouteQueryMessage;
 use crate::sub_lib::neighborhood::RouteQueryResponse;
+use crate::sub_lib::neighborhood::SetInitialNeighborsMessage;
+use crate::sub_lib::neighborhood::SetRatePackMessage;
 use crate::sub_lib::neighborhood::TargetType;
 use crate::sub_lib::neighborhood::{sentinel_ip_addr, NodeRecordMetadataMessage};
 use crate::sub_lib::neighborhood::{BootstrapNeighborhoodNowMessage, NodeDescriptor};
@@ -32,31 +46,150 @@ use crate::sub_lib::peer_actors::BindMessage;
 use crate::sub_lib::route::Route;
 use crate::sub_lib::route::RouteSegment;
 use crate::sub_lib::stream_handler_pool::DispatcherNodeQueryResponse;
+use crate::sub_lib::ui_gateway::ConnectionStateMessage;
+use crate::sub_lib::ui_gateway::{UiCarrierMessage, UiMessage};
 use crate::sub_lib::utils::NODE_MAILBOX_CAPACITY;
 use crate::sub_lib::wallet::Wallet;
 use actix::Actor;
 use actix::Addr;
+use actix::AsyncContext;
 use actix::Context;
 use actix::Handler;
 use actix::MessageResult;
 use actix::Recipient;
+use rand::Rng;
+use std::cmp::max;
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::convert::TryFrom;
 use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::time::Duration;
+use std::time::Instant;
+
+// Limits on the work done by a single route search; see Neighborhood::complete_routes().
+const ROUTE_SEARCH_STEP_BUDGET: usize = 10_000;
+const ROUTE_SEARCH_CANDIDATE_LIMIT: usize = 32;
+const ROUTE_SEARCH_DETOUR_HOPS: usize = 2;
+
+// How often we Ping our neighbors, and how many unanswered Pings make a neighbor dead.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+const MISSED_HEARTBEAT_LIMIT: usize = 3;
+
+// How much Gossip we send to each neighbor, and accept from each source IP, per interval.
+const GOSSIP_RATE_INTERVAL: Duration = Duration::from_secs(10);
+const MAX_OUTBOUND_GOSSIP_PER_NEIGHBOR: usize = 5;
+const MAX_INBOUND_GOSSIP_PER_SOURCE: usize = 20;
+
+// How long to wait for an answer to a Debut before trying the next descriptor, and how long to
+// wait after the first unanswered round of Debuts; each later round waits twice as long.
+const DEBUT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEBUT_RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_DEBUT_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+// How long we trust a neighbor's version summary. Once it expires, the neighbor gets full Gossip
+// again, which carries our own summary back to it.
+const VERSION_SUMMARY_LIFETIME: Duration = Duration::from_secs(300);
+
+// Changes to the NeighborhoodDatabase are saved together at most this often.
+const DATABASE_SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+// How long a relay client has to answer our challenge before it must send a new RelayRequest.
+const RELAY_CHALLENGE_TTL: Duration = Duration::from_secs(60);
 
 pub struct Neighborhood {
     cryptde: &'static dyn CryptDE,
     hopper: Option<Recipient<IncipientCoresPackage>>,
     hopper_no_lookup: Option<Recipient<NoLookupIncipientCoresPackage>>,
+    connection_state_sub: Option<Recipient<ConnectionStateMessage>>,
+    ui_message_sub: Option<Recipient<UiCarrierMessage>>,
     gossip_acceptor: Box<dyn GossipAcceptor>,
     gossip_producer: Box<dyn GossipProducer>,
     neighborhood_database: NeighborhoodDatabase,
     consuming_wallet_opt: Option<Wallet>,
     next_return_route_id: u32,
     initial_neighbors: Vec<NodeDescriptor>,
+    neighborhood_dao_opt: Option<Box<dyn NeighborhoodDao>>,
+    database_changed: bool,
+    neighbor_liveness: HashMap<PublicKey, NeighborLiveness>,
+    next_heartbeat_sequence: u32,
+    neighbor_version_summaries: HashMap<PublicKey, NeighborVersionSummary>,
+    gossip_batch_window: Duration,
+    pending_gossip_targets: HashSet<PublicKey>,
+    outbound_gossip_limiter: RateLimiter<PublicKey>,
+    inbound_gossip_limiter: RateLimiter<IpAddr>,
+    diversity: DiversityConfig,
+    behind_nat: bool,
+    relay_clients: HashMap<PublicKey, SocketAddr>,
+    // Challenges sent in answer to RelayRequests, with when they were sent, by the connection
+    // they were sent over
+    relay_challenges: HashMap<SocketAddr, (PublicKey, u64, Instant)>,
+    relay_challenge_ttl: Duration,
+    connection_state: ConnectionState,
+    reported_neighbor_count: usize,
+    debut_queue: Vec<NodeDescriptor>,
+    debut_round: u32,
+    // Identifies the latest Debut or retry wait, so that timers for earlier ones can be ignored
+    debut_sequence: u64,
+    debut_timeout: Duration,
+    debut_retry_delay: Duration,
     logger: Logger,
 }
 
+// The versions a neighbor has of the records we know about: what its last full Gossip said it
+// had, plus what it has sent us since. What we send it doesn't count: it may be dropped by the
+// neighbor's rate limiter or refused record by record, and only the neighbor can tell us which.
+struct NeighborVersionSummary {
+    versions: BTreeMap<PublicKey, u32>,
+    received: Instant,
+}
+
+impl NeighborVersionSummary {
+    fn new(versions: BTreeMap<PublicKey, u32>, received: Instant) -> NeighborVersionSummary {
+        NeighborVersionSummary { versions, received }
+    }
+
+    fn note_version(&mut self, key: &PublicKey, version: u32) {
+        let known_version = self.versions.entry(key.clone()).or_insert(version);
+        if *known_version < version {
+            *known_version = version;
+        }
+    }
+}
+
+#[derive(Default)]
+struct NeighborLiveness {
+    outstanding_ping_opt: Option<(u32, Instant)>,
+    missed_heartbeats: usize,
+}
+
+struct RouteSearch<'a, 'b> {
+    target: Option<&'a PublicKey>,
+    next_door_exit_allowed: bool,
+    avoided_keys: &'b [PublicKey],
+    constraints: &'b RouteConstraints,
+    hop_counts_to_target: HashMap<&'a PublicKey, usize>,
+    max_hop_count: usize,
+    steps_remaining: usize,
+    routes: Vec<Vec<&'a PublicKey>>,
+}
+
+impl<'a, 'b> RouteSearch<'a, 'b> {
+    fn can_reach_target_in_time(&self, hop_count: usize, key: &PublicKey) -> bool {
+        match self.target {
+            None => true,
+            Some(_) => match self.hop_counts_to_target.get(key) {
+                Some(hops_to_target) => hop_count + hops_to_target <= self.max_hop_count,
+                None => false,
+            },
+        }
+    }
+}
+
 impl Actor for Neighborhood {
     type Context = Context<Self>;
 }
@@ -68,6 +201,25 @@ impl Handler<BindMessage> for Neighborhood {
         ctx.set_mailbox_capacity(NODE_MAILBOX_CAPACITY);
         self.hopper = Some(msg.peer_actors.hopper.from_hopper_client);
         self.hopper_no_lookup = Some(msg.peer_actors.hopper.from_hopper_client_no_lookup);
+        self.connection_state_sub = Some(msg.peer_actors.ui_gateway.connection_state_sub);
+        self.ui_message_sub = Some(msg.peer_actors.ui_gateway.ui_message_sub);
+        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+            act.send_heartbeats();
+            act.rejoin_if_alone(ctx);
+            act.report_neighbor_count_change();
+        });
+        // Even without batching, Gossip held back by the outbound limit must go out eventually.
+        let gossip_flush_interval = if self.gossip_batch_window == Duration::from_millis(0) {
+            GOSSIP_RATE_INTERVAL
+        } else {
+            self.gossip_batch_window
+        };
+        ctx.run_interval(gossip_flush_interval, |act, _ctx| {
+            act.flush_pending_gossip()
+        });
+        ctx.run_interval(DATABASE_SAVE_INTERVAL, |act, _ctx| {
+            act.save_database_if_changed()
+        });
     }
 }
 
@@ -77,39 +229,25 @@ impl Handler<BootstrapNeighborhoodNowMessage> for Neighborhood {
     fn handle(
         &mut self,
         _msg: BootstrapNeighborhoodNowMessage,
-        _ctx: &mut Self::Context,
+        ctx: &mut Self::Context,
     ) -> Self::Result {
+        let remembered_neighbor_count =
+            self.neighborhood_database.root().half_neighbor_keys().len();
+        if remembered_neighbor_count > 0 {
+            self.logger.info(format!(
+                "Rejoining through {} remembered neighbors",
+                remembered_neighbor_count
+            ));
+            self.gossip_to_neighbors();
+        }
         if self.initial_neighbors.is_empty() {
-            self.logger
-                .info(format!("No bootstrap Nodes to report to; continuing"));
+            if remembered_neighbor_count == 0 {
+                self.logger
+                    .info(format!("No bootstrap Nodes to report to; continuing"));
+            }
             return;
         }
-
-        let gossip = self
-            .gossip_producer
-            .produce_debut(&self.neighborhood_database);
-        &self.initial_neighbors.iter().for_each(|neighbor| {
-            self.hopper_no_lookup
-                .as_ref()
-                .expect("unbound hopper")
-                .try_send(
-                    NoLookupIncipientCoresPackage::new(
-                        self.cryptde,
-                        &neighbor.public_key,
-                        &neighbor.node_addr,
-                        MessageType::Gossip(gossip.clone()),
-                    )
-                    .expect("Key magically disappeared"),
-                )
-                .expect("hopper is dead");
-            self.logger.trace(format!(
-                "Sent Gossip: {}",
-                gossip.to_dot_graph(
-                    self.neighborhood_database.root(),
-                    (&neighbor.public_key, &Some(neighbor.node_addr.clone()))
-                )
-            ));
-        });
+        self.start_debut_round(ctx);
     }
 }
 
@@ -156,10 +294,7 @@ impl Handler<DispatcherNodeQueryMessage> for Neighborhood {
         let node_descriptor = match node_record_ref_opt {
             Some(node_record_ref) => Some(NodeQueryResponseMetadata::new(
                 node_record_ref.public_key().clone(),
-                match node_record_ref.node_addr_opt() {
-                    Some(node_addr) => Some(node_addr.clone()),
-                    None => None,
-                },
+                self.reachable_node_addr_opt(node_record_ref),
                 node_record_ref.rate_pack().clone(),
             )),
             None => None,
@@ -216,6 +351,7 @@ impl Handler<ExpiredCoresPackage<Gossip>> for Neighborhood {
         let incoming_gossip = msg.payload;
         self.log_incoming_gossip(&incoming_gossip, msg.immediate_neighbor_ip);
         self.handle_gossip(incoming_gossip, msg.immediate_neighbor_ip);
+        self.notice_connection();
     }
 }
 
@@ -229,6 +365,7 @@ impl Handler<RemoveNeighborMessage> for Neighborhood {
             Ok(db_changed) => {
                 if db_changed {
                     self.gossip_to_neighbors();
+                    self.database_changed = true;
                     self.logger
                         .info(format!("removed neighbor by public key: {}", public_key))
                 }
@@ -237,6 +374,89 @@ impl Handler<RemoveNeighborMessage> for Neighborhood {
     }
 }
 
+impl Handler<ExpiredCoresPackage<Heartbeat>> for Neighborhood {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: ExpiredCoresPackage<Heartbeat>,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        self.handle_heartbeat(msg.payload, msg.immediate_neighbor_ip);
+    }
+}
+
+impl Handler<NeighborhoodGraphRequest> for Neighborhood {
+    type Result = ();
+
+    fn handle(&mut self, msg: NeighborhoodGraphRequest, _ctx: &mut Self::Context) -> Self::Result {
+        self.ui_message_sub
+            .as_ref()
+            .expect("UiGateway is unbound")
+            .try_send(UiCarrierMessage {
+                client_id: msg.client_id,
+                correlation_id: msg.correlation_id,
+                data: UiMessage::NeighborhoodGraph(self.neighborhood_database.to_graph()),
+            })
+            .expect("UiGateway is dead");
+    }
+}
+
+impl Handler<SetRatePackMessage> for Neighborhood {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetRatePackMessage, _ctx: &mut Self::Context) -> Self::Result {
+        self.set_rate_pack(msg.rate_pack);
+    }
+}
+
+impl Handler<SetInitialNeighborsMessage> for Neighborhood {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetInitialNeighborsMessage, ctx: &mut Self::Context) -> Self::Result {
+        let added = msg
+            .neighbors
+            .iter()
+            .filter(|neighbor| !self.initial_neighbors.contains(neighbor))
+            .cloned()
+            .collect::<Vec<NodeDescriptor>>();
+        self.logger.info(format!(
+            "Initial neighbors change from {} to {}",
+            self.initial_neighbors.len(),
+            msg.neighbors.len()
+        ));
+        self.initial_neighbors = msg.neighbors;
+        if self.connection_state == ConnectionState::Connected {
+            added.iter().for_each(|neighbor| self.send_debut(neighbor));
+        } else if self.initial_neighbors.is_empty() {
+            self.debut_sequence += 1;
+            self.debut_queue.clear();
+            self.set_connection_state(ConnectionState::Standalone);
+        } else {
+            self.debut_round = 0;
+            self.start_debut_round(ctx);
+        }
+    }
+}
+
+impl Handler<RelayRequestMessage> for Neighborhood {
+    type Result = ();
+
+    fn handle(&mut self, msg: RelayRequestMessage, _ctx: &mut Self::Context) -> Self::Result {
+        match msg.relay_request {
+            RelayRequest::Request { node_record } => {
+                self.handle_relay_request(node_record, msg.client_addr)
+            }
+            RelayRequest::Challenge(challenge) => {
+                self.handle_relay_challenge(challenge, msg.client_addr)
+            }
+            RelayRequest::Response { signature } => {
+                self.handle_relay_response(signature, msg.client_addr)
+            }
+        }
+    }
+}
+
 impl Handler<NodeRecordMetadataMessage> for Neighborhood {
     type Result = ();
 
@@ -244,7 +464,10 @@ impl Handler<NodeRecordMetadataMessage> for Neighborhood {
         match msg {
             NodeRecordMetadataMessage::Desirable(public_key, desirable) => {
                 if let Some(node_record) = self.neighborhood_database.node_by_key_mut(&public_key) {
-                    node_record.set_desirable(desirable);
+                    if node_record.is_desirable() != desirable {
+                        node_record.set_desirable(desirable);
+                        self.database_changed = true;
+                    }
                 };
             }
         };
@@ -285,10 +508,11 @@ impl Neighborhood {
                 panic! ("A SubstratumNode without an --ip setting is not decentralized and cannot be --node_type bootstrap")
             }
         }
-        let gossip_acceptor: Box<dyn GossipAcceptor> = Box::new(GossipAcceptorReal::new(cryptde));
+        let gossip_acceptor: Box<dyn GossipAcceptor> =
+            Box::new(GossipAcceptorReal::new(cryptde, config.diversity));
         let gossip_producer = Box::new(GossipProducerReal::new());
         let local_node_addr = NodeAddr::new(&config.local_ip_addr, &config.clandestine_port_list);
-        let neighborhood_database = NeighborhoodDatabase::new(
+        let mut neighborhood_database = NeighborhoodDatabase::new(
             &cryptde.public_key(),
             &local_node_addr,
             config.earning_wallet.clone(),
@@ -296,21 +520,136 @@ impl Neighborhood {
             config.is_bootstrap_node,
             cryptde,
         );
+        neighborhood_database.set_diversity(config.diversity);
 
         Neighborhood {
             cryptde,
             hopper: None,
             hopper_no_lookup: None,
+            connection_state_sub: None,
+            ui_message_sub: None,
             gossip_acceptor,
             gossip_producer,
             neighborhood_database,
             consuming_wallet_opt: config.consuming_wallet,
             next_return_route_id: 0,
             initial_neighbors: config.neighbor_configs,
+            neighborhood_dao_opt: None,
+            database_changed: false,
+            neighbor_liveness: HashMap::new(),
+            next_heartbeat_sequence: 0,
+            neighbor_version_summaries: HashMap::new(),
+            gossip_batch_window: config.gossip_batch_window,
+            pending_gossip_targets: HashSet::new(),
+            outbound_gossip_limiter: RateLimiter::new(
+                MAX_OUTBOUND_GOSSIP_PER_NEIGHBOR,
+                GOSSIP_RATE_INTERVAL,
+            ),
+            inbound_gossip_limiter: RateLimiter::new(
+                MAX_INBOUND_GOSSIP_PER_SOURCE,
+                GOSSIP_RATE_INTERVAL,
+            ),
+            diversity: config.diversity,
+            behind_nat: config.behind_nat,
+            relay_clients: HashMap::new(),
+            relay_challenges: HashMap::new(),
+            relay_challenge_ttl: RELAY_CHALLENGE_TTL,
+            connection_state: ConnectionState::Standalone,
+            reported_neighbor_count: 0,
+            debut_queue: vec![],
+            debut_round: 0,
+            debut_sequence: 0,
+            debut_timeout: DEBUT_TIMEOUT,
+            debut_retry_delay: DEBUT_RETRY_DELAY,
             logger: Logger::new("Neighborhood"),
         }
     }
 
+    // Reloads the NeighborhoodDatabase saved by a previous run, then periodically saves changes
+    // to it through the same DAO. Our keys are new every run, so the old root record
+    // isn't restored; instead, its neighbors become our half neighbors.
+    pub fn restore_from(&mut self, neighborhood_dao: Box<dyn NeighborhoodDao>) {
+        let mut remembered_neighbor_keys = vec![];
+        let mut restored_count = 0;
+        let stored_records = match neighborhood_dao.node_records() {
+            Ok(stored_records) => stored_records,
+            Err(e) => {
+                self.logger
+                    .error(format!("Couldn't read saved Node records: {}", e));
+                vec![]
+            }
+        };
+        for stored_result in stored_records {
+            let stored = match stored_result {
+                Ok(stored) => stored,
+                Err(e) => {
+                    self.logger
+                        .warning(format!("Discarding unreadable saved Node record: {}", e));
+                    continue;
+                }
+            };
+            let agr = match AccessibleGossipRecord::try_from(GossipNodeRecord::from(&stored)) {
+                Ok(agr) => agr,
+                Err(e) => {
+                    self.logger.warning(format!(
+                        "Discarding non-deserializable saved Node record: {}",
+                        e
+                    ));
+                    continue;
+                }
+            };
+            if !self.cryptde.verify_signature(
+                &agr.signed_gossip,
+                &agr.signature,
+                &agr.inner.public_key,
+            ) {
+                self.logger.warning(format!(
+                    "Discarding saved record for Node {} with invalid signature",
+                    agr.inner.public_key
+                ));
+                continue;
+            }
+            if stored.root {
+                remembered_neighbor_keys = agr.inner.neighbors.into_iter().collect();
+                continue;
+            }
+            let public_key = agr.inner.public_key.clone();
+            let mut node_record = NodeRecord::from(agr);
+            node_record.set_desirable(stored.desirable);
+            match self.neighborhood_database.add_node(node_record) {
+                Ok(_) => restored_count += 1,
+                Err(e) => self.logger.warning(format!(
+                    "Discarding saved record for Node {}: {:?}",
+                    public_key, e
+                )),
+            }
+        }
+        let remembered_neighbor_count = remembered_neighbor_keys
+            .iter()
+            .filter(|key| {
+                match self.neighborhood_database.node_by_key(key) {
+                    Some(node_record) if node_record.node_addr_opt().is_some() => (),
+                    _ => return false,
+                };
+                match self.neighborhood_database.add_half_neighbor(key) {
+                    Ok(true) => true,
+                    _ => false,
+                }
+            })
+            .count();
+        if remembered_neighbor_count > 0 {
+            let root = self.neighborhood_database.root_mut();
+            root.increment_version();
+            root.regenerate_signed_gossip(self.cryptde);
+        }
+        self.logger.info(format!(
+            "Restored {} saved Nodes, including {} remembered neighbors",
+            restored_count, remembered_neighbor_count
+        ));
+        self.neighborhood_dao_opt = Some(neighborhood_dao);
+        self.save_database();
+    }
+
     pub fn initial_neighbors(&self) -> &Vec<NodeDescriptor> {
         &self.initial_neighbors
     }
@@ -325,27 +664,207 @@ impl Neighborhood {
             from_hopper: addr.clone().recipient::<ExpiredCoresPackage<Gossip>>(),
             dispatcher_node_query: addr.clone().recipient::<DispatcherNodeQueryMessage>(),
             remove_neighbor: addr.clone().recipient::<RemoveNeighborMessage>(),
+            heartbeat_from_hopper: addr.clone().recipient::<ExpiredCoresPackage<Heartbeat>>(),
+            from_relay_client: addr.clone().recipient::<RelayRequestMessage>(),
+            graph_request: addr.clone().recipient::<NeighborhoodGraphRequest>(),
+            set_rate_pack: addr.clone().recipient::<SetRatePackMessage>(),
+            set_initial_neighbors: addr.clone().recipient::<SetInitialNeighborsMessage>(),
+        }
+    }
+
+    // Debuts to the initial neighbors one at a time, in random order, until one answers.
+    fn start_debut_round(&mut self, ctx: &mut Context<Self>) {
+        let mut debut_queue = self.initial_neighbors.clone();
+        rand::thread_rng().shuffle(&mut debut_queue);
+        self.debut_queue = debut_queue;
+        self.debut_to_next(ctx);
+    }
+
+    fn debut_to_next(&mut self, ctx: &mut Context<Self>) {
+        if self.connection_state == ConnectionState::Connected {
+            return;
+        }
+        self.debut_sequence += 1;
+        let debut_sequence = self.debut_sequence;
+        match self.debut_queue.pop() {
+            Some(target) => {
+                self.send_debut(&target);
+                self.set_connection_state(ConnectionState::Debuting {
+                    target: target.to_string(),
+                    round: self.debut_round,
+                });
+                ctx.run_later(self.debut_timeout, move |act, ctx| {
+                    if act.debut_sequence == debut_sequence {
+                        act.logger.warning(format!(
+                            "Debut to {} went unanswered for {:?}",
+                            target, act.debut_timeout
+                        ));
+                        act.debut_to_next(ctx);
+                    }
+                });
+            }
+            None => {
+                let delay = self.debut_retry_delay_for(self.debut_round);
+                self.debut_round += 1;
+                self.logger.warning(format!(
+                    "No bootstrap Node answered our Debut; trying again in {:?}",
+                    delay
+                ));
+                self.set_connection_state(ConnectionState::WaitingToRetry {
+                    round: self.debut_round,
+                    delay_secs: delay.as_secs(),
+                });
+                ctx.run_later(delay, move |act, ctx| {
+                    if act.debut_sequence == debut_sequence {
+                        act.start_debut_round(ctx);
+                    }
+                });
+            }
+        }
+    }
+
+    fn debut_retry_delay_for(&self, round: u32) -> Duration {
+        self.debut_retry_delay
+            .checked_mul(1 << round.min(16))
+            .map(|delay| delay.min(MAX_DEBUT_RETRY_DELAY))
+            .unwrap_or(MAX_DEBUT_RETRY_DELAY)
+    }
+
+    fn send_debut(&self, target: &NodeDescriptor) {
+        let gossip = self
+            .gossip_producer
+            .produce_debut(&self.neighborhood_database);
+        self.hopper_no_lookup
+            .as_ref()
+            .expect("unbound hopper")
+            .try_send(
+                NoLookupIncipientCoresPackage::new(
+                    self.cryptde,
+                    &target.public_key,
+                    &target.node_addr,
+                    MessageType::Gossip(gossip.clone()),
+                )
+                .expect("Key magically disappeared"),
+            )
+            .expect("hopper is dead");
+        self.logger.trace_with(
+            LogFields::new()
+                .public_key(&target.public_key)
+                .field("node_count", gossip.node_records.len()),
+            format!(
+                "Sent Gossip: {}",
+                gossip.to_dot_graph(
+                    self.neighborhood_database.root(),
+                    (&target.public_key, &Some(target.node_addr.clone()))
+                )
+            ),
+        );
+    }
+
+    // We're connected once a neighbor of ours has us as its neighbor too.
+    fn is_connected(&self) -> bool {
+        let root = self.neighborhood_database.root();
+        root.half_neighbor_keys()
+            .into_iter()
+            .flat_map(|key| self.neighborhood_database.node_by_key(key))
+            .any(|neighbor| neighbor.has_half_neighbor(root.public_key()))
+    }
+
+    fn notice_connection(&mut self) {
+        if self.connection_state != ConnectionState::Connected && self.is_connected() {
+            self.debut_sequence += 1;
+            self.debut_queue.clear();
+            self.debut_round = 0;
+            self.logger
+                .info("Joined the Substratum Network".to_string());
+            self.set_connection_state(ConnectionState::Connected);
+        }
+        self.report_neighbor_count_change();
+    }
+
+    fn rejoin_if_alone(&mut self, ctx: &mut Context<Self>) {
+        if self.connection_state != ConnectionState::Connected || self.is_connected() {
+            return;
+        }
+        if self.initial_neighbors.is_empty() {
+            self.set_connection_state(ConnectionState::Standalone);
+            return;
+        }
+        self.logger
+            .warning("Lost touch with all our neighbors; debuting again".to_string());
+        self.set_connection_state(ConnectionState::Standalone);
+        self.start_debut_round(ctx);
+    }
+
+    fn set_connection_state(&mut self, connection_state: ConnectionState) {
+        if self.connection_state == connection_state {
+            return;
+        }
+        self.connection_state = connection_state;
+        self.report_connection_state();
+    }
+
+    fn report_neighbor_count_change(&mut self) {
+        if self.full_neighbor_count() != self.reported_neighbor_count {
+            self.report_connection_state();
         }
     }
 
+    fn report_connection_state(&mut self) {
+        self.reported_neighbor_count = self.full_neighbor_count();
+        if let Some(ref sub) = self.connection_state_sub {
+            sub.try_send(ConnectionStateMessage {
+                state: self.connection_state.clone(),
+                neighbor_count: self.reported_neighbor_count,
+            })
+            .expect("UiGateway is dead");
+        }
+    }
+
+    fn full_neighbor_count(&self) -> usize {
+        self.neighborhood_database
+            .root()
+            .full_neighbor_keys(&self.neighborhood_database)
+            .len()
+    }
+
     fn log_incoming_gossip(&self, incoming_gossip: &Gossip, gossip_source: IpAddr) {
         let source = match self.neighborhood_database.node_by_ip(&gossip_source) {
             Some(node) => DotGossipEndpoint::from(node),
             None => DotGossipEndpoint::from(gossip_source),
         };
-        self.logger.trace(format!(
-            "Received Gossip: {}",
-            incoming_gossip.to_dot_graph(source, self.neighborhood_database.root())
-        ));
+        self.logger.trace_with(
+            LogFields::new()
+                .field("source_ip", gossip_source.to_string())
+                .field("node_count", incoming_gossip.node_records.len()),
+            format!(
+                "Received Gossip: {}",
+                incoming_gossip.to_dot_graph(source, self.neighborhood_database.root())
+            ),
+        );
     }
 
     fn handle_gossip(&mut self, incoming_gossip: Gossip, gossip_source: IpAddr) {
-        self.logger.info(format!(
-            "Processing Gossip about {} Nodes",
-            incoming_gossip.node_records.len()
-        ));
+        if !self
+            .inbound_gossip_limiter
+            .admit(&gossip_source, Instant::now())
+        {
+            self.report_excess_inbound_gossip(gossip_source);
+            return;
+        }
+        self.logger.info_with(
+            LogFields::new()
+                .field("source_ip", gossip_source.to_string())
+                .field("node_count", incoming_gossip.node_records.len()),
+            format!(
+                "Processing Gossip about {} Nodes",
+                incoming_gossip.node_records.len()
+            ),
+        );
 
         let record_count = incoming_gossip.node_records.len();
+        let version_summary_opt = incoming_gossip.version_summary_opt;
+        let delta = incoming_gossip.delta;
         let agrs: Vec<AccessibleGossipRecord> = incoming_gossip
             .node_records
             .into_iter()
@@ -378,23 +897,65 @@ impl Neighborhood {
             return;
         }
 
-        self.handle_agrs(agrs, gossip_source);
+        self.handle_agrs(agrs, version_summary_opt, delta, gossip_source);
         self.announce_gossip_handling_completion(record_count);
     }
 
-    fn handle_agrs(&mut self, agrs: Vec<AccessibleGossipRecord>, gossip_source: IpAddr) {
+    fn handle_agrs(
+        &mut self,
+        agrs: Vec<AccessibleGossipRecord>,
+        version_summary_opt: Option<BTreeMap<PublicKey, u32>>,
+        delta: bool,
+        gossip_source: IpAddr,
+    ) {
         let ignored_node_name = self.gossip_source_name(&agrs, gossip_source);
         let gossip_record_count = agrs.len();
-        let acceptance_result =
+        let delta_versions: Vec<(PublicKey, u32)> = if delta {
+            agrs.iter()
+                .map(|agr| (agr.inner.public_key.clone(), agr.inner.version))
+                .collect()
+        } else {
+            vec![]
+        };
+        let acceptance_result = if version_summary_opt.is_some() || delta {
+            self.gossip_acceptor.handle_standard(
+                &mut self.neighborhood_database,
+                agrs,
+                gossip_source,
+            )
+        } else {
             self.gossip_acceptor
-                .handle(&mut self.neighborhood_database, agrs, gossip_source);
+                .handle(&mut self.neighborhood_database, agrs, gossip_source)
+        };
+        let source_key_opt = self
+            .neighborhood_database
+            .node_by_ip(&gossip_source)
+            .map(|source_node| source_node.public_key().clone());
+        if let Some(source_key) = source_key_opt {
+            match version_summary_opt {
+                Some(version_summary) => {
+                    self.neighbor_version_summaries.insert(
+                        source_key,
+                        NeighborVersionSummary::new(version_summary, Instant::now()),
+                    );
+                }
+                None => {
+                    if let Some(summary) = self.neighbor_version_summaries.get_mut(&source_key) {
+                        delta_versions
+                            .iter()
+                            .for_each(|(key, version)| summary.note_version(key, *version));
+                    }
+                }
+            }
+        }
         match acceptance_result {
             GossipAcceptanceResult::Accepted(gossip_triples) => {
                 self.logger.debug(format!(
                     "Current database: {}",
                     self.neighborhood_database.to_dot_graph()
                 ));
-                self.handle_gossip_acceptance(gossip_triples)
+                self.handle_gossip_acceptance(gossip_triples);
+                self.database_changed = true;
             }
             GossipAcceptanceResult::Relay(next_debut, relay_target, relay_node_addr) => {
                 self.handle_gossip_relay(next_debut, relay_target, relay_node_addr, gossip_source)
@@ -405,51 +966,563 @@ impl Neighborhood {
         }
     }
 
-    fn announce_gossip_handling_completion(&self, record_count: usize) {
-        self.logger.info(format!(
-            "Finished processing Gossip about {} Nodes",
-            record_count,
-        ));
+    fn send_heartbeats(&mut self) {
+        let neighbor_keys: Vec<PublicKey> = self
+            .neighborhood_database
+            .root()
+            .half_neighbor_keys()
+            .into_iter()
+            .filter(|key| match self.neighborhood_database.node_by_key(key) {
+                Some(node_record) => node_record.node_addr_opt().is_some(),
+                None => false,
+            })
+            .cloned()
+            .collect();
+        self.neighbor_liveness
+            .retain(|key, _| neighbor_keys.contains(key));
+        let mut dead_neighbor_keys = vec![];
+        for neighbor_key in neighbor_keys {
+            let missed_heartbeats = {
+                let liveness = self
+                    .neighbor_liveness
+                    .entry(neighbor_key.clone())
+                    .or_insert_with(NeighborLiveness::default);
+                if liveness.outstanding_ping_opt.is_some() {
+                    liveness.missed_heartbeats += 1;
+                }
+                liveness.missed_heartbeats
+            };
+            if missed_heartbeats >= MISSED_HEARTBEAT_LIMIT {
+                dead_neighbor_keys.push(neighbor_key);
+                continue;
+            }
+            let sequence = self.next_heartbeat_sequence;
+            self.next_heartbeat_sequence = self.next_heartbeat_sequence.wrapping_add(1);
+            if let Some(liveness) = self.neighbor_liveness.get_mut(&neighbor_key) {
+                liveness.outstanding_ping_opt = Some((sequence, Instant::now()));
+            }
+            self.send_heartbeat(&neighbor_key, Heartbeat::Ping(sequence));
+        }
+        if !dead_neighbor_keys.is_empty() {
+            self.remove_dead_neighbors(dead_neighbor_keys);
+        }
+        // Our relays forget us if the connection we keep open to them is replaced, so remind them.
+        self.send_relay_requests();
     }
 
-    fn gossip_to_neighbors(&mut self) {
-        self.neighborhood_database
-            .root_mut()
-            .regenerate_signed_gossip(self.cryptde);
-        let neighbors = self.neighborhood_database.root().half_neighbor_keys();
-        neighbors.iter().for_each(|neighbor| {
-            let gossip = self
-                .gossip_producer
-                .produce(&self.neighborhood_database, neighbor);
-            let gossip_len = gossip.node_records.len();
-            let route = self.create_single_hop_route(neighbor);
-            let package =
-                IncipientCoresPackage::new(self.cryptde, route, gossip.clone().into(), neighbor)
-                    .expect("Key magically disappeared");
-            self.logger.info(format!(
-                "Sending update Gossip about {} Nodes to Node {}",
-                gossip_len, neighbor
-            ));
-            self.hopper
-                .as_ref()
-                .expect("unbound hopper")
-                .try_send(package)
-                .expect("hopper is dead");
-            self.logger.trace(format!(
-                "Sent Gossip: {}",
-                gossip.to_dot_graph(
-                    self.neighborhood_database.root(),
-                    self.neighborhood_database
-                        .node_by_key(*neighbor)
-                        .expect("Node magically disappeared")
-                )
-            ));
-        });
+    fn remove_dead_neighbors(&mut self, dead_neighbor_keys: Vec<PublicKey>) {
+        let mut db_changed = false;
+        for dead_neighbor_key in dead_neighbor_keys {
+            self.neighbor_liveness.remove(&dead_neighbor_key);
+            self.relay_clients.remove(&dead_neighbor_key);
+            match self
+                .neighborhood_database
+                .remove_neighbor(&dead_neighbor_key)
+            {
+                Err(s) => self.logger.error(s),
+                Ok(changed) => {
+                    self.logger.warning(format!(
+                        "Removing neighbor {} after {} unanswered heartbeats",
+                        dead_neighbor_key, MISSED_HEARTBEAT_LIMIT
+                    ));
+                    db_changed = changed || db_changed;
+                }
+            }
+        }
+        if db_changed {
+            self.gossip_to_neighbors();
+            self.database_changed = true;
+        }
     }
 
-    fn create_single_hop_route(&self, destination: &PublicKey) -> Route {
-        Route::one_way(
-            RouteSegment::new(
+    fn handle_heartbeat(&mut self, heartbeat: Heartbeat, source_ip: IpAddr) {
+        let source_key = match self.neighborhood_database.node_by_ip(&source_ip) {
+            Some(node_record) => node_record.public_key().clone(),
+            None => {
+                self.logger.debug(format!(
+                    "Ignoring {:?} from unknown Node at {}",
+                    heartbeat, source_ip
+                ));
+                return;
+            }
+        };
+        match heartbeat {
+            Heartbeat::Ping(sequence) => {
+                self.send_heartbeat(&source_key, Heartbeat::Pong(sequence))
+            }
+            Heartbeat::Pong(sequence) => {
+                let round_trip_time = match self.neighbor_liveness.get_mut(&source_key) {
+                    Some(liveness) => match liveness.outstanding_ping_opt {
+                        Some((expected_sequence, sent_at)) if expected_sequence == sequence => {
+                            liveness.outstanding_ping_opt = None;
+                            liveness.missed_heartbeats = 0;
+                            sent_at.elapsed()
+                        }
+                        _ => {
+                            self.logger.debug(format!(
+                                "Ignoring stale Pong {} from {}",
+                                sequence, source_key
+                            ));
+                            return;
+                        }
+                    },
+                    None => {
+                        self.logger.debug(format!(
+                            "Ignoring unsolicited Pong {} from {}",
+                            sequence, source_key
+                        ));
+                        return;
+                    }
+                };
+                if let Some(node_record) = self.neighborhood_database.node_by_key_mut(&source_key) {
+                    node_record.set_round_trip_time(round_trip_time);
+                }
+                self.logger.trace(format!(
+                    "Neighbor {} answered heartbeat in {:?}",
+                    source_key, round_trip_time
+                ));
+            }
+        }
+    }
+
+    fn send_heartbeat(&self, target_key: &PublicKey, heartbeat: Heartbeat) {
+        self.send_single_hop(target_key, heartbeat.into());
+    }
+
+    fn send_single_hop(&self, target_key: &PublicKey, payload: MessageType) {
+        let route = self.create_single_hop_route(target_key);
+        let package = match IncipientCoresPackage::new(self.cryptde, route, payload, target_key) {
+            Ok(p) => p,
+            Err(e) => {
+                self.logger.error(e);
+                return;
+            }
+        };
+        self.hopper
+            .as_ref()
+            .expect("unbound hopper")
+            .try_send(package)
+            .expect("hopper is dead");
+    }
+
+    // Behind NAT, our full neighbors with public addresses are our relays. Returns true if the
+    // root record changed.
+    fn update_relays(&mut self) -> bool {
+        if !self.behind_nat {
+            return false;
+        }
+        let relays: BTreeSet<PublicKey> = self
+            .neighborhood_database
+            .root()
+            .full_neighbors(&self.neighborhood_database)
+            .into_iter()
+            .filter(|node_record| node_record.node_addr_opt().is_some())
+            .map(|node_record| node_record.public_key().clone())
+            .collect();
+        let root = self.neighborhood_database.root_mut();
+        if !root.set_relays(relays) {
+            return false;
+        }
+        root.increment_version();
+        root.regenerate_signed_gossip(self.cryptde);
+        self.logger.info(format!(
+            "Relays changed to {:?}",
+            self.neighborhood_database.root().relays()
+        ));
+        true
+    }
+
+    // Neighbors hear about the new rates with the next Gossip, which carries the root record
+    // re-signed at its new version.
+    fn set_rate_pack(&mut self, rate_pack: RatePack) {
+        let root = self.neighborhood_database.root_mut();
+        if !root.set_rate_pack(rate_pack) {
+            return;
+        }
+        root.increment_version();
+        self.logger.info(format!(
+            "Rate pack changed to {:?}",
+            self.neighborhood_database.root().rate_pack()
+        ));
+        self.gossip_to_neighbors();
+        self.database_changed = true;
+    }
+
+    fn send_relay_requests(&self) {
+        let root = self.neighborhood_database.root();
+        root.relays().iter().for_each(|relay_key| {
+            let relay_request = RelayRequest::Request {
+                node_record: GossipNodeRecord::from(root.clone()),
+            };
+            self.send_single_hop(relay_key, relay_request.into());
+        });
+    }
+
+    fn handle_relay_request(&mut self, node_record: GossipNodeRecord, client_addr: SocketAddr) {
+        let agr = match AccessibleGossipRecord::try_from(node_record) {
+            Ok(agr) => agr,
+            Err(e) => {
+                self.logger.error(format!(
+                    "Received non-deserializable RelayRequest from {}: {}",
+                    client_addr, e
+                ));
+                return;
+            }
+        };
+        if !self
+            .cryptde
+            .verify_signature(&agr.signed_gossip, &agr.signature, &agr.inner.public_key)
+        {
+            self.logger.error(format!(
+                "Received RelayRequest with invalid signature from {}",
+                client_addr
+            ));
+            return;
+        }
+        let client_key = agr.inner.public_key;
+        if !agr
+            .inner
+            .relays
+            .contains(self.neighborhood_database.root().public_key())
+        {
+            self.logger.warning(format!(
+                "Refusing to relay for {}: its record doesn't list us as a relay",
+                client_key
+            ));
+            return;
+        }
+        // A signed record can be replayed by anyone, so make sure the Node itself is at the
+        // other end of this connection before routing its packages over it.
+        let challenge = rand::random::<u64>();
+        let now = Instant::now();
+        let ttl = self.relay_challenge_ttl;
+        self.relay_challenges
+            .retain(|_, (_, _, sent_at)| now.duration_since(*sent_at) < ttl);
+        self.relay_challenges
+            .insert(client_addr, (client_key.clone(), challenge, now));
+        let package = match NoLookupIncipientCoresPackage::new(
+            self.cryptde,
+            &client_key,
+            &NodeAddr::from(&client_addr),
+            RelayRequest::Challenge(challenge).into(),
+        ) {
+            Ok(p) => p,
+            Err(e) => {
+                self.logger.error(e);
+                return;
+            }
+        };
+        self.hopper_no_lookup
+            .as_ref()
+            .expect("No-lookup Hopper is unbound")
+            .try_send(package)
+            .expect("Hopper is dead");
+    }
+
+    fn handle_relay_challenge(&self, challenge: u64, relay_addr: SocketAddr) {
+        let root = self.neighborhood_database.root();
+        let relay_key = match self
+            .neighborhood_database
+            .node_by_ip(&relay_addr.ip())
+            .map(|node_record| node_record.public_key())
+        {
+            Some(relay_key) if root.relays().contains(relay_key) => relay_key.clone(),
+            _ => {
+                self.logger.warning(format!(
+                    "Ignoring RelayRequest challenge from {}: it isn't one of our relays",
+                    relay_addr
+                ));
+                return;
+            }
+        };
+        let signature = match self
+            .cryptde
+            .sign(&RelayRequest::challenge_data(&relay_key, challenge))
+        {
+            Ok(signature) => signature,
+            Err(e) => {
+                self.logger.error(format!(
+                    "Couldn't sign RelayRequest challenge from {}: {:?}",
+                    relay_key, e
+                ));
+                return;
+            }
+        };
+        self.send_single_hop(&relay_key, RelayRequest::Response { signature }.into());
+    }
+
+    fn handle_relay_response(&mut self, signature: CryptData, client_addr: SocketAddr) {
+        let (client_key, challenge) = match self.relay_challenges.remove(&client_addr) {
+            Some((client_key, challenge, sent_at)) => {
+                if sent_at.elapsed() >= self.relay_challenge_ttl {
+                    self.logger.warning(format!(
+                        "Ignoring RelayRequest response from {}: the challenge sent there expired",
+                        client_addr
+                    ));
+                    return;
+                }
+                (client_key, challenge)
+            }
+            None => {
+                self.logger.warning(format!(
+                    "Ignoring RelayRequest response from {}: no challenge was sent there",
+                    client_addr
+                ));
+                return;
+            }
+        };
+        let challenge_data = RelayRequest::challenge_data(&self.cryptde.public_key(), challenge);
+        if !self
+            .cryptde
+            .verify_signature(&challenge_data, &signature, &client_key)
+        {
+            self.logger.warning(format!(
+                "Refusing to relay for {}: the challenge sent to {} wasn't signed by it",
+                client_key, client_addr
+            ));
+            return;
+        }
+        if self.relay_clients.insert(client_key.clone(), client_addr) != Some(client_addr) {
+            self.logger.info(format!(
+                "Relaying for {} over connection from {}",
+                client_key, client_addr
+            ));
+        }
+    }
+
+    // Where to send CORES packages for the Node: the connection it keeps open to us if we're
+    // its relay, otherwise its own address.
+    fn reachable_node_addr_opt(&self, node_record: &NodeRecord) -> Option<NodeAddr> {
+        match self.relay_clients.get(node_record.public_key()) {
+            Some(client_addr) => Some(NodeAddr::from(client_addr)),
+            None => node_record.node_addr_opt(),
+        }
+    }
+
+    fn save_database_if_changed(&mut self) {
+        if self.database_changed {
+            self.save_database();
+        }
+    }
+
+    fn save_database(&mut self) {
+        self.database_changed = false;
+        let neighborhood_dao = match self.neighborhood_dao_opt {
+            Some(ref neighborhood_dao) => neighborhood_dao,
+            None => return,
+        };
+        let root_key = self.neighborhood_database.root().public_key();
+        let stored_node_records = self
+            .neighborhood_database
+            .keys()
+            .into_iter()
+            .flat_map(|key| self.neighborhood_database.node_by_key(key))
+            .map(|node_record| {
+                StoredNodeRecord::new(node_record, node_record.public_key() == root_key)
+            })
+            .collect::<Vec<StoredNodeRecord>>();
+        if let Err(e) = neighborhood_dao.replace_node_records(&stored_node_records) {
+            self.logger
+                .error(format!("Couldn't save Neighborhood database: {}", e));
+        }
+    }
+
+    fn announce_gossip_handling_completion(&self, record_count: usize) {
+        self.logger.info_with(
+            LogFields::new().field("node_count", record_count),
+            format!("Finished processing Gossip about {} Nodes", record_count,),
+        );
+    }
+
+    // Marks every neighbor as due for update Gossip. Unless there's a batch window, the Gossip
+    // goes out right away; otherwise, changes made during the window go out together at its end.
+    fn gossip_to_neighbors(&mut self) {
+        if self.update_relays() {
+            self.send_relay_requests();
+        }
+        self.neighborhood_database
+            .root_mut()
+            .regenerate_signed_gossip(self.cryptde);
+        let neighbors: Vec<PublicKey> = self
+            .neighborhood_database
+            .root()
+            .half_neighbor_keys()
+            .into_iter()
+            .cloned()
+            .collect();
+        self.pending_gossip_targets.extend(neighbors);
+        if self.gossip_batch_window == Duration::from_millis(0) {
+            self.flush_pending_gossip();
+        }
+    }
+
+    fn flush_pending_gossip(&mut self) {
+        let now = Instant::now();
+        self.prune_gossip_limiters(now);
+        self.expire_version_summaries(now);
+        if self.pending_gossip_targets.is_empty() {
+            return;
+        }
+        self.neighborhood_database
+            .root_mut()
+            .regenerate_signed_gossip(self.cryptde);
+        let neighbors: Vec<PublicKey> = self
+            .neighborhood_database
+            .root()
+            .half_neighbor_keys()
+            .into_iter()
+            .filter(|key| self.pending_gossip_targets.contains(*key))
+            .cloned()
+            .collect();
+        self.pending_gossip_targets.clear();
+        neighbors.iter().for_each(|neighbor| {
+            if !self.outbound_gossip_limiter.admit(neighbor, now) {
+                self.report_excess_outbound_gossip(neighbor);
+                self.pending_gossip_targets.insert(neighbor.clone());
+                return;
+            }
+            let gossip = match self.neighbor_version_summaries.get(neighbor) {
+                Some(version_summary) => match self.gossip_producer.produce_delta(
+                    &self.neighborhood_database,
+                    neighbor,
+                    &version_summary.versions,
+                ) {
+                    Some(gossip) => gossip,
+                    None => {
+                        self.logger.debug_with(
+                            LogFields::new().public_key(neighbor),
+                            format!(
+                                "Node {} already has everything we know; sending no Gossip",
+                                neighbor
+                            ),
+                        );
+                        return;
+                    }
+                },
+                None => self
+                    .gossip_producer
+                    .produce(&self.neighborhood_database, neighbor),
+            };
+            let gossip_len = gossip.node_records.len();
+            let route = self.create_single_hop_route(neighbor);
+            let package =
+                IncipientCoresPackage::new(self.cryptde, route, gossip.clone().into(), neighbor)
+                    .expect("Key magically disappeared");
+            self.logger.info_with(
+                LogFields::new()
+                    .public_key(neighbor)
+                    .field("node_count", gossip_len),
+                format!(
+                    "Sending update Gossip about {} Nodes to Node {}",
+                    gossip_len, neighbor
+                ),
+            );
+            self.hopper
+                .as_ref()
+                .expect("unbound hopper")
+                .try_send(package)
+                .expect("hopper is dead");
+            self.logger.trace_with(
+                LogFields::new()
+                    .public_key(neighbor)
+                    .field("node_count", gossip_len),
+                format!(
+                    "Sent Gossip: {}",
+                    gossip.to_dot_graph(
+                        self.neighborhood_database.root(),
+                        self.neighborhood_database
+                            .node_by_key(neighbor)
+                            .expect("Node magically disappeared")
+                    )
+                ),
+            );
+        });
+    }
+
+    fn expire_version_summaries(&mut self, now: Instant) {
+        let root = self.neighborhood_database.root();
+        self.neighbor_version_summaries.retain(|key, summary| {
+            root.has_half_neighbor(key)
+                && now.duration_since(summary.received) < VERSION_SUMMARY_LIFETIME
+        });
+    }
+
+    fn report_excess_outbound_gossip(&self, neighbor: &PublicKey) {
+        // Report only the first excess in each interval, to keep a Gossip storm out of the log
+        if self.outbound_gossip_limiter.refused_in_interval(neighbor) == 1 {
+            self.logger.warning_with(
+                LogFields::new()
+                    .public_key(neighbor)
+                    .field("total_refused", self.outbound_gossip_limiter.total_refused(neighbor)),
+                format!(
+                "Holding back Gossip to Node {}: more than {} changes in {:?} ({} held back so far); possible Gossip storm",
+                neighbor,
+                self.outbound_gossip_limiter.limit(),
+                self.outbound_gossip_limiter.interval(),
+                self.outbound_gossip_limiter.total_refused(neighbor)
+                ),
+            );
+        }
+    }
+
+    // Sums up each excess once its source has calmed down, since its count is forgotten then.
+    fn prune_gossip_limiters(&mut self, now: Instant) {
+        self.outbound_gossip_limiter
+            .prune(now)
+            .into_iter()
+            .for_each(|(neighbor, total_refused)| {
+                self.logger.info_with(
+                    LogFields::new()
+                        .public_key(&neighbor)
+                        .field("total_refused", total_refused),
+                    format!(
+                        "Gossip to Node {} is back within its limit after {} changes were held back",
+                        neighbor, total_refused
+                    ),
+                )
+            });
+        self.inbound_gossip_limiter.prune(now).into_iter().for_each(
+            |(gossip_source, total_refused)| {
+                self.logger.info_with(
+                    LogFields::new()
+                        .field("source_ip", gossip_source.to_string())
+                        .field("total_refused", total_refused),
+                    format!(
+                        "Gossip from {} is back within its limit after {} messages were dropped",
+                        gossip_source, total_refused
+                    ),
+                )
+            },
+        );
+    }
+
+    fn report_excess_inbound_gossip(&self, gossip_source: IpAddr) {
+        if self
+            .inbound_gossip_limiter
+            .refused_in_interval(&gossip_source)
+            == 1
+        {
+            self.logger.warning_with(
+                LogFields::new()
+                    .field("source_ip", gossip_source.to_string())
+                    .field(
+                        "total_refused",
+                        self.inbound_gossip_limiter.total_refused(&gossip_source),
+                    ),
+                format!(
+                "Dropping Gossip from {}: more than {} messages in {:?} ({} dropped so far); possible abuse",
+                gossip_source,
+                self.inbound_gossip_limiter.limit(),
+                self.inbound_gossip_limiter.interval(),
+                self.inbound_gossip_limiter.total_refused(&gossip_source)
+                ),
+            );
+        }
+    }
+
+    fn create_single_hop_route(&self, destination: &PublicKey) -> Route {
+        Route::one_way(
+            RouteSegment::new(
                 vec![&self.cryptde.public_key(), destination],
                 Component::Neighborhood,
             ),
@@ -482,6 +1555,7 @@ impl Neighborhood {
                 vec![ExpectedService::Nothing, ExpectedService::Nothing],
                 return_route_id,
             ),
+            exit_supports_compression: false,
         }
     }
 
@@ -501,6 +1575,8 @@ impl Neighborhood {
             msg.minimum_hop_count,
             msg.target_component,
             false,
+            &msg.avoided_keys,
+            &msg.constraints,
         )?;
         self.logger.debug(format!("Route over: {:?}", over));
         let back = self.make_route_segment(
@@ -510,6 +1586,8 @@ impl Neighborhood {
             msg.minimum_hop_count,
             msg.return_component_opt.expect("No return component"),
             true,
+            &msg.avoided_keys,
+            &msg.constraints,
         )?;
         self.logger.debug(format!("Route back: {:?}", back));
         self.compose_route_query_response(over, back)
@@ -544,6 +1622,13 @@ impl Neighborhood {
             Err(e) => return Err(e),
         };
 
+        let exit_supports_compression = over
+            .keys
+            .last()
+            .and_then(|exit_key| self.neighborhood_database.node_by_key(exit_key))
+            .map(|exit_node| exit_node.supports_compression())
+            .unwrap_or(false);
+
         let return_route_id = self.advance_return_route_id();
         Ok(RouteQueryResponse {
             route: Route::round_trip(
@@ -559,6 +1644,7 @@ impl Neighborhood {
                 expected_response_services,
                 return_route_id,
             ),
+            exit_supports_compression,
         })
     }
 
@@ -570,18 +1656,53 @@ impl Neighborhood {
         minimum_hop_count: usize,
         target_component: Component,
         next_door_allowed: bool,
+        avoided_keys: &[PublicKey],
+        constraints: &RouteConstraints,
     ) -> Result<RouteSegment, String> {
-        let mut node_seqs =
-            self.complete_routes(vec![origin], target, minimum_hop_count, next_door_allowed);
+        let mut node_seqs = match target {
+            // With no target, the route must end at one of the pinned exits, if there are any.
+            None if !constraints.exit_keys.is_empty() => constraints
+                .exit_keys
+                .iter()
+                .flat_map(|exit_key| {
+                    self.complete_routes(
+                        vec![origin],
+                        Some(exit_key),
+                        minimum_hop_count,
+                        next_door_allowed,
+                        avoided_keys,
+                        constraints,
+                    )
+                })
+                .collect(),
+            _ => self.complete_routes(
+                vec![origin],
+                target,
+                minimum_hop_count,
+                next_door_allowed,
+                avoided_keys,
+                constraints,
+            ),
+        };
 
         if node_seqs.is_empty() {
             let target_str = match target {
                 Some(t) => format!(" {}", t),
                 None => String::new(),
             };
+            let constraints_str = if constraints.is_empty() {
+                ""
+            } else {
+                " within route constraints"
+            };
             Err(format!(
-                "Couldn't find any routes: at least {}-hop from {} to {:?} at {:?}{}",
-                minimum_hop_count, origin, target_component, target_type, target_str
+                "Couldn't find any routes: at least {}-hop from {} to {:?} at {:?}{}{}",
+                minimum_hop_count,
+                origin,
+                target_component,
+                target_type,
+                target_str,
+                constraints_str
             ))
         } else {
             // When the target is Some all exit nodes will be the target and it is not optimal to sort.
@@ -701,71 +1822,222 @@ impl Neighborhood {
         return_route_id
     }
 
-    // Main recursive routing engine. Supply origin key as single-element vector in prefix,
-    // target key, if any, in target, and minimum hop count in hops_remaining. Return value is
-    // a list of all the node sequences that will either go from the origin to the target in
-    // hops_remaining or more hops with no cycles, or from the origin hops_remaining hops out into
-    // the Substratum Network. No round trips; if you want a round trip, call this method twice.
-    // If the return value is empty, no qualifying route was found.
+    // Main routing engine. Supply origin key as single-element vector in prefix, target key, if
+    // any, in target, and minimum hop count in hops_remaining. Return value is a list of node
+    // sequences that will either go from the origin to the target in hops_remaining or more hops
+    // with no cycles, or from the origin hops_remaining hops out into the Substratum Network,
+    // without routing through any of the avoided keys. No round trips; if you want a round trip,
+    // call this method twice. Nodes excluded by the constraints don't appear in the routes at all,
+    // except as the origin. If the return value is empty, no qualifying route was found.
+    //
+    // The search is a depth-first walk that tries neighbors in random order and stops when it
+    // has ROUTE_SEARCH_CANDIDATE_LIMIT routes or has examined ROUTE_SEARCH_STEP_BUDGET partial
+    // routes, so its cost doesn't grow with the size of the NeighborhoodDatabase. When there's a
+    // target, partial routes that can't reach it within ROUTE_SEARCH_DETOUR_HOPS of the shortest
+    // qualifying length are abandoned.
     fn complete_routes<'a>(
         &'a self,
         prefix: Vec<&'a PublicKey>,
         target: Option<&'a PublicKey>,
         hops_remaining: usize,
         next_door_exit_allowed: bool,
+        avoided_keys: &[PublicKey],
+        constraints: &RouteConstraints,
     ) -> Vec<Vec<&'a PublicKey>> {
+        let (hop_counts_to_target, max_hop_count) = match target {
+            Some(target_key) => {
+                if self.is_excluded(target_key, constraints) {
+                    return vec![];
+                }
+                let origin = prefix.last().expect("Empty prefix");
+                match self.hop_counts_to(target_key, origin, hops_remaining, constraints) {
+                    Some((hop_counts, max_hop_count)) => {
+                        (hop_counts, (prefix.len() - 1) + max_hop_count)
+                    }
+                    None => return vec![],
+                }
+            }
+            None => (HashMap::new(), usize::max_value()),
+        };
+        let mut search = RouteSearch {
+            target,
+            next_door_exit_allowed,
+            avoided_keys,
+            constraints,
+            hop_counts_to_target,
+            max_hop_count,
+            steps_remaining: ROUTE_SEARCH_STEP_BUDGET,
+            routes: vec![],
+        };
+        self.search_routes(&mut search, prefix, hops_remaining);
+        search.routes
+    }
+
+    fn search_routes<'a>(
+        &'a self,
+        search: &mut RouteSearch<'a, '_>,
+        prefix: Vec<&'a PublicKey>,
+        hops_remaining: usize,
+    ) {
+        if search.steps_remaining == 0 || search.routes.len() >= ROUTE_SEARCH_CANDIDATE_LIMIT {
+            return;
+        }
+        search.steps_remaining -= 1;
         let previous_node = self
             .neighborhood_database
             .node_by_key(prefix.last().expect("Empty prefix"))
             .expect("Node magically disappeared");
         // Check to see if we're done. If we are, all three of these qualifications will pass.
         if self.route_length_qualifies(hops_remaining)
-            && self.last_key_qualifies(previous_node, target)
-            && Self::validate_last_next_door_exit(previous_node, next_door_exit_allowed)
+            && self.last_key_qualifies(previous_node, search.target)
+            && Self::validate_last_next_door_exit(previous_node, search.next_door_exit_allowed)
         {
-            vec![prefix]
+            search.routes.push(prefix);
+            return;
         }
         // If we're not done, then last_node is for routing, and bootstrap Nodes don't route.
-        else if previous_node.is_bootstrap_node() {
-            vec![]
-        } else if hops_remaining == 0
-            && !Self::validate_last_next_door_exit(previous_node, next_door_exit_allowed)
+        if previous_node.is_bootstrap_node() {
+            return;
+        }
+        // don't return routes with next door exit nodes
+        if hops_remaining == 0
+            && !Self::validate_last_next_door_exit(previous_node, search.next_door_exit_allowed)
         {
-            //         don't return routes with next door exit nodes
-            vec![]
-        } else {
-            // Go through all the neighbors and compute shorter routes through all the ones we're not already using.
-            previous_node
-                .full_neighbors(&self.neighborhood_database)
-                .iter()
-                .filter(|node_record| !prefix.contains(&node_record.public_key()))
-                .flat_map(|node_record| {
-                    let mut new_prefix = prefix.clone();
-                    new_prefix.push(node_record.public_key());
-
-                    let new_hops_remaining = if hops_remaining == 0 {
-                        0
-                    } else {
-                        hops_remaining - 1
-                    };
+            return;
+        }
+        // Avoided Nodes may end a route, but they don't route.
+        if prefix.len() > 1 && search.avoided_keys.contains(previous_node.public_key()) {
+            return;
+        }
+        // Try shorter routes through the neighbors we're not already using.
+        let mut neighbor_keys: Vec<&'a PublicKey> = previous_node
+            .full_neighbors(&self.neighborhood_database)
+            .into_iter()
+            .map(|node_record| node_record.public_key())
+            .filter(|key| !prefix.contains(key))
+            .filter(|key| !self.is_excluded(key, search.constraints))
+            .filter(|key| self.is_diverse_from(key, &prefix))
+            .filter(|key| self.is_reachable_from(key, previous_node.public_key()))
+            .filter(|key| search.can_reach_target_in_time(prefix.len(), key))
+            .collect();
+        rand::thread_rng().shuffle(&mut neighbor_keys);
+        let new_hops_remaining = hops_remaining.saturating_sub(1);
+        neighbor_keys.into_iter().for_each(|key| {
+            let mut new_prefix = prefix.clone();
+            new_prefix.push(key);
+            self.search_routes(search, new_prefix, new_hops_remaining);
+        });
+    }
 
-                    self.complete_routes(
-                        new_prefix.clone(),
-                        target,
-                        new_hops_remaining,
-                        next_door_exit_allowed,
-                    )
-                })
-                .collect()
+    // Breadth-first hop counts to the target from the Nodes close enough to it to be on a route
+    // from the origin, along with the longest route worth considering. None if the origin can't
+    // reach the target, or is too far away to find within ROUTE_SEARCH_STEP_BUDGET steps.
+    fn hop_counts_to<'a>(
+        &'a self,
+        target: &'a PublicKey,
+        origin: &PublicKey,
+        min_hop_count: usize,
+        constraints: &RouteConstraints,
+    ) -> Option<(HashMap<&'a PublicKey, usize>, usize)> {
+        let max_hop_count_for = |hop_counts: &HashMap<&PublicKey, usize>| {
+            hop_counts
+                .get(origin)
+                .map(|hop_count| max(min_hop_count, *hop_count) + ROUTE_SEARCH_DETOUR_HOPS)
+        };
+        let mut hop_counts = HashMap::new();
+        hop_counts.insert(target, 0);
+        let mut queue = VecDeque::new();
+        queue.push_back(target);
+        let mut steps_remaining = ROUTE_SEARCH_STEP_BUDGET;
+        while let Some(key) = queue.pop_front() {
+            let hop_count = hop_counts[key];
+            match max_hop_count_for(&hop_counts) {
+                Some(max_hop_count) if hop_count >= max_hop_count => break,
+                _ => (),
+            }
+            if steps_remaining == 0 {
+                break;
+            }
+            steps_remaining -= 1;
+            let node = match self.neighborhood_database.node_by_key(key) {
+                Some(node) => node,
+                None => continue,
+            };
+            node.full_neighbors(&self.neighborhood_database)
+                .into_iter()
+                .for_each(|neighbor| {
+                    let neighbor_key = neighbor.public_key();
+                    if !hop_counts.contains_key(neighbor_key)
+                        && !self.is_excluded(neighbor_key, constraints)
+                    {
+                        hop_counts.insert(neighbor_key, hop_count + 1);
+                        queue.push_back(neighbor_key);
+                    }
+                });
         }
+        max_hop_count_for(&hop_counts).map(|max_hop_count| (hop_counts, max_hop_count))
     }
 
-    fn handle_gossip_acceptance(&mut self, gossip_triples: Vec<(Gossip, PublicKey, NodeAddr)>) {
-        if gossip_triples.is_empty() {
-            // If the GossipAcceptor generated no response Gossip, then this is a standard-Gossip
-            // response to changes caused by Gossip received, and should consist of a broadcast
-            // of our NeighborhoodDatabase to all our neighbors.
-            self.gossip_to_neighbors()
+    // Our own Node is never excluded: every route starts or ends with it.
+    fn is_excluded(&self, key: &PublicKey, constraints: &RouteConstraints) -> bool {
+        if key == self.neighborhood_database.root().public_key() {
+            return false;
+        }
+        let ip_addr_opt = self
+            .neighborhood_database
+            .node_by_key(key)
+            .and_then(|node_record| node_record.node_addr_opt())
+            .map(|node_addr| node_addr.ip_addr());
+        constraints.excludes(key, ip_addr_opt)
+    }
+
+    // A Node behind NAT can only be reached through one of its relays.
+    fn is_reachable_from(&self, key: &PublicKey, previous_key: &PublicKey) -> bool {
+        match self.neighborhood_database.node_by_key(key) {
+            Some(node_record) => {
+                node_record.relays().is_empty() || node_record.is_relayed_by(previous_key)
+            }
+            None => false,
+        }
+    }
+
+    // A Node may join a route only if no Node already on it, apart from our own, shares its IP
+    // prefix or its earning wallet; otherwise one operator might see more than one of its hops.
+    // Nobody earns through the default wallet, so sharing it proves nothing.
+    fn is_diverse_from(&self, key: &PublicKey, route: &[&PublicKey]) -> bool {
+        let root_key = self.neighborhood_database.root().public_key();
+        if key == root_key {
+            return true;
+        }
+        let candidate = match self.neighborhood_database.node_by_key(key) {
+            Some(node) => node,
+            None => return false,
+        };
+        let candidate_prefix_opt = candidate
+            .node_addr_opt()
+            .map(|node_addr| self.diversity.prefix_of(&node_addr.ip_addr()));
+        let candidate_wallet = candidate.earning_wallet();
+        route
+            .iter()
+            .filter(|route_key| **route_key != root_key)
+            .filter_map(|route_key| self.neighborhood_database.node_by_key(route_key))
+            .all(|node| {
+                let shares_prefix = match (candidate_prefix_opt, node.node_addr_opt()) {
+                    (Some(prefix), Some(node_addr)) => prefix.contains(&node_addr.ip_addr()),
+                    _ => false,
+                };
+                let shares_wallet = candidate_wallet != *DEFAULT_EARNING_WALLET
+                    && node.earning_wallet() == candidate_wallet;
+                !shares_prefix && !shares_wallet
+            })
+    }
+
+    fn handle_gossip_acceptance(&mut self, gossip_triples: Vec<(Gossip, PublicKey, NodeAddr)>) {
+        if gossip_triples.is_empty() {
+            // If the GossipAcceptor generated no response Gossip, then this is a standard-Gossip
+            // response to changes caused by Gossip received, and should consist of a broadcast
+            // of our NeighborhoodDatabase to all our neighbors.
+            self.gossip_to_neighbors()
         } else {
             // The Gossip generated by the GossipAcceptor and contained in these triples may be:
             // A Debut (if we just received a Pass)
@@ -784,32 +2056,51 @@ impl Neighborhood {
         relay_node_addr: NodeAddr,
         gossip_source: IpAddr,
     ) {
-        self.logger.info(format!(
-            "Node {} sent a Pass: sending Debut to {}/{}",
-            gossip_source, relay_target, relay_node_addr
-        ));
+        self.logger.info_with(
+            LogFields::new()
+                .field("source_ip", gossip_source.to_string())
+                .public_key(&relay_target),
+            format!(
+                "Node {} sent a Pass: sending Debut to {}/{}",
+                gossip_source, relay_target, relay_node_addr
+            ),
+        );
         self.send_gossip(next_debut_gossip, relay_target, relay_node_addr);
     }
 
     fn handle_gossip_ignored(&self, ignored_node_name: String, gossip_record_count: usize) {
-        self.logger.info(format!(
-            "Ignored Gossip about {} Nodes from {}",
-            gossip_record_count, ignored_node_name
-        ));
+        self.logger.info_with(
+            LogFields::new()
+                .field("source", ignored_node_name.clone())
+                .field("node_count", gossip_record_count),
+            format!(
+                "Ignored Gossip about {} Nodes from {}",
+                gossip_record_count, ignored_node_name
+            ),
+        );
     }
 
     fn send_gossip_from_triples(&self, gossip_triples: Vec<(Gossip, PublicKey, NodeAddr)>) {
         for (gossip, gossip_target_key, gossip_target_addr) in gossip_triples {
-            self.logger.info(format!(
-                "Accepting Debut, Pass, or Introduction: sending single response to Node {}/{}",
-                &gossip_target_key,
-                &gossip_target_addr.ip_addr()
-            ));
+            self.logger.info_with(
+                LogFields::new()
+                    .public_key(&gossip_target_key)
+                    .field("node_count", gossip.node_records.len()),
+                format!(
+                    "Accepting Debut, Pass, or Introduction: sending single response to Node {}/{}",
+                    &gossip_target_key,
+                    &gossip_target_addr.ip_addr()
+                ),
+            );
             self.send_gossip(gossip, gossip_target_key, gossip_target_addr);
         }
     }
 
     fn send_gossip(&self, gossip: Gossip, target_key: PublicKey, target_node_addr: NodeAddr) {
+        let target_node_addr = match self.relay_clients.get(&target_key) {
+            Some(client_addr) => NodeAddr::from(client_addr),
+            None => target_node_addr,
+        };
         let package = match NoLookupIncipientCoresPackage::new(
             self.cryptde,
             &target_key,
@@ -827,13 +2118,18 @@ impl Neighborhood {
             .expect("No-lookup Hopper is unbound")
             .try_send(package)
             .expect("Hopper is dead");
-        self.logger.debug(format!(
-            "Sent Gossip: {}",
-            gossip.to_dot_graph(
-                self.neighborhood_database.root(),
-                (&target_key, &Some(target_node_addr))
-            )
-        ));
+        self.logger.debug_with(
+            LogFields::new()
+                .public_key(&target_key)
+                .field("node_count", gossip.node_records.len()),
+            format!(
+                "Sent Gossip: {}",
+                gossip.to_dot_graph(
+                    self.neighborhood_database.root(),
+                    (&target_key, &Some(target_node_addr))
+                )
+            ),
+        );
     }
 
     fn gossip_source_name(
@@ -870,6 +2166,7 @@ mod tests {
     use crate::sub_lib::dispatcher::Endpoint;
     use crate::sub_lib::hop::LiveHop;
     use crate::sub_lib::hopper::MessageType;
+    use crate::sub_lib::ip_range::IpRange;
     use crate::sub_lib::neighborhood::sentinel_ip_addr;
     use crate::sub_lib::neighborhood::ExpectedServices;
     use crate::sub_lib::stream_handler_pool::TransmitDataMsg;
@@ -883,19 +2180,24 @@ mod tests {
     use crate::test_utils::test_utils::assert_contains;
     use crate::test_utils::test_utils::cryptde;
     use crate::test_utils::test_utils::make_meaningless_route;
+    use crate::test_utils::test_utils::make_paying_wallet;
     use crate::test_utils::test_utils::rate_pack;
     use crate::test_utils::test_utils::vec_to_set;
     use actix::dev::{MessageResponse, ResponseChannel};
     use actix::Message;
     use actix::Recipient;
     use actix::System;
+    use actix::SystemRunner;
     use serde_cbor;
     use std::cell::RefCell;
+    use std::collections::HashSet;
     use std::convert::TryInto;
+    use std::iter::FromIterator;
     use std::net::IpAddr;
     use std::str::FromStr;
     use std::sync::{Arc, Mutex};
     use std::thread;
+    use std::time::Instant;
     use tokio::prelude::Future;
 
     fn make_standard_subject() -> Neighborhood {
@@ -906,6 +2208,8 @@ mod tests {
 
     pub struct GossipAcceptorMock {
         handle_params: Arc<Mutex<Vec<(NeighborhoodDatabase, Vec<AccessibleGossipRecord>, IpAddr)>>>,
+        handle_standard_params:
+            Arc<Mutex<Vec<(NeighborhoodDatabase, Vec<AccessibleGossipRecord>, IpAddr)>>>,
         handle_results: RefCell<Vec<GossipAcceptanceResult>>,
     }
 
@@ -922,12 +2226,27 @@ mod tests {
                 .push((database.clone(), agrs, gossip_source));
             self.handle_results.borrow_mut().remove(0)
         }
+
+        fn handle_standard(
+            &self,
+            database: &mut NeighborhoodDatabase,
+            agrs: Vec<AccessibleGossipRecord>,
+            gossip_source: IpAddr,
+        ) -> GossipAcceptanceResult {
+            self.handle_standard_params.lock().unwrap().push((
+                database.clone(),
+                agrs,
+                gossip_source,
+            ));
+            self.handle_results.borrow_mut().remove(0)
+        }
     }
 
     impl GossipAcceptorMock {
         pub fn new() -> GossipAcceptorMock {
             GossipAcceptorMock {
                 handle_params: Arc::new(Mutex::new(vec![])),
+                handle_standard_params: Arc::new(Mutex::new(vec![])),
                 handle_results: RefCell::new(vec![]),
             }
         }
@@ -942,6 +2261,16 @@ mod tests {
             self
         }
 
+        pub fn handle_standard_params(
+            mut self,
+            params_arc: &Arc<
+                Mutex<Vec<(NeighborhoodDatabase, Vec<AccessibleGossipRecord>, IpAddr)>>,
+            >,
+        ) -> GossipAcceptorMock {
+            self.handle_standard_params = params_arc.clone();
+            self
+        }
+
         pub fn handle_result(self, result: GossipAcceptanceResult) -> GossipAcceptorMock {
             self.handle_results.borrow_mut().push(result);
             self
@@ -954,158 +2283,1452 @@ mod tests {
         produce_results: RefCell<Vec<Gossip>>,
     }
 
-    impl GossipProducer for GossipProducerMock {
-        fn produce(&self, database: &NeighborhoodDatabase, target: &PublicKey) -> Gossip {
-            self.produce_params
-                .lock()
-                .unwrap()
-                .push((database.clone(), target.clone()));
-            self.produce_results.borrow_mut().remove(0)
-        }
+    impl GossipProducer for GossipProducerMock {
+        fn produce(&self, database: &NeighborhoodDatabase, target: &PublicKey) -> Gossip {
+            self.produce_params
+                .lock()
+                .unwrap()
+                .push((database.clone(), target.clone()));
+            self.produce_results.borrow_mut().remove(0)
+        }
+
+        fn produce_delta(
+            &self,
+            _database: &NeighborhoodDatabase,
+            _target: &PublicKey,
+            _target_version_summary: &BTreeMap<PublicKey, u32>,
+        ) -> Option<Gossip> {
+            unimplemented!()
+        }
+
+        fn produce_debut(&self, _database: &NeighborhoodDatabase) -> Gossip {
+            unimplemented!()
+        }
+    }
+
+    impl GossipProducerMock {
+        pub fn new() -> GossipProducerMock {
+            Self::default()
+        }
+
+        pub fn produce_params(
+            mut self,
+            params_arc: &Arc<Mutex<Vec<(NeighborhoodDatabase, PublicKey)>>>,
+        ) -> GossipProducerMock {
+            self.produce_params = params_arc.clone();
+            self
+        }
+
+        pub fn produce_result(self, result: Gossip) -> GossipProducerMock {
+            self.produce_results.borrow_mut().push(result);
+            self
+        }
+    }
+
+    #[derive(Debug, Default)]
+    pub struct NeighborhoodDaoMock {
+        node_records_results: RefCell<Vec<Result<Vec<Result<StoredNodeRecord, String>>, String>>>,
+        replace_node_records_params: Arc<Mutex<Vec<Vec<StoredNodeRecord>>>>,
+        replace_node_records_results: RefCell<Vec<Result<(), String>>>,
+    }
+
+    impl NeighborhoodDao for NeighborhoodDaoMock {
+        fn node_records(&self) -> Result<Vec<Result<StoredNodeRecord, String>>, String> {
+            self.node_records_results.borrow_mut().remove(0)
+        }
+
+        fn replace_node_records(&self, node_records: &[StoredNodeRecord]) -> Result<(), String> {
+            self.replace_node_records_params
+                .lock()
+                .unwrap()
+                .push(node_records.to_vec());
+            self.replace_node_records_results.borrow_mut().remove(0)
+        }
+    }
+
+    impl NeighborhoodDaoMock {
+        pub fn new() -> NeighborhoodDaoMock {
+            Self::default()
+        }
+
+        pub fn node_records_result(
+            self,
+            result: Result<Vec<Result<StoredNodeRecord, String>>, String>,
+        ) -> NeighborhoodDaoMock {
+            self.node_records_results.borrow_mut().push(result);
+            self
+        }
+
+        pub fn replace_node_records_params(
+            mut self,
+            params_arc: &Arc<Mutex<Vec<Vec<StoredNodeRecord>>>>,
+        ) -> NeighborhoodDaoMock {
+            self.replace_node_records_params = params_arc.clone();
+            self
+        }
+
+        pub fn replace_node_records_result(
+            self,
+            result: Result<(), String>,
+        ) -> NeighborhoodDaoMock {
+            self.replace_node_records_results.borrow_mut().push(result);
+            self
+        }
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "A SubstratumNode without an --ip setting is not decentralized and cannot have any --neighbor settings"
+    )]
+    fn neighborhood_cannot_be_created_with_neighbors_and_default_ip() {
+        let cryptde = cryptde();
+        let earning_wallet = Wallet::new("earning");
+        let consuming_wallet = Some(make_paying_wallet(b"consuming"));
+        let neighbor = make_node_record(1234, true, false);
+
+        Neighborhood::new(
+            cryptde,
+            NeighborhoodConfig {
+                neighbor_configs: vec![NodeDescriptor {
+                    public_key: neighbor.public_key().clone(),
+                    node_addr: neighbor.node_addr_opt().unwrap().clone(),
+                }],
+                is_bootstrap_node: false,
+                local_ip_addr: sentinel_ip_addr(),
+                clandestine_port_list: vec![0],
+                earning_wallet: earning_wallet.clone(),
+                consuming_wallet: consuming_wallet.clone(),
+                rate_pack: rate_pack(100),
+                gossip_batch_window: Duration::from_millis(0),
+                diversity: DiversityConfig::default(),
+                behind_nat: false,
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "A SubstratumNode without an --ip setting is not decentralized and cannot be --node_type bootstrap"
+    )]
+    fn neighborhood_cannot_be_created_as_a_bootstrap_node_with_default_ip() {
+        let cryptde = cryptde();
+        let earning_wallet = Wallet::new("earning");
+        let consuming_wallet = Some(make_paying_wallet(b"consuming"));
+
+        Neighborhood::new(
+            cryptde,
+            NeighborhoodConfig {
+                neighbor_configs: vec![],
+                is_bootstrap_node: true,
+                local_ip_addr: sentinel_ip_addr(),
+                clandestine_port_list: vec![],
+                earning_wallet: earning_wallet.clone(),
+                consuming_wallet: consuming_wallet.clone(),
+                rate_pack: rate_pack(100),
+                gossip_batch_window: Duration::from_millis(0),
+                diversity: DiversityConfig::default(),
+                behind_nat: false,
+            },
+        );
+    }
+
+    #[test]
+    fn bootstrap_node_neighborhood_creates_single_node_database() {
+        let cryptde = cryptde();
+        let earning_wallet = Wallet::new("earning");
+        let this_node_addr = NodeAddr::new(&IpAddr::from_str("5.4.3.2").unwrap(), &vec![5678]);
+
+        let subject = Neighborhood::new(
+            cryptde,
+            NeighborhoodConfig {
+                neighbor_configs: vec![],
+                is_bootstrap_node: true,
+                local_ip_addr: this_node_addr.ip_addr(),
+                clandestine_port_list: this_node_addr.ports().clone(),
+                earning_wallet: earning_wallet.clone(),
+                consuming_wallet: None,
+                rate_pack: rate_pack(100),
+                gossip_batch_window: Duration::from_millis(0),
+                diversity: DiversityConfig::default(),
+                behind_nat: false,
+            },
+        );
+
+        let root_node_record_ref = subject.neighborhood_database.root();
+
+        assert_eq!(root_node_record_ref.public_key(), cryptde.public_key());
+        assert_eq!(root_node_record_ref.node_addr_opt(), Some(this_node_addr));
+        assert_eq!(root_node_record_ref.is_bootstrap_node(), true);
+        assert_eq!(root_node_record_ref.half_neighbor_keys().len(), 0);
+    }
+
+    #[test]
+    fn bootstrap_node_with_no_neighbor_configs_ignores_bootstrap_neighborhood_now_message() {
+        init_test_logging();
+        let cryptde = cryptde();
+        let earning_wallet = Wallet::new("earning");
+        let consuming_wallet = Some(make_paying_wallet(b"consuming"));
+        let system = System::new("bootstrap_node_ignores_bootstrap_neighborhood_now_message");
+        let subject = Neighborhood::new(
+            cryptde,
+            NeighborhoodConfig {
+                neighbor_configs: vec![],
+                is_bootstrap_node: true,
+                local_ip_addr: IpAddr::from_str("5.4.3.2").unwrap(),
+                clandestine_port_list: vec![5678],
+                earning_wallet: earning_wallet.clone(),
+                consuming_wallet: consuming_wallet.clone(),
+                rate_pack: rate_pack(100),
+                gossip_batch_window: Duration::from_millis(0),
+                diversity: DiversityConfig::default(),
+                behind_nat: false,
+            },
+        );
+        let addr: Addr<Neighborhood> = subject.start();
+        let sub: Recipient<BootstrapNeighborhoodNowMessage> =
+            addr.clone().recipient::<BootstrapNeighborhoodNowMessage>();
+        let (hopper, _, hopper_recording_arc) = make_recorder();
+        let peer_actors = peer_actors_builder().hopper(hopper).build();
+        addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        sub.try_send(BootstrapNeighborhoodNowMessage {}).unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        let recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(recording.len(), 0);
+        TestLogHandler::new().exists_log_containing(
+            "INFO: Neighborhood: No bootstrap Nodes to report to; continuing",
+        );
+    }
+
+    #[test]
+    fn restore_from_adds_saved_nodes_and_adopts_neighbors_of_previous_root() {
+        init_test_logging();
+        let mut subject = make_standard_subject();
+        let mut old_root = make_node_record(1111, true, false);
+        let mut remembered = make_node_record(2222, true, false);
+        remembered.set_desirable(false);
+        let stranger = make_node_record(3333, true, false);
+        let unreachable = make_node_record(4444, false, false);
+        old_root.add_half_neighbor_key(remembered.public_key().clone());
+        old_root.add_half_neighbor_key(unreachable.public_key().clone());
+        old_root.resign();
+        let root_version = subject.neighborhood_database.root().version();
+        let replace_node_records_params_arc = Arc::new(Mutex::new(vec![]));
+        let neighborhood_dao = NeighborhoodDaoMock::new()
+            .node_records_result(Ok(vec![
+                Ok(StoredNodeRecord::new(&old_root, true)),
+                Ok(StoredNodeRecord::new(&remembered, false)),
+                Ok(StoredNodeRecord::new(&stranger, false)),
+                Ok(StoredNodeRecord::new(&unreachable, false)),
+            ]))
+            .replace_node_records_params(&replace_node_records_params_arc)
+            .replace_node_records_result(Ok(()));
+
+        subject.restore_from(Box::new(neighborhood_dao));
+
+        let database = &subject.neighborhood_database;
+        assert_eq!(database.node_by_key(old_root.public_key()), None);
+        assert_eq!(
+            database.node_by_key(remembered.public_key()),
+            Some(&remembered)
+        );
+        assert_eq!(database.node_by_key(stranger.public_key()), Some(&stranger));
+        assert_eq!(
+            database.node_by_key(unreachable.public_key()),
+            Some(&unreachable)
+        );
+        let root = database.root();
+        assert_eq!(
+            root.half_neighbor_keys(),
+            vec![remembered.public_key()].into_iter().collect()
+        );
+        assert_eq!(root.version(), root_version + 1);
+        assert!(cryptde().verify_signature(
+            root.signed_gossip(),
+            root.signature(),
+            root.public_key()
+        ));
+        let replace_node_records_params = replace_node_records_params_arc.lock().unwrap();
+        assert_eq!(replace_node_records_params.len(), 1);
+        assert_eq!(replace_node_records_params[0].len(), 4);
+        assert!(
+            replace_node_records_params[0].contains(&StoredNodeRecord::new(database.root(), true))
+        );
+        TestLogHandler::new().exists_log_containing(
+            "INFO: Neighborhood: Restored 3 saved Nodes, including 1 remembered neighbors",
+        );
+    }
+
+    #[test]
+    fn restore_from_discards_saved_records_with_invalid_signatures() {
+        init_test_logging();
+        let mut subject = make_standard_subject();
+        let forged = make_node_record(5432, true, false);
+        let mut stored_forged = StoredNodeRecord::new(&forged, false);
+        stored_forged.signature = CryptData::new(b"forged");
+        let neighborhood_dao = NeighborhoodDaoMock::new()
+            .node_records_result(Ok(vec![Ok(stored_forged)]))
+            .replace_node_records_result(Ok(()));
+
+        subject.restore_from(Box::new(neighborhood_dao));
+
+        assert_eq!(
+            subject
+                .neighborhood_database
+                .node_by_key(forged.public_key()),
+            None
+        );
+        TestLogHandler::new().exists_log_containing(
+            format!(
+                "WARN: Neighborhood: Discarding saved record for Node {} with invalid signature",
+                forged.public_key()
+            )
+            .as_str(),
+        );
+    }
+
+    #[test]
+    fn restore_from_discards_unreadable_saved_records_and_keeps_the_rest() {
+        init_test_logging();
+        let mut subject = make_standard_subject();
+        let readable = make_node_record(5433, true, false);
+        let neighborhood_dao = NeighborhoodDaoMock::new()
+            .node_records_result(Ok(vec![
+                Err("Invalid node_addr 'booga'".to_string()),
+                Ok(StoredNodeRecord::new(&readable, false)),
+            ]))
+            .replace_node_records_result(Ok(()));
+
+        subject.restore_from(Box::new(neighborhood_dao));
+
+        assert!(subject
+            .neighborhood_database
+            .node_by_key(readable.public_key())
+            .is_some());
+        TestLogHandler::new().exists_log_containing(
+            "WARN: Neighborhood: Discarding unreadable saved Node record: Invalid node_addr 'booga'",
+        );
+    }
+
+    #[test]
+    fn restore_from_starts_empty_when_saved_records_cannot_be_read() {
+        init_test_logging();
+        let mut subject = make_standard_subject();
+        let replace_node_records_params_arc = Arc::new(Mutex::new(vec![]));
+        let neighborhood_dao = NeighborhoodDaoMock::new()
+            .node_records_result(Err("no such table: neighborhood_node".to_string()))
+            .replace_node_records_params(&replace_node_records_params_arc)
+            .replace_node_records_result(Ok(()));
+
+        subject.restore_from(Box::new(neighborhood_dao));
+
+        assert_eq!(subject.neighborhood_database.keys().len(), 1);
+        assert_eq!(replace_node_records_params_arc.lock().unwrap().len(), 1);
+        TestLogHandler::new().exists_log_containing(
+            "ERROR: Neighborhood: Couldn't read saved Node records: no such table: neighborhood_node",
+        );
+    }
+
+    #[test]
+    fn restore_from_logs_failure_to_save_database() {
+        init_test_logging();
+        let mut subject = make_standard_subject();
+        let neighborhood_dao = NeighborhoodDaoMock::new()
+            .node_records_result(Ok(vec![]))
+            .replace_node_records_result(Err("booga".to_string()));
+
+        subject.restore_from(Box::new(neighborhood_dao));
+
+        TestLogHandler::new().exists_log_containing(
+            "ERROR: Neighborhood: Couldn't save Neighborhood database: booga",
+        );
+    }
+
+    #[test]
+    fn neighborhood_without_bootstrap_nodes_rejoins_through_remembered_neighbors() {
+        init_test_logging();
+        let cryptde = cryptde();
+        let mut old_root = make_node_record(1111, true, false);
+        let remembered = make_node_record(2222, true, false);
+        old_root.add_half_neighbor_key(remembered.public_key().clone());
+        old_root.resign();
+        let neighborhood_dao = NeighborhoodDaoMock::new()
+            .node_records_result(Ok(vec![
+                Ok(StoredNodeRecord::new(&old_root, true)),
+                Ok(StoredNodeRecord::new(&remembered, false)),
+            ]))
+            .replace_node_records_result(Ok(()));
+        let system = System::new("neighborhood_rejoins_through_remembered_neighbors");
+        let mut subject = Neighborhood::new(
+            cryptde,
+            NeighborhoodConfig {
+                neighbor_configs: vec![],
+                is_bootstrap_node: false,
+                local_ip_addr: IpAddr::from_str("5.4.3.2").unwrap(),
+                clandestine_port_list: vec![5678],
+                earning_wallet: Wallet::new("earning"),
+                consuming_wallet: None,
+                rate_pack: rate_pack(100),
+                gossip_batch_window: Duration::from_millis(0),
+                diversity: DiversityConfig::default(),
+                behind_nat: false,
+            },
+        );
+        subject.restore_from(Box::new(neighborhood_dao));
+        let addr: Addr<Neighborhood> = subject.start();
+        let (hopper, _, hopper_recording_arc) = make_recorder();
+        let peer_actors = peer_actors_builder().hopper(hopper).build();
+        addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        addr.try_send(BootstrapNeighborhoodNowMessage {}).unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        let recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(recording.len(), 1);
+        let package = recording.get_record::<IncipientCoresPackage>(0);
+        assert_eq!(
+            package.route.next_hop(cryptde).unwrap().public_key,
+            remembered.public_key().clone()
+        );
+        TestLogHandler::new()
+            .exists_log_containing("INFO: Neighborhood: Rejoining through 1 remembered neighbors");
+    }
+
+    #[test]
+    fn accepted_gossip_is_saved_to_the_database_at_the_next_save_interval() {
+        let mut subject = make_standard_subject();
+        let replace_node_records_params_arc = Arc::new(Mutex::new(vec![]));
+        let neighborhood_dao = NeighborhoodDaoMock::new()
+            .node_records_result(Ok(vec![]))
+            .replace_node_records_params(&replace_node_records_params_arc)
+            .replace_node_records_result(Ok(()))
+            .replace_node_records_result(Ok(()));
+        subject.restore_from(Box::new(neighborhood_dao));
+        let new_node = make_node_record(3456, true, false);
+        subject
+            .neighborhood_database
+            .add_node(new_node.clone())
+            .unwrap();
+        subject.gossip_acceptor = Box::new(
+            GossipAcceptorMock::new().handle_result(GossipAcceptanceResult::Accepted(vec![])),
+        );
+
+        subject.handle_gossip(
+            Gossip {
+                node_records: vec![],
+                version_summary_opt: None,
+                delta: false,
+            },
+            IpAddr::from_str("1.1.1.1").unwrap(),
+        );
+
+        assert_eq!(replace_node_records_params_arc.lock().unwrap().len(), 1);
+        subject.save_database_if_changed();
+        subject.save_database_if_changed();
+        let replace_node_records_params = replace_node_records_params_arc.lock().unwrap();
+        assert_eq!(replace_node_records_params.len(), 2);
+        assert!(replace_node_records_params[1].contains(&StoredNodeRecord::new(&new_node, false)));
+    }
+
+    fn make_heartbeat_subject(
+        neighbors: Vec<&NodeRecord>,
+    ) -> (Neighborhood, Arc<Mutex<Recording>>, SystemRunner) {
+        let mut subject = make_standard_subject();
+        let root_key = subject.neighborhood_database.root().public_key().clone();
+        neighbors.into_iter().for_each(|neighbor| {
+            subject
+                .neighborhood_database
+                .add_node(neighbor.clone())
+                .unwrap();
+            subject
+                .neighborhood_database
+                .add_arbitrary_half_neighbor(&root_key, neighbor.public_key());
+        });
+        let system = System::new("heartbeat_test");
+        let (hopper, _, hopper_recording_arc) = make_recorder();
+        let peer_actors = peer_actors_builder().hopper(hopper).build();
+        subject.hopper = Some(peer_actors.hopper.from_hopper_client);
+        (subject, hopper_recording_arc, system)
+    }
+
+    fn heartbeat_from(package: &IncipientCoresPackage, target: &NodeRecord) -> Heartbeat {
+        assert_eq!(
+            package.route.next_hop(cryptde()).unwrap().public_key,
+            target.public_key().clone()
+        );
+        match decodex::<MessageType>(&CryptDENull::from(target.public_key()), &package.payload) {
+            Ok(MessageType::Heartbeat(heartbeat)) => heartbeat,
+            x => panic!("Expected MessageType::Heartbeat, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn send_heartbeats_pings_neighbors_that_have_node_addrs() {
+        let reachable = make_node_record(1234, true, false);
+        let unreachable = make_node_record(2345, false, false);
+        let (mut subject, hopper_recording_arc, system) =
+            make_heartbeat_subject(vec![&reachable, &unreachable]);
+
+        subject.send_heartbeats();
+
+        System::current().stop();
+        system.run();
+        let hopper_recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(hopper_recording.len(), 1);
+        let package = hopper_recording.get_record::<IncipientCoresPackage>(0);
+        assert_eq!(heartbeat_from(package, &reachable), Heartbeat::Ping(0));
+    }
+
+    #[test]
+    fn neighbor_that_misses_too_many_heartbeats_is_removed() {
+        init_test_logging();
+        let silent = make_node_record(3456, true, false);
+        let (mut subject, hopper_recording_arc, system) = make_heartbeat_subject(vec![&silent]);
+        let root_version = subject.neighborhood_database.root().version();
+
+        (0..(MISSED_HEARTBEAT_LIMIT + 1)).for_each(|_| subject.send_heartbeats());
+
+        System::current().stop();
+        system.run();
+        let hopper_recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(hopper_recording.len(), MISSED_HEARTBEAT_LIMIT);
+        let root = subject.neighborhood_database.root();
+        assert!(!root.has_half_neighbor(silent.public_key()));
+        assert_eq!(root.version(), root_version + 1);
+        assert!(subject.neighbor_liveness.is_empty());
+        TestLogHandler::new().exists_log_containing(
+            format!(
+                "WARN: Neighborhood: Removing neighbor {} after {} unanswered heartbeats",
+                silent.public_key(),
+                MISSED_HEARTBEAT_LIMIT
+            )
+            .as_str(),
+        );
+    }
+
+    #[test]
+    fn ping_is_answered_with_pong() {
+        let neighbor = make_node_record(4567, true, false);
+        let (mut subject, hopper_recording_arc, system) = make_heartbeat_subject(vec![&neighbor]);
+
+        subject.handle_heartbeat(
+            Heartbeat::Ping(7),
+            neighbor.node_addr_opt().unwrap().ip_addr(),
+        );
+
+        System::current().stop();
+        system.run();
+        let hopper_recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(hopper_recording.len(), 1);
+        let package = hopper_recording.get_record::<IncipientCoresPackage>(0);
+        assert_eq!(heartbeat_from(package, &neighbor), Heartbeat::Pong(7));
+    }
+
+    #[test]
+    fn pong_records_round_trip_time_and_forgives_missed_heartbeats() {
+        let neighbor = make_node_record(5678, true, false);
+        let (mut subject, _, _system) = make_heartbeat_subject(vec![&neighbor]);
+        subject.send_heartbeats();
+        subject.send_heartbeats();
+
+        subject.handle_heartbeat(
+            Heartbeat::Pong(1),
+            neighbor.node_addr_opt().unwrap().ip_addr(),
+        );
+
+        let liveness = subject
+            .neighbor_liveness
+            .get(neighbor.public_key())
+            .unwrap();
+        assert_eq!(liveness.outstanding_ping_opt, None);
+        assert_eq!(liveness.missed_heartbeats, 0);
+        assert!(subject
+            .neighborhood_database
+            .node_by_key(neighbor.public_key())
+            .unwrap()
+            .round_trip_time_opt()
+            .is_some());
+    }
+
+    #[test]
+    fn stale_pong_is_ignored() {
+        let neighbor = make_node_record(6789, true, false);
+        let (mut subject, _, _system) = make_heartbeat_subject(vec![&neighbor]);
+        subject.send_heartbeats();
+        subject.send_heartbeats();
+
+        subject.handle_heartbeat(
+            Heartbeat::Pong(0),
+            neighbor.node_addr_opt().unwrap().ip_addr(),
+        );
+
+        let liveness = subject
+            .neighbor_liveness
+            .get(neighbor.public_key())
+            .unwrap();
+        assert_eq!(liveness.outstanding_ping_opt.unwrap().0, 1);
+        assert_eq!(liveness.missed_heartbeats, 1);
+        assert_eq!(
+            subject
+                .neighborhood_database
+                .node_by_key(neighbor.public_key())
+                .unwrap()
+                .round_trip_time_opt(),
+            None
+        );
+    }
+
+    #[test]
+    fn heartbeat_from_unknown_node_is_ignored() {
+        let (mut subject, hopper_recording_arc, system) = make_heartbeat_subject(vec![]);
+
+        subject.handle_heartbeat(Heartbeat::Ping(3), IpAddr::from_str("9.8.7.6").unwrap());
+
+        System::current().stop();
+        system.run();
+        let hopper_recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(hopper_recording.len(), 0);
+    }
+
+    #[test]
+    fn node_behind_nat_makes_its_public_full_neighbors_relays_and_asks_them_to_relay() {
+        let public_neighbor = make_node_record(1234, true, false);
+        let hidden_neighbor = make_node_record(2345, false, false);
+        let (mut subject, hopper_recording_arc, system) =
+            make_heartbeat_subject(vec![&public_neighbor, &hidden_neighbor]);
+        subject.behind_nat = true;
+        let root_key = subject.neighborhood_database.root().public_key().clone();
+        subject
+            .neighborhood_database
+            .add_arbitrary_half_neighbor(public_neighbor.public_key(), &root_key);
+        subject
+            .neighborhood_database
+            .add_arbitrary_half_neighbor(hidden_neighbor.public_key(), &root_key);
+        let root_version = subject.neighborhood_database.root().version();
+
+        assert!(subject.update_relays());
+        assert!(!subject.update_relays());
+        subject.send_relay_requests();
+
+        System::current().stop();
+        system.run();
+        let root = subject.neighborhood_database.root();
+        assert_eq!(
+            root.relays(),
+            &BTreeSet::from_iter(vec![public_neighbor.public_key().clone()])
+        );
+        assert_eq!(root.version(), root_version + 1);
+        assert!(cryptde().verify_signature(
+            root.signed_gossip(),
+            root.signature(),
+            root.public_key()
+        ));
+        let hopper_recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(hopper_recording.len(), 1);
+        let package = hopper_recording.get_record::<IncipientCoresPackage>(0);
+        assert_eq!(
+            package.route.next_hop(cryptde()).unwrap().public_key,
+            public_neighbor.public_key().clone()
+        );
+        match decodex::<MessageType>(
+            &CryptDENull::from(public_neighbor.public_key()),
+            &package.payload,
+        ) {
+            Ok(MessageType::RelayRequest(relay_request)) => assert_eq!(
+                relay_request,
+                RelayRequest::Request {
+                    node_record: GossipNodeRecord::from(root.clone())
+                }
+            ),
+            x => panic!("Expected MessageType::RelayRequest, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn neighborhood_graph_request_is_answered_with_the_database_as_a_graph() {
+        let subject = make_standard_subject();
+        let expected_graph = subject.neighborhood_database.to_graph();
+        let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        thread::spawn(move || {
+            let system =
+                System::new("neighborhood_graph_request_is_answered_with_the_database_as_a_graph");
+            let addr: Addr<Neighborhood> = subject.start();
+            let peer_actors = peer_actors_builder().ui_gateway(ui_gateway).build();
+            addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            addr.try_send(NeighborhoodGraphRequest {
+                client_id: 1234,
+                correlation_id: None,
+            })
+            .unwrap();
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(1);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 1234,
+                correlation_id: None,
+                data: UiMessage::NeighborhoodGraph(expected_graph),
+            }
+        );
+    }
+
+    #[test]
+    fn node_with_a_public_address_has_no_relays() {
+        let neighbor = make_node_record(1234, true, false);
+        let (mut subject, _, _system) = make_heartbeat_subject(vec![&neighbor]);
+        let root_key = subject.neighborhood_database.root().public_key().clone();
+        subject
+            .neighborhood_database
+            .add_arbitrary_half_neighbor(neighbor.public_key(), &root_key);
+
+        assert!(!subject.update_relays());
+
+        assert!(subject.neighborhood_database.root().relays().is_empty());
+    }
+
+    // Like most Nodes behind NAT, the client advertises no address of its own.
+    fn make_relay_client(subject: &mut Neighborhood, lists_subject: bool) -> NodeRecord {
+        let mut client = make_node_record(2345, false, false);
+        if lists_subject {
+            let root_key = subject.neighborhood_database.root().public_key().clone();
+            client.set_relays(BTreeSet::from_iter(vec![root_key]));
+        }
+        client.resign();
+        subject
+            .neighborhood_database
+            .add_node(client.clone())
+            .unwrap();
+        client
+    }
+
+    fn bind_hopper_no_lookup(subject: &mut Neighborhood) -> (Arc<Mutex<Recording>>, SystemRunner) {
+        let system = System::new("relay_test");
+        let (hopper, _, hopper_recording_arc) = make_recorder();
+        let peer_actors = peer_actors_builder().hopper(hopper).build();
+        subject.hopper_no_lookup = Some(peer_actors.hopper.from_hopper_client_no_lookup);
+        (hopper_recording_arc, system)
+    }
+
+    fn sign_relay_challenge(
+        client: &NodeRecord,
+        relay_key: &PublicKey,
+        challenge: u64,
+    ) -> CryptData {
+        CryptDENull::from(client.public_key())
+            .sign(&RelayRequest::challenge_data(relay_key, challenge))
+            .unwrap()
+    }
+
+    #[test]
+    fn relay_request_is_answered_with_a_challenge_over_the_same_connection() {
+        let mut subject = make_standard_subject();
+        let (hopper_recording_arc, system) = bind_hopper_no_lookup(&mut subject);
+        let client = make_relay_client(&mut subject, true);
+        let client_addr = SocketAddr::from_str("2.3.4.5:40000").unwrap();
+
+        subject.handle_relay_request(GossipNodeRecord::from(client.clone()), client_addr);
+
+        System::current().stop();
+        system.run();
+        assert!(subject.relay_clients.is_empty());
+        let (challenged_key, challenge, _) = subject.relay_challenges.get(&client_addr).unwrap();
+        assert_eq!(challenged_key, client.public_key());
+        let hopper_recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(hopper_recording.len(), 1);
+        let package = hopper_recording.get_record::<NoLookupIncipientCoresPackage>(0);
+        assert_eq!(&package.public_key, client.public_key());
+        assert_eq!(package.node_addr, NodeAddr::from(&client_addr));
+        match decodex::<MessageType>(&CryptDENull::from(client.public_key()), &package.payload) {
+            Ok(MessageType::RelayRequest(RelayRequest::Challenge(sent_challenge))) => {
+                assert_eq!(sent_challenge, *challenge)
+            }
+            x => panic!("Expected RelayRequest::Challenge, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn relay_client_with_no_address_is_relayed_for_once_it_signs_the_challenge() {
+        init_test_logging();
+        let mut subject = make_standard_subject();
+        let (_, system) = bind_hopper_no_lookup(&mut subject);
+        let client = make_relay_client(&mut subject, true);
+        let client_addr = SocketAddr::from_str("2.3.4.5:40000").unwrap();
+        subject.handle_relay_request(GossipNodeRecord::from(client.clone()), client_addr);
+        let challenge = subject.relay_challenges.get(&client_addr).unwrap().1;
+        let root_key = subject.neighborhood_database.root().public_key().clone();
+
+        subject.handle_relay_response(
+            sign_relay_challenge(&client, &root_key, challenge),
+            client_addr,
+        );
+
+        System::current().stop();
+        system.run();
+        assert_eq!(client.node_addr_opt(), None);
+        assert_eq!(
+            subject.reachable_node_addr_opt(&client),
+            Some(NodeAddr::from(&client_addr))
+        );
+        assert_eq!(subject.relay_challenges.get(&client_addr), None);
+        TestLogHandler::new().exists_log_containing(
+            format!(
+                "INFO: Neighborhood: Relaying for {} over connection from 2.3.4.5:40000",
+                client.public_key()
+            )
+            .as_str(),
+        );
+    }
+
+    #[test]
+    fn relay_request_from_a_client_that_does_not_list_us_is_refused() {
+        init_test_logging();
+        let mut subject = make_standard_subject();
+        let (hopper_recording_arc, system) = bind_hopper_no_lookup(&mut subject);
+        let client = make_relay_client(&mut subject, false);
+        let client_addr = SocketAddr::from_str("2.3.4.5:40000").unwrap();
+
+        subject.handle_relay_request(GossipNodeRecord::from(client.clone()), client_addr);
+
+        System::current().stop();
+        system.run();
+        assert_eq!(subject.relay_challenges.get(&client_addr), None);
+        assert_eq!(hopper_recording_arc.lock().unwrap().len(), 0);
+        TestLogHandler::new().exists_log_containing(
+            format!(
+                "WARN: Neighborhood: Refusing to relay for {}: its record doesn't list us as a relay",
+                client.public_key()
+            )
+            .as_str(),
+        );
+    }
+
+    #[test]
+    fn replayed_relay_request_is_refused_when_the_challenge_comes_back_unsigned_by_the_client() {
+        init_test_logging();
+        let mut subject = make_standard_subject();
+        let (_, system) = bind_hopper_no_lookup(&mut subject);
+        let client = make_relay_client(&mut subject, true);
+        let impostor = make_node_record(6666, true, false);
+        let impostor_addr = SocketAddr::from_str("6.6.6.6:40000").unwrap();
+        subject.handle_relay_request(GossipNodeRecord::from(client.clone()), impostor_addr);
+        let challenge = subject.relay_challenges.get(&impostor_addr).unwrap().1;
+        let root_key = subject.neighborhood_database.root().public_key().clone();
+
+        subject.handle_relay_response(
+            sign_relay_challenge(&impostor, &root_key, challenge),
+            impostor_addr,
+        );
+
+        System::current().stop();
+        system.run();
+        assert!(subject.relay_clients.is_empty());
+        assert_eq!(subject.relay_challenges.get(&impostor_addr), None);
+        TestLogHandler::new().exists_log_containing(
+            format!(
+                "WARN: Neighborhood: Refusing to relay for {}: the challenge sent to 6.6.6.6:40000 wasn't signed by it",
+                client.public_key()
+            )
+            .as_str(),
+        );
+    }
+
+    #[test]
+    fn relay_response_on_a_connection_that_was_not_challenged_is_ignored() {
+        init_test_logging();
+        let mut subject = make_standard_subject();
+        let (_, system) = bind_hopper_no_lookup(&mut subject);
+        let client = make_relay_client(&mut subject, true);
+        let client_addr = SocketAddr::from_str("2.3.4.5:40000").unwrap();
+        subject.handle_relay_request(GossipNodeRecord::from(client.clone()), client_addr);
+        let challenge = subject.relay_challenges.get(&client_addr).unwrap().1;
+        let root_key = subject.neighborhood_database.root().public_key().clone();
+
+        subject.handle_relay_response(
+            sign_relay_challenge(&client, &root_key, challenge),
+            SocketAddr::from_str("2.3.4.5:40001").unwrap(),
+        );
+
+        System::current().stop();
+        system.run();
+        assert!(subject.relay_clients.is_empty());
+        TestLogHandler::new().exists_log_containing(
+            "WARN: Neighborhood: Ignoring RelayRequest response from 2.3.4.5:40001: no challenge was sent there",
+        );
+    }
+
+    #[test]
+    fn relay_response_to_an_expired_challenge_is_ignored() {
+        init_test_logging();
+        let mut subject = make_standard_subject();
+        subject.relay_challenge_ttl = Duration::from_millis(10);
+        let (_, system) = bind_hopper_no_lookup(&mut subject);
+        let client = make_relay_client(&mut subject, true);
+        let client_addr = SocketAddr::from_str("2.3.4.6:40000").unwrap();
+        subject.handle_relay_request(GossipNodeRecord::from(client.clone()), client_addr);
+        let challenge = subject.relay_challenges.get(&client_addr).unwrap().1;
+        let root_key = subject.neighborhood_database.root().public_key().clone();
+        thread::sleep(Duration::from_millis(20));
+
+        subject.handle_relay_response(
+            sign_relay_challenge(&client, &root_key, challenge),
+            client_addr,
+        );
+
+        System::current().stop();
+        system.run();
+        assert!(subject.relay_clients.is_empty());
+        assert_eq!(subject.relay_challenges.get(&client_addr), None);
+        TestLogHandler::new().exists_log_containing(
+            "WARN: Neighborhood: Ignoring RelayRequest response from 2.3.4.6:40000: the challenge sent there expired",
+        );
+    }
+
+    #[test]
+    fn unanswered_relay_challenges_are_dropped_once_they_expire() {
+        let mut subject = make_standard_subject();
+        subject.relay_challenge_ttl = Duration::from_millis(10);
+        let (_, system) = bind_hopper_no_lookup(&mut subject);
+        let client = make_relay_client(&mut subject, true);
+        let first_addr = SocketAddr::from_str("2.3.4.7:40000").unwrap();
+        let second_addr = SocketAddr::from_str("2.3.4.7:40001").unwrap();
+        subject.handle_relay_request(GossipNodeRecord::from(client.clone()), first_addr);
+        thread::sleep(Duration::from_millis(20));
+
+        subject.handle_relay_request(GossipNodeRecord::from(client.clone()), second_addr);
+
+        System::current().stop();
+        system.run();
+        assert_eq!(subject.relay_challenges.get(&first_addr), None);
+        assert_eq!(subject.relay_challenges.len(), 1);
+    }
+
+    #[test]
+    fn relay_request_with_invalid_signature_is_refused() {
+        init_test_logging();
+        let mut subject = make_standard_subject();
+        let client = make_relay_client(&mut subject, true);
+        let mut node_record = GossipNodeRecord::from(client.clone());
+        node_record.signature = CryptData::new(b"forged");
+        let client_addr = SocketAddr::from_str("2.3.4.5:40000").unwrap();
+
+        subject.handle_relay_request(node_record, client_addr);
+
+        assert_eq!(subject.relay_challenges.get(&client_addr), None);
+        TestLogHandler::new().exists_log_containing(
+            "ERROR: Neighborhood: Received RelayRequest with invalid signature from 2.3.4.5:40000",
+        );
+    }
+
+    #[test]
+    fn relay_challenge_from_one_of_our_relays_is_signed_and_sent_back() {
+        let relay = make_node_record(1234, true, false);
+        let (mut subject, hopper_recording_arc, system) = make_heartbeat_subject(vec![&relay]);
+        subject
+            .neighborhood_database
+            .root_mut()
+            .set_relays(BTreeSet::from_iter(vec![relay.public_key().clone()]));
+        let relay_addr = SocketAddr::new(relay.node_addr_opt().unwrap().ip_addr(), 1234);
+
+        subject.handle_relay_challenge(42, relay_addr);
+
+        System::current().stop();
+        system.run();
+        let hopper_recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(hopper_recording.len(), 1);
+        let package = hopper_recording.get_record::<IncipientCoresPackage>(0);
+        assert_eq!(
+            package.route.next_hop(cryptde()).unwrap().public_key,
+            relay.public_key().clone()
+        );
+        match decodex::<MessageType>(&CryptDENull::from(relay.public_key()), &package.payload) {
+            Ok(MessageType::RelayRequest(RelayRequest::Response { signature })) => {
+                assert!(cryptde().verify_signature(
+                    &RelayRequest::challenge_data(relay.public_key(), 42),
+                    &signature,
+                    &cryptde().public_key()
+                ))
+            }
+            x => panic!("Expected RelayRequest::Response, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn relay_challenge_from_a_node_that_is_not_our_relay_is_ignored() {
+        init_test_logging();
+        let neighbor = make_node_record(1234, true, false);
+        let (subject, hopper_recording_arc, system) = make_heartbeat_subject(vec![&neighbor]);
+
+        subject.handle_relay_challenge(42, SocketAddr::from_str("1.2.3.4:1234").unwrap());
+
+        System::current().stop();
+        system.run();
+        assert_eq!(hopper_recording_arc.lock().unwrap().len(), 0);
+        TestLogHandler::new().exists_log_containing(
+            "WARN: Neighborhood: Ignoring RelayRequest challenge from 1.2.3.4:1234: it isn't one of our relays",
+        );
+    }
+
+    #[test]
+    fn gossip_with_version_summary_is_standard_and_its_summary_is_remembered() {
+        let mut subject = make_standard_subject();
+        let neighbor = make_node_record(1234, true, false);
+        subject
+            .neighborhood_database
+            .add_node(neighbor.clone())
+            .unwrap();
+        let handle_params_arc = Arc::new(Mutex::new(vec![]));
+        let handle_standard_params_arc = Arc::new(Mutex::new(vec![]));
+        subject.gossip_acceptor = Box::new(
+            GossipAcceptorMock::new()
+                .handle_params(&handle_params_arc)
+                .handle_standard_params(&handle_standard_params_arc)
+                .handle_result(GossipAcceptanceResult::Ignored),
+        );
+        let neighbor_db = db_from_node(&neighbor);
+        let gossip = GossipBuilder::new(&neighbor_db)
+            .node(neighbor.public_key(), true)
+            .version_summary()
+            .build();
+
+        subject.handle_gossip(gossip.clone(), neighbor.node_addr_opt().unwrap().ip_addr());
+
+        assert_eq!(handle_params_arc.lock().unwrap().len(), 0);
+        assert_eq!(handle_standard_params_arc.lock().unwrap().len(), 1);
+        assert_eq!(
+            subject
+                .neighbor_version_summaries
+                .get(neighbor.public_key())
+                .map(|summary| &summary.versions),
+            gossip.version_summary_opt.as_ref()
+        );
+    }
+
+    #[test]
+    fn delta_gossip_is_standard_and_its_records_are_added_to_the_senders_summary() {
+        let mut subject = make_standard_subject();
+        let neighbor = make_node_record(1234, true, false);
+        subject
+            .neighborhood_database
+            .add_node(neighbor.clone())
+            .unwrap();
+        let mut other = make_node_record(2345, true, false);
+        other.set_version(5);
+        other.resign();
+        let received = Instant::now() - Duration::from_secs(10);
+        subject.neighbor_version_summaries.insert(
+            neighbor.public_key().clone(),
+            NeighborVersionSummary::new(
+                vec![(other.public_key().clone(), 3)].into_iter().collect(),
+                received,
+            ),
+        );
+        let handle_params_arc = Arc::new(Mutex::new(vec![]));
+        let handle_standard_params_arc = Arc::new(Mutex::new(vec![]));
+        subject.gossip_acceptor = Box::new(
+            GossipAcceptorMock::new()
+                .handle_params(&handle_params_arc)
+                .handle_standard_params(&handle_standard_params_arc)
+                .handle_result(GossipAcceptanceResult::Ignored),
+        );
+        let mut neighbor_db = db_from_node(&neighbor);
+        neighbor_db.add_node(other.clone()).unwrap();
+        let gossip = GossipBuilder::new(&neighbor_db)
+            .node(other.public_key(), false)
+            .delta()
+            .build();
+
+        subject.handle_gossip(gossip, neighbor.node_addr_opt().unwrap().ip_addr());
+
+        assert_eq!(handle_params_arc.lock().unwrap().len(), 0);
+        assert_eq!(handle_standard_params_arc.lock().unwrap().len(), 1);
+        let summary = subject
+            .neighbor_version_summaries
+            .get(neighbor.public_key())
+            .unwrap();
+        assert_eq!(summary.versions.get(other.public_key()), Some(&5));
+        assert_eq!(summary.received, received);
+    }
+
+    #[test]
+    fn version_summaries_expire_and_are_forgotten_for_nodes_that_are_no_longer_neighbors() {
+        let mut subject = make_standard_subject();
+        let root_key = subject.neighborhood_database.root().public_key().clone();
+        let fresh = make_node_record(1234, true, false);
+        let stale = make_node_record(2345, true, false);
+        let former = make_node_record(3456, true, false);
+        vec![&fresh, &stale, &former]
+            .into_iter()
+            .for_each(|node_record| {
+                subject
+                    .neighborhood_database
+                    .add_node(node_record.clone())
+                    .unwrap();
+            });
+        vec![&fresh, &stale].into_iter().for_each(|neighbor| {
+            subject
+                .neighborhood_database
+                .add_arbitrary_full_neighbor(&root_key, neighbor.public_key());
+        });
+        let now = Instant::now();
+        let mut insert_summary = |node_record: &NodeRecord, received: Instant| {
+            subject.neighbor_version_summaries.insert(
+                node_record.public_key().clone(),
+                NeighborVersionSummary::new(BTreeMap::new(), received),
+            );
+        };
+        insert_summary(&fresh, now - Duration::from_secs(10));
+        insert_summary(
+            &stale,
+            now - VERSION_SUMMARY_LIFETIME - Duration::from_secs(1),
+        );
+        insert_summary(&former, now - Duration::from_secs(10));
+
+        subject.expire_version_summaries(now);
+
+        assert_eq!(
+            subject
+                .neighbor_version_summaries
+                .keys()
+                .collect::<Vec<&PublicKey>>(),
+            vec![fresh.public_key()]
+        );
+    }
+
+    #[test]
+    fn delta_gossip_is_sent_again_while_the_neighbors_summary_does_not_show_it_arrived() {
+        let mut subject = make_standard_subject();
+        let root_key = subject.neighborhood_database.root().public_key().clone();
+        let neighbor = make_node_record(1234, true, false);
+        let other = make_node_record(2345, true, false);
+        subject
+            .neighborhood_database
+            .add_node(neighbor.clone())
+            .unwrap();
+        subject
+            .neighborhood_database
+            .add_node(other.clone())
+            .unwrap();
+        subject
+            .neighborhood_database
+            .add_arbitrary_full_neighbor(&root_key, neighbor.public_key());
+        subject
+            .neighborhood_database
+            .root_mut()
+            .regenerate_signed_gossip(cryptde());
+        subject.neighbor_version_summaries.insert(
+            neighbor.public_key().clone(),
+            NeighborVersionSummary::new(
+                vec![(neighbor.public_key().clone(), 0)]
+                    .into_iter()
+                    .collect(),
+                Instant::now(),
+            ),
+        );
+        let system = System::new(
+            "delta_gossip_is_sent_again_while_the_neighbors_summary_does_not_show_it_arrived",
+        );
+        let (hopper, _, hopper_recording_arc) = make_recorder();
+        let peer_actors = peer_actors_builder().hopper(hopper).build();
+        subject.hopper = Some(peer_actors.hopper.from_hopper_client);
+
+        subject.gossip_to_neighbors();
+        subject.gossip_to_neighbors();
+
+        System::current().stop();
+        system.run();
+        let hopper_recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(hopper_recording.len(), 2);
+        let gossip_keys = (0..2)
+            .map(|idx| {
+                let package = hopper_recording.get_record::<IncipientCoresPackage>(idx);
+                let gossip = match decodex::<MessageType>(
+                    &CryptDENull::from(neighbor.public_key()),
+                    &package.payload,
+                ) {
+                    Ok(MessageType::Gossip(g)) => g,
+                    x => panic!("Expected MessageType::Gossip, got {:?}", x),
+                };
+                assert_eq!(gossip.delta, true);
+                gossip
+                    .node_records
+                    .into_iter()
+                    .map(|gnr| NodeRecordInner::try_from(gnr).unwrap().public_key)
+                    .collect::<HashSet<PublicKey>>()
+            })
+            .collect::<Vec<HashSet<PublicKey>>>();
+        let expected_keys = vec_to_set(vec![root_key.clone(), other.public_key().clone()]);
+        assert_eq!(gossip_keys, vec![expected_keys.clone(), expected_keys]);
+    }
+
+    #[test]
+    fn gossip_to_neighbors_sends_only_what_each_neighbor_lacks() {
+        let mut subject = make_standard_subject();
+        let root_key = subject.neighborhood_database.root().public_key().clone();
+        let up_to_date = make_node_record(1234, true, false);
+        let behind = make_node_record(2345, true, false);
+        let unknown = make_node_record(3456, true, false);
+        vec![&up_to_date, &behind, &unknown]
+            .into_iter()
+            .for_each(|neighbor| {
+                subject
+                    .neighborhood_database
+                    .add_node(neighbor.clone())
+                    .unwrap();
+                subject
+                    .neighborhood_database
+                    .add_arbitrary_full_neighbor(&root_key, neighbor.public_key());
+            });
+        subject
+            .neighborhood_database
+            .root_mut()
+            .regenerate_signed_gossip(cryptde());
+        let current_summary = GossipBuilder::new(&subject.neighborhood_database)
+            .version_summary()
+            .build()
+            .version_summary_opt
+            .unwrap();
+        let mut behind_summary = current_summary.clone();
+        behind_summary.insert(root_key.clone(), 0);
+        behind_summary.remove(unknown.public_key());
+        subject.neighborhood_database.root_mut().set_version(1);
+        let mut current_summary = current_summary;
+        current_summary.insert(root_key.clone(), 1);
+        subject.neighbor_version_summaries.insert(
+            up_to_date.public_key().clone(),
+            NeighborVersionSummary::new(current_summary, Instant::now()),
+        );
+        subject.neighbor_version_summaries.insert(
+            behind.public_key().clone(),
+            NeighborVersionSummary::new(behind_summary, Instant::now()),
+        );
+        let system = System::new("gossip_to_neighbors_sends_only_what_each_neighbor_lacks");
+        let (hopper, _, hopper_recording_arc) = make_recorder();
+        let peer_actors = peer_actors_builder().hopper(hopper).build();
+        subject.hopper = Some(peer_actors.hopper.from_hopper_client);
+
+        subject.gossip_to_neighbors();
 
-        fn produce_debut(&self, _database: &NeighborhoodDatabase) -> Gossip {
-            unimplemented!()
-        }
+        System::current().stop();
+        system.run();
+        let hopper_recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(hopper_recording.len(), 2);
+        let gossip_keys_by_target = (0..2)
+            .map(|idx| {
+                let package = hopper_recording.get_record::<IncipientCoresPackage>(idx);
+                let target_key = package.route.next_hop(cryptde()).unwrap().public_key;
+                let gossip =
+                    match decodex::<MessageType>(&CryptDENull::from(&target_key), &package.payload)
+                    {
+                        Ok(MessageType::Gossip(g)) => g,
+                        x => panic!("Expected MessageType::Gossip, got {:?}", x),
+                    };
+                let gossip_keys = gossip
+                    .node_records
+                    .into_iter()
+                    .map(|gnr| NodeRecordInner::try_from(gnr).unwrap().public_key)
+                    .collect::<HashSet<PublicKey>>();
+                (target_key, gossip_keys)
+            })
+            .collect::<HashMap<PublicKey, HashSet<PublicKey>>>();
+        assert_eq!(
+            gossip_keys_by_target.get(behind.public_key()),
+            Some(&vec_to_set(vec![
+                root_key.clone(),
+                unknown.public_key().clone()
+            ]))
+        );
+        assert_eq!(
+            gossip_keys_by_target.get(unknown.public_key()),
+            Some(&vec_to_set(vec![
+                root_key.clone(),
+                up_to_date.public_key().clone(),
+                behind.public_key().clone()
+            ]))
+        );
     }
 
-    impl GossipProducerMock {
-        pub fn new() -> GossipProducerMock {
-            Self::default()
-        }
+    #[test]
+    fn gossip_to_neighbors_waits_for_the_end_of_the_batch_window() {
+        let mut subject = make_standard_subject();
+        subject.gossip_batch_window = Duration::from_millis(1000);
+        let root_key = subject.neighborhood_database.root().public_key().clone();
+        let neighbor = make_node_record(1234, true, false);
+        subject
+            .neighborhood_database
+            .add_node(neighbor.clone())
+            .unwrap();
+        subject
+            .neighborhood_database
+            .add_arbitrary_full_neighbor(&root_key, neighbor.public_key());
+        let system = System::new("gossip_to_neighbors_waits_for_the_end_of_the_batch_window");
+        let (hopper, _, hopper_recording_arc) = make_recorder();
+        let peer_actors = peer_actors_builder().hopper(hopper).build();
+        subject.hopper = Some(peer_actors.hopper.from_hopper_client);
 
-        pub fn produce_params(
-            mut self,
-            params_arc: &Arc<Mutex<Vec<(NeighborhoodDatabase, PublicKey)>>>,
-        ) -> GossipProducerMock {
-            self.produce_params = params_arc.clone();
-            self
-        }
+        subject.gossip_to_neighbors();
+        subject.gossip_to_neighbors();
+        assert!(subject
+            .pending_gossip_targets
+            .contains(neighbor.public_key()));
+        subject.flush_pending_gossip();
 
-        pub fn produce_result(self, result: Gossip) -> GossipProducerMock {
-            self.produce_results.borrow_mut().push(result);
-            self
-        }
+        System::current().stop();
+        system.run();
+        assert_eq!(hopper_recording_arc.lock().unwrap().len(), 1);
+        assert!(subject.pending_gossip_targets.is_empty());
     }
 
     #[test]
-    #[should_panic(
-        expected = "A SubstratumNode without an --ip setting is not decentralized and cannot have any --neighbor settings"
-    )]
-    fn neighborhood_cannot_be_created_with_neighbors_and_default_ip() {
-        let cryptde = cryptde();
-        let earning_wallet = Wallet::new("earning");
-        let consuming_wallet = Some(Wallet::new("consuming"));
+    fn set_rate_pack_advertises_new_rates_in_a_new_version_of_the_root_record() {
+        let mut subject = make_standard_subject();
+        subject.gossip_batch_window = Duration::from_millis(1000);
+        let root_key = subject.neighborhood_database.root().public_key().clone();
         let neighbor = make_node_record(1234, true, false);
-
-        Neighborhood::new(
-            cryptde,
-            NeighborhoodConfig {
-                neighbor_configs: vec![NodeDescriptor {
-                    public_key: neighbor.public_key().clone(),
-                    node_addr: neighbor.node_addr_opt().unwrap().clone(),
-                }],
-                is_bootstrap_node: false,
-                local_ip_addr: sentinel_ip_addr(),
-                clandestine_port_list: vec![0],
-                earning_wallet: earning_wallet.clone(),
-                consuming_wallet: consuming_wallet.clone(),
-                rate_pack: rate_pack(100),
-            },
-        );
+        subject
+            .neighborhood_database
+            .add_node(neighbor.clone())
+            .unwrap();
+        subject
+            .neighborhood_database
+            .add_arbitrary_full_neighbor(&root_key, neighbor.public_key());
+        let root_version = subject.neighborhood_database.root().version();
+
+        subject.set_rate_pack(rate_pack(2000));
+        subject.set_rate_pack(rate_pack(2000));
+
+        let root = subject.neighborhood_database.root();
+        assert_eq!(root.rate_pack(), &rate_pack(2000));
+        assert_eq!(root.version(), root_version + 1);
+        assert!(cryptde().verify_signature(
+            root.signed_gossip(),
+            root.signature(),
+            root.public_key()
+        ));
+        let agr = AccessibleGossipRecord::try_from(GossipNodeRecord::from(root.clone())).unwrap();
+        assert_eq!(agr.inner.rate_pack, rate_pack(2000));
+        assert!(subject
+            .pending_gossip_targets
+            .contains(neighbor.public_key()));
     }
 
     #[test]
-    #[should_panic(
-        expected = "A SubstratumNode without an --ip setting is not decentralized and cannot be --node_type bootstrap"
-    )]
-    fn neighborhood_cannot_be_created_as_a_bootstrap_node_with_default_ip() {
-        let cryptde = cryptde();
-        let earning_wallet = Wallet::new("earning");
-        let consuming_wallet = Some(Wallet::new("consuming"));
+    fn gossip_to_a_neighbor_beyond_the_outbound_limit_is_held_back() {
+        init_test_logging();
+        let mut subject = make_standard_subject();
+        let root_key = subject.neighborhood_database.root().public_key().clone();
+        let neighbor = make_node_record(1235, true, false);
+        subject
+            .neighborhood_database
+            .add_node(neighbor.clone())
+            .unwrap();
+        subject
+            .neighborhood_database
+            .add_arbitrary_full_neighbor(&root_key, neighbor.public_key());
+        let system = System::new("gossip_to_a_neighbor_beyond_the_outbound_limit_is_held_back");
+        let (hopper, _, hopper_recording_arc) = make_recorder();
+        let peer_actors = peer_actors_builder().hopper(hopper).build();
+        subject.hopper = Some(peer_actors.hopper.from_hopper_client);
 
-        Neighborhood::new(
-            cryptde,
-            NeighborhoodConfig {
-                neighbor_configs: vec![],
-                is_bootstrap_node: true,
-                local_ip_addr: sentinel_ip_addr(),
-                clandestine_port_list: vec![],
-                earning_wallet: earning_wallet.clone(),
-                consuming_wallet: consuming_wallet.clone(),
-                rate_pack: rate_pack(100),
-            },
+        (0..(MAX_OUTBOUND_GOSSIP_PER_NEIGHBOR + 2)).for_each(|_| subject.gossip_to_neighbors());
+
+        System::current().stop();
+        system.run();
+        assert_eq!(
+            hopper_recording_arc.lock().unwrap().len(),
+            MAX_OUTBOUND_GOSSIP_PER_NEIGHBOR
+        );
+        assert!(subject
+            .pending_gossip_targets
+            .contains(neighbor.public_key()));
+        assert_eq!(
+            subject
+                .outbound_gossip_limiter
+                .total_refused(neighbor.public_key()),
+            2
         );
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: Neighborhood: Holding back Gossip to Node {}: more than {} changes in {:?} (1 held back so far); possible Gossip storm",
+            neighbor.public_key(),
+            MAX_OUTBOUND_GOSSIP_PER_NEIGHBOR,
+            GOSSIP_RATE_INTERVAL
+        ));
     }
 
     #[test]
-    fn bootstrap_node_neighborhood_creates_single_node_database() {
-        let cryptde = cryptde();
-        let earning_wallet = Wallet::new("earning");
-        let this_node_addr = NodeAddr::new(&IpAddr::from_str("5.4.3.2").unwrap(), &vec![5678]);
-
-        let subject = Neighborhood::new(
-            cryptde,
-            NeighborhoodConfig {
-                neighbor_configs: vec![],
-                is_bootstrap_node: true,
-                local_ip_addr: this_node_addr.ip_addr(),
-                clandestine_port_list: this_node_addr.ports().clone(),
-                earning_wallet: earning_wallet.clone(),
-                consuming_wallet: None,
-                rate_pack: rate_pack(100),
-            },
-        );
+    fn gossip_limits_report_excesses_when_they_are_pruned() {
+        init_test_logging();
+        let mut subject = make_standard_subject();
+        let neighbor_key = PublicKey::new(b"neighbor");
+        let source_ip = IpAddr::from_str("3.4.5.6").unwrap();
+        let start = Instant::now();
+        (0..(MAX_OUTBOUND_GOSSIP_PER_NEIGHBOR + 3)).for_each(|_| {
+            subject.outbound_gossip_limiter.admit(&neighbor_key, start);
+        });
+        (0..(MAX_INBOUND_GOSSIP_PER_SOURCE + 4)).for_each(|_| {
+            subject.inbound_gossip_limiter.admit(&source_ip, start);
+        });
 
-        let root_node_record_ref = subject.neighborhood_database.root();
+        subject.prune_gossip_limiters(start + GOSSIP_RATE_INTERVAL);
 
-        assert_eq!(root_node_record_ref.public_key(), cryptde.public_key());
-        assert_eq!(root_node_record_ref.node_addr_opt(), Some(this_node_addr));
-        assert_eq!(root_node_record_ref.is_bootstrap_node(), true);
-        assert_eq!(root_node_record_ref.half_neighbor_keys().len(), 0);
+        assert_eq!(
+            subject.outbound_gossip_limiter.total_refused(&neighbor_key),
+            0
+        );
+        assert_eq!(subject.inbound_gossip_limiter.total_refused(&source_ip), 0);
+        let tlh = TestLogHandler::new();
+        tlh.exists_log_containing(&format!(
+            "INFO: Neighborhood: Gossip to Node {} is back within its limit after 3 changes were held back",
+            neighbor_key
+        ));
+        tlh.exists_log_containing(
+            "INFO: Neighborhood: Gossip from 3.4.5.6 is back within its limit after 4 messages were dropped",
+        );
     }
 
     #[test]
-    fn bootstrap_node_with_no_neighbor_configs_ignores_bootstrap_neighborhood_now_message() {
+    fn gossip_from_a_source_beyond_the_inbound_limit_is_dropped() {
         init_test_logging();
-        let cryptde = cryptde();
-        let earning_wallet = Wallet::new("earning");
-        let consuming_wallet = Some(Wallet::new("consuming"));
-        let system = System::new("bootstrap_node_ignores_bootstrap_neighborhood_now_message");
-        let subject = Neighborhood::new(
-            cryptde,
-            NeighborhoodConfig {
-                neighbor_configs: vec![],
-                is_bootstrap_node: true,
-                local_ip_addr: IpAddr::from_str("5.4.3.2").unwrap(),
-                clandestine_port_list: vec![5678],
-                earning_wallet: earning_wallet.clone(),
-                consuming_wallet: consuming_wallet.clone(),
-                rate_pack: rate_pack(100),
-            },
+        let mut subject = make_standard_subject();
+        let handle_params_arc = Arc::new(Mutex::new(vec![]));
+        let gossip_acceptor = (0..MAX_INBOUND_GOSSIP_PER_SOURCE).fold(
+            GossipAcceptorMock::new().handle_params(&handle_params_arc),
+            |acceptor, _| acceptor.handle_result(GossipAcceptanceResult::Ignored),
         );
-        let addr: Addr<Neighborhood> = subject.start();
-        let sub: Recipient<BootstrapNeighborhoodNowMessage> =
-            addr.clone().recipient::<BootstrapNeighborhoodNowMessage>();
-        let (hopper, _, hopper_recording_arc) = make_recorder();
-        let peer_actors = peer_actors_builder().hopper(hopper).build();
-        addr.try_send(BindMessage { peer_actors }).unwrap();
+        subject.gossip_acceptor = Box::new(gossip_acceptor);
+        let source = make_node_record(3456, true, false);
+        let source_db = db_from_node(&source);
+        let gossip = GossipBuilder::new(&source_db)
+            .node(source.public_key(), true)
+            .build();
+        let source_ip = IpAddr::from_str("3.4.5.6").unwrap();
 
-        sub.try_send(BootstrapNeighborhoodNowMessage {}).unwrap();
+        (0..(MAX_INBOUND_GOSSIP_PER_SOURCE + 1))
+            .for_each(|_| subject.handle_gossip(gossip.clone(), source_ip));
 
-        System::current().stop_with_code(0);
-        system.run();
-        let recording = hopper_recording_arc.lock().unwrap();
-        assert_eq!(recording.len(), 0);
-        TestLogHandler::new().exists_log_containing(
-            "INFO: Neighborhood: No bootstrap Nodes to report to; continuing",
+        assert_eq!(
+            handle_params_arc.lock().unwrap().len(),
+            MAX_INBOUND_GOSSIP_PER_SOURCE
         );
+        assert_eq!(subject.inbound_gossip_limiter.total_refused(&source_ip), 1);
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: Neighborhood: Dropping Gossip from 3.4.5.6: more than {} messages in {:?} (1 dropped so far); possible abuse",
+            MAX_INBOUND_GOSSIP_PER_SOURCE, GOSSIP_RATE_INTERVAL
+        ));
     }
 
     #[test]
     fn neighborhood_adds_nodes_and_links() {
         let cryptde = cryptde();
         let earning_wallet = Wallet::new("earning");
-        let consuming_wallet = Some(Wallet::new("consuming"));
+        let consuming_wallet = Some(make_paying_wallet(b"consuming"));
         let one_bootstrap_node = make_node_record(3456, true, true);
         let another_bootstrap_node = make_node_record(4567, true, true);
         let this_node_addr = NodeAddr::new(&IpAddr::from_str("5.4.3.2").unwrap(), &vec![5678]);
@@ -1129,6 +3752,9 @@ mod tests {
                 earning_wallet: earning_wallet.clone(),
                 consuming_wallet: consuming_wallet.clone(),
                 rate_pack: rate_pack(100),
+                gossip_batch_window: Duration::from_millis(0),
+                diversity: DiversityConfig::default(),
+                behind_nat: false,
             },
         );
 
@@ -1183,7 +3809,7 @@ mod tests {
     fn node_query_responds_with_none_when_key_query_matches_no_configured_data() {
         let cryptde = cryptde();
         let earning_wallet = Wallet::new("earning");
-        let consuming_wallet = Some(Wallet::new("consuming"));
+        let consuming_wallet = Some(make_paying_wallet(b"consuming"));
         let system =
             System::new("node_query_responds_with_none_when_key_query_matches_no_configured_data");
         let subject = Neighborhood::new(
@@ -1202,6 +3828,9 @@ mod tests {
                 earning_wallet: earning_wallet.clone(),
                 consuming_wallet: consuming_wallet.clone(),
                 rate_pack: rate_pack(100),
+                gossip_batch_window: Duration::from_millis(0),
+                diversity: DiversityConfig::default(),
+                behind_nat: false,
             },
         );
         let addr: Addr<Neighborhood> = subject.start();
@@ -1219,7 +3848,7 @@ mod tests {
     fn node_query_responds_with_result_when_key_query_matches_configured_data() {
         let cryptde = cryptde();
         let earning_wallet = Wallet::new("earning");
-        let consuming_wallet = Some(Wallet::new("consuming"));
+        let consuming_wallet = Some(make_paying_wallet(b"consuming"));
         let system =
             System::new("node_query_responds_with_result_when_key_query_matches_configured_data");
         let one_neighbor = make_node_record(2345, true, false);
@@ -1234,6 +3863,9 @@ mod tests {
                 earning_wallet: earning_wallet.clone(),
                 consuming_wallet: consuming_wallet.clone(),
                 rate_pack: rate_pack(100),
+                gossip_batch_window: Duration::from_millis(0),
+                diversity: DiversityConfig::default(),
+                behind_nat: false,
             },
         );
         subject
@@ -1264,7 +3896,7 @@ mod tests {
     fn node_query_responds_with_none_when_ip_address_query_matches_no_configured_data() {
         let cryptde = cryptde();
         let earning_wallet = Wallet::new("earning");
-        let consuming_wallet = Some(Wallet::new("consuming"));
+        let consuming_wallet = Some(make_paying_wallet(b"consuming"));
         let system = System::new(
             "node_query_responds_with_none_when_ip_address_query_matches_no_configured_data",
         );
@@ -1284,6 +3916,9 @@ mod tests {
                 earning_wallet: earning_wallet.clone(),
                 consuming_wallet: consuming_wallet.clone(),
                 rate_pack: rate_pack(100),
+                gossip_batch_window: Duration::from_millis(0),
+                diversity: DiversityConfig::default(),
+                behind_nat: false,
             },
         );
         let addr: Addr<Neighborhood> = subject.start();
@@ -1325,6 +3960,9 @@ mod tests {
                 earning_wallet: node_record.earning_wallet(),
                 consuming_wallet: None,
                 rate_pack: rate_pack(100),
+                gossip_batch_window: Duration::from_millis(0),
+                diversity: DiversityConfig::default(),
+                behind_nat: false,
             },
         );
         subject
@@ -1463,10 +4101,36 @@ mod tests {
                 ],
                 0,
             ),
+            exit_supports_compression: true,
         };
         assert_eq!(expected_response, result);
     }
 
+    #[test]
+    fn route_query_response_reports_when_exit_does_not_support_compression() {
+        let system =
+            System::new("route_query_response_reports_when_exit_does_not_support_compression");
+        let mut subject = make_standard_subject();
+        subject.consuming_wallet_opt = None;
+        let mut exit_node = make_node_record(3456, false, false);
+        exit_node.set_supports_compression(false);
+        let originating_node = &subject.neighborhood_database.root().clone();
+        {
+            let db = &mut subject.neighborhood_database;
+            db.add_node(exit_node.clone()).unwrap();
+            db.add_arbitrary_full_neighbor(exit_node.public_key(), originating_node.public_key());
+        }
+        let addr: Addr<Neighborhood> = subject.start();
+        let sub: Recipient<RouteQueryMessage> = addr.recipient::<RouteQueryMessage>();
+
+        let future = sub.send(RouteQueryMessage::data_indefinite_route_request(1));
+
+        System::current().stop_with_code(0);
+        system.run();
+        let result = future.wait().unwrap().unwrap();
+        assert_eq!(result.exit_supports_compression, false);
+    }
+
     #[test]
     fn route_query_responds_with_none_when_asked_for_one_hop_round_trip_route_without_consuming_wallet_when_back_route_needs_two_hops(
     ) {
@@ -1548,6 +4212,7 @@ mod tests {
                 vec![ExpectedService::Nothing, ExpectedService::Nothing],
                 0,
             ),
+            exit_supports_compression: false,
         };
         assert_eq!(result, expected_response);
     }
@@ -1639,15 +4304,34 @@ mod tests {
         };
 
         let result = data_route.wait().unwrap().unwrap();
+        let expected_route = Route::round_trip(
+            segment(vec![p, q, r], Component::ProxyClient),
+            segment(vec![r, q, p], Component::ProxyServer),
+            cryptde,
+            consuming_wallet_opt,
+            0,
+        )
+        .unwrap();
+        // Each Route is signed with its own nonce, so their hops can only be compared unsigned.
+        let unsigned_hops = |route: &Route| {
+            let mut key = p.public_key().clone();
+            let mut hops = vec![];
+            while !key.is_empty() {
+                let hop =
+                    LiveHop::decode(&CryptDENull::from(&key), &route.hops[hops.len()]).unwrap();
+                key = hop.public_key.clone();
+                hops.push((
+                    hop.public_key.clone(),
+                    hop.component,
+                    hop.consuming_wallet(),
+                ));
+            }
+            hops
+        };
+        assert_eq!(unsigned_hops(&result.route), unsigned_hops(&expected_route));
+        assert_eq!(result.route.hops.len(), expected_route.hops.len());
         let expected_response = RouteQueryResponse {
-            route: Route::round_trip(
-                segment(vec![p, q, r], Component::ProxyClient),
-                segment(vec![r, q, p], Component::ProxyServer),
-                cryptde,
-                consuming_wallet_opt,
-                0,
-            )
-            .unwrap(),
+            route: result.route.clone(),
             expected_services: ExpectedServices::RoundTrip(
                 vec![
                     ExpectedService::Nothing,
@@ -1677,10 +4361,98 @@ mod tests {
                 ],
                 0,
             ),
+            exit_supports_compression: true,
         };
         assert_eq!(result, expected_response);
     }
 
+    #[test]
+    fn disjoint_route_query_avoids_specified_intermediate_nodes() {
+        let mut subject = make_standard_subject();
+        let p = &subject.neighborhood_database.root().clone();
+        let q = &make_node_record(3456, true, false);
+        let u = &make_node_record(4567, true, false);
+        let r = &make_node_record(5678, false, false);
+        {
+            let db = &mut subject.neighborhood_database;
+            db.add_node(q.clone()).unwrap();
+            db.add_node(u.clone()).unwrap();
+            db.add_node(r.clone()).unwrap();
+            let mut dual_edge = |a: &NodeRecord, b: &NodeRecord| {
+                db.add_arbitrary_full_neighbor(a.public_key(), b.public_key());
+            };
+            dual_edge(p, q);
+            dual_edge(p, u);
+            dual_edge(q, r);
+            dual_edge(u, r);
+        }
+
+        let result = subject
+            .make_round_trip_route(RouteQueryMessage::data_disjoint_route_request(
+                2,
+                r.public_key().clone(),
+                vec![q.public_key().clone()],
+                RouteConstraints::default(),
+            ))
+            .unwrap();
+
+        let routing_keys = |services: &Vec<ExpectedService>| -> Vec<PublicKey> {
+            services
+                .iter()
+                .filter_map(|service| match service {
+                    ExpectedService::Routing(key, _, _) => Some(key.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+        match result.expected_services {
+            ExpectedServices::RoundTrip(over, back, _) => {
+                assert_eq!(routing_keys(&over), vec![u.public_key().clone()]);
+                assert_eq!(routing_keys(&back), vec![u.public_key().clone()]);
+                assert_eq!(
+                    over.last(),
+                    Some(&ExpectedService::Exit(
+                        r.public_key().clone(),
+                        r.earning_wallet(),
+                        rate_pack(5678),
+                    ))
+                );
+            }
+            x => panic!("Expected RoundTrip, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn disjoint_route_query_fails_when_every_route_uses_an_avoided_node() {
+        let mut subject = make_standard_subject();
+        let p = &subject.neighborhood_database.root().clone();
+        let q = &make_node_record(3456, true, false);
+        let r = &make_node_record(5678, false, false);
+        {
+            let db = &mut subject.neighborhood_database;
+            db.add_node(q.clone()).unwrap();
+            db.add_node(r.clone()).unwrap();
+            db.add_arbitrary_full_neighbor(p.public_key(), q.public_key());
+            db.add_arbitrary_full_neighbor(q.public_key(), r.public_key());
+        }
+
+        let result = subject.make_round_trip_route(RouteQueryMessage::data_disjoint_route_request(
+            2,
+            r.public_key().clone(),
+            vec![q.public_key().clone()],
+            RouteConstraints::default(),
+        ));
+
+        assert_eq!(
+            Err(format!(
+                "Couldn't find any routes: at least 2-hop from {} to ProxyClient at Standard {}",
+                p.public_key(),
+                r.public_key()
+            )),
+            result
+        );
+    }
+
     #[test]
     fn sort_routes_by_desirable_exit_nodes() {
         let mut subject = make_standard_subject();
@@ -1918,6 +4690,114 @@ mod tests {
             Test is written from the standpoint of P
     */
 
+    #[test]
+    fn constrained_route_query_exits_only_through_a_pinned_exit_node() {
+        let mut subject = make_standard_subject();
+        let p = &subject.neighborhood_database.root().clone();
+        let q = &make_node_record(3456, true, false);
+        let u = &make_node_record(4567, true, false);
+        let r = &make_node_record(5678, false, false);
+        let s = &make_node_record(6789, false, false);
+        {
+            let db = &mut subject.neighborhood_database;
+            db.add_node(q.clone()).unwrap();
+            db.add_node(u.clone()).unwrap();
+            db.add_node(r.clone()).unwrap();
+            db.add_node(s.clone()).unwrap();
+            let mut dual_edge = |a: &NodeRecord, b: &NodeRecord| {
+                db.add_arbitrary_full_neighbor(a.public_key(), b.public_key());
+            };
+            dual_edge(p, q);
+            dual_edge(p, u);
+            dual_edge(q, r);
+            dual_edge(u, s);
+        }
+        let constraints = RouteConstraints {
+            exit_keys: vec![s.public_key().clone()],
+            excluded_keys: vec![],
+            excluded_ip_ranges: vec![],
+        };
+
+        let result = subject
+            .make_round_trip_route(RouteQueryMessage::data_constrained_route_request(
+                2,
+                constraints,
+            ))
+            .unwrap();
+
+        match result.expected_services {
+            ExpectedServices::RoundTrip(over, _, _) => assert_eq!(
+                over.last(),
+                Some(&ExpectedService::Exit(
+                    s.public_key().clone(),
+                    s.earning_wallet(),
+                    rate_pack(6789),
+                ))
+            ),
+            x => panic!("Expected RoundTrip, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn constrained_route_query_avoids_nodes_excluded_by_key_and_by_ip_address() {
+        let mut subject = make_standard_subject();
+        let p = &subject.neighborhood_database.root().clone();
+        let q = &make_node_record(3456, true, false);
+        let u = &make_node_record(4567, true, false);
+        let r = &make_node_record(5678, false, false);
+        {
+            let db = &mut subject.neighborhood_database;
+            db.add_node(q.clone()).unwrap();
+            db.add_node(u.clone()).unwrap();
+            db.add_node(r.clone()).unwrap();
+            let mut dual_edge = |a: &NodeRecord, b: &NodeRecord| {
+                db.add_arbitrary_full_neighbor(a.public_key(), b.public_key());
+            };
+            dual_edge(p, q);
+            dual_edge(p, u);
+            dual_edge(q, r);
+            dual_edge(u, r);
+        }
+        let key_constraints = RouteConstraints {
+            exit_keys: vec![],
+            excluded_keys: vec![q.public_key().clone()],
+            excluded_ip_ranges: vec![],
+        };
+        let all_constraints = RouteConstraints {
+            excluded_ip_ranges: vec![IpRange::from_str("4.0.0.0/8").unwrap()],
+            ..key_constraints.clone()
+        };
+
+        let key_result = subject
+            .make_round_trip_route(RouteQueryMessage::data_constrained_route_request(
+                2,
+                key_constraints,
+            ))
+            .unwrap();
+        let all_result = subject.make_round_trip_route(
+            RouteQueryMessage::data_constrained_route_request(2, all_constraints),
+        );
+
+        match key_result.expected_services {
+            ExpectedServices::RoundTrip(over, _, _) => assert_eq!(
+                over[1],
+                ExpectedService::Routing(
+                    u.public_key().clone(),
+                    u.earning_wallet(),
+                    rate_pack(4567)
+                )
+            ),
+            x => panic!("Expected RoundTrip, got {:?}", x),
+        }
+        assert_eq!(
+            Err(format!(
+                "Couldn't find any routes: at least 2-hop from {} to ProxyClient at Standard within route constraints",
+                p.public_key()
+            )),
+            all_result
+        );
+    }
+
     #[test]
     fn complete_routes_exercise() {
         let mut subject = make_standard_subject();
@@ -1941,7 +4821,8 @@ mod tests {
         };
 
         // At least two hops from P to anywhere standard
-        let routes = subject.complete_routes(vec![p], None, 2, true);
+        let routes =
+            subject.complete_routes(vec![p], None, 2, true, &[], &RouteConstraints::default());
 
         contains(&routes, vec![p, s, t]);
         contains(&routes, vec![p, r, s]);
@@ -1949,30 +4830,270 @@ mod tests {
         assert_eq!(3, routes.len());
 
         // At least two hops from P to T
-        let routes = subject.complete_routes(vec![p], Some(t), 2, true);
+        let routes =
+            subject.complete_routes(vec![p], Some(t), 2, true, &[], &RouteConstraints::default());
 
         contains(&routes, vec![p, s, t]);
         contains(&routes, vec![p, r, s, t]);
         assert_eq!(2, routes.len());
 
         // At least two hops from P to S - one choice
-        let routes = subject.complete_routes(vec![p], Some(s), 2, true);
+        let routes =
+            subject.complete_routes(vec![p], Some(s), 2, true, &[], &RouteConstraints::default());
 
         contains(&routes, vec![p, r, s]);
         assert_eq!(1, routes.len());
 
         // At least two hops from P to Q - impossible
-        let routes = subject.complete_routes(vec![p], Some(q), 2, true);
+        let routes =
+            subject.complete_routes(vec![p], Some(q), 2, true, &[], &RouteConstraints::default());
 
         assert_eq!(0, routes.len());
     }
 
+    #[test]
+    fn complete_routes_keeps_nodes_with_the_same_ip_prefix_or_earning_wallet_apart() {
+        let mut subject = make_standard_subject();
+        let db = &mut subject.neighborhood_database;
+        let p = &db.root_mut().public_key().clone();
+        let q = &db.add_node(make_node_record(3456, true, false)).unwrap();
+        let r = &db.add_node(make_node_record(3457, true, false)).unwrap(); // same /24 as Q
+        let t = &db.add_node(make_node_record(4567, true, false)).unwrap();
+        let s = &db.add_node(make_node_record(5678, false, false)).unwrap();
+        db.add_arbitrary_full_neighbor(p, q);
+        db.add_arbitrary_full_neighbor(q, r);
+        db.add_arbitrary_full_neighbor(q, t);
+        db.add_arbitrary_full_neighbor(r, s);
+        db.add_arbitrary_full_neighbor(t, s);
+
+        let diverse_routes =
+            subject.complete_routes(vec![p], Some(s), 3, true, &[], &RouteConstraints::default());
+
+        assert_eq!(diverse_routes, vec![vec![p, q, t, s]]);
+
+        let q_wallet = subject
+            .neighborhood_database
+            .node_by_key(q)
+            .unwrap()
+            .earning_wallet();
+        subject
+            .neighborhood_database
+            .node_by_key_mut(t)
+            .unwrap()
+            .set_earning_wallet(q_wallet);
+
+        let shared_wallet_routes =
+            subject.complete_routes(vec![p], Some(s), 3, true, &[], &RouteConstraints::default());
+
+        assert_eq!(shared_wallet_routes, Vec::<Vec<&PublicKey>>::new());
+    }
+
+    #[test]
+    fn complete_routes_enters_a_relayed_node_only_from_one_of_its_relays() {
+        let mut subject = make_standard_subject();
+        let db = &mut subject.neighborhood_database;
+        let p = &db.root_mut().public_key().clone();
+        let q = &db.add_node(make_node_record(3456, true, false)).unwrap();
+        let r = &db.add_node(make_node_record(4567, true, false)).unwrap();
+        let s = &db.add_node(make_node_record(5678, true, false)).unwrap();
+        db.add_arbitrary_full_neighbor(p, q);
+        db.add_arbitrary_full_neighbor(p, r);
+        db.add_arbitrary_full_neighbor(q, s);
+        db.add_arbitrary_full_neighbor(r, s);
+        db.node_by_key_mut(s)
+            .unwrap()
+            .set_relays(BTreeSet::from_iter(vec![r.clone()]));
+
+        let routes =
+            subject.complete_routes(vec![p], Some(s), 2, true, &[], &RouteConstraints::default());
+
+        assert_eq!(routes, vec![vec![p, r, s]]);
+    }
+
+    #[test]
+    fn complete_routes_abandons_long_detours_to_target() {
+        let mut subject = make_standard_subject();
+        let db = &mut subject.neighborhood_database;
+        let p = &db.root_mut().public_key().clone();
+        let a = &db.add_node(make_node_record(1234, false, false)).unwrap();
+        let b = &db.add_node(make_node_record(2345, false, false)).unwrap();
+        let c = &db.add_node(make_node_record(3456, false, false)).unwrap();
+        let d = &db.add_node(make_node_record(4567, false, false)).unwrap();
+        let e = &db.add_node(make_node_record(5678, false, false)).unwrap();
+        let t = &db.add_node(make_node_record(6789, false, false)).unwrap();
+        db.add_arbitrary_full_neighbor(p, a);
+        db.add_arbitrary_full_neighbor(a, t);
+        db.add_arbitrary_full_neighbor(p, b);
+        db.add_arbitrary_full_neighbor(b, c);
+        db.add_arbitrary_full_neighbor(c, d);
+        db.add_arbitrary_full_neighbor(d, e);
+        db.add_arbitrary_full_neighbor(e, t);
+
+        let routes =
+            subject.complete_routes(vec![p], Some(t), 2, true, &[], &RouteConstraints::default());
+
+        assert_eq!(routes, vec![vec![p, a, t]]);
+    }
+
+    #[test]
+    fn complete_routes_stops_looking_when_it_has_enough_candidates() {
+        let mut subject = make_standard_subject();
+        let db = &mut subject.neighborhood_database;
+        let p = &db.root_mut().public_key().clone();
+        (1..=(ROUTE_SEARCH_CANDIDATE_LIMIT as u16 + 8)).for_each(|n| {
+            let key = db.add_node(make_node_record(n, false, false)).unwrap();
+            db.add_arbitrary_full_neighbor(p, &key);
+        });
+
+        let routes =
+            subject.complete_routes(vec![p], None, 1, true, &[], &RouteConstraints::default());
+
+        assert_eq!(routes.len(), ROUTE_SEARCH_CANDIDATE_LIMIT);
+    }
+
+    #[test]
+    fn complete_routes_finds_qualifying_routes_in_large_network() {
+        let mut subject = make_standard_subject();
+        let keys = add_synthetic_network(&mut subject, 1000);
+        let p = &subject.neighborhood_database.root().public_key().clone();
+        let t = &keys[500];
+
+        let routes =
+            subject.complete_routes(vec![p], None, 3, false, &[], &RouteConstraints::default());
+        let targeted_routes = subject.complete_routes(
+            vec![p],
+            Some(t),
+            3,
+            false,
+            &[],
+            &RouteConstraints::default(),
+        );
+
+        assert!(!routes.is_empty());
+        routes
+            .iter()
+            .for_each(|route| check_synthetic_route(&subject, route, 3));
+        assert!(!targeted_routes.is_empty());
+        targeted_routes.iter().for_each(|route| {
+            assert_eq!(route.last(), Some(&t));
+            check_synthetic_route(&subject, route, 3);
+        });
+    }
+
+    #[test]
+    #[ignore] // Benchmark: cargo test --release route_search_benchmark -- --ignored --nocapture
+    fn route_search_benchmark() {
+        let iterations = 100;
+        vec![1000, 2500, 5000, 9000]
+            .into_iter()
+            .for_each(|node_count| {
+                let mut subject = make_standard_subject();
+                let keys = add_synthetic_network(&mut subject, node_count);
+                let p = &subject.neighborhood_database.root().public_key().clone();
+                let start = Instant::now();
+                (0..iterations).for_each(|idx| {
+                    let over = subject.complete_routes(
+                        vec![p],
+                        None,
+                        3,
+                        false,
+                        &[],
+                        &RouteConstraints::default(),
+                    );
+                    let exit = over[0].last().unwrap();
+                    let back = subject.complete_routes(
+                        vec![exit],
+                        Some(p),
+                        3,
+                        true,
+                        &[],
+                        &RouteConstraints::default(),
+                    );
+                    assert!(!back.is_empty(), "No route back from {}", exit);
+                    let distant = &keys[(idx * 97) % keys.len()];
+                    subject.complete_routes(
+                        vec![p],
+                        Some(distant),
+                        3,
+                        false,
+                        &[],
+                        &RouteConstraints::default(),
+                    );
+                });
+                println!(
+                    "{} Nodes: {:?} per round trip plus targeted route",
+                    node_count,
+                    start.elapsed() / iterations as u32
+                );
+            });
+    }
+
+    // A ring of Nodes, each a full neighbor of the next two around the ring and of one other
+    // Node scattered across it. The root is a full neighbor of the first three, which are the
+    // only ones with IP addresses, and every hundredth Node is a bootstrap Node.
+    fn add_synthetic_network(subject: &mut Neighborhood, node_count: u16) -> Vec<PublicKey> {
+        let root_key = subject.neighborhood_database.root().public_key().clone();
+        let db = &mut subject.neighborhood_database;
+        let keys: Vec<PublicKey> = (1..=node_count)
+            .map(|n| {
+                db.add_node(make_node_record(n, n <= 3, n % 100 == 50))
+                    .unwrap()
+            })
+            .collect();
+        let count = keys.len();
+        (0..count).for_each(|idx| {
+            let chord_idx = (idx * 7919 + 13) % count;
+            vec![(idx + 1) % count, (idx + 2) % count, chord_idx]
+                .into_iter()
+                .filter(|other_idx| *other_idx != idx)
+                .for_each(|other_idx| {
+                    db.add_arbitrary_full_neighbor(&keys[idx], &keys[other_idx]);
+                })
+        });
+        keys.iter().take(3).for_each(|key| {
+            db.add_arbitrary_full_neighbor(&root_key, key);
+        });
+        keys
+    }
+
+    fn check_synthetic_route(subject: &Neighborhood, route: &Vec<&PublicKey>, min_hops: usize) {
+        let db = &subject.neighborhood_database;
+        assert!(route.len() > min_hops, "Route too short: {:?}", route);
+        assert_eq!(
+            route.iter().collect::<HashSet<_>>().len(),
+            route.len(),
+            "Route has a cycle: {:?}",
+            route
+        );
+        route.windows(2).for_each(|pair| {
+            assert!(
+                db.has_full_neighbor(pair[0], pair[1]),
+                "Route has a gap: {:?}",
+                route
+            )
+        });
+        route[1..(route.len() - 1)].iter().for_each(|key| {
+            assert!(
+                !db.node_by_key(key).unwrap().is_bootstrap_node(),
+                "Route goes through bootstrap Node: {:?}",
+                route
+            )
+        });
+        let exit = db.node_by_key(route.last().unwrap()).unwrap();
+        assert_eq!(
+            exit.node_addr_opt(),
+            None,
+            "Route has next-door exit: {:?}",
+            route
+        );
+    }
+
     #[test]
     fn gossips_after_removing_a_neighbor() {
         let (hopper, hopper_awaiter, hopper_recording) = make_recorder();
         let cryptde = cryptde();
         let earning_wallet = Wallet::new("earning");
-        let consuming_wallet = Some(Wallet::new("consuming"));
+        let consuming_wallet = Some(make_paying_wallet(b"consuming"));
         let this_node = NodeRecord::new_for_tests(
             &cryptde.public_key(),
             Some(&NodeAddr::new(
@@ -2000,6 +5121,9 @@ mod tests {
                     earning_wallet: earning_wallet.clone(),
                     consuming_wallet: consuming_wallet.clone(),
                     rate_pack: rate_pack(100),
+                    gossip_batch_window: Duration::from_millis(0),
+                    diversity: DiversityConfig::default(),
+                    behind_nat: false,
                 },
             );
             let db = &mut subject.neighborhood_database;
@@ -2185,6 +5309,8 @@ mod tests {
         subject.handle_gossip(
             Gossip {
                 node_records: vec![],
+                version_summary_opt: None,
+                delta: false,
             },
             IpAddr::from_str("1.1.1.1").unwrap(),
         );
@@ -2271,6 +5397,8 @@ mod tests {
         subject.gossip_acceptor = Box::new(gossip_acceptor);
         let gossip = Gossip {
             node_records: vec![],
+            version_summary_opt: None,
+            delta: false,
         };
         let produce_params_arc = Arc::new(Mutex::new(vec![]));
         let gossip_producer = GossipProducerMock::new()
@@ -2288,6 +5416,8 @@ mod tests {
         subject.handle_gossip(
             Gossip {
                 node_records: vec![],
+                version_summary_opt: None,
+                delta: false,
             },
             IpAddr::from_str("1.1.1.1").unwrap(),
         );
@@ -2374,6 +5504,8 @@ mod tests {
             // In real life this would be Relay Gossip from gossip_source to debut_node.
             Gossip {
                 node_records: vec![],
+                version_summary_opt: None,
+                delta: false,
             },
             gossip_source,
         );
@@ -2428,6 +5560,8 @@ mod tests {
         subject.handle_gossip(
             Gossip {
                 node_records: vec![],
+                version_summary_opt: None,
+                delta: false,
             },
             subject_node.node_addr_opt().unwrap().ip_addr(),
         );
@@ -2524,7 +5658,7 @@ mod tests {
             .build();
         let cores_package = ExpiredCoresPackage {
             immediate_neighbor_ip: IpAddr::from_str("1.2.3.4").unwrap(),
-            consuming_wallet: Some(Wallet::new("consuming")),
+            consuming_wallet: Some(make_paying_wallet(b"consuming")),
             remaining_route: make_meaningless_route(),
             payload: gossip,
             payload_len: 0,
@@ -2543,6 +5677,9 @@ mod tests {
                     earning_wallet: this_node_inside.earning_wallet(),
                     consuming_wallet: None,
                     rate_pack: rate_pack(100),
+                    gossip_batch_window: Duration::from_millis(0),
+                    diversity: DiversityConfig::default(),
+                    behind_nat: false,
                 },
             );
 
@@ -2590,6 +5727,9 @@ mod tests {
                 earning_wallet: NodeRecord::earning_wallet_from_key(&cryptde.public_key()),
                 consuming_wallet: NodeRecord::consuming_wallet_from_key(&cryptde.public_key()),
                 rate_pack: rate_pack(100),
+                gossip_batch_window: Duration::from_millis(0),
+                diversity: DiversityConfig::default(),
+                behind_nat: false,
             },
         );
         let this_node = subject.neighborhood_database.root().clone();
@@ -2621,6 +5761,252 @@ mod tests {
         assert_eq!(1, gossip.node_records.len());
     }
 
+    #[test]
+    fn unanswered_debuts_fail_over_to_the_next_descriptor_and_then_retry() {
+        let cryptde = cryptde();
+        let one_bootstrap = make_node_record(1234, true, true);
+        let another_bootstrap = make_node_record(2345, true, true);
+        let (hopper, hopper_awaiter, hopper_recording) = make_recorder();
+        let (ui_gateway, _, ui_gateway_recording) = make_recorder();
+        let descriptor_of = |node: &NodeRecord| NodeDescriptor {
+            public_key: node.public_key().clone(),
+            node_addr: node.node_addr_opt().unwrap().clone(),
+        };
+        let mut subject = Neighborhood::new(
+            cryptde,
+            NeighborhoodConfig {
+                neighbor_configs: vec![
+                    descriptor_of(&one_bootstrap),
+                    descriptor_of(&another_bootstrap),
+                ],
+                is_bootstrap_node: false,
+                local_ip_addr: IpAddr::from_str("5.4.3.2").unwrap(),
+                clandestine_port_list: vec![1234],
+                earning_wallet: NodeRecord::earning_wallet_from_key(&cryptde.public_key()),
+                consuming_wallet: NodeRecord::consuming_wallet_from_key(&cryptde.public_key()),
+                rate_pack: rate_pack(100),
+                gossip_batch_window: Duration::from_millis(0),
+                diversity: DiversityConfig::default(),
+                behind_nat: false,
+            },
+        );
+        subject.debut_timeout = Duration::from_millis(10);
+        subject.debut_retry_delay = Duration::from_millis(20);
+        thread::spawn(move || {
+            let system =
+                System::new("unanswered_debuts_fail_over_to_the_next_descriptor_and_then_retry");
+            let addr: Addr<Neighborhood> = subject.start();
+            let peer_actors = peer_actors_builder()
+                .hopper(hopper)
+                .ui_gateway(ui_gateway)
+                .build();
+            addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            addr.try_send(BootstrapNeighborhoodNowMessage {}).unwrap();
+
+            system.run();
+        });
+        hopper_awaiter.await_message_count(3);
+        let hopper_recording = hopper_recording.lock().unwrap();
+        let targets = (0..3)
+            .map(|idx| {
+                hopper_recording
+                    .get_record::<NoLookupIncipientCoresPackage>(idx)
+                    .public_key
+                    .clone()
+            })
+            .collect::<Vec<PublicKey>>();
+        assert_ne!(targets[0], targets[1]);
+        assert!(
+            targets[2] == *one_bootstrap.public_key()
+                || targets[2] == *another_bootstrap.public_key()
+        );
+        let ui_gateway_recording = ui_gateway_recording.lock().unwrap();
+        let states = (0..4)
+            .map(|idx| {
+                ui_gateway_recording
+                    .get_record::<ConnectionStateMessage>(idx)
+                    .state
+                    .clone()
+            })
+            .collect::<Vec<ConnectionState>>();
+        assert_eq!(
+            states[2],
+            ConnectionState::WaitingToRetry {
+                round: 1,
+                delay_secs: 0
+            }
+        );
+        assert_eq!(
+            states[3],
+            ConnectionState::Debuting {
+                target: NodeDescriptor {
+                    public_key: targets[2].clone(),
+                    node_addr: hopper_recording
+                        .get_record::<NoLookupIncipientCoresPackage>(2)
+                        .node_addr
+                        .clone(),
+                }
+                .to_string(),
+                round: 1
+            }
+        );
+    }
+
+    #[test]
+    fn set_initial_neighbors_message_debuts_to_the_new_neighbors_while_not_connected() {
+        let cryptde = cryptde();
+        let new_neighbor = make_node_record(3456, true, true);
+        let (hopper, _, hopper_recording_arc) = make_recorder();
+        let (ui_gateway, _, ui_gateway_recording_arc) = make_recorder();
+        let subject = Neighborhood::new(
+            cryptde,
+            NeighborhoodConfig {
+                neighbor_configs: vec![],
+                is_bootstrap_node: false,
+                local_ip_addr: IpAddr::from_str("5.4.3.2").unwrap(),
+                clandestine_port_list: vec![1234],
+                earning_wallet: NodeRecord::earning_wallet_from_key(&cryptde.public_key()),
+                consuming_wallet: NodeRecord::consuming_wallet_from_key(&cryptde.public_key()),
+                rate_pack: rate_pack(100),
+                gossip_batch_window: Duration::from_millis(0),
+                diversity: DiversityConfig::default(),
+                behind_nat: false,
+            },
+        );
+        let new_descriptor = NodeDescriptor {
+            public_key: new_neighbor.public_key().clone(),
+            node_addr: new_neighbor.node_addr_opt().unwrap().clone(),
+        };
+        let system = System::new(
+            "set_initial_neighbors_message_debuts_to_the_new_neighbors_while_not_connected",
+        );
+        let addr: Addr<Neighborhood> = subject.start();
+        let peer_actors = peer_actors_builder()
+            .hopper(hopper)
+            .ui_gateway(ui_gateway)
+            .build();
+        addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        addr.try_send(SetInitialNeighborsMessage {
+            neighbors: vec![new_descriptor.clone()],
+        })
+        .unwrap();
+
+        System::current().stop();
+        system.run();
+        let hopper_recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(hopper_recording.len(), 1);
+        let package = hopper_recording.get_record::<NoLookupIncipientCoresPackage>(0);
+        assert_eq!(&package.public_key, new_neighbor.public_key());
+        assert_eq!(
+            &package.node_addr,
+            new_neighbor.node_addr_opt().as_ref().unwrap()
+        );
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording
+                .get_record::<ConnectionStateMessage>(0)
+                .state,
+            ConnectionState::Debuting {
+                target: new_descriptor.to_string(),
+                round: 0
+            }
+        );
+    }
+
+    #[test]
+    fn debut_retry_delay_doubles_each_round_up_to_a_maximum() {
+        let subject = make_standard_subject();
+
+        let delays = vec![0, 1, 2, 5, 6, 40]
+            .into_iter()
+            .map(|round| subject.debut_retry_delay_for(round))
+            .collect::<Vec<Duration>>();
+
+        assert_eq!(
+            delays,
+            vec![
+                Duration::from_secs(5),
+                Duration::from_secs(10),
+                Duration::from_secs(20),
+                Duration::from_secs(160),
+                MAX_DEBUT_RETRY_DELAY,
+                MAX_DEBUT_RETRY_DELAY,
+            ]
+        );
+    }
+
+    #[test]
+    fn changes_in_the_number_of_full_neighbors_are_reported_with_the_connection_state() {
+        let system = System::new(
+            "changes_in_the_number_of_full_neighbors_are_reported_with_the_connection_state",
+        );
+        let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        let mut subject = make_standard_subject();
+        subject.connection_state_sub =
+            Some(ui_gateway.start().recipient::<ConnectionStateMessage>());
+        let root_key = subject.neighborhood_database.root().public_key().clone();
+        let neighbor = make_node_record(1234, true, false);
+        subject
+            .neighborhood_database
+            .add_node(neighbor.clone())
+            .unwrap();
+        subject
+            .neighborhood_database
+            .add_arbitrary_full_neighbor(&root_key, neighbor.public_key());
+
+        subject.report_neighbor_count_change();
+        subject.report_neighbor_count_change();
+
+        System::current().stop();
+        system.run();
+        ui_gateway_awaiter.await_message_count(1);
+        let recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            recording.get_record::<ConnectionStateMessage>(0),
+            &ConnectionStateMessage {
+                state: ConnectionState::Standalone,
+                neighbor_count: 1,
+            }
+        );
+        assert_eq!(recording.len(), 1);
+    }
+
+    #[test]
+    fn node_is_connected_once_a_neighbor_lists_it_back() {
+        let mut subject = make_standard_subject();
+        let root_key = subject.neighborhood_database.root().public_key().clone();
+        let neighbor = make_node_record(1234, true, false);
+        subject
+            .neighborhood_database
+            .add_node(neighbor.clone())
+            .unwrap();
+        subject
+            .neighborhood_database
+            .add_arbitrary_half_neighbor(&root_key, neighbor.public_key());
+        subject.debut_queue = vec![NodeDescriptor {
+            public_key: neighbor.public_key().clone(),
+            node_addr: neighbor.node_addr_opt().unwrap(),
+        }];
+        subject.debut_round = 3;
+
+        subject.notice_connection();
+
+        assert_ne!(subject.connection_state, ConnectionState::Connected);
+        subject
+            .neighborhood_database
+            .add_arbitrary_half_neighbor(neighbor.public_key(), &root_key);
+        let debut_sequence = subject.debut_sequence;
+
+        subject.notice_connection();
+
+        assert_eq!(subject.connection_state, ConnectionState::Connected);
+        assert_eq!(subject.debut_sequence, debut_sequence + 1);
+        assert!(subject.debut_queue.is_empty());
+        assert_eq!(subject.debut_round, 0);
+    }
+
     /*
             Database, where we'll fail to make a three-hop route to C after removing A:
 
@@ -2683,6 +6069,8 @@ mod tests {
             target_component: Component::ProxyClient,
             minimum_hop_count: 3,
             return_component_opt: None,
+            avoided_keys: vec![],
+            constraints: RouteConstraints::default(),
         };
         let unsuccessful_three_hop_route = addr.send(three_hop_route_request);
         let public_key_query = addr.send(NodeQueryMessage::PublicKey(a.public_key().clone()));
@@ -2750,7 +6138,7 @@ mod tests {
     {
         let cryptde = cryptde();
         let earning_wallet = Wallet::new("earning");
-        let consuming_wallet = Some(Wallet::new("consuming"));
+        let consuming_wallet = Some(make_paying_wallet(b"consuming"));
         let (recorder, awaiter, recording_arc) = make_recorder();
         thread::spawn(move || {
             let system = System::new ("neighborhood_sends_node_query_response_with_none_when_key_query_matches_no_configured_data");
@@ -2774,6 +6162,9 @@ mod tests {
                     earning_wallet: earning_wallet.clone(),
                     consuming_wallet: consuming_wallet.clone(),
                     rate_pack: rate_pack(100),
+                    gossip_batch_window: Duration::from_millis(0),
+                    diversity: DiversityConfig::default(),
+                    behind_nat: false,
                 },
             );
             let addr: Addr<Neighborhood> = subject.start();
@@ -2806,7 +6197,7 @@ mod tests {
     fn neighborhood_sends_node_query_response_with_result_when_key_query_matches_configured_data() {
         let cryptde = cryptde();
         let earning_wallet = Wallet::new("earning");
-        let consuming_wallet = Some(Wallet::new("consuming"));
+        let consuming_wallet = Some(make_paying_wallet(b"consuming"));
         let (recorder, awaiter, recording_arc) = make_recorder();
         let one_neighbor = make_node_record(2345, true, false);
         let another_neighbor = make_node_record(3456, true, false);
@@ -2832,6 +6223,9 @@ mod tests {
                     earning_wallet: earning_wallet.clone(),
                     consuming_wallet: consuming_wallet.clone(),
                     rate_pack: rate_pack(100),
+                    gossip_batch_window: Duration::from_millis(0),
+                    diversity: DiversityConfig::default(),
+                    behind_nat: false,
                 },
             );
             subject
@@ -2870,7 +6264,7 @@ mod tests {
     ) {
         let cryptde = cryptde();
         let earning_wallet = Wallet::new("earning");
-        let consuming_wallet = Some(Wallet::new("consuming"));
+        let consuming_wallet = Some(make_paying_wallet(b"consuming"));
         let (recorder, awaiter, recording_arc) = make_recorder();
         thread::spawn(move || {
             let system = System::new("neighborhood_sends_node_query_response_with_none_when_ip_address_query_matches_no_configured_data");
@@ -2893,6 +6287,9 @@ mod tests {
                     earning_wallet: earning_wallet.clone(),
                     consuming_wallet: consuming_wallet.clone(),
                     rate_pack: rate_pack(100),
+                    gossip_batch_window: Duration::from_millis(0),
+                    diversity: DiversityConfig::default(),
+                    behind_nat: false,
                 },
             );
             let addr: Addr<Neighborhood> = subject.start();
@@ -2959,6 +6356,9 @@ mod tests {
                     earning_wallet: node_record.earning_wallet(),
                     consuming_wallet: None,
                     rate_pack: rate_pack(100),
+                    gossip_batch_window: Duration::from_millis(0),
+                    diversity: DiversityConfig::default(),
+                    behind_nat: false,
                 },
             );
             subject
@@ -3017,6 +6417,8 @@ mod tests {
             target_component: Component::ProxyClient,
             minimum_hop_count,
             return_component_opt: Some(Component::ProxyServer),
+            avoided_keys: vec![],
+            constraints: RouteConstraints::default(),
         });
 
         assert_eq!(
@@ -3063,6 +6465,8 @@ mod tests {
             target_component: Component::ProxyClient,
             minimum_hop_count,
             return_component_opt: Some(Component::ProxyServer),
+            avoided_keys: vec![],
+            constraints: RouteConstraints::default(),
         });
 
         let next_door_neighbor_cryptde = CryptDENull::from(&next_door_neighbor.public_key());
@@ -3070,21 +6474,23 @@ mod tests {
 
         let hops = result.clone().unwrap().route.hops;
         let actual_keys: Vec<PublicKey> = match hops.as_slice() {
-            [hop, exit, hop_back, origin, empty, _accounting] => vec![
-                decodex::<LiveHop>(cryptde(), hop).expect("hop").public_key,
-                decodex::<LiveHop>(&next_door_neighbor_cryptde, exit)
-                    .expect("exit")
-                    .public_key,
-                decodex::<LiveHop>(&exit_node_cryptde, hop_back)
-                    .expect("hop_back")
-                    .public_key,
-                decodex::<LiveHop>(&next_door_neighbor_cryptde, origin)
-                    .expect("origin")
-                    .public_key,
-                decodex::<LiveHop>(cryptde(), empty)
-                    .expect("empty")
-                    .public_key,
-            ],
+            [hop, exit, hop_back, origin, empty, _accounting, _failure_hop, _failure_origin, _failure_accounting] => {
+                vec![
+                    decodex::<LiveHop>(cryptde(), hop).expect("hop").public_key,
+                    decodex::<LiveHop>(&next_door_neighbor_cryptde, exit)
+                        .expect("exit")
+                        .public_key,
+                    decodex::<LiveHop>(&exit_node_cryptde, hop_back)
+                        .expect("hop_back")
+                        .public_key,
+                    decodex::<LiveHop>(&next_door_neighbor_cryptde, origin)
+                        .expect("origin")
+                        .public_key,
+                    decodex::<LiveHop>(cryptde(), empty)
+                        .expect("empty")
+                        .public_key,
+                ]
+            }
             l => panic!("our match is wrong, real size is {}, {:?}", l.len(), l),
         };
         let expected_public_keys = vec![
@@ -0,0 +1,232 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::database::db_initializer::ConnectionWrapper;
+use crate::neighborhood::gossip::GossipNodeRecord;
+use crate::neighborhood::node_record::NodeRecord;
+use crate::sub_lib::cryptde::{CryptData, PlainData};
+use crate::sub_lib::node_addr::NodeAddr;
+use rusqlite::types::ToSql;
+use rusqlite::Row;
+use rusqlite::NO_PARAMS;
+use std::fmt::Debug;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoredNodeRecord {
+    pub signed_gossip: PlainData,
+    pub signature: CryptData,
+    pub node_addr_opt: Option<NodeAddr>,
+    pub desirable: bool,
+    // Whether this was the local Node's own record when it was stored
+    pub root: bool,
+}
+
+impl StoredNodeRecord {
+    pub fn new(node_record: &NodeRecord, root: bool) -> StoredNodeRecord {
+        StoredNodeRecord {
+            signed_gossip: node_record.signed_gossip().clone(),
+            signature: node_record.signature().clone(),
+            node_addr_opt: node_record.node_addr_opt(),
+            desirable: node_record.is_desirable(),
+            root,
+        }
+    }
+}
+
+impl From<&StoredNodeRecord> for GossipNodeRecord {
+    fn from(stored: &StoredNodeRecord) -> Self {
+        GossipNodeRecord {
+            signed_data: stored.signed_gossip.clone(),
+            signature: stored.signature.clone(),
+            node_addr_opt: stored.node_addr_opt.clone(),
+        }
+    }
+}
+
+pub trait NeighborhoodDao: Debug + Send {
+    // Err if the records can't be read at all; otherwise one Err for each record that can't be
+    // read, so that the rest can still be used.
+    fn node_records(&self) -> Result<Vec<Result<StoredNodeRecord, String>>, String>;
+
+    fn replace_node_records(&self, node_records: &[StoredNodeRecord]) -> Result<(), String>;
+}
+
+#[derive(Debug)]
+pub struct NeighborhoodDaoReal {
+    conn: Box<ConnectionWrapper>,
+}
+
+impl NeighborhoodDao for NeighborhoodDaoReal {
+    fn node_records(&self) -> Result<Vec<Result<StoredNodeRecord, String>>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("select signed_gossip, signature, node_addr, desirable, root from neighborhood_node")
+            .map_err(|e| format!("{}", e))?;
+        let rows = stmt
+            .query_map(NO_PARAMS, |row| Ok(Self::stored_node_record(row)))
+            .map_err(|e| format!("{}", e))?;
+        Ok(rows
+            .map(|row| row.map_err(|e| format!("{}", e)).and_then(|record| record))
+            .collect())
+    }
+
+    fn replace_node_records(&self, node_records: &[StoredNodeRecord]) -> Result<(), String> {
+        self.execute("begin transaction", &[])?;
+        match self.try_replace(node_records) {
+            Ok(()) => self.execute("commit", &[]),
+            Err(e) => {
+                self.execute("rollback", &[])?;
+                Err(e)
+            }
+        }
+    }
+}
+
+impl NeighborhoodDaoReal {
+    pub fn new(conn: Box<ConnectionWrapper>) -> NeighborhoodDaoReal {
+        NeighborhoodDaoReal { conn }
+    }
+
+    fn stored_node_record(row: &Row) -> Result<StoredNodeRecord, String> {
+        let column_error = |e| format!("{}", e);
+        let node_addr_opt = match row.get::<usize, Option<String>>(2).map_err(column_error)? {
+            Some(node_addr_str) => Some(
+                NodeAddr::from_str(&node_addr_str)
+                    .map_err(|_| format!("Invalid node_addr '{}'", node_addr_str))?,
+            ),
+            None => None,
+        };
+        Ok(StoredNodeRecord {
+            signed_gossip: PlainData::from(row.get::<usize, Vec<u8>>(0).map_err(column_error)?),
+            signature: CryptData::from(row.get::<usize, Vec<u8>>(1).map_err(column_error)?),
+            node_addr_opt,
+            desirable: row.get::<usize, i64>(3).map_err(column_error)? != 0,
+            root: row.get::<usize, i64>(4).map_err(column_error)? != 0,
+        })
+    }
+
+    fn try_replace(&self, node_records: &[StoredNodeRecord]) -> Result<(), String> {
+        self.execute("delete from neighborhood_node", &[])?;
+        node_records.iter().fold(Ok(()), |so_far, node_record| {
+            so_far?;
+            let node_addr_opt = node_record
+                .node_addr_opt
+                .as_ref()
+                .map(|node_addr| node_addr.to_string());
+            let desirable = if node_record.desirable { 1i64 } else { 0i64 };
+            let root = if node_record.root { 1i64 } else { 0i64 };
+            let params: &[&ToSql] = &[
+                &node_record.signed_gossip.as_slice(),
+                &node_record.signature.as_slice(),
+                &node_addr_opt,
+                &desirable,
+                &root,
+            ];
+            self.execute("insert into neighborhood_node (signed_gossip, signature, node_addr, desirable, root) values (?, ?, ?, ?, ?)", params)
+        })
+    }
+
+    fn execute(&self, sql: &str, params: &[&ToSql]) -> Result<(), String> {
+        let mut stmt = self.conn.prepare(sql).map_err(|e| format!("{}", e))?;
+        match stmt.execute(params) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("{}", e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::db_initializer::DbInitializer;
+    use crate::database::db_initializer::DbInitializerReal;
+    use crate::database::db_initializer::DATABASE_FILE;
+    use crate::neighborhood::neighborhood_test_utils::make_node_record;
+    use crate::test_utils::test_utils::ensure_node_home_directory_exists;
+
+    #[test]
+    fn node_records_returns_an_empty_vec_when_the_database_is_empty() {
+        let home_dir = ensure_node_home_directory_exists(
+            "neighborhood",
+            "node_records_returns_an_empty_vec_when_the_database_is_empty",
+        );
+        let subject =
+            NeighborhoodDaoReal::new(DbInitializerReal::new().initialize(&home_dir).unwrap());
+
+        assert_eq!(subject.node_records(), Ok(vec![]));
+    }
+
+    #[test]
+    fn replace_node_records_stores_records_that_node_records_retrieves() {
+        let home_dir = ensure_node_home_directory_exists(
+            "neighborhood",
+            "replace_node_records_stores_records_that_node_records_retrieves",
+        );
+        let subject =
+            NeighborhoodDaoReal::new(DbInitializerReal::new().initialize(&home_dir).unwrap());
+        let root = StoredNodeRecord::new(&make_node_record(1234, true, false), true);
+        let mut undesirable_record = make_node_record(2345, false, false);
+        undesirable_record.set_desirable(false);
+        let undesirable = StoredNodeRecord::new(&undesirable_record, false);
+
+        subject
+            .replace_node_records(&[root.clone(), undesirable.clone()])
+            .unwrap();
+
+        assert_eq!(subject.node_records(), Ok(vec![Ok(root), Ok(undesirable)]));
+    }
+
+    #[test]
+    fn replace_node_records_discards_previously_stored_records() {
+        let home_dir = ensure_node_home_directory_exists(
+            "neighborhood",
+            "replace_node_records_discards_previously_stored_records",
+        );
+        let subject =
+            NeighborhoodDaoReal::new(DbInitializerReal::new().initialize(&home_dir).unwrap());
+        let old_record = StoredNodeRecord::new(&make_node_record(1234, true, false), true);
+        let new_record = StoredNodeRecord::new(&make_node_record(2345, true, false), false);
+        subject.replace_node_records(&[old_record]).unwrap();
+
+        subject.replace_node_records(&[new_record.clone()]).unwrap();
+
+        assert_eq!(subject.node_records(), Ok(vec![Ok(new_record)]));
+    }
+
+    #[test]
+    fn node_records_reports_unreadable_records_alongside_readable_ones() {
+        let home_dir = ensure_node_home_directory_exists(
+            "neighborhood",
+            "node_records_reports_unreadable_records_alongside_readable_ones",
+        );
+        let subject =
+            NeighborhoodDaoReal::new(DbInitializerReal::new().initialize(&home_dir).unwrap());
+        let root = StoredNodeRecord::new(&make_node_record(1234, true, false), true);
+        let corrupt = StoredNodeRecord::new(&make_node_record(2345, true, false), false);
+        subject
+            .replace_node_records(&[root.clone(), corrupt])
+            .unwrap();
+        let conn = rusqlite::Connection::open(home_dir.join(DATABASE_FILE)).unwrap();
+        conn.execute(
+            "update neighborhood_node set node_addr = 'booga' where root = 0",
+            NO_PARAMS,
+        )
+        .unwrap();
+
+        let result = subject.node_records();
+
+        assert_eq!(
+            result,
+            Ok(vec![Ok(root), Err("Invalid node_addr 'booga'".to_string())])
+        );
+    }
+
+    #[test]
+    fn stored_node_record_converts_to_gossip_node_record() {
+        let node_record = make_node_record(1234, true, false);
+        let stored = StoredNodeRecord::new(&node_record, false);
+
+        let result = GossipNodeRecord::from(&stored);
+
+        assert_eq!(result, GossipNodeRecord::from(node_record));
+    }
+}
@@ -6,7 +6,10 @@ use crate::neighborhood::dot_graph::{
 use crate::neighborhood::node_record::NodeRecord;
 use crate::sub_lib::cryptde::PublicKey;
 use crate::sub_lib::cryptde::{CryptDE, PlainData};
+use crate::sub_lib::ip_range::IpRange;
+use crate::sub_lib::neighborhood::DiversityConfig;
 use crate::sub_lib::neighborhood::RatePack;
+use crate::sub_lib::neighborhood::{GraphEdge, GraphNode, NeighborhoodGraph};
 use crate::sub_lib::node_addr::NodeAddr;
 use crate::sub_lib::wallet::Wallet;
 use std::collections::HashMap;
@@ -21,6 +24,9 @@ pub struct NeighborhoodDatabase {
     this_node: PublicKey,
     by_public_key: HashMap<PublicKey, NodeRecord>,
     by_ip_addr: HashMap<IpAddr, PublicKey>,
+    diversity: DiversityConfig,
+    // How many Nodes other than this one have a NodeAddr in each IP prefix
+    ip_prefix_counts: HashMap<IpRange, usize>,
 }
 
 impl Debug for NeighborhoodDatabase {
@@ -42,6 +48,8 @@ impl NeighborhoodDatabase {
             this_node: public_key.clone(),
             by_public_key: HashMap::new(),
             by_ip_addr: HashMap::new(),
+            diversity: DiversityConfig::default(),
+            ip_prefix_counts: HashMap::new(),
         };
 
         let mut node_record = NodeRecord::new(
@@ -96,6 +104,29 @@ impl NeighborhoodDatabase {
         }
     }
 
+    // Changes the size of the IP prefixes whose Nodes are counted.
+    pub fn set_diversity(&mut self, diversity: DiversityConfig) {
+        self.diversity = diversity;
+        let ip_addrs = self
+            .by_public_key
+            .values()
+            .filter(|node_record| node_record.public_key() != &self.this_node)
+            .flat_map(|node_record| node_record.node_addr_opt())
+            .map(|node_addr| node_addr.ip_addr())
+            .collect::<Vec<IpAddr>>();
+        self.ip_prefix_counts.clear();
+        ip_addrs
+            .iter()
+            .for_each(|ip_addr| self.count_ip_addr(ip_addr));
+    }
+
+    // The IP prefix around an address, and how many Nodes other than this one we know in it
+    pub fn nodes_in_prefix_of(&self, ip_addr: &IpAddr) -> (IpRange, usize) {
+        let prefix = self.diversity.prefix_of(ip_addr);
+        let count = self.ip_prefix_counts.get(&prefix).cloned().unwrap_or(0);
+        (prefix, count)
+    }
+
     pub fn has_half_neighbor(&self, from: &PublicKey, to: &PublicKey) -> bool {
         match self.node_by_key(from) {
             Some(f) => f.has_half_neighbor(to),
@@ -176,10 +207,12 @@ impl NeighborhoodDatabase {
             };
             to_remove.unset_node_addr();
         }
-        match ip_addr {
-            Some(ip) => self.by_ip_addr.remove(&ip),
-            None => None,
-        };
+        if let Some(ip) = ip_addr {
+            self.by_ip_addr.remove(&ip);
+            if node_key != &self.this_node {
+                self.uncount_ip_addr(&ip);
+            }
+        }
 
         match self.root_mut().remove_half_neighbor_key(node_key) {
             true => {
@@ -195,16 +228,46 @@ impl NeighborhoodDatabase {
         render_dot_graph(renderables)
     }
 
+    pub fn to_graph(&self) -> NeighborhoodGraph {
+        let node_records = self.sorted_node_records();
+        let nodes = node_records
+            .iter()
+            .map(|nr| GraphNode {
+                public_key: nr.public_key().to_string(),
+                version: nr.version(),
+                is_root: nr.public_key() == &self.this_node,
+                is_bootstrap_node: nr.is_bootstrap_node(),
+                address_revealed: nr.node_addr_opt().is_some(),
+                node_addr: nr.node_addr_opt().map(|node_addr| node_addr.to_string()),
+                desirable: nr.is_desirable(),
+                rate_pack: nr.rate_pack().clone(),
+            })
+            .collect();
+        let edges = node_records
+            .iter()
+            .flat_map(|nr| {
+                NeighborhoodDatabase::sorted_keys(nr.half_neighbor_keys())
+                    .map(|key| GraphEdge {
+                        from: nr.public_key().to_string(),
+                        to: key.to_string(),
+                        full: self.has_half_neighbor(key, nr.public_key()),
+                    })
+                    .collect::<Vec<GraphEdge>>()
+            })
+            .collect();
+        NeighborhoodGraph {
+            dot: self.to_dot_graph(),
+            nodes,
+            edges,
+        }
+    }
+
     fn to_dot_renderables(&self) -> Vec<Box<DotRenderable>> {
         let mut mentioned: HashSet<PublicKey> = HashSet::new();
         let mut present: HashSet<PublicKey> = HashSet::new();
         let mut node_renderables: Vec<NodeRenderable> = vec![];
         let mut edge_renderables: Vec<EdgeRenderable> = vec![];
-        let node_records = self
-            .keys()
-            .into_iter()
-            .map(|k| self.node_by_key(k).expect("Node magically disappeared"))
-            .collect::<Vec<&NodeRecord>>();
+        let node_records = self.sorted_node_records();
         let bootstrap_keys: HashSet<PublicKey> = node_records
             .iter()
             .filter(|n| n.is_bootstrap_node())
@@ -213,7 +276,7 @@ impl NeighborhoodDatabase {
         node_records.into_iter().for_each(|nr| {
             present.insert(nr.public_key().clone());
             let public_key = nr.public_key();
-            nr.half_neighbor_keys().into_iter().for_each(|k| {
+            NeighborhoodDatabase::sorted_keys(nr.half_neighbor_keys()).for_each(|k| {
                 mentioned.insert(k.clone());
                 edge_renderables.push(EdgeRenderable {
                     from: public_key.clone(),
@@ -232,7 +295,7 @@ impl NeighborhoodDatabase {
                 is_present: true,
             });
         });
-        mentioned.difference(&present).into_iter().for_each(|k| {
+        NeighborhoodDatabase::sorted_keys(mentioned.difference(&present).collect()).for_each(|k| {
             node_renderables.push(NodeRenderable {
                 version: None,
                 public_key: k.clone(),
@@ -253,12 +316,70 @@ impl NeighborhoodDatabase {
         result
     }
 
+    // Sorted so that renderings of the same database always come out the same
+    fn sorted_node_records(&self) -> Vec<&NodeRecord> {
+        NeighborhoodDatabase::sorted_keys(self.keys())
+            .map(|k| self.node_by_key(k).expect("Node magically disappeared"))
+            .collect()
+    }
+
+    fn sorted_keys(keys: HashSet<&PublicKey>) -> impl Iterator<Item = &PublicKey> {
+        let mut keys = keys.into_iter().collect::<Vec<&PublicKey>>();
+        keys.sort();
+        keys.into_iter()
+    }
+
+    // Gives a Node known only by its key the NodeAddr it has since revealed.
+    pub fn set_node_addr(
+        &mut self,
+        public_key: &PublicKey,
+        node_addr: &NodeAddr,
+    ) -> Result<bool, NeighborhoodDatabaseError> {
+        let changed = match self.by_public_key.get_mut(public_key) {
+            Some(node_record) => node_record.set_node_addr(node_addr)?,
+            None => return Err(NodeKeyNotFound(public_key.clone())),
+        };
+        if changed {
+            self.index_node_addr(public_key, node_addr);
+        }
+        Ok(changed)
+    }
+
     fn add_arbitrary_node(&mut self, node_record: NodeRecord) {
         let public_key = node_record.public_key().clone();
         let node_addr_opt = node_record.node_addr_opt();
         self.by_public_key.insert(public_key.clone(), node_record);
         if let Some(node_addr) = node_addr_opt {
-            self.by_ip_addr.insert(node_addr.ip_addr(), public_key);
+            self.index_node_addr(&public_key, &node_addr);
+        }
+    }
+
+    fn index_node_addr(&mut self, public_key: &PublicKey, node_addr: &NodeAddr) {
+        let ip_addr = node_addr.ip_addr();
+        self.by_ip_addr.insert(ip_addr, public_key.clone());
+        if public_key != &self.this_node {
+            self.count_ip_addr(&ip_addr);
+        }
+    }
+
+    fn count_ip_addr(&mut self, ip_addr: &IpAddr) {
+        *self
+            .ip_prefix_counts
+            .entry(self.diversity.prefix_of(ip_addr))
+            .or_insert(0) += 1;
+    }
+
+    fn uncount_ip_addr(&mut self, ip_addr: &IpAddr) {
+        let prefix = self.diversity.prefix_of(ip_addr);
+        let now_empty = match self.ip_prefix_counts.get_mut(&prefix) {
+            Some(count) => {
+                *count -= 1;
+                *count == 0
+            }
+            None => false,
+        };
+        if now_empty {
+            self.ip_prefix_counts.remove(&prefix);
         }
     }
 
@@ -733,6 +854,80 @@ mod tests {
         assert_string_contains(&result, "\"BAUGBw\" -> \"AQIDBA\" [style=dashed];");
     }
 
+    #[test]
+    fn database_can_be_exported_as_a_graph() {
+        let this_node = make_node_record(1234, true, false); // AQIDBA
+        let node_one = make_node_record(2345, true, true); // AgMEBQ
+        let mut node_two = make_node_record(3456, false, false); // AwQFBg
+        node_two.set_desirable(false);
+        let mut subject = db_from_node(&this_node);
+        subject.add_node(node_one.clone()).unwrap();
+        subject.add_node(node_two.clone()).unwrap();
+        subject.add_arbitrary_half_neighbor(this_node.public_key(), node_one.public_key());
+        subject.add_arbitrary_half_neighbor(node_one.public_key(), this_node.public_key());
+        subject.add_arbitrary_half_neighbor(node_one.public_key(), node_two.public_key());
+        subject.root_mut().increment_version();
+
+        let result = subject.to_graph();
+
+        assert_eq!(result.dot, subject.to_dot_graph());
+        assert_eq!(
+            result.nodes,
+            vec![
+                GraphNode {
+                    public_key: "AQIDBA".to_string(),
+                    version: 1,
+                    is_root: true,
+                    is_bootstrap_node: false,
+                    address_revealed: true,
+                    node_addr: Some("1.2.3.4:1234".to_string()),
+                    desirable: true,
+                    rate_pack: this_node.rate_pack().clone(),
+                },
+                GraphNode {
+                    public_key: "AgMEBQ".to_string(),
+                    version: 0,
+                    is_root: false,
+                    is_bootstrap_node: true,
+                    address_revealed: true,
+                    node_addr: Some("2.3.4.5:2345".to_string()),
+                    desirable: true,
+                    rate_pack: node_one.rate_pack().clone(),
+                },
+                GraphNode {
+                    public_key: "AwQFBg".to_string(),
+                    version: 0,
+                    is_root: false,
+                    is_bootstrap_node: false,
+                    address_revealed: false,
+                    node_addr: None,
+                    desirable: false,
+                    rate_pack: node_two.rate_pack().clone(),
+                },
+            ]
+        );
+        assert_eq!(
+            result.edges,
+            vec![
+                GraphEdge {
+                    from: "AQIDBA".to_string(),
+                    to: "AgMEBQ".to_string(),
+                    full: true,
+                },
+                GraphEdge {
+                    from: "AgMEBQ".to_string(),
+                    to: "AQIDBA".to_string(),
+                    full: true,
+                },
+                GraphEdge {
+                    from: "AgMEBQ".to_string(),
+                    to: "AwQFBg".to_string(),
+                    full: false,
+                },
+            ]
+        );
+    }
+
     #[test]
     fn remove_neighbor_returns_error_when_given_nonexistent_node_key() {
         let this_node = make_node_record(123, true, false);
@@ -811,4 +1006,81 @@ mod tests {
         assert_eq!(0, subject.root().version());
         assert!(!result.ok().expect("should be ok"));
     }
+
+    #[test]
+    fn nodes_in_prefix_of_follows_nodes_as_they_come_and_go_and_leaves_out_the_root() {
+        let this_node = make_node_record(3456, true, false);
+        let mut subject = db_from_node(&this_node);
+        let first = make_node_record(3457, true, false);
+        let second = make_node_record(3458, false, false);
+        let elsewhere = make_node_record(4567, true, false);
+        let ip_addr = IpAddr::from_str("3.4.5.100").unwrap();
+        let prefix = IpRange::from_str("3.4.5.0/24").unwrap();
+        assert_eq!(subject.nodes_in_prefix_of(&ip_addr), (prefix, 0));
+
+        subject.add_node(first.clone()).unwrap();
+        subject.add_node(second.clone()).unwrap();
+        subject.add_node(elsewhere.clone()).unwrap();
+        assert_eq!(subject.nodes_in_prefix_of(&ip_addr), (prefix, 1));
+
+        let result = subject.set_node_addr(
+            second.public_key(),
+            &NodeAddr::new(&IpAddr::from_str("3.4.5.8").unwrap(), &vec![1234]),
+        );
+        assert_eq!(result, Ok(true));
+        assert_eq!(subject.nodes_in_prefix_of(&ip_addr), (prefix, 2));
+        assert_eq!(
+            subject
+                .node_by_ip(&IpAddr::from_str("3.4.5.8").unwrap())
+                .map(|node_record| node_record.public_key()),
+            Some(second.public_key())
+        );
+
+        subject.remove_neighbor(first.public_key()).unwrap();
+        assert_eq!(subject.nodes_in_prefix_of(&ip_addr), (prefix, 1));
+        assert_eq!(
+            subject.nodes_in_prefix_of(&IpAddr::from_str("4.5.6.7").unwrap()),
+            (IpRange::from_str("4.5.6.0/24").unwrap(), 1)
+        );
+    }
+
+    #[test]
+    fn set_diversity_recounts_nodes_by_the_new_prefix() {
+        let this_node = make_node_record(3456, true, false);
+        let mut subject = db_from_node(&this_node);
+        subject
+            .add_node(make_node_record(3457, true, false))
+            .unwrap();
+        subject
+            .add_node(make_node_record(3467, true, false))
+            .unwrap();
+        let ip_addr = IpAddr::from_str("3.4.5.100").unwrap();
+
+        subject.set_diversity(DiversityConfig {
+            ipv4_prefix_len: 16,
+            ..DiversityConfig::default()
+        });
+
+        assert_eq!(
+            subject.nodes_in_prefix_of(&ip_addr),
+            (IpRange::from_str("3.4.0.0/16").unwrap(), 2)
+        );
+    }
+
+    #[test]
+    fn set_node_addr_complains_about_unknown_nodes() {
+        let this_node = make_node_record(3456, true, false);
+        let mut subject = db_from_node(&this_node);
+        let stranger = make_node_record(3457, true, false);
+
+        let result =
+            subject.set_node_addr(stranger.public_key(), &stranger.node_addr_opt().unwrap());
+
+        assert_eq!(
+            result,
+            Err(NeighborhoodDatabaseError::NodeKeyNotFound(
+                stranger.public_key().clone()
+            ))
+        );
+    }
 }
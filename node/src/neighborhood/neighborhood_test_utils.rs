@@ -8,14 +8,16 @@ use crate::neighborhood::node_record::NodeRecordInner;
 use crate::sub_lib::cryptde::PublicKey;
 use crate::sub_lib::cryptde::{CryptDE, PlainData};
 use crate::sub_lib::cryptde_null::CryptDENull;
-use crate::sub_lib::neighborhood::{NeighborhoodConfig, NodeDescriptor};
+use crate::sub_lib::neighborhood::{DiversityConfig, NeighborhoodConfig, NodeDescriptor};
 use crate::sub_lib::node_addr::NodeAddr;
 use crate::sub_lib::wallet::Wallet;
 use crate::test_utils::test_utils::cryptde;
+use crate::test_utils::test_utils::make_paying_wallet;
 use crate::test_utils::test_utils::rate_pack;
 use std::net::IpAddr;
 use std::net::Ipv4Addr;
 use std::str::FromStr;
+use std::time::Duration;
 
 pub fn make_node_record(n: u16, has_ip: bool, is_bootstrap_node: bool) -> NodeRecord {
     let a = ((n / 1000) % 10) as u8;
@@ -86,8 +88,11 @@ pub fn neighborhood_from_nodes(
                 .ip_addr(),
             clandestine_port_list: root.node_addr_opt().unwrap().ports(),
             earning_wallet: root.earning_wallet(),
-            consuming_wallet: Some(Wallet::new("consuming")),
+            consuming_wallet: Some(make_paying_wallet(b"consuming")),
             rate_pack: root.rate_pack().clone(),
+            gossip_batch_window: Duration::from_millis(0),
+            diversity: DiversityConfig::default(),
+            behind_nat: false,
         },
     )
 }
@@ -98,7 +103,7 @@ impl NodeRecord {
         for i in public_key.as_slice() {
             result.push_str(&format!("{:x}", i));
         }
-        Wallet { address: result }
+        Wallet::new(&result)
     }
 
     pub fn consuming_wallet_from_key(public_key: &PublicKey) -> Option<Wallet> {
@@ -108,7 +113,7 @@ impl NodeRecord {
         for i in &reversed_public_key_data {
             result.push_str(&format!("{:x}", i));
         }
-        Some(Wallet { address: result })
+        Some(Wallet::new(&result))
     }
 
     pub fn new_for_tests(
@@ -12,6 +12,7 @@ use std::collections::btree_set::BTreeSet;
 use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::iter::FromIterator;
+use std::time::Duration;
 
 #[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct NodeRecordInner {
@@ -21,6 +22,13 @@ pub struct NodeRecordInner {
     pub is_bootstrap_node: bool,
     pub neighbors: BTreeSet<PublicKey>,
     pub version: u32,
+    // Whether the Node can exchange compressed ClientRequestPayloads and ClientResponsePayloads
+    #[serde(default)]
+    pub supports_compression: bool,
+    // Public neighbors that accept CORES packages for this Node and forward them over the
+    // connection it keeps open to them; only Nodes without a public address need relays
+    #[serde(default)]
+    pub relays: BTreeSet<PublicKey>,
 }
 
 impl TryFrom<GossipNodeRecord> for NodeRecordInner {
@@ -68,6 +76,8 @@ impl NodeRecord {
                 is_bootstrap_node,
                 neighbors: BTreeSet::new(),
                 version,
+                supports_compression: true,
+                relays: BTreeSet::new(),
             },
             signed_gossip: PlainData::new(&[]),
             signature: CryptData::new(&[]),
@@ -223,6 +233,40 @@ impl NodeRecord {
         &self.inner.rate_pack
     }
 
+    pub fn set_rate_pack(&mut self, rate_pack: RatePack) -> bool {
+        if self.inner.rate_pack == rate_pack {
+            false
+        } else {
+            self.inner.rate_pack = rate_pack;
+            true
+        }
+    }
+
+    pub fn supports_compression(&self) -> bool {
+        self.inner.supports_compression
+    }
+
+    pub fn set_supports_compression(&mut self, supports_compression: bool) {
+        self.inner.supports_compression = supports_compression
+    }
+
+    pub fn relays(&self) -> &BTreeSet<PublicKey> {
+        &self.inner.relays
+    }
+
+    pub fn set_relays(&mut self, relays: BTreeSet<PublicKey>) -> bool {
+        if self.inner.relays == relays {
+            false
+        } else {
+            self.inner.relays = relays;
+            true
+        }
+    }
+
+    pub fn is_relayed_by(&self, key: &PublicKey) -> bool {
+        self.inner.relays.contains(key)
+    }
+
     pub fn is_desirable(&self) -> bool {
         self.metadata.desirable
     }
@@ -230,6 +274,14 @@ impl NodeRecord {
     pub fn set_desirable(&mut self, is_desirable: bool) {
         self.metadata.desirable = is_desirable
     }
+
+    pub fn round_trip_time_opt(&self) -> Option<Duration> {
+        self.metadata.round_trip_time_opt
+    }
+
+    pub fn set_round_trip_time(&mut self, round_trip_time: Duration) {
+        self.metadata.round_trip_time_opt = Some(round_trip_time)
+    }
 }
 
 impl From<AccessibleGossipRecord> for NodeRecord {
@@ -272,6 +324,8 @@ impl TryFrom<&GossipNodeRecord> for NodeRecord {
 pub struct NodeRecordMetadata {
     pub desirable: bool,
     pub node_addr_opt: Option<NodeAddr>,
+    // Most recently measured heartbeat round trip, if this Node is our neighbor
+    pub round_trip_time_opt: Option<Duration>,
 }
 
 impl NodeRecordMetadata {
@@ -279,6 +333,7 @@ impl NodeRecordMetadata {
         NodeRecordMetadata {
             desirable: true,
             node_addr_opt: None,
+            round_trip_time_opt: None,
         }
     }
 }
@@ -634,6 +689,16 @@ mod tests {
         assert_eq!(this_node.earning_wallet(), Wallet::new("0x2345"));
     }
 
+    #[test]
+    fn set_rate_pack_returns_true_only_when_the_rate_pack_changes() {
+        let mut this_node = make_node_record(1234, true, false);
+
+        assert!(this_node.set_rate_pack(rate_pack(2345)));
+        assert!(!this_node.set_rate_pack(rate_pack(2345)));
+
+        assert_eq!(this_node.rate_pack(), &rate_pack(2345));
+    }
+
     #[test]
     fn set_earning_wallet_returns_false_when_the_wallet_does_not_change() {
         let mut this_node = make_node_record(1234, true, false);
@@ -685,6 +750,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn supports_compression_defaults_to_true_and_can_be_changed() {
+        let mut subject = make_node_record(5432, true, false);
+
+        assert!(subject.supports_compression());
+        subject.set_supports_compression(false);
+        assert!(!subject.supports_compression());
+    }
+
+    #[test]
+    fn relays_default_to_empty_and_can_be_changed() {
+        let mut subject = make_node_record(5432, false, false);
+        let relay_key = PublicKey::new(&[1, 2, 3, 4]);
+
+        assert!(subject.relays().is_empty());
+        assert!(subject.set_relays(BTreeSet::from_iter(vec![relay_key.clone()])));
+        assert!(!subject.set_relays(BTreeSet::from_iter(vec![relay_key.clone()])));
+        assert!(subject.is_relayed_by(&relay_key));
+        assert!(!subject.is_relayed_by(&PublicKey::new(&[5, 6, 7, 8])));
+    }
+
+    #[test]
+    fn record_serialized_before_relays_existed_deserializes_without_relays() {
+        #[derive(Serialize)]
+        struct OldNodeRecordInner {
+            public_key: PublicKey,
+            earning_wallet: Wallet,
+            rate_pack: RatePack,
+            is_bootstrap_node: bool,
+            neighbors: BTreeSet<PublicKey>,
+            version: u32,
+            supports_compression: bool,
+        }
+        let old_inner = OldNodeRecordInner {
+            public_key: PublicKey::new(&[1, 2, 3, 4]),
+            earning_wallet: Wallet::new("0x1234"),
+            rate_pack: rate_pack(100),
+            is_bootstrap_node: false,
+            neighbors: BTreeSet::new(),
+            version: 3,
+            supports_compression: true,
+        };
+        let serialized = serde_cbor::ser::to_vec(&old_inner).unwrap();
+
+        let result: NodeRecordInner = serde_cbor::de::from_slice(&serialized[..]).unwrap();
+
+        assert_eq!(result.public_key, PublicKey::new(&[1, 2, 3, 4]));
+        assert_eq!(result.version, 3);
+        assert!(result.relays.is_empty());
+    }
+
     #[test]
     fn from_gnr_to_nri_when_gossip_is_corrupt() {
         let corrupt_gnr = GossipNodeRecord {
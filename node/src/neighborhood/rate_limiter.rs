@@ -0,0 +1,153 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::Duration;
+use std::time::Instant;
+
+// Admits at most a fixed number of events per key in each interval, and keeps count of the
+// events it refuses so that a key that keeps exceeding its limit can be reported.
+pub struct RateLimiter<K: Hash + Eq + Clone> {
+    limit: usize,
+    interval: Duration,
+    windows: HashMap<K, RateWindow>,
+}
+
+struct RateWindow {
+    started: Instant,
+    admitted: usize,
+    refused: usize,
+    total_refused: u64,
+}
+
+impl<K: Hash + Eq + Clone> RateLimiter<K> {
+    pub fn new(limit: usize, interval: Duration) -> RateLimiter<K> {
+        RateLimiter {
+            limit,
+            interval,
+            windows: HashMap::new(),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn admit(&mut self, key: &K, now: Instant) -> bool {
+        let interval = self.interval;
+        let window = self.windows.entry(key.clone()).or_insert(RateWindow {
+            started: now,
+            admitted: 0,
+            refused: 0,
+            total_refused: 0,
+        });
+        if now.duration_since(window.started) >= interval {
+            window.started = now;
+            window.admitted = 0;
+            window.refused = 0;
+        }
+        if window.admitted < self.limit {
+            window.admitted += 1;
+            true
+        } else {
+            window.refused += 1;
+            window.total_refused += 1;
+            false
+        }
+    }
+
+    // Events refused for this key in its current interval
+    pub fn refused_in_interval(&self, key: &K) -> usize {
+        self.windows.get(key).map(|w| w.refused).unwrap_or(0)
+    }
+
+    // Events refused for this key since its window was created; prune forgets the count along with
+    // the window
+    pub fn total_refused(&self, key: &K) -> u64 {
+        self.windows.get(key).map(|w| w.total_refused).unwrap_or(0)
+    }
+
+    // Forgets keys whose interval has run out. Returns the ones that exceeded the limit while
+    // they were remembered, with the number of events refused for each, so they can be reported.
+    pub fn prune(&mut self, now: Instant) -> Vec<(K, u64)> {
+        let interval = self.interval;
+        let expired_keys = self
+            .windows
+            .iter()
+            .filter(|(_, window)| now.duration_since(window.started) >= interval)
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<K>>();
+        expired_keys
+            .into_iter()
+            .flat_map(|key| {
+                let window = self.windows.remove(&key).expect("Window disappeared");
+                if window.total_refused > 0 {
+                    Some((key, window.total_refused))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn admits_up_to_the_limit_per_key_and_refuses_the_rest() {
+        let mut subject = RateLimiter::new(2, Duration::from_secs(10));
+        let now = Instant::now();
+
+        let results = vec![
+            subject.admit(&"a", now),
+            subject.admit(&"a", now),
+            subject.admit(&"a", now),
+            subject.admit(&"b", now),
+            subject.admit(&"a", now),
+        ];
+
+        assert_eq!(results, vec![true, true, false, true, false]);
+        assert_eq!(subject.refused_in_interval(&"a"), 2);
+        assert_eq!(subject.refused_in_interval(&"b"), 0);
+        assert_eq!(subject.refused_in_interval(&"c"), 0);
+    }
+
+    #[test]
+    fn a_new_interval_starts_over_but_the_total_refused_count_survives() {
+        let mut subject = RateLimiter::new(1, Duration::from_secs(10));
+        let start = Instant::now();
+        subject.admit(&"a", start);
+        subject.admit(&"a", start);
+
+        let result = subject.admit(&"a", start + Duration::from_secs(10));
+
+        assert_eq!(result, true);
+        assert_eq!(subject.refused_in_interval(&"a"), 0);
+        assert_eq!(subject.total_refused(&"a"), 1);
+    }
+
+    #[test]
+    fn prune_forgets_expired_keys_and_reports_the_ones_that_exceeded_the_limit() {
+        let mut subject = RateLimiter::new(1, Duration::from_secs(10));
+        let start = Instant::now();
+        subject.admit(&"quiet", start);
+        subject.admit(&"noisy", start);
+        subject.admit(&"noisy", start);
+        subject.admit(&"noisy", start);
+        subject.admit(&"recent", start + Duration::from_secs(5));
+        subject.admit(&"recent", start + Duration::from_secs(5));
+
+        let result = subject.prune(start + Duration::from_secs(10));
+
+        assert_eq!(result, vec![("noisy", 2)]);
+        let keys = subject.windows.keys().cloned().collect::<Vec<&str>>();
+        assert_eq!(keys, vec!["recent"]);
+        assert_eq!(subject.total_refused(&"noisy"), 0);
+        assert_eq!(subject.total_refused(&"recent"), 1);
+    }
+}
@@ -1,19 +1,24 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::proxy_client::resolver_wrapper::ResolverWrapper;
 use crate::proxy_client::resolver_wrapper::ResolverWrapperFactory;
 use crate::proxy_client::resolver_wrapper::ResolverWrapperFactoryReal;
 use crate::proxy_client::stream_handler_pool::StreamHandlerPool;
 use crate::proxy_client::stream_handler_pool::StreamHandlerPoolFactory;
 use crate::proxy_client::stream_handler_pool::StreamHandlerPoolFactoryReal;
 use crate::sub_lib::accountant::ReportExitServiceProvidedMessage;
+use crate::sub_lib::compression;
+use crate::sub_lib::compression::PayloadCompression;
 use crate::sub_lib::cryptde::CryptDE;
 use crate::sub_lib::cryptde::PublicKey;
 use crate::sub_lib::hopper::MessageType;
 use crate::sub_lib::hopper::{ExpiredCoresPackage, IncipientCoresPackage};
 use crate::sub_lib::logger::Logger;
+use crate::sub_lib::neighborhood::SetRatePackMessage;
 use crate::sub_lib::peer_actors::BindMessage;
 use crate::sub_lib::proxy_client::InboundServerData;
 use crate::sub_lib::proxy_client::ProxyClientConfig;
 use crate::sub_lib::proxy_client::ProxyClientSubs;
+use crate::sub_lib::proxy_client::SetDnsServersMessage;
 use crate::sub_lib::proxy_client::{ClientResponsePayload, DnsResolveFailure};
 use crate::sub_lib::proxy_server::ClientRequestPayload;
 use crate::sub_lib::route::Route;
@@ -27,6 +32,8 @@ use actix::Context;
 use actix::Handler;
 use actix::Recipient;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::iter;
 use std::net::SocketAddr;
 use trust_dns_resolver::config::NameServerConfig;
 use trust_dns_resolver::config::Protocol;
@@ -59,18 +66,7 @@ impl Handler<BindMessage> for ProxyClient {
         ctx.set_mailbox_capacity(NODE_MAILBOX_CAPACITY);
         self.to_hopper = Some(msg.peer_actors.hopper.from_hopper_client);
         self.to_accountant = Some(msg.peer_actors.accountant.report_exit_service_provided);
-        let mut config = ResolverConfig::new();
-        for dns_server_ref in &self.dns_servers {
-            self.logger
-                .info(format!("Adding DNS server: {}", dns_server_ref.ip()));
-            config.add_name_server(NameServerConfig {
-                socket_addr: *dns_server_ref,
-                protocol: Protocol::Udp,
-                tls_dns_name: None,
-            })
-        }
-        let opts = ResolverOpts::default();
-        let resolver = self.resolver_wrapper_factory.make(config, opts);
+        let resolver = self.make_resolver();
         self.pool = Some(self.stream_handler_pool_factory.make(
             resolver,
             self.cryptde,
@@ -90,16 +86,63 @@ impl Handler<ExpiredCoresPackage<ClientRequestPayload>> for ProxyClient {
         msg: ExpiredCoresPackage<ClientRequestPayload>,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
-        let payload = msg.payload;
+        let mut payload = msg.payload;
         let consuming_wallet = msg.consuming_wallet;
         if consuming_wallet.is_some() || &payload.originator_public_key == self.cryptde.public_key()
         {
             let pool = self.pool.as_mut().expect("StreamHandlerPool unbound");
             let return_route = msg.remaining_route;
+            let sequence_number = payload.sequenced_packet.sequence_number;
+            if let Some(stream_context) = self.stream_contexts.get_mut(&payload.stream_key) {
+                if stream_context
+                    .unmatched_sequence_numbers
+                    .remove(&sequence_number)
+                {
+                    self.logger.debug(format!(
+                        "Received redundant copy of packet {} for stream {:?}; responses will use both routes",
+                        sequence_number, payload.stream_key
+                    ));
+                    stream_context.redundant_return_route_opt = Some(return_route);
+                    return;
+                }
+            }
+            if payload.compression == PayloadCompression::Compressed {
+                match compression::decompress(
+                    &payload.sequenced_packet.data,
+                    compression::MAX_DECOMPRESSED_LEN,
+                ) {
+                    Ok(data) => payload.sequenced_packet.data = data,
+                    Err(e) => {
+                        self.logger.error(format!(
+                            "Discarding packet {} for stream {:?}: can't decompress request: {}",
+                            sequence_number, payload.stream_key, e
+                        ));
+                        return;
+                    }
+                }
+            }
+            let (mut unmatched_sequence_numbers, redundant_return_route_opt) =
+                match self.stream_contexts.remove(&payload.stream_key) {
+                    Some(stream_context) => (
+                        stream_context.unmatched_sequence_numbers,
+                        stream_context.redundant_return_route_opt,
+                    ),
+                    None => (HashSet::new(), None),
+                };
+            if payload.redundant {
+                unmatched_sequence_numbers.insert(sequence_number);
+            }
             let latest_stream_context = StreamContext {
                 return_route,
+                redundant_return_route_opt: if payload.redundant {
+                    redundant_return_route_opt
+                } else {
+                    None
+                },
                 payload_destination_key: payload.originator_public_key.clone(),
                 consuming_wallet: consuming_wallet.clone(),
+                unmatched_sequence_numbers,
+                originator_understands_compression: payload.compression.is_understood(),
             };
             self.stream_contexts
                 .insert(payload.stream_key.clone(), latest_stream_context);
@@ -173,6 +216,34 @@ impl Handler<DnsResolveFailure> for ProxyClient {
     }
 }
 
+impl Handler<SetRatePackMessage> for ProxyClient {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetRatePackMessage, _ctx: &mut Self::Context) -> Self::Result {
+        self.exit_service_rate = msg.rate_pack.exit_service_rate;
+        self.exit_byte_rate = msg.rate_pack.exit_byte_rate;
+        if let Some(pool) = self.pool.as_ref() {
+            pool.set_exit_rates(self.exit_service_rate, self.exit_byte_rate);
+        }
+    }
+}
+
+impl Handler<SetDnsServersMessage> for ProxyClient {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetDnsServersMessage, _ctx: &mut Self::Context) -> Self::Result {
+        if msg.dns_servers.is_empty() {
+            self.logger
+                .error("Refusing to replace the DNS servers with none".to_string());
+            return;
+        }
+        self.dns_servers = msg.dns_servers;
+        if let Some(pool) = self.pool.as_ref() {
+            pool.set_resolver(self.make_resolver());
+        }
+    }
+}
+
 impl ProxyClient {
     pub fn new(config: ProxyClientConfig) -> ProxyClient {
         if config.dns_servers.is_empty() {
@@ -201,7 +272,24 @@ impl ProxyClient {
                 .recipient::<ExpiredCoresPackage<ClientRequestPayload>>(),
             inbound_server_data: addr.clone().recipient::<InboundServerData>(),
             dns_resolve_failed: addr.clone().recipient::<DnsResolveFailure>(),
+            set_rate_pack: addr.clone().recipient::<SetRatePackMessage>(),
+            set_dns_servers: addr.clone().recipient::<SetDnsServersMessage>(),
+        }
+    }
+
+    fn make_resolver(&self) -> Box<dyn ResolverWrapper> {
+        let mut config = ResolverConfig::new();
+        for dns_server_ref in &self.dns_servers {
+            self.logger
+                .info(format!("Adding DNS server: {}", dns_server_ref.ip()));
+            config.add_name_server(NameServerConfig {
+                socket_addr: *dns_server_ref,
+                protocol: Protocol::Udp,
+                tls_dns_name: None,
+            })
         }
+        let opts = ResolverOpts::default();
+        self.resolver_wrapper_factory.make(config, opts)
     }
 
     fn send_response_to_hopper(
@@ -212,31 +300,42 @@ impl ProxyClient {
         let msg_data_len = msg.data.len() as u32;
         let msg_source = msg.source;
         let msg_sequence_number = msg.sequence_number;
+        let (data, compression) = if stream_context.originator_understands_compression {
+            compression::compress_if_smaller(msg.data)
+        } else {
+            (msg.data, PayloadCompression::None)
+        };
         let payload = MessageType::ClientResponse(ClientResponsePayload {
             stream_key: msg.stream_key,
             sequenced_packet: SequencedPacket {
-                data: msg.data,
+                data,
                 sequence_number: msg.sequence_number,
                 last_data: msg.last_data,
             },
+            compression,
+            redundant: stream_context.redundant_return_route_opt.is_some(),
         });
-        let icp = match IncipientCoresPackage::new(
-            self.cryptde,
-            stream_context.return_route.clone(),
-            payload,
-            &stream_context.payload_destination_key,
-        ) {
-            Ok(icp) => icp,
+        let icps = iter::once(&stream_context.return_route)
+            .chain(stream_context.redundant_return_route_opt.iter())
+            .map(|route| {
+                IncipientCoresPackage::new(
+                    self.cryptde,
+                    route.clone(),
+                    payload.clone(),
+                    &stream_context.payload_destination_key,
+                )
+            })
+            .collect::<Result<Vec<IncipientCoresPackage>, String>>();
+        let icps = match icps {
+            Ok(icps) => icps,
             Err(err) => {
                 self.logger.error(format!("Could not create CORES package for {}-byte response from {}, seq {}: {} - ignoring", msg_data_len, msg_source, msg_sequence_number, err));
                 return Err(());
             }
         };
-        self.to_hopper
-            .as_ref()
-            .expect("Hopper unbound")
-            .try_send(icp)
-            .expect("Hopper is dead");
+        let hopper = self.to_hopper.as_ref().expect("Hopper unbound");
+        icps.into_iter()
+            .for_each(|icp| hopper.try_send(icp).expect("Hopper is dead"));
         Ok(())
     }
 
@@ -268,8 +367,14 @@ impl ProxyClient {
 
 struct StreamContext {
     return_route: Route,
+    // Present when the originator sent the latest request over a second, disjoint route
+    redundant_return_route_opt: Option<Route>,
     payload_destination_key: PublicKey,
     consuming_wallet: Option<Wallet>,
+    // Sequence numbers of requests processed but whose redundant copy hasn't arrived (yet)
+    unmatched_sequence_numbers: HashSet<u64>,
+    // Whether responses may be compressed on their way back to the originator
+    originator_understands_compression: bool,
 }
 
 #[cfg(test)]
@@ -297,6 +402,8 @@ mod tests {
     use crate::test_utils::recorder::Recorder;
     use crate::test_utils::test_utils::make_meaningless_route;
     use crate::test_utils::test_utils::make_meaningless_stream_key;
+    use crate::test_utils::test_utils::make_paying_wallet;
+    use crate::test_utils::test_utils::rate_pack;
     use crate::test_utils::test_utils::rate_pack_exit;
     use crate::test_utils::test_utils::rate_pack_exit_byte;
     use crate::test_utils::test_utils::route_to_proxy_client;
@@ -316,6 +423,8 @@ mod tests {
 
     pub struct StreamHandlerPoolMock {
         process_package_parameters: Arc<Mutex<Vec<(ClientRequestPayload, Option<Wallet>)>>>,
+        set_exit_rates_parameters: Arc<Mutex<Vec<(u64, u64)>>>,
+        set_resolver_count: Arc<Mutex<usize>>,
     }
 
     impl StreamHandlerPool for StreamHandlerPoolMock {
@@ -325,15 +434,41 @@ mod tests {
                 .unwrap()
                 .push((payload, consuming_wallet));
         }
+
+        fn set_exit_rates(&self, exit_service_rate: u64, exit_byte_rate: u64) {
+            self.set_exit_rates_parameters
+                .lock()
+                .unwrap()
+                .push((exit_service_rate, exit_byte_rate));
+        }
+
+        fn set_resolver(&self, _resolver: Box<dyn ResolverWrapper>) {
+            *self.set_resolver_count.lock().unwrap() += 1;
+        }
     }
 
     impl StreamHandlerPoolMock {
         pub fn new() -> StreamHandlerPoolMock {
             StreamHandlerPoolMock {
                 process_package_parameters: Arc::new(Mutex::new(vec![])),
+                set_exit_rates_parameters: Arc::new(Mutex::new(vec![])),
+                set_resolver_count: Arc::new(Mutex::new(0)),
             }
         }
 
+        pub fn set_resolver_count(self, count: &mut Arc<Mutex<usize>>) -> StreamHandlerPoolMock {
+            *count = self.set_resolver_count.clone();
+            self
+        }
+
+        pub fn set_exit_rates_parameters(
+            self,
+            parameters: &mut Arc<Mutex<Vec<(u64, u64)>>>,
+        ) -> StreamHandlerPoolMock {
+            *parameters = self.set_exit_rates_parameters.clone();
+            self
+        }
+
         pub fn process_package_parameters(
             self,
             parameters: &mut Arc<Mutex<Vec<(ClientRequestPayload, Option<Wallet>)>>>,
@@ -430,6 +565,35 @@ mod tests {
         });
     }
 
+    #[test]
+    fn set_rate_pack_message_changes_the_exit_rates_the_pool_charges() {
+        let system = System::new("set_rate_pack_message_changes_the_exit_rates_the_pool_charges");
+        let mut set_exit_rates_parameters = Arc::new(Mutex::new(vec![]));
+        let pool =
+            StreamHandlerPoolMock::new().set_exit_rates_parameters(&mut set_exit_rates_parameters);
+        let mut subject = ProxyClient::new(ProxyClientConfig {
+            cryptde: cryptde(),
+            dns_servers: dnss(),
+            exit_service_rate: 100,
+            exit_byte_rate: 200,
+        });
+        subject.pool = Some(Box::new(pool));
+        let subject_addr: Addr<ProxyClient> = subject.start();
+
+        subject_addr
+            .try_send(SetRatePackMessage {
+                rate_pack: rate_pack(300),
+            })
+            .unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        assert_eq!(
+            *set_exit_rates_parameters.lock().unwrap(),
+            vec![(rate_pack_exit(300), rate_pack_exit_byte(300))]
+        );
+    }
+
     #[test]
     fn bind_operates_properly() {
         let system = System::new("bind_initializes_resolver_wrapper_properly");
@@ -488,6 +652,52 @@ mod tests {
         assert_eq!(resolver_wrapper_new_parameters.is_empty(), true);
     }
 
+    #[test]
+    fn set_dns_servers_message_gives_the_pool_a_resolver_for_the_new_servers() {
+        let system =
+            System::new("set_dns_servers_message_gives_the_pool_a_resolver_for_the_new_servers");
+        let mut resolver_wrapper_new_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let resolver_wrapper_factory = ResolverWrapperFactoryMock::new()
+            .new_parameters(&mut resolver_wrapper_new_parameters_arc)
+            .new_result(Box::new(ResolverWrapperMock::new()));
+        let mut set_resolver_count = Arc::new(Mutex::new(0));
+        let pool = StreamHandlerPoolMock::new().set_resolver_count(&mut set_resolver_count);
+        let mut subject = ProxyClient::new(ProxyClientConfig {
+            cryptde: cryptde(),
+            dns_servers: dnss(),
+            exit_service_rate: 100,
+            exit_byte_rate: 200,
+        });
+        subject.resolver_wrapper_factory = Box::new(resolver_wrapper_factory);
+        subject.pool = Some(Box::new(pool));
+        let subject_addr: Addr<ProxyClient> = subject.start();
+
+        subject_addr
+            .try_send(SetDnsServersMessage {
+                dns_servers: vec![],
+            })
+            .unwrap();
+        subject_addr
+            .try_send(SetDnsServersMessage {
+                dns_servers: vec![SocketAddr::from_str("9.8.7.6:53").unwrap()],
+            })
+            .unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        let resolver_wrapper_new_parameters = resolver_wrapper_new_parameters_arc.lock().unwrap();
+        assert_eq!(resolver_wrapper_new_parameters.len(), 1);
+        assert_eq!(
+            resolver_wrapper_new_parameters[0].0.name_servers(),
+            &[NameServerConfig {
+                socket_addr: SocketAddr::from_str("9.8.7.6:53").unwrap(),
+                protocol: Protocol::Udp,
+                tls_dns_name: None,
+            }]
+        );
+        assert_eq!(*set_resolver_count.lock().unwrap(), 1);
+    }
+
     #[test]
     #[should_panic(expected = "StreamHandlerPool unbound")]
     fn panics_if_unbound() {
@@ -502,11 +712,13 @@ mod tests {
             target_port: 1234,
             protocol: ProxyProtocol::HTTP,
             originator_public_key: PublicKey::new(&b"originator_public_key"[..]),
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let cryptde = cryptde();
         let package = ExpiredCoresPackage::new(
             IpAddr::from_str("1.2.3.4").unwrap(),
-            Some(Wallet::new("consuming")),
+            Some(make_paying_wallet(b"consuming")),
             route_to_proxy_client(&cryptde.public_key(), cryptde),
             request,
             0,
@@ -585,8 +797,11 @@ mod tests {
                 stream_key_inner,
                 StreamContext {
                     return_route: return_route_inner,
+                    redundant_return_route_opt: None,
                     payload_destination_key: originator_key_inner,
                     consuming_wallet: None,
+                    unmatched_sequence_numbers: HashSet::new(),
+                    originator_understands_compression: false,
                 },
             );
             let subject_addr = subject.start();
@@ -648,10 +863,12 @@ mod tests {
             target_port: 0,
             protocol: ProxyProtocol::HTTP,
             originator_public_key: PublicKey::new(&b"originator"[..]),
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let package = ExpiredCoresPackage::new(
             IpAddr::from_str("1.2.3.4").unwrap(),
-            Some(Wallet::new("consuming")),
+            Some(make_paying_wallet(b"consuming")),
             make_meaningless_route(),
             request.clone().into(),
             0,
@@ -685,7 +902,173 @@ mod tests {
         System::current().stop_with_code(0);
         system.run();
         let parameter = process_package_parameters.lock().unwrap().remove(0);
-        assert_eq!(parameter, (request, Some(Wallet::new("consuming")),));
+        assert_eq!(
+            parameter,
+            (request, Some(make_paying_wallet(b"consuming")),)
+        );
+    }
+
+    #[test]
+    fn compressed_request_is_decompressed_and_its_responses_are_compressed() {
+        let cryptde = cryptde();
+        let data = b"GET /index.html HTTP/1.1\r\nHost: nowhere.com\r\nAccept: text/html\r\nAccept-Language: en-US\r\n\r\n".to_vec();
+        let response_data =
+            b"<html><body><p>booga</p><p>booga</p><p>booga</p><p>booga</p></body></html>".to_vec();
+        let request = ClientRequestPayload {
+            stream_key: make_meaningless_stream_key(),
+            sequenced_packet: SequencedPacket {
+                data: compression::compress(&data),
+                sequence_number: 0,
+                last_data: false,
+            },
+            target_hostname: None,
+            target_port: 0,
+            protocol: ProxyProtocol::HTTP,
+            originator_public_key: PublicKey::new(&b"originator"[..]),
+            compression: PayloadCompression::Compressed,
+            redundant: false,
+        };
+        let package = ExpiredCoresPackage::new(
+            IpAddr::from_str("1.2.3.4").unwrap(),
+            Some(make_paying_wallet(b"consuming")),
+            make_meaningless_route(),
+            request.clone().into(),
+            0,
+        );
+        let (hopper, _, hopper_recording_arc) = make_recorder();
+        let (accountant, _, accountant_recording_arc) = make_recorder();
+        let system =
+            System::new("compressed_request_is_decompressed_and_its_responses_are_compressed");
+        let mut process_package_parameters = Arc::new(Mutex::new(vec![]));
+        let pool = Box::new(
+            StreamHandlerPoolMock::new()
+                .process_package_parameters(&mut process_package_parameters),
+        );
+        let pool_factory = StreamHandlerPoolFactoryMock::new().make_result(pool);
+        let mut subject = ProxyClient::new(ProxyClientConfig {
+            cryptde,
+            dns_servers: dnss(),
+            exit_service_rate: 100,
+            exit_byte_rate: 200,
+        });
+        subject.stream_handler_pool_factory = Box::new(pool_factory);
+        let subject_addr: Addr<ProxyClient> = subject.start();
+        let peer_actors = peer_actors_builder()
+            .hopper(hopper)
+            .accountant(accountant)
+            .build();
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        subject_addr.try_send(package).unwrap();
+        subject_addr
+            .try_send(InboundServerData {
+                stream_key: make_meaningless_stream_key(),
+                last_data: true,
+                sequence_number: 0,
+                source: SocketAddr::from_str("1.2.3.4:80").unwrap(),
+                data: response_data.clone(),
+            })
+            .unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        let parameter = process_package_parameters.lock().unwrap().remove(0);
+        let expected_request = ClientRequestPayload {
+            sequenced_packet: SequencedPacket {
+                data,
+                sequence_number: 0,
+                last_data: false,
+            },
+            ..request
+        };
+        assert_eq!(
+            parameter,
+            (expected_request, Some(make_paying_wallet(b"consuming")))
+        );
+        let hopper_recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(
+            hopper_recording.get_record::<IncipientCoresPackage>(0),
+            &IncipientCoresPackage::new(
+                cryptde,
+                make_meaningless_route(),
+                MessageType::ClientResponse(ClientResponsePayload {
+                    stream_key: make_meaningless_stream_key(),
+                    sequenced_packet: SequencedPacket {
+                        data: compression::compress(&response_data),
+                        sequence_number: 0,
+                        last_data: true,
+                    },
+                    compression: PayloadCompression::Compressed,
+                    redundant: false,
+                }),
+                &PublicKey::new(&b"originator"[..]),
+            )
+            .unwrap()
+        );
+        let accountant_recording = accountant_recording_arc.lock().unwrap();
+        assert_eq!(
+            accountant_recording.get_record::<ReportExitServiceProvidedMessage>(0),
+            &ReportExitServiceProvidedMessage {
+                consuming_wallet: make_paying_wallet(b"consuming"),
+                payload_size: response_data.len(),
+                service_rate: 100,
+                byte_rate: 200,
+            }
+        );
+    }
+
+    #[test]
+    fn request_that_cant_be_decompressed_is_logged_and_dropped() {
+        init_test_logging();
+        let cryptde = cryptde();
+        let request = ClientRequestPayload {
+            stream_key: make_meaningless_stream_key(),
+            sequenced_packet: SequencedPacket {
+                data: vec![0xFF, 0xFF, 0xFF],
+                sequence_number: 0,
+                last_data: false,
+            },
+            target_hostname: None,
+            target_port: 0,
+            protocol: ProxyProtocol::HTTP,
+            originator_public_key: PublicKey::new(&b"originator"[..]),
+            compression: PayloadCompression::Compressed,
+            redundant: false,
+        };
+        let package = ExpiredCoresPackage::new(
+            IpAddr::from_str("1.2.3.4").unwrap(),
+            Some(make_paying_wallet(b"consuming")),
+            make_meaningless_route(),
+            request.clone().into(),
+            0,
+        );
+        let system = System::new("request_that_cant_be_decompressed_is_logged_and_dropped");
+        let peer_actors = peer_actors_builder().build();
+        let mut process_package_parameters = Arc::new(Mutex::new(vec![]));
+        let pool = Box::new(
+            StreamHandlerPoolMock::new()
+                .process_package_parameters(&mut process_package_parameters),
+        );
+        let pool_factory = StreamHandlerPoolFactoryMock::new().make_result(pool);
+        let mut subject = ProxyClient::new(ProxyClientConfig {
+            cryptde,
+            dns_servers: dnss(),
+            exit_service_rate: 100,
+            exit_byte_rate: 200,
+        });
+        subject.stream_handler_pool_factory = Box::new(pool_factory);
+        let subject_addr: Addr<ProxyClient> = subject.start();
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        subject_addr.try_send(package).unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        assert!(process_package_parameters.lock().unwrap().is_empty());
+        TestLogHandler::new().exists_log_containing(&format!(
+            "ERROR: Proxy Client: Discarding packet 0 for stream {:?}: can't decompress request: Can't decompress 3-byte data: ",
+            make_meaningless_stream_key()
+        ));
     }
 
     #[test]
@@ -703,6 +1086,8 @@ mod tests {
             target_port: 0,
             protocol: ProxyProtocol::HTTP,
             originator_public_key: PublicKey::new(&b"originator"[..]),
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let package = ExpiredCoresPackage::new(
             IpAddr::from_str("1.2.3.4").unwrap(),
@@ -757,6 +1142,8 @@ mod tests {
             target_port: 0,
             protocol: ProxyProtocol::HTTP,
             originator_public_key: cryptde.public_key().clone(),
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let package = ExpiredCoresPackage::new(
             IpAddr::from_str("1.2.3.4").unwrap(),
@@ -815,8 +1202,11 @@ mod tests {
             stream_key.clone(),
             StreamContext {
                 return_route: make_meaningless_route(),
+                redundant_return_route_opt: None,
                 payload_destination_key: PublicKey::new(&b"abcd"[..]),
-                consuming_wallet: Some(Wallet::new("consuming")),
+                consuming_wallet: Some(make_paying_wallet(b"consuming")),
+                unmatched_sequence_numbers: HashSet::new(),
+                originator_understands_compression: false,
             },
         );
         let subject_addr: Addr<ProxyClient> = subject.start();
@@ -870,6 +1260,8 @@ mod tests {
                         sequence_number: 1234,
                         last_data: false,
                     },
+                    compression: PayloadCompression::None,
+                    redundant: false,
                 }),
                 &PublicKey::new(&b"abcd"[..]),
             )
@@ -887,6 +1279,8 @@ mod tests {
                         sequence_number: 1235,
                         last_data: true,
                     },
+                    compression: PayloadCompression::None,
+                    redundant: false,
                 }),
                 &PublicKey::new(&b"abcd"[..]),
             )
@@ -898,7 +1292,7 @@ mod tests {
         assert_eq!(
             accountant_recording.get_record::<ReportExitServiceProvidedMessage>(0),
             &ReportExitServiceProvidedMessage {
-                consuming_wallet: Wallet::new("consuming"),
+                consuming_wallet: make_paying_wallet(b"consuming"),
                 payload_size: data.len(),
                 service_rate: 100,
                 byte_rate: 200,
@@ -907,7 +1301,7 @@ mod tests {
         assert_eq!(
             accountant_recording.get_record::<ReportExitServiceProvidedMessage>(1),
             &ReportExitServiceProvidedMessage {
-                consuming_wallet: Wallet::new("consuming"),
+                consuming_wallet: make_paying_wallet(b"consuming"),
                 payload_size: data.len(),
                 service_rate: 100,
                 byte_rate: 200,
@@ -934,8 +1328,11 @@ mod tests {
             stream_key.clone(),
             StreamContext {
                 return_route: make_meaningless_route(),
+                redundant_return_route_opt: None,
                 payload_destination_key: PublicKey::new(&b"abcd"[..]),
                 consuming_wallet: None,
+                unmatched_sequence_numbers: HashSet::new(),
+                originator_understands_compression: false,
             },
         );
         let subject_addr: Addr<ProxyClient> = subject.start();
@@ -984,8 +1381,11 @@ mod tests {
             stream_key.clone(),
             StreamContext {
                 return_route: make_meaningless_route(),
+                redundant_return_route_opt: None,
                 payload_destination_key: PublicKey::new(&[]),
-                consuming_wallet: Some(Wallet::new("consuming")),
+                consuming_wallet: Some(make_paying_wallet(b"consuming")),
+                unmatched_sequence_numbers: HashSet::new(),
+                originator_understands_compression: false,
             },
         );
         let subject_addr: Addr<ProxyClient> = subject.start();
@@ -1041,8 +1441,11 @@ mod tests {
             stream_key.clone(),
             StreamContext {
                 return_route: old_return_route,
+                redundant_return_route_opt: None,
                 payload_destination_key: originator_public_key.clone(),
-                consuming_wallet: Some(Wallet::new("consuming")),
+                consuming_wallet: Some(make_paying_wallet(b"consuming")),
+                unmatched_sequence_numbers: HashSet::new(),
+                originator_understands_compression: false,
             },
         );
         subject.stream_handler_pool_factory = Box::new(pool_factory);
@@ -1063,6 +1466,8 @@ mod tests {
             target_port: 0,
             protocol: ProxyProtocol::HTTP,
             originator_public_key: originator_public_key.clone(),
+            compression: PayloadCompression::None,
+            redundant: false,
         };
 
         subject_addr
@@ -1101,6 +1506,8 @@ mod tests {
                     sequence_number: 1234,
                     last_data: false,
                 },
+                compression: PayloadCompression::None,
+                redundant: false,
             }),
             &originator_public_key,
         )
@@ -1121,4 +1528,246 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    fn redundant_copy_of_request_is_not_reprocessed_and_responses_return_on_both_routes() {
+        let cryptde = cryptde();
+        let (hopper, _, hopper_recording_arc) = make_recorder();
+        let (accountant, _, accountant_recording_arc) = make_recorder();
+        let stream_key = make_meaningless_stream_key();
+        let data: &[u8] = b"Two is one and one is none.";
+        let system = System::new(
+            "redundant_copy_of_request_is_not_reprocessed_and_responses_return_on_both_routes",
+        );
+        let mut subject = ProxyClient::new(ProxyClientConfig {
+            cryptde,
+            dns_servers: vec![SocketAddr::from_str("8.7.6.5:4321").unwrap()],
+            exit_service_rate: 100,
+            exit_byte_rate: 200,
+        });
+        let mut process_package_params_arc = Arc::new(Mutex::new(vec![]));
+        let pool = StreamHandlerPoolMock::new()
+            .process_package_parameters(&mut process_package_params_arc);
+        let pool_factory = StreamHandlerPoolFactoryMock::new().make_result(Box::new(pool));
+        subject.stream_handler_pool_factory = Box::new(pool_factory);
+        let first_return_route = make_meaningless_route();
+        let second_return_route = Route {
+            hops: vec![CryptData::new(&[1, 2, 3, 4])],
+        };
+        let originator_public_key = PublicKey::new(&[4, 3, 2, 1]);
+        let subject_addr: Addr<ProxyClient> = subject.start();
+        let peer_actors = peer_actors_builder()
+            .hopper(hopper)
+            .accountant(accountant)
+            .build();
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+        let payload = ClientRequestPayload {
+            stream_key: stream_key.clone(),
+            sequenced_packet: SequencedPacket {
+                data: b"request".to_vec(),
+                sequence_number: 0,
+                last_data: false,
+            },
+            target_hostname: None,
+            target_port: 0,
+            protocol: ProxyProtocol::HTTP,
+            originator_public_key: originator_public_key.clone(),
+            compression: PayloadCompression::None,
+            redundant: true,
+        };
+        let make_package = |route: &Route| {
+            ExpiredCoresPackage::new(
+                IpAddr::from_str("2.3.4.5").unwrap(),
+                Some(make_paying_wallet(b"consuming")),
+                route.clone(),
+                payload.clone().into(),
+                0,
+            )
+        };
+
+        subject_addr
+            .try_send(make_package(&first_return_route))
+            .unwrap();
+        subject_addr
+            .try_send(make_package(&second_return_route))
+            .unwrap();
+        subject_addr
+            .try_send(InboundServerData {
+                stream_key: stream_key.clone(),
+                last_data: false,
+                sequence_number: 0,
+                source: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+                data: Vec::from(data),
+            })
+            .unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        assert_eq!(process_package_params_arc.lock().unwrap().len(), 1);
+        let expected_payload = MessageType::ClientResponse(ClientResponsePayload {
+            stream_key,
+            sequenced_packet: SequencedPacket {
+                data: Vec::from(data),
+                sequence_number: 0,
+                last_data: false,
+            },
+            compression: PayloadCompression::None,
+            redundant: true,
+        });
+        let hopper_recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(
+            hopper_recording.get_record::<IncipientCoresPackage>(0),
+            &IncipientCoresPackage::new(
+                cryptde,
+                first_return_route,
+                expected_payload.clone(),
+                &originator_public_key,
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            hopper_recording.get_record::<IncipientCoresPackage>(1),
+            &IncipientCoresPackage::new(
+                cryptde,
+                second_return_route,
+                expected_payload,
+                &originator_public_key,
+            )
+            .unwrap()
+        );
+        assert_eq!(hopper_recording.len(), 2);
+        let accountant_recording = accountant_recording_arc.lock().unwrap();
+        assert_eq!(accountant_recording.len(), 1);
+    }
+
+    #[test]
+    fn requests_without_a_redundant_copy_are_not_held_for_matching() {
+        let cryptde = cryptde();
+        let (hopper, _, _) = make_recorder();
+        let stream_key = make_meaningless_stream_key();
+        let system = System::new("requests_without_a_redundant_copy_are_not_held_for_matching");
+        let mut subject = ProxyClient::new(ProxyClientConfig {
+            cryptde,
+            dns_servers: vec![SocketAddr::from_str("8.7.6.5:4321").unwrap()],
+            exit_service_rate: 100,
+            exit_byte_rate: 200,
+        });
+        let mut process_package_params_arc = Arc::new(Mutex::new(vec![]));
+        let pool = StreamHandlerPoolMock::new()
+            .process_package_parameters(&mut process_package_params_arc);
+        let pool_factory = StreamHandlerPoolFactoryMock::new().make_result(Box::new(pool));
+        subject.stream_handler_pool_factory = Box::new(pool_factory);
+        let subject_addr: Addr<ProxyClient> = subject.start();
+        let peer_actors = peer_actors_builder().hopper(hopper).build();
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+        let payload = ClientRequestPayload {
+            stream_key: stream_key.clone(),
+            sequenced_packet: SequencedPacket {
+                data: b"request".to_vec(),
+                sequence_number: 0,
+                last_data: false,
+            },
+            target_hostname: None,
+            target_port: 0,
+            protocol: ProxyProtocol::HTTP,
+            originator_public_key: PublicKey::new(&[4, 3, 2, 1]),
+            compression: PayloadCompression::None,
+            redundant: false,
+        };
+        let package = ExpiredCoresPackage::new(
+            IpAddr::from_str("2.3.4.5").unwrap(),
+            Some(make_paying_wallet(b"consuming")),
+            make_meaningless_route(),
+            payload.into(),
+            0,
+        );
+
+        subject_addr.try_send(package.clone()).unwrap();
+        subject_addr.try_send(package).unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        assert_eq!(process_package_params_arc.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn redundant_return_route_survives_later_requests_on_the_stream() {
+        let cryptde = cryptde();
+        let (hopper, _, hopper_recording_arc) = make_recorder();
+        let stream_key = make_meaningless_stream_key();
+        let system = System::new("redundant_return_route_survives_later_requests_on_the_stream");
+        let mut subject = ProxyClient::new(ProxyClientConfig {
+            cryptde,
+            dns_servers: vec![SocketAddr::from_str("8.7.6.5:4321").unwrap()],
+            exit_service_rate: 100,
+            exit_byte_rate: 200,
+        });
+        let mut process_package_params_arc = Arc::new(Mutex::new(vec![]));
+        let pool = StreamHandlerPoolMock::new()
+            .process_package_parameters(&mut process_package_params_arc);
+        let pool_factory = StreamHandlerPoolFactoryMock::new().make_result(Box::new(pool));
+        subject.stream_handler_pool_factory = Box::new(pool_factory);
+        let first_return_route = make_meaningless_route();
+        let second_return_route = Route {
+            hops: vec![CryptData::new(&[1, 2, 3, 4])],
+        };
+        let originator_public_key = PublicKey::new(&[4, 3, 2, 1]);
+        let subject_addr: Addr<ProxyClient> = subject.start();
+        let peer_actors = peer_actors_builder().hopper(hopper).build();
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+        let make_package = |sequence_number: u64, route: &Route| {
+            let payload = ClientRequestPayload {
+                stream_key: stream_key.clone(),
+                sequenced_packet: SequencedPacket {
+                    data: b"request".to_vec(),
+                    sequence_number,
+                    last_data: false,
+                },
+                target_hostname: None,
+                target_port: 0,
+                protocol: ProxyProtocol::HTTP,
+                originator_public_key: originator_public_key.clone(),
+                compression: PayloadCompression::None,
+                redundant: true,
+            };
+            ExpiredCoresPackage::new(
+                IpAddr::from_str("2.3.4.5").unwrap(),
+                Some(make_paying_wallet(b"consuming")),
+                route.clone(),
+                payload.into(),
+                0,
+            )
+        };
+
+        subject_addr
+            .try_send(make_package(0, &first_return_route))
+            .unwrap();
+        subject_addr
+            .try_send(make_package(0, &second_return_route))
+            .unwrap();
+        subject_addr
+            .try_send(make_package(1, &first_return_route))
+            .unwrap();
+        subject_addr
+            .try_send(InboundServerData {
+                stream_key: stream_key.clone(),
+                last_data: false,
+                sequence_number: 0,
+                source: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+                data: b"response".to_vec(),
+            })
+            .unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        assert_eq!(process_package_params_arc.lock().unwrap().len(), 2);
+        let hopper_recording = hopper_recording_arc.lock().unwrap();
+        assert_eq!(
+            hopper_recording
+                .get_record::<IncipientCoresPackage>(1)
+                .route,
+            second_return_route
+        );
+        assert_eq!(hopper_recording.len(), 2);
+    }
 }
@@ -174,6 +174,7 @@ impl StreamEstablisherFactory for StreamEstablisherFactoryReal {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::sub_lib::compression::PayloadCompression;
     use crate::sub_lib::proxy_server::ProxyProtocol;
     use crate::test_utils::recorder::make_recorder;
     use crate::test_utils::recorder::peer_actors_builder;
@@ -238,6 +239,8 @@ mod tests {
                         target_port: 0,
                         protocol: ProxyProtocol::HTTP,
                         originator_public_key: subject.cryptde.public_key().clone(),
+                        compression: PayloadCompression::None,
+                        redundant: false,
                     },
                     read_stream,
                     SocketAddr::from_str("1.2.3.4:5678").unwrap(),
@@ -319,6 +322,8 @@ mod tests {
                         target_port: 0,
                         protocol: ProxyProtocol::TLS,
                         originator_public_key: subject.cryptde.public_key().clone(),
+                        compression: PayloadCompression::None,
+                        redundant: false,
                     },
                     read_stream,
                     SocketAddr::from_str("1.2.3.4:5678").unwrap(),
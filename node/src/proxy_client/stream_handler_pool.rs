@@ -27,6 +27,8 @@ use tokio::prelude::future::{err, ok};
 
 pub trait StreamHandlerPool {
     fn process_package(&self, payload: ClientRequestPayload, consuming_wallet: Option<Wallet>);
+    fn set_exit_rates(&self, exit_service_rate: u64, exit_byte_rate: u64);
+    fn set_resolver(&self, resolver: Box<dyn ResolverWrapper>);
 }
 
 pub struct StreamHandlerPoolReal {
@@ -63,6 +65,17 @@ impl StreamHandlerPool for StreamHandlerPoolReal {
             Self::process_package(payload, consuming_wallet, self.inner.clone())
         }
     }
+
+    fn set_exit_rates(&self, exit_service_rate: u64, exit_byte_rate: u64) {
+        let mut inner = self.inner.lock().expect("Stream handler pool is poisoned");
+        inner.exit_service_rate = exit_service_rate;
+        inner.exit_byte_rate = exit_byte_rate;
+    }
+
+    fn set_resolver(&self, resolver: Box<dyn ResolverWrapper>) {
+        let mut inner = self.inner.lock().expect("Stream handler pool is poisoned");
+        inner.resolver = resolver;
+    }
 }
 
 impl StreamHandlerPoolReal {
@@ -378,11 +391,11 @@ mod tests {
     use crate::proxy_client::stream_establisher::StreamEstablisher;
     use crate::sub_lib::channel_wrappers::FuturesChannelFactoryReal;
     use crate::sub_lib::channel_wrappers::SenderWrapperReal;
+    use crate::sub_lib::compression::PayloadCompression;
     use crate::sub_lib::cryptde::PublicKey;
     use crate::sub_lib::hopper::ExpiredCoresPackage;
     use crate::sub_lib::hopper::MessageType;
     use crate::sub_lib::proxy_server::ProxyProtocol;
-    use crate::sub_lib::wallet::Wallet;
     use crate::test_utils::channel_wrapper_mocks::FuturesChannelFactoryMock;
     use crate::test_utils::channel_wrapper_mocks::ReceiverWrapperMock;
     use crate::test_utils::channel_wrapper_mocks::SenderWrapperMock;
@@ -395,6 +408,7 @@ mod tests {
     use crate::test_utils::test_utils::cryptde;
     use crate::test_utils::test_utils::make_meaningless_route;
     use crate::test_utils::test_utils::make_meaningless_stream_key;
+    use crate::test_utils::test_utils::make_paying_wallet;
     use crate::test_utils::tokio_wrapper_mocks::ReadHalfWrapperMock;
     use crate::test_utils::tokio_wrapper_mocks::WriteHalfWrapperMock;
     use actix::System;
@@ -480,6 +494,8 @@ mod tests {
                 target_port: HTTP_PORT,
                 protocol: ProxyProtocol::HTTP,
                 originator_public_key: cryptde.public_key().clone(),
+                compression: PayloadCompression::None,
+                redundant: false,
             };
 
             StreamHandlerPoolReal::process_package(payload, None, Arc::new(Mutex::new(inner)));
@@ -513,6 +529,8 @@ mod tests {
             target_port: HTTP_PORT,
             protocol: ProxyProtocol::HTTP,
             originator_public_key: PublicKey::new(&b"men's souls"[..]),
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let mut tx_to_write = Box::new(SenderWrapperMock::new(
             SocketAddr::from_str("1.2.3.4:5678").unwrap(),
@@ -522,7 +540,7 @@ mod tests {
 
         let package = ExpiredCoresPackage::new(
             IpAddr::from_str("1.2.3.4").unwrap(),
-            Some(Wallet::new("consuming")),
+            Some(make_paying_wallet(b"consuming")),
             make_meaningless_route(),
             client_request_payload.clone().into(),
             0,
@@ -574,10 +592,12 @@ mod tests {
                 target_port: HTTP_PORT,
                 protocol: ProxyProtocol::HTTP,
                 originator_public_key: originator_key,
+                compression: PayloadCompression::None,
+                redundant: false,
             };
             let package = ExpiredCoresPackage::new(
                 IpAddr::from_str("1.2.3.4").unwrap(),
-                Some(Wallet::new("consuming")),
+                Some(make_paying_wallet(b"consuming")),
                 make_meaningless_route(),
                 client_request_payload.clone().into(),
                 0,
@@ -641,10 +661,12 @@ mod tests {
                 target_port: HTTP_PORT,
                 protocol: ProxyProtocol::HTTP,
                 originator_public_key: originator_key,
+                compression: PayloadCompression::None,
+                redundant: false,
             };
             let package = ExpiredCoresPackage::new(
                 IpAddr::from_str("1.2.3.4").unwrap(),
-                Some(Wallet::new("consuming")),
+                Some(make_paying_wallet(b"consuming")),
                 make_meaningless_route(),
                 client_request_payload.into(),
                 0,
@@ -705,10 +727,12 @@ mod tests {
                 target_port: HTTP_PORT,
                 protocol: ProxyProtocol::HTTP,
                 originator_public_key: PublicKey::new(&b"men's souls"[..]),
+                compression: PayloadCompression::None,
+                redundant: false,
             };
             let package = ExpiredCoresPackage::new(
                 IpAddr::from_str("1.2.3.4").unwrap(),
-                Some(Wallet::new("consuming")),
+                Some(make_paying_wallet(b"consuming")),
                 make_meaningless_route(),
                 client_request_payload.into(),
                 0,
@@ -813,10 +837,12 @@ mod tests {
                 target_port: HTTP_PORT,
                 protocol: ProxyProtocol::HTTP,
                 originator_public_key: originator_key,
+                compression: PayloadCompression::None,
+                redundant: false,
             };
             let package = ExpiredCoresPackage::new(
                 IpAddr::from_str("1.2.3.4").unwrap(),
-                Some(Wallet::new("consuming")),
+                Some(make_paying_wallet(b"consuming")),
                 make_meaningless_route(),
                 client_request_payload.into(),
                 0,
@@ -899,11 +925,13 @@ mod tests {
                 target_port: HTTP_PORT,
                 protocol: ProxyProtocol::HTTP,
                 originator_public_key: PublicKey::new(&b"men's souls"[..]),
+                compression: PayloadCompression::None,
+                redundant: false,
             };
 
             let package = ExpiredCoresPackage::new(
                 IpAddr::from_str("1.2.3.4").unwrap(),
-                Some(Wallet::new("consuming")),
+                Some(make_paying_wallet(b"consuming")),
                 make_meaningless_route(),
                 client_request_payload.into(),
                 0,
@@ -1009,10 +1037,12 @@ mod tests {
                 target_port: HTTP_PORT,
                 protocol: ProxyProtocol::HTTP,
                 originator_public_key: originator_key,
+                compression: PayloadCompression::None,
+                redundant: false,
             };
             let package = ExpiredCoresPackage::new(
                 IpAddr::from_str("1.2.3.4").unwrap(),
-                Some(Wallet::new("consuming")),
+                Some(make_paying_wallet(b"consuming")),
                 make_meaningless_route(),
                 client_request_payload.into(),
                 0,
@@ -1070,10 +1100,12 @@ mod tests {
             target_port: HTTP_PORT,
             protocol: ProxyProtocol::HTTP,
             originator_public_key: PublicKey::new(&b"men's souls"[..]),
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let package = ExpiredCoresPackage::new(
             IpAddr::from_str("1.2.3.4").unwrap(),
-            Some(Wallet::new("consuming")),
+            Some(make_paying_wallet(b"consuming")),
             make_meaningless_route(),
             client_request_payload.into(),
             0,
@@ -1142,10 +1174,12 @@ mod tests {
             target_port: HTTP_PORT,
             protocol: ProxyProtocol::HTTP,
             originator_public_key: PublicKey::new(&b"men's souls"[..]),
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let package = ExpiredCoresPackage::new(
             IpAddr::from_str("1.2.3.4").unwrap(),
-            Some(Wallet::new("consuming")),
+            Some(make_paying_wallet(b"consuming")),
             make_meaningless_route(),
             client_request_payload.into(),
             0,
@@ -1213,10 +1247,12 @@ mod tests {
                 target_port: HTTP_PORT,
                 protocol: ProxyProtocol::HTTP,
                 originator_public_key: PublicKey::new(&b"men's souls"[..]),
+                compression: PayloadCompression::None,
+                redundant: false,
             };
             let package = ExpiredCoresPackage::new(
                 IpAddr::from_str("1.2.3.4").unwrap(),
-                Some(Wallet::new("consuming")),
+                Some(make_paying_wallet(b"consuming")),
                 make_meaningless_route(),
                 client_request_payload.into(),
                 0,
@@ -1,5 +1,6 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 use crate::proxy_server::protocol_pack::for_standard_port;
+use crate::sub_lib::compression::PayloadCompression;
 use crate::sub_lib::cryptde::CryptDE;
 use crate::sub_lib::cryptde::PlainData;
 use crate::sub_lib::dispatcher::InboundClientData;
@@ -75,6 +76,8 @@ impl ClientRequestPayloadFactory {
             target_port,
             protocol: protocol_pack.proxy_protocol(),
             originator_public_key: cryptde.public_key().clone(),
+            compression: PayloadCompression::None,
+            redundant: false,
         })
     }
 }
@@ -121,6 +124,8 @@ mod tests {
                 target_port: 2345,
                 protocol: ProxyProtocol::HTTP,
                 originator_public_key: cryptde.public_key().clone(),
+                compression: PayloadCompression::None,
+                redundant: false,
             })
         );
     }
@@ -155,6 +160,8 @@ mod tests {
                 target_port: HTTP_PORT,
                 protocol: ProxyProtocol::HTTP,
                 originator_public_key: cryptde.public_key().clone(),
+                compression: PayloadCompression::None,
+                redundant: false,
             })
         );
     }
@@ -209,6 +216,8 @@ mod tests {
                 target_port: 443,
                 protocol: ProxyProtocol::TLS,
                 originator_public_key: cryptde.public_key().clone(),
+                compression: PayloadCompression::None,
+                redundant: false,
             })
         );
     }
@@ -256,6 +265,8 @@ mod tests {
                 target_port: 443,
                 protocol: ProxyProtocol::TLS,
                 originator_public_key: cryptde.public_key().clone(),
+                compression: PayloadCompression::None,
+                redundant: false,
             })
         );
     }
@@ -35,9 +35,17 @@ pub fn for_standard_port(_standard_port: u16) -> Option<Box<ProtocolPack>> {
 
 pub trait ServerImpersonator {
     fn route_query_failure_response(&self, server_name: &str) -> Vec<u8>;
+    fn route_constraint_failure_response(&self, server_name: &str) -> Vec<u8>;
     fn dns_resolution_failure_response(
         &self,
         exit_key: &PublicKey,
         server_name_opt: Option<String>,
     ) -> Vec<u8>;
+    fn route_failure_response(
+        &self,
+        reporting_key: &PublicKey,
+        failed_key: &PublicKey,
+        reason: &str,
+        server_name_opt: Option<String>,
+    ) -> Vec<u8>;
 }
@@ -5,14 +5,18 @@ use crate::proxy_server::protocol_pack::{for_protocol, ProtocolPack};
 use crate::sub_lib::accountant::ReportExitServiceConsumedMessage;
 use crate::sub_lib::accountant::ReportRoutingServiceConsumedMessage;
 use crate::sub_lib::bidi_hashmap::BidiHashMap;
+use crate::sub_lib::compression;
+use crate::sub_lib::compression::PayloadCompression;
 use crate::sub_lib::cryptde::CryptDE;
 use crate::sub_lib::cryptde::PublicKey;
 use crate::sub_lib::dispatcher::Endpoint;
 use crate::sub_lib::dispatcher::InboundClientData;
-use crate::sub_lib::hopper::{ExpiredCoresPackage, IncipientCoresPackage};
+use crate::sub_lib::hopper::{ExpiredCoresPackage, IncipientCoresPackage, RouteFailure};
+use crate::sub_lib::logger::LogFields;
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::neighborhood::ExpectedServices;
 use crate::sub_lib::neighborhood::RatePack;
+use crate::sub_lib::neighborhood::RouteConstraints;
 use crate::sub_lib::neighborhood::RouteQueryMessage;
 use crate::sub_lib::neighborhood::RouteQueryResponse;
 use crate::sub_lib::neighborhood::{ExpectedService, NodeRecordMetadataMessage};
@@ -21,10 +25,13 @@ use crate::sub_lib::proxy_client::{ClientResponsePayload, DnsResolveFailure};
 use crate::sub_lib::proxy_server::AddReturnRouteMessage;
 use crate::sub_lib::proxy_server::ClientRequestPayload;
 use crate::sub_lib::proxy_server::ProxyServerSubs;
+use crate::sub_lib::proxy_server::SetRouteConstraintsMessage;
+use crate::sub_lib::proxy_server::{ActiveStreamsRequest, StreamInfo};
 use crate::sub_lib::route::Route;
 use crate::sub_lib::stream_handler_pool::TransmitDataMsg;
 use crate::sub_lib::stream_key::StreamKey;
 use crate::sub_lib::ttl_hashmap::TtlHashMap;
+use crate::sub_lib::ui_gateway::{UiCarrierMessage, UiEvent, UiEventMessage, UiMessage};
 use crate::sub_lib::utils::NODE_MAILBOX_CAPACITY;
 use crate::sub_lib::wallet::Wallet;
 use actix::Actor;
@@ -33,7 +40,11 @@ use actix::Context;
 use actix::Handler;
 use actix::MailboxError;
 use actix::Recipient;
+use futures::future::ok;
+use futures::future::Either;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::mem;
 use std::net::SocketAddr;
 use std::rc::Rc;
 use std::time::Duration;
@@ -50,11 +61,21 @@ pub struct ProxyServer {
     route_source: Option<Recipient<RouteQueryMessage>>,
     update_node_record_metadata: Option<Recipient<NodeRecordMetadataMessage>>,
     add_return_route: Option<Recipient<AddReturnRouteMessage>>,
+    ui_message_sub: Option<Recipient<UiCarrierMessage>>,
+    ui_event_sub: Option<Recipient<UiEventMessage>>,
     client_request_payload_factory: ClientRequestPayloadFactory,
     stream_key_factory: Box<dyn StreamKeyFactory>,
     keys_and_addrs: BidiHashMap<StreamKey, SocketAddr>,
     tunneled_hosts: HashMap<StreamKey, String>,
     is_decentralized: bool,
+    multipath: bool,
+    route_constraints: RouteConstraints,
+    // Sequence numbers of multipath responses whose redundant copy hasn't arrived (yet)
+    unmatched_response_sequence_numbers: HashMap<StreamKey, HashSet<u64>>,
+    // Streams that have had a redundant copy of a request sent over a second route
+    multipath_streams: HashSet<StreamKey>,
+    // Streams that have already survived the failure of one of their redundant routes
+    streams_with_failed_route: HashSet<StreamKey>,
     // TODO: This should be replaced by something more general and configurable.
     cryptde: &'static dyn CryptDE,
     logger: Logger,
@@ -79,6 +100,8 @@ impl Handler<BindMessage> for ProxyServer {
         self.update_node_record_metadata =
             Some(msg.peer_actors.neighborhood.update_node_record_metadata);
         self.add_return_route = Some(msg.peer_actors.proxy_server.add_return_route);
+        self.ui_message_sub = Some(msg.peer_actors.ui_gateway.ui_message_sub);
+        self.ui_event_sub = Some(msg.peer_actors.ui_gateway.event_sub);
     }
 }
 
@@ -99,11 +122,56 @@ impl Handler<AddReturnRouteMessage> for ProxyServer {
     type Result = ();
 
     fn handle(&mut self, msg: AddReturnRouteMessage, _ctx: &mut Self::Context) -> Self::Result {
+        if msg.redundant {
+            self.multipath_streams.insert(msg.stream_key);
+        }
         self.route_ids_to_return_routes
             .insert(msg.return_route_id, msg);
     }
 }
 
+impl Handler<SetRouteConstraintsMessage> for ProxyServer {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: SetRouteConstraintsMessage,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        self.logger.info(format!(
+            "Route constraints changed from {:?} to {:?}",
+            self.route_constraints, msg.constraints
+        ));
+        self.route_constraints = msg.constraints;
+    }
+}
+
+impl Handler<ActiveStreamsRequest> for ProxyServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: ActiveStreamsRequest, _ctx: &mut Self::Context) -> Self::Result {
+        let mut streams = self
+            .keys_and_addrs
+            .iter()
+            .map(|(stream_key, client_addr)| StreamInfo {
+                stream_key: format!("{:?}", stream_key),
+                client_addr: client_addr.to_string(),
+                tunneled_host: self.tunneled_hosts.get(stream_key).cloned(),
+            })
+            .collect::<Vec<StreamInfo>>();
+        streams.sort_by(|a, b| a.client_addr.cmp(&b.client_addr));
+        self.ui_message_sub
+            .as_ref()
+            .expect("UiGateway is unbound")
+            .try_send(UiCarrierMessage {
+                client_id: msg.client_id,
+                correlation_id: msg.correlation_id,
+                data: UiMessage::ActiveStreams(streams),
+            })
+            .expect("UiGateway is dead");
+    }
+}
+
 impl AddReturnRouteMessage {
     pub fn find_exit_node_key(&self) -> Option<&PublicKey> {
         self.expected_services
@@ -160,7 +228,11 @@ impl Handler<ExpiredCoresPackage<DnsResolveFailure>> for ProxyServer {
                     ))
                     .expect("Neighborhood is dead");
 
-                self.report_response_services_consumed(&return_route_info, 0, msg.payload_len);
+                self.report_response_services_consumed(
+                    &return_route_info,
+                    Some(0),
+                    msg.payload_len,
+                );
 
                 self.dispatcher
                     .as_ref()
@@ -178,6 +250,7 @@ impl Handler<ExpiredCoresPackage<DnsResolveFailure>> for ProxyServer {
                     })
                     .expect("Dispatcher is dead");
                 self.keys_and_addrs.remove_a(&response.stream_key);
+                self.stream_closed(&response.stream_key);
             }
             None => {
                 let server_name = match &return_route_info.server_name {
@@ -194,6 +267,97 @@ impl Handler<ExpiredCoresPackage<DnsResolveFailure>> for ProxyServer {
     }
 }
 
+impl Handler<ExpiredCoresPackage<RouteFailure>> for ProxyServer {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: ExpiredCoresPackage<RouteFailure>,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let return_route_info = match self.get_return_route_info(&msg.remaining_route) {
+            Some(rri) => rri,
+            None => return,
+        };
+        let route_failure = msg.payload;
+        // Relays that predate failure reasons don't send one
+        let reason = if route_failure.reason.is_empty() {
+            "no reason given"
+        } else {
+            route_failure.reason.as_str()
+        };
+        self.update_node_record_metadata
+            .as_ref()
+            .expect("Neighborhood is unbound in ProxyServer")
+            .try_send(NodeRecordMetadataMessage::Desirable(
+                route_failure.failed_key.clone(),
+                false,
+            ))
+            .expect("Neighborhood is dead");
+        ProxyServer::publish(
+            &self.ui_event_sub,
+            UiEvent::RouteFailure {
+                target: return_route_info
+                    .server_name
+                    .clone()
+                    .unwrap_or_else(|| "<unknown>".to_string()),
+                reason: format!(
+                    "Node {} couldn't relay request to {}: {}",
+                    route_failure.reporting_key, route_failure.failed_key, reason
+                ),
+            },
+        );
+        if self
+            .multipath_streams
+            .contains(&return_route_info.stream_key)
+            && self
+                .streams_with_failed_route
+                .insert(return_route_info.stream_key)
+        {
+            self.logger.warning(format!(
+                "Node {} couldn't relay request for stream {:?} to {}; continuing on the redundant route",
+                route_failure.reporting_key,
+                return_route_info.stream_key,
+                route_failure.failed_key
+            ));
+            return;
+        }
+        match self.keys_and_addrs.a_to_b(&return_route_info.stream_key) {
+            Some(socket_addr) => {
+                self.logger.warning(format!(
+                    "Node {} couldn't relay request for stream {:?} to {}",
+                    route_failure.reporting_key,
+                    return_route_info.stream_key,
+                    route_failure.failed_key
+                ));
+                self.dispatcher
+                    .as_ref()
+                    .expect("Dispatcher unbound in ProxyServer")
+                    .try_send(TransmitDataMsg {
+                        endpoint: Endpoint::Socket(socket_addr),
+                        last_data: true,
+                        sequence_number: Some(0),
+                        data: for_protocol(return_route_info.protocol)
+                            .server_impersonator()
+                            .route_failure_response(
+                                &route_failure.reporting_key,
+                                &route_failure.failed_key,
+                                reason,
+                                return_route_info.server_name.clone(),
+                            ),
+                    })
+                    .expect("Dispatcher is dead");
+                self.keys_and_addrs.remove_a(&return_route_info.stream_key);
+                self.stream_closed(&return_route_info.stream_key);
+            }
+            None => self.logger.error(format!(
+                "Discarding RouteFailure message from an unrecognized stream key {:?}",
+                return_route_info.stream_key
+            )),
+        }
+    }
+}
+
 impl Handler<ExpiredCoresPackage<ClientResponsePayload>> for ProxyServer {
     type Result = ();
 
@@ -203,7 +367,7 @@ impl Handler<ExpiredCoresPackage<ClientResponsePayload>> for ProxyServer {
         _ctx: &mut Self::Context,
     ) -> Self::Result {
         let payload_data_len = msg.payload_len;
-        let response = msg.payload;
+        let mut response = msg.payload;
         self.logger.debug(format!(
             "Relaying {}-byte ExpiredCoresPackage payload from Hopper to Dispatcher",
             response.sequenced_packet.data.len()
@@ -212,11 +376,47 @@ impl Handler<ExpiredCoresPackage<ClientResponsePayload>> for ProxyServer {
             Some(rri) => rri,
             None => return,
         };
+        if response.compression == PayloadCompression::Compressed {
+            match compression::decompress(
+                &response.sequenced_packet.data,
+                compression::MAX_DECOMPRESSED_LEN,
+            ) {
+                Ok(data) => response.sequenced_packet.data = data,
+                Err(e) => {
+                    self.logger.error(format!(
+                        "Discarding packet {} for stream {:?}: can't decompress response: {}",
+                        response.sequenced_packet.sequence_number, response.stream_key, e
+                    ));
+                    return;
+                }
+            }
+        }
         match self.keys_and_addrs.a_to_b(&response.stream_key) {
             Some(socket_addr) => {
+                if response.redundant
+                    && self.match_redundant_response(
+                        &response.stream_key,
+                        response.sequenced_packet.sequence_number,
+                    )
+                {
+                    // The exit Node was charged for this response when its first copy arrived.
+                    self.report_response_services_consumed(
+                        &return_route_info,
+                        None,
+                        payload_data_len,
+                    );
+                    self.logger.debug_with(
+                        LogFields::new().stream_key(&response.stream_key),
+                        format!(
+                            "Discarding redundant copy of packet {} for stream {:?}",
+                            response.sequenced_packet.sequence_number, response.stream_key
+                        ),
+                    );
+                    return;
+                }
                 self.report_response_services_consumed(
                     &return_route_info,
-                    response.sequenced_packet.data.len(),
+                    Some(response.sequenced_packet.data.len()),
                     payload_data_len,
                 );
 
@@ -237,20 +437,42 @@ impl Handler<ExpiredCoresPackage<ClientResponsePayload>> for ProxyServer {
                     .expect("Dispatcher is dead");
                 if last_data {
                     self.keys_and_addrs.remove_b(&socket_addr);
+                    self.stream_closed(&response.stream_key);
                 }
             }
-            None => self.logger.error(format!(
-                "Discarding {}-byte packet {} from an unrecognized stream key: {:?}",
-                response.sequenced_packet.data.len(),
-                response.sequenced_packet.sequence_number,
-                response.stream_key
-            )),
+            None if response.redundant => {
+                // The first copy closed the stream; the exit Node was charged for it then.
+                self.report_response_services_consumed(&return_route_info, None, payload_data_len);
+                self.logger.debug_with(
+                    LogFields::new().stream_key(&response.stream_key),
+                    format!(
+                        "Discarding redundant copy of packet {} for finished stream {:?}",
+                        response.sequenced_packet.sequence_number, response.stream_key
+                    ),
+                )
+            }
+            None => self.logger.error_with(
+                LogFields::new()
+                    .stream_key(&response.stream_key)
+                    .bytes(response.sequenced_packet.data.len()),
+                format!(
+                    "Discarding {}-byte packet {} from an unrecognized stream key: {:?}",
+                    response.sequenced_packet.data.len(),
+                    response.sequenced_packet.sequence_number,
+                    response.stream_key
+                ),
+            ),
         }
     }
 }
 
 impl ProxyServer {
-    pub fn new(cryptde: &'static dyn CryptDE, is_decentralized: bool) -> ProxyServer {
+    pub fn new(
+        cryptde: &'static dyn CryptDE,
+        is_decentralized: bool,
+        multipath: bool,
+        route_constraints: RouteConstraints,
+    ) -> ProxyServer {
         ProxyServer {
             dispatcher: None,
             hopper: None,
@@ -259,11 +481,18 @@ impl ProxyServer {
             route_source: None,
             update_node_record_metadata: None,
             add_return_route: None,
+            ui_message_sub: None,
+            ui_event_sub: None,
             client_request_payload_factory: ClientRequestPayloadFactory::new(),
             stream_key_factory: Box::new(StreamKeyFactoryReal {}),
             keys_and_addrs: BidiHashMap::new(),
             tunneled_hosts: HashMap::new(),
             is_decentralized,
+            multipath,
+            route_constraints,
+            unmatched_response_sequence_numbers: HashMap::new(),
+            multipath_streams: HashSet::new(),
+            streams_with_failed_route: HashSet::new(),
             cryptde,
             logger: Logger::new("Proxy Server"),
             route_ids_to_return_routes: TtlHashMap::new(RETURN_ROUTE_TTL),
@@ -281,7 +510,12 @@ impl ProxyServer {
             dns_failure_from_hopper: addr
                 .clone()
                 .recipient::<ExpiredCoresPackage<DnsResolveFailure>>(),
+            route_failure_from_hopper: addr
+                .clone()
+                .recipient::<ExpiredCoresPackage<RouteFailure>>(),
             add_return_route: addr.clone().recipient::<AddReturnRouteMessage>(),
+            set_route_constraints: addr.clone().recipient::<SetRouteConstraintsMessage>(),
+            active_streams_request: addr.clone().recipient::<ActiveStreamsRequest>(),
         }
     }
 
@@ -358,28 +592,125 @@ impl ProxyServer {
         };
         let logger = self.logger.clone();
         let minimum_hop_count = if self.is_decentralized { 3 } else { 0 };
+        let multipath = self.multipath;
+        // Zero-hop routes don't go through any other Nodes, so there's nothing to constrain.
+        let route_constraints = if self.is_decentralized {
+            self.route_constraints.clone()
+        } else {
+            RouteConstraints::default()
+        };
+        let route_constrained = !route_constraints.is_empty();
+        let ui_event_sub = self.ui_event_sub.clone();
         tokio::spawn(
             route_source
-                .send(RouteQueryMessage::data_indefinite_route_request(
+                .send(RouteQueryMessage::data_constrained_route_request(
                     minimum_hop_count,
+                    route_constraints.clone(),
                 ))
                 .then(move |route_result| {
-                    ProxyServer::try_transmit_to_hopper(
-                        cryptde,
-                        &hopper,
-                        route_result,
-                        payload,
-                        logger,
-                        source_addr,
-                        &dispatcher,
-                        &accountant_exit_sub,
-                        &accountant_routing_sub,
-                        &add_return_route_sub,
-                    )
+                    if let Ok(None) = route_result {
+                        ProxyServer::publish(
+                            &ui_event_sub,
+                            UiEvent::RouteFailure {
+                                target: ProxyServer::hostname(&payload),
+                                reason: "No route could be found".to_string(),
+                            },
+                        );
+                    }
+                    let redundant_query_opt = if multipath {
+                        ProxyServer::make_redundant_route_query(
+                            minimum_hop_count,
+                            &route_result,
+                            route_constraints,
+                        )
+                    } else {
+                        None
+                    };
+                    // The second route is found before anything is sent, so that the exit Node
+                    // is told to expect a redundant copy only when one is really coming.
+                    let redundant_route_future = match redundant_query_opt {
+                        Some(redundant_query) => Either::A(
+                            route_source
+                                .send(redundant_query)
+                                .then(|redundant_route_result| Ok(Some(redundant_route_result))),
+                        ),
+                        None => Either::B(ok(None)),
+                    };
+                    redundant_route_future.and_then(move |redundant_route_result_opt| {
+                        let redundant_route_opt = ProxyServer::redundant_route(
+                            redundant_route_result_opt,
+                            &payload,
+                            &logger,
+                        );
+                        let mut payload = payload;
+                        payload.redundant = redundant_route_opt.is_some();
+                        let redundant_payload = payload.clone();
+                        ProxyServer::try_transmit_to_hopper(
+                            cryptde,
+                            &hopper,
+                            route_result,
+                            payload,
+                            logger.clone(),
+                            source_addr,
+                            &dispatcher,
+                            &accountant_exit_sub,
+                            &accountant_routing_sub,
+                            &add_return_route_sub,
+                            route_constrained,
+                        )?;
+                        if let Some(redundant_route) = redundant_route_opt {
+                            ProxyServer::try_transmit_redundantly_to_hopper(
+                                cryptde,
+                                &hopper,
+                                redundant_route,
+                                redundant_payload,
+                                &logger,
+                                &accountant_routing_sub,
+                                &add_return_route_sub,
+                            );
+                        }
+                        Ok(())
+                    })
                 }),
         );
     }
 
+    // A second route to the exit Node of the first one, sharing none of its intermediate Nodes
+    fn make_redundant_route_query(
+        minimum_hop_count: usize,
+        route_result: &Result<Option<RouteQueryResponse>, MailboxError>,
+        route_constraints: RouteConstraints,
+    ) -> Option<RouteQueryMessage> {
+        let (over, back) = match route_result {
+            Ok(Some(RouteQueryResponse {
+                expected_services: ExpectedServices::RoundTrip(over, back, _),
+                ..
+            })) => (over, back),
+            _ => return None,
+        };
+        let exit_key = over.iter().find_map(|service| match service {
+            ExpectedService::Exit(public_key, _, _) => Some(public_key.clone()),
+            _ => None,
+        })?;
+        let mut routing_keys: Vec<PublicKey> = vec![];
+        over.iter()
+            .chain(back.iter())
+            .for_each(|service| match service {
+                ExpectedService::Routing(public_key, _, _)
+                    if !routing_keys.contains(public_key) =>
+                {
+                    routing_keys.push(public_key.clone())
+                }
+                _ => (),
+            });
+        Some(RouteQueryMessage::data_disjoint_route_request(
+            minimum_hop_count,
+            exit_key,
+            routing_keys,
+            route_constraints,
+        ))
+    }
+
     fn make_stream_key(&mut self, ibcd: &InboundClientData) -> StreamKey {
         match self.keys_and_addrs.b_to_a(&ibcd.peer_addr) {
             Some(stream_key) => stream_key,
@@ -388,6 +719,13 @@ impl ProxyServer {
                     .stream_key_factory
                     .make(&self.cryptde.public_key(), ibcd.peer_addr);
                 self.keys_and_addrs.insert(stream_key, ibcd.peer_addr);
+                ProxyServer::publish(
+                    &self.ui_event_sub,
+                    UiEvent::StreamOpened {
+                        stream_key: format!("{:?}", stream_key),
+                        client_addr: ibcd.peer_addr.to_string(),
+                    },
+                );
                 stream_key
             }
         }
@@ -435,6 +773,7 @@ impl ProxyServer {
         accountant_exit_sub: &Recipient<ReportExitServiceConsumedMessage>,
         accountant_routing_sub: &Recipient<ReportRoutingServiceConsumedMessage>,
         add_return_route_sub: &Recipient<AddReturnRouteMessage>,
+        route_constrained: bool,
     ) -> Result<(), ()> {
         match route_result {
             Ok(Some(route_query_response)) => match route_query_response.expected_services {
@@ -444,6 +783,8 @@ impl ProxyServer {
                         expected_services: back.clone(),
                         protocol: payload.protocol,
                         server_name: payload.target_hostname.clone(),
+                        stream_key: payload.stream_key,
+                        redundant: false,
                     };
                     logger.debug(format!(
                         "Adding expectant return route info: {:?}",
@@ -461,7 +802,10 @@ impl ProxyServer {
                     ProxyServer::transmit_to_hopper(
                         cryptde,
                         hopper,
-                        payload,
+                        ProxyServer::compress_payload(
+                            payload,
+                            route_query_response.exit_supports_compression,
+                        ),
                         &route_query_response.route,
                         over.clone(),
                         &logger,
@@ -473,7 +817,13 @@ impl ProxyServer {
                 _ => panic!("Expected RoundTrip ExpectedServices but got OneWay"),
             },
             Ok(None) => {
-                ProxyServer::handle_route_failure(payload, &logger, source_addr, dispatcher);
+                ProxyServer::handle_route_failure(
+                    payload,
+                    &logger,
+                    source_addr,
+                    dispatcher,
+                    route_constrained,
+                );
             }
             Err(e) => {
                 let msg = format!("Neighborhood refused to answer route request: {}", e);
@@ -483,6 +833,103 @@ impl ProxyServer {
         Ok(())
     }
 
+    // None if no redundant route was asked for, or none could be had.
+    fn redundant_route(
+        route_result_opt: Option<Result<Option<RouteQueryResponse>, MailboxError>>,
+        payload: &ClientRequestPayload,
+        logger: &Logger,
+    ) -> Option<RouteQueryResponse> {
+        match route_result_opt {
+            None => None,
+            Some(Ok(Some(route_query_response))) => Some(route_query_response),
+            Some(Ok(None)) => {
+                logger.warning(format!(
+                    "No disjoint route available for packet {} of stream {:?}; sending it over one route only",
+                    payload.sequenced_packet.sequence_number, payload.stream_key
+                ));
+                None
+            }
+            Some(Err(e)) => {
+                logger.error(format!(
+                    "Neighborhood refused to answer redundant route request: {}",
+                    e
+                ));
+                None
+            }
+        }
+    }
+
+    fn try_transmit_redundantly_to_hopper(
+        cryptde: &'static dyn CryptDE,
+        hopper: &Recipient<IncipientCoresPackage>,
+        route_query_response: RouteQueryResponse,
+        payload: ClientRequestPayload,
+        logger: &Logger,
+        accountant_routing_sub: &Recipient<ReportRoutingServiceConsumedMessage>,
+        add_return_route_sub: &Recipient<AddReturnRouteMessage>,
+    ) {
+        match route_query_response.expected_services {
+            ExpectedServices::RoundTrip(over, back, return_route_id) => {
+                let exit_key = match over.iter().find_map(|service| match service {
+                    ExpectedService::Exit(public_key, _, _) => Some(public_key.clone()),
+                    _ => None,
+                }) {
+                    Some(exit_key) => exit_key,
+                    None => {
+                        logger.error(format!(
+                            "Redundant route for packet {} has no exit Node; not sending",
+                            payload.sequenced_packet.sequence_number
+                        ));
+                        return;
+                    }
+                };
+                add_return_route_sub
+                    .try_send(AddReturnRouteMessage {
+                        return_route_id,
+                        expected_services: back,
+                        protocol: payload.protocol,
+                        server_name: payload.target_hostname.clone(),
+                        stream_key: payload.stream_key,
+                        redundant: true,
+                    })
+                    .expect("ProxyServer is dead");
+                // The exit Node serves the request only once, so only the routing is charged here.
+                let payload = ProxyServer::compress_payload(
+                    payload,
+                    route_query_response.exit_supports_compression,
+                );
+                let pkg = IncipientCoresPackage::new(
+                    cryptde,
+                    route_query_response.route,
+                    payload.into(),
+                    &exit_key,
+                )
+                .expect("Key magically disappeared");
+                ProxyServer::report_routing_service(
+                    accountant_routing_sub,
+                    over,
+                    pkg.payload.len(),
+                    logger,
+                );
+                hopper.try_send(pkg).expect("Hopper is dead");
+            }
+            _ => panic!("Expected RoundTrip ExpectedServices but got OneWay"),
+        }
+    }
+
+    fn compress_payload(
+        mut payload: ClientRequestPayload,
+        exit_supports_compression: bool,
+    ) -> ClientRequestPayload {
+        if exit_supports_compression {
+            let data = mem::replace(&mut payload.sequenced_packet.data, vec![]);
+            let (data, compression) = compression::compress_if_smaller(data);
+            payload.sequenced_packet.data = data;
+            payload.compression = compression;
+        }
+        payload
+    }
+
     fn report_routing_service(
         accountant_routing_sub: &Recipient<ReportRoutingServiceConsumedMessage>,
         expected_services: Vec<ExpectedService>,
@@ -573,7 +1020,9 @@ impl ProxyServer {
         };
 
         match destination_key_opt {
-            None => ProxyServer::handle_route_failure(payload, &logger, source_addr, dispatcher),
+            None => {
+                ProxyServer::handle_route_failure(payload, &logger, source_addr, dispatcher, false)
+            }
             Some(payload_destination_key) => {
                 logger.debug(format!(
                     "transmit to hopper with destination key {:?}",
@@ -602,20 +1051,33 @@ impl ProxyServer {
         logger: &Logger,
         source_addr: SocketAddr,
         dispatcher: &Recipient<TransmitDataMsg>,
+        route_constrained: bool,
     ) {
         let target_hostname = ProxyServer::hostname(&payload);
-        ProxyServer::send_route_failure(payload, source_addr, dispatcher);
-        logger.error(format!("Failed to find route to {}", target_hostname));
+        ProxyServer::send_route_failure(payload, source_addr, dispatcher, route_constrained);
+        if route_constrained {
+            logger.error(format!(
+                "Failed to find route to {} within route constraints",
+                target_hostname
+            ));
+        } else {
+            logger.error(format!("Failed to find route to {}", target_hostname));
+        }
     }
 
     fn send_route_failure(
         payload: ClientRequestPayload,
         source_addr: SocketAddr,
         dispatcher: &Recipient<TransmitDataMsg>,
+        route_constrained: bool,
     ) {
-        let data = for_protocol(payload.protocol)
-            .server_impersonator()
-            .route_query_failure_response(&ProxyServer::hostname(&payload));
+        let server_impersonator = for_protocol(payload.protocol).server_impersonator();
+        let hostname = ProxyServer::hostname(&payload);
+        let data = if route_constrained {
+            server_impersonator.route_constraint_failure_response(&hostname)
+        } else {
+            server_impersonator.route_query_failure_response(&hostname)
+        };
         let msg = TransmitDataMsg {
             endpoint: Endpoint::Socket(source_addr),
             last_data: true,
@@ -625,6 +1087,30 @@ impl ProxyServer {
         dispatcher.try_send(msg).expect("Dispatcher is dead");
     }
 
+    // Forgets everything kept about a stream's redundant route along with the stream itself.
+    fn stream_closed(&mut self, stream_key: &StreamKey) {
+        self.unmatched_response_sequence_numbers.remove(stream_key);
+        self.multipath_streams.remove(stream_key);
+        self.streams_with_failed_route.remove(stream_key);
+        self.publish_stream_closed(stream_key);
+    }
+
+    fn publish_stream_closed(&self, stream_key: &StreamKey) {
+        ProxyServer::publish(
+            &self.ui_event_sub,
+            UiEvent::StreamClosed {
+                stream_key: format!("{:?}", stream_key),
+            },
+        );
+    }
+
+    // Events are a courtesy to watching UIs; a busy or missing UiGateway mustn't stop traffic.
+    fn publish(ui_event_sub: &Option<Recipient<UiEventMessage>>, event: UiEvent) {
+        if let Some(ui_event_sub) = ui_event_sub {
+            let _ = ui_event_sub.try_send(UiEventMessage { event });
+        }
+    }
+
     fn hostname(payload: &ClientRequestPayload) -> String {
         match payload.target_hostname {
             Some(ref thn) => thn.clone(),
@@ -654,18 +1140,37 @@ impl ProxyServer {
         }
     }
 
+    // Returns true if a copy of this response has already arrived over another route.
+    fn match_redundant_response(&mut self, stream_key: &StreamKey, sequence_number: u64) -> bool {
+        let sequence_numbers = self
+            .unmatched_response_sequence_numbers
+            .entry(*stream_key)
+            .or_insert_with(HashSet::new);
+        if sequence_numbers.remove(&sequence_number) {
+            if sequence_numbers.is_empty() {
+                self.unmatched_response_sequence_numbers.remove(stream_key);
+            }
+            true
+        } else {
+            sequence_numbers.insert(sequence_number);
+            false
+        }
+    }
+
+    // exit_size_opt is None when the exit Node has already been charged for this response.
     fn report_response_services_consumed(
         &self,
         return_route_info: &AddReturnRouteMessage,
-        exit_size: usize,
+        exit_size_opt: Option<usize>,
         routing_size: usize,
     ) {
         return_route_info
             .expected_services
             .iter()
-            .for_each(|service| match service {
-                ExpectedService::Nothing => (),
-                ExpectedService::Exit(_, wallet, rate_pack) => self
+            .for_each(|service| match (service, exit_size_opt) {
+                (ExpectedService::Nothing, _) => (),
+                (ExpectedService::Exit(_, _, _), None) => (),
+                (ExpectedService::Exit(_, wallet, rate_pack), Some(exit_size)) => self
                     .accountant_exit
                     .as_ref()
                     .expect("ProxyServer unbound")
@@ -676,7 +1181,7 @@ impl ProxyServer {
                         byte_rate: rate_pack.exit_byte_rate,
                     })
                     .expect("Accountant is dead"),
-                ExpectedService::Routing(_, wallet, rate_pack) => self
+                (ExpectedService::Routing(_, wallet, rate_pack), _) => self
                     .accountant_routing
                     .as_ref()
                     .expect("ProxyServer unbound")
@@ -717,6 +1222,8 @@ mod tests {
     use crate::sub_lib::dispatcher::Component;
     use crate::sub_lib::hop::LiveHop;
     use crate::sub_lib::hopper::MessageType;
+    use crate::sub_lib::hopper::RouteFailure;
+    use crate::sub_lib::ip_range::IpRange;
     use crate::sub_lib::neighborhood::ExpectedService;
     use crate::sub_lib::neighborhood::ExpectedServices;
     use crate::sub_lib::neighborhood::RatePack;
@@ -737,6 +1244,7 @@ mod tests {
     use crate::test_utils::test_utils::cryptde;
     use crate::test_utils::test_utils::make_meaningless_route;
     use crate::test_utils::test_utils::make_meaningless_stream_key;
+    use crate::test_utils::test_utils::make_paying_wallet;
     use crate::test_utils::test_utils::rate_pack;
     use crate::test_utils::test_utils::rate_pack_exit;
     use crate::test_utils::test_utils::rate_pack_exit_byte;
@@ -810,8 +1318,9 @@ mod tests {
             &cryptde.public_key(),
             &LiveHop {
                 public_key: cryptde.public_key().clone(),
-                consuming_wallet: None,
+                payer: None,
                 component: Component::ProxyServer,
+                failure_return: None,
             },
         )
         .unwrap()
@@ -888,6 +1397,8 @@ mod tests {
             target_port: HTTP_PORT,
             protocol: ProxyProtocol::HTTP,
             originator_public_key: key.clone(),
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let expected_pkg =
             IncipientCoresPackage::new(cryptde, route.clone(), expected_payload.into(), &key)
@@ -899,7 +1410,7 @@ mod tests {
                 .make_parameters(&make_parameters_arc)
                 .make_result(stream_key);
             let system = System::new("proxy_server_receives_http_request_from_dispatcher_then_sends_cores_package_to_hopper");
-            let mut subject = ProxyServer::new(cryptde, false);
+            let mut subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
             subject.stream_key_factory = Box::new(stream_key_factory);
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
@@ -980,6 +1491,8 @@ mod tests {
             target_port: 443,
             protocol: ProxyProtocol::TLS,
             originator_public_key: key.clone(),
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let expected_pkg =
             IncipientCoresPackage::new(cryptde, route.clone(), expected_payload.into(), &key)
@@ -995,7 +1508,7 @@ mod tests {
             let system = System::new(
                 "proxy_server_receives_connect_responds_with_ok_and_stores_stream_key_and_hostname",
             );
-            let mut subject = ProxyServer::new(cryptde, false);
+            let mut subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
             subject.stream_key_factory = Box::new(stream_key_factory);
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
@@ -1040,7 +1553,7 @@ mod tests {
         let system = System::new("handle_client_response_payload_increments_sequence_number_when_browser_proxy_sequence_offset_is_true");
         let (dispatcher_mock, _, dispatcher_log_arc) = make_recorder();
         let cryptde = cryptde();
-        let mut subject = ProxyServer::new(cryptde, false);
+        let mut subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let stream_key = make_meaningless_stream_key();
         subject
@@ -1054,6 +1567,8 @@ mod tests {
                 expected_services: vec![ExpectedService::Nothing],
                 protocol: ProxyProtocol::TLS,
                 server_name: None,
+                stream_key,
+                redundant: false,
             },
         );
         let subject_addr: Addr<ProxyServer> = subject.start();
@@ -1075,12 +1590,14 @@ mod tests {
                 sequence_number: 0,
                 last_data: false,
             },
+            compression: PayloadCompression::None,
+            redundant: false,
         };
 
         let expired_cores_package: ExpiredCoresPackage<ClientResponsePayload> =
             ExpiredCoresPackage::new(
                 IpAddr::from_str("1.2.3.4").unwrap(),
-                Some(Wallet::new("irrelevant")),
+                Some(make_paying_wallet(b"irrelevant")),
                 return_route_with_id(cryptde, 1234),
                 client_response_payload.into(),
                 0,
@@ -1139,7 +1656,7 @@ mod tests {
             let system = System::new(
                 "proxy_server_receives_connect_responds_with_ok_and_stores_stream_key_and_hostname",
             );
-            let mut subject = ProxyServer::new(cryptde, false);
+            let mut subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
             subject.stream_key_factory = Box::new(stream_key_factory);
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
@@ -1204,7 +1721,7 @@ mod tests {
             let system = System::new(
                 "proxy_server_receives_connect_responds_with_ok_and_stores_stream_key_and_hostname",
             );
-            let mut subject = ProxyServer::new(cryptde, false);
+            let mut subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
             subject.stream_key_factory = Box::new(stream_key_factory);
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
@@ -1270,6 +1787,8 @@ mod tests {
             target_port: HTTP_PORT,
             protocol: ProxyProtocol::HTTP,
             originator_public_key: key.clone(),
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let expected_pkg =
             IncipientCoresPackage::new(cryptde, route.clone(), expected_payload.into(), &key)
@@ -1277,7 +1796,7 @@ mod tests {
         thread::spawn(move || {
             let stream_key_factory = StreamKeyFactoryMock::new(); // can't make any stream keys; shouldn't have to
             let system = System::new("proxy_server_receives_http_request_from_dispatcher_then_sends_cores_package_to_hopper");
-            let mut subject = ProxyServer::new(cryptde, false);
+            let mut subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
             subject.stream_key_factory = Box::new(stream_key_factory);
             subject.keys_and_addrs.insert(stream_key, socket_addr);
             let subject_addr: Addr<ProxyServer> = subject.start();
@@ -1303,7 +1822,7 @@ mod tests {
     fn proxy_server_receives_http_request_from_dispatcher_then_sends_multihop_cores_package_to_hopper(
     ) {
         let cryptde = cryptde();
-        let consuming_wallet = Wallet::new("consuming wallet");
+        let consuming_wallet = make_paying_wallet(b"consuming wallet");
         let earning_wallet = Wallet::new("earning wallet");
         let http_request = b"GET /index.html HTTP/1.1\r\nHost: nowhere.com\r\n\r\n";
         let hopper_mock = Recorder::new();
@@ -1352,6 +1871,7 @@ mod tests {
                 ],
                 1234,
             ),
+            exit_supports_compression: false,
         }));
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let stream_key = make_meaningless_stream_key();
@@ -1377,6 +1897,8 @@ mod tests {
             target_port: HTTP_PORT,
             protocol: ProxyProtocol::HTTP,
             originator_public_key: key.clone(),
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let expected_pkg = IncipientCoresPackage::new(
             cryptde,
@@ -1388,7 +1910,7 @@ mod tests {
         thread::spawn(move || {
             let stream_key_factory = StreamKeyFactoryMock::new().make_result(stream_key);
             let system = System::new("proxy_server_receives_http_request_from_dispatcher_then_sends_cores_package_to_hopper");
-            let mut subject = ProxyServer::new(cryptde, true);
+            let mut subject = ProxyServer::new(cryptde, true, false, RouteConstraints::default());
             subject.stream_key_factory = Box::new(stream_key_factory);
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
@@ -1412,6 +1934,108 @@ mod tests {
         assert_eq!(record, &RouteQueryMessage::data_indefinite_route_request(3));
     }
 
+    #[test]
+    fn proxy_server_compresses_request_data_for_exit_that_supports_compression() {
+        let cryptde = cryptde();
+        let http_request = b"GET /index.html HTTP/1.1\r\nHost: nowhere.com\r\nAccept: text/html\r\nAccept-Language: en-US\r\n\r\n";
+        let hopper_mock = Recorder::new();
+        let hopper_log_arc = hopper_mock.get_recording();
+        let hopper_awaiter = hopper_mock.get_awaiter();
+        let payload_destination_key = PublicKey::new(&[3]);
+        let route = Route::round_trip(
+            RouteSegment::new(
+                vec![&cryptde.public_key(), &payload_destination_key],
+                Component::ProxyClient,
+            ),
+            RouteSegment::new(
+                vec![&payload_destination_key, &cryptde.public_key()],
+                Component::ProxyServer,
+            ),
+            cryptde,
+            Some(make_paying_wallet(b"consuming wallet")),
+            1234,
+        )
+        .unwrap();
+        let earning_wallet = Wallet::new("earning wallet");
+        let neighborhood_mock = Recorder::new().route_query_response(Some(RouteQueryResponse {
+            route: route.clone(),
+            expected_services: ExpectedServices::RoundTrip(
+                vec![
+                    ExpectedService::Nothing,
+                    ExpectedService::Exit(
+                        payload_destination_key.clone(),
+                        earning_wallet.clone(),
+                        rate_pack(101),
+                    ),
+                ],
+                vec![
+                    ExpectedService::Exit(
+                        payload_destination_key.clone(),
+                        earning_wallet,
+                        rate_pack(102),
+                    ),
+                    ExpectedService::Nothing,
+                ],
+                1234,
+            ),
+            exit_supports_compression: true,
+        }));
+        let stream_key = make_meaningless_stream_key();
+        let msg_from_dispatcher = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            reception_port: Some(HTTP_PORT),
+            sequence_number: Some(0),
+            last_data: true,
+            is_clandestine: false,
+            data: http_request.to_vec(),
+        };
+        let expected_payload = ClientRequestPayload {
+            stream_key: stream_key.clone(),
+            sequenced_packet: SequencedPacket {
+                data: compression::compress(http_request),
+                sequence_number: 0,
+                last_data: true,
+            },
+            target_hostname: Some(String::from("nowhere.com")),
+            target_port: HTTP_PORT,
+            protocol: ProxyProtocol::HTTP,
+            originator_public_key: cryptde.public_key().clone(),
+            compression: PayloadCompression::Compressed,
+            redundant: false,
+        };
+        let expected_pkg = IncipientCoresPackage::new(
+            cryptde,
+            route,
+            expected_payload.into(),
+            &payload_destination_key,
+        )
+        .unwrap();
+        thread::spawn(move || {
+            let stream_key_factory = StreamKeyFactoryMock::new().make_result(stream_key);
+            let system = System::new(
+                "proxy_server_compresses_request_data_for_exit_that_supports_compression",
+            );
+            let mut subject = ProxyServer::new(cryptde, true, false, RouteConstraints::default());
+            subject.stream_key_factory = Box::new(stream_key_factory);
+            let subject_addr: Addr<ProxyServer> = subject.start();
+            let mut peer_actors = peer_actors_builder()
+                .hopper(hopper_mock)
+                .neighborhood(neighborhood_mock)
+                .build();
+            peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            subject_addr.try_send(msg_from_dispatcher).unwrap();
+
+            system.run();
+        });
+
+        hopper_awaiter.await_message_count(1);
+        let recording = hopper_log_arc.lock().unwrap();
+        let record = recording.get_record::<IncipientCoresPackage>(0);
+        assert_eq!(record, &expected_pkg);
+    }
+
     #[test]
     fn proxy_server_sends_message_to_accountant_for_request_routing_service_consumed() {
         let cryptde = cryptde();
@@ -1462,6 +2086,7 @@ mod tests {
                 ],
                 0,
             ),
+            exit_supports_compression: false,
         }));
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let stream_key = make_meaningless_stream_key();
@@ -1479,7 +2104,7 @@ mod tests {
             let system = System::new(
                 "proxy_server_sends_message_to_accountant_for_routing_service_consumed",
             );
-            let mut subject = ProxyServer::new(cryptde, true);
+            let mut subject = ProxyServer::new(cryptde, true, false, RouteConstraints::default());
             subject.stream_key_factory = Box::new(stream_key_factory);
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
@@ -1500,6 +2125,8 @@ mod tests {
             target_port: HTTP_PORT,
             protocol: ProxyProtocol::HTTP,
             originator_public_key: exit_key,
+            compression: PayloadCompression::None,
+            redundant: false,
         });
         let payload_ser = PlainData::from(serde_cbor::ser::to_vec(&payload).unwrap());
         let payload_enc = cryptde.encode(&cryptde.public_key(), &payload_ser).unwrap();
@@ -1529,12 +2156,241 @@ mod tests {
     }
 
     #[test]
-    fn proxy_server_logs_messages_when_routing_services_are_not_requested() {
-        init_test_logging();
+    fn multipath_proxy_server_sends_request_over_disjoint_route_and_charges_exit_once() {
         let cryptde = cryptde();
+        let exit_wallet = Wallet::new("exit wallet");
+        let route_1_wallet = Wallet::new("route 1 wallet");
+        let route_2_wallet = Wallet::new("route 2 wallet");
         let http_request = b"GET /index.html HTTP/1.1\r\nHost: nowhere.com\r\n\r\n";
-        let (accountant_mock, _, accountant_log_arc) = make_recorder();
-        let (neighborhood_mock, _, _) = make_recorder();
+        let (accountant_mock, accountant_awaiter, accountant_log_arc) = make_recorder();
+        let (hopper_mock, hopper_awaiter, hopper_log_arc) = make_recorder();
+        let (neighborhood_mock, _, neighborhood_log_arc) = make_recorder();
+        let exit_key = PublicKey::new(&[3]);
+        let make_response =
+            |routing_key: PublicKey, wallet: &Wallet, return_route_id: u32| RouteQueryResponse {
+                route: make_meaningless_route(),
+                expected_services: ExpectedServices::RoundTrip(
+                    vec![
+                        ExpectedService::Nothing,
+                        ExpectedService::Routing(
+                            routing_key.clone(),
+                            wallet.clone(),
+                            rate_pack(101),
+                        ),
+                        ExpectedService::Exit(
+                            exit_key.clone(),
+                            exit_wallet.clone(),
+                            rate_pack(103),
+                        ),
+                    ],
+                    vec![
+                        ExpectedService::Exit(
+                            exit_key.clone(),
+                            exit_wallet.clone(),
+                            rate_pack(103),
+                        ),
+                        ExpectedService::Routing(routing_key, wallet.clone(), rate_pack(101)),
+                        ExpectedService::Nothing,
+                    ],
+                    return_route_id,
+                ),
+                exit_supports_compression: false,
+            };
+        let neighborhood_mock = neighborhood_mock
+            .route_query_response(Some(make_response(
+                PublicKey::new(&[1]),
+                &route_1_wallet,
+                1,
+            )))
+            .route_query_response(Some(make_response(
+                PublicKey::new(&[2]),
+                &route_2_wallet,
+                2,
+            )));
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let stream_key = make_meaningless_stream_key();
+        let expected_data = http_request.to_vec();
+        let msg_from_dispatcher = InboundClientData {
+            peer_addr: socket_addr.clone(),
+            reception_port: Some(HTTP_PORT),
+            sequence_number: Some(0),
+            last_data: true,
+            is_clandestine: false,
+            data: expected_data.clone(),
+        };
+        thread::spawn(move || {
+            let stream_key_factory = StreamKeyFactoryMock::new().make_result(stream_key);
+            let system = System::new(
+                "multipath_proxy_server_sends_request_over_disjoint_route_and_charges_exit_once",
+            );
+            let mut subject = ProxyServer::new(cryptde, true, true, RouteConstraints::default());
+            subject.stream_key_factory = Box::new(stream_key_factory);
+            let subject_addr: Addr<ProxyServer> = subject.start();
+            let mut peer_actors = peer_actors_builder()
+                .accountant(accountant_mock)
+                .hopper(hopper_mock)
+                .neighborhood(neighborhood_mock)
+                .build();
+            peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+            subject_addr.try_send(msg_from_dispatcher).unwrap();
+            system.run();
+        });
+
+        hopper_awaiter.await_message_count(2);
+        accountant_awaiter.await_message_count(3);
+        let payload = MessageType::ClientRequest(ClientRequestPayload {
+            stream_key,
+            sequenced_packet: SequencedPacket::new(expected_data, 0, true),
+            target_hostname: Some("nowhere.com".to_string()),
+            target_port: HTTP_PORT,
+            protocol: ProxyProtocol::HTTP,
+            originator_public_key: cryptde.public_key().clone(),
+            compression: PayloadCompression::None,
+            redundant: true,
+        });
+        let expected_pkg =
+            IncipientCoresPackage::new(cryptde, make_meaningless_route(), payload, &exit_key)
+                .unwrap();
+        let hopper_recording = hopper_log_arc.lock().unwrap();
+        assert_eq!(
+            hopper_recording.get_record::<IncipientCoresPackage>(0),
+            &expected_pkg
+        );
+        assert_eq!(
+            hopper_recording.get_record::<IncipientCoresPackage>(1),
+            &expected_pkg
+        );
+        let neighborhood_recording = neighborhood_log_arc.lock().unwrap();
+        assert_eq!(
+            neighborhood_recording.get_record::<RouteQueryMessage>(1),
+            &RouteQueryMessage::data_disjoint_route_request(
+                3,
+                exit_key,
+                vec![PublicKey::new(&[1])],
+                RouteConstraints::default(),
+            )
+        );
+        let accountant_recording = accountant_log_arc.lock().unwrap();
+        let payload_size = expected_pkg.payload.len();
+        assert_eq!(
+            accountant_recording.get_record::<ReportExitServiceConsumedMessage>(0),
+            &ReportExitServiceConsumedMessage {
+                earning_wallet: exit_wallet,
+                payload_size: http_request.len(),
+                service_rate: rate_pack_exit(103),
+                byte_rate: rate_pack_exit_byte(103),
+            }
+        );
+        check_routing_report(
+            &accountant_recording,
+            1,
+            &route_1_wallet,
+            payload_size,
+            &rate_pack(101),
+        );
+        check_routing_report(
+            &accountant_recording,
+            2,
+            &route_2_wallet,
+            payload_size,
+            &rate_pack(101),
+        );
+        assert_eq!(accountant_recording.len(), 3);
+    }
+
+    #[test]
+    fn multipath_proxy_server_does_not_mark_request_redundant_when_no_disjoint_route_is_found() {
+        let cryptde = cryptde();
+        let http_request = b"GET /index.html HTTP/1.1\r\nHost: nowhere.com\r\n\r\n";
+        let (hopper_mock, hopper_awaiter, hopper_log_arc) = make_recorder();
+        let (neighborhood_mock, neighborhood_awaiter, _) = make_recorder();
+        let exit_key = PublicKey::new(&[3]);
+        let exit_wallet = Wallet::new("exit wallet");
+        let neighborhood_mock = neighborhood_mock
+            .route_query_response(Some(RouteQueryResponse {
+                route: make_meaningless_route(),
+                expected_services: ExpectedServices::RoundTrip(
+                    vec![
+                        ExpectedService::Nothing,
+                        ExpectedService::Routing(
+                            PublicKey::new(&[1]),
+                            Wallet::new("route wallet"),
+                            rate_pack(101),
+                        ),
+                        ExpectedService::Exit(
+                            exit_key.clone(),
+                            exit_wallet.clone(),
+                            rate_pack(103),
+                        ),
+                    ],
+                    vec![ExpectedService::Exit(
+                        exit_key.clone(),
+                        exit_wallet,
+                        rate_pack(103),
+                    )],
+                    1,
+                ),
+                exit_supports_compression: false,
+            }))
+            .route_query_response(None);
+        let stream_key = make_meaningless_stream_key();
+        let msg_from_dispatcher = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            reception_port: Some(HTTP_PORT),
+            sequence_number: Some(0),
+            last_data: true,
+            is_clandestine: false,
+            data: http_request.to_vec(),
+        };
+        thread::spawn(move || {
+            let stream_key_factory = StreamKeyFactoryMock::new().make_result(stream_key);
+            let system = System::new(
+                "multipath_proxy_server_does_not_mark_request_redundant_when_no_disjoint_route_is_found",
+            );
+            let mut subject = ProxyServer::new(cryptde, true, true, RouteConstraints::default());
+            subject.stream_key_factory = Box::new(stream_key_factory);
+            let subject_addr: Addr<ProxyServer> = subject.start();
+            let mut peer_actors = peer_actors_builder()
+                .hopper(hopper_mock)
+                .neighborhood(neighborhood_mock)
+                .build();
+            peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+            subject_addr.try_send(msg_from_dispatcher).unwrap();
+            system.run();
+        });
+
+        neighborhood_awaiter.await_message_count(2);
+        hopper_awaiter.await_message_count(1);
+        let payload = MessageType::ClientRequest(ClientRequestPayload {
+            stream_key,
+            sequenced_packet: SequencedPacket::new(http_request.to_vec(), 0, true),
+            target_hostname: Some("nowhere.com".to_string()),
+            target_port: HTTP_PORT,
+            protocol: ProxyProtocol::HTTP,
+            originator_public_key: cryptde.public_key().clone(),
+            compression: PayloadCompression::None,
+            redundant: false,
+        });
+        let expected_pkg =
+            IncipientCoresPackage::new(cryptde, make_meaningless_route(), payload, &exit_key)
+                .unwrap();
+        let hopper_recording = hopper_log_arc.lock().unwrap();
+        assert_eq!(
+            hopper_recording.get_record::<IncipientCoresPackage>(0),
+            &expected_pkg
+        );
+        assert_eq!(hopper_recording.len(), 1);
+    }
+
+    #[test]
+    fn proxy_server_logs_messages_when_routing_services_are_not_requested() {
+        init_test_logging();
+        let cryptde = cryptde();
+        let http_request = b"GET /index.html HTTP/1.1\r\nHost: nowhere.com\r\n\r\n";
+        let (accountant_mock, _, accountant_log_arc) = make_recorder();
+        let (neighborhood_mock, _, _) = make_recorder();
         let zero_hop_route_reponse = zero_hop_route_response(&cryptde.public_key(), cryptde);
         let neighborhood_mock =
             neighborhood_mock.route_query_response(Some(zero_hop_route_reponse.clone()));
@@ -1553,7 +2409,7 @@ mod tests {
             let stream_key_factory = StreamKeyFactoryMock::new().make_result(stream_key);
             let system =
                 System::new("proxy_server_logs_messages_when_routing_services_are_not_requested");
-            let mut subject = ProxyServer::new(cryptde, true);
+            let mut subject = ProxyServer::new(cryptde, true, false, RouteConstraints::default());
             subject.stream_key_factory = Box::new(stream_key_factory);
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
@@ -1600,6 +2456,7 @@ mod tests {
                 ],
                 0,
             ),
+            exit_supports_compression: false,
         }));
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let stream_key = make_meaningless_stream_key();
@@ -1616,7 +2473,7 @@ mod tests {
             let stream_key_factory = StreamKeyFactoryMock::new().make_result(stream_key);
             let system =
                 System::new("proxy_server_sends_message_to_accountant_for_exit_service_consumed");
-            let mut subject = ProxyServer::new(cryptde, true);
+            let mut subject = ProxyServer::new(cryptde, true, false, RouteConstraints::default());
             subject.stream_key_factory = Box::new(stream_key_factory);
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
@@ -1668,7 +2525,7 @@ mod tests {
             let stream_key_factory = StreamKeyFactoryMock::new().make_result(stream_key);
             let system =
                 System::new("proxy_server_logs_message_when_exit_services_are_not_consumed");
-            let mut subject = ProxyServer::new(cryptde, true);
+            let mut subject = ProxyServer::new(cryptde, true, false, RouteConstraints::default());
             subject.stream_key_factory = Box::new(stream_key_factory);
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
@@ -1709,7 +2566,7 @@ mod tests {
         };
         thread::spawn(move || {
             let system = System::new("proxy_server_receives_http_request_from_dispatcher_but_neighborhood_cant_make_route");
-            let subject = ProxyServer::new(cryptde, true);
+            let subject = ProxyServer::new(cryptde, true, false, RouteConstraints::default());
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
                 .dispatcher(dispatcher)
@@ -1740,6 +2597,178 @@ mod tests {
             .exists_log_containing("ERROR: Proxy Server: Failed to find route to nowhere.com");
     }
 
+    #[test]
+    fn proxy_server_with_route_constraints_sends_them_to_neighborhood_and_explains_route_failure() {
+        init_test_logging();
+        let cryptde = cryptde();
+        let http_request = b"GET /index.html HTTP/1.1\r\nHost: nowhere.com\r\n\r\n";
+        let (neighborhood_mock, _, neighborhood_recording_arc) = make_recorder();
+        let neighborhood_mock = neighborhood_mock.route_query_response(None);
+        let dispatcher = Recorder::new();
+        let dispatcher_awaiter = dispatcher.get_awaiter();
+        let dispatcher_recording_arc = dispatcher.get_recording();
+        let route_constraints = RouteConstraints {
+            exit_keys: vec![PublicKey::new(&[3])],
+            excluded_keys: vec![PublicKey::new(&[4])],
+            excluded_ip_ranges: vec![],
+        };
+        let msg_from_dispatcher = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            reception_port: Some(HTTP_PORT),
+            sequence_number: Some(0),
+            last_data: true,
+            data: http_request.to_vec(),
+            is_clandestine: false,
+        };
+        let route_constraints_inner = route_constraints.clone();
+        thread::spawn(move || {
+            let system = System::new("proxy_server_with_route_constraints_sends_them_to_neighborhood_and_explains_route_failure");
+            let subject = ProxyServer::new(cryptde, true, false, route_constraints_inner);
+            let subject_addr: Addr<ProxyServer> = subject.start();
+            let mut peer_actors = peer_actors_builder()
+                .dispatcher(dispatcher)
+                .neighborhood(neighborhood_mock)
+                .build();
+            peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            subject_addr.try_send(msg_from_dispatcher).unwrap();
+
+            system.run();
+        });
+
+        dispatcher_awaiter.await_message_count(1);
+        let recording = dispatcher_recording_arc.lock().unwrap();
+        let record = recording.get_record::<TransmitDataMsg>(0);
+        assert_eq!(
+            record.data,
+            ServerImpersonatorHttp {}.route_constraint_failure_response("nowhere.com")
+        );
+        let recording = neighborhood_recording_arc.lock().unwrap();
+        let record = recording.get_record::<RouteQueryMessage>(0);
+        assert_eq!(
+            record,
+            &RouteQueryMessage::data_constrained_route_request(3, route_constraints)
+        );
+        TestLogHandler::new().exists_log_containing(
+            "ERROR: Proxy Server: Failed to find route to nowhere.com within route constraints",
+        );
+    }
+
+    #[test]
+    fn set_route_constraints_message_changes_constraints_for_subsequent_requests() {
+        init_test_logging();
+        let cryptde = cryptde();
+        let http_request = b"GET /index.html HTTP/1.1\r\nHost: nowhere.com\r\n\r\n";
+        let (neighborhood_mock, _, neighborhood_recording_arc) = make_recorder();
+        let neighborhood_mock = neighborhood_mock.route_query_response(None);
+        let dispatcher = Recorder::new();
+        let dispatcher_awaiter = dispatcher.get_awaiter();
+        let route_constraints = RouteConstraints {
+            exit_keys: vec![],
+            excluded_keys: vec![],
+            excluded_ip_ranges: vec![IpRange::from_str("10.0.0.0/8").unwrap()],
+        };
+        let msg_from_dispatcher = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            reception_port: Some(HTTP_PORT),
+            sequence_number: Some(0),
+            last_data: true,
+            data: http_request.to_vec(),
+            is_clandestine: false,
+        };
+        let route_constraints_inner = route_constraints.clone();
+        thread::spawn(move || {
+            let system = System::new(
+                "set_route_constraints_message_changes_constraints_for_subsequent_requests",
+            );
+            let subject = ProxyServer::new(cryptde, true, false, RouteConstraints::default());
+            let subject_addr: Addr<ProxyServer> = subject.start();
+            let mut peer_actors = peer_actors_builder()
+                .dispatcher(dispatcher)
+                .neighborhood(neighborhood_mock)
+                .build();
+            peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            subject_addr
+                .try_send(SetRouteConstraintsMessage {
+                    constraints: route_constraints_inner,
+                })
+                .unwrap();
+            subject_addr.try_send(msg_from_dispatcher).unwrap();
+
+            system.run();
+        });
+
+        dispatcher_awaiter.await_message_count(1);
+        let recording = neighborhood_recording_arc.lock().unwrap();
+        let record = recording.get_record::<RouteQueryMessage>(0);
+        assert_eq!(
+            record,
+            &RouteQueryMessage::data_constrained_route_request(3, route_constraints.clone())
+        );
+        TestLogHandler::new().exists_log_containing(&format!(
+            "INFO: Proxy Server: Route constraints changed from {:?} to {:?}",
+            RouteConstraints::default(),
+            route_constraints
+        ));
+    }
+
+    #[test]
+    fn active_streams_request_is_answered_with_the_streams_being_carried() {
+        let cryptde = cryptde();
+        let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        let http_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let tls_addr = SocketAddr::from_str("2.3.4.5:6789").unwrap();
+        let http_key = StreamKey::new(cryptde.public_key().clone(), http_addr);
+        let tls_key = StreamKey::new(cryptde.public_key().clone(), tls_addr);
+        thread::spawn(move || {
+            let system =
+                System::new("active_streams_request_is_answered_with_the_streams_being_carried");
+            let mut subject = ProxyServer::new(cryptde, true, false, RouteConstraints::default());
+            subject.keys_and_addrs.insert(http_key, http_addr);
+            subject.keys_and_addrs.insert(tls_key, tls_addr);
+            subject
+                .tunneled_hosts
+                .insert(tls_key, "tunneled.com".to_string());
+            let subject_addr: Addr<ProxyServer> = subject.start();
+            let peer_actors = peer_actors_builder().ui_gateway(ui_gateway).build();
+            subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            subject_addr
+                .try_send(ActiveStreamsRequest {
+                    client_id: 1234,
+                    correlation_id: Some(17),
+                })
+                .unwrap();
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(1);
+        let recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 1234,
+                correlation_id: Some(17),
+                data: UiMessage::ActiveStreams(vec![
+                    StreamInfo {
+                        stream_key: format!("{:?}", http_key),
+                        client_addr: "1.2.3.4:5678".to_string(),
+                        tunneled_host: None,
+                    },
+                    StreamInfo {
+                        stream_key: format!("{:?}", tls_key),
+                        client_addr: "2.3.4.5:6789".to_string(),
+                        tunneled_host: Some("tunneled.com".to_string()),
+                    },
+                ])
+            }
+        );
+    }
+
     #[test]
     #[should_panic(expected = "Expected RoundTrip ExpectedServices but got OneWay")]
     fn proxy_server_panics_if_it_receives_a_one_way_route_from_a_request_for_a_round_trip_route() {
@@ -1767,6 +2796,7 @@ mod tests {
                     rate_pack(103),
                 ),
             ]),
+            exit_supports_compression: false,
         }));
         let payload = ClientRequestPayload {
             stream_key: make_meaningless_stream_key(),
@@ -1779,6 +2809,8 @@ mod tests {
             target_port: 0,
             protocol: ProxyProtocol::TLS,
             originator_public_key: cryptde.public_key().clone(),
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let logger = Logger::new("ProxyServer");
         let source_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
@@ -1794,6 +2826,7 @@ mod tests {
             &peer_actors.accountant.report_exit_service_consumed,
             &peer_actors.accountant.report_routing_service_consumed,
             &peer_actors.proxy_server.add_return_route,
+            false,
         )
         .unwrap();
     }
@@ -1816,6 +2849,7 @@ mod tests {
             )
             .unwrap(),
             expected_services: ExpectedServices::RoundTrip(vec![], vec![], 1234),
+            exit_supports_compression: false,
         };
         let neighborhood_mock = neighborhood_mock.route_query_response(Some(route_query_response));
         let dispatcher = Recorder::new();
@@ -1833,7 +2867,7 @@ mod tests {
         };
         thread::spawn(move || {
             let system = System::new("proxy_server_receives_http_request_from_dispatcher_but_neighborhood_cant_make_route");
-            let subject = ProxyServer::new(cryptde, true);
+            let subject = ProxyServer::new(cryptde, true, false, RouteConstraints::default());
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
                 .dispatcher(dispatcher)
@@ -1919,12 +2953,14 @@ mod tests {
             target_port: TLS_PORT,
             protocol: ProxyProtocol::TLS,
             originator_public_key: key.clone(),
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let expected_pkg =
             IncipientCoresPackage::new(cryptde, route.clone(), expected_payload.into(), &key)
                 .unwrap();
         thread::spawn(move || {
-            let mut subject = ProxyServer::new(cryptde, false);
+            let mut subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
             subject.stream_key_factory =
                 Box::new(StreamKeyFactoryMock::new().make_result(stream_key.clone()));
             let system = System::new("proxy_server_receives_tls_client_hello_from_dispatcher_then_sends_cores_package_to_hopper");
@@ -1988,12 +3024,14 @@ mod tests {
             target_port: TLS_PORT,
             protocol: ProxyProtocol::TLS,
             originator_public_key: key.clone(),
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let expected_pkg =
             IncipientCoresPackage::new(cryptde, route.clone(), expected_payload.into(), &key)
                 .unwrap();
         thread::spawn(move || {
-            let mut subject = ProxyServer::new(cryptde, false);
+            let mut subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
             subject.stream_key_factory =
                 Box::new(StreamKeyFactoryMock::new().make_result(stream_key.clone()));
             let system = System::new("proxy_server_receives_tls_client_hello_from_dispatcher_then_sends_cores_package_to_hopper");
@@ -2055,12 +3093,14 @@ mod tests {
             target_port: TLS_PORT,
             protocol: ProxyProtocol::TLS,
             originator_public_key: key.clone(),
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let expected_pkg =
             IncipientCoresPackage::new(cryptde, route.clone(), expected_payload.into(), &key)
                 .unwrap();
         thread::spawn(move || {
-            let mut subject = ProxyServer::new(cryptde, false);
+            let mut subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
             subject.stream_key_factory =
                 Box::new(StreamKeyFactoryMock::new().make_result(stream_key.clone()));
             let system = System::new("proxy_server_receives_tls_client_hello_from_dispatcher_then_sends_cores_package_to_hopper");
@@ -2124,7 +3164,7 @@ mod tests {
         };
         thread::spawn(move || {
             let system = System::new("proxy_server_receives_tls_client_hello_from_dispatcher_but_neighborhood_cant_make_route");
-            let subject = ProxyServer::new(cryptde, false);
+            let subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
             let subject_addr: Addr<ProxyServer> = subject.start();
             let mut peer_actors = peer_actors_builder()
                 .dispatcher(dispatcher)
@@ -2158,7 +3198,7 @@ mod tests {
         let system = System::new("proxy_server_receives_response_from_hopper");
         let (dispatcher_mock, _, dispatcher_log_arc) = make_recorder();
         let cryptde = cryptde();
-        let mut subject = ProxyServer::new(cryptde, false);
+        let mut subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let stream_key = make_meaningless_stream_key();
         subject
@@ -2171,6 +3211,8 @@ mod tests {
                 expected_services: vec![ExpectedService::Nothing],
                 protocol: ProxyProtocol::TLS,
                 server_name: None,
+                stream_key,
+                redundant: false,
             },
         );
         let subject_addr: Addr<ProxyServer> = subject.start();
@@ -2182,10 +3224,12 @@ mod tests {
                 sequence_number: 12345678,
                 last_data: true,
             },
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let first_expired_cores_package = ExpiredCoresPackage::new(
             IpAddr::from_str("1.2.3.4").unwrap(),
-            Some(Wallet::new("consuming")),
+            Some(make_paying_wallet(b"consuming")),
             remaining_route,
             client_response_payload,
             0,
@@ -2210,50 +3254,162 @@ mod tests {
     }
 
     #[test]
-    fn proxy_server_receives_nonterminal_response_from_hopper() {
-        let system = System::new("proxy_server_receives_response_from_hopper");
+    fn proxy_server_decompresses_compressed_response_from_hopper() {
+        let system = System::new("proxy_server_decompresses_compressed_response_from_hopper");
         let (dispatcher_mock, _, dispatcher_log_arc) = make_recorder();
-        let (accountant, _, accountant_recording_arc) = make_recorder();
         let cryptde = cryptde();
-        let mut subject = ProxyServer::new(cryptde, false);
+        let mut subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let stream_key = make_meaningless_stream_key();
-        let irrelevant_public_key = PublicKey::from(&b"irrelevant"[..]);
         subject
             .keys_and_addrs
             .insert(stream_key.clone(), socket_addr.clone());
-        let incoming_route_d_wallet = Wallet::new("D Earning");
-        let incoming_route_e_wallet = Wallet::new("E Earning");
-        let incoming_route_f_wallet = Wallet::new("F Earning");
         subject.route_ids_to_return_routes.insert(
             1234,
             AddReturnRouteMessage {
                 return_route_id: 1234,
-                expected_services: vec![
-                    ExpectedService::Exit(
-                        irrelevant_public_key.clone(),
-                        incoming_route_d_wallet.clone(),
-                        rate_pack(101),
-                    ),
-                    ExpectedService::Routing(
-                        irrelevant_public_key.clone(),
-                        incoming_route_e_wallet.clone(),
-                        rate_pack(102),
-                    ),
-                    ExpectedService::Routing(
-                        irrelevant_public_key.clone(),
-                        incoming_route_f_wallet.clone(),
-                        rate_pack(103),
-                    ),
-                    ExpectedService::Nothing,
-                ],
-                protocol: ProxyProtocol::TLS,
+                expected_services: vec![ExpectedService::Nothing],
+                protocol: ProxyProtocol::HTTP,
                 server_name: None,
+                stream_key,
+                redundant: false,
             },
         );
-        let incoming_route_g_wallet = Wallet::new("G Earning");
-        let incoming_route_h_wallet = Wallet::new("H Earning");
-        let incoming_route_i_wallet = Wallet::new("I Earning");
+        let subject_addr: Addr<ProxyServer> = subject.start();
+        let data = b"<p>booga</p><p>booga</p><p>booga</p><p>booga</p>".to_vec();
+        let client_response_payload = ClientResponsePayload {
+            stream_key: stream_key.clone(),
+            sequenced_packet: SequencedPacket {
+                data: compression::compress(&data),
+                sequence_number: 0,
+                last_data: true,
+            },
+            compression: PayloadCompression::Compressed,
+            redundant: false,
+        };
+        let expired_cores_package = ExpiredCoresPackage::new(
+            IpAddr::from_str("1.2.3.4").unwrap(),
+            Some(make_paying_wallet(b"consuming")),
+            return_route_with_id(cryptde, 1234),
+            client_response_payload,
+            0,
+        );
+        let mut peer_actors = peer_actors_builder().dispatcher(dispatcher_mock).build();
+        peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        subject_addr.try_send(expired_cores_package).unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        let recording = dispatcher_log_arc.lock().unwrap();
+        let record = recording.get_record::<TransmitDataMsg>(0);
+        assert_eq!(record.data, data);
+    }
+
+    #[test]
+    fn proxy_server_logs_and_drops_response_that_cant_be_decompressed() {
+        init_test_logging();
+        let system = System::new("proxy_server_logs_and_drops_response_that_cant_be_decompressed");
+        let (dispatcher_mock, _, dispatcher_log_arc) = make_recorder();
+        let cryptde = cryptde();
+        let mut subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let stream_key = make_meaningless_stream_key();
+        subject
+            .keys_and_addrs
+            .insert(stream_key.clone(), socket_addr.clone());
+        subject.route_ids_to_return_routes.insert(
+            1234,
+            AddReturnRouteMessage {
+                return_route_id: 1234,
+                expected_services: vec![ExpectedService::Nothing],
+                protocol: ProxyProtocol::HTTP,
+                server_name: None,
+                stream_key,
+                redundant: false,
+            },
+        );
+        let subject_addr: Addr<ProxyServer> = subject.start();
+        let client_response_payload = ClientResponsePayload {
+            stream_key: stream_key.clone(),
+            sequenced_packet: SequencedPacket {
+                data: vec![0xFF, 0xFF, 0xFF],
+                sequence_number: 3,
+                last_data: true,
+            },
+            compression: PayloadCompression::Compressed,
+            redundant: false,
+        };
+        let expired_cores_package = ExpiredCoresPackage::new(
+            IpAddr::from_str("1.2.3.4").unwrap(),
+            Some(make_paying_wallet(b"consuming")),
+            return_route_with_id(cryptde, 1234),
+            client_response_payload,
+            0,
+        );
+        let mut peer_actors = peer_actors_builder().dispatcher(dispatcher_mock).build();
+        peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        subject_addr.try_send(expired_cores_package).unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        assert_eq!(dispatcher_log_arc.lock().unwrap().len(), 0);
+        TestLogHandler::new().exists_log_containing(&format!(
+            "ERROR: Proxy Server: Discarding packet 3 for stream {:?}: can't decompress response: Can't decompress 3-byte data: ",
+            stream_key
+        ));
+    }
+
+    #[test]
+    fn proxy_server_receives_nonterminal_response_from_hopper() {
+        let system = System::new("proxy_server_receives_response_from_hopper");
+        let (dispatcher_mock, _, dispatcher_log_arc) = make_recorder();
+        let (accountant, _, accountant_recording_arc) = make_recorder();
+        let cryptde = cryptde();
+        let mut subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let stream_key = make_meaningless_stream_key();
+        let irrelevant_public_key = PublicKey::from(&b"irrelevant"[..]);
+        subject
+            .keys_and_addrs
+            .insert(stream_key.clone(), socket_addr.clone());
+        let incoming_route_d_wallet = Wallet::new("D Earning");
+        let incoming_route_e_wallet = Wallet::new("E Earning");
+        let incoming_route_f_wallet = Wallet::new("F Earning");
+        subject.route_ids_to_return_routes.insert(
+            1234,
+            AddReturnRouteMessage {
+                return_route_id: 1234,
+                expected_services: vec![
+                    ExpectedService::Exit(
+                        irrelevant_public_key.clone(),
+                        incoming_route_d_wallet.clone(),
+                        rate_pack(101),
+                    ),
+                    ExpectedService::Routing(
+                        irrelevant_public_key.clone(),
+                        incoming_route_e_wallet.clone(),
+                        rate_pack(102),
+                    ),
+                    ExpectedService::Routing(
+                        irrelevant_public_key.clone(),
+                        incoming_route_f_wallet.clone(),
+                        rate_pack(103),
+                    ),
+                    ExpectedService::Nothing,
+                ],
+                protocol: ProxyProtocol::TLS,
+                server_name: None,
+                stream_key,
+                redundant: false,
+            },
+        );
+        let incoming_route_g_wallet = Wallet::new("G Earning");
+        let incoming_route_h_wallet = Wallet::new("H Earning");
+        let incoming_route_i_wallet = Wallet::new("I Earning");
         subject.route_ids_to_return_routes.insert(
             1235,
             AddReturnRouteMessage {
@@ -2278,6 +3434,8 @@ mod tests {
                 ],
                 protocol: ProxyProtocol::TLS,
                 server_name: None,
+                stream_key,
+                redundant: false,
             },
         );
         let subject_addr: Addr<ProxyServer> = subject.start();
@@ -2288,12 +3446,14 @@ mod tests {
                 sequence_number: 4321,
                 last_data: false,
             },
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let first_exit_size = first_client_response_payload.sequenced_packet.data.len();
         let first_expired_cores_package: ExpiredCoresPackage<ClientResponsePayload> =
             ExpiredCoresPackage::new(
                 IpAddr::from_str("1.2.3.4").unwrap(),
-                Some(Wallet::new("irrelevant")),
+                Some(make_paying_wallet(b"irrelevant")),
                 return_route_with_id(cryptde, 1234),
                 first_client_response_payload.into(),
                 0,
@@ -2307,12 +3467,14 @@ mod tests {
                 sequence_number: 4322,
                 last_data: false,
             },
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let second_exit_size = second_client_response_payload.sequenced_packet.data.len();
         let second_expired_cores_package: ExpiredCoresPackage<ClientResponsePayload> =
             ExpiredCoresPackage::new(
                 IpAddr::from_str("1.2.3.5").unwrap(),
-                Some(Wallet::new("irrelevant")),
+                Some(make_paying_wallet(b"irrelevant")),
                 return_route_with_id(cryptde, 1235),
                 second_client_response_payload.into(),
                 0,
@@ -2391,6 +3553,292 @@ mod tests {
         assert_eq!(accountant_recording.len(), 6);
     }
 
+    #[test]
+    fn multipath_proxy_server_charges_exit_only_for_first_copy_of_response() {
+        let system =
+            System::new("multipath_proxy_server_charges_exit_only_for_first_copy_of_response");
+        let (dispatcher_mock, _, dispatcher_log_arc) = make_recorder();
+        let (accountant, _, accountant_recording_arc) = make_recorder();
+        let cryptde = cryptde();
+        let mut subject = ProxyServer::new(cryptde, true, true, RouteConstraints::default());
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let stream_key = make_meaningless_stream_key();
+        let exit_public_key = PublicKey::from(&b"exit"[..]);
+        subject
+            .keys_and_addrs
+            .insert(stream_key.clone(), socket_addr.clone());
+        let exit_wallet = Wallet::new("exit wallet");
+        let first_routing_wallet = Wallet::new("first routing wallet");
+        let second_routing_wallet = Wallet::new("second routing wallet");
+        let make_return_route_info =
+            |return_route_id: u32, routing_wallet: &Wallet| AddReturnRouteMessage {
+                return_route_id,
+                expected_services: vec![
+                    ExpectedService::Exit(
+                        exit_public_key.clone(),
+                        exit_wallet.clone(),
+                        rate_pack(101),
+                    ),
+                    ExpectedService::Routing(
+                        PublicKey::from(&b"routing"[..]),
+                        routing_wallet.clone(),
+                        rate_pack(102),
+                    ),
+                    ExpectedService::Nothing,
+                ],
+                protocol: ProxyProtocol::TLS,
+                server_name: None,
+                stream_key,
+                redundant: false,
+            };
+        subject
+            .route_ids_to_return_routes
+            .insert(1234, make_return_route_info(1234, &first_routing_wallet));
+        subject
+            .route_ids_to_return_routes
+            .insert(1235, make_return_route_info(1235, &second_routing_wallet));
+        let subject_addr: Addr<ProxyServer> = subject.start();
+        let client_response_payload = ClientResponsePayload {
+            stream_key,
+            sequenced_packet: SequencedPacket {
+                data: b"some data".to_vec(),
+                sequence_number: 4321,
+                last_data: false,
+            },
+            compression: PayloadCompression::None,
+            redundant: true,
+        };
+        let make_package = |return_route_id: u32| -> ExpiredCoresPackage<ClientResponsePayload> {
+            ExpiredCoresPackage::new(
+                IpAddr::from_str("1.2.3.4").unwrap(),
+                Some(make_paying_wallet(b"irrelevant")),
+                return_route_with_id(cryptde, return_route_id),
+                client_response_payload.clone().into(),
+                0,
+            )
+        };
+        let first_package = make_package(1234);
+        let second_package = make_package(1235);
+        let routing_size = first_package.payload_len;
+        let mut peer_actors = peer_actors_builder()
+            .dispatcher(dispatcher_mock)
+            .accountant(accountant)
+            .build();
+        peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        subject_addr.try_send(first_package).unwrap();
+        subject_addr.try_send(second_package).unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        let dispatcher_recording = dispatcher_log_arc.lock().unwrap();
+        assert_eq!(
+            dispatcher_recording.get_record::<TransmitDataMsg>(0),
+            &TransmitDataMsg {
+                endpoint: Endpoint::Socket(socket_addr),
+                last_data: false,
+                sequence_number: Some(4321),
+                data: b"some data".to_vec(),
+            }
+        );
+        assert_eq!(dispatcher_recording.len(), 1);
+        let accountant_recording = accountant_recording_arc.lock().unwrap();
+        check_exit_report(
+            &accountant_recording,
+            0,
+            &exit_wallet,
+            b"some data".len(),
+            &rate_pack(101),
+        );
+        check_routing_report(
+            &accountant_recording,
+            1,
+            &first_routing_wallet,
+            routing_size,
+            &rate_pack(102),
+        );
+        check_routing_report(
+            &accountant_recording,
+            2,
+            &second_routing_wallet,
+            routing_size,
+            &rate_pack(102),
+        );
+        assert_eq!(accountant_recording.len(), 3);
+    }
+
+    #[test]
+    fn multipath_proxy_server_does_not_match_responses_that_have_no_redundant_copy() {
+        let system = System::new(
+            "multipath_proxy_server_does_not_match_responses_that_have_no_redundant_copy",
+        );
+        let (dispatcher_mock, _, dispatcher_log_arc) = make_recorder();
+        let cryptde = cryptde();
+        let mut subject = ProxyServer::new(cryptde, true, true, RouteConstraints::default());
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let stream_key = make_meaningless_stream_key();
+        subject
+            .keys_and_addrs
+            .insert(stream_key.clone(), socket_addr.clone());
+        subject.route_ids_to_return_routes.insert(
+            1234,
+            AddReturnRouteMessage {
+                return_route_id: 1234,
+                expected_services: vec![ExpectedService::Nothing],
+                protocol: ProxyProtocol::TLS,
+                server_name: None,
+                stream_key,
+                redundant: false,
+            },
+        );
+        let subject_addr: Addr<ProxyServer> = subject.start();
+        let package: ExpiredCoresPackage<ClientResponsePayload> = ExpiredCoresPackage::new(
+            IpAddr::from_str("1.2.3.4").unwrap(),
+            Some(make_paying_wallet(b"irrelevant")),
+            return_route_with_id(cryptde, 1234),
+            ClientResponsePayload {
+                stream_key,
+                sequenced_packet: SequencedPacket {
+                    data: b"some data".to_vec(),
+                    sequence_number: 4321,
+                    last_data: false,
+                },
+                compression: PayloadCompression::None,
+                redundant: false,
+            }
+            .into(),
+            0,
+        );
+        let mut peer_actors = peer_actors_builder().dispatcher(dispatcher_mock).build();
+        peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        subject_addr.try_send(package.clone()).unwrap();
+        subject_addr.try_send(package).unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        let dispatcher_recording = dispatcher_log_arc.lock().unwrap();
+        assert_eq!(dispatcher_recording.len(), 2);
+    }
+
+    #[test]
+    fn multipath_proxy_server_quietly_discards_redundant_copy_arriving_after_stream_closes() {
+        init_test_logging();
+        let system = System::new(
+            "multipath_proxy_server_quietly_discards_redundant_copy_arriving_after_stream_closes",
+        );
+        let (dispatcher_mock, _, dispatcher_log_arc) = make_recorder();
+        let (accountant, _, accountant_recording_arc) = make_recorder();
+        let cryptde = cryptde();
+        let mut subject = ProxyServer::new(cryptde, true, true, RouteConstraints::default());
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let stream_key = make_meaningless_stream_key();
+        subject
+            .keys_and_addrs
+            .insert(stream_key.clone(), socket_addr.clone());
+        let exit_wallet = Wallet::new("exit wallet");
+        let routing_wallet = Wallet::new("routing wallet");
+        subject.route_ids_to_return_routes.insert(
+            1234,
+            AddReturnRouteMessage {
+                return_route_id: 1234,
+                expected_services: vec![
+                    ExpectedService::Exit(
+                        PublicKey::from(&b"exit"[..]),
+                        exit_wallet.clone(),
+                        rate_pack(101),
+                    ),
+                    ExpectedService::Routing(
+                        PublicKey::from(&b"routing"[..]),
+                        routing_wallet.clone(),
+                        rate_pack(102),
+                    ),
+                    ExpectedService::Nothing,
+                ],
+                protocol: ProxyProtocol::TLS,
+                server_name: None,
+                stream_key,
+                redundant: false,
+            },
+        );
+        let subject_addr: Addr<ProxyServer> = subject.start();
+        let package: ExpiredCoresPackage<ClientResponsePayload> = ExpiredCoresPackage::new(
+            IpAddr::from_str("1.2.3.4").unwrap(),
+            Some(make_paying_wallet(b"irrelevant")),
+            return_route_with_id(cryptde, 1234),
+            ClientResponsePayload {
+                stream_key,
+                sequenced_packet: SequencedPacket {
+                    data: b"some data".to_vec(),
+                    sequence_number: 4321,
+                    last_data: true,
+                },
+                compression: PayloadCompression::None,
+                redundant: true,
+            }
+            .into(),
+            0,
+        );
+        let routing_size = package.payload_len;
+        let mut peer_actors = peer_actors_builder()
+            .dispatcher(dispatcher_mock)
+            .accountant(accountant)
+            .build();
+        peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        subject_addr.try_send(package.clone()).unwrap();
+        subject_addr.try_send(package).unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        let dispatcher_recording = dispatcher_log_arc.lock().unwrap();
+        assert_eq!(dispatcher_recording.len(), 1);
+        let accountant_recording = accountant_recording_arc.lock().unwrap();
+        check_exit_report(
+            &accountant_recording,
+            0,
+            &exit_wallet,
+            b"some data".len(),
+            &rate_pack(101),
+        );
+        check_routing_report(
+            &accountant_recording,
+            1,
+            &routing_wallet,
+            routing_size,
+            &rate_pack(102),
+        );
+        check_routing_report(
+            &accountant_recording,
+            2,
+            &routing_wallet,
+            routing_size,
+            &rate_pack(102),
+        );
+        assert_eq!(accountant_recording.len(), 3);
+        TestLogHandler::new().exists_log_containing(&format!(
+            "Discarding redundant copy of packet 4321 for finished stream {:?}",
+            stream_key
+        ));
+    }
+
+    #[test]
+    fn closing_a_stream_forgets_its_redundant_route_state() {
+        let mut subject = ProxyServer::new(cryptde(), true, true, RouteConstraints::default());
+        let stream_key = make_meaningless_stream_key();
+        subject.match_redundant_response(&stream_key, 1);
+        subject.match_redundant_response(&stream_key, 2);
+        subject.streams_with_failed_route.insert(stream_key);
+
+        subject.stream_closed(&stream_key);
+
+        assert!(subject.unmatched_response_sequence_numbers.is_empty());
+        assert!(subject.streams_with_failed_route.is_empty());
+    }
+
     #[test]
     fn handle_dns_resolve_failure_sends_message_to_dispatcher() {
         let system = System::new("proxy_server_receives_response_from_routing_services");
@@ -2398,7 +3846,7 @@ mod tests {
         let (dispatcher_mock, _, dispatcher_log_arc) = make_recorder();
 
         let cryptde = cryptde();
-        let mut subject = ProxyServer::new(cryptde, false);
+        let mut subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
 
         let stream_key = make_meaningless_stream_key();
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
@@ -2417,7 +3865,7 @@ mod tests {
         let expired_cores_package: ExpiredCoresPackage<DnsResolveFailure> =
             ExpiredCoresPackage::new(
                 IpAddr::from_str("1.2.3.4").unwrap(),
-                Some(Wallet::new("irrelevant")),
+                Some(make_paying_wallet(b"irrelevant")),
                 return_route_with_id(cryptde, 1234),
                 dns_resolve_failure.into(),
                 0,
@@ -2437,6 +3885,8 @@ mod tests {
                 )],
                 protocol: ProxyProtocol::HTTP,
                 server_name: Some("server.com".to_string()),
+                stream_key,
+                redundant: false,
             })
             .unwrap();
 
@@ -2461,12 +3911,301 @@ mod tests {
         );
     }
 
+    #[test]
+    fn handle_route_failure_sends_error_page_to_dispatcher_and_deprioritizes_failed_node() {
+        let system = System::new(
+            "handle_route_failure_sends_error_page_to_dispatcher_and_deprioritizes_failed_node",
+        );
+        let (dispatcher_mock, _, dispatcher_log_arc) = make_recorder();
+        let (neighborhood_mock, _, neighborhood_log_arc) = make_recorder();
+        let (ui_gateway, _, ui_gateway_log_arc) = make_recorder();
+        let cryptde = cryptde();
+        let mut subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
+        let stream_key = make_meaningless_stream_key();
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        subject
+            .keys_and_addrs
+            .insert(stream_key.clone(), socket_addr.clone());
+        let relay_public_key = PublicKey::from(&b"relay_key"[..]);
+        let exit_public_key = PublicKey::from(&b"exit_key"[..]);
+        let subject_addr: Addr<ProxyServer> = subject.start();
+        let route_failure = RouteFailure {
+            reporting_key: relay_public_key.clone(),
+            failed_key: exit_public_key.clone(),
+            reason: "connection refused".to_string(),
+        };
+        let expired_cores_package: ExpiredCoresPackage<RouteFailure> = ExpiredCoresPackage::new(
+            IpAddr::from_str("1.2.3.4").unwrap(),
+            Some(make_paying_wallet(b"irrelevant")),
+            return_route_with_id(cryptde, 1234),
+            route_failure,
+            0,
+        );
+        let mut peer_actors = peer_actors_builder()
+            .dispatcher(dispatcher_mock)
+            .neighborhood(neighborhood_mock)
+            .ui_gateway(ui_gateway)
+            .build();
+        peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+        subject_addr
+            .try_send(AddReturnRouteMessage {
+                return_route_id: 1234,
+                expected_services: vec![ExpectedService::Exit(
+                    exit_public_key.clone(),
+                    Wallet::new("exit wallet"),
+                    rate_pack(10),
+                )],
+                protocol: ProxyProtocol::HTTP,
+                server_name: Some("server.com".to_string()),
+                stream_key,
+                redundant: false,
+            })
+            .unwrap();
+
+        subject_addr.try_send(expired_cores_package).unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        let dispatcher_recording = dispatcher_log_arc.lock().unwrap();
+        let record = dispatcher_recording.get_record::<TransmitDataMsg>(0);
+        assert_eq!(
+            TransmitDataMsg {
+                endpoint: Endpoint::Socket(socket_addr),
+                last_data: true,
+                sequence_number: Some(0),
+                data: ServerImpersonatorHttp {}.route_failure_response(
+                    &relay_public_key,
+                    &exit_public_key,
+                    "connection refused",
+                    Some("server.com".to_string()),
+                ),
+            },
+            *record
+        );
+        let neighborhood_recording = neighborhood_log_arc.lock().unwrap();
+        let record = neighborhood_recording.get_record::<NodeRecordMetadataMessage>(0);
+        assert_eq!(
+            record,
+            &NodeRecordMetadataMessage::Desirable(exit_public_key.clone(), false)
+        );
+        let ui_gateway_recording = ui_gateway_log_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiEventMessage>(0),
+            &UiEventMessage {
+                event: UiEvent::RouteFailure {
+                    target: "server.com".to_string(),
+                    reason: format!(
+                        "Node {} couldn't relay request to {}: connection refused",
+                        relay_public_key, exit_public_key
+                    ),
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn multipath_proxy_server_survives_the_failure_of_one_route() {
+        init_test_logging();
+        let system = System::new("multipath_proxy_server_survives_the_failure_of_one_route");
+        let (dispatcher_mock, _, dispatcher_log_arc) = make_recorder();
+        let (neighborhood_mock, _, neighborhood_log_arc) = make_recorder();
+        let cryptde = cryptde();
+        let mut subject = ProxyServer::new(cryptde, true, true, RouteConstraints::default());
+        let stream_key = make_meaningless_stream_key();
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        subject
+            .keys_and_addrs
+            .insert(stream_key.clone(), socket_addr.clone());
+        let relay_public_key = PublicKey::from(&b"relay_key"[..]);
+        let failed_public_key = PublicKey::from(&b"failed_key"[..]);
+        let subject_addr: Addr<ProxyServer> = subject.start();
+        let make_package = |return_route_id: u32| -> ExpiredCoresPackage<RouteFailure> {
+            ExpiredCoresPackage::new(
+                IpAddr::from_str("1.2.3.4").unwrap(),
+                Some(make_paying_wallet(b"irrelevant")),
+                return_route_with_id(cryptde, return_route_id),
+                RouteFailure {
+                    reporting_key: relay_public_key.clone(),
+                    failed_key: failed_public_key.clone(),
+                    reason: "connection refused".to_string(),
+                },
+                0,
+            )
+        };
+        let mut peer_actors = peer_actors_builder()
+            .dispatcher(dispatcher_mock)
+            .neighborhood(neighborhood_mock)
+            .build();
+        peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+        for (return_route_id, redundant) in vec![(1234, false), (1235, true)] {
+            subject_addr
+                .try_send(AddReturnRouteMessage {
+                    return_route_id,
+                    expected_services: vec![ExpectedService::Nothing],
+                    protocol: ProxyProtocol::HTTP,
+                    server_name: Some("server.com".to_string()),
+                    stream_key,
+                    redundant,
+                })
+                .unwrap();
+        }
+
+        subject_addr.try_send(make_package(1234)).unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        let dispatcher_recording = dispatcher_log_arc.lock().unwrap();
+        assert_eq!(dispatcher_recording.len(), 0);
+        let neighborhood_recording = neighborhood_log_arc.lock().unwrap();
+        assert_eq!(
+            neighborhood_recording.get_record::<NodeRecordMetadataMessage>(0),
+            &NodeRecordMetadataMessage::Desirable(failed_public_key.clone(), false)
+        );
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: Proxy Server: Node {} couldn't relay request for stream {:?} to {}; continuing on the redundant route",
+            relay_public_key, stream_key, failed_public_key
+        ));
+    }
+
+    #[test]
+    fn multipath_proxy_server_reports_route_failure_when_no_redundant_copy_was_sent() {
+        init_test_logging();
+        let system = System::new(
+            "multipath_proxy_server_reports_route_failure_when_no_redundant_copy_was_sent",
+        );
+        let (dispatcher_mock, _, dispatcher_log_arc) = make_recorder();
+        let (neighborhood_mock, _, neighborhood_log_arc) = make_recorder();
+        let cryptde = cryptde();
+        let mut subject = ProxyServer::new(cryptde, true, true, RouteConstraints::default());
+        let stream_key = make_meaningless_stream_key();
+        let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        subject
+            .keys_and_addrs
+            .insert(stream_key.clone(), socket_addr.clone());
+        let relay_public_key = PublicKey::from(&b"lone_relay_key"[..]);
+        let failed_public_key = PublicKey::from(&b"failed_key"[..]);
+        let subject_addr: Addr<ProxyServer> = subject.start();
+        let make_package = |return_route_id: u32| -> ExpiredCoresPackage<RouteFailure> {
+            ExpiredCoresPackage::new(
+                IpAddr::from_str("1.2.3.4").unwrap(),
+                Some(make_paying_wallet(b"irrelevant")),
+                return_route_with_id(cryptde, return_route_id),
+                RouteFailure {
+                    reporting_key: relay_public_key.clone(),
+                    failed_key: failed_public_key.clone(),
+                    reason: "connection refused".to_string(),
+                },
+                0,
+            )
+        };
+        let mut peer_actors = peer_actors_builder()
+            .dispatcher(dispatcher_mock)
+            .neighborhood(neighborhood_mock)
+            .build();
+        peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+        for (return_route_id, redundant) in vec![(1234, false), (1235, false)] {
+            subject_addr
+                .try_send(AddReturnRouteMessage {
+                    return_route_id,
+                    expected_services: vec![ExpectedService::Nothing],
+                    protocol: ProxyProtocol::HTTP,
+                    server_name: Some("server.com".to_string()),
+                    stream_key,
+                    redundant,
+                })
+                .unwrap();
+        }
+
+        subject_addr.try_send(make_package(1234)).unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        let dispatcher_recording = dispatcher_log_arc.lock().unwrap();
+        assert_eq!(dispatcher_recording.len(), 1);
+        assert_eq!(
+            dispatcher_recording
+                .get_record::<TransmitDataMsg>(0)
+                .endpoint,
+            Endpoint::Socket(socket_addr)
+        );
+        let neighborhood_recording = neighborhood_log_arc.lock().unwrap();
+        assert_eq!(
+            neighborhood_recording.get_record::<NodeRecordMetadataMessage>(0),
+            &NodeRecordMetadataMessage::Desirable(failed_public_key.clone(), false)
+        );
+        TestLogHandler::new().exists_no_log_containing(&format!(
+            "WARN: Proxy Server: Node {} couldn't relay request for stream {:?} to {}; continuing on the redundant route",
+            relay_public_key, stream_key, failed_public_key
+        ));
+    }
+
+    #[test]
+    fn handle_route_failure_logs_when_stream_key_is_gone_but_still_deprioritizes_failed_node() {
+        init_test_logging();
+        let system = System::new(
+            "handle_route_failure_logs_when_stream_key_is_gone_but_still_deprioritizes_failed_node",
+        );
+        let (dispatcher_mock, _, dispatcher_log_arc) = make_recorder();
+        let (neighborhood_mock, _, neighborhood_log_arc) = make_recorder();
+        let cryptde = cryptde();
+        let subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
+        let stream_key = make_meaningless_stream_key();
+        let exit_public_key = PublicKey::from(&b"exit_key"[..]);
+        let subject_addr: Addr<ProxyServer> = subject.start();
+        let expired_cores_package: ExpiredCoresPackage<RouteFailure> = ExpiredCoresPackage::new(
+            IpAddr::from_str("1.2.3.4").unwrap(),
+            Some(make_paying_wallet(b"irrelevant")),
+            return_route_with_id(cryptde, 1234),
+            RouteFailure {
+                reporting_key: PublicKey::from(&b"relay_key"[..]),
+                failed_key: exit_public_key.clone(),
+                reason: "connection refused".to_string(),
+            },
+            0,
+        );
+        let mut peer_actors = peer_actors_builder()
+            .dispatcher(dispatcher_mock)
+            .neighborhood(neighborhood_mock)
+            .build();
+        peer_actors.proxy_server = ProxyServer::make_subs_from(&subject_addr);
+        subject_addr.try_send(BindMessage { peer_actors }).unwrap();
+        subject_addr
+            .try_send(AddReturnRouteMessage {
+                return_route_id: 1234,
+                expected_services: vec![],
+                protocol: ProxyProtocol::HTTP,
+                server_name: Some("server.com".to_string()),
+                stream_key,
+                redundant: false,
+            })
+            .unwrap();
+
+        subject_addr.try_send(expired_cores_package).unwrap();
+
+        System::current().stop_with_code(0);
+        system.run();
+        assert_eq!(dispatcher_log_arc.lock().unwrap().len(), 0);
+        let neighborhood_recording = neighborhood_log_arc.lock().unwrap();
+        let record = neighborhood_recording.get_record::<NodeRecordMetadataMessage>(0);
+        assert_eq!(
+            record,
+            &NodeRecordMetadataMessage::Desirable(exit_public_key, false)
+        );
+        TestLogHandler::new().exists_log_containing(&format!(
+            "ERROR: Proxy Server: Discarding RouteFailure message from an unrecognized stream key {:?}",
+            stream_key
+        ));
+    }
+
     #[test]
     fn handle_dns_resolve_failure_reports_services_consumed() {
         let system = System::new("proxy_server_records_accounting");
         let (accountant, _, accountant_recording_arc) = make_recorder();
         let cryptde = cryptde();
-        let mut subject = ProxyServer::new(cryptde, false);
+        let mut subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let stream_key = make_meaningless_stream_key();
         let irrelevant_public_key = PublicKey::from(&b"irrelevant"[..]);
@@ -2500,6 +4239,8 @@ mod tests {
                 ],
                 protocol: ProxyProtocol::TLS,
                 server_name: Some("server.com".to_string()),
+                stream_key,
+                redundant: false,
             },
         );
 
@@ -2509,7 +4250,7 @@ mod tests {
         let expired_cores_package: ExpiredCoresPackage<DnsResolveFailure> =
             ExpiredCoresPackage::new(
                 IpAddr::from_str("1.2.3.4").unwrap(),
-                Some(Wallet::new("irrelevant")),
+                Some(make_paying_wallet(b"irrelevant")),
                 return_route_with_id(cryptde, 1234),
                 dns_resolve_failure_payload.into(),
                 0,
@@ -2559,7 +4300,7 @@ mod tests {
         let (neighborhood_mock, _, neighborhood_log_arc) = make_recorder();
 
         let cryptde = cryptde();
-        let mut subject = ProxyServer::new(cryptde, false);
+        let mut subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
 
         let stream_key = make_meaningless_stream_key();
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
@@ -2581,6 +4322,8 @@ mod tests {
                 )],
                 protocol: ProxyProtocol::HTTP,
                 server_name: Some("server.com".to_string()),
+                stream_key,
+                redundant: false,
             },
         );
 
@@ -2591,7 +4334,7 @@ mod tests {
         let expired_cores_package: ExpiredCoresPackage<DnsResolveFailure> =
             ExpiredCoresPackage::new(
                 IpAddr::from_str("1.2.3.4").unwrap(),
-                Some(Wallet::new("irrelevant")),
+                Some(make_paying_wallet(b"irrelevant")),
                 return_route_with_id(cryptde, 1234),
                 dns_resolve_failure.into(),
                 0,
@@ -2625,7 +4368,7 @@ mod tests {
         let (neighborhood_mock, _, _) = make_recorder();
 
         let cryptde = cryptde();
-        let mut subject = ProxyServer::new(cryptde, false);
+        let mut subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
 
         let stream_key = make_meaningless_stream_key();
         let return_route_id = 1234;
@@ -2648,6 +4391,8 @@ mod tests {
                 )],
                 protocol: ProxyProtocol::HTTP,
                 server_name: Some("server.com".to_string()),
+                stream_key,
+                redundant: false,
             },
         );
 
@@ -2658,7 +4403,7 @@ mod tests {
         let expired_cores_package: ExpiredCoresPackage<DnsResolveFailure> =
             ExpiredCoresPackage::new(
                 IpAddr::from_str("1.2.3.4").unwrap(),
-                Some(Wallet::new("irrelevant")),
+                Some(make_paying_wallet(b"irrelevant")),
                 return_route_with_id(cryptde, return_route_id),
                 dns_resolve_failure.into(),
                 0,
@@ -2694,7 +4439,7 @@ mod tests {
         let (neighborhood_mock, _, _) = make_recorder();
 
         let cryptde = cryptde();
-        let mut subject = ProxyServer::new(cryptde, false);
+        let mut subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
 
         let stream_key = make_meaningless_stream_key();
         let return_route_id = 1234;
@@ -2717,6 +4462,8 @@ mod tests {
                 )],
                 protocol: ProxyProtocol::HTTP,
                 server_name: None,
+                stream_key,
+                redundant: false,
             },
         );
 
@@ -2727,7 +4474,7 @@ mod tests {
         let expired_cores_package: ExpiredCoresPackage<DnsResolveFailure> =
             ExpiredCoresPackage::new(
                 IpAddr::from_str("1.2.3.4").unwrap(),
-                Some(Wallet::new("irrelevant")),
+                Some(make_paying_wallet(b"irrelevant")),
                 return_route_with_id(cryptde, return_route_id),
                 dns_resolve_failure.into(),
                 0,
@@ -2762,7 +4509,7 @@ mod tests {
         let cryptde = cryptde();
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let stream_key = make_meaningless_stream_key();
-        let mut subject = ProxyServer::new(cryptde, false);
+        let mut subject = ProxyServer::new(cryptde, false, false, RouteConstraints::default());
         subject
             .keys_and_addrs
             .insert(stream_key.clone(), socket_addr.clone());
@@ -2774,6 +4521,8 @@ mod tests {
                 expected_services: vec![ExpectedService::Nothing],
                 protocol: ProxyProtocol::HTTP,
                 server_name: None,
+                stream_key,
+                redundant: false,
             },
         );
         let subject_addr: Addr<ProxyServer> = subject.start();
@@ -2785,10 +4534,12 @@ mod tests {
                 sequence_number: 0,
                 last_data: true,
             },
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let expired_cores_package = ExpiredCoresPackage::new(
             IpAddr::from_str("1.2.3.4").unwrap(),
-            Some(Wallet::new("consuming")),
+            Some(make_paying_wallet(b"consuming")),
             remaining_route,
             client_response_payload,
             0,
@@ -2805,7 +4556,7 @@ mod tests {
     fn panics_if_hopper_is_unbound() {
         let system = System::new("panics_if_hopper_is_unbound");
         let http_request = b"GET /index.html HTTP/1.1\r\nHost: nowhere.com\r\n\r\n";
-        let subject = ProxyServer::new(cryptde(), false);
+        let subject = ProxyServer::new(cryptde(), false, false, RouteConstraints::default());
         let socket_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
         let expected_data = http_request.to_vec();
         let msg_from_dispatcher = InboundClientData {
@@ -2832,7 +4583,7 @@ mod tests {
         let (dispatcher, _, dispatcher_recording_arc) = make_recorder();
         let (accountant, _, accountant_recording_arc) = make_recorder();
         let system = System::new("report_response_services_consumed_complains_and_drops_package_if_return_route_id_is_unrecognized");
-        let mut subject = ProxyServer::new(cryptde, true);
+        let mut subject = ProxyServer::new(cryptde, true, false, RouteConstraints::default());
         let stream_key = make_meaningless_stream_key();
         subject
             .keys_and_addrs
@@ -2850,10 +4601,12 @@ mod tests {
                 sequence_number: 4321,
                 last_data: false,
             },
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let expired_cores_package = ExpiredCoresPackage::new(
             IpAddr::from_str("1.2.3.4").unwrap(),
-            Some(Wallet::new("irrelevant")),
+            Some(make_paying_wallet(b"irrelevant")),
             return_route_with_id(cryptde, 1234),
             client_response_payload,
             0,
@@ -2877,7 +4630,7 @@ mod tests {
         let (dispatcher, _, dispatcher_recording_arc) = make_recorder();
         let (accountant, _, accountant_recording_arc) = make_recorder();
         let system = System::new("report_response_services_consumed_complains_and_drops_package_if_return_route_id_is_unreadable");
-        let mut subject = ProxyServer::new(cryptde, true);
+        let mut subject = ProxyServer::new(cryptde, true, false, RouteConstraints::default());
         let stream_key = make_meaningless_stream_key();
         subject
             .keys_and_addrs
@@ -2895,10 +4648,12 @@ mod tests {
                 sequence_number: 4321,
                 last_data: false,
             },
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let expired_cores_package = ExpiredCoresPackage::new(
             IpAddr::from_str("1.2.3.4").unwrap(),
-            Some(Wallet::new("irrelevant")),
+            Some(make_paying_wallet(b"irrelevant")),
             Route {
                 hops: vec![make_cover_hop(cryptde), CryptData::new(&[0])],
             },
@@ -2927,7 +4682,7 @@ mod tests {
         let (tx, rx) = mpsc::channel();
         thread::spawn(move || {
             let system = System::new("report_response_services_consumed_complains_and_drops_package_if_return_route_id_does_not_exist");
-            let mut subject = ProxyServer::new(cryptde, true);
+            let mut subject = ProxyServer::new(cryptde, true, false, RouteConstraints::default());
             subject.route_ids_to_return_routes = TtlHashMap::new(Duration::from_millis(250));
             subject
                 .keys_and_addrs
@@ -2939,6 +4694,8 @@ mod tests {
                     expected_services: vec![],
                     protocol: ProxyProtocol::TLS,
                     server_name: None,
+                    stream_key,
+                    redundant: false,
                 },
             );
             let subject_addr: Addr<ProxyServer> = subject.start();
@@ -2961,10 +4718,12 @@ mod tests {
                 sequence_number: 4321,
                 last_data: false,
             },
+            compression: PayloadCompression::None,
+            redundant: false,
         };
         let expired_cores_package = ExpiredCoresPackage::new(
             IpAddr::from_str("1.2.3.4").unwrap(),
-            Some(Wallet::new("irrelevant")),
+            Some(make_paying_wallet(b"irrelevant")),
             return_route_with_id(cryptde, 1234),
             client_response_payload,
             0,
@@ -2973,4 +4732,49 @@ mod tests {
 
         TestLogHandler::new().await_log_containing("ERROR: Proxy Server: Can't report services consumed: received response with bogus return-route ID 1234. Ignoring", 1000);
     }
+
+    #[test]
+    fn opening_and_closing_streams_is_published_to_the_ui_gateway() {
+        let system = System::new("opening_and_closing_streams_is_published_to_the_ui_gateway");
+        let (ui_gateway, _, ui_gateway_recording_arc) = make_recorder();
+        let stream_key = make_meaningless_stream_key();
+        let mut subject = ProxyServer::new(cryptde(), false, false, RouteConstraints::default());
+        subject.stream_key_factory = Box::new(StreamKeyFactoryMock::new().make_result(stream_key));
+        subject.ui_event_sub = Some(ui_gateway.start().recipient::<UiEventMessage>());
+        let ibcd = InboundClientData {
+            peer_addr: SocketAddr::from_str("1.2.3.4:5678").unwrap(),
+            reception_port: Some(HTTP_PORT),
+            sequence_number: Some(0),
+            last_data: false,
+            is_clandestine: false,
+            data: vec![],
+        };
+
+        subject.make_stream_key(&ibcd);
+        subject.make_stream_key(&ibcd);
+        subject.keys_and_addrs.remove_a(&stream_key);
+        subject.publish_stream_closed(&stream_key);
+
+        System::current().stop();
+        system.run();
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiEventMessage>(0),
+            &UiEventMessage {
+                event: UiEvent::StreamOpened {
+                    stream_key: format!("{:?}", stream_key),
+                    client_addr: "1.2.3.4:5678".to_string(),
+                }
+            }
+        );
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiEventMessage>(1),
+            &UiEventMessage {
+                event: UiEvent::StreamClosed {
+                    stream_key: format!("{:?}", stream_key),
+                }
+            }
+        );
+        assert_eq!(ui_gateway_recording.len(), 2);
+    }
 }
@@ -20,6 +20,25 @@ impl ServerImpersonator for ServerImpersonatorHttp {
         )
     }
 
+    fn route_constraint_failure_response(&self, server_name: &str) -> Vec<u8> {
+        ServerImpersonatorHttp::make_error_response(
+            503,
+            "Routing Problem",
+            format!(
+                "Can't find a route to {} that meets your constraints",
+                server_name
+            )
+            .as_str(),
+            format!(
+                "Substratum can't find a route to {} that exits through one of the Nodes you \
+                 chose and stays clear of the Nodes and IP addresses you excluded. Loosen those \
+                 constraints, or try again later when more is known about the Network.",
+                server_name
+            )
+            .as_str(),
+        )
+    }
+
     fn dns_resolution_failure_response(
         &self,
         exit_key: &PublicKey,
@@ -40,6 +59,30 @@ impl ServerImpersonator for ServerImpersonatorHttp {
                      exit_key, server_name, server_name, server_name),
         )
     }
+
+    fn route_failure_response(
+        &self,
+        reporting_key: &PublicKey,
+        failed_key: &PublicKey,
+        reason: &str,
+        server_name_opt: Option<String>,
+    ) -> Vec<u8> {
+        let server_name = match &server_name_opt {
+            Some(name) => name.clone(),
+            None => "<unspecified>".to_string(),
+        };
+        ServerImpersonatorHttp::make_error_response(
+            503,
+            "Routing Problem",
+            &format!("Your request to {} was lost along the way", server_name),
+            &format!(
+                "The Node {} on the route we chose for your request to {} couldn't pass it \
+                 along to the next Node, {} ({}). We've deprioritized that Node. Reload the \
+                 page, and we'll try to find another route.",
+                reporting_key, server_name, failed_key, reason
+            ),
+        )
+    }
 }
 
 impl ServerImpersonatorHttp {
@@ -165,6 +208,23 @@ mod tests {
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn route_constraint_failure_response_produces_expected_error_page() {
+        let subject = ServerImpersonatorHttp {};
+
+        let result = subject.route_constraint_failure_response("server.com");
+
+        let expected = ServerImpersonatorHttp::make_error_response(
+            503,
+            "Routing Problem",
+            "Can't find a route to server.com that meets your constraints",
+            "Substratum can't find a route to server.com that exits through one of the Nodes you \
+             chose and stays clear of the Nodes and IP addresses you excluded. Loosen those \
+             constraints, or try again later when more is known about the Network.",
+        );
+        assert_eq!(expected, result);
+    }
+
     #[test]
     fn dns_resolution_failure_response_with_server_name_produces_expected_error_page() {
         let subject = ServerImpersonatorHttp {};
@@ -203,4 +263,26 @@ mod tests {
         );
         assert_eq!(expected, result);
     }
+
+    #[test]
+    fn route_failure_response_produces_expected_error_page() {
+        let subject = ServerImpersonatorHttp {};
+
+        let result = subject.route_failure_response(
+            &PublicKey::new(&b"relay"[..]),
+            &PublicKey::new(&b"exit"[..]),
+            "connection refused",
+            Some("server.com".to_string()),
+        );
+
+        let expected = ServerImpersonatorHttp::make_error_response(
+            503,
+            "Routing Problem",
+            "Your request to server.com was lost along the way",
+            "The Node cmVsYXk on the route we chose for your request to server.com couldn't \
+             pass it along to the next Node, ZXhpdA (connection refused). We've deprioritized \
+             that Node. Reload the page, and we'll try to find another route.",
+        );
+        assert_eq!(expected, result);
+    }
 }
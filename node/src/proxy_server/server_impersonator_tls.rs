@@ -9,6 +9,10 @@ impl ServerImpersonator for ServerImpersonatorTls {
         Vec::from(&TLS_INTERNAL_ERROR_ALERT[..])
     }
 
+    fn route_constraint_failure_response(&self, _server_name: &str) -> Vec<u8> {
+        Vec::from(&TLS_INTERNAL_ERROR_ALERT[..])
+    }
+
     fn dns_resolution_failure_response(
         &self,
         _exit_key: &PublicKey,
@@ -16,6 +20,16 @@ impl ServerImpersonator for ServerImpersonatorTls {
     ) -> Vec<u8> {
         Vec::from(&TLS_UNRECOGNIZED_NAME_ALERT[..])
     }
+
+    fn route_failure_response(
+        &self,
+        _reporting_key: &PublicKey,
+        _failed_key: &PublicKey,
+        _reason: &str,
+        _server_name_opt: Option<String>,
+    ) -> Vec<u8> {
+        Vec::from(&TLS_INTERNAL_ERROR_ALERT[..])
+    }
 }
 
 const TLS_INTERNAL_ERROR_ALERT: [u8; 7] = [
@@ -47,6 +61,15 @@ mod tests {
         assert_eq!(Vec::from(&TLS_INTERNAL_ERROR_ALERT[..]), result);
     }
 
+    #[test]
+    fn route_constraint_failure_response_produces_internal_error_alert() {
+        let subject = ServerImpersonatorTls {};
+
+        let result = subject.route_constraint_failure_response("ignored");
+
+        assert_eq!(Vec::from(&TLS_INTERNAL_ERROR_ALERT[..]), result);
+    }
+
     #[test]
     fn dns_resolution_failure_response_produces_unrecognized_name_alert() {
         let subject = ServerImpersonatorTls {};
@@ -56,4 +79,18 @@ mod tests {
 
         assert_eq!(Vec::from(&TLS_UNRECOGNIZED_NAME_ALERT[..]), result);
     }
+
+    #[test]
+    fn route_failure_response_produces_internal_error_alert() {
+        let subject = ServerImpersonatorTls {};
+
+        let result = subject.route_failure_response(
+            &PublicKey::new(&b"ignored"[..]),
+            &PublicKey::new(&b"ignored"[..]),
+            "ignored",
+            None,
+        );
+
+        assert_eq!(Vec::from(&TLS_INTERNAL_ERROR_ALERT[..]), result);
+    }
 }
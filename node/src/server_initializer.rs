@@ -3,15 +3,18 @@ use super::bootstrapper::Bootstrapper;
 use super::entry_dns::new_dns_socket_server;
 use super::privilege_drop::PrivilegeDropper;
 use super::privilege_drop::PrivilegeDropperReal;
+use crate::log_file::LogRotation;
+use crate::log_file::NodeLog;
+use crate::log_file::RotatingLogFile;
+use crate::sub_lib::logger;
+use crate::sub_lib::logger::LogFilter;
+use crate::sub_lib::logger::LogFormat;
 use crate::sub_lib::main_tools::Command;
 use crate::sub_lib::main_tools::StdStreams;
 use crate::sub_lib::socket_server::SocketServer;
-use flexi_logger::Duplicate;
-use flexi_logger::LevelFilter;
-use flexi_logger::LogSpecification;
-use flexi_logger::Logger;
 use futures::try_ready;
-use std::env::temp_dir;
+use log::LevelFilter;
+use std::path::Path;
 use tokio::prelude::Async;
 use tokio::prelude::Future;
 
@@ -79,22 +82,44 @@ impl ServerInitializer<PrivilegeDropperReal> {
 }
 
 pub trait LoggerInitializerWrapper: Send {
-    fn init(&mut self, log_level: LevelFilter) -> bool;
+    fn init(
+        &mut self,
+        log_level: LevelFilter,
+        log_format: LogFormat,
+        log_directory: &Path,
+        log_rotation: &LogRotation,
+    ) -> bool;
 }
 
 struct LoggerInitializerWrapperReal {}
 
 impl LoggerInitializerWrapper for LoggerInitializerWrapperReal {
-    fn init(&mut self, log_level: LevelFilter) -> bool {
-        match Logger::with(LogSpecification::default(log_level).finalize())
-            .log_to_file()
-            .directory(&temp_dir().to_str().expect("Bad temporary filename")[..])
-            .print_message()
-            .duplicate_to_stderr(Duplicate::Info)
-            .suppress_timestamp()
-            .start()
-        {
-            Ok(_) => true,
+    fn init(
+        &mut self,
+        log_level: LevelFilter,
+        log_format: LogFormat,
+        log_directory: &Path,
+        log_rotation: &LogRotation,
+    ) -> bool {
+        // Lines from the Node's own Loggers are filtered by the LogFilter, which can change
+        // while the Node runs; log_level holds only for other crates.
+        logger::set_log_filter(LogFilter::new(log_level));
+        logger::set_log_format(log_format);
+        let log_file = match RotatingLogFile::open(log_directory, log_rotation.clone()) {
+            Ok(log_file) => log_file,
+            Err(e) => {
+                eprintln!("Couldn't open log file in {:?}: {}", log_directory, e);
+                return false;
+            }
+        };
+        println!("Log is written to {}", log_file.path().display());
+        let node_log: &'static NodeLog =
+            Box::leak(Box::new(NodeLog::new(log_level, log_format, log_file)));
+        match log::set_logger(node_log) {
+            Ok(()) => {
+                log::set_max_level(LevelFilter::Trace);
+                true
+            }
             Err(_) => false,
         }
     }
@@ -102,12 +127,18 @@ impl LoggerInitializerWrapper for LoggerInitializerWrapperReal {
 
 #[cfg(test)]
 pub mod test_utils {
+    use crate::log_file::LogRotation;
     use crate::privilege_drop::PrivilegeDropper;
     use crate::server_initializer::LoggerInitializerWrapper;
+    use crate::sub_lib::logger::LogFormat;
     use crate::test_utils::logging::init_test_logging;
     use log::LevelFilter;
+    use std::path::Path;
+    use std::path::PathBuf;
     use std::sync::{Arc, Mutex};
 
+    pub type LoggerInitParameters = (LevelFilter, LogFormat, PathBuf, LogRotation);
+
     pub struct PrivilegeDropperMock {
         pub call_count: Arc<Mutex<usize>>,
     }
@@ -128,12 +159,23 @@ pub mod test_utils {
     }
 
     pub struct LoggerInitializerWrapperMock {
-        init_parameters: Arc<Mutex<Vec<LevelFilter>>>,
+        init_parameters: Arc<Mutex<Vec<LoggerInitParameters>>>,
     }
 
     impl LoggerInitializerWrapper for LoggerInitializerWrapperMock {
-        fn init(&mut self, log_level: LevelFilter) -> bool {
-            self.init_parameters.lock().unwrap().push(log_level);
+        fn init(
+            &mut self,
+            log_level: LevelFilter,
+            log_format: LogFormat,
+            log_directory: &Path,
+            log_rotation: &LogRotation,
+        ) -> bool {
+            self.init_parameters.lock().unwrap().push((
+                log_level,
+                log_format,
+                log_directory.to_path_buf(),
+                log_rotation.clone(),
+            ));
             init_test_logging()
         }
     }
@@ -145,7 +187,7 @@ pub mod test_utils {
             }
         }
 
-        pub fn init_parameters(&mut self, parameters: &Arc<Mutex<Vec<LevelFilter>>>) {
+        pub fn init_parameters(&mut self, parameters: &Arc<Mutex<Vec<LoggerInitParameters>>>) {
             self.init_parameters = parameters.clone();
         }
     }
@@ -156,7 +198,7 @@ pub mod tests {
     use super::*;
     use crate::crash_test_dummy::CrashTestDummy;
     use crate::server_initializer::test_utils::{
-        LoggerInitializerWrapperMock, PrivilegeDropperMock,
+        LoggerInitParameters, LoggerInitializerWrapperMock, PrivilegeDropperMock,
     };
     use crate::sub_lib::crash_point::CrashPoint;
     use crate::test_utils::test_utils::ByteArrayReader;
@@ -186,7 +228,8 @@ pub mod tests {
 
         let privilege_dropper = PrivilegeDropperMock::new();
         let mut logger_initializer_wrapper_mock = LoggerInitializerWrapperMock::new();
-        let logger_init_parameters: Arc<Mutex<Vec<LevelFilter>>> = Arc::new(Mutex::new(vec![]));
+        let logger_init_parameters: Arc<Mutex<Vec<LoggerInitParameters>>> =
+            Arc::new(Mutex::new(vec![]));
         logger_initializer_wrapper_mock.init_parameters(&logger_init_parameters);
 
         let mut subject = ServerInitializer {
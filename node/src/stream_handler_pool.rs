@@ -14,6 +14,7 @@ use crate::sub_lib::cryptde::PublicKey;
 use crate::sub_lib::dispatcher;
 use crate::sub_lib::dispatcher::DispatcherSubs;
 use crate::sub_lib::dispatcher::Endpoint;
+use crate::sub_lib::hopper::UndeliverablePackageMessage;
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::neighborhood::DispatcherNodeQueryMessage;
 use crate::sub_lib::neighborhood::NodeQueryMessage;
@@ -101,6 +102,7 @@ pub struct StreamHandlerPool {
     self_subs: Option<StreamHandlerPoolSubs>,
     ask_neighborhood: Option<Recipient<DispatcherNodeQueryMessage>>,
     tell_neighborhood: Option<Recipient<RemoveNeighborMessage>>,
+    tell_hopper: Option<Recipient<UndeliverablePackageMessage>>,
     logger: Logger,
     stream_connector: Box<dyn StreamConnector>,
     channel_factory: Box<dyn FuturesChannelFactory<SequencedPacket>>,
@@ -223,6 +225,17 @@ impl Handler<DispatcherNodeQueryResponse> for StreamHandlerPool {
                     "No neighbor found at endpoint {:?}",
                     msg.context.endpoint
                 ));
+                if let Endpoint::Key(key) = msg.context.endpoint {
+                    self.tell_hopper
+                        .as_ref()
+                        .expect("StreamHandlerPool is unbound.")
+                        .try_send(UndeliverablePackageMessage {
+                            public_key: key,
+                            data: msg.context.data,
+                            reason: "no such neighbor".to_string(),
+                        })
+                        .expect("Hopper is dead");
+                }
                 return;
             }
         };
@@ -318,6 +331,7 @@ impl Handler<DispatcherNodeQueryResponse> for StreamHandlerPool {
                 let node_query_response_sub = subs.node_query_response;
                 let remove_sub = subs.remove_sub;
                 let tell_neighborhood = self.tell_neighborhood.clone().expect("Internal error");
+                let tell_hopper = self.tell_hopper.clone().expect("Internal error");
 
                 self.stream_writers
                     .insert(StreamWriterKey::from(peer_addr), None);
@@ -326,6 +340,7 @@ impl Handler<DispatcherNodeQueryResponse> for StreamHandlerPool {
                 let clandestine_discriminator_factories =
                     self.clandestine_discriminator_factories.clone();
                 let msg_data_len = msg.context.data.len();
+                let msg_data = msg.context.data.clone();
                 let peer_addr_e = peer_addr;
                 let key = msg
                     .result
@@ -348,6 +363,12 @@ impl Handler<DispatcherNodeQueryResponse> for StreamHandlerPool {
                         logger_me.error(format!("Stream to {} does not exist and could not be connected; discarding {} bytes: {}", peer_addr, msg_data_len, err));
                         remove_sub.try_send(RemoveStreamMsg { socket_addr: peer_addr_e }).expect("StreamHandlerPool is dead");
 
+                        tell_hopper.try_send(UndeliverablePackageMessage {
+                            public_key: key.clone(),
+                            data: msg_data,
+                            reason: format!("{}", err),
+                        }).expect("Hopper is dead");
+
                         let remove_node_message = RemoveNeighborMessage { public_key: key };
                         tell_neighborhood.try_send(remove_node_message).expect("Neighborhood is Dead");
                     });
@@ -376,6 +397,7 @@ impl Handler<PoolBindMessage> for StreamHandlerPool {
         self.self_subs = Some(msg.stream_handler_pool_subs);
         self.ask_neighborhood = Some(msg.neighborhood_subs.dispatcher_node_query);
         self.tell_neighborhood = Some(msg.neighborhood_subs.remove_neighbor);
+        self.tell_hopper = Some(msg.hopper_subs.undeliverable_package);
     }
 }
 
@@ -389,6 +411,7 @@ impl StreamHandlerPool {
             self_subs: None,
             ask_neighborhood: None,
             tell_neighborhood: None,
+            tell_hopper: None,
             logger: Logger::new("Dispatcher"),
             stream_connector: Box::new(StreamConnectorReal {}),
             channel_factory: Box::new(FuturesChannelFactoryReal {}),
@@ -555,6 +578,7 @@ mod tests {
                     dispatcher_subs: peer_actors.dispatcher,
                     stream_handler_pool_subs: subject_subs.clone(),
                     neighborhood_subs: peer_actors.neighborhood,
+                    hopper_subs: peer_actors.hopper,
                 })
                 .unwrap();
 
@@ -653,6 +677,7 @@ mod tests {
                     dispatcher_subs: peer_actors.dispatcher,
                     stream_handler_pool_subs: subject_subs.clone(),
                     neighborhood_subs: peer_actors.neighborhood,
+                    hopper_subs: peer_actors.hopper,
                 })
                 .unwrap();
 
@@ -720,6 +745,7 @@ mod tests {
                     dispatcher_subs: peer_actors.dispatcher,
                     stream_handler_pool_subs: subject_subs.clone(),
                     neighborhood_subs: peer_actors.neighborhood,
+                    hopper_subs: peer_actors.hopper,
                 })
                 .unwrap();
 
@@ -822,6 +848,7 @@ mod tests {
                     dispatcher_subs: peer_actors.dispatcher,
                     stream_handler_pool_subs: subject_subs.clone(),
                     neighborhood_subs: peer_actors.neighborhood,
+                    hopper_subs: peer_actors.hopper,
                 })
                 .unwrap();
 
@@ -896,6 +923,7 @@ mod tests {
                     dispatcher_subs: peer_actors.dispatcher,
                     stream_handler_pool_subs: subject_subs.clone(),
                     neighborhood_subs: peer_actors.neighborhood,
+                    hopper_subs: peer_actors.hopper,
                 })
                 .unwrap();
 
@@ -985,6 +1013,7 @@ mod tests {
                     dispatcher_subs: peer_actors.dispatcher,
                     stream_handler_pool_subs: subject_subs.clone(),
                     neighborhood_subs: peer_actors.neighborhood,
+                    hopper_subs: peer_actors.hopper,
                 })
                 .unwrap();
 
@@ -1075,6 +1104,7 @@ mod tests {
                     dispatcher_subs: peer_actors.dispatcher,
                     stream_handler_pool_subs: subject_subs.clone(),
                     neighborhood_subs: peer_actors.neighborhood,
+                    hopper_subs: peer_actors.hopper,
                 })
                 .unwrap();
 
@@ -1153,6 +1183,7 @@ mod tests {
                     dispatcher_subs: peer_actors.dispatcher,
                     stream_handler_pool_subs: subject_subs.clone(),
                     neighborhood_subs: peer_actors.neighborhood,
+                    hopper_subs: peer_actors.hopper,
                 })
                 .unwrap();
 
@@ -1182,6 +1213,118 @@ mod tests {
         );
     }
 
+    #[test]
+    fn node_query_response_handler_reports_undeliverable_package_to_hopper_when_neighbor_is_not_found(
+    ) {
+        let key = PublicKey::from(vec![0, 1, 2, 3]);
+        let expected_key = key.clone();
+        let (hopper, hopper_awaiter, hopper_recording_arc) = make_recorder();
+        thread::spawn(move || {
+            let system = System::new("node_query_response_handler_reports_undeliverable_package_to_hopper_when_neighbor_is_not_found");
+            let subject = StreamHandlerPool::new(vec![]);
+            let subject_addr: Addr<StreamHandlerPool> = subject.start();
+            let subject_subs = StreamHandlerPool::make_subs_from(&subject_addr);
+            let peer_actors = peer_actors_builder().hopper(hopper).build();
+            subject_subs
+                .bind
+                .try_send(PoolBindMessage {
+                    dispatcher_subs: peer_actors.dispatcher,
+                    stream_handler_pool_subs: subject_subs.clone(),
+                    neighborhood_subs: peer_actors.neighborhood,
+                    hopper_subs: peer_actors.hopper,
+                })
+                .unwrap();
+
+            subject_subs
+                .node_query_response
+                .try_send(DispatcherNodeQueryResponse {
+                    result: None,
+                    context: TransmitDataMsg {
+                        endpoint: Endpoint::Key(key),
+                        last_data: false,
+                        sequence_number: None,
+                        data: b"hello".to_vec(),
+                    },
+                })
+                .unwrap();
+
+            system.run();
+        });
+
+        hopper_awaiter.await_message_count(1);
+        let undeliverable_msg =
+            Recording::get::<UndeliverablePackageMessage>(&hopper_recording_arc, 0);
+        assert_eq!(
+            undeliverable_msg,
+            UndeliverablePackageMessage {
+                public_key: expected_key,
+                data: b"hello".to_vec(),
+                reason: "no such neighbor".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn when_stream_handler_pool_fails_to_create_nonexistent_stream_for_write_then_it_reports_undeliverable_package_to_hopper(
+    ) {
+        let public_key = PublicKey::from(vec![0, 1, 2, 3]);
+        let expected_key = public_key.clone();
+        let (hopper, hopper_awaiter, hopper_recording_arc) = make_recorder();
+        thread::spawn(move || {
+            let system = System::new("when_stream_handler_pool_fails_to_create_nonexistent_stream_for_write_then_it_reports_undeliverable_package_to_hopper");
+            let mut subject = StreamHandlerPool::new(vec![]);
+            subject.stream_connector = Box::new(
+                StreamConnectorMock::new().connect_pair_result(Err(Error::from(ErrorKind::Other))),
+            );
+            let subject_addr: Addr<StreamHandlerPool> = subject.start();
+            let subject_subs = StreamHandlerPool::make_subs_from(&subject_addr);
+            let peer_actors = peer_actors_builder().hopper(hopper).build();
+            subject_subs
+                .bind
+                .try_send(PoolBindMessage {
+                    dispatcher_subs: peer_actors.dispatcher,
+                    stream_handler_pool_subs: subject_subs.clone(),
+                    neighborhood_subs: peer_actors.neighborhood,
+                    hopper_subs: peer_actors.hopper,
+                })
+                .unwrap();
+
+            subject_subs
+                .node_query_response
+                .try_send(DispatcherNodeQueryResponse {
+                    result: Some(NodeQueryResponseMetadata::new(
+                        public_key.clone(),
+                        Some(NodeAddr::new(
+                            &IpAddr::V4(Ipv4Addr::new(1, 2, 3, 5)),
+                            &vec![7000],
+                        )),
+                        rate_pack(100),
+                    )),
+                    context: TransmitDataMsg {
+                        endpoint: Endpoint::Key(public_key),
+                        last_data: false,
+                        sequence_number: None,
+                        data: b"hello".to_vec(),
+                    },
+                })
+                .unwrap();
+
+            system.run();
+        });
+
+        hopper_awaiter.await_message_count(1);
+        let undeliverable_msg =
+            Recording::get::<UndeliverablePackageMessage>(&hopper_recording_arc, 0);
+        assert_eq!(
+            undeliverable_msg,
+            UndeliverablePackageMessage {
+                public_key: expected_key,
+                data: b"hello".to_vec(),
+                reason: "other os error".to_string(),
+            }
+        );
+    }
+
     #[test]
     fn node_query_response_handler_does_not_try_to_write_when_neighbor_ip_is_not_known() {
         init_test_logging();
@@ -1201,6 +1344,7 @@ mod tests {
                     dispatcher_subs: peer_actors.dispatcher,
                     stream_handler_pool_subs: subject_subs.clone(),
                     neighborhood_subs: peer_actors.neighborhood,
+                    hopper_subs: peer_actors.hopper,
                 })
                 .unwrap();
 
@@ -1267,6 +1411,7 @@ mod tests {
                     dispatcher_subs: peer_actors.dispatcher,
                     stream_handler_pool_subs: subject_subs.clone(),
                     neighborhood_subs: peer_actors.neighborhood,
+                    hopper_subs: peer_actors.hopper,
                 })
                 .unwrap();
 
@@ -1392,6 +1537,7 @@ mod tests {
                     dispatcher_subs: peer_actors.dispatcher,
                     stream_handler_pool_subs: subject_subs.clone(),
                     neighborhood_subs: peer_actors.neighborhood,
+                    hopper_subs: peer_actors.hopper,
                 })
                 .unwrap();
 
@@ -1461,6 +1607,7 @@ mod tests {
                 dispatcher_subs: peer_actors.dispatcher,
                 stream_handler_pool_subs: subject_subs.clone(),
                 neighborhood_subs: peer_actors.neighborhood,
+                hopper_subs: peer_actors.hopper,
             })
             .unwrap();
 
@@ -1510,6 +1657,7 @@ mod tests {
                     dispatcher_subs: peer_actors.dispatcher,
                     stream_handler_pool_subs: subject_subs.clone(),
                     neighborhood_subs: peer_actors.neighborhood,
+                    hopper_subs: peer_actors.hopper,
                 })
                 .unwrap();
 
@@ -1584,6 +1732,7 @@ mod tests {
                     dispatcher_subs: peer_actors.dispatcher,
                     stream_handler_pool_subs: subject_subs.clone(),
                     neighborhood_subs: peer_actors.neighborhood,
+                    hopper_subs: peer_actors.hopper,
                 })
                 .unwrap();
 
@@ -1644,6 +1793,7 @@ mod tests {
                     dispatcher_subs: peer_actors.dispatcher,
                     stream_handler_pool_subs: subject_subs.clone(),
                     neighborhood_subs: peer_actors.neighborhood,
+                    hopper_subs: peer_actors.hopper,
                 })
                 .unwrap();
 
@@ -2,6 +2,7 @@
 use crate::configuration::PortConfiguration;
 use crate::stream_handler_pool::StreamHandlerPoolSubs;
 use crate::sub_lib::dispatcher::DispatcherSubs;
+use crate::sub_lib::hopper::HopperSubs;
 use crate::sub_lib::neighborhood::NeighborhoodSubs;
 use crate::sub_lib::stream_connector::ConnectionInfo;
 use actix::Message;
@@ -41,6 +42,7 @@ pub struct PoolBindMessage {
     pub dispatcher_subs: DispatcherSubs,
     pub stream_handler_pool_subs: StreamHandlerPoolSubs,
     pub neighborhood_subs: NeighborhoodSubs,
+    pub hopper_subs: HopperSubs,
 }
 
 impl Debug for PoolBindMessage {
@@ -62,10 +64,12 @@ mod tests {
         let dispatcher_subs = peer_actors_builder().build().dispatcher;
         let stream_handler_pool_subs = make_stream_handler_pool_subs_from(None);
         let neighborhood_subs = peer_actors_builder().build().neighborhood;
+        let hopper_subs = peer_actors_builder().build().hopper;
         let subject = PoolBindMessage {
             dispatcher_subs,
             stream_handler_pool_subs,
             neighborhood_subs,
+            hopper_subs,
         };
 
         let result = format!("{:?}", subject);
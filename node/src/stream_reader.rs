@@ -4,6 +4,7 @@ use crate::discriminator::DiscriminatorFactory;
 use crate::proxy_server::http_protocol_pack::HttpProtocolPack;
 use crate::stream_messages::*;
 use crate::sub_lib::dispatcher;
+use crate::sub_lib::logger::LogFields;
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::sequencer::Sequencer;
 use crate::sub_lib::tokio_wrappers::ReadHalfWrapper;
@@ -46,8 +47,10 @@ impl Future for StreamReaderReal {
                     return Ok(Async::Ready(()));
                 }
                 Ok(Async::Ready(length)) => {
-                    self.logger
-                        .debug(format!("Read {}-byte chunk from port {}", length, port));
+                    self.logger.debug_with(
+                        LogFields::new().peer_addr(self.peer_addr).bytes(length),
+                        format!("Read {}-byte chunk from port {}", length, port),
+                    );
                     self.wrangle_discriminators(&buf, length)
                 }
                 Err(e) => {
@@ -1,4 +1,5 @@
 use crate::sub_lib::channel_wrappers::ReceiverWrapper;
+use crate::sub_lib::logger::LogFields;
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::sequence_buffer::SequencedPacket;
 use crate::sub_lib::tokio_wrappers::WriteHalfWrapper;
@@ -53,11 +54,14 @@ impl Future for StreamWriterUnsorted {
                             }
                         }
                         Ok(Async::Ready(len)) => {
-                            self.logger.debug(format!(
-                                "Wrote {}/{} bytes of clandestine data",
-                                len,
-                                &packet.data.len()
-                            ));
+                            self.logger.debug_with(
+                                LogFields::new().bytes(len),
+                                format!(
+                                    "Wrote {}/{} bytes of clandestine data",
+                                    len,
+                                    &packet.data.len()
+                                ),
+                            );
                             if len != packet.data.len() {
                                 self.logger.debug(format!(
                                     "rescheduling {} bytes",
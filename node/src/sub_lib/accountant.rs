@@ -9,9 +9,6 @@ use std::time::Duration;
 lazy_static! {
     // TODO: This is not a real wallet address. We need a Substratum wallet to accept default payments.
     pub static ref DEFAULT_EARNING_WALLET: Wallet = Wallet::new("0xAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA");
-    // TODO: The consuming wallet should never be defaulted; it should always come in from a
-    // (possibly-complicated) command-line parameter, or the bidirectional GUI.
-    pub static ref TEMPORARY_CONSUMING_WALLET: Wallet = Wallet::new ("0xFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF");
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -26,6 +23,13 @@ pub struct AccountantSubs {
     pub report_exit_service_provided: Recipient<ReportExitServiceProvidedMessage>,
     pub report_routing_service_consumed: Recipient<ReportRoutingServiceConsumedMessage>,
     pub report_exit_service_consumed: Recipient<ReportExitServiceConsumedMessage>,
+    pub set_payable_scan_interval: Recipient<SetPayableScanIntervalMessage>,
+}
+
+// Reschedules payable scans; the next one comes a full interval after the message arrives
+#[derive(Clone, PartialEq, Debug, Message)]
+pub struct SetPayableScanIntervalMessage {
+    pub payable_scan_interval: Duration,
 }
 
 #[derive(Clone, PartialEq, Debug, Message)]
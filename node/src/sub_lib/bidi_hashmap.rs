@@ -1,5 +1,6 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
+use std::collections::hash_map::Iter;
 use std::collections::HashMap;
 use std::hash::Hash;
 
@@ -49,6 +50,10 @@ where
         })
     }
 
+    pub fn iter(&self) -> Iter<'_, A, B> {
+        self.a_to_b.iter()
+    }
+
     pub fn remove_b(&mut self, b: &B) -> Option<A> {
         self.b_to_a.remove(b).map(|a| {
             self.a_to_b.remove(&a);
@@ -71,6 +76,18 @@ mod tests {
         assert_eq!(result, Some(5));
     }
 
+    #[test]
+    fn iter_visits_every_pair() {
+        let mut subject = BidiHashMap::new();
+        subject.insert("Polly", 5);
+        subject.insert("Molly", 6);
+
+        let mut result = subject.iter().collect::<Vec<(&&str, &i32)>>();
+        result.sort();
+
+        assert_eq!(result, vec![(&"Molly", &6), (&"Polly", &5)]);
+    }
+
     #[test]
     fn new_insert_and_b_to_a() {
         let mut subject = BidiHashMap::new();
@@ -0,0 +1,171 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde_derive::{Deserialize, Serialize};
+use std::io::Read;
+use std::io::Write;
+
+// Compressed payloads from other Nodes are never allowed to expand beyond this.
+pub const MAX_DECOMPRESSED_LEN: usize = 4 * 1024 * 1024;
+
+/// Describes the data in the SequencedPacket of a ClientRequestPayload or ClientResponsePayload.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PayloadCompression {
+    /// Uncompressed; the sender can't decompress anything sent back to it
+    None,
+    /// Uncompressed, because compressing it wouldn't have made it any smaller; the sender can
+    /// decompress anything sent back to it
+    Available,
+    /// Compressed
+    Compressed,
+}
+
+impl Default for PayloadCompression {
+    fn default() -> Self {
+        PayloadCompression::None
+    }
+}
+
+impl PayloadCompression {
+    pub fn is_understood(self) -> bool {
+        self != PayloadCompression::None
+    }
+}
+
+pub fn compress_if_smaller(data: Vec<u8>) -> (Vec<u8>, PayloadCompression) {
+    let compressed = compress(&data);
+    if compressed.len() < data.len() {
+        (compressed, PayloadCompression::Compressed)
+    } else {
+        (data, PayloadCompression::Available)
+    }
+}
+
+// Compressed data is a raw DEFLATE stream (RFC 1951), without zlib or gzip framing.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut encoder = DeflateEncoder::new(Vec::with_capacity(data.len()), Compression::default());
+    encoder
+        .write_all(data)
+        .and_then(|_| encoder.finish())
+        .expect("Compressing into memory failed")
+}
+
+pub fn decompress(data: &[u8], max_len: usize) -> Result<Vec<u8>, String> {
+    let mut output = Vec::with_capacity(data.len() * 2);
+    DeflateDecoder::new(data)
+        .take(max_len as u64 + 1)
+        .read_to_end(&mut output)
+        .map_err(|e| format!("Can't decompress {}-byte data: {}", data.len(), e))?;
+    if output.len() > max_len {
+        return Err(format!("Compressed data expands beyond {} bytes", max_len));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compression_round_trips_an_http_request() {
+        let data = b"GET /index.html HTTP/1.1\r\nHost: nowhere.com\r\nAccept: text/html\r\nAccept-Encoding: identity\r\nAccept-Language: en-US\r\n\r\n".to_vec();
+
+        let compressed = compress(&data);
+        let result = decompress(&compressed, MAX_DECOMPRESSED_LEN).unwrap();
+
+        assert!(compressed.len() < data.len());
+        assert_eq!(result, data);
+    }
+
+    #[test]
+    fn compression_round_trips_long_runs_and_long_literals() {
+        let mut data = vec![b'x'; 1000];
+        data.extend((0..1000).map(|i| ((i * 7919) % 251) as u8));
+        data.extend(vec![b'y'; 300]);
+
+        let compressed = compress(&data);
+        let result = decompress(&compressed, MAX_DECOMPRESSED_LEN).unwrap();
+
+        assert_eq!(result, data);
+    }
+
+    #[test]
+    fn compression_round_trips_empty_data() {
+        let compressed = compress(&[]);
+
+        assert_eq!(
+            decompress(&compressed, MAX_DECOMPRESSED_LEN).unwrap(),
+            Vec::<u8>::new()
+        );
+    }
+
+    #[test]
+    fn compress_if_smaller_leaves_incompressible_data_alone() {
+        let data = vec![1, 2, 3, 4, 5, 6, 7, 8];
+
+        let result = compress_if_smaller(data.clone());
+
+        assert_eq!(result, (data, PayloadCompression::Available));
+    }
+
+    #[test]
+    fn compress_if_smaller_compresses_repetitive_data() {
+        let data = b"booga booga booga booga booga booga".to_vec();
+
+        let (compressed, compression) = compress_if_smaller(data.clone());
+
+        assert_eq!(compression, PayloadCompression::Compressed);
+        assert_eq!(decompress(&compressed, MAX_DECOMPRESSED_LEN).unwrap(), data);
+    }
+
+    #[test]
+    fn decompress_complains_about_data_that_is_not_deflate() {
+        let result = decompress(&[0xFF, 0xFF, 0xFF, 0xFF], MAX_DECOMPRESSED_LEN);
+
+        assert_eq!(
+            result
+                .unwrap_err()
+                .starts_with("Can't decompress 4-byte data: "),
+            true
+        );
+    }
+
+    #[test]
+    fn decompress_complains_about_truncated_data() {
+        let compressed = compress(&vec![b'x'; 1000]);
+
+        let result = decompress(&compressed[..(compressed.len() - 1)], MAX_DECOMPRESSED_LEN);
+
+        assert_eq!(result.is_err(), true);
+    }
+
+    #[test]
+    fn decompress_refuses_to_expand_beyond_limit() {
+        let compressed = compress(&vec![0; 201]);
+
+        let result = decompress(&compressed, 200);
+
+        assert_eq!(
+            result,
+            Err("Compressed data expands beyond 200 bytes".to_string())
+        );
+    }
+
+    #[test]
+    fn decompress_accepts_data_that_expands_exactly_to_limit() {
+        let compressed = compress(&vec![0; 200]);
+
+        let result = decompress(&compressed, 200);
+
+        assert_eq!(result, Ok(vec![0; 200]));
+    }
+
+    #[test]
+    fn payload_compression_defaults_to_none() {
+        assert_eq!(PayloadCompression::default(), PayloadCompression::None);
+        assert_eq!(PayloadCompression::None.is_understood(), false);
+        assert_eq!(PayloadCompression::Available.is_understood(), true);
+        assert_eq!(PayloadCompression::Compressed.is_understood(), true);
+    }
+}
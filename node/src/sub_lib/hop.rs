@@ -3,8 +3,11 @@ use crate::sub_lib::cryptde::decodex;
 use crate::sub_lib::cryptde::encodex;
 use crate::sub_lib::cryptde::CryptDE;
 use crate::sub_lib::cryptde::CryptData;
+use crate::sub_lib::cryptde::PlainData;
 use crate::sub_lib::cryptde::PublicKey;
 use crate::sub_lib::dispatcher::Component;
+use crate::sub_lib::hopper::IncipientCoresPackage;
+use crate::sub_lib::route::Route;
 use crate::sub_lib::wallet::Wallet;
 use serde_derive::{Deserialize, Serialize};
 
@@ -14,16 +17,76 @@ use serde_derive::{Deserialize, Serialize};
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct LiveHop {
     pub public_key: PublicKey,
-    pub consuming_wallet: Option<Wallet>,
+    pub payer: Option<Payer>,
     pub component: Component,
+    // Launched by the Node that decrypts this hop if it can't forward the package to public_key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_return: Option<FailureReturn>,
 }
 
 impl LiveHop {
-    pub fn new(key: &PublicKey, consuming_wallet: Option<Wallet>, component: Component) -> Self {
+    pub fn new(key: &PublicKey, payer: Option<Payer>, component: Component) -> Self {
         LiveHop {
             public_key: key.clone(),
-            consuming_wallet: consuming_wallet.clone(),
+            payer,
             component,
+            failure_return: None,
+        }
+    }
+
+    pub fn consuming_wallet(&self) -> Option<Wallet> {
+        self.payer.as_ref().map(|payer| payer.wallet.clone())
+    }
+
+    // hop_owner_key is the key this hop will be encrypted for; next_hop_opt is the encrypted hop
+    // that will follow it in the Route, if there is one.
+    pub fn sign_payer(
+        &mut self,
+        wallet: &Wallet,
+        hop_owner_key: &PublicKey,
+        nonce: u64,
+        reusable: bool,
+        next_hop_opt: Option<&CryptData>,
+    ) -> Result<(), String> {
+        let claim = self.claim(wallet, hop_owner_key, nonce, reusable, next_hop_opt)?;
+        let proof = wallet
+            .sign(&claim)
+            .map_err(|e| format!("Couldn't sign payer claim: {}", e))?;
+        self.payer = Some(Payer {
+            wallet: wallet.clone(),
+            nonce,
+            reusable,
+            bound_to_next_hop: next_hop_opt.is_some(),
+            proof,
+        });
+        Ok(())
+    }
+
+    // hop_owner_key must be the public key of the Node that decrypted the hop, and following_hops
+    // the rest of the Route after it.
+    pub fn payer_owns_secret_key(
+        &self,
+        hop_owner_key: &PublicKey,
+        following_hops: &[CryptData],
+    ) -> bool {
+        let payer = match self.payer {
+            Some(ref payer) => payer,
+            None => return false,
+        };
+        let next_hop_opt = match (payer.bound_to_next_hop, following_hops.first()) {
+            (false, _) => None,
+            (true, Some(next_hop)) => Some(next_hop),
+            (true, None) => return false,
+        };
+        match self.claim(
+            &payer.wallet,
+            hop_owner_key,
+            payer.nonce,
+            payer.reusable,
+            next_hop_opt,
+        ) {
+            Ok(claim) => payer.wallet.verify(&claim, &payer.proof),
+            Err(_) => false,
         }
     }
 
@@ -38,25 +101,169 @@ impl LiveHop {
     ) -> Result<CryptData, String> {
         encodex(cryptde, public_key, &self)
     }
+
+    fn claim(
+        &self,
+        wallet: &Wallet,
+        hop_owner_key: &PublicKey,
+        nonce: u64,
+        reusable: bool,
+        next_hop_opt: Option<&CryptData>,
+    ) -> Result<PlainData, String> {
+        serde_cbor::ser::to_vec(&(
+            hop_owner_key,
+            &self.public_key,
+            self.component,
+            &wallet.address,
+            nonce,
+            reusable,
+            next_hop_opt,
+        ))
+        .map(PlainData::from)
+        .map_err(|e| format!("Couldn't serialize payer claim: {:?}", e))
+    }
+}
+
+// A consuming wallet's promise to pay for a hop, signed with the wallet's private key. The proof
+// covers the hop, the Node it's encrypted for, and the encrypted hop after it (whose own proof
+// covers the one after that), so it can't be moved to another hop, Node or Route. Only the Node
+// that decrypts the hop sees it, and a Node refuses to honor a single-use nonce twice.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Payer {
+    pub wallet: Wallet,
+    pub nonce: u64,
+    // Back-segment hops carry every response to a request, so they can't be single-use.
+    pub reusable: bool,
+    pub bound_to_next_hop: bool,
+    pub proof: CryptData,
+}
+
+// What a Node launches back toward the originator if it can't forward a package. Rather than a
+// whole Route of its own, each hop carries just the originator's RouteFailure and where its way
+// back begins: the failure Routes of the Nodes in the over segment are tails of one another, so the
+// originator appends the longest one to the Route once and each Node uses its own tail of it.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct FailureReturn {
+    pub route_offset: usize,
+    pub payload: CryptData,
+}
+
+impl FailureReturn {
+    // route must be the Route as the Node received it, still topped by the hop carrying this.
+    pub fn to_package(&self, route: &Route) -> Option<IncipientCoresPackage> {
+        if self.route_offset >= route.hops.len() {
+            return None;
+        }
+        Some(IncipientCoresPackage {
+            route: Route {
+                hops: route.hops[self.route_offset..].to_vec(),
+            },
+            payload: self.payload.clone(),
+            failure_reason_opt: None,
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::sub_lib::cryptde_null::CryptDENull;
+    use crate::test_utils::test_utils::make_paying_wallet;
 
     #[test]
     fn can_construct_hop() {
         let subject = LiveHop::new(
             &PublicKey::new("key".as_bytes()),
-            Some(Wallet::new("wallet")),
+            None,
             Component::Neighborhood,
         );
 
         assert_eq!(subject.public_key, PublicKey::new("key".as_bytes()));
+        assert_eq!(subject.payer, None);
+        assert_eq!(subject.consuming_wallet(), None);
         assert_eq!(subject.component, Component::Neighborhood);
     }
 
+    #[test]
+    fn failure_return_package_takes_the_route_from_its_offset() {
+        let subject = FailureReturn {
+            route_offset: 2,
+            payload: CryptData::new(b"payload"),
+        };
+        let route = Route {
+            hops: vec![
+                CryptData::new(b"zero"),
+                CryptData::new(b"one"),
+                CryptData::new(b"two"),
+                CryptData::new(b"three"),
+            ],
+        };
+
+        let result = subject.to_package(&route);
+
+        assert_eq!(
+            result,
+            Some(IncipientCoresPackage {
+                route: Route {
+                    hops: vec![CryptData::new(b"two"), CryptData::new(b"three")],
+                },
+                payload: CryptData::new(b"payload"),
+                failure_reason_opt: None,
+            })
+        );
+    }
+
+    #[test]
+    fn failure_return_package_is_none_when_the_offset_is_past_the_end_of_the_route() {
+        let subject = FailureReturn {
+            route_offset: 2,
+            payload: CryptData::new(b"payload"),
+        };
+        let route = Route {
+            hops: vec![CryptData::new(b"zero"), CryptData::new(b"one")],
+        };
+
+        let result = subject.to_package(&route);
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn sign_payer_adds_payer_for_wallet() {
+        let wallet = make_paying_wallet(b"wallet");
+        let mut subject = LiveHop::new(&PublicKey::new(b"key"), None, Component::Hopper);
+
+        subject
+            .sign_payer(&wallet, &PublicKey::new(b"owner"), 1234, false, None)
+            .unwrap();
+
+        let payer = subject.payer.clone().unwrap();
+        assert_eq!(payer.wallet, wallet);
+        assert_eq!(payer.nonce, 1234);
+        assert_eq!(payer.reusable, false);
+        assert_eq!(payer.bound_to_next_hop, false);
+        assert_eq!(subject.consuming_wallet(), Some(wallet));
+    }
+
+    #[test]
+    fn sign_payer_complains_about_wallet_without_private_key() {
+        let mut subject = LiveHop::new(&PublicKey::new(b"key"), None, Component::Hopper);
+
+        let result = subject.sign_payer(
+            &Wallet::new("0xabcdef"),
+            &PublicKey::new(b"owner"),
+            1234,
+            false,
+            None,
+        );
+
+        assert_eq!(
+            result,
+            Err("Couldn't sign payer claim: No private key for wallet 0xabcdef".to_string())
+        );
+        assert_eq!(subject.payer, None);
+    }
+
     #[test]
     fn decode_can_handle_errors() {
         let cryptde = CryptDENull::new();
@@ -76,31 +283,41 @@ mod tests {
     #[test]
     fn encode_decode() {
         let cryptde = CryptDENull::new();
-        let consuming_wallet = Wallet::new("wallet");
         let encode_key = cryptde.public_key();
+        let mut payer_hop = LiveHop::new(&PublicKey::new(b""), None, Component::Hopper);
+        payer_hop
+            .sign_payer(
+                &make_paying_wallet(b"wallet"),
+                &encode_key,
+                1234,
+                true,
+                None,
+            )
+            .unwrap();
+        let payer = payer_hop.payer.unwrap();
         let hopper_hop = LiveHop::new(
             &&PublicKey::new(&[4, 3, 2, 1]),
-            Some(consuming_wallet.clone()),
+            Some(payer.clone()),
             Component::Hopper,
         );
         let neighborhood_hop = LiveHop::new(
             &PublicKey::new(&[1, 2, 3, 4]),
-            Some(consuming_wallet.clone()),
+            Some(payer.clone()),
             Component::Neighborhood,
         );
         let proxy_server_hop = LiveHop::new(
             &PublicKey::new(&[127, 128]),
-            Some(consuming_wallet.clone()),
+            Some(payer.clone()),
             Component::ProxyServer,
         );
         let proxy_client_hop = LiveHop::new(
             &PublicKey::new(&[253, 254, 255]),
-            Some(consuming_wallet.clone()),
+            Some(payer.clone()),
             Component::ProxyClient,
         );
         let relay_hop = LiveHop::new(
             &PublicKey::new(&[123]),
-            Some(consuming_wallet.clone()),
+            Some(payer.clone()),
             Component::Hopper,
         );
 
@@ -131,4 +348,107 @@ mod tests {
             relay_hop
         );
     }
+
+    fn make_signed_hop(owner_key: &PublicKey, next_hop_opt: Option<&CryptData>) -> LiveHop {
+        let mut hop = LiveHop::new(&PublicKey::new(b"next"), None, Component::Hopper);
+        hop.sign_payer(
+            &make_paying_wallet(b"wallet"),
+            owner_key,
+            1234,
+            false,
+            next_hop_opt,
+        )
+        .unwrap();
+        hop
+    }
+
+    #[test]
+    fn payer_owns_secret_key_for_the_node_and_route_it_was_made_for() {
+        let owner_key = PublicKey::new(b"owner");
+        let next_hop = CryptData::new(b"next hop");
+        let unbound = make_signed_hop(&owner_key, None);
+        let bound = make_signed_hop(&owner_key, Some(&next_hop));
+
+        assert_eq!(unbound.payer_owns_secret_key(&owner_key, &[]), true);
+        assert_eq!(
+            bound.payer_owns_secret_key(&owner_key, &[next_hop, CryptData::new(b"garbage")]),
+            true
+        );
+    }
+
+    #[test]
+    fn payer_does_not_own_secret_key_for_a_different_node() {
+        let subject = make_signed_hop(&PublicKey::new(b"owner"), None);
+
+        assert_eq!(
+            subject.payer_owns_secret_key(&PublicKey::new(b"thief"), &[]),
+            false
+        );
+    }
+
+    #[test]
+    fn payer_does_not_own_secret_key_for_a_different_route() {
+        let owner_key = PublicKey::new(b"owner");
+        let subject = make_signed_hop(&owner_key, Some(&CryptData::new(b"next hop")));
+
+        assert_eq!(
+            subject.payer_owns_secret_key(&owner_key, &[CryptData::new(b"other hop")]),
+            false
+        );
+        assert_eq!(subject.payer_owns_secret_key(&owner_key, &[]), false);
+    }
+
+    #[test]
+    fn payer_does_not_own_secret_key_when_hop_or_claim_is_altered() {
+        let owner_key = PublicKey::new(b"owner");
+        let original = make_signed_hop(&owner_key, None);
+        let original_payer = original.payer.clone().unwrap();
+        let with_payer = |payer: Payer| LiveHop {
+            payer: Some(payer),
+            ..original.clone()
+        };
+        let with_other_wallet = with_payer(Payer {
+            wallet: Wallet::new("victim"),
+            ..original_payer.clone()
+        });
+        let with_other_nonce = with_payer(Payer {
+            nonce: 4321,
+            ..original_payer.clone()
+        });
+        let made_reusable = with_payer(Payer {
+            reusable: true,
+            ..original_payer.clone()
+        });
+        let with_other_next_key = LiveHop {
+            public_key: PublicKey::new(b"elsewhere"),
+            ..original.clone()
+        };
+        let with_other_component = LiveHop {
+            component: Component::ProxyClient,
+            ..original.clone()
+        };
+        let without_payer = LiveHop {
+            payer: None,
+            ..original
+        };
+
+        assert_eq!(
+            with_other_wallet.payer_owns_secret_key(&owner_key, &[]),
+            false
+        );
+        assert_eq!(
+            with_other_nonce.payer_owns_secret_key(&owner_key, &[]),
+            false
+        );
+        assert_eq!(made_reusable.payer_owns_secret_key(&owner_key, &[]), false);
+        assert_eq!(
+            with_other_next_key.payer_owns_secret_key(&owner_key, &[]),
+            false
+        );
+        assert_eq!(
+            with_other_component.payer_owns_secret_key(&owner_key, &[]),
+            false
+        );
+        assert_eq!(without_payer.payer_owns_secret_key(&owner_key, &[]), false);
+    }
 }
@@ -5,6 +5,9 @@ use crate::sub_lib::cryptde::CryptDE;
 use crate::sub_lib::cryptde::CryptData;
 use crate::sub_lib::cryptde::PublicKey;
 use crate::sub_lib::dispatcher::InboundClientData;
+use crate::sub_lib::neighborhood::Heartbeat;
+use crate::sub_lib::neighborhood::RelayRequest;
+use crate::sub_lib::neighborhood::SetRatePackMessage;
 use crate::sub_lib::node_addr::NodeAddr;
 use crate::sub_lib::peer_actors::BindMessage;
 use crate::sub_lib::proxy_client::{ClientResponsePayload, DnsResolveFailure};
@@ -54,10 +57,14 @@ impl NoLookupIncipientCoresPackage {
 }
 
 /// New CORES package about to be sent to the Hopper and thence put on the Substratum Network
-#[derive(Clone, Debug, PartialEq, Message)]
+#[derive(Clone, Debug, PartialEq, Eq, Message, Serialize, Deserialize)]
 pub struct IncipientCoresPackage {
     pub route: Route,
     pub payload: CryptData,
+    /// Why the package carrying this package's failure return couldn't be forwarded; set only on
+    /// failure returns. See LiveCoresPackage::failure_reason_opt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_reason_opt: Option<String>,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -66,6 +73,37 @@ pub enum MessageType {
     ClientResponse(ClientResponsePayload),
     Gossip(Gossip),
     DnsResolveFailed(DnsResolveFailure),
+    RouteFailed(RouteFailure),
+    Heartbeat(Heartbeat),
+    RelayRequest(RelayRequest),
+}
+
+/// Tells the originator of a Route that a Node along it couldn't forward a CORES package to the
+/// next Node. The originator builds these itself when it makes the Route, since the Nodes along
+/// the way don't know where the Route started.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RouteFailure {
+    pub reporting_key: PublicKey,
+    pub failed_key: PublicKey,
+    /// Empty as the originator builds it; filled in from the failure return's
+    /// failure_reason_opt when it arrives back at the originator.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub reason: String,
+}
+
+impl Into<MessageType> for RouteFailure {
+    fn into(self) -> MessageType {
+        MessageType::RouteFailed(self)
+    }
+}
+
+/// Sent by the StreamHandlerPool to the Hopper when data it was asked to transmit to another Node
+/// couldn't be delivered, either because the Node is unknown or because it couldn't be connected.
+#[derive(Clone, Debug, PartialEq, Message)]
+pub struct UndeliverablePackageMessage {
+    pub public_key: PublicKey,
+    pub data: Vec<u8>,
+    pub reason: String,
 }
 
 impl IncipientCoresPackage {
@@ -83,6 +121,7 @@ impl IncipientCoresPackage {
         Ok(IncipientCoresPackage {
             route,
             payload: encrypted_payload,
+            failure_reason_opt: None,
         })
     }
 }
@@ -129,6 +168,8 @@ pub struct HopperSubs {
     pub from_hopper_client: Recipient<IncipientCoresPackage>,
     pub from_hopper_client_no_lookup: Recipient<NoLookupIncipientCoresPackage>,
     pub from_dispatcher: Recipient<InboundClientData>,
+    pub undeliverable_package: Recipient<UndeliverablePackageMessage>,
+    pub set_rate_pack: Recipient<SetRatePackMessage>,
 }
 
 #[cfg(test)]
@@ -139,6 +180,7 @@ mod tests {
     use crate::sub_lib::dispatcher::Component;
     use crate::sub_lib::route::RouteSegment;
     use crate::test_utils::test_utils::make_meaningless_message_type;
+    use crate::test_utils::test_utils::make_paying_wallet;
     use std::str::FromStr;
 
     #[test]
@@ -185,7 +227,7 @@ mod tests {
     #[test]
     fn incipient_cores_package_is_created_correctly() {
         let cryptde = CryptDENull::new();
-        let consuming_wallet = Wallet::new("wallet");
+        let consuming_wallet = make_paying_wallet(b"wallet");
         let key12 = cryptde.public_key();
         let key34 = PublicKey::new(&[3, 4]);
         let key56 = PublicKey::new(&[5, 6]);
@@ -236,7 +278,7 @@ mod tests {
         let a_key = PublicKey::new(&[65, 65, 65]);
         let b_key = PublicKey::new(&[66, 66, 66]);
         let cryptde = CryptDENull::new();
-        let consuming_wallet = Wallet::new("wallet");
+        let consuming_wallet = make_paying_wallet(b"wallet");
         let route = Route::one_way(
             RouteSegment::new(vec![&a_key, &b_key], Component::Neighborhood),
             &cryptde,
@@ -254,7 +296,10 @@ mod tests {
         );
 
         assert_eq!(subject.immediate_neighbor_ip, immediate_neighbor_ip);
-        assert_eq!(subject.consuming_wallet, Some(Wallet::new("wallet")));
+        assert_eq!(
+            subject.consuming_wallet,
+            Some(make_paying_wallet(b"wallet"))
+        );
         assert_eq!(subject.remaining_route, route);
         assert_eq!(subject.payload, payload);
         assert_eq!(subject.payload_len, 42);
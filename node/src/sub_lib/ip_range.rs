@@ -0,0 +1,224 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::str::FromStr;
+
+// A block of IP addresses in CIDR notation, like 10.0.0.0/8 or 2001:db8::/32. A bare address
+// is a range containing only that address.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct IpRange {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpRange {
+    pub fn new(network: IpAddr, prefix_len: u8) -> Result<IpRange, String> {
+        if prefix_len > Self::max_prefix_len(&network) {
+            return Err(format!(
+                "Prefix length {} is too long for {}",
+                prefix_len, network
+            ));
+        }
+        Ok(IpRange {
+            network,
+            prefix_len,
+        })
+    }
+
+    // The range of the given length that contains the given address. Prefix lengths too long
+    // for the address are shortened to fit.
+    pub fn enclosing(ip_addr: &IpAddr, prefix_len: u8) -> IpRange {
+        let prefix_len = prefix_len.min(Self::max_prefix_len(ip_addr));
+        let network = match ip_addr {
+            IpAddr::V4(ip_addr) => {
+                let mut octets = ip_addr.octets();
+                Self::clear_host_bits(&mut octets, prefix_len as usize);
+                IpAddr::V4(Ipv4Addr::from(octets))
+            }
+            IpAddr::V6(ip_addr) => {
+                let mut octets = ip_addr.octets();
+                Self::clear_host_bits(&mut octets, prefix_len as usize);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+        };
+        IpRange {
+            network,
+            prefix_len,
+        }
+    }
+
+    pub fn contains(&self, ip_addr: &IpAddr) -> bool {
+        match (self.network, ip_addr) {
+            (IpAddr::V4(network), IpAddr::V4(ip_addr)) => Self::prefixes_match(
+                &network.octets(),
+                &ip_addr.octets(),
+                self.prefix_len as usize,
+            ),
+            (IpAddr::V6(network), IpAddr::V6(ip_addr)) => Self::prefixes_match(
+                &network.octets(),
+                &ip_addr.octets(),
+                self.prefix_len as usize,
+            ),
+            _ => false,
+        }
+    }
+
+    fn max_prefix_len(ip_addr: &IpAddr) -> u8 {
+        match ip_addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        }
+    }
+
+    fn clear_host_bits(octets: &mut [u8], prefix_len: usize) {
+        octets.iter_mut().enumerate().for_each(|(index, octet)| {
+            let bits_kept = prefix_len.saturating_sub(index * 8).min(8);
+            *octet &= !(0xFFu16 >> bits_kept) as u8;
+        });
+    }
+
+    fn prefixes_match(network: &[u8], ip_addr: &[u8], prefix_len: usize) -> bool {
+        let whole_bytes = prefix_len / 8;
+        if network[..whole_bytes] != ip_addr[..whole_bytes] {
+            return false;
+        }
+        let remaining_bits = prefix_len % 8;
+        if remaining_bits == 0 {
+            return true;
+        }
+        let mask = 0xFFu8 << (8 - remaining_bits);
+        (network[whole_bytes] & mask) == (ip_addr[whole_bytes] & mask)
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let pieces: Vec<&str> = s.splitn(2, '/').collect();
+        let network = match IpAddr::from_str(pieces[0]) {
+            Ok(ip_addr) => ip_addr,
+            Err(_) => return Err(format!("'{}' is not an IP address or range", s)),
+        };
+        let prefix_len = match pieces.get(1) {
+            None => Self::max_prefix_len(&network),
+            Some(prefix_len_str) => match prefix_len_str.parse::<u8>() {
+                Ok(prefix_len) => prefix_len,
+                Err(_) => return Err(format!("'{}' has an invalid prefix length", s)),
+            },
+        };
+        IpRange::new(network, prefix_len)
+    }
+}
+
+impl Display for IpRange {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ipv4_range_contains_only_addresses_with_its_prefix() {
+        let subject = IpRange::from_str("10.20.0.0/14").unwrap();
+
+        assert_eq!(
+            subject.contains(&IpAddr::from_str("10.20.0.1").unwrap()),
+            true
+        );
+        assert_eq!(
+            subject.contains(&IpAddr::from_str("10.23.255.255").unwrap()),
+            true
+        );
+        assert_eq!(
+            subject.contains(&IpAddr::from_str("10.24.0.0").unwrap()),
+            false
+        );
+        assert_eq!(
+            subject.contains(&IpAddr::from_str("11.20.0.1").unwrap()),
+            false
+        );
+        assert_eq!(
+            subject.contains(&IpAddr::from_str("::a14:1").unwrap()),
+            false
+        );
+    }
+
+    #[test]
+    fn ipv6_range_contains_only_addresses_with_its_prefix() {
+        let subject = IpRange::from_str("2001:db8::/32").unwrap();
+
+        assert_eq!(
+            subject.contains(&IpAddr::from_str("2001:db8:1::1").unwrap()),
+            true
+        );
+        assert_eq!(
+            subject.contains(&IpAddr::from_str("2001:db9::1").unwrap()),
+            false
+        );
+        assert_eq!(
+            subject.contains(&IpAddr::from_str("32.1.13.184").unwrap()),
+            false
+        );
+    }
+
+    #[test]
+    fn bare_address_is_a_range_of_one_and_zero_prefix_contains_everything() {
+        let one = IpRange::from_str("1.2.3.4").unwrap();
+        let everything = IpRange::from_str("0.0.0.0/0").unwrap();
+
+        assert_eq!(one.to_string(), "1.2.3.4/32".to_string());
+        assert_eq!(one.contains(&IpAddr::from_str("1.2.3.4").unwrap()), true);
+        assert_eq!(one.contains(&IpAddr::from_str("1.2.3.5").unwrap()), false);
+        assert_eq!(
+            everything.contains(&IpAddr::from_str("255.1.2.3").unwrap()),
+            true
+        );
+    }
+
+    #[test]
+    fn enclosing_clears_host_bits_and_shortens_overlong_prefixes() {
+        let ipv4 = IpAddr::from_str("10.20.30.40").unwrap();
+        let ipv6 = IpAddr::from_str("2001:db8:abcd:1234::1").unwrap();
+
+        assert_eq!(
+            IpRange::enclosing(&ipv4, 20).to_string(),
+            "10.20.16.0/20".to_string()
+        );
+        assert_eq!(
+            IpRange::enclosing(&ipv4, 0).to_string(),
+            "0.0.0.0/0".to_string()
+        );
+        assert_eq!(
+            IpRange::enclosing(&ipv4, 48).to_string(),
+            "10.20.30.40/32".to_string()
+        );
+        assert_eq!(
+            IpRange::enclosing(&ipv6, 48).to_string(),
+            "2001:db8:abcd::/48".to_string()
+        );
+    }
+
+    #[test]
+    fn bad_ranges_are_rejected() {
+        assert_eq!(
+            IpRange::from_str("booga/8"),
+            Err("'booga/8' is not an IP address or range".to_string())
+        );
+        assert_eq!(
+            IpRange::from_str("1.2.3.4/x"),
+            Err("'1.2.3.4/x' has an invalid prefix length".to_string())
+        );
+        assert_eq!(
+            IpRange::from_str("1.2.3.4/33"),
+            Err("Prefix length 33 is too long for 1.2.3.4".to_string())
+        );
+    }
+}
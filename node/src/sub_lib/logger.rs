@@ -1,13 +1,235 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::cryptde::PublicKey;
+use crate::sub_lib::stream_key::StreamKey;
+use crate::sub_lib::ui_gateway::{UiEvent, UiEventMessage};
+use actix::Recipient;
 use chrono::format::strftime::StrftimeItems;
 use chrono::NaiveDateTime;
+use lazy_static::lazy_static;
+use log::logger;
 use log::Level;
+use log::LevelFilter;
 use log::Record;
-use log::{logger, Metadata};
+use serde_json::json;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::RwLock;
 use std::thread;
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 
+// Log lines from these would come right back to them as events, so they aren't forwarded.
+const UNFORWARDED_LOGGERS: [&str; 2] = ["UiGateway", "WebSocketSupervisor"];
+
+// Every Node log line carries this target, so that the log backend can let all of them through
+// and leave the filtering to the LogFilter below.
+pub const LOG_TARGET: &str = "SubstratumNode";
+
+// In the data directory; read when the Node gets SIGUSR1. Holds a filter like
+// "warn,GossipAcceptor=trace".
+pub const LOG_FILTER_FILE: &str = "log_filter";
+
+lazy_static! {
+    static ref UI_LOG_FORWARDING: Mutex<Option<(Level, Recipient<UiEventMessage>)>> =
+        Mutex::new(None);
+    // Until the Node sets it from --log_level, everything gets logged; tests depend on that.
+    static ref LOG_FILTER: RwLock<LogFilter> = RwLock::new(LogFilter::new(LevelFilter::Trace));
+    static ref LOG_FORMAT: RwLock<LogFormat> = RwLock::new(LogFormat::Text);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogFormat {
+    Text,
+    // One JSON object per line, for log pipelines to index
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<LogFormat, String> {
+        match string.to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("'{}' is not a log format", string)),
+        }
+    }
+}
+
+pub fn set_log_format(format: LogFormat) {
+    *LOG_FORMAT.write().expect("Log format is poisoned") = format;
+}
+
+/// Data a log line is about, for the JSON log format to carry as fields rather than leave
+/// buried in the message. The text format leaves them out.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LogFields {
+    fields: BTreeMap<String, Value>,
+}
+
+impl LogFields {
+    pub fn new() -> LogFields {
+        LogFields::default()
+    }
+
+    pub fn stream_key(self, stream_key: &StreamKey) -> LogFields {
+        self.field("stream_key", format!("{:?}", stream_key))
+    }
+
+    pub fn peer_addr(self, peer_addr: SocketAddr) -> LogFields {
+        self.field("peer_addr", peer_addr.to_string())
+    }
+
+    pub fn public_key(self, public_key: &PublicKey) -> LogFields {
+        self.field("public_key", public_key.to_string())
+    }
+
+    pub fn bytes(self, count: usize) -> LogFields {
+        self.field("bytes", count)
+    }
+
+    pub fn field<V: Into<Value>>(mut self, name: &str, value: V) -> LogFields {
+        self.fields.insert(name.to_string(), value.into());
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+fn json_line(
+    timestamp: &SystemTime,
+    thread: &str,
+    component: &str,
+    level: Level,
+    message: &str,
+    fields: &LogFields,
+) -> String {
+    let mut line = json!({
+        "timestamp": Logger::timestamp_as_string(timestamp),
+        "thread": thread,
+        "component": component,
+        "level": level.to_string(),
+        "message": message,
+    });
+    if !fields.is_empty() {
+        line["fields"] = json!(fields.fields);
+    }
+    line.to_string()
+}
+
+/// How the log backend writes a record. Lines from the Node's own Loggers are formatted already;
+/// lines from other crates are formatted here.
+pub fn format_record(format: LogFormat, record: &Record) -> String {
+    if record.target() == LOG_TARGET {
+        return record.args().to_string();
+    }
+    let component = record.module_path().unwrap_or_else(|| record.target());
+    match format {
+        LogFormat::Text => format!("{} [{}] {}", record.level(), component, record.args()),
+        LogFormat::Json => json_line(
+            &SystemTime::now(),
+            &format!("{:?}", thread::current().id()),
+            component,
+            record.level(),
+            &record.args().to_string(),
+            &LogFields::new(),
+        ),
+    }
+}
+
+/// Which log lines get written: a global level, and levels for particular components that
+/// override it. Components are the names given to Logger::new.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogFilter {
+    pub global: LevelFilter,
+    pub components: BTreeMap<String, LevelFilter>,
+}
+
+impl LogFilter {
+    pub fn new(global: LevelFilter) -> LogFilter {
+        LogFilter {
+            global,
+            components: BTreeMap::new(),
+        }
+    }
+
+    pub fn level_for(&self, component: &str) -> LevelFilter {
+        *self.components.get(component).unwrap_or(&self.global)
+    }
+}
+
+// The same form as --log_level, optionally followed by component overrides:
+// "warn,GossipAcceptor=trace,ProxyServer=off"
+impl FromStr for LogFilter {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<LogFilter, String> {
+        let mut parts = spec
+            .split(',')
+            .map(str::trim)
+            .filter(|part| !part.is_empty());
+        let global = match parts.next() {
+            Some(level) => parse_level_filter(level)?,
+            None => return Err("Log filter specifies no level".to_string()),
+        };
+        let mut filter = LogFilter::new(global);
+        for part in parts {
+            let mut halves = part.splitn(2, '=');
+            match (halves.next(), halves.next()) {
+                (Some(component), Some(level)) if !component.trim().is_empty() => {
+                    filter
+                        .components
+                        .insert(component.trim().to_string(), parse_level_filter(level)?);
+                }
+                _ => {
+                    return Err(format!(
+                        "'{}' should look like 'Component=level' in log filter",
+                        part
+                    ));
+                }
+            }
+        }
+        Ok(filter)
+    }
+}
+
+impl fmt::Display for LogFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.global.to_string().to_lowercase())?;
+        for (component, level) in &self.components {
+            write!(f, ",{}={}", component, level.to_string().to_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+pub fn parse_level_filter(level: &str) -> Result<LevelFilter, String> {
+    LevelFilter::from_str(level.trim()).map_err(|_| format!("'{}' is not a log level", level))
+}
+
+pub fn log_filter() -> LogFilter {
+    LOG_FILTER.read().expect("Log filter is poisoned").clone()
+}
+
+/// Replaces the filter every Logger consults; takes effect with the next log line.
+pub fn set_log_filter(filter: LogFilter) {
+    *LOG_FILTER.write().expect("Log filter is poisoned") = filter;
+}
+
+/// Sends every log line at the given level or more severe to the UiGateway as a UiEvent;
+/// None stops it.
+pub fn forward_logs_to_ui(forwarding: Option<(Level, Recipient<UiEventMessage>)>) {
+    *UI_LOG_FORWARDING
+        .lock()
+        .expect("UI log forwarding is poisoned") = forwarding;
+}
+
 #[derive(Clone)]
 pub struct Logger {
     name: String,
@@ -40,6 +262,26 @@ impl Logger {
         self.generic_log(Level::Error, string);
     }
 
+    pub fn trace_with(&self, fields: LogFields, string: String) {
+        self.generic_log_with(Level::Trace, fields, string);
+    }
+
+    pub fn debug_with(&self, fields: LogFields, string: String) {
+        self.generic_log_with(Level::Debug, fields, string);
+    }
+
+    pub fn info_with(&self, fields: LogFields, string: String) {
+        self.generic_log_with(Level::Info, fields, string);
+    }
+
+    pub fn warning_with(&self, fields: LogFields, string: String) {
+        self.generic_log_with(Level::Warn, fields, string);
+    }
+
+    pub fn error_with(&self, fields: LogFields, string: String) {
+        self.generic_log_with(Level::Error, fields, string);
+    }
+
     pub fn timestamp_as_string(timestamp: &SystemTime) -> String {
         let time_t = timestamp
             .duration_since(UNIX_EPOCH)
@@ -51,21 +293,67 @@ impl Logger {
     }
 
     fn generic_log(&self, level: Level, string: String) {
+        self.generic_log_with(level, LogFields::new(), string);
+    }
+
+    fn generic_log_with(&self, level: Level, fields: LogFields, string: String) {
+        self.forward_to_ui(level, &string);
+        if !self.level_enabled(level) {
+            return;
+        }
+        let format = *LOG_FORMAT.read().expect("Log format is poisoned");
+        let line = self.format_line(format, level, &string, &fields, &SystemTime::now());
         let logger = logger();
         logger.log(
             &Record::builder()
-                .args(format_args!(
-                    "{} {:?}: {}: {}: {}",
-                    Logger::timestamp_as_string(&SystemTime::now()),
-                    thread::current().id(),
-                    level,
-                    self.name,
-                    string
-                ))
+                .level(level)
+                .target(LOG_TARGET)
+                .args(format_args!("{}", line))
                 .build(),
         );
     }
 
+    fn format_line(
+        &self,
+        format: LogFormat,
+        level: Level,
+        string: &str,
+        fields: &LogFields,
+        timestamp: &SystemTime,
+    ) -> String {
+        let thread = format!("{:?}", thread::current().id());
+        match format {
+            LogFormat::Text => format!(
+                "{} {}: {}: {}: {}",
+                Logger::timestamp_as_string(timestamp),
+                thread,
+                level,
+                self.name,
+                string
+            ),
+            LogFormat::Json => json_line(timestamp, &thread, &self.name, level, string, fields),
+        }
+    }
+
+    fn forward_to_ui(&self, level: Level, string: &str) {
+        let forwarding = UI_LOG_FORWARDING
+            .lock()
+            .expect("UI log forwarding is poisoned")
+            .clone();
+        if let Some((threshold, recipient)) = forwarding {
+            if level <= threshold && !UNFORWARDED_LOGGERS.contains(&self.name.as_str()) {
+                // If the UiGateway is gone, there's nobody left to tell.
+                let _ = recipient.try_send(UiEventMessage {
+                    event: UiEvent::LogLine {
+                        level: level.to_string(),
+                        component: self.name.clone(),
+                        message: string.to_string(),
+                    },
+                });
+            }
+        }
+    }
+
     pub fn trace_enabled(&self) -> bool {
         self.level_enabled(Level::Trace)
     }
@@ -87,7 +375,7 @@ impl Logger {
     }
 
     pub fn level_enabled(&self, level: Level) -> bool {
-        logger().enabled(&Metadata::builder().level(level).target(&self.name).build())
+        level <= log_filter().level_for(&self.name)
     }
 }
 
@@ -96,6 +384,240 @@ mod tests {
     use super::*;
     use crate::test_utils::logging::init_test_logging;
     use crate::test_utils::logging::TestLogHandler;
+    use crate::test_utils::recorder::make_recorder;
+    use actix::Actor;
+    use actix::System;
+
+    #[test]
+    fn log_lines_are_forwarded_to_the_ui_only_at_or_above_the_requested_level() {
+        init_test_logging();
+        let system =
+            System::new("log_lines_are_forwarded_to_the_ui_only_at_or_above_the_requested_level");
+        let (ui_gateway, _, ui_gateway_recording_arc) = make_recorder();
+        let recipient = ui_gateway.start().recipient::<UiEventMessage>();
+        let subject = Logger::new("forwarded_logger");
+        let unforwarded = Logger::new("UiGateway");
+
+        forward_logs_to_ui(Some((Level::Warn, recipient)));
+        subject.warning(String::from("forward me"));
+        subject.info(String::from("too chatty"));
+        unforwarded.error(String::from("would loop"));
+        forward_logs_to_ui(None);
+        subject.error(String::from("too late"));
+
+        System::current().stop();
+        system.run();
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        // Other tests may be logging at the same time, so only our own lines count.
+        let forwarded = (0..ui_gateway_recording.len())
+            .map(|index| {
+                ui_gateway_recording
+                    .get_record::<UiEventMessage>(index)
+                    .clone()
+            })
+            .filter(|msg| match &msg.event {
+                UiEvent::LogLine { component, .. } => {
+                    component == "forwarded_logger" || component == "UiGateway"
+                }
+                _ => false,
+            })
+            .collect::<Vec<UiEventMessage>>();
+        assert_eq!(
+            forwarded,
+            vec![UiEventMessage {
+                event: UiEvent::LogLine {
+                    level: "WARN".to_string(),
+                    component: "forwarded_logger".to_string(),
+                    message: "forward me".to_string(),
+                }
+            }]
+        );
+    }
+
+    #[test]
+    fn log_filter_parses_global_level_and_component_overrides() {
+        let result = LogFilter::from_str(" Warn, GossipAcceptor=trace ,ProxyServer=OFF").unwrap();
+
+        assert_eq!(result.global, LevelFilter::Warn);
+        assert_eq!(result.level_for("GossipAcceptor"), LevelFilter::Trace);
+        assert_eq!(result.level_for("ProxyServer"), LevelFilter::Off);
+        assert_eq!(result.level_for("Neighborhood"), LevelFilter::Warn);
+        assert_eq!(
+            result.to_string(),
+            "warn,GossipAcceptor=trace,ProxyServer=off".to_string()
+        );
+    }
+
+    #[test]
+    fn log_filter_rejects_bad_specs() {
+        assert_eq!(
+            LogFilter::from_str(""),
+            Err("Log filter specifies no level".to_string())
+        );
+        assert_eq!(
+            LogFilter::from_str("loud"),
+            Err("'loud' is not a log level".to_string())
+        );
+        assert_eq!(
+            LogFilter::from_str("info,GossipAcceptor"),
+            Err("'GossipAcceptor' should look like 'Component=level' in log filter".to_string())
+        );
+        assert_eq!(
+            LogFilter::from_str("info,=debug"),
+            Err("'=debug' should look like 'Component=level' in log filter".to_string())
+        );
+    }
+
+    #[test]
+    fn log_lines_below_a_components_level_are_not_written() {
+        init_test_logging();
+        let quiet = Logger::new("log_lines_below_a_components_level_are_not_written_quiet");
+        let loud = Logger::new("log_lines_below_a_components_level_are_not_written_loud");
+        // The global level stays at trace, so tests logging in parallel aren't affected.
+        let mut filter = LogFilter::new(LevelFilter::Trace);
+        filter.components.insert(
+            "log_lines_below_a_components_level_are_not_written_quiet".to_string(),
+            LevelFilter::Warn,
+        );
+        set_log_filter(filter);
+
+        let quiet_info_enabled = quiet.info_enabled();
+        let quiet_warning_enabled = quiet.warning_enabled();
+        quiet.info(String::from("suppressed"));
+        quiet.warning(String::from("written"));
+        loud.trace(String::from("written"));
+        set_log_filter(LogFilter::new(LevelFilter::Trace));
+
+        assert_eq!(quiet_info_enabled, false);
+        assert_eq!(quiet_warning_enabled, true);
+        let tlh = TestLogHandler::new();
+        tlh.exists_no_log_containing(
+            "log_lines_below_a_components_level_are_not_written_quiet: suppressed",
+        );
+        tlh.exists_log_containing(
+            "WARN: log_lines_below_a_components_level_are_not_written_quiet: written",
+        );
+        tlh.exists_log_containing(
+            "TRACE: log_lines_below_a_components_level_are_not_written_loud: written",
+        );
+    }
+
+    #[test]
+    fn log_format_parses_case_insensitively() {
+        assert_eq!(LogFormat::from_str("Text"), Ok(LogFormat::Text));
+        assert_eq!(LogFormat::from_str("json"), Ok(LogFormat::Json));
+        assert_eq!(
+            LogFormat::from_str("xml"),
+            Err("'xml' is not a log format".to_string())
+        );
+    }
+
+    #[test]
+    fn json_lines_carry_structured_fields() {
+        let subject = Logger::new("ProxyServer");
+        let timestamp = SystemTime::now();
+        let public_key = PublicKey::new(&b"booga"[..]);
+        let peer_addr = SocketAddr::from_str("1.2.3.4:5678").unwrap();
+        let stream_key = StreamKey::new(public_key.clone(), peer_addr);
+        let fields = LogFields::new()
+            .stream_key(&stream_key)
+            .peer_addr(peer_addr)
+            .public_key(&public_key)
+            .bytes(1234)
+            .field("sequence_number", 7);
+
+        let result = subject.format_line(
+            LogFormat::Json,
+            Level::Info,
+            "Said \"hi\"",
+            &fields,
+            &timestamp,
+        );
+
+        let parsed: Value = serde_json::from_str(&result).unwrap();
+        assert_eq!(
+            parsed,
+            json!({
+                "timestamp": Logger::timestamp_as_string(&timestamp),
+                "thread": format!("{:?}", thread::current().id()),
+                "component": "ProxyServer",
+                "level": "INFO",
+                "message": "Said \"hi\"",
+                "fields": {
+                    "stream_key": format!("{:?}", stream_key),
+                    "peer_addr": "1.2.3.4:5678",
+                    "public_key": public_key.to_string(),
+                    "bytes": 1234,
+                    "sequence_number": 7,
+                }
+            })
+        );
+        assert_eq!(result.contains('\n'), false);
+    }
+
+    #[test]
+    fn json_lines_without_fields_leave_them_out_and_text_lines_ignore_them() {
+        let subject = Logger::new("Neighborhood");
+        let timestamp = SystemTime::now();
+        let fields = LogFields::new().bytes(12);
+
+        let json_result = subject.format_line(
+            LogFormat::Json,
+            Level::Warn,
+            "plain",
+            &LogFields::new(),
+            &timestamp,
+        );
+        let text_result =
+            subject.format_line(LogFormat::Text, Level::Warn, "plain", &fields, &timestamp);
+
+        let parsed: Value = serde_json::from_str(&json_result).unwrap();
+        assert_eq!(parsed.get("fields"), None);
+        assert_eq!(parsed["message"], json!("plain"));
+        assert_eq!(
+            text_result,
+            format!(
+                "{} {:?}: WARN: Neighborhood: plain",
+                Logger::timestamp_as_string(&timestamp),
+                thread::current().id()
+            )
+        );
+    }
+
+    #[test]
+    fn format_record_passes_node_lines_through_and_formats_other_crates_lines() {
+        let node_result = format_record(
+            LogFormat::Json,
+            &Record::builder()
+                .target(LOG_TARGET)
+                .args(format_args!("{{\"already\":\"formatted\"}}"))
+                .build(),
+        );
+        let foreign_results = [LogFormat::Text, LogFormat::Json]
+            .iter()
+            .map(|format| {
+                format_record(
+                    *format,
+                    &Record::builder()
+                        .level(Level::Debug)
+                        .target("tokio_reactor")
+                        .module_path(Some("tokio_reactor"))
+                        .args(format_args!("polling"))
+                        .build(),
+                )
+            })
+            .collect::<Vec<String>>();
+
+        assert_eq!(node_result, "{\"already\":\"formatted\"}".to_string());
+        assert_eq!(
+            foreign_results[0],
+            "DEBUG [tokio_reactor] polling".to_string()
+        );
+        let parsed: Value = serde_json::from_str(&foreign_results[1]).unwrap();
+        assert_eq!(parsed["component"], json!("tokio_reactor"));
+        assert_eq!(parsed["level"], json!("DEBUG"));
+        assert_eq!(parsed["message"], json!("polling"));
+    }
 
     #[test]
     fn logger_format_is_correct() {
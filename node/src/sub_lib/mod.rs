@@ -4,6 +4,7 @@ pub mod bidi_hashmap;
 pub mod binary_traverser;
 pub mod blockchain_bridge;
 pub mod channel_wrappers;
+pub mod compression;
 pub mod crash_point;
 pub mod cryptde;
 pub mod cryptde_null;
@@ -14,6 +15,7 @@ pub mod hop;
 pub mod hopper;
 pub mod http_packet_framer;
 pub mod http_response_start_finder;
+pub mod ip_range;
 pub mod limiter;
 pub mod logger;
 pub mod main_tools;
@@ -1,8 +1,12 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 use crate::neighborhood::gossip::Gossip;
+use crate::neighborhood::gossip::GossipNodeRecord;
 use crate::sub_lib::cryptde::PublicKey;
+use crate::sub_lib::cryptde::{CryptData, PlainData};
 use crate::sub_lib::dispatcher::Component;
 use crate::sub_lib::hopper::ExpiredCoresPackage;
+use crate::sub_lib::hopper::MessageType;
+use crate::sub_lib::ip_range::IpRange;
 use crate::sub_lib::node_addr::NodeAddr;
 use crate::sub_lib::peer_actors::BindMessage;
 use crate::sub_lib::route::Route;
@@ -12,9 +16,18 @@ use crate::sub_lib::wallet::Wallet;
 use actix::Message;
 use actix::Recipient;
 use serde_derive::{Deserialize, Serialize};
+use std::fmt;
 use std::net::IpAddr;
 use std::net::Ipv4Addr;
+use std::net::SocketAddr;
 use std::str::FromStr;
+use std::time::Duration;
+
+pub const DEFAULT_GOSSIP_BATCH_WINDOW_MS: u64 = 500;
+
+pub const DEFAULT_IPV4_DIVERSITY_PREFIX_LEN: u8 = 24;
+pub const DEFAULT_IPV6_DIVERSITY_PREFIX_LEN: u8 = 48;
+pub const DEFAULT_MAX_NODES_PER_PREFIX: usize = 4;
 
 pub const SENTINEL_IP_OCTETS: [u8; 4] = [255, 255, 255, 255];
 
@@ -25,6 +38,10 @@ pub const DEFAULT_RATE_PACK: RatePack = RatePack {
     exit_service_rate: 10001,
 };
 
+// Rates above this are taken to be mistakes. It also keeps rates multiplied by payload sizes far
+// from overflowing.
+pub const MAX_RATE: u64 = 1_000_000_000;
+
 pub const ZERO_RATE_PACK: RatePack = RatePack {
     routing_byte_rate: 0,
     routing_service_rate: 0,
@@ -57,15 +74,11 @@ impl FromStr for NodeDescriptor {
             return Err(String::from(s));
         }
 
-        let public_key = match base64::decode(pieces[0]) {
-            Ok(key) => PublicKey::new(&key),
+        let public_key = match public_key_from_str(pieces[0]) {
+            Ok(public_key) => public_key,
             Err(_) => return Err(String::from(s)),
         };
 
-        if public_key.is_empty() {
-            return Err(String::from(s));
-        }
-
         let node_addr = match NodeAddr::from_str(&pieces[1]) {
             Ok(node_addr) => node_addr,
             Err(_) => return Err(String::from(s)),
@@ -78,6 +91,12 @@ impl FromStr for NodeDescriptor {
     }
 }
 
+impl fmt::Display for NodeDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.public_key, self.node_addr)
+    }
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct NeighborhoodConfig {
     pub neighbor_configs: Vec<NodeDescriptor>,
@@ -87,6 +106,11 @@ pub struct NeighborhoodConfig {
     pub earning_wallet: Wallet,
     pub consuming_wallet: Option<Wallet>,
     pub rate_pack: RatePack,
+    // How long to collect database changes before Gossiping them; zero sends them immediately
+    pub gossip_batch_window: Duration,
+    pub diversity: DiversityConfig,
+    // Whether this Node can't accept inbound connections and needs its neighbors to relay to it
+    pub behind_nat: bool,
 }
 
 impl NeighborhoodConfig {
@@ -97,6 +121,35 @@ impl NeighborhoodConfig {
     }
 }
 
+// Nodes whose IP addresses share a prefix of these lengths are assumed to be run by the same
+// operator: no route may contain two of them, and only so many of them are accepted from Gossip.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DiversityConfig {
+    pub ipv4_prefix_len: u8,
+    pub ipv6_prefix_len: u8,
+    pub max_nodes_per_prefix: usize,
+}
+
+impl Default for DiversityConfig {
+    fn default() -> Self {
+        DiversityConfig {
+            ipv4_prefix_len: DEFAULT_IPV4_DIVERSITY_PREFIX_LEN,
+            ipv6_prefix_len: DEFAULT_IPV6_DIVERSITY_PREFIX_LEN,
+            max_nodes_per_prefix: DEFAULT_MAX_NODES_PER_PREFIX,
+        }
+    }
+}
+
+impl DiversityConfig {
+    pub fn prefix_of(&self, ip_addr: &IpAddr) -> IpRange {
+        let prefix_len = match ip_addr {
+            IpAddr::V4(_) => self.ipv4_prefix_len,
+            IpAddr::V6(_) => self.ipv6_prefix_len,
+        };
+        IpRange::enclosing(ip_addr, prefix_len)
+    }
+}
+
 #[derive(Clone)]
 pub struct NeighborhoodSubs {
     pub bind: Recipient<BindMessage>,
@@ -107,6 +160,11 @@ pub struct NeighborhoodSubs {
     pub from_hopper: Recipient<ExpiredCoresPackage<Gossip>>,
     pub dispatcher_node_query: Recipient<DispatcherNodeQueryMessage>,
     pub remove_neighbor: Recipient<RemoveNeighborMessage>,
+    pub heartbeat_from_hopper: Recipient<ExpiredCoresPackage<Heartbeat>>,
+    pub from_relay_client: Recipient<RelayRequestMessage>,
+    pub graph_request: Recipient<NeighborhoodGraphRequest>,
+    pub set_rate_pack: Recipient<SetRatePackMessage>,
+    pub set_initial_neighbors: Recipient<SetInitialNeighborsMessage>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -156,6 +214,66 @@ pub enum TargetType {
     Standard,
 }
 
+pub fn public_key_from_str(s: &str) -> Result<PublicKey, String> {
+    match base64::decode(s) {
+        Ok(ref key) if !key.is_empty() => Ok(PublicKey::new(key)),
+        _ => Err(format!("'{}' is not a public key", s)),
+    }
+}
+
+// Limits a user places on the routes chosen for their requests
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct RouteConstraints {
+    // If any are specified, requests must exit through one of these Nodes
+    pub exit_keys: Vec<PublicKey>,
+    // Nodes that must not appear anywhere in a route
+    pub excluded_keys: Vec<PublicKey>,
+    // Nodes at addresses in these ranges must not appear anywhere in a route
+    pub excluded_ip_ranges: Vec<IpRange>,
+}
+
+impl RouteConstraints {
+    pub fn from_strs(
+        exit_keys: &[&str],
+        excluded_keys: &[&str],
+        excluded_ip_ranges: &[&str],
+    ) -> Result<RouteConstraints, String> {
+        Ok(RouteConstraints {
+            exit_keys: exit_keys
+                .iter()
+                .map(|s| public_key_from_str(s))
+                .collect::<Result<Vec<PublicKey>, String>>()?,
+            excluded_keys: excluded_keys
+                .iter()
+                .map(|s| public_key_from_str(s))
+                .collect::<Result<Vec<PublicKey>, String>>()?,
+            excluded_ip_ranges: excluded_ip_ranges
+                .iter()
+                .map(|s| IpRange::from_str(s))
+                .collect::<Result<Vec<IpRange>, String>>()?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exit_keys.is_empty()
+            && self.excluded_keys.is_empty()
+            && self.excluded_ip_ranges.is_empty()
+    }
+
+    pub fn excludes(&self, public_key: &PublicKey, ip_addr_opt: Option<IpAddr>) -> bool {
+        if self.excluded_keys.contains(public_key) {
+            return true;
+        }
+        match ip_addr_opt {
+            Some(ip_addr) => self
+                .excluded_ip_ranges
+                .iter()
+                .any(|range| range.contains(&ip_addr)),
+            None => false,
+        }
+    }
+}
+
 #[derive(PartialEq, Debug)]
 pub struct RouteQueryMessage {
     pub target_type: TargetType,
@@ -163,6 +281,9 @@ pub struct RouteQueryMessage {
     pub target_component: Component,
     pub minimum_hop_count: usize,
     pub return_component_opt: Option<Component>,
+    // Nodes that must not appear in the route except as its origin or its target
+    pub avoided_keys: Vec<PublicKey>,
+    pub constraints: RouteConstraints,
 }
 
 impl Message for RouteQueryMessage {
@@ -171,12 +292,45 @@ impl Message for RouteQueryMessage {
 
 impl RouteQueryMessage {
     pub fn data_indefinite_route_request(minimum_hop_count: usize) -> RouteQueryMessage {
+        RouteQueryMessage::data_constrained_route_request(
+            minimum_hop_count,
+            RouteConstraints::default(),
+        )
+    }
+
+    pub fn data_constrained_route_request(
+        minimum_hop_count: usize,
+        constraints: RouteConstraints,
+    ) -> RouteQueryMessage {
+        let target_key_opt = match constraints.exit_keys.as_slice() {
+            [exit_key] => Some(exit_key.clone()),
+            _ => None,
+        };
+        RouteQueryMessage {
+            target_type: TargetType::Standard,
+            target_key_opt,
+            target_component: Component::ProxyClient,
+            minimum_hop_count,
+            return_component_opt: Some(Component::ProxyServer),
+            avoided_keys: vec![],
+            constraints,
+        }
+    }
+
+    pub fn data_disjoint_route_request(
+        minimum_hop_count: usize,
+        exit_key: PublicKey,
+        avoided_keys: Vec<PublicKey>,
+        constraints: RouteConstraints,
+    ) -> RouteQueryMessage {
         RouteQueryMessage {
             target_type: TargetType::Standard,
-            target_key_opt: None,
+            target_key_opt: Some(exit_key),
             target_component: Component::ProxyClient,
             minimum_hop_count,
             return_component_opt: Some(Component::ProxyServer),
+            avoided_keys,
+            constraints,
         }
     }
 }
@@ -198,6 +352,7 @@ pub enum ExpectedServices {
 pub struct RouteQueryResponse {
     pub route: Route,
     pub expected_services: ExpectedServices,
+    pub exit_supports_compression: bool,
 }
 
 #[derive(PartialEq, Debug, Message, Clone)]
@@ -205,12 +360,110 @@ pub struct RemoveNeighborMessage {
     pub public_key: PublicKey,
 }
 
+/// Exchanged between neighbors to find out whether they're still alive and how long a round trip
+/// takes. A Node answers each Ping with a Pong carrying the same sequence number.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Heartbeat {
+    Ping(u32),
+    Pong(u32),
+}
+
+impl Into<MessageType> for Heartbeat {
+    fn into(self) -> MessageType {
+        MessageType::Heartbeat(self)
+    }
+}
+
+/// How far the Neighborhood has gotten toward joining the Substratum Network.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ConnectionState {
+    // No neighbors, and no descriptors to debut to
+    Standalone,
+    // Waiting for an answer to our Debut to the described Node
+    Debuting { target: String, round: u32 },
+    // Nobody answered a whole round of Debuts; the next round starts after the delay
+    WaitingToRetry { round: u32, delay_secs: u64 },
+    // At least one neighbor knows about us
+    Connected,
+}
+
+/// Exchanged over the connection a Node behind NAT keeps open to each neighbor it advertises as
+/// a relay. The client sends a Request carrying its signed record, which lists the relay. Since
+/// anyone can replay a signed record, the relay answers with a Challenge over the same
+/// connection, and relays for the client only once a Response signing that Challenge comes
+/// back on it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RelayRequest {
+    Request { node_record: GossipNodeRecord },
+    Challenge(u64),
+    Response { signature: CryptData },
+}
+
+impl RelayRequest {
+    /// What a client signs to answer a relay's Challenge.
+    pub fn challenge_data(relay_key: &PublicKey, challenge: u64) -> PlainData {
+        let mut data = relay_key.as_slice().to_vec();
+        data.extend_from_slice(&challenge.to_be_bytes());
+        PlainData::new(&data)
+    }
+}
+
+impl Into<MessageType> for RelayRequest {
+    fn into(self) -> MessageType {
+        MessageType::RelayRequest(self)
+    }
+}
+
+/// A RelayRequest as the RoutingService delivers it to the Neighborhood, along with the address
+/// of the connection it arrived on: the client's on the relay, the relay's on the client.
+#[derive(Clone, Debug, PartialEq, Message)]
+pub struct RelayRequestMessage {
+    pub relay_request: RelayRequest,
+    pub client_addr: SocketAddr,
+}
+
+/// Asks the Neighborhood to send its current view of the network to a UI client.
+#[derive(Clone, Debug, PartialEq, Message)]
+pub struct NeighborhoodGraphRequest {
+    pub client_id: u64,
+    pub correlation_id: Option<u64>,
+}
+
+/// The NeighborhoodDatabase as a UI sees it: the DOT rendering plus the same graph in a form
+/// that doesn't need a DOT parser.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NeighborhoodGraph {
+    pub dot: String,
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GraphNode {
+    pub public_key: String,
+    pub version: u32,
+    pub is_root: bool,
+    pub is_bootstrap_node: bool,
+    pub address_revealed: bool,
+    pub node_addr: Option<String>,
+    pub desirable: bool,
+    pub rate_pack: RatePack,
+}
+
+/// One half neighborship; `full` is set when the Node at `to` has `from` as a neighbor as well.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
+    pub full: bool,
+}
+
 #[derive(PartialEq, Debug, Message, Clone)]
 pub enum NodeRecordMetadataMessage {
     Desirable(PublicKey, bool),
 }
 
-#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
 pub struct RatePack {
     pub routing_byte_rate: u64,
     pub routing_service_rate: u64,
@@ -218,6 +471,38 @@ pub struct RatePack {
     pub exit_service_rate: u64,
 }
 
+impl RatePack {
+    pub fn check_bounds(&self) -> Result<(), String> {
+        let rates = [
+            ("routing_byte_rate", self.routing_byte_rate),
+            ("routing_service_rate", self.routing_service_rate),
+            ("exit_byte_rate", self.exit_byte_rate),
+            ("exit_service_rate", self.exit_service_rate),
+        ];
+        match rates.iter().find(|(_, rate)| *rate > MAX_RATE) {
+            Some((name, rate)) => Err(format!(
+                "{} of {} is more than the maximum of {}",
+                name, rate, MAX_RATE
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+// Replaces the rates the Node charges for its services; the Neighborhood advertises them in the
+// Node's own record from the next Gossip on
+#[derive(Message, Clone, Debug, PartialEq)]
+pub struct SetRatePackMessage {
+    pub rate_pack: RatePack,
+}
+
+// Replaces the neighbors the Node debuts to when it has nobody else to talk to. Neighbors that
+// weren't there before hear from the Node right away.
+#[derive(Message, Clone, Debug, PartialEq)]
+pub struct SetInitialNeighborsMessage {
+    pub neighbors: Vec<NodeDescriptor>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -276,6 +561,15 @@ mod tests {
         )
     }
 
+    #[test]
+    fn node_descriptor_is_displayed_the_way_it_is_parsed() {
+        let descriptor = "R29vZEtleQ:1.2.3.4:1234,2345,3456";
+
+        let result = NodeDescriptor::from_str(descriptor).unwrap().to_string();
+
+        assert_eq!(result, descriptor);
+    }
+
     #[test]
     fn data_indefinite_route_request() {
         let result = RouteQueryMessage::data_indefinite_route_request(2);
@@ -288,6 +582,134 @@ mod tests {
                 target_component: Component::ProxyClient,
                 minimum_hop_count: 2,
                 return_component_opt: Some(Component::ProxyServer),
+                avoided_keys: vec![],
+                constraints: RouteConstraints::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn data_constrained_route_request_targets_a_single_pinned_exit() {
+        let constraints = RouteConstraints {
+            exit_keys: vec![PublicKey::new(b"exit")],
+            excluded_keys: vec![PublicKey::new(b"bad")],
+            excluded_ip_ranges: vec![],
+        };
+
+        let result = RouteQueryMessage::data_constrained_route_request(3, constraints.clone());
+
+        assert_eq!(
+            result,
+            RouteQueryMessage {
+                target_type: TargetType::Standard,
+                target_key_opt: Some(PublicKey::new(b"exit")),
+                target_component: Component::ProxyClient,
+                minimum_hop_count: 3,
+                return_component_opt: Some(Component::ProxyServer),
+                avoided_keys: vec![],
+                constraints,
+            }
+        );
+    }
+
+    #[test]
+    fn data_constrained_route_request_leaves_a_choice_of_pinned_exits_to_the_neighborhood() {
+        let constraints = RouteConstraints {
+            exit_keys: vec![PublicKey::new(b"one"), PublicKey::new(b"two")],
+            excluded_keys: vec![],
+            excluded_ip_ranges: vec![],
+        };
+
+        let result = RouteQueryMessage::data_constrained_route_request(3, constraints.clone());
+
+        assert_eq!(result.target_key_opt, None);
+        assert_eq!(result.constraints, constraints);
+    }
+
+    #[test]
+    fn route_constraints_are_parsed_from_strings() {
+        let result = RouteConstraints::from_strs(
+            &["ZXhpdA"],
+            &["YmFk", "d29yc2U"],
+            &["10.0.0.0/8", "1.2.3.4"],
+        );
+
+        assert_eq!(
+            result,
+            Ok(RouteConstraints {
+                exit_keys: vec![PublicKey::new(b"exit")],
+                excluded_keys: vec![PublicKey::new(b"bad"), PublicKey::new(b"worse")],
+                excluded_ip_ranges: vec![
+                    IpRange::from_str("10.0.0.0/8").unwrap(),
+                    IpRange::from_str("1.2.3.4/32").unwrap()
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn route_constraints_complain_about_bad_strings() {
+        assert_eq!(
+            RouteConstraints::from_strs(&["not a key"], &[], &[]),
+            Err("'not a key' is not a public key".to_string())
+        );
+        assert_eq!(
+            RouteConstraints::from_strs(&[], &[""], &[]),
+            Err("'' is not a public key".to_string())
+        );
+        assert_eq!(
+            RouteConstraints::from_strs(&[], &[], &["1.2.3.4/99"]),
+            Err("Prefix length 99 is too long for 1.2.3.4".to_string())
+        );
+    }
+
+    #[test]
+    fn route_constraints_exclude_by_key_or_by_ip_range() {
+        let subject = RouteConstraints {
+            exit_keys: vec![],
+            excluded_keys: vec![PublicKey::new(b"bad")],
+            excluded_ip_ranges: vec![IpRange::from_str("10.0.0.0/8").unwrap()],
+        };
+
+        assert_eq!(subject.excludes(&PublicKey::new(b"bad"), None), true);
+        assert_eq!(
+            subject.excludes(
+                &PublicKey::new(b"good"),
+                Some(IpAddr::from_str("10.1.2.3").unwrap())
+            ),
+            true
+        );
+        assert_eq!(
+            subject.excludes(
+                &PublicKey::new(b"good"),
+                Some(IpAddr::from_str("11.1.2.3").unwrap())
+            ),
+            false
+        );
+        assert_eq!(subject.excludes(&PublicKey::new(b"good"), None), false);
+        assert_eq!(RouteConstraints::default().is_empty(), true);
+        assert_eq!(subject.is_empty(), false);
+    }
+
+    #[test]
+    fn data_disjoint_route_request() {
+        let result = RouteQueryMessage::data_disjoint_route_request(
+            3,
+            PublicKey::new(b"exit"),
+            vec![PublicKey::new(b"one"), PublicKey::new(b"two")],
+            RouteConstraints::default(),
+        );
+
+        assert_eq!(
+            result,
+            RouteQueryMessage {
+                target_type: TargetType::Standard,
+                target_key_opt: Some(PublicKey::new(b"exit")),
+                target_component: Component::ProxyClient,
+                minimum_hop_count: 3,
+                return_component_opt: Some(Component::ProxyServer),
+                avoided_keys: vec![PublicKey::new(b"one"), PublicKey::new(b"two")],
+                constraints: RouteConstraints::default(),
             }
         );
     }
@@ -302,6 +724,9 @@ mod tests {
             is_bootstrap_node: false,
             local_ip_addr: IpAddr::from_str("1.2.3.4").unwrap(),
             clandestine_port_list: vec![1234],
+            gossip_batch_window: Duration::from_millis(0),
+            diversity: DiversityConfig::default(),
+            behind_nat: false,
         };
 
         let result = subject.is_decentralized();
@@ -322,6 +747,9 @@ mod tests {
             is_bootstrap_node: false,
             local_ip_addr: sentinel_ip_addr(),
             clandestine_port_list: vec![1234],
+            gossip_batch_window: Duration::from_millis(0),
+            diversity: DiversityConfig::default(),
+            behind_nat: false,
         };
 
         let result = subject.is_decentralized();
@@ -342,6 +770,9 @@ mod tests {
             is_bootstrap_node: false,
             local_ip_addr: IpAddr::from_str("1.2.3.4").unwrap(),
             clandestine_port_list: vec![],
+            gossip_batch_window: Duration::from_millis(0),
+            diversity: DiversityConfig::default(),
+            behind_nat: false,
         };
 
         let result = subject.is_decentralized();
@@ -349,6 +780,44 @@ mod tests {
         assert_eq!(result, false);
     }
 
+    #[test]
+    fn diversity_config_uses_the_prefix_length_for_the_address_family() {
+        let subject = DiversityConfig {
+            ipv4_prefix_len: 16,
+            ipv6_prefix_len: 32,
+            max_nodes_per_prefix: 1,
+        };
+
+        assert_eq!(
+            subject.prefix_of(&IpAddr::from_str("10.20.30.40").unwrap()),
+            IpRange::from_str("10.20.0.0/16").unwrap()
+        );
+        assert_eq!(
+            subject.prefix_of(&IpAddr::from_str("2001:db8:1::1").unwrap()),
+            IpRange::from_str("2001:db8::/32").unwrap()
+        );
+    }
+
+    #[test]
+    fn rate_pack_check_bounds_names_the_first_rate_over_the_maximum() {
+        let mut subject = rate_pack(100);
+        assert_eq!(subject.check_bounds(), Ok(()));
+        subject.routing_service_rate = MAX_RATE;
+        subject.exit_byte_rate = MAX_RATE + 1;
+        subject.exit_service_rate = MAX_RATE + 2;
+
+        let result = subject.check_bounds();
+
+        assert_eq!(
+            result,
+            Err(format!(
+                "exit_byte_rate of {} is more than the maximum of {}",
+                MAX_RATE + 1,
+                MAX_RATE
+            ))
+        );
+    }
+
     #[test]
     fn neighborhood_config_is_decentralized_if_neighbor_config_and_local_ip_addr_and_clandestine_port(
     ) {
@@ -363,6 +832,9 @@ mod tests {
             is_bootstrap_node: false,
             local_ip_addr: IpAddr::from_str("1.2.3.4").unwrap(),
             clandestine_port_list: vec![1234],
+            gossip_batch_window: Duration::from_millis(0),
+            diversity: DiversityConfig::default(),
+            behind_nat: false,
         };
 
         let result = subject.is_decentralized();
@@ -1,6 +1,8 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::compression::PayloadCompression;
 use crate::sub_lib::cryptde::CryptDE;
 use crate::sub_lib::hopper::{ExpiredCoresPackage, MessageType};
+use crate::sub_lib::neighborhood::SetRatePackMessage;
 use crate::sub_lib::peer_actors::BindMessage;
 use crate::sub_lib::proxy_server::ClientRequestPayload;
 use crate::sub_lib::sequence_buffer::SequencedPacket;
@@ -28,6 +30,17 @@ pub struct ProxyClientConfig {
 pub struct ClientResponsePayload {
     pub stream_key: StreamKey,
     pub sequenced_packet: SequencedPacket,
+    #[serde(default)]
+    pub compression: PayloadCompression,
+    // Another copy of this packet is traveling over a second, disjoint route
+    #[serde(default)]
+    pub redundant: bool,
+}
+
+// Replaces the DNS servers the ProxyClient resolves with; streams already open are unaffected
+#[derive(Message, Clone, Debug, PartialEq)]
+pub struct SetDnsServersMessage {
+    pub dns_servers: Vec<SocketAddr>,
 }
 
 #[derive(Message, Clone, Debug, PartialEq, Deserialize, Serialize)]
@@ -53,6 +66,8 @@ pub struct ProxyClientSubs {
     pub from_hopper: Recipient<ExpiredCoresPackage<ClientRequestPayload>>,
     pub inbound_server_data: Recipient<InboundServerData>,
     pub dns_resolve_failed: Recipient<DnsResolveFailure>,
+    pub set_rate_pack: Recipient<SetRatePackMessage>,
+    pub set_dns_servers: Recipient<SetDnsServersMessage>,
 }
 
 impl ClientResponsePayload {
@@ -64,6 +79,8 @@ impl ClientResponsePayload {
                 sequence_number: 0,
                 last_data: true,
             },
+            compression: PayloadCompression::None,
+            redundant: false,
         }
     }
 }
@@ -97,6 +114,8 @@ mod tests {
                     sequence_number: 0,
                     last_data: true
                 },
+                compression: PayloadCompression::None,
+                redundant: false,
             }
         )
     }
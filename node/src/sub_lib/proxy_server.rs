@@ -1,8 +1,10 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::compression::PayloadCompression;
 use crate::sub_lib::cryptde::PublicKey;
 use crate::sub_lib::dispatcher::InboundClientData;
-use crate::sub_lib::hopper::{ExpiredCoresPackage, MessageType};
+use crate::sub_lib::hopper::{ExpiredCoresPackage, MessageType, RouteFailure};
 use crate::sub_lib::neighborhood::ExpectedService;
+use crate::sub_lib::neighborhood::RouteConstraints;
 use crate::sub_lib::peer_actors::BindMessage;
 use crate::sub_lib::proxy_client::{ClientResponsePayload, DnsResolveFailure};
 use crate::sub_lib::sequence_buffer::SequencedPacket;
@@ -28,6 +30,11 @@ pub struct ClientRequestPayload {
     pub target_port: u16,
     pub protocol: ProxyProtocol,
     pub originator_public_key: PublicKey,
+    #[serde(default)]
+    pub compression: PayloadCompression,
+    // Another copy of this packet is traveling over a second, disjoint route
+    #[serde(default)]
+    pub redundant: bool,
 }
 
 impl Into<MessageType> for ClientRequestPayload {
@@ -42,6 +49,15 @@ pub struct AddReturnRouteMessage {
     pub expected_services: Vec<ExpectedService>,
     pub protocol: ProxyProtocol,
     pub server_name: Option<String>,
+    pub stream_key: StreamKey,
+    // True for the route back from the second copy of a multipath request
+    pub redundant: bool,
+}
+
+// Replaces the route constraints the ProxyServer puts on every route it asks for
+#[derive(Message, Clone, Debug, PartialEq)]
+pub struct SetRouteConstraintsMessage {
+    pub constraints: RouteConstraints,
 }
 
 #[derive(Clone)]
@@ -51,5 +67,22 @@ pub struct ProxyServerSubs {
     pub from_dispatcher: Recipient<InboundClientData>,
     pub from_hopper: Recipient<ExpiredCoresPackage<ClientResponsePayload>>,
     pub dns_failure_from_hopper: Recipient<ExpiredCoresPackage<DnsResolveFailure>>,
+    pub route_failure_from_hopper: Recipient<ExpiredCoresPackage<RouteFailure>>,
     pub add_return_route: Recipient<AddReturnRouteMessage>,
+    pub set_route_constraints: Recipient<SetRouteConstraintsMessage>,
+    pub active_streams_request: Recipient<ActiveStreamsRequest>,
+}
+
+/// Asks the ProxyServer to send a UI client the streams it's currently carrying.
+#[derive(Clone, Debug, PartialEq, Message)]
+pub struct ActiveStreamsRequest {
+    pub client_id: u64,
+    pub correlation_id: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StreamInfo {
+    pub stream_key: String,
+    pub client_addr: String,
+    pub tunneled_host: Option<String>,
 }
@@ -5,12 +5,15 @@ use crate::sub_lib::cryptde::CryptDE;
 use crate::sub_lib::cryptde::CryptData;
 use crate::sub_lib::cryptde::PublicKey;
 use crate::sub_lib::dispatcher::Component;
+use crate::sub_lib::hop::FailureReturn;
 use crate::sub_lib::hop::LiveHop;
+use crate::sub_lib::hopper::MessageType;
+use crate::sub_lib::hopper::RouteFailure;
 use crate::sub_lib::wallet::Wallet;
 use serde_derive::{Deserialize, Serialize};
 use std::iter;
 
-#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Route {
     pub hops: Vec<CryptData>,
 }
@@ -100,45 +103,98 @@ impl Route {
         let over_component = over.recipient;
         let over_keys = over.keys.iter().skip(1);
 
-        let mut hops = Route::over_segment(
-            back.is_none(),
-            consuming_wallet.clone(),
-            over_keys,
-            over_component,
-        );
+        let mut hops = Route::over_segment(back.is_none(), over_keys, over_component);
+        let single_use_hop_count = hops.len();
 
-        Route::back_segment(&back, consuming_wallet.clone(), over_component, &mut hops);
+        Route::back_segment(&back, over_component, &mut hops);
 
-        Route::hops_to_route(
+        let failure_return_hops = match (&back, return_route_id_opt) {
+            (Some(_), Some(return_route_id)) => Route::add_failure_returns(
+                &over.keys,
+                &mut hops,
+                cryptde,
+                &consuming_wallet,
+                return_route_id,
+            )?,
+            _ => vec![],
+        };
+
+        let mut route = Route::hops_to_route(
             hops[0..].to_vec(),
             &over.keys[0],
+            consuming_wallet,
+            single_use_hop_count,
             return_route_id_opt,
             cryptde,
-        )
+        )?;
+        route.hops.extend(failure_return_hops);
+        Ok(route)
     }
 
     fn over_segment<'a>(
         one_way: bool,
-        consuming_wallet: Option<Wallet>,
         over_keys: impl Iterator<Item = &'a PublicKey>,
         over_component: Component,
     ) -> Vec<LiveHop> {
         let mut hops: Vec<LiveHop> = over_keys
-            .map(|key| LiveHop::new(key, consuming_wallet.clone(), Component::Hopper))
+            .map(|key| LiveHop::new(key, None, Component::Hopper))
             .collect();
         if one_way {
-            hops.push(LiveHop::new(
-                &PublicKey::new(b""),
-                consuming_wallet.clone(),
-                over_component,
-            ));
+            hops.push(LiveHop::new(&PublicKey::new(b""), None, over_component));
         };
         hops
     }
 
+    // The originator's own hop needs no way back; every other Node in the over segment gets a
+    // RouteFailure it can send to tell the originator that it couldn't reach the next Node. The
+    // way back from the last of them passes through all the others, so it's made once, to be
+    // appended to the Route, and each Node's way back is the tail of it that starts with that
+    // Node's own hop. The returned hops must follow hops and the return_route_id directly.
+    fn add_failure_returns(
+        over_keys: &[PublicKey],
+        hops: &mut Vec<LiveHop>,
+        cryptde: &dyn CryptDE,
+        consuming_wallet: &Option<Wallet>,
+        return_route_id: u32,
+    ) -> Result<Vec<CryptData>, String> {
+        if over_keys.len() < 3 {
+            return Ok(vec![]);
+        }
+        let last_index = over_keys.len() - 2;
+        let failure_route = Route::construct(
+            RouteSegment::new(
+                over_keys[..=last_index].iter().rev().collect(),
+                Component::ProxyServer,
+            ),
+            None,
+            cryptde,
+            consuming_wallet.clone(),
+            Some(return_route_id),
+        )?;
+        let route_len = hops.len() + 1;
+        for hop_index in 1..=last_index {
+            let route_failure = RouteFailure {
+                reporting_key: over_keys[hop_index].clone(),
+                failed_key: over_keys[hop_index + 1].clone(),
+                reason: String::new(),
+            };
+            let payload = encodex(
+                cryptde,
+                &over_keys[0],
+                &MessageType::RouteFailed(route_failure),
+            )
+            .map_err(|e| format!("Could not encrypt payload: {:?}", e))?;
+            hops[hop_index].failure_return = Some(FailureReturn {
+                // By the time the Route reaches this Node, it has been shifted hop_index times.
+                route_offset: route_len + (last_index - hop_index) - hop_index,
+                payload,
+            });
+        }
+        Ok(failure_route.hops)
+    }
+
     fn back_segment(
         back_option: &Option<RouteSegment>,
-        consuming_wallet: Option<Wallet>,
         over_component: Component,
         hops: &mut Vec<LiveHop>,
     ) {
@@ -152,14 +208,10 @@ impl Route {
                     Component::Hopper
                 };
 
-                hops.push(LiveHop::new(key, consuming_wallet.clone(), component))
+                hops.push(LiveHop::new(key, None, component))
             }
 
-            hops.push(LiveHop::new(
-                &PublicKey::new(b""),
-                consuming_wallet.clone(),
-                back_component,
-            ));
+            hops.push(LiveHop::new(&PublicKey::new(b""), None, back_component));
         }
     }
 
@@ -191,30 +243,50 @@ impl Route {
         }
     }
 
+    // Hops are encrypted last to first, so that each Payer can be bound to the hop after it.
     fn hops_to_route(
-        hops: Vec<LiveHop>,
+        mut hops: Vec<LiveHop>,
         top_hop_key: &PublicKey,
+        consuming_wallet: Option<Wallet>,
+        single_use_hop_count: usize,
         return_route_id_opt: Option<u32>,
         cryptde: &dyn CryptDE,
     ) -> Result<Route, String> {
-        let mut hops_enc: Vec<CryptData> = Vec::new();
-        let mut hop_key = top_hop_key;
-        for hop_index in 0..hops.len() {
-            let data_hop = &hops[hop_index];
+        let nonce = Route::make_nonce();
+        let hop_keys: Vec<PublicKey> = iter::once(top_hop_key.clone())
+            .chain(hops.iter().map(|hop| hop.public_key.clone()))
+            .collect();
+        let mut hops_enc: Vec<CryptData> = return_route_id_opt
+            .map(|return_route_id| Self::encrypt_return_route_id(return_route_id, cryptde))
+            .into_iter()
+            .collect();
+        for (hop_index, data_hop) in hops.iter_mut().enumerate().rev() {
+            let hop_key = &hop_keys[hop_index];
+            if let Some(ref wallet) = consuming_wallet {
+                data_hop.sign_payer(
+                    wallet,
+                    hop_key,
+                    nonce,
+                    hop_index >= single_use_hop_count,
+                    hops_enc.first(),
+                )?;
+            }
             // crashpoint - should not be possible, can this be restructured to remove Option?
-            hops_enc.push(match data_hop.encode(hop_key, cryptde) {
+            let hop_enc = match data_hop.encode(hop_key, cryptde) {
                 Ok(crypt_data) => crypt_data,
                 Err(e) => return Err(format!("Couldn't encode hop: {}", e)),
-            });
-            hop_key = &data_hop.public_key;
-        }
-        if let Some(return_route_id) = return_route_id_opt {
-            let return_route_id_enc = Self::encrypt_return_route_id(return_route_id, cryptde);
-            hops_enc.push(return_route_id_enc);
+            };
+            hops_enc.insert(0, hop_enc);
         }
         Ok(Route { hops: hops_enc })
     }
 
+    // Relays refuse a single-use nonce they've seen before, so this can't come from the CryptDE,
+    // whose randomness may be predictable.
+    fn make_nonce() -> u64 {
+        rand::random()
+    }
+
     fn encrypt_return_route_id(return_route_id: u32, cryptde: &CryptDE) -> CryptData {
         encodex(cryptde, &cryptde.public_key(), &return_route_id)
             .expect("Internal error encrypting u32 return_route_id")
@@ -248,8 +320,36 @@ pub enum RouteError {
 mod tests {
     use super::*;
     use crate::sub_lib::cryptde_null::CryptDENull;
+    use crate::sub_lib::hopper::MessageType;
+    use crate::test_utils::test_utils::make_paying_wallet;
     use serde_cbor;
 
+    // The hop Route would make for owner_key
+    fn paid_hop(
+        key: &PublicKey,
+        component: Component,
+        wallet: &Wallet,
+        owner_key: &PublicKey,
+        nonce: u64,
+        reusable: bool,
+        next_hop_opt: Option<&CryptData>,
+    ) -> LiveHop {
+        let mut hop = LiveHop::new(key, None, component);
+        hop.sign_payer(wallet, owner_key, nonce, reusable, next_hop_opt)
+            .unwrap();
+        hop
+    }
+
+    // Every hop in a Route shares the nonce of its top hop.
+    fn route_nonce(route: &Route, top_hop_key: &PublicKey) -> u64 {
+        route
+            .next_hop(&CryptDENull::from(top_hop_key))
+            .unwrap()
+            .payer
+            .unwrap()
+            .nonce
+    }
+
     #[test]
     fn id_decodes_return_route_id() {
         let mut cryptde = CryptDENull::new();
@@ -290,7 +390,7 @@ mod tests {
     #[test]
     fn construct_does_not_like_route_segments_with_too_few_keys() {
         let cryptde = CryptDENull::new();
-        let consuming_wallet = Wallet::new("wallet");
+        let consuming_wallet = make_paying_wallet(b"wallet");
         let result = Route::one_way(
             RouteSegment::new(vec![], Component::ProxyClient),
             &cryptde,
@@ -309,7 +409,7 @@ mod tests {
         let c_key = PublicKey::new(&[67, 67, 67]);
         let d_key = PublicKey::new(&[68, 68, 68]);
         let mut cryptde = CryptDENull::new();
-        let consuming_wallet = Wallet::new("wallet");
+        let consuming_wallet = make_paying_wallet(b"wallet");
         cryptde.generate_key_pair();
 
         let result = Route::round_trip(
@@ -359,7 +459,7 @@ mod tests {
 
         let mut cryptde = CryptDENull::new();
         cryptde.generate_key_pair();
-        let consuming_wallet = Wallet::new("wallet");
+        let consuming_wallet = make_paying_wallet(b"wallet");
         let return_route_id = 4321;
 
         let subject = Route::round_trip(
@@ -370,76 +470,254 @@ mod tests {
             return_route_id,
         )
         .unwrap();
-
+        let nonce = route_nonce(&subject, &a_key);
+
+        assert_eq!(subject.hops.len(), 12);
+        let failure_nonce = LiveHop::decode(&CryptDENull::from(&c_key), &subject.hops[8])
+            .unwrap()
+            .payer
+            .unwrap()
+            .nonce;
+        let twelfth_hop = Route::encrypt_return_route_id(return_route_id, &cryptde);
+        assert_eq!(twelfth_hop, subject.hops[11], "twelfth hop");
+        let eleventh_hop = paid_hop(
+            &PublicKey::new(b""),
+            Component::ProxyServer,
+            &consuming_wallet,
+            &a_key,
+            failure_nonce,
+            false,
+            Some(&twelfth_hop),
+        )
+        .encode(&a_key, &cryptde)
+        .unwrap();
+        assert_eq!(eleventh_hop, subject.hops[10], "eleventh hop");
+        let tenth_hop = paid_hop(
+            &a_key,
+            Component::Hopper,
+            &consuming_wallet,
+            &b_key,
+            failure_nonce,
+            false,
+            Some(&eleventh_hop),
+        )
+        .encode(&b_key, &cryptde)
+        .unwrap();
+        assert_eq!(tenth_hop, subject.hops[9], "tenth hop");
+        let ninth_hop = paid_hop(
+            &b_key,
+            Component::Hopper,
+            &consuming_wallet,
+            &c_key,
+            failure_nonce,
+            false,
+            Some(&tenth_hop),
+        )
+        .encode(&c_key, &cryptde)
+        .unwrap();
+        assert_eq!(ninth_hop, subject.hops[8], "ninth hop");
+        let eighth_hop = Route::encrypt_return_route_id(return_route_id, &cryptde);
+        assert_eq!(eighth_hop, subject.hops[7], "eighth hop");
+        let seventh_hop = paid_hop(
+            &PublicKey::new(b""),
+            Component::ProxyServer,
+            &consuming_wallet,
+            &a_key,
+            nonce,
+            true,
+            Some(&eighth_hop),
+        )
+        .encode(&a_key, &cryptde)
+        .unwrap();
+        assert_eq!(seventh_hop, subject.hops[6], "seventh hop");
+        let sixth_hop = paid_hop(
+            &a_key,
+            Component::Hopper,
+            &consuming_wallet,
+            &f_key,
+            nonce,
+            true,
+            Some(&seventh_hop),
+        )
+        .encode(&f_key, &cryptde)
+        .unwrap();
+        assert_eq!(sixth_hop, subject.hops[5], "sixth hop");
+        let fifth_hop = paid_hop(
+            &f_key,
+            Component::Hopper,
+            &consuming_wallet,
+            &e_key,
+            nonce,
+            true,
+            Some(&sixth_hop),
+        )
+        .encode(&e_key, &cryptde)
+        .unwrap();
+        assert_eq!(fifth_hop, subject.hops[4], "fifth hop");
+        let fourth_hop = paid_hop(
+            &e_key,
+            Component::ProxyClient,
+            &consuming_wallet,
+            &d_key,
+            nonce,
+            true,
+            Some(&fifth_hop),
+        )
+        .encode(&d_key, &cryptde)
+        .unwrap();
+        assert_eq!(fourth_hop, subject.hops[3], "fourth hop");
+        let mut third_hop = LiveHop::new(&d_key, None, Component::Hopper);
+        third_hop.failure_return = Some(FailureReturn {
+            route_offset: 6,
+            payload: make_failure_return(&c_key, &d_key, &a_key, &cryptde),
+        });
+        third_hop
+            .sign_payer(&consuming_wallet, &c_key, nonce, false, Some(&fourth_hop))
+            .unwrap();
+        let third_hop = third_hop.encode(&c_key, &cryptde).unwrap();
+        assert_eq!(third_hop, subject.hops[2], "third hop");
+        let mut second_hop = LiveHop::new(&c_key, None, Component::Hopper);
+        second_hop.failure_return = Some(FailureReturn {
+            route_offset: 8,
+            payload: make_failure_return(&b_key, &c_key, &a_key, &cryptde),
+        });
+        second_hop
+            .sign_payer(&consuming_wallet, &b_key, nonce, false, Some(&third_hop))
+            .unwrap();
+        let second_hop = second_hop.encode(&b_key, &cryptde).unwrap();
+        assert_eq!(second_hop, subject.hops[1], "second hop");
         assert_eq!(
-            LiveHop::new(&b_key, Some(consuming_wallet.clone()), Component::Hopper)
-                .encode(&a_key, &cryptde)
-                .unwrap(),
+            paid_hop(
+                &b_key,
+                Component::Hopper,
+                &consuming_wallet,
+                &a_key,
+                nonce,
+                false,
+                Some(&second_hop),
+            )
+            .encode(&a_key, &cryptde)
+            .unwrap(),
             subject.hops[0],
             "first hop"
         );
+    }
 
-        assert_eq!(
-            LiveHop::new(&c_key, Some(consuming_wallet.clone()), Component::Hopper)
-                .encode(&b_key, &cryptde)
-                .unwrap(),
-            subject.hops[1],
-            "second hop"
-        );
+    #[test]
+    fn failure_returns_lead_back_through_the_traversed_nodes_to_the_originating_proxy_server() {
+        let originator_cryptde = CryptDENull::new();
+        let originator_key = originator_cryptde.public_key().clone();
+        let first_relay_key = PublicKey::new(&[66, 66, 66]);
+        let second_relay_key = PublicKey::new(&[67, 67, 67]);
+        let exit_key = PublicKey::new(&[68, 68, 68]);
+        let consuming_wallet = make_paying_wallet(b"wallet");
+        let mut route = Route::round_trip(
+            RouteSegment::new(
+                vec![
+                    &originator_key,
+                    &first_relay_key,
+                    &second_relay_key,
+                    &exit_key,
+                ],
+                Component::ProxyClient,
+            ),
+            RouteSegment::new(vec![&exit_key, &originator_key], Component::ProxyServer),
+            &originator_cryptde,
+            Some(consuming_wallet.clone()),
+            1234,
+        )
+        .unwrap();
+        route.shift(&originator_cryptde).unwrap();
+        let first_relay_route = route.clone();
+        route.shift(&CryptDENull::from(&first_relay_key)).unwrap();
+        let second_relay_route = route;
+
+        let check = |route: &Route, traversed_keys: Vec<&PublicKey>, failed_key: &PublicKey| {
+            let reporting_key = traversed_keys[0];
+            let failure_return = route
+                .next_hop(&CryptDENull::from(reporting_key))
+                .unwrap()
+                .failure_return
+                .unwrap();
+            let package = failure_return.to_package(route).unwrap();
+            let mut failure_route = package.route.clone();
+            for (index, key) in traversed_keys.iter().enumerate() {
+                let key_cryptde = CryptDENull::from(key);
+                let hop = failure_route.next_hop(&key_cryptde).unwrap();
+                assert_eq!(
+                    hop.payer_owns_secret_key(key, &failure_route.hops[1..]),
+                    true
+                );
+                assert_eq!(hop.consuming_wallet(), Some(consuming_wallet.clone()));
+                match traversed_keys.get(index + 1) {
+                    Some(next_key) => {
+                        assert_eq!(&&hop.public_key, next_key);
+                        assert_eq!(hop.component, Component::Hopper);
+                    }
+                    None => {
+                        assert_eq!(hop.public_key, PublicKey::new(b""));
+                        assert_eq!(hop.component, Component::ProxyServer);
+                    }
+                }
+                failure_route.shift(&key_cryptde).unwrap();
+            }
+            assert_eq!(failure_route.id(&originator_cryptde), Ok(1234));
+            assert_eq!(
+                decodex::<MessageType>(&originator_cryptde, &package.payload).unwrap(),
+                MessageType::RouteFailed(RouteFailure {
+                    reporting_key: reporting_key.clone(),
+                    failed_key: failed_key.clone(),
+                    reason: String::new(),
+                })
+            );
+        };
 
-        assert_eq!(
-            LiveHop::new(&d_key, Some(consuming_wallet.clone()), Component::Hopper)
-                .encode(&c_key, &cryptde)
-                .unwrap(),
-            subject.hops[2],
-            "third hop"
+        check(
+            &first_relay_route,
+            vec![&first_relay_key, &originator_key],
+            &second_relay_key,
         );
-
-        assert_eq!(
-            LiveHop::new(
-                &e_key,
-                Some(consuming_wallet.clone()),
-                Component::ProxyClient
-            )
-            .encode(&d_key, &cryptde)
-            .unwrap(),
-            subject.hops[3],
-            "fourth hop"
+        check(
+            &second_relay_route,
+            vec![&second_relay_key, &first_relay_key, &originator_key],
+            &exit_key,
         );
+    }
 
-        assert_eq!(
-            LiveHop::new(&f_key, Some(consuming_wallet.clone()), Component::Hopper)
-                .encode(&e_key, &cryptde)
-                .unwrap(),
-            subject.hops[4],
-            "fifth hop"
-        );
+    #[test]
+    fn construct_does_not_add_failure_returns_to_one_way_routes() {
+        let cryptde = CryptDENull::new();
+        let a_key = cryptde.public_key().clone();
+        let b_key = PublicKey::new(&[66, 66, 66]);
+        let c_key = PublicKey::new(&[67, 67, 67]);
+        let route = Route::one_way(
+            RouteSegment::new(vec![&a_key, &b_key, &c_key], Component::Neighborhood),
+            &cryptde,
+            None,
+        )
+        .unwrap();
 
-        assert_eq!(
-            LiveHop::new(&a_key, Some(consuming_wallet.clone()), Component::Hopper)
-                .encode(&f_key, &cryptde)
-                .unwrap(),
-            subject.hops[5],
-            "sixth hop"
-        );
+        let b_hop = LiveHop::decode(&CryptDENull::from(&b_key), &route.hops[1]).unwrap();
 
-        assert_eq!(
-            LiveHop::new(
-                &PublicKey::new(b""),
-                Some(consuming_wallet.clone()),
-                Component::ProxyServer,
-            )
-            .encode(&a_key, &cryptde)
-            .unwrap(),
-            subject.hops[6],
-            "seventh hop"
-        );
+        assert_eq!(b_hop.failure_return, None);
+    }
 
-        assert_eq!(
-            Route::encrypt_return_route_id(return_route_id, &cryptde),
-            subject.hops[7],
-            "eighth hop"
-        );
+    fn make_failure_return(
+        reporting_key: &PublicKey,
+        failed_key: &PublicKey,
+        originator_key: &PublicKey,
+        cryptde: &dyn CryptDE,
+    ) -> CryptData {
+        encodex(
+            cryptde,
+            originator_key,
+            &MessageType::RouteFailed(RouteFailure {
+                reporting_key: reporting_key.clone(),
+                failed_key: failed_key.clone(),
+                reason: String::new(),
+            }),
+        )
+        .unwrap()
     }
 
     #[test]
@@ -448,7 +726,7 @@ mod tests {
         let b_key = PublicKey::new(&[66, 66, 66]);
         let mut cryptde = CryptDENull::new();
         cryptde.generate_key_pair();
-        let consuming_wallet = Wallet::new("wallet");
+        let consuming_wallet = make_paying_wallet(b"wallet");
 
         let subject = Route::one_way(
             RouteSegment::new(vec![&a_key, &b_key], Component::Neighborhood),
@@ -456,29 +734,58 @@ mod tests {
             Some(consuming_wallet.clone()),
         )
         .unwrap();
+        let nonce = route_nonce(&subject, &a_key);
+
+        let second_hop = paid_hop(
+            &PublicKey::new(b""),
+            Component::Neighborhood,
+            &consuming_wallet,
+            &b_key,
+            nonce,
+            false,
+            None,
+        )
+        .encode(&b_key, &cryptde)
+        .unwrap();
+        let first_hop = paid_hop(
+            &b_key,
+            Component::Hopper,
+            &consuming_wallet,
+            &a_key,
+            nonce,
+            false,
+            Some(&second_hop),
+        )
+        .encode(&a_key, &cryptde)
+        .unwrap();
+        assert_eq!(vec!(first_hop, second_hop), subject.hops);
+    }
 
-        assert_eq!(
-            vec!(
-                LiveHop::new(&b_key, Some(consuming_wallet.clone()), Component::Hopper)
-                    .encode(&a_key, &cryptde)
-                    .unwrap(),
-                LiveHop::new(
-                    &PublicKey::new(b""),
-                    Some(consuming_wallet.clone()),
-                    Component::Neighborhood,
-                )
-                .encode(&b_key, &cryptde)
-                .unwrap(),
-            ),
-            subject.hops,
-        );
+    #[test]
+    fn construct_gives_each_route_its_own_nonce() {
+        let cryptde = CryptDENull::new();
+        let a_key = cryptde.public_key().clone();
+        let b_key = PublicKey::new(&[66, 66, 66]);
+        let make_route = || {
+            Route::one_way(
+                RouteSegment::new(vec![&a_key, &b_key], Component::Neighborhood),
+                &cryptde,
+                Some(make_paying_wallet(b"wallet")),
+            )
+            .unwrap()
+        };
+
+        let first_nonce = route_nonce(&make_route(), &a_key);
+        let second_nonce = route_nonce(&make_route(), &a_key);
+
+        assert_ne!(first_nonce, second_nonce);
     }
 
     #[test]
     fn next_hop_decodes_top_hop() {
         let mut cryptde = CryptDENull::new();
         cryptde.generate_key_pair();
-        let consuming_wallet = Wallet::new("wallet");
+        let consuming_wallet = make_paying_wallet(b"wallet");
         let key12 = cryptde.public_key();
         let key34 = PublicKey::new(&[3, 4]);
         let key56 = PublicKey::new(&[5, 6]);
@@ -488,29 +795,48 @@ mod tests {
             Some(consuming_wallet.clone()),
         )
         .unwrap();
+        let nonce = route_nonce(&subject, &key12);
 
         let next_hop = subject.next_hop(&cryptde).unwrap();
 
-        assert_eq!(
-            next_hop,
-            LiveHop::new(&key34, Some(consuming_wallet.clone()), Component::Hopper)
+        let third_hop = paid_hop(
+            &PublicKey::new(b""),
+            Component::Neighborhood,
+            &consuming_wallet,
+            &key56,
+            nonce,
+            false,
+            None,
+        )
+        .encode(&key56, &cryptde)
+        .unwrap();
+        let second_hop = paid_hop(
+            &key56,
+            Component::Hopper,
+            &consuming_wallet,
+            &key34,
+            nonce,
+            false,
+            Some(&third_hop),
+        )
+        .encode(&key34, &cryptde)
+        .unwrap();
+        let first_hop = paid_hop(
+            &key34,
+            Component::Hopper,
+            &consuming_wallet,
+            &key12,
+            nonce,
+            false,
+            Some(&second_hop),
         );
+        assert_eq!(next_hop, first_hop);
         assert_eq!(
             subject.hops,
             vec!(
-                LiveHop::new(&key34, Some(consuming_wallet.clone()), Component::Hopper)
-                    .encode(&key12, &cryptde)
-                    .unwrap(),
-                LiveHop::new(&key56, Some(consuming_wallet.clone()), Component::Hopper)
-                    .encode(&key34, &cryptde)
-                    .unwrap(),
-                LiveHop::new(
-                    &PublicKey::new(b""),
-                    Some(consuming_wallet.clone()),
-                    Component::Neighborhood,
-                )
-                .encode(&key56, &cryptde)
-                .unwrap(),
+                first_hop.encode(&key12, &cryptde).unwrap(),
+                second_hop,
+                third_hop
             )
         );
     }
@@ -519,7 +845,7 @@ mod tests {
     fn shift_returns_next_hop_and_adds_garbage_at_the_bottom() {
         let mut cryptde = CryptDENull::new();
         cryptde.generate_key_pair();
-        let consuming_wallet = Wallet::new("wallet");
+        let consuming_wallet = make_paying_wallet(b"wallet");
         let key12 = cryptde.public_key();
         let key34 = PublicKey::new(&[3, 4]);
         let key56 = PublicKey::new(&[5, 6]);
@@ -530,30 +856,49 @@ mod tests {
         )
         .unwrap();
         let top_hop_len = subject.hops.first().unwrap().len();
+        let nonce = route_nonce(&subject, &key12);
 
         let next_hop = subject.shift(&cryptde).unwrap();
 
+        let third_hop = paid_hop(
+            &PublicKey::new(b""),
+            Component::Neighborhood,
+            &consuming_wallet,
+            &key56,
+            nonce,
+            false,
+            None,
+        )
+        .encode(&key56, &cryptde)
+        .unwrap();
+        let second_hop = paid_hop(
+            &key56,
+            Component::Hopper,
+            &consuming_wallet,
+            &key34,
+            nonce,
+            false,
+            Some(&third_hop),
+        )
+        .encode(&key34, &cryptde)
+        .unwrap();
         assert_eq!(
             next_hop,
-            LiveHop::new(&key34, Some(consuming_wallet.clone()), Component::Hopper)
+            paid_hop(
+                &key34,
+                Component::Hopper,
+                &consuming_wallet,
+                &key12,
+                nonce,
+                false,
+                Some(&second_hop),
+            )
         );
         let mut garbage_can: Vec<u8> = iter::repeat(0u8).take(top_hop_len).collect();
         cryptde.random(&mut garbage_can[..]);
         assert_eq!(
             subject.hops,
-            vec!(
-                LiveHop::new(&key56, Some(consuming_wallet.clone()), Component::Hopper)
-                    .encode(&key34, &cryptde)
-                    .unwrap(),
-                LiveHop::new(
-                    &PublicKey::new(b""),
-                    Some(consuming_wallet.clone()),
-                    Component::Neighborhood,
-                )
-                .encode(&key56, &cryptde)
-                .unwrap(),
-                CryptData::new(&garbage_can[..])
-            )
+            vec!(second_hop, third_hop, CryptData::new(&garbage_can[..]))
         )
     }
 
@@ -584,7 +929,7 @@ mod tests {
         let key1 = PublicKey::new(&[1, 2, 3, 4]);
         let key2 = PublicKey::new(&[4, 3, 2, 1]);
         let cryptde = CryptDENull::new();
-        let consuming_wallet = Wallet::new("wallet");
+        let consuming_wallet = make_paying_wallet(b"wallet");
         let original = Route::round_trip(
             RouteSegment::new(vec![&key1, &key2], Component::ProxyClient),
             RouteSegment::new(vec![&key2, &key1], Component::ProxyServer),
@@ -54,6 +54,12 @@ where
         }
     }
 
+    pub fn remove(&mut self, key: &K) -> Option<Rc<V>> {
+        self.remove_expired_entries();
+
+        self.data.borrow_mut().remove(key).map(|(result, _)| result)
+    }
+
     fn remove_expired_entries(&self) {
         let now = Instant::now();
 
@@ -111,6 +117,17 @@ mod tests {
         assert_eq!(subject.ttl(), Duration::from_millis(10));
     }
 
+    #[test]
+    fn ttl_hashmap_remove_returns_and_forgets_entry() {
+        let mut subject = TtlHashMap::new(Duration::from_millis(1000));
+
+        subject.insert(42u32, "Hello");
+
+        assert_eq!(subject.remove(&42u32).unwrap().as_ref(), &"Hello");
+        assert_eq!(subject.get(&42u32), None);
+        assert_eq!(subject.remove(&42u32), None);
+    }
+
     #[test]
     fn ttl_hashmap_get_removes_expired_entry() {
         let mut subject = TtlHashMap::new(Duration::from_millis(10));
@@ -1,15 +1,75 @@
 // Copyright (c) 2017-2018, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::config_reload::ReloadableConfiguration;
+use crate::sub_lib::neighborhood::ConnectionState;
+use crate::sub_lib::neighborhood::NeighborhoodGraph;
+use crate::sub_lib::neighborhood::RatePack;
 use crate::sub_lib::peer_actors::BindMessage;
+use crate::sub_lib::proxy_server::StreamInfo;
 use actix::Message;
 use actix::Recipient;
 use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 
 pub const DEFAULT_UI_PORT: u16 = 5333;
+pub const UI_PROTOCOL_VERSION: u32 = 1;
+// Files in the data directory holding the tokens UIs authenticate with
+pub const CONTROL_TOKEN_FILE: &str = "ui_control_token";
+pub const READ_ONLY_TOKEN_FILE: &str = "ui_read_only_token";
 
 #[derive(Clone)]
 pub struct UiGatewayConfig {
     pub ui_port: u16,
     pub node_descriptor: String,
+    pub node_configuration: NodeConfiguration,
+    pub ui_tokens: UiTokens,
+    // What the Node was started with, so that it can read its configuration again on request
+    pub reload_args: Vec<String>,
+    pub reloadable_configuration: ReloadableConfiguration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum UiClientRole {
+    ReadOnly,
+    Control,
+}
+
+/// The secrets a UI must present before the Node will talk to it. They're regenerated every
+/// time the Node starts; an empty token never authenticates anybody.
+#[derive(Clone, Default, PartialEq)]
+pub struct UiTokens {
+    pub control: String,
+    pub read_only: String,
+}
+
+impl fmt::Debug for UiTokens {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "UiTokens {{ <redacted> }}")
+    }
+}
+
+impl UiTokens {
+    pub fn role_for(&self, token: &str) -> Option<UiClientRole> {
+        if UiTokens::matches(&self.control, token) {
+            Some(UiClientRole::Control)
+        } else if UiTokens::matches(&self.read_only, token) {
+            Some(UiClientRole::ReadOnly)
+        } else {
+            None
+        }
+    }
+
+    // Takes the same time however much of the token is right.
+    fn matches(expected: &str, candidate: &str) -> bool {
+        if expected.is_empty() || expected.len() != candidate.len() {
+            return false;
+        }
+        expected
+            .bytes()
+            .zip(candidate.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+    }
 }
 
 #[derive(Clone)]
@@ -17,23 +77,274 @@ pub struct UiGatewaySubs {
     pub bind: Recipient<BindMessage>,
     pub ui_message_sub: Recipient<UiCarrierMessage>,
     pub from_ui_message_sub: Recipient<FromUiMessage>,
+    pub connection_state_sub: Recipient<ConnectionStateMessage>,
+    pub event_sub: Recipient<UiEventMessage>,
 }
 
 #[derive(Message, Debug, Serialize, Deserialize, PartialEq)]
 pub struct UiCarrierMessage {
     pub client_id: u64,
+    // Present when the UI sent its request in a UiEnvelope; the answer goes back in one too.
+    pub correlation_id: Option<u64>,
     pub data: UiMessage,
 }
 
+/// The versioned form of a UiMessage on the wire. UIs that send bare UiMessages get bare
+/// UiMessages back.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct UiEnvelope {
+    pub version: u32,
+    pub correlation_id: u64,
+    pub message: UiMessage,
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub enum UiMessage {
     ShutdownMessage,
     GetNodeDescriptor,
     NodeDescriptor(String),
+    GetConnectionState,
+    ConnectionState(ConnectionState),
+    GetNeighborhoodGraph,
+    NeighborhoodGraph(NeighborhoodGraph),
+    GetNodeStatus,
+    NodeStatus(NodeStatus),
+    GetActiveStreams,
+    ActiveStreams(Vec<StreamInfo>),
+    GetConfiguration,
+    Configuration(NodeConfiguration),
+    GetWalletAddresses,
+    WalletAddresses(WalletAddresses),
+    Error(String),
+    // log_level applies to EventTopic::Logs: the least severe level of log line to send
+    Subscribe {
+        topics: Vec<EventTopic>,
+        log_level: Option<String>,
+    },
+    Unsubscribe {
+        topics: Vec<EventTopic>,
+    },
+    Subscriptions(Vec<EventTopic>),
+    Event(UiEvent),
+    // Must be the first message from a UI that didn't authenticate in its upgrade request
+    Authenticate {
+        token: String,
+    },
+    Authenticated(UiClientRole),
+    SetRouteConstraints {
+        exit_keys: Vec<String>,
+        excluded_keys: Vec<String>,
+        excluded_ip_ranges: Vec<String>,
+    },
+    GetLogLevels,
+    LogLevels(LogLevels),
+    // With no component, sets the global level; with a component but no level, removes the
+    // component's override so that it follows the global level again
+    SetLogLevel {
+        component: Option<String>,
+        level: Option<String>,
+    },
+    // Takes effect for new traffic right away, and for other Nodes with the next Gossip
+    SetRatePack(RatePack),
+    // Rereads the command line and config file, like SIGHUP; answered with the names of the
+    // parameters that changed, or with an Error if any of them can't change without a restart
+    ReloadConfiguration,
+    ConfigurationReloaded(Vec<String>),
+}
+
+impl UiMessage {
+    // Messages that change what the Node does, as opposed to asking about it
+    pub fn requires_control(&self) -> bool {
+        match self {
+            UiMessage::ShutdownMessage
+            | UiMessage::SetRouteConstraints { .. }
+            | UiMessage::SetLogLevel { .. }
+            | UiMessage::SetRatePack(_)
+            | UiMessage::ReloadConfiguration => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NodeStatus {
+    pub uptime_secs: u64,
+    pub decentralized: bool,
+    pub connection_state: ConnectionState,
+    pub neighbor_count: usize,
+}
+
+/// The parts of the Node's startup configuration a UI may show. Secrets, like the consuming
+/// wallet's private key, stay out of it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodeConfiguration {
+    pub is_decentralized: bool,
+    pub is_bootstrap_node: bool,
+    pub ip_address: String,
+    pub clandestine_ports: Vec<u16>,
+    pub neighbors: Vec<String>,
+    pub dns_servers: Vec<String>,
+    pub ui_port: u16,
+    pub data_directory: String,
+    pub log_level: String,
+    pub blockchain_service_url: Option<String>,
+    pub multipath: bool,
+    pub behind_nat: bool,
+    pub rate_pack: RatePack,
+    pub wallet_addresses: WalletAddresses,
+}
+
+/// The levels at which the Node's components are logging right now
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LogLevels {
+    pub global: String,
+    pub components: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct WalletAddresses {
+    pub earning: String,
+    pub consuming: Option<String>,
+}
+
+/// Sent by the Neighborhood whenever its ConnectionState or its number of full neighbors
+/// changes, so the UiGateway can report them.
+#[derive(Message, Clone, PartialEq, Debug)]
+pub struct ConnectionStateMessage {
+    pub state: ConnectionState,
+    pub neighbor_count: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum EventTopic {
+    Neighborhood,
+    Streams,
+    RouteFailures,
+    Payments,
+    Logs,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum UiEvent {
+    NeighborhoodChanged {
+        connection_state: ConnectionState,
+        neighbor_count: usize,
+    },
+    StreamOpened {
+        stream_key: String,
+        client_addr: String,
+    },
+    StreamClosed {
+        stream_key: String,
+    },
+    RouteFailure {
+        target: String,
+        reason: String,
+    },
+    PaymentRequested {
+        wallet: String,
+        amount: i64,
+    },
+    LogLine {
+        level: String,
+        component: String,
+        message: String,
+    },
+    // Sent to a subscriber in place of events it was too slow to take
+    EventsDropped {
+        count: usize,
+    },
+    ShuttingDown,
+}
+
+impl UiEvent {
+    // Events without a topic go to every connected UI, subscribed or not.
+    pub fn topic(&self) -> Option<EventTopic> {
+        match self {
+            UiEvent::NeighborhoodChanged { .. } => Some(EventTopic::Neighborhood),
+            UiEvent::StreamOpened { .. } | UiEvent::StreamClosed { .. } => {
+                Some(EventTopic::Streams)
+            }
+            UiEvent::RouteFailure { .. } => Some(EventTopic::RouteFailures),
+            UiEvent::PaymentRequested { .. } => Some(EventTopic::Payments),
+            UiEvent::LogLine { .. } => Some(EventTopic::Logs),
+            UiEvent::EventsDropped { .. } | UiEvent::ShuttingDown => None,
+        }
+    }
+}
+
+/// Sent to the UiGateway by any actor with something to tell subscribed UIs.
+#[derive(Message, Clone, Debug, PartialEq)]
+pub struct UiEventMessage {
+    pub event: UiEvent,
 }
 
 #[derive(Message, PartialEq, Debug)]
 pub struct FromUiMessage {
     pub client_id: u64,
+    pub role: UiClientRole,
     pub json: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ui_tokens_grant_roles_only_for_exact_nonempty_matches() {
+        let subject = UiTokens {
+            control: "control-token".to_string(),
+            read_only: "read-only-token".to_string(),
+        };
+
+        assert_eq!(
+            subject.role_for("control-token"),
+            Some(UiClientRole::Control)
+        );
+        assert_eq!(
+            subject.role_for("read-only-token"),
+            Some(UiClientRole::ReadOnly)
+        );
+        assert_eq!(subject.role_for("control-tokeN"), None);
+        assert_eq!(subject.role_for("control"), None);
+        assert_eq!(UiTokens::default().role_for(""), None);
+        assert_eq!(format!("{:?}", subject), "UiTokens { <redacted> }");
+    }
+
+    #[test]
+    fn only_messages_that_change_the_node_require_control() {
+        assert_eq!(UiMessage::ShutdownMessage.requires_control(), true);
+        assert_eq!(
+            UiMessage::SetRouteConstraints {
+                exit_keys: vec![],
+                excluded_keys: vec![],
+                excluded_ip_ranges: vec![],
+            }
+            .requires_control(),
+            true
+        );
+        assert_eq!(
+            UiMessage::SetLogLevel {
+                component: None,
+                level: Some("debug".to_string()),
+            }
+            .requires_control(),
+            true
+        );
+        assert_eq!(
+            UiMessage::SetRatePack(RatePack::default()).requires_control(),
+            true
+        );
+        assert_eq!(UiMessage::ReloadConfiguration.requires_control(), true);
+        assert_eq!(UiMessage::GetNodeStatus.requires_control(), false);
+        assert_eq!(UiMessage::GetLogLevels.requires_control(), false);
+        assert_eq!(
+            UiMessage::Subscribe {
+                topics: vec![EventTopic::Logs],
+                log_level: None,
+            }
+            .requires_control(),
+            false
+        );
+    }
+}
@@ -1,17 +1,111 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::cryptde::CryptData;
+use crate::sub_lib::cryptde::PlainData;
+use rustc_hex::{FromHex, ToHex};
+use secp256k1::{Message, PublicKey, RecoveryId, SecretKey, Signature};
 use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use tiny_keccak::keccak256;
 use web3::types::{H160, H256};
 
-#[derive(Clone, Debug, Deserialize, Serialize, Eq, Hash, PartialEq)]
+const SIGNATURE_LENGTH: usize = 65;
+
+// The secret key never leaves the Node: it isn't serialized, and a Wallet is compared, hashed and
+// printed by its address alone.
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Wallet {
     pub address: String,
+    #[serde(skip)]
+    secret_key_opt: Option<SecretKey>,
 }
 
 impl Wallet {
     pub fn new(address: &str) -> Wallet {
         Wallet {
             address: String::from(address),
+            secret_key_opt: None,
+        }
+    }
+
+    // A wallet that can sign, with the Ethereum address that belongs to private_key
+    pub fn from_private_key(private_key: &str) -> Result<Wallet, String> {
+        let bytes: Vec<u8> = private_key
+            .from_hex()
+            .map_err(|e| format!("Private key is not hexadecimal: {:?}", e))?;
+        let secret_key = SecretKey::parse_slice(&bytes)
+            .map_err(|e| format!("Private key is not a valid secp256k1 key: {:?}", e))?;
+        Ok(Wallet {
+            address: Wallet::address_of(&PublicKey::from_secret_key(&secret_key)),
+            secret_key_opt: Some(secret_key),
+        })
+    }
+
+    pub fn can_sign(&self) -> bool {
+        self.secret_key_opt.is_some()
+    }
+
+    pub fn sign(&self, data: &PlainData) -> Result<CryptData, String> {
+        let secret_key = match self.secret_key_opt {
+            Some(ref secret_key) => secret_key,
+            None => return Err(format!("No private key for wallet {}", self.address)),
+        };
+        let (signature, recovery_id) = secp256k1::sign(&Wallet::message(data), secret_key)
+            .map_err(|e| format!("Couldn't sign for wallet {}: {:?}", self.address, e))?;
+        let mut bytes = signature.serialize().to_vec();
+        bytes.push(recovery_id.serialize());
+        Ok(CryptData::new(&bytes))
+    }
+
+    // Good only if the key that made the signature is the one this wallet's address came from
+    pub fn verify(&self, data: &PlainData, signature: &CryptData) -> bool {
+        let bytes = signature.as_slice();
+        if bytes.len() != SIGNATURE_LENGTH {
+            return false;
         }
+        let (signature, recovery_id) = match (
+            Signature::parse_slice(&bytes[..SIGNATURE_LENGTH - 1]),
+            RecoveryId::parse(bytes[SIGNATURE_LENGTH - 1]),
+        ) {
+            (Ok(signature), Ok(recovery_id)) => (signature, recovery_id),
+            _ => return false,
+        };
+        match secp256k1::recover(&Wallet::message(data), &signature, &recovery_id) {
+            Ok(public_key) => Wallet::address_of(&public_key).eq_ignore_ascii_case(&self.address),
+            Err(_) => false,
+        }
+    }
+
+    fn message(data: &PlainData) -> Message {
+        Message::parse(&keccak256(data.as_slice()))
+    }
+
+    fn address_of(public_key: &PublicKey) -> String {
+        // The first byte of the serialized key only says that it's uncompressed.
+        let hash = keccak256(&public_key.serialize()[1..]);
+        format!("0x{}", hash[12..].to_hex::<String>())
+    }
+}
+
+impl PartialEq for Wallet {
+    fn eq(&self, other: &Wallet) -> bool {
+        self.address == other.address
+    }
+}
+
+impl Eq for Wallet {}
+
+impl Hash for Wallet {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.address.hash(state)
+    }
+}
+
+impl fmt::Debug for Wallet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Wallet")
+            .field("address", &self.address)
+            .finish()
     }
 }
 
@@ -24,12 +118,16 @@ impl From<H256> for Wallet {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::test_utils::make_paying_wallet;
+
+    const PRIVATE_KEY: &str = "cc46befe8d169b89db447bd725fc2368b12542113555302598430cb5d5c74ea9";
 
     #[test]
     fn can_create_with_str_address() {
         let subject = Wallet::new("totally valid eth address");
 
         assert_eq!("totally valid eth address", subject.address);
+        assert_eq!(subject.can_sign(), false);
     }
 
     #[test]
@@ -40,4 +138,105 @@ mod tests {
 
         assert_eq!("0x3f69f9efd4f2592fd70be8c32ecd9dce71c472fc", result.address);
     }
+
+    #[test]
+    fn can_create_from_a_private_key() {
+        let result = Wallet::from_private_key(PRIVATE_KEY).unwrap();
+
+        assert_eq!(
+            result.address,
+            "0x".to_string()
+                + &keccak256(
+                    &PublicKey::from_secret_key(
+                        &SecretKey::parse_slice(&PRIVATE_KEY.from_hex::<Vec<u8>>().unwrap())
+                            .unwrap()
+                    )
+                    .serialize()[1..]
+                )[12..]
+                    .to_hex::<String>()
+        );
+        assert_eq!(result.can_sign(), true);
+    }
+
+    #[test]
+    fn cannot_create_from_a_bad_private_key() {
+        assert!(Wallet::from_private_key("booga").is_err());
+        assert!(Wallet::from_private_key(&"00".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn secret_key_is_neither_serialized_nor_printed_nor_compared() {
+        let subject = Wallet::from_private_key(PRIVATE_KEY).unwrap();
+
+        let serialized = serde_cbor::ser::to_vec(&subject).unwrap();
+        let deserialized: Wallet = serde_cbor::de::from_slice(&serialized).unwrap();
+
+        assert_eq!(deserialized.can_sign(), false);
+        assert_eq!(deserialized, subject);
+        assert_eq!(
+            format!("{:?}", subject),
+            format!("Wallet {{ address: {:?} }}", subject.address)
+        );
+    }
+
+    #[test]
+    fn wallet_can_verify_its_own_signature() {
+        let subject = make_paying_wallet(b"wallet");
+        let data = PlainData::new(b"I promise to pay");
+
+        let signature = subject.sign(&data).unwrap();
+
+        assert_eq!(subject.verify(&data, &signature), true);
+        assert_eq!(
+            Wallet::new(&subject.address.to_uppercase().replace("0X", "0x"))
+                .verify(&data, &signature),
+            true
+        );
+    }
+
+    #[test]
+    fn wallet_without_private_key_cannot_sign() {
+        let subject = Wallet::new("0xabcdef");
+
+        let result = subject.sign(&PlainData::new(b"I promise to pay"));
+
+        assert_eq!(
+            result,
+            Err("No private key for wallet 0xabcdef".to_string())
+        );
+    }
+
+    #[test]
+    fn wallet_rejects_signature_of_different_data() {
+        let subject = make_paying_wallet(b"wallet");
+        let signature = subject.sign(&PlainData::new(b"I promise to pay")).unwrap();
+
+        let result = subject.verify(&PlainData::new(b"I promise to pay more"), &signature);
+
+        assert_eq!(result, false);
+    }
+
+    #[test]
+    fn wallet_rejects_signature_made_by_another_wallet() {
+        let subject = make_paying_wallet(b"wallet");
+        let data = PlainData::new(b"I promise to pay");
+        let signature = make_paying_wallet(b"impostor").sign(&data).unwrap();
+
+        let result = subject.verify(&data, &signature);
+
+        assert_eq!(result, false);
+    }
+
+    #[test]
+    fn wallet_rejects_malformed_signature() {
+        let subject = make_paying_wallet(b"wallet");
+        let data = PlainData::new(b"I promise to pay");
+        let signature = subject.sign(&data).unwrap();
+
+        assert_eq!(
+            subject.verify(&data, &CryptData::new(&signature.as_slice()[1..])),
+            false
+        );
+        assert_eq!(subject.verify(&data, &CryptData::new(&[0u8; 65])), false);
+    }
 }
@@ -5,6 +5,7 @@ use crate::sub_lib::accountant::ReportExitServiceConsumedMessage;
 use crate::sub_lib::accountant::ReportExitServiceProvidedMessage;
 use crate::sub_lib::accountant::ReportRoutingServiceConsumedMessage;
 use crate::sub_lib::accountant::ReportRoutingServiceProvidedMessage;
+use crate::sub_lib::accountant::SetPayableScanIntervalMessage;
 use crate::sub_lib::blockchain_bridge::BlockchainBridgeSubs;
 use crate::sub_lib::blockchain_bridge::ReportAccountsPayable;
 use crate::sub_lib::dispatcher::DispatcherSubs;
@@ -12,30 +13,41 @@ use crate::sub_lib::dispatcher::InboundClientData;
 use crate::sub_lib::hopper::IncipientCoresPackage;
 use crate::sub_lib::hopper::{ExpiredCoresPackage, NoLookupIncipientCoresPackage};
 use crate::sub_lib::hopper::{HopperSubs, MessageType};
+use crate::sub_lib::hopper::{RouteFailure, UndeliverablePackageMessage};
 use crate::sub_lib::neighborhood::DispatcherNodeQueryMessage;
+use crate::sub_lib::neighborhood::Heartbeat;
+use crate::sub_lib::neighborhood::NeighborhoodGraphRequest;
 use crate::sub_lib::neighborhood::NeighborhoodSubs;
 use crate::sub_lib::neighborhood::NodeQueryMessage;
 use crate::sub_lib::neighborhood::NodeQueryResponseMetadata;
+use crate::sub_lib::neighborhood::RelayRequestMessage;
 use crate::sub_lib::neighborhood::RemoveNeighborMessage;
 use crate::sub_lib::neighborhood::RouteQueryMessage;
 use crate::sub_lib::neighborhood::RouteQueryResponse;
+use crate::sub_lib::neighborhood::SetInitialNeighborsMessage;
+use crate::sub_lib::neighborhood::SetRatePackMessage;
 use crate::sub_lib::neighborhood::{BootstrapNeighborhoodNowMessage, NodeRecordMetadataMessage};
 use crate::sub_lib::peer_actors::BindMessage;
 use crate::sub_lib::peer_actors::PeerActors;
 use crate::sub_lib::proxy_client::{ClientResponsePayload, InboundServerData};
-use crate::sub_lib::proxy_client::{DnsResolveFailure, ProxyClientSubs};
+use crate::sub_lib::proxy_client::{DnsResolveFailure, ProxyClientSubs, SetDnsServersMessage};
+use crate::sub_lib::proxy_server::ActiveStreamsRequest;
 use crate::sub_lib::proxy_server::ProxyServerSubs;
+use crate::sub_lib::proxy_server::SetRouteConstraintsMessage;
 use crate::sub_lib::proxy_server::{AddReturnRouteMessage, ClientRequestPayload};
 use crate::sub_lib::stream_handler_pool::DispatcherNodeQueryResponse;
 use crate::sub_lib::stream_handler_pool::TransmitDataMsg;
 use crate::sub_lib::ui_gateway::UiGatewaySubs;
-use crate::sub_lib::ui_gateway::{FromUiMessage, UiCarrierMessage};
+use crate::sub_lib::ui_gateway::{
+    ConnectionStateMessage, FromUiMessage, UiCarrierMessage, UiEventMessage,
+};
 use crate::test_utils::test_utils::to_millis;
 use actix::Actor;
 use actix::Addr;
 use actix::Context;
 use actix::Handler;
 use actix::MessageResult;
+use actix::System;
 use std::any::Any;
 use std::sync::Arc;
 use std::sync::Mutex;
@@ -47,6 +59,7 @@ pub struct Recorder {
     recording: Arc<Mutex<Recording>>,
     node_query_responses: Vec<Option<NodeQueryResponseMetadata>>,
     route_query_responses: Vec<Option<RouteQueryResponse>>,
+    stop_after_count: Option<usize>,
 }
 
 pub struct Recording {
@@ -78,7 +91,17 @@ recorder_message_handler!(ExpiredCoresPackage<ClientRequestPayload>);
 recorder_message_handler!(ExpiredCoresPackage<ClientResponsePayload>);
 recorder_message_handler!(ExpiredCoresPackage<DnsResolveFailure>);
 recorder_message_handler!(ExpiredCoresPackage<Gossip>);
+recorder_message_handler!(ExpiredCoresPackage<RouteFailure>);
+recorder_message_handler!(ExpiredCoresPackage<Heartbeat>);
+recorder_message_handler!(RelayRequestMessage);
+recorder_message_handler!(NeighborhoodGraphRequest);
 recorder_message_handler!(AddReturnRouteMessage);
+recorder_message_handler!(SetRouteConstraintsMessage);
+recorder_message_handler!(SetRatePackMessage);
+recorder_message_handler!(SetInitialNeighborsMessage);
+recorder_message_handler!(SetDnsServersMessage);
+recorder_message_handler!(SetPayableScanIntervalMessage);
+recorder_message_handler!(ActiveStreamsRequest);
 recorder_message_handler!(TransmitDataMsg);
 recorder_message_handler!(BindMessage);
 recorder_message_handler!(IncipientCoresPackage);
@@ -91,6 +114,8 @@ recorder_message_handler!(DispatcherNodeQueryResponse);
 recorder_message_handler!(DispatcherNodeQueryMessage);
 recorder_message_handler!(UiCarrierMessage);
 recorder_message_handler!(FromUiMessage);
+recorder_message_handler!(ConnectionStateMessage);
+recorder_message_handler!(UiEventMessage);
 recorder_message_handler!(ReportRoutingServiceProvidedMessage);
 recorder_message_handler!(ReportExitServiceProvidedMessage);
 recorder_message_handler!(ReportRoutingServiceConsumedMessage);
@@ -98,6 +123,7 @@ recorder_message_handler!(ReportExitServiceConsumedMessage);
 recorder_message_handler!(ReportAccountsPayable);
 recorder_message_handler!(DnsResolveFailure);
 recorder_message_handler!(NodeRecordMetadataMessage);
+recorder_message_handler!(UndeliverablePackageMessage);
 
 impl Handler<NodeQueryMessage> for Recorder {
     type Result = MessageResult<NodeQueryMessage>;
@@ -148,6 +174,7 @@ impl Recorder {
             recording: Arc::new(Mutex::new(Recording { messages: vec![] })),
             node_query_responses: vec![],
             route_query_responses: vec![],
+            stop_after_count: None,
         }
     }
 
@@ -159,6 +186,11 @@ impl Recorder {
         let messages: &mut Vec<Box<dyn Any + Send>> = &mut recording.messages;
         let item_box = Box::new(item);
         messages.push(item_box);
+        if let Some(count) = self.stop_after_count {
+            if messages.len() >= count {
+                System::current().stop();
+            }
+        }
     }
 
     pub fn get_recording(&self) -> Arc<Mutex<Recording>> {
@@ -180,6 +212,13 @@ impl Recorder {
         self.route_query_responses.push(response);
         self
     }
+
+    // Stops the System once this many messages are recorded, so timers in the actors under test
+    // don't keep firing after the test is done with them.
+    pub fn stop_after_count(mut self, count: usize) -> Recorder {
+        self.stop_after_count = Some(count);
+        self
+    }
 }
 
 impl Recording {
@@ -257,7 +296,12 @@ pub fn make_proxy_server_subs_from(addr: &Addr<Recorder>) -> ProxyServerSubs {
         dns_failure_from_hopper: addr
             .clone()
             .recipient::<ExpiredCoresPackage<DnsResolveFailure>>(),
+        route_failure_from_hopper: addr
+            .clone()
+            .recipient::<ExpiredCoresPackage<RouteFailure>>(),
         add_return_route: addr.clone().recipient::<AddReturnRouteMessage>(),
+        set_route_constraints: addr.clone().recipient::<SetRouteConstraintsMessage>(),
+        active_streams_request: addr.clone().recipient::<ActiveStreamsRequest>(),
     }
 }
 
@@ -275,6 +319,8 @@ pub fn make_hopper_subs_from(addr: &Addr<Recorder>) -> HopperSubs {
         from_hopper_client: addr.clone().recipient::<IncipientCoresPackage>(),
         from_hopper_client_no_lookup: addr.clone().recipient::<NoLookupIncipientCoresPackage>(),
         from_dispatcher: addr.clone().recipient::<InboundClientData>(),
+        undeliverable_package: addr.clone().recipient::<UndeliverablePackageMessage>(),
+        set_rate_pack: addr.clone().recipient::<SetRatePackMessage>(),
     }
 }
 
@@ -286,6 +332,8 @@ pub fn make_proxy_client_subs_from(addr: &Addr<Recorder>) -> ProxyClientSubs {
             .recipient::<ExpiredCoresPackage<ClientRequestPayload>>(),
         inbound_server_data: addr.clone().recipient::<InboundServerData>(),
         dns_resolve_failed: addr.clone().recipient::<DnsResolveFailure>(),
+        set_rate_pack: addr.clone().recipient::<SetRatePackMessage>(),
+        set_dns_servers: addr.clone().recipient::<SetDnsServersMessage>(),
     }
 }
 
@@ -299,6 +347,11 @@ pub fn make_neighborhood_subs_from(addr: &Addr<Recorder>) -> NeighborhoodSubs {
         from_hopper: addr.clone().recipient::<ExpiredCoresPackage<Gossip>>(),
         dispatcher_node_query: addr.clone().recipient::<DispatcherNodeQueryMessage>(),
         remove_neighbor: addr.clone().recipient::<RemoveNeighborMessage>(),
+        heartbeat_from_hopper: addr.clone().recipient::<ExpiredCoresPackage<Heartbeat>>(),
+        from_relay_client: addr.clone().recipient::<RelayRequestMessage>(),
+        graph_request: addr.clone().recipient::<NeighborhoodGraphRequest>(),
+        set_rate_pack: addr.clone().recipient::<SetRatePackMessage>(),
+        set_initial_neighbors: addr.clone().recipient::<SetInitialNeighborsMessage>(),
     }
 }
 
@@ -313,6 +366,7 @@ pub fn make_accountant_subs_from(addr: &Addr<Recorder>) -> AccountantSubs {
             .clone()
             .recipient::<ReportRoutingServiceConsumedMessage>(),
         report_exit_service_consumed: addr.clone().recipient::<ReportExitServiceConsumedMessage>(),
+        set_payable_scan_interval: addr.clone().recipient::<SetPayableScanIntervalMessage>(),
     }
 }
 
@@ -321,6 +375,8 @@ pub fn make_ui_gateway_subs_from(addr: &Addr<Recorder>) -> UiGatewaySubs {
         bind: addr.clone().recipient::<BindMessage>(),
         ui_message_sub: addr.clone().recipient::<UiCarrierMessage>(),
         from_ui_message_sub: addr.clone().recipient::<FromUiMessage>(),
+        connection_state_sub: addr.clone().recipient::<ConnectionStateMessage>(),
+        event_sub: addr.clone().recipient::<UiEventMessage>(),
     }
 }
 
@@ -1,5 +1,6 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 use crate::persistent_configuration::HTTP_PORT;
+use crate::sub_lib::compression::PayloadCompression;
 use crate::sub_lib::cryptde::CryptDE;
 use crate::sub_lib::cryptde::CryptData;
 use crate::sub_lib::cryptde::PlainData;
@@ -21,6 +22,7 @@ use crate::sub_lib::stream_key::StreamKey;
 use crate::sub_lib::wallet::Wallet;
 use lazy_static::lazy_static;
 use regex::Regex;
+use rustc_hex::ToHex;
 use std::cmp::min;
 use std::collections::btree_set::BTreeSet;
 use std::collections::HashSet;
@@ -45,9 +47,23 @@ use std::thread;
 use std::time::Duration;
 use std::time::Instant;
 use std::{fs, io};
+use tiny_keccak::keccak256;
 
 lazy_static! {
     static ref CRYPT_DE_NULL: CryptDENull = CryptDENull::new();
+    // Every Route gets a fresh nonce, so this one is made once to stay equal to itself.
+    static ref MEANINGLESS_ROUTE: Route = Route::one_way(
+        RouteSegment::new(
+            vec![
+                &PublicKey::new(&b"ooga"[..]),
+                &PublicKey::new(&b"booga"[..]),
+            ],
+            Component::ProxyClient,
+        ),
+        &CryptDENull::new(),
+        Some(make_paying_wallet(b"irrelevant")),
+    )
+    .unwrap();
 }
 
 pub fn cryptde() -> &'static CryptDENull {
@@ -215,18 +231,12 @@ pub fn make_meaningless_message_type() -> MessageType {
 }
 
 pub fn make_meaningless_route() -> Route {
-    Route::one_way(
-        RouteSegment::new(
-            vec![
-                &PublicKey::new(&b"ooga"[..]),
-                &PublicKey::new(&b"booga"[..]),
-            ],
-            Component::ProxyClient,
-        ),
-        &CryptDENull::new(),
-        Some(Wallet::new("irrelevant")),
-    )
-    .unwrap()
+    MEANINGLESS_ROUTE.clone()
+}
+
+// A wallet that can sign, with a private key made from seed
+pub fn make_paying_wallet(seed: &[u8]) -> Wallet {
+    Wallet::from_private_key(&keccak256(seed).to_hex::<String>()).unwrap()
 }
 
 pub fn make_meaningless_public_key() -> PublicKey {
@@ -264,6 +274,7 @@ pub fn zero_hop_route_response(
             vec![ExpectedService::Nothing, ExpectedService::Nothing],
             0,
         ),
+        exit_supports_compression: false,
     }
 }
 
@@ -298,6 +309,8 @@ pub fn make_request_payload(bytes: usize, cryptde: &CryptDE) -> ClientRequestPay
         target_port: HTTP_PORT,
         protocol: ProxyProtocol::HTTP,
         originator_public_key: cryptde.public_key().clone(),
+        compression: PayloadCompression::None,
+        redundant: false,
     }
 }
 
@@ -312,6 +325,8 @@ pub fn make_response_payload(bytes: usize, cryptde: &CryptDE) -> ClientResponseP
             sequence_number: 0,
             last_data: false,
         },
+        compression: PayloadCompression::None,
+        redundant: false,
     }
 }
 
@@ -503,7 +518,7 @@ mod tests {
 
         let subject = route_to_proxy_client(&key, &cryptde);
 
-        let mut garbage_can: Vec<u8> = iter::repeat(0u8).take(68).collect();
+        let mut garbage_can: Vec<u8> = iter::repeat(0u8).take(57).collect();
         cryptde.random(&mut garbage_can[..]);
         assert_eq!(
             subject.hops,
@@ -527,7 +542,7 @@ mod tests {
 
         let subject = route_from_proxy_client(&key, &cryptde);
 
-        let mut garbage_can: Vec<u8> = iter::repeat(0u8).take(68).collect();
+        let mut garbage_can: Vec<u8> = iter::repeat(0u8).take(57).collect();
         cryptde.random(&mut garbage_can[..]);
         assert_eq!(
             subject.hops,
@@ -551,7 +566,7 @@ mod tests {
 
         let subject = route_to_proxy_server(&key, &cryptde);
 
-        let mut garbage_can: Vec<u8> = iter::repeat(0u8).take(68).collect();
+        let mut garbage_can: Vec<u8> = iter::repeat(0u8).take(57).collect();
         cryptde.random(&mut garbage_can[..]);
         assert_eq!(
             subject.hops,
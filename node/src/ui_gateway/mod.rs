@@ -2,5 +2,6 @@
 
 mod shutdown_supervisor;
 pub mod ui_gateway;
+pub mod ui_tokens;
 mod ui_traffic_converter;
 mod websocket_supervisor;
@@ -1,28 +1,156 @@
 // Copyright (c) 2017-2018, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::config_reload::ConfigurationChange;
+use crate::config_reload::ReloadableConfiguration;
+use crate::sub_lib::accountant::SetPayableScanIntervalMessage;
+use crate::sub_lib::logger;
+use crate::sub_lib::logger::LogFilter;
 use crate::sub_lib::logger::Logger;
+use crate::sub_lib::logger::LOG_FILTER_FILE;
+use crate::sub_lib::neighborhood::ConnectionState;
+use crate::sub_lib::neighborhood::NeighborhoodGraphRequest;
+use crate::sub_lib::neighborhood::RatePack;
+use crate::sub_lib::neighborhood::RouteConstraints;
+use crate::sub_lib::neighborhood::SetInitialNeighborsMessage;
+use crate::sub_lib::neighborhood::SetRatePackMessage;
 use crate::sub_lib::peer_actors::BindMessage;
+use crate::sub_lib::proxy_client::SetDnsServersMessage;
+use crate::sub_lib::proxy_server::ActiveStreamsRequest;
+use crate::sub_lib::proxy_server::SetRouteConstraintsMessage;
+use crate::sub_lib::ui_gateway::ConnectionStateMessage;
+use crate::sub_lib::ui_gateway::EventTopic;
+use crate::sub_lib::ui_gateway::LogLevels;
+use crate::sub_lib::ui_gateway::NodeConfiguration;
+use crate::sub_lib::ui_gateway::NodeStatus;
+use crate::sub_lib::ui_gateway::UiClientRole;
+use crate::sub_lib::ui_gateway::UiEvent;
+use crate::sub_lib::ui_gateway::UiEventMessage;
 use crate::sub_lib::ui_gateway::UiGatewaySubs;
+use crate::sub_lib::ui_gateway::UiTokens;
 use crate::sub_lib::ui_gateway::{FromUiMessage, UiCarrierMessage};
 use crate::sub_lib::ui_gateway::{UiGatewayConfig, UiMessage};
+use crate::sub_lib::utils::NODE_MAILBOX_CAPACITY;
 use crate::ui_gateway::shutdown_supervisor::ShutdownSupervisor;
 use crate::ui_gateway::shutdown_supervisor::ShutdownSupervisorReal;
 use crate::ui_gateway::ui_traffic_converter::UiTrafficConverter;
 use crate::ui_gateway::ui_traffic_converter::UiTrafficConverterReal;
+use crate::ui_gateway::ui_traffic_converter::BROADCAST;
 use crate::ui_gateway::websocket_supervisor::WebSocketSupervisor;
 use crate::ui_gateway::websocket_supervisor::WebSocketSupervisorReal;
 use actix::Actor;
 use actix::Addr;
+use actix::AsyncContext;
 use actix::Context;
 use actix::Handler;
 use actix::Recipient;
+use actix::Running;
+use actix::StreamHandler;
+#[cfg(unix)]
+use futures::Future;
+#[cfg(unix)]
+use futures::Stream;
+use log::Level;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use std::time::Instant;
+#[cfg(unix)]
+use tokio_signal::unix::Signal;
+#[cfg(unix)]
+use tokio_signal::unix::SIGHUP;
+#[cfg(unix)]
+use tokio_signal::unix::SIGUSR1;
+
+const EVENT_FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+const EVENTS_PER_FLUSH: usize = 100;
+const EVENT_QUEUE_CAPACITY: usize = 1000;
+// A subscriber that's had to drop events this many flushes in a row is unsubscribed.
+const MAX_CONSECUTIVE_OVERFLOWS: usize = 50;
+
+type LogForwarder = Box<dyn Fn(Option<(Level, Recipient<UiEventMessage>)>)>;
+type LogFilterSetter = Box<dyn Fn(LogFilter)>;
+type ConfigurationReader = Box<dyn Fn(&[String]) -> Result<ReloadableConfiguration, String>>;
+
+// What the Node gets when somebody wants it to reread its log filter file
+#[derive(Debug)]
+struct ReloadLogFilter;
+
+// What the Node gets when somebody wants it to reread its command line and config file
+#[derive(Debug)]
+struct ReloadConfiguration;
+
+struct Subscriber {
+    topics: HashSet<EventTopic>,
+    log_level: Level,
+    queue: VecDeque<String>,
+    dropped: usize,
+    consecutive_overflows: usize,
+}
+
+impl Subscriber {
+    fn new() -> Subscriber {
+        Subscriber {
+            topics: HashSet::new(),
+            log_level: Level::Info,
+            queue: VecDeque::new(),
+            dropped: 0,
+            consecutive_overflows: 0,
+        }
+    }
+
+    fn wants(&self, event: &UiEvent, topic: EventTopic) -> bool {
+        if !self.topics.contains(&topic) {
+            return false;
+        }
+        match event {
+            UiEvent::LogLine { level, .. } => match Level::from_str(level) {
+                Ok(level) => level <= self.log_level,
+                Err(_) => true,
+            },
+            _ => true,
+        }
+    }
+
+    fn sorted_topics(&self) -> Vec<EventTopic> {
+        let mut topics = self.topics.iter().cloned().collect::<Vec<EventTopic>>();
+        topics.sort();
+        topics
+    }
+}
 
 pub struct UiGateway {
     port: u16,
     node_descriptor: String,
+    node_configuration: NodeConfiguration,
+    ui_tokens: UiTokens,
+    started_at: Instant,
+    connection_state: ConnectionState,
+    neighbor_count: usize,
     converter: Box<dyn UiTrafficConverter>,
     ui_message_sub: Option<Recipient<UiCarrierMessage>>,
+    set_route_constraints_sub: Option<Recipient<SetRouteConstraintsMessage>>,
+    neighborhood_graph_sub: Option<Recipient<NeighborhoodGraphRequest>>,
+    // The Neighborhood advertises the rates; the Hopper and ProxyClient charge them
+    set_rate_pack_subs: Vec<Recipient<SetRatePackMessage>>,
+    set_dns_servers_sub: Option<Recipient<SetDnsServersMessage>>,
+    set_initial_neighbors_sub: Option<Recipient<SetInitialNeighborsMessage>>,
+    set_payable_scan_interval_sub: Option<Recipient<SetPayableScanIntervalMessage>>,
+    active_streams_sub: Option<Recipient<ActiveStreamsRequest>>,
+    event_sub: Option<Recipient<UiEventMessage>>,
     websocket_supervisor: Option<Box<dyn WebSocketSupervisor>>,
     shutdown_supervisor: Box<dyn ShutdownSupervisor>,
+    subscribers: HashMap<u64, Subscriber>,
+    log_forwarder: LogForwarder,
+    startup_log_filter: LogFilter,
+    log_filter: LogFilter,
+    log_filter_setter: LogFilterSetter,
+    reload_args: Vec<String>,
+    reloadable_configuration: ReloadableConfiguration,
+    configuration_reader: ConfigurationReader,
     logger: Logger,
 }
 
@@ -31,10 +159,31 @@ impl UiGateway {
         UiGateway {
             port: config.ui_port,
             node_descriptor: config.node_descriptor.clone(),
+            node_configuration: config.node_configuration.clone(),
+            ui_tokens: config.ui_tokens.clone(),
+            started_at: Instant::now(),
+            connection_state: ConnectionState::Standalone,
+            neighbor_count: 0,
             converter: Box::new(UiTrafficConverterReal::new()),
             ui_message_sub: None,
+            set_route_constraints_sub: None,
+            neighborhood_graph_sub: None,
+            set_rate_pack_subs: vec![],
+            set_dns_servers_sub: None,
+            set_initial_neighbors_sub: None,
+            set_payable_scan_interval_sub: None,
+            active_streams_sub: None,
+            event_sub: None,
             websocket_supervisor: None,
             shutdown_supervisor: Box::new(ShutdownSupervisorReal::new()),
+            subscribers: HashMap::new(),
+            log_forwarder: Box::new(logger::forward_logs_to_ui),
+            startup_log_filter: logger::log_filter(),
+            log_filter: logger::log_filter(),
+            log_filter_setter: Box::new(logger::set_log_filter),
+            reload_args: config.reload_args.clone(),
+            reloadable_configuration: config.reloadable_configuration.clone(),
+            configuration_reader: Box::new(|args| ReloadableConfiguration::read(args)),
             logger: Logger::new("UiGateway"),
         }
     }
@@ -44,6 +193,338 @@ impl UiGateway {
             bind: addr.clone().recipient::<BindMessage>(),
             ui_message_sub: addr.clone().recipient::<UiCarrierMessage>(),
             from_ui_message_sub: addr.clone().recipient::<FromUiMessage>(),
+            connection_state_sub: addr.clone().recipient::<ConnectionStateMessage>(),
+            event_sub: addr.clone().recipient::<UiEventMessage>(),
+        }
+    }
+
+    fn as_strs(strings: &[String]) -> Vec<&str> {
+        strings.iter().map(|s| s.as_str()).collect()
+    }
+
+    fn respond(&self, client_id: u64, correlation_id: Option<u64>, data: UiMessage) {
+        self.ui_message_sub
+            .as_ref()
+            .expect("UiGateway is unbound")
+            .try_send(UiCarrierMessage {
+                client_id,
+                correlation_id,
+                data,
+            })
+            .expect("UiGateway is dead")
+    }
+
+    fn node_status(&self) -> NodeStatus {
+        NodeStatus {
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            decentralized: self.node_configuration.is_decentralized,
+            connection_state: self.connection_state.clone(),
+            neighbor_count: self.neighbor_count,
+        }
+    }
+
+    fn subscribe(
+        &mut self,
+        client_id: u64,
+        topics: Vec<EventTopic>,
+        log_level: Option<String>,
+    ) -> UiMessage {
+        let log_level = match log_level {
+            None => None,
+            Some(level_str) => match Level::from_str(&level_str) {
+                Ok(level) => Some(level),
+                Err(_) => return UiMessage::Error(format!("Bad log level: {}", level_str)),
+            },
+        };
+        let subscriber = self
+            .subscribers
+            .entry(client_id)
+            .or_insert_with(Subscriber::new);
+        subscriber.topics.extend(topics);
+        if let Some(level) = log_level {
+            subscriber.log_level = level;
+        }
+        let response = UiMessage::Subscriptions(subscriber.sorted_topics());
+        self.update_log_forwarding();
+        response
+    }
+
+    fn unsubscribe(&mut self, client_id: u64, topics: Vec<EventTopic>) -> UiMessage {
+        let remaining = match self.subscribers.get_mut(&client_id) {
+            None => vec![],
+            Some(subscriber) => {
+                topics.iter().for_each(|topic| {
+                    subscriber.topics.remove(topic);
+                });
+                subscriber.sorted_topics()
+            }
+        };
+        if remaining.is_empty() {
+            self.subscribers.remove(&client_id);
+        }
+        self.update_log_forwarding();
+        UiMessage::Subscriptions(remaining)
+    }
+
+    // Log lines are only generated as events while somebody wants them.
+    fn update_log_forwarding(&self) {
+        let most_verbose = self
+            .subscribers
+            .values()
+            .filter(|subscriber| subscriber.topics.contains(&EventTopic::Logs))
+            .map(|subscriber| subscriber.log_level)
+            .max();
+        let forwarding = match (most_verbose, self.event_sub.as_ref()) {
+            (Some(level), Some(event_sub)) => Some((level, event_sub.clone())),
+            _ => None,
+        };
+        (self.log_forwarder)(forwarding);
+    }
+
+    fn log_levels(&self) -> LogLevels {
+        LogLevels {
+            global: self.log_filter.global.to_string().to_lowercase(),
+            components: self
+                .log_filter
+                .components
+                .iter()
+                .map(|(component, level)| (component.clone(), level.to_string().to_lowercase()))
+                .collect(),
+        }
+    }
+
+    fn set_log_level(&mut self, component: Option<String>, level: Option<String>) -> UiMessage {
+        let level = match level {
+            None => None,
+            Some(level_str) => match logger::parse_level_filter(&level_str) {
+                Ok(level) => Some(level),
+                Err(e) => return UiMessage::Error(e),
+            },
+        };
+        let mut filter = self.log_filter.clone();
+        match (component, level) {
+            (None, Some(level)) => filter.global = level,
+            (Some(component), Some(level)) => {
+                filter.components.insert(component, level);
+            }
+            (Some(component), None) => {
+                filter.components.remove(&component);
+            }
+            (None, None) => {
+                return UiMessage::Error(
+                    "SetLogLevel needs a component, a level, or both".to_string(),
+                )
+            }
+        }
+        self.apply_log_filter(filter);
+        UiMessage::LogLevels(self.log_levels())
+    }
+
+    fn apply_log_filter(&mut self, filter: LogFilter) {
+        self.logger.info(format!(
+            "Log filter changes from {} to {}",
+            self.log_filter, filter
+        ));
+        (self.log_filter_setter)(filter.clone());
+        self.log_filter = filter;
+    }
+
+    // A missing file means the operator is done with it, so the filter goes back to --log_level.
+    fn reload_log_filter(&mut self) {
+        let path = PathBuf::from(&self.node_configuration.data_directory).join(LOG_FILTER_FILE);
+        match fs::read_to_string(&path) {
+            Ok(spec) => match LogFilter::from_str(&spec) {
+                Ok(filter) => self.apply_log_filter(filter),
+                Err(e) => self
+                    .logger
+                    .warning(format!("Ignoring log filter in {:?}: {}", path, e)),
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                let filter = self.startup_log_filter.clone();
+                self.apply_log_filter(filter)
+            }
+            Err(e) => self
+                .logger
+                .warning(format!("Couldn't read log filter from {:?}: {}", path, e)),
+        }
+    }
+
+    fn set_rate_pack(&mut self, rate_pack: RatePack) {
+        self.logger
+            .info(format!("Rate pack changes to {:?}", rate_pack));
+        self.set_rate_pack_subs.iter().for_each(|sub| {
+            sub.try_send(SetRatePackMessage {
+                rate_pack: rate_pack.clone(),
+            })
+            .expect("Rate pack recipient is dead")
+        });
+        self.node_configuration.rate_pack = rate_pack;
+    }
+
+    // Answers with the names of the parameters that changed
+    fn reload_configuration(&mut self) -> Result<Vec<String>, String> {
+        let new_configuration = (self.configuration_reader)(&self.reload_args)?;
+        let changes = self
+            .reloadable_configuration
+            .changes_to(&new_configuration)?;
+        changes
+            .iter()
+            .for_each(|change| self.apply_configuration_change(change.clone()));
+        self.reloadable_configuration = new_configuration;
+        let names = changes
+            .iter()
+            .map(|change| change.name().to_string())
+            .collect::<Vec<String>>();
+        if names.is_empty() {
+            self.logger
+                .info("Configuration reloaded; nothing changed".to_string());
+        } else {
+            self.logger.info(format!(
+                "Configuration reloaded; changed {}",
+                names.join(", ")
+            ));
+        }
+        Ok(names)
+    }
+
+    fn apply_configuration_change(&mut self, change: ConfigurationChange) {
+        match change {
+            ConfigurationChange::DnsServers(dns_servers) => {
+                self.node_configuration.dns_servers = dns_servers
+                    .iter()
+                    .map(|server| server.to_string())
+                    .collect();
+                self.set_dns_servers_sub
+                    .as_ref()
+                    .expect("ProxyClient is unbound")
+                    .try_send(SetDnsServersMessage { dns_servers })
+                    .expect("ProxyClient is dead")
+            }
+            // Per-component overrides, from the UI or the log filter file, stay as they are
+            ConfigurationChange::LogLevel(level) => {
+                self.node_configuration.log_level = level.to_string();
+                self.startup_log_filter.global = level;
+                let mut filter = self.log_filter.clone();
+                filter.global = level;
+                self.apply_log_filter(filter)
+            }
+            ConfigurationChange::Neighbors(neighbors) => {
+                self.node_configuration.neighbors = neighbors
+                    .iter()
+                    .map(|descriptor| descriptor.to_string())
+                    .collect();
+                self.set_initial_neighbors_sub
+                    .as_ref()
+                    .expect("Neighborhood is unbound")
+                    .try_send(SetInitialNeighborsMessage { neighbors })
+                    .expect("Neighborhood is dead")
+            }
+            ConfigurationChange::RatePack(rate_pack) => self.set_rate_pack(rate_pack),
+            ConfigurationChange::PayableScanInterval(payable_scan_interval) => self
+                .set_payable_scan_interval_sub
+                .as_ref()
+                .expect("Accountant is unbound")
+                .try_send(SetPayableScanIntervalMessage {
+                    payable_scan_interval,
+                })
+                .expect("Accountant is dead"),
+        }
+    }
+
+    fn publish(&mut self, event: UiEvent) {
+        let topic = match event.topic() {
+            Some(topic) => topic,
+            None => {
+                let marshalled = self
+                    .converter
+                    .marshal(UiMessage::Event(event), None)
+                    .expect("Internal error: failed to marshal UiEvent");
+                self.websocket_supervisor
+                    .as_ref()
+                    .expect("WebsocketSupervisor is unbound")
+                    .send(BROADCAST, &marshalled);
+                return;
+            }
+        };
+        let mut marshalled_opt: Option<String> = None;
+        for subscriber in self.subscribers.values_mut() {
+            if !subscriber.wants(&event, topic) {
+                continue;
+            }
+            if marshalled_opt.is_none() {
+                marshalled_opt = Some(
+                    self.converter
+                        .marshal(UiMessage::Event(event.clone()), None)
+                        .expect("Internal error: failed to marshal UiEvent"),
+                );
+            }
+            if subscriber.queue.len() >= EVENT_QUEUE_CAPACITY {
+                subscriber.queue.pop_front();
+                subscriber.dropped += 1;
+            }
+            subscriber.queue.push_back(
+                marshalled_opt
+                    .clone()
+                    .expect("Marshalled event disappeared"),
+            );
+        }
+    }
+
+    fn flush_events(&mut self) {
+        let mut departed = vec![];
+        let mut client_ids = self.subscribers.keys().cloned().collect::<Vec<u64>>();
+        client_ids.sort();
+        for client_id in client_ids {
+            let (outgoing, overflows) = {
+                let subscriber = self
+                    .subscribers
+                    .get_mut(&client_id)
+                    .expect("Subscriber disappeared");
+                let mut outgoing = vec![];
+                if subscriber.dropped > 0 {
+                    outgoing.push(
+                        self.converter
+                            .marshal(
+                                UiMessage::Event(UiEvent::EventsDropped {
+                                    count: subscriber.dropped,
+                                }),
+                                None,
+                            )
+                            .expect("Internal error: failed to marshal UiEvent"),
+                    );
+                    subscriber.dropped = 0;
+                    subscriber.consecutive_overflows += 1;
+                } else {
+                    subscriber.consecutive_overflows = 0;
+                }
+                let count = subscriber.queue.len().min(EVENTS_PER_FLUSH);
+                outgoing.extend(subscriber.queue.drain(..count));
+                (outgoing, subscriber.consecutive_overflows)
+            };
+            if overflows >= MAX_CONSECUTIVE_OVERFLOWS {
+                self.logger.warning(format!(
+                    "UI client {} can't keep up with its events; unsubscribing it",
+                    client_id
+                ));
+                departed.push(client_id);
+                continue;
+            }
+            let supervisor = self
+                .websocket_supervisor
+                .as_ref()
+                .expect("WebsocketSupervisor is unbound");
+            if !outgoing
+                .iter()
+                .all(|json| supervisor.send_if_connected(client_id, json))
+            {
+                departed.push(client_id);
+            }
+        }
+        if !departed.is_empty() {
+            departed.iter().for_each(|client_id| {
+                self.subscribers.remove(client_id);
+            });
+            self.update_log_forwarding();
         }
     }
 }
@@ -55,70 +536,295 @@ impl Actor for UiGateway {
 impl Handler<BindMessage> for UiGateway {
     type Result = ();
 
-    fn handle(&mut self, msg: BindMessage, _ctx: &mut Self::Context) -> Self::Result {
-        //        ctx.set_mailbox_capacity(?);
+    fn handle(&mut self, msg: BindMessage, ctx: &mut Self::Context) -> Self::Result {
+        ctx.set_mailbox_capacity(NODE_MAILBOX_CAPACITY);
         self.ui_message_sub = Some(msg.peer_actors.ui_gateway.ui_message_sub.clone());
+        self.event_sub = Some(msg.peer_actors.ui_gateway.event_sub.clone());
+        self.set_route_constraints_sub =
+            Some(msg.peer_actors.proxy_server.set_route_constraints.clone());
+        self.neighborhood_graph_sub = Some(msg.peer_actors.neighborhood.graph_request.clone());
+        self.set_rate_pack_subs = vec![
+            msg.peer_actors.neighborhood.set_rate_pack.clone(),
+            msg.peer_actors.hopper.set_rate_pack.clone(),
+            msg.peer_actors.proxy_client.set_rate_pack.clone(),
+        ];
+        self.set_dns_servers_sub = Some(msg.peer_actors.proxy_client.set_dns_servers.clone());
+        self.set_initial_neighbors_sub =
+            Some(msg.peer_actors.neighborhood.set_initial_neighbors.clone());
+        self.set_payable_scan_interval_sub =
+            Some(msg.peer_actors.accountant.set_payable_scan_interval.clone());
+        self.active_streams_sub = Some(msg.peer_actors.proxy_server.active_streams_request.clone());
         self.websocket_supervisor = Some(Box::new(WebSocketSupervisorReal::new(
             self.port,
             msg.peer_actors.ui_gateway.from_ui_message_sub.clone(),
+            self.ui_tokens.clone(),
         )));
+        ctx.run_interval(EVENT_FLUSH_INTERVAL, |act, _ctx| act.flush_events());
+        listen_for_signals(ctx);
         ()
     }
 }
 
+#[cfg(unix)]
+fn listen_for_signals(ctx: &mut Context<UiGateway>) {
+    ctx.add_stream(
+        Signal::new(SIGUSR1)
+            .flatten_stream()
+            .map(|_| ReloadLogFilter),
+    );
+    ctx.add_stream(
+        Signal::new(SIGHUP)
+            .flatten_stream()
+            .map(|_| ReloadConfiguration),
+    );
+}
+
+#[cfg(not(unix))]
+fn listen_for_signals(_ctx: &mut Context<UiGateway>) {}
+
+impl StreamHandler<ReloadLogFilter, io::Error> for UiGateway {
+    fn handle(&mut self, _msg: ReloadLogFilter, _ctx: &mut Self::Context) {
+        self.reload_log_filter();
+    }
+
+    // Without the signal, the log filter can still be changed through the UI.
+    fn error(&mut self, err: io::Error, _ctx: &mut Self::Context) -> Running {
+        self.logger
+            .warning(format!("Can't listen for SIGUSR1: {}", err));
+        Running::Continue
+    }
+
+    fn finished(&mut self, _ctx: &mut Self::Context) {}
+}
+
+impl StreamHandler<ReloadConfiguration, io::Error> for UiGateway {
+    fn handle(&mut self, _msg: ReloadConfiguration, _ctx: &mut Self::Context) {
+        if let Err(e) = self.reload_configuration() {
+            self.logger
+                .warning(format!("Configuration not reloaded: {}", e));
+        }
+    }
+
+    // Without the signal, the configuration can still be reloaded through the UI.
+    fn error(&mut self, err: io::Error, _ctx: &mut Self::Context) -> Running {
+        self.logger
+            .warning(format!("Can't listen for SIGHUP: {}", err));
+        Running::Continue
+    }
+
+    fn finished(&mut self, _ctx: &mut Self::Context) {}
+}
+
 impl Handler<UiCarrierMessage> for UiGateway {
     type Result = ();
 
     // All UI messages, both inbound and outbound, come through here
     fn handle(&mut self, msg: UiCarrierMessage, _ctx: &mut Self::Context) -> Self::Result {
+        let (client_id, correlation_id) = (msg.client_id, msg.correlation_id);
         match msg.data {
             UiMessage::ShutdownMessage => {
                 self.logger.info(String::from("Received shutdown order"));
+                self.publish(UiEvent::ShuttingDown);
                 self.shutdown_supervisor.shutdown();
             }
-            UiMessage::GetNodeDescriptor => self
-                .ui_message_sub
+            UiMessage::GetNodeDescriptor => self.respond(
+                client_id,
+                correlation_id,
+                UiMessage::NodeDescriptor(self.node_descriptor.clone()),
+            ),
+            UiMessage::GetConnectionState => self.respond(
+                client_id,
+                correlation_id,
+                UiMessage::ConnectionState(self.connection_state.clone()),
+            ),
+            UiMessage::GetNodeStatus => self.respond(
+                client_id,
+                correlation_id,
+                UiMessage::NodeStatus(self.node_status()),
+            ),
+            UiMessage::GetConfiguration => self.respond(
+                client_id,
+                correlation_id,
+                UiMessage::Configuration(self.node_configuration.clone()),
+            ),
+            UiMessage::GetWalletAddresses => self.respond(
+                client_id,
+                correlation_id,
+                UiMessage::WalletAddresses(self.node_configuration.wallet_addresses.clone()),
+            ),
+            UiMessage::GetNeighborhoodGraph => self
+                .neighborhood_graph_sub
                 .as_ref()
-                .expect("UiGateway is unbound")
-                .try_send(UiCarrierMessage {
-                    client_id: msg.client_id,
-                    data: UiMessage::NodeDescriptor(self.node_descriptor.clone()),
+                .expect("Neighborhood is unbound")
+                .try_send(NeighborhoodGraphRequest {
+                    client_id,
+                    correlation_id,
+                })
+                .expect("Neighborhood is dead"),
+            UiMessage::Subscribe { topics, log_level } => {
+                let response = self.subscribe(client_id, topics, log_level);
+                self.respond(client_id, correlation_id, response)
+            }
+            UiMessage::Unsubscribe { topics } => {
+                let response = self.unsubscribe(client_id, topics);
+                self.respond(client_id, correlation_id, response)
+            }
+            UiMessage::GetLogLevels => self.respond(
+                client_id,
+                correlation_id,
+                UiMessage::LogLevels(self.log_levels()),
+            ),
+            UiMessage::SetLogLevel { component, level } => {
+                let response = self.set_log_level(component, level);
+                self.respond(client_id, correlation_id, response)
+            }
+            UiMessage::ReloadConfiguration => {
+                let response = match self.reload_configuration() {
+                    Ok(names) => UiMessage::ConfigurationReloaded(names),
+                    Err(e) => {
+                        self.logger
+                            .warning(format!("Configuration not reloaded: {}", e));
+                        UiMessage::Error(format!("Configuration not reloaded: {}", e))
+                    }
+                };
+                self.respond(client_id, correlation_id, response)
+            }
+            UiMessage::GetActiveStreams => self
+                .active_streams_sub
+                .as_ref()
+                .expect("ProxyServer is unbound")
+                .try_send(ActiveStreamsRequest {
+                    client_id,
+                    correlation_id,
                 })
-                .expect("UiGateway is dead"),
-            UiMessage::NodeDescriptor(_) => {
+                .expect("ProxyServer is dead"),
+            UiMessage::NodeDescriptor(_)
+            | UiMessage::ConnectionState(_)
+            | UiMessage::NeighborhoodGraph(_)
+            | UiMessage::NodeStatus(_)
+            | UiMessage::ActiveStreams(_)
+            | UiMessage::Configuration(_)
+            | UiMessage::WalletAddresses(_)
+            | UiMessage::Subscriptions(_)
+            | UiMessage::LogLevels(_)
+            | UiMessage::ConfigurationReloaded(_)
+            | UiMessage::Event(_)
+            | UiMessage::Authenticated(_)
+            | UiMessage::Error(_) => {
                 let marshalled = self
                     .converter
-                    .marshal(msg.data)
+                    .marshal(msg.data, correlation_id)
                     .expect("Internal error: failed to marshal UiMessage");
                 self.websocket_supervisor
                     .as_ref()
                     .expect("WebsocketSupervisor is unbound")
-                    .send(msg.client_id, &marshalled);
+                    .send(client_id, &marshalled);
+            }
+            // Answered by the FromUiMessage handler, which knows what the token was good for
+            UiMessage::Authenticate { .. } => (),
+            UiMessage::SetRouteConstraints {
+                exit_keys,
+                excluded_keys,
+                excluded_ip_ranges,
+            } => {
+                match RouteConstraints::from_strs(
+                    &UiGateway::as_strs(&exit_keys),
+                    &UiGateway::as_strs(&excluded_keys),
+                    &UiGateway::as_strs(&excluded_ip_ranges),
+                ) {
+                    Ok(constraints) => self
+                        .set_route_constraints_sub
+                        .as_ref()
+                        .expect("ProxyServer is unbound")
+                        .try_send(SetRouteConstraintsMessage { constraints })
+                        .expect("ProxyServer is dead"),
+                    Err(e) => {
+                        self.logger
+                            .warning(format!("Ignoring route constraints from UI: {}", e));
+                        self.respond(
+                            client_id,
+                            correlation_id,
+                            UiMessage::Error(format!("Bad route constraints: {}", e)),
+                        )
+                    }
+                }
             }
+            UiMessage::SetRatePack(rate_pack) => match rate_pack.check_bounds() {
+                Ok(()) => self.set_rate_pack(rate_pack),
+                Err(e) => {
+                    self.logger
+                        .warning(format!("Ignoring rate pack from UI: {}", e));
+                    self.respond(
+                        client_id,
+                        correlation_id,
+                        UiMessage::Error(format!("Bad rate pack: {}", e)),
+                    )
+                }
+            },
         }
         ()
     }
 }
 
+impl Handler<ConnectionStateMessage> for UiGateway {
+    type Result = ();
+
+    fn handle(&mut self, msg: ConnectionStateMessage, _ctx: &mut Self::Context) -> Self::Result {
+        self.connection_state = msg.state;
+        self.neighbor_count = msg.neighbor_count;
+        self.publish(UiEvent::NeighborhoodChanged {
+            connection_state: self.connection_state.clone(),
+            neighbor_count: self.neighbor_count,
+        });
+    }
+}
+
+impl Handler<UiEventMessage> for UiGateway {
+    type Result = ();
+
+    fn handle(&mut self, msg: UiEventMessage, _ctx: &mut Self::Context) -> Self::Result {
+        self.publish(msg.event);
+    }
+}
+
 impl Handler<FromUiMessage> for UiGateway {
     type Result = ();
 
     // JSON messages from external UIs come in here, are translated to UiMessages, and sent to the handler above
     fn handle(&mut self, msg: FromUiMessage, _ctx: &mut Self::Context) -> Self::Result {
         match self.converter.unmarshal(&msg.json) {
-            Err(e) => self.logger.warning(format!(
-                "Error unmarshalling message from UI - ignoring: '{}'",
-                e
-            )),
-            Ok(ui_message) => self
-                .ui_message_sub
-                .as_ref()
-                .expect("UiGateway is unbound")
-                .try_send(UiCarrierMessage {
-                    client_id: msg.client_id,
-                    data: ui_message,
-                })
-                .expect("UiGateway is dead"),
+            Err(e) => {
+                self.logger.warning(format!(
+                    "Error unmarshalling message from UI - answering with an error: '{}'",
+                    e.message
+                ));
+                self.respond(
+                    msg.client_id,
+                    e.correlation_id,
+                    UiMessage::Error(format!("Unrecognized message: {}", e.message)),
+                )
+            }
+            Ok((UiMessage::Authenticate { .. }, correlation_id)) => self.respond(
+                msg.client_id,
+                correlation_id,
+                UiMessage::Authenticated(msg.role),
+            ),
+            Ok((ref ui_message, correlation_id))
+                if msg.role == UiClientRole::ReadOnly && ui_message.requires_control() =>
+            {
+                self.logger.warning(format!(
+                    "Read-only UI client {} tried to send {:?}; refusing",
+                    msg.client_id, ui_message
+                ));
+                self.respond(
+                    msg.client_id,
+                    correlation_id,
+                    UiMessage::Error("Not authorized: this connection is read-only".to_string()),
+                )
+            }
+            Ok((ui_message, correlation_id)) => {
+                self.respond(msg.client_id, correlation_id, ui_message)
+            }
         };
         ()
     }
@@ -127,33 +833,50 @@ impl Handler<FromUiMessage> for UiGateway {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::sub_lib::neighborhood::NodeDescriptor;
+    use crate::sub_lib::neighborhood::MAX_RATE;
     use crate::sub_lib::ui_gateway::UiMessage;
+    use crate::sub_lib::ui_gateway::WalletAddresses;
     use crate::test_utils::logging::init_test_logging;
     use crate::test_utils::logging::TestLogHandler;
     use crate::test_utils::recorder::make_recorder;
     use crate::test_utils::recorder::peer_actors_builder;
+    use crate::test_utils::test_utils::ensure_node_home_directory_exists;
     use crate::test_utils::test_utils::find_free_port;
+    use crate::test_utils::test_utils::rate_pack;
     use crate::test_utils::test_utils::wait_for;
+    use crate::ui_gateway::ui_traffic_converter::UnmarshalError;
     use actix::System;
+    use log::LevelFilter;
     use std::cell::RefCell;
+    use std::collections::BTreeMap;
+    use std::net::IpAddr;
+    use std::net::SocketAddr;
     use std::sync::Arc;
     use std::sync::Mutex;
     use std::thread;
 
     pub struct UiTrafficConverterMock {
-        marshal_parameters: Arc<Mutex<Vec<UiMessage>>>,
+        marshal_parameters: Arc<Mutex<Vec<(UiMessage, Option<u64>)>>>,
         marshal_results: RefCell<Vec<Result<String, String>>>,
         unmarshal_parameters: Arc<Mutex<Vec<String>>>,
-        unmarshal_results: RefCell<Vec<Result<UiMessage, String>>>,
+        unmarshal_results: RefCell<Vec<Result<(UiMessage, Option<u64>), UnmarshalError>>>,
     }
 
     impl UiTrafficConverter for UiTrafficConverterMock {
-        fn marshal(&self, ui_message: UiMessage) -> Result<String, String> {
-            self.marshal_parameters.lock().unwrap().push(ui_message);
+        fn marshal(
+            &self,
+            ui_message: UiMessage,
+            correlation_id: Option<u64>,
+        ) -> Result<String, String> {
+            self.marshal_parameters
+                .lock()
+                .unwrap()
+                .push((ui_message, correlation_id));
             self.marshal_results.borrow_mut().remove(0)
         }
 
-        fn unmarshal(&self, json: &str) -> Result<UiMessage, String> {
+        fn unmarshal(&self, json: &str) -> Result<(UiMessage, Option<u64>), UnmarshalError> {
             self.unmarshal_parameters
                 .lock()
                 .unwrap()
@@ -175,7 +898,7 @@ mod tests {
         #[allow(dead_code)]
         fn marshal_parameters(
             mut self,
-            parameters: &Arc<Mutex<Vec<UiMessage>>>,
+            parameters: &Arc<Mutex<Vec<(UiMessage, Option<u64>)>>>,
         ) -> UiTrafficConverterMock {
             self.marshal_parameters = parameters.clone();
             self
@@ -195,7 +918,10 @@ mod tests {
             self
         }
 
-        fn unmarshal_result(self, result: Result<UiMessage, String>) -> UiTrafficConverterMock {
+        fn unmarshal_result(
+            self,
+            result: Result<(UiMessage, Option<u64>), UnmarshalError>,
+        ) -> UiTrafficConverterMock {
             self.unmarshal_results.borrow_mut().push(result);
             self
         }
@@ -204,6 +930,7 @@ mod tests {
     #[derive(Default)]
     struct WebSocketSupervisorMock {
         send_parameters: Arc<Mutex<Vec<(u64, String)>>>,
+        send_if_connected_results: RefCell<Vec<bool>>,
     }
 
     impl WebSocketSupervisor for WebSocketSupervisorMock {
@@ -213,15 +940,31 @@ mod tests {
                 .unwrap()
                 .push((client_id, String::from(message_json)));
         }
+
+        fn send_if_connected(&self, client_id: u64, message_json: &str) -> bool {
+            self.send(client_id, message_json);
+            let mut results = self.send_if_connected_results.borrow_mut();
+            if results.is_empty() {
+                true
+            } else {
+                results.remove(0)
+            }
+        }
     }
 
     impl WebSocketSupervisorMock {
         fn new() -> WebSocketSupervisorMock {
             WebSocketSupervisorMock {
                 send_parameters: Arc::new(Mutex::new(vec![])),
+                send_if_connected_results: RefCell::new(vec![]),
             }
         }
 
+        fn send_if_connected_result(self, result: bool) -> WebSocketSupervisorMock {
+            self.send_if_connected_results.borrow_mut().push(result);
+            self
+        }
+
         fn send_parameters(
             mut self,
             parameters: &Arc<Mutex<Vec<(u64, String)>>>,
@@ -257,6 +1000,235 @@ mod tests {
         }
     }
 
+    fn make_event_subject(
+        websocket_supervisor: WebSocketSupervisorMock,
+        log_forwarding_arc: &Arc<Mutex<Vec<Option<Level>>>>,
+    ) -> UiGateway {
+        let mut subject = UiGateway::new(&UiGatewayConfig {
+            ui_port: find_free_port(),
+            node_descriptor: String::from(""),
+            node_configuration: NodeConfiguration::default(),
+            ui_tokens: UiTokens::default(),
+            reload_args: vec![],
+            reloadable_configuration: ReloadableConfiguration::default(),
+        });
+        subject.websocket_supervisor = Some(Box::new(websocket_supervisor));
+        let log_forwarding_arc = log_forwarding_arc.clone();
+        subject.log_forwarder = Box::new(move |forwarding| {
+            log_forwarding_arc
+                .lock()
+                .unwrap()
+                .push(forwarding.map(|(level, _)| level))
+        });
+        subject
+    }
+
+    fn event_json(event: UiEvent) -> String {
+        serde_json::to_string(&UiMessage::Event(event)).unwrap()
+    }
+
+    fn log_line(level: Level, message: &str) -> UiEvent {
+        UiEvent::LogLine {
+            level: level.to_string(),
+            component: "Test".to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn subscribe_and_unsubscribe_are_answered_with_the_remaining_subscriptions() {
+        let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        let log_forwarding_arc = Arc::new(Mutex::new(vec![]));
+        let log_forwarding_inner = log_forwarding_arc.clone();
+
+        thread::spawn(move || {
+            let system = System::new(
+                "subscribe_and_unsubscribe_are_answered_with_the_remaining_subscriptions",
+            );
+            let mut subject =
+                make_event_subject(WebSocketSupervisorMock::new(), &log_forwarding_inner);
+            let ui_gateway_addr = ui_gateway.start();
+            subject.ui_message_sub = Some(ui_gateway_addr.clone().recipient::<UiCarrierMessage>());
+            subject.event_sub = Some(ui_gateway_addr.recipient::<UiEventMessage>());
+            let addr: Addr<UiGateway> = subject.start();
+            vec![
+                UiMessage::Subscribe {
+                    topics: vec![EventTopic::Streams, EventTopic::Logs],
+                    log_level: Some("debug".to_string()),
+                },
+                UiMessage::Subscribe {
+                    topics: vec![EventTopic::Neighborhood],
+                    log_level: Some("chatty".to_string()),
+                },
+                UiMessage::Unsubscribe {
+                    topics: vec![EventTopic::Logs],
+                },
+                UiMessage::Unsubscribe {
+                    topics: vec![EventTopic::Streams],
+                },
+            ]
+            .into_iter()
+            .enumerate()
+            .for_each(|(index, data)| {
+                addr.try_send(UiCarrierMessage {
+                    client_id: 5,
+                    correlation_id: Some(index as u64),
+                    data,
+                })
+                .unwrap()
+            });
+
+            system.run();
+        });
+        ui_gateway_awaiter.await_message_count(4);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        let replies = (0..4)
+            .map(|index| ui_gateway_recording.get_record::<UiCarrierMessage>(index))
+            .collect::<Vec<&UiCarrierMessage>>();
+        assert_eq!(
+            replies,
+            vec![
+                &UiCarrierMessage {
+                    client_id: 5,
+                    correlation_id: Some(0),
+                    data: UiMessage::Subscriptions(vec![EventTopic::Streams, EventTopic::Logs])
+                },
+                &UiCarrierMessage {
+                    client_id: 5,
+                    correlation_id: Some(1),
+                    data: UiMessage::Error("Bad log level: chatty".to_string())
+                },
+                &UiCarrierMessage {
+                    client_id: 5,
+                    correlation_id: Some(2),
+                    data: UiMessage::Subscriptions(vec![EventTopic::Streams])
+                },
+                &UiCarrierMessage {
+                    client_id: 5,
+                    correlation_id: Some(3),
+                    data: UiMessage::Subscriptions(vec![])
+                },
+            ]
+        );
+        assert_eq!(
+            *log_forwarding_arc.lock().unwrap(),
+            vec![Some(Level::Debug), None, None]
+        );
+    }
+
+    #[test]
+    fn events_go_only_to_clients_subscribed_to_their_topics_at_their_log_levels() {
+        let send_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let log_forwarding_arc = Arc::new(Mutex::new(vec![]));
+        let mut subject = make_event_subject(
+            WebSocketSupervisorMock::new().send_parameters(&send_parameters_arc),
+            &log_forwarding_arc,
+        );
+        subject.subscribe(1, vec![EventTopic::Streams], None);
+        subject.subscribe(
+            2,
+            vec![EventTopic::Streams, EventTopic::Logs],
+            Some("warn".to_string()),
+        );
+        let opened = UiEvent::StreamOpened {
+            stream_key: "key".to_string(),
+            client_addr: "1.2.3.4:5678".to_string(),
+        };
+        let failed = UiEvent::RouteFailure {
+            target: "booga.com".to_string(),
+            reason: "No route could be found".to_string(),
+        };
+
+        subject.publish(opened.clone());
+        subject.publish(failed);
+        subject.publish(log_line(Level::Error, "severe"));
+        subject.publish(log_line(Level::Info, "chatty"));
+        subject.flush_events();
+
+        assert_eq!(
+            *send_parameters_arc.lock().unwrap(),
+            vec![
+                (1, event_json(opened.clone())),
+                (2, event_json(opened)),
+                (2, event_json(log_line(Level::Error, "severe"))),
+            ]
+        );
+    }
+
+    #[test]
+    fn slow_subscriber_is_told_of_dropped_events_and_eventually_unsubscribed() {
+        init_test_logging();
+        let send_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let log_forwarding_arc = Arc::new(Mutex::new(vec![]));
+        let mut subject = make_event_subject(
+            WebSocketSupervisorMock::new().send_parameters(&send_parameters_arc),
+            &log_forwarding_arc,
+        );
+        subject.subscribe(1, vec![EventTopic::Logs], Some("info".to_string()));
+        (0..(EVENT_QUEUE_CAPACITY + 3))
+            .for_each(|index| subject.publish(log_line(Level::Info, &format!("line {}", index))));
+
+        subject.flush_events();
+
+        {
+            let send_parameters = send_parameters_arc.lock().unwrap();
+            assert_eq!(send_parameters.len(), EVENTS_PER_FLUSH + 1);
+            assert_eq!(
+                send_parameters[0],
+                (1, event_json(UiEvent::EventsDropped { count: 3 }))
+            );
+            assert_eq!(
+                send_parameters[1],
+                (1, event_json(log_line(Level::Info, "line 3")))
+            );
+        }
+        (1..MAX_CONSECUTIVE_OVERFLOWS).for_each(|_| {
+            subject.publish(log_line(Level::Info, "more"));
+            subject.subscribers.get_mut(&1).unwrap().dropped = 1;
+            subject.flush_events();
+        });
+        assert_eq!(subject.subscribers.contains_key(&1), false);
+        assert_eq!(log_forwarding_arc.lock().unwrap().last(), Some(&None));
+        TestLogHandler::new().exists_log_containing(
+            "WARN: UiGateway: UI client 1 can't keep up with its events; unsubscribing it",
+        );
+    }
+
+    #[test]
+    fn subscriber_whose_connection_fails_is_unsubscribed() {
+        let log_forwarding_arc = Arc::new(Mutex::new(vec![]));
+        let mut subject = make_event_subject(
+            WebSocketSupervisorMock::new().send_if_connected_result(false),
+            &log_forwarding_arc,
+        );
+        subject.subscribe(1, vec![EventTopic::Neighborhood], None);
+        subject.publish(UiEvent::NeighborhoodChanged {
+            connection_state: ConnectionState::Connected,
+            neighbor_count: 3,
+        });
+
+        subject.flush_events();
+
+        assert_eq!(subject.subscribers.is_empty(), true);
+    }
+
+    #[test]
+    fn events_without_topics_are_broadcast_immediately() {
+        let send_parameters_arc = Arc::new(Mutex::new(vec![]));
+        let log_forwarding_arc = Arc::new(Mutex::new(vec![]));
+        let mut subject = make_event_subject(
+            WebSocketSupervisorMock::new().send_parameters(&send_parameters_arc),
+            &log_forwarding_arc,
+        );
+
+        subject.publish(UiEvent::ShuttingDown);
+
+        assert_eq!(
+            *send_parameters_arc.lock().unwrap(),
+            vec![(BROADCAST, event_json(UiEvent::ShuttingDown))]
+        );
+    }
+
     #[test]
     fn receiving_a_shutdown_message_triggers_the_shutdown_supervisor() {
         let shutdown_parameters = Arc::new(Mutex::new(vec![]));
@@ -268,6 +1240,10 @@ mod tests {
             let mut subject = UiGateway::new(&UiGatewayConfig {
                 ui_port: find_free_port(),
                 node_descriptor: String::from(""),
+                node_configuration: NodeConfiguration::default(),
+                ui_tokens: UiTokens::default(),
+                reload_args: vec![],
+                reloadable_configuration: ReloadableConfiguration::default(),
             });
             subject.shutdown_supervisor = Box::new(supervisor);
             let system =
@@ -279,6 +1255,7 @@ mod tests {
 
             addr.try_send(UiCarrierMessage {
                 client_id: 0,
+                correlation_id: None,
                 data: UiMessage::ShutdownMessage,
             })
             .unwrap();
@@ -299,6 +1276,10 @@ mod tests {
             let mut subject = UiGateway::new(&UiGatewayConfig {
                 ui_port: find_free_port(),
                 node_descriptor: String::from("NODE-DESCRIPTOR"),
+                node_configuration: NodeConfiguration::default(),
+                ui_tokens: UiTokens::default(),
+                reload_args: vec![],
+                reloadable_configuration: ReloadableConfiguration::default(),
             });
             let ui_gateway_recorder_addr = ui_gateway_recorder.start();
             subject.ui_message_sub = Some(ui_gateway_recorder_addr.recipient::<UiCarrierMessage>());
@@ -310,6 +1291,7 @@ mod tests {
                 .from_ui_message_sub
                 .try_send(FromUiMessage {
                     client_id: 1234,
+                    role: UiClientRole::Control,
                     json: request,
                 })
                 .unwrap();
@@ -318,6 +1300,7 @@ mod tests {
                 .ui_message_sub
                 .try_send(UiCarrierMessage {
                     client_id: 1234,
+                    correlation_id: None,
                     data: UiMessage::GetNodeDescriptor,
                 })
                 .unwrap();
@@ -333,41 +1316,509 @@ mod tests {
             ui_gateway_recording.get_record::<UiCarrierMessage>(1),
             &UiCarrierMessage {
                 client_id: 1234,
+                correlation_id: None,
                 data: UiMessage::NodeDescriptor("NODE-DESCRIPTOR".to_string())
             }
         );
     }
 
     #[test]
-    fn node_descriptor_message_is_directed_to_websocket_supervisor() {
-        let (ui_gateway_recorder, _, _) = make_recorder();
-        let receive_parameters_arc = Arc::new(Mutex::new(vec![]));
-
-        let system = System::new("node_descriptor_message_is_directed_to_websocket_supervisor");
-        let mut subject = UiGateway::new(&UiGatewayConfig {
-            ui_port: find_free_port(),
-            node_descriptor: String::from(""),
-        });
-        subject.websocket_supervisor = Some(Box::new(
-            WebSocketSupervisorMock::new().send_parameters(&receive_parameters_arc),
-        ));
-        let ui_gateway_recorder_addr = ui_gateway_recorder.start();
-        subject.ui_message_sub = Some(ui_gateway_recorder_addr.recipient::<UiCarrierMessage>());
-        let subject_addr = subject.start();
-        let subject_subs = UiGateway::make_subs_from(&subject_addr);
-
-        subject_subs
-            .ui_message_sub
-            .try_send(UiCarrierMessage {
-                client_id: 1234,
-                data: UiMessage::NodeDescriptor("NODE-DESCRIPTOR".to_string()),
-            })
-            .unwrap();
+    fn get_connection_state_message_is_answered_with_the_latest_reported_connection_state() {
+        let (ui_gateway_recorder, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
 
-        System::current().stop();
-        system.run();
+        thread::spawn(move || {
+            let system = System::new(
+                "get_connection_state_message_is_answered_with_the_latest_reported_connection_state",
+            );
+            let mut subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from(""),
+                node_configuration: NodeConfiguration::default(),
+                ui_tokens: UiTokens::default(),
+                reload_args: vec![],
+                reloadable_configuration: ReloadableConfiguration::default(),
+            });
+            let ui_gateway_recorder_addr = ui_gateway_recorder.start();
+            subject.ui_message_sub = Some(ui_gateway_recorder_addr.recipient::<UiCarrierMessage>());
+            let subject_addr = subject.start();
+            let subject_subs = UiGateway::make_subs_from(&subject_addr);
 
-        wait_for(None, None, || {
+            subject_subs
+                .ui_message_sub
+                .try_send(UiCarrierMessage {
+                    client_id: 1234,
+                    correlation_id: None,
+                    data: UiMessage::GetConnectionState,
+                })
+                .unwrap();
+            subject_subs
+                .connection_state_sub
+                .try_send(ConnectionStateMessage {
+                    state: ConnectionState::Debuting {
+                        target: "R29vZEtleQ:1.2.3.4:1234".to_string(),
+                        round: 2,
+                    },
+                    neighbor_count: 2,
+                })
+                .unwrap();
+            subject_subs
+                .ui_message_sub
+                .try_send(UiCarrierMessage {
+                    client_id: 1234,
+                    correlation_id: None,
+                    data: UiMessage::GetConnectionState,
+                })
+                .unwrap();
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(2);
+
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 1234,
+                correlation_id: None,
+                data: UiMessage::ConnectionState(ConnectionState::Standalone)
+            }
+        );
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(1),
+            &UiCarrierMessage {
+                client_id: 1234,
+                correlation_id: None,
+                data: UiMessage::ConnectionState(ConnectionState::Debuting {
+                    target: "R29vZEtleQ:1.2.3.4:1234".to_string(),
+                    round: 2,
+                })
+            }
+        );
+    }
+
+    #[test]
+    fn status_configuration_and_wallet_requests_are_answered_with_their_correlation_ids() {
+        let (ui_gateway_recorder, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        let node_configuration = NodeConfiguration {
+            is_decentralized: true,
+            ui_port: 5333,
+            wallet_addresses: WalletAddresses {
+                earning: "0xEARNING".to_string(),
+                consuming: Some("0xCONSUMING".to_string()),
+            },
+            ..NodeConfiguration::default()
+        };
+        let node_configuration_inside = node_configuration.clone();
+
+        thread::spawn(move || {
+            let system = System::new(
+                "status_configuration_and_wallet_requests_are_answered_with_their_correlation_ids",
+            );
+            let mut subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from(""),
+                node_configuration: node_configuration_inside,
+                ui_tokens: UiTokens::default(),
+                reload_args: vec![],
+                reloadable_configuration: ReloadableConfiguration::default(),
+            });
+            let ui_gateway_recorder_addr = ui_gateway_recorder.start();
+            subject.ui_message_sub = Some(ui_gateway_recorder_addr.recipient::<UiCarrierMessage>());
+            let subject_addr = subject.start();
+            let subject_subs = UiGateway::make_subs_from(&subject_addr);
+
+            subject_subs
+                .connection_state_sub
+                .try_send(ConnectionStateMessage {
+                    state: ConnectionState::Connected,
+                    neighbor_count: 3,
+                })
+                .unwrap();
+            vec![
+                UiMessage::GetNodeStatus,
+                UiMessage::GetConfiguration,
+                UiMessage::GetWalletAddresses,
+            ]
+            .into_iter()
+            .enumerate()
+            .for_each(|(idx, data)| {
+                subject_subs
+                    .ui_message_sub
+                    .try_send(UiCarrierMessage {
+                        client_id: 1234,
+                        correlation_id: Some(idx as u64),
+                        data,
+                    })
+                    .unwrap()
+            });
+
+            system.run();
+        });
+
+        ui_gateway_awaiter.await_message_count(3);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        let status = ui_gateway_recording.get_record::<UiCarrierMessage>(0);
+        assert_eq!(status.correlation_id, Some(0));
+        match &status.data {
+            UiMessage::NodeStatus(status) => {
+                assert!(status.uptime_secs < 60);
+                assert_eq!(status.decentralized, true);
+                assert_eq!(status.connection_state, ConnectionState::Connected);
+                assert_eq!(status.neighbor_count, 3);
+            }
+            x => panic!("Expected NodeStatus, got {:?}", x),
+        }
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(1),
+            &UiCarrierMessage {
+                client_id: 1234,
+                correlation_id: Some(1),
+                data: UiMessage::Configuration(node_configuration.clone())
+            }
+        );
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(2),
+            &UiCarrierMessage {
+                client_id: 1234,
+                correlation_id: Some(2),
+                data: UiMessage::WalletAddresses(node_configuration.wallet_addresses)
+            }
+        );
+    }
+
+    #[test]
+    fn get_active_streams_message_is_passed_to_the_proxy_server() {
+        let (proxy_server, proxy_server_awaiter, proxy_server_recording_arc) = make_recorder();
+
+        thread::spawn(move || {
+            let system = System::new("get_active_streams_message_is_passed_to_the_proxy_server");
+            let subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from(""),
+                node_configuration: NodeConfiguration::default(),
+                ui_tokens: UiTokens::default(),
+                reload_args: vec![],
+                reloadable_configuration: ReloadableConfiguration::default(),
+            });
+            let addr: Addr<UiGateway> = subject.start();
+            let mut peer_actors = peer_actors_builder().proxy_server(proxy_server).build();
+            peer_actors.ui_gateway = UiGateway::make_subs_from(&addr);
+            addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            addr.try_send(UiCarrierMessage {
+                client_id: 4321,
+                correlation_id: Some(17),
+                data: UiMessage::GetActiveStreams,
+            })
+            .unwrap();
+
+            system.run();
+        });
+
+        proxy_server_awaiter.await_message_count(1);
+        let recording = proxy_server_recording_arc.lock().unwrap();
+        assert_eq!(
+            recording.get_record::<ActiveStreamsRequest>(0),
+            &ActiveStreamsRequest {
+                client_id: 4321,
+                correlation_id: Some(17),
+            }
+        );
+    }
+
+    #[test]
+    fn set_route_constraints_message_is_parsed_and_sent_to_proxy_server() {
+        let (proxy_server, proxy_server_awaiter, proxy_server_recording_arc) = make_recorder();
+
+        thread::spawn(move || {
+            let system =
+                System::new("set_route_constraints_message_is_parsed_and_sent_to_proxy_server");
+            let subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from(""),
+                node_configuration: NodeConfiguration::default(),
+                ui_tokens: UiTokens::default(),
+                reload_args: vec![],
+                reloadable_configuration: ReloadableConfiguration::default(),
+            });
+            let addr: Addr<UiGateway> = subject.start();
+            let mut peer_actors = peer_actors_builder().proxy_server(proxy_server).build();
+            peer_actors.ui_gateway = UiGateway::make_subs_from(&addr);
+            addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            addr.try_send(UiCarrierMessage {
+                client_id: 0,
+                correlation_id: None,
+                data: UiMessage::SetRouteConstraints {
+                    exit_keys: vec!["QmlsbA".to_string()],
+                    excluded_keys: vec![],
+                    excluded_ip_ranges: vec!["10.0.0.0/8".to_string()],
+                },
+            })
+            .unwrap();
+
+            system.run();
+        });
+
+        proxy_server_awaiter.await_message_count(1);
+        let recording = proxy_server_recording_arc.lock().unwrap();
+        assert_eq!(
+            recording.get_record::<SetRouteConstraintsMessage>(0),
+            &SetRouteConstraintsMessage {
+                constraints: RouteConstraints::from_strs(&["QmlsbA"], &[], &["10.0.0.0/8"])
+                    .unwrap()
+            }
+        );
+    }
+
+    #[test]
+    fn get_neighborhood_graph_message_is_passed_to_the_neighborhood() {
+        let (neighborhood, neighborhood_awaiter, neighborhood_recording_arc) = make_recorder();
+
+        thread::spawn(move || {
+            let system =
+                System::new("get_neighborhood_graph_message_is_passed_to_the_neighborhood");
+            let subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from(""),
+                node_configuration: NodeConfiguration::default(),
+                ui_tokens: UiTokens::default(),
+                reload_args: vec![],
+                reloadable_configuration: ReloadableConfiguration::default(),
+            });
+            let addr: Addr<UiGateway> = subject.start();
+            let mut peer_actors = peer_actors_builder().neighborhood(neighborhood).build();
+            peer_actors.ui_gateway = UiGateway::make_subs_from(&addr);
+            addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            addr.try_send(UiCarrierMessage {
+                client_id: 4321,
+                correlation_id: None,
+                data: UiMessage::GetNeighborhoodGraph,
+            })
+            .unwrap();
+
+            system.run();
+        });
+
+        neighborhood_awaiter.await_message_count(1);
+        let recording = neighborhood_recording_arc.lock().unwrap();
+        assert_eq!(
+            recording.get_record::<NeighborhoodGraphRequest>(0),
+            &NeighborhoodGraphRequest {
+                client_id: 4321,
+                correlation_id: None,
+            }
+        );
+    }
+
+    #[test]
+    fn set_rate_pack_message_goes_to_the_neighborhood_hopper_and_proxy_client() {
+        let (neighborhood, _, neighborhood_recording_arc) = make_recorder();
+        let (hopper, _, hopper_recording_arc) = make_recorder();
+        let (proxy_client, _, proxy_client_recording_arc) = make_recorder();
+        let (ui_gateway, _, ui_gateway_recording_arc) = make_recorder();
+        let system =
+            System::new("set_rate_pack_message_goes_to_the_neighborhood_hopper_and_proxy_client");
+        let subject = UiGateway::new(&UiGatewayConfig {
+            ui_port: find_free_port(),
+            node_descriptor: String::from(""),
+            node_configuration: NodeConfiguration::default(),
+            ui_tokens: UiTokens::default(),
+            reload_args: vec![],
+            reloadable_configuration: ReloadableConfiguration::default(),
+        });
+        let addr: Addr<UiGateway> = subject.start();
+        let peer_actors = peer_actors_builder()
+            .neighborhood(neighborhood)
+            .hopper(hopper)
+            .proxy_client(proxy_client)
+            .ui_gateway(ui_gateway)
+            .build();
+        addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        addr.try_send(UiCarrierMessage {
+            client_id: 0,
+            correlation_id: None,
+            data: UiMessage::SetRatePack(rate_pack(100)),
+        })
+        .unwrap();
+        addr.try_send(UiCarrierMessage {
+            client_id: 0,
+            correlation_id: None,
+            data: UiMessage::GetConfiguration,
+        })
+        .unwrap();
+
+        System::current().stop();
+        system.run();
+        let expected_message = SetRatePackMessage {
+            rate_pack: rate_pack(100),
+        };
+        vec![
+            neighborhood_recording_arc,
+            hopper_recording_arc,
+            proxy_client_recording_arc,
+        ]
+        .into_iter()
+        .for_each(|recording_arc| {
+            let recording = recording_arc.lock().unwrap();
+            assert_eq!(recording.len(), 1);
+            assert_eq!(
+                recording.get_record::<SetRatePackMessage>(0),
+                &expected_message
+            );
+        });
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        match &ui_gateway_recording.get_record::<UiCarrierMessage>(0).data {
+            UiMessage::Configuration(configuration) => {
+                assert_eq!(configuration.rate_pack, rate_pack(100))
+            }
+            x => panic!("Expected Configuration, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn set_rate_pack_message_with_an_excessive_rate_is_answered_with_an_error() {
+        let (neighborhood, _, neighborhood_recording_arc) = make_recorder();
+        let (ui_gateway, _, ui_gateway_recording_arc) = make_recorder();
+        let system =
+            System::new("set_rate_pack_message_with_an_excessive_rate_is_answered_with_an_error");
+        let subject = UiGateway::new(&UiGatewayConfig {
+            ui_port: find_free_port(),
+            node_descriptor: String::from(""),
+            node_configuration: NodeConfiguration::default(),
+            ui_tokens: UiTokens::default(),
+            reload_args: vec![],
+            reloadable_configuration: ReloadableConfiguration::default(),
+        });
+        let addr: Addr<UiGateway> = subject.start();
+        let peer_actors = peer_actors_builder()
+            .neighborhood(neighborhood)
+            .ui_gateway(ui_gateway)
+            .build();
+        addr.try_send(BindMessage { peer_actors }).unwrap();
+        let mut excessive = rate_pack(100);
+        excessive.exit_byte_rate = MAX_RATE + 1;
+
+        addr.try_send(UiCarrierMessage {
+            client_id: 0,
+            correlation_id: Some(5),
+            data: UiMessage::SetRatePack(excessive),
+        })
+        .unwrap();
+
+        System::current().stop();
+        system.run();
+        assert_eq!(neighborhood_recording_arc.lock().unwrap().len(), 0);
+        assert_eq!(
+            ui_gateway_recording_arc
+                .lock()
+                .unwrap()
+                .get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 0,
+                correlation_id: Some(5),
+                data: UiMessage::Error(format!(
+                    "Bad rate pack: exit_byte_rate of {} is more than the maximum of {}",
+                    MAX_RATE + 1,
+                    MAX_RATE
+                ))
+            }
+        );
+    }
+
+    #[test]
+    fn unparseable_set_route_constraints_message_is_logged_and_answered_with_an_error() {
+        init_test_logging();
+        let (proxy_server, _, proxy_server_recording_arc) = make_recorder();
+        let (ui_gateway, _, ui_gateway_recording_arc) = make_recorder();
+        let system = System::new(
+            "unparseable_set_route_constraints_message_is_logged_and_answered_with_an_error",
+        );
+        let subject = UiGateway::new(&UiGatewayConfig {
+            ui_port: find_free_port(),
+            node_descriptor: String::from(""),
+            node_configuration: NodeConfiguration::default(),
+            ui_tokens: UiTokens::default(),
+            reload_args: vec![],
+            reloadable_configuration: ReloadableConfiguration::default(),
+        });
+        let addr: Addr<UiGateway> = subject.start();
+        let peer_actors = peer_actors_builder()
+            .proxy_server(proxy_server)
+            .ui_gateway(ui_gateway)
+            .build();
+        addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        addr.try_send(UiCarrierMessage {
+            client_id: 0,
+            correlation_id: None,
+            data: UiMessage::SetRouteConstraints {
+                exit_keys: vec![],
+                excluded_keys: vec![],
+                excluded_ip_ranges: vec!["booga".to_string()],
+            },
+        })
+        .unwrap();
+
+        System::current().stop();
+        system.run();
+        TestLogHandler::new().exists_log_containing(
+            "WARN: UiGateway: Ignoring route constraints from UI: 'booga' is not an IP address or range",
+        );
+        assert_eq!(proxy_server_recording_arc.lock().unwrap().len(), 0);
+        assert_eq!(
+            ui_gateway_recording_arc
+                .lock()
+                .unwrap()
+                .get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 0,
+                correlation_id: None,
+                data: UiMessage::Error(
+                    "Bad route constraints: 'booga' is not an IP address or range".to_string()
+                )
+            }
+        );
+    }
+
+    #[test]
+    fn node_descriptor_message_is_directed_to_websocket_supervisor() {
+        let (ui_gateway_recorder, _, _) = make_recorder();
+        let receive_parameters_arc = Arc::new(Mutex::new(vec![]));
+
+        let system = System::new("node_descriptor_message_is_directed_to_websocket_supervisor");
+        let mut subject = UiGateway::new(&UiGatewayConfig {
+            ui_port: find_free_port(),
+            node_descriptor: String::from(""),
+            node_configuration: NodeConfiguration::default(),
+            ui_tokens: UiTokens::default(),
+            reload_args: vec![],
+            reloadable_configuration: ReloadableConfiguration::default(),
+        });
+        subject.websocket_supervisor = Some(Box::new(
+            WebSocketSupervisorMock::new().send_parameters(&receive_parameters_arc),
+        ));
+        let ui_gateway_recorder_addr = ui_gateway_recorder.start();
+        subject.ui_message_sub = Some(ui_gateway_recorder_addr.recipient::<UiCarrierMessage>());
+        let subject_addr = subject.start();
+        let subject_subs = UiGateway::make_subs_from(&subject_addr);
+
+        subject_subs
+            .ui_message_sub
+            .try_send(UiCarrierMessage {
+                client_id: 1234,
+                correlation_id: None,
+                data: UiMessage::NodeDescriptor("NODE-DESCRIPTOR".to_string()),
+            })
+            .unwrap();
+
+        System::current().stop();
+        system.run();
+
+        wait_for(None, None, || {
             receive_parameters_arc.lock().unwrap().len() > 0
         });
         assert_eq!(
@@ -385,18 +1836,68 @@ mod tests {
         )
     }
 
+    #[test]
+    fn correlated_message_is_sent_to_websocket_supervisor_in_an_envelope() {
+        let (ui_gateway_recorder, _, _) = make_recorder();
+        let receive_parameters_arc = Arc::new(Mutex::new(vec![]));
+
+        let system =
+            System::new("correlated_message_is_sent_to_websocket_supervisor_in_an_envelope");
+        let mut subject = UiGateway::new(&UiGatewayConfig {
+            ui_port: find_free_port(),
+            node_descriptor: String::from(""),
+            node_configuration: NodeConfiguration::default(),
+            ui_tokens: UiTokens::default(),
+            reload_args: vec![],
+            reloadable_configuration: ReloadableConfiguration::default(),
+        });
+        subject.websocket_supervisor = Some(Box::new(
+            WebSocketSupervisorMock::new().send_parameters(&receive_parameters_arc),
+        ));
+        let ui_gateway_recorder_addr = ui_gateway_recorder.start();
+        subject.ui_message_sub = Some(ui_gateway_recorder_addr.recipient::<UiCarrierMessage>());
+        let subject_addr = subject.start();
+
+        subject_addr
+            .try_send(UiCarrierMessage {
+                client_id: 1234,
+                correlation_id: Some(17),
+                data: UiMessage::Error("booga".to_string()),
+            })
+            .unwrap();
+
+        System::current().stop();
+        system.run();
+
+        wait_for(None, None, || {
+            receive_parameters_arc.lock().unwrap().len() > 0
+        });
+        let receive_parameters = receive_parameters_arc.lock().unwrap();
+        assert_eq!(
+            receive_parameters.get(0).unwrap(),
+            &(
+                1234 as u64,
+                r#"{"version":1,"correlation_id":17,"message":{"Error":"booga"}}"#.to_string()
+            )
+        );
+    }
+
     #[test]
     fn good_from_ui_message_is_unmarshalled_and_resent() {
         let unmarshal_parameters = Arc::new(Mutex::new(vec![]));
         let handler = UiTrafficConverterMock::new()
             .unmarshal_parameters(&unmarshal_parameters)
-            .unmarshal_result(Ok(UiMessage::ShutdownMessage));
+            .unmarshal_result(Ok((UiMessage::ShutdownMessage, Some(17))));
         let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
 
         thread::spawn(move || {
             let mut subject = UiGateway::new(&UiGatewayConfig {
                 ui_port: find_free_port(),
                 node_descriptor: String::from(""),
+                node_configuration: NodeConfiguration::default(),
+                ui_tokens: UiTokens::default(),
+                reload_args: vec![],
+                reloadable_configuration: ReloadableConfiguration::default(),
             });
             subject.converter = Box::new(handler);
             let system = System::new("good_from_ui_message_is_unmarshalled_and_resent");
@@ -406,6 +1907,7 @@ mod tests {
 
             addr.try_send(FromUiMessage {
                 client_id: 42,
+                role: UiClientRole::Control,
                 json: String::from("pretend I'm JSON"),
             })
             .unwrap();
@@ -424,31 +1926,112 @@ mod tests {
             ui_gateway_recording.get_record::<UiCarrierMessage>(0),
             &UiCarrierMessage {
                 client_id: 42,
+                correlation_id: Some(17),
                 data: UiMessage::ShutdownMessage
             }
         );
     }
 
     #[test]
-    fn bad_from_ui_message_is_logged_and_ignored() {
+    fn read_only_clients_are_refused_control_messages_and_told_their_role_on_authentication() {
         init_test_logging();
-        let handler =
-            UiTrafficConverterMock::new().unmarshal_result(Err(String::from("I have a tummyache")));
-        let (ui_gateway, _, ui_gateway_recording_arc) = make_recorder();
+        let handler = UiTrafficConverterMock::new()
+            .unmarshal_result(Ok((
+                UiMessage::Authenticate {
+                    token: "read-only-token".to_string(),
+                },
+                Some(1),
+            )))
+            .unmarshal_result(Ok((UiMessage::ShutdownMessage, Some(2))))
+            .unmarshal_result(Ok((UiMessage::GetNodeStatus, Some(3))));
+        let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+
+        thread::spawn(move || {
+            let mut subject = UiGateway::new(&UiGatewayConfig {
+                ui_port: find_free_port(),
+                node_descriptor: String::from(""),
+                node_configuration: NodeConfiguration::default(),
+                ui_tokens: UiTokens::default(),
+                reload_args: vec![],
+                reloadable_configuration: ReloadableConfiguration::default(),
+            });
+            subject.converter = Box::new(handler);
+            let system = System::new(
+                "read_only_clients_are_refused_control_messages_and_told_their_role_on_authentication",
+            );
+            let addr: Addr<UiGateway> = subject.start();
+            let peer_actors = peer_actors_builder().ui_gateway(ui_gateway).build();
+            addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            (0..3).for_each(|_| {
+                addr.try_send(FromUiMessage {
+                    client_id: 42,
+                    role: UiClientRole::ReadOnly,
+                    json: String::from("pretend I'm JSON"),
+                })
+                .unwrap()
+            });
+
+            system.run();
+        });
+        ui_gateway_awaiter.await_message_count(3);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 42,
+                correlation_id: Some(1),
+                data: UiMessage::Authenticated(UiClientRole::ReadOnly)
+            }
+        );
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(1),
+            &UiCarrierMessage {
+                client_id: 42,
+                correlation_id: Some(2),
+                data: UiMessage::Error("Not authorized: this connection is read-only".to_string())
+            }
+        );
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(2),
+            &UiCarrierMessage {
+                client_id: 42,
+                correlation_id: Some(3),
+                data: UiMessage::GetNodeStatus
+            }
+        );
+        TestLogHandler::new().exists_log_containing(
+            "WARN: UiGateway: Read-only UI client 42 tried to send ShutdownMessage; refusing",
+        );
+    }
+
+    #[test]
+    fn bad_from_ui_message_is_logged_and_answered_with_an_error() {
+        init_test_logging();
+        let handler = UiTrafficConverterMock::new().unmarshal_result(Err(UnmarshalError {
+            correlation_id: Some(17),
+            message: String::from("I have a tummyache"),
+        }));
+        let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
 
         thread::spawn(move || {
             let mut subject = UiGateway::new(&UiGatewayConfig {
                 ui_port: find_free_port(),
                 node_descriptor: String::from(""),
+                node_configuration: NodeConfiguration::default(),
+                ui_tokens: UiTokens::default(),
+                reload_args: vec![],
+                reloadable_configuration: ReloadableConfiguration::default(),
             });
             subject.converter = Box::new(handler);
-            let system = System::new("bad_from_ui_message_is_logged_and_ignored");
+            let system = System::new("bad_from_ui_message_is_logged_and_answered_with_an_error");
             let addr: Addr<UiGateway> = subject.start();
             let peer_actors = peer_actors_builder().ui_gateway(ui_gateway).build();
             addr.try_send(BindMessage { peer_actors }).unwrap();
 
             addr.try_send(FromUiMessage {
                 client_id: 0,
+                role: UiClientRole::Control,
                 json: String::from("pretend I'm JSON"),
             })
             .unwrap();
@@ -456,10 +2039,399 @@ mod tests {
             system.run();
         });
         TestLogHandler::new().await_log_containing(
-            "Error unmarshalling message from UI - ignoring: 'I have a tummyache'",
+            "Error unmarshalling message from UI - answering with an error: 'I have a tummyache'",
             1000,
         );
+        ui_gateway_awaiter.await_message_count(1);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 0,
+                correlation_id: Some(17),
+                data: UiMessage::Error("Unrecognized message: I have a tummyache".to_string())
+            }
+        );
+    }
+
+    fn make_reload_subject(
+        new_configuration: Result<ReloadableConfiguration, String>,
+        log_filter_arc: &Arc<Mutex<Vec<LogFilter>>>,
+    ) -> UiGateway {
+        let mut subject = make_log_filter_subject("", log_filter_arc);
+        subject.reload_args = vec!["SubstratumNode".to_string(), "--ip".to_string()];
+        subject.reloadable_configuration = ReloadableConfiguration {
+            local_ip_addr: IpAddr::from_str("1.2.3.4").unwrap(),
+            log_level: LevelFilter::Warn,
+            ..ReloadableConfiguration::default()
+        };
+        subject.configuration_reader = Box::new(move |args| {
+            assert_eq!(args, &["SubstratumNode".to_string(), "--ip".to_string()]);
+            new_configuration.clone()
+        });
+        subject
+    }
+
+    #[test]
+    fn reload_configuration_message_sends_each_change_to_the_actor_that_handles_it() {
+        let (neighborhood, _, neighborhood_recording_arc) = make_recorder();
+        let (proxy_client, _, proxy_client_recording_arc) = make_recorder();
+        let (accountant, _, accountant_recording_arc) = make_recorder();
+        let (ui_gateway, _, ui_gateway_recording_arc) = make_recorder();
+        let log_filter_arc = Arc::new(Mutex::new(vec![]));
+        let neighbor = NodeDescriptor::from_str("QmlsbA:2.3.4.5:3456").unwrap();
+        let dns_server = SocketAddr::from_str("8.8.8.8:53").unwrap();
+        let system = System::new(
+            "reload_configuration_message_sends_each_change_to_the_actor_that_handles_it",
+        );
+        let subject = make_reload_subject(
+            Ok(ReloadableConfiguration {
+                dns_servers: vec![dns_server],
+                log_level: LevelFilter::Debug,
+                neighbors: vec![neighbor.clone()],
+                rate_pack: rate_pack(100),
+                payable_scan_interval: Duration::from_secs(60),
+                local_ip_addr: IpAddr::from_str("1.2.3.4").unwrap(),
+                fixed: BTreeMap::new(),
+            }),
+            &log_filter_arc,
+        );
+        let addr: Addr<UiGateway> = subject.start();
+        let peer_actors = peer_actors_builder()
+            .neighborhood(neighborhood)
+            .proxy_client(proxy_client)
+            .accountant(accountant)
+            .ui_gateway(ui_gateway)
+            .build();
+        addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        addr.try_send(UiCarrierMessage {
+            client_id: 0,
+            correlation_id: Some(7),
+            data: UiMessage::ReloadConfiguration,
+        })
+        .unwrap();
+        addr.try_send(UiCarrierMessage {
+            client_id: 0,
+            correlation_id: None,
+            data: UiMessage::GetConfiguration,
+        })
+        .unwrap();
+
+        System::current().stop();
+        system.run();
+        let proxy_client_recording = proxy_client_recording_arc.lock().unwrap();
+        assert_eq!(
+            proxy_client_recording.get_record::<SetDnsServersMessage>(0),
+            &SetDnsServersMessage {
+                dns_servers: vec![dns_server]
+            }
+        );
+        assert_eq!(
+            proxy_client_recording.get_record::<SetRatePackMessage>(1),
+            &SetRatePackMessage {
+                rate_pack: rate_pack(100)
+            }
+        );
+        let neighborhood_recording = neighborhood_recording_arc.lock().unwrap();
+        assert_eq!(
+            neighborhood_recording.get_record::<SetInitialNeighborsMessage>(0),
+            &SetInitialNeighborsMessage {
+                neighbors: vec![neighbor.clone()]
+            }
+        );
+        assert_eq!(
+            neighborhood_recording.get_record::<SetRatePackMessage>(1),
+            &SetRatePackMessage {
+                rate_pack: rate_pack(100)
+            }
+        );
+        assert_eq!(
+            accountant_recording_arc
+                .lock()
+                .unwrap()
+                .get_record::<SetPayableScanIntervalMessage>(0),
+            &SetPayableScanIntervalMessage {
+                payable_scan_interval: Duration::from_secs(60)
+            }
+        );
+        assert_eq!(
+            *log_filter_arc.lock().unwrap(),
+            vec![LogFilter::new(LevelFilter::Debug)]
+        );
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 0,
+                correlation_id: Some(7),
+                data: UiMessage::ConfigurationReloaded(vec![
+                    "dns_servers".to_string(),
+                    "log_level".to_string(),
+                    "neighbors".to_string(),
+                    "rate_pack".to_string(),
+                    "payable_scan_interval".to_string(),
+                ])
+            }
+        );
+        match &ui_gateway_recording.get_record::<UiCarrierMessage>(1).data {
+            UiMessage::Configuration(configuration) => {
+                assert_eq!(configuration.dns_servers, vec!["8.8.8.8:53".to_string()]);
+                assert_eq!(configuration.log_level, "DEBUG".to_string());
+                assert_eq!(configuration.neighbors, vec![neighbor.to_string()]);
+                assert_eq!(configuration.rate_pack, rate_pack(100));
+            }
+            x => panic!("Expected Configuration, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn reload_configuration_that_needs_a_restart_is_answered_with_an_error_and_changes_nothing() {
+        init_test_logging();
+        let (neighborhood, _, neighborhood_recording_arc) = make_recorder();
+        let (ui_gateway, _, ui_gateway_recording_arc) = make_recorder();
+        let log_filter_arc = Arc::new(Mutex::new(vec![]));
+        let system = System::new(
+            "reload_configuration_that_needs_a_restart_is_answered_with_an_error_and_changes_nothing",
+        );
+        let mut subject = make_reload_subject(
+            Ok(ReloadableConfiguration {
+                rate_pack: rate_pack(100),
+                fixed: vec![("ui_port", "5334".to_string())].into_iter().collect(),
+                ..ReloadableConfiguration::default()
+            }),
+            &log_filter_arc,
+        );
+        subject
+            .reloadable_configuration
+            .fixed
+            .insert("ui_port", "5333".to_string());
+        let addr: Addr<UiGateway> = subject.start();
+        let peer_actors = peer_actors_builder()
+            .neighborhood(neighborhood)
+            .ui_gateway(ui_gateway)
+            .build();
+        addr.try_send(BindMessage { peer_actors }).unwrap();
+
+        addr.try_send(UiCarrierMessage {
+            client_id: 0,
+            correlation_id: Some(8),
+            data: UiMessage::ReloadConfiguration,
+        })
+        .unwrap();
+
+        System::current().stop();
+        system.run();
+        let expected_error = "Configuration not reloaded: Can't change ip, ui_port without restarting the Node; nothing was reloaded";
+        assert_eq!(neighborhood_recording_arc.lock().unwrap().len(), 0);
+        assert_eq!(log_filter_arc.lock().unwrap().is_empty(), true);
+        assert_eq!(
+            ui_gateway_recording_arc
+                .lock()
+                .unwrap()
+                .get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 0,
+                correlation_id: Some(8),
+                data: UiMessage::Error(expected_error.to_string()),
+            }
+        );
+        TestLogHandler::new()
+            .exists_log_containing(&format!("WARN: UiGateway: {}", expected_error));
+    }
+
+    #[test]
+    fn reloading_unreadable_configuration_keeps_the_configuration_it_had() {
+        let log_filter_arc = Arc::new(Mutex::new(vec![]));
+        let mut subject = make_reload_subject(
+            Err("Invalid value for '--ui_port <UI_PORT>': 80".to_string()),
+            &log_filter_arc,
+        );
+        let before = subject.reloadable_configuration.clone();
+
+        let result = subject.reload_configuration();
+
+        assert_eq!(
+            result,
+            Err("Invalid value for '--ui_port <UI_PORT>': 80".to_string())
+        );
+        assert_eq!(subject.reloadable_configuration, before);
+        assert_eq!(log_filter_arc.lock().unwrap().is_empty(), true);
+    }
+
+    fn make_log_filter_subject(
+        data_directory: &str,
+        log_filter_arc: &Arc<Mutex<Vec<LogFilter>>>,
+    ) -> UiGateway {
+        let mut subject = UiGateway::new(&UiGatewayConfig {
+            ui_port: find_free_port(),
+            node_descriptor: String::from(""),
+            node_configuration: NodeConfiguration {
+                data_directory: data_directory.to_string(),
+                ..NodeConfiguration::default()
+            },
+            ui_tokens: UiTokens::default(),
+            reload_args: vec![],
+            reloadable_configuration: ReloadableConfiguration::default(),
+        });
+        subject.startup_log_filter = LogFilter::new(LevelFilter::Warn);
+        subject.log_filter = LogFilter::new(LevelFilter::Warn);
+        let log_filter_arc = log_filter_arc.clone();
+        subject.log_filter_setter =
+            Box::new(move |filter| log_filter_arc.lock().unwrap().push(filter));
+        subject
+    }
+
+    #[test]
+    fn set_log_level_changes_global_and_component_levels_and_reports_them() {
+        let log_filter_arc = Arc::new(Mutex::new(vec![]));
+        let mut subject = make_log_filter_subject("", &log_filter_arc);
+
+        let global_result = subject.set_log_level(None, Some("Info".to_string()));
+        let component_result = subject.set_log_level(
+            Some("GossipAcceptor".to_string()),
+            Some("trace".to_string()),
+        );
+        subject.set_log_level(Some("ProxyServer".to_string()), Some("off".to_string()));
+        let removal_result = subject.set_log_level(Some("ProxyServer".to_string()), None);
+
+        assert_eq!(
+            global_result,
+            UiMessage::LogLevels(LogLevels {
+                global: "info".to_string(),
+                components: BTreeMap::new(),
+            })
+        );
+        let expected_levels = LogLevels {
+            global: "info".to_string(),
+            components: vec![("GossipAcceptor".to_string(), "trace".to_string())]
+                .into_iter()
+                .collect(),
+        };
+        assert_eq!(
+            component_result,
+            UiMessage::LogLevels(expected_levels.clone())
+        );
+        assert_eq!(
+            removal_result,
+            UiMessage::LogLevels(expected_levels.clone())
+        );
+        assert_eq!(subject.log_levels(), expected_levels);
+        let log_filters = log_filter_arc.lock().unwrap();
+        assert_eq!(log_filters.len(), 4);
+        assert_eq!(log_filters[2].level_for("ProxyServer"), LevelFilter::Off);
+        assert_eq!(
+            log_filters[3],
+            LogFilter::from_str("info,GossipAcceptor=trace").unwrap()
+        );
+    }
+
+    #[test]
+    fn set_log_level_rejects_bad_requests_without_changing_anything() {
+        let log_filter_arc = Arc::new(Mutex::new(vec![]));
+        let mut subject = make_log_filter_subject("", &log_filter_arc);
+
+        let bad_level_result =
+            subject.set_log_level(Some("Neighborhood".to_string()), Some("loud".to_string()));
+        let empty_result = subject.set_log_level(None, None);
+
+        assert_eq!(
+            bad_level_result,
+            UiMessage::Error("'loud' is not a log level".to_string())
+        );
+        assert_eq!(
+            empty_result,
+            UiMessage::Error("SetLogLevel needs a component, a level, or both".to_string())
+        );
+        assert_eq!(subject.log_filter, LogFilter::new(LevelFilter::Warn));
+        assert_eq!(log_filter_arc.lock().unwrap().is_empty(), true);
+    }
+
+    #[test]
+    fn reloading_the_log_filter_reads_the_file_and_reverts_to_the_startup_filter_without_it() {
+        init_test_logging();
+        let data_directory = ensure_node_home_directory_exists(
+            "ui_gateway",
+            "reloading_the_log_filter_reads_the_file_and_reverts_to_the_startup_filter_without_it",
+        );
+        let file_path = data_directory.join(LOG_FILTER_FILE);
+        let log_filter_arc = Arc::new(Mutex::new(vec![]));
+        let mut subject =
+            make_log_filter_subject(data_directory.to_str().unwrap(), &log_filter_arc);
+
+        fs::write(&file_path, "debug,GossipAcceptor=trace\n").unwrap();
+        subject.reload_log_filter();
+        fs::write(&file_path, "debug,GossipAcceptor").unwrap();
+        subject.reload_log_filter();
+        fs::remove_file(&file_path).unwrap();
+        subject.reload_log_filter();
+
+        assert_eq!(
+            *log_filter_arc.lock().unwrap(),
+            vec![
+                LogFilter::from_str("debug,GossipAcceptor=trace").unwrap(),
+                LogFilter::new(LevelFilter::Warn),
+            ]
+        );
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: UiGateway: Ignoring log filter in {:?}: 'GossipAcceptor' should look like 'Component=level' in log filter",
+            file_path
+        ));
+    }
+
+    #[test]
+    fn log_level_messages_are_answered_through_the_ui() {
+        let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+
+        thread::spawn(move || {
+            let log_filter_arc = Arc::new(Mutex::new(vec![]));
+            let subject = make_log_filter_subject("", &log_filter_arc);
+            let system = System::new("log_level_messages_are_answered_through_the_ui");
+            let addr: Addr<UiGateway> = subject.start();
+            let peer_actors = peer_actors_builder().ui_gateway(ui_gateway).build();
+            addr.try_send(BindMessage { peer_actors }).unwrap();
+
+            addr.try_send(UiCarrierMessage {
+                client_id: 42,
+                correlation_id: Some(1),
+                data: UiMessage::SetLogLevel {
+                    component: Some("GossipAcceptor".to_string()),
+                    level: Some("debug".to_string()),
+                },
+            })
+            .unwrap();
+            addr.try_send(UiCarrierMessage {
+                client_id: 42,
+                correlation_id: Some(2),
+                data: UiMessage::GetLogLevels,
+            })
+            .unwrap();
+
+            system.run();
+        });
+        ui_gateway_awaiter.await_message_count(2);
         let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
-        assert_eq!(ui_gateway_recording.len(), 0);
+        let expected_levels = LogLevels {
+            global: "warn".to_string(),
+            components: vec![("GossipAcceptor".to_string(), "debug".to_string())]
+                .into_iter()
+                .collect(),
+        };
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(0),
+            &UiCarrierMessage {
+                client_id: 42,
+                correlation_id: Some(1),
+                data: UiMessage::LogLevels(expected_levels.clone())
+            }
+        );
+        assert_eq!(
+            ui_gateway_recording.get_record::<UiCarrierMessage>(1),
+            &UiCarrierMessage {
+                client_id: 42,
+                correlation_id: Some(2),
+                data: UiMessage::LogLevels(expected_levels)
+            }
+        );
     }
 }
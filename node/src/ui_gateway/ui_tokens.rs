@@ -0,0 +1,113 @@
+// Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
+use crate::sub_lib::ui_gateway::UiTokens;
+use crate::sub_lib::ui_gateway::CONTROL_TOKEN_FILE;
+use crate::sub_lib::ui_gateway::READ_ONLY_TOKEN_FILE;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+const TOKEN_BYTES: usize = 32;
+
+/// Makes fresh UI tokens and leaves them in the data directory, readable only by the user the
+/// Node runs as. A UI that can read a file can use its token.
+pub fn make_ui_tokens(data_directory: &Path) -> io::Result<UiTokens> {
+    fs::create_dir_all(data_directory)?;
+    let tokens = UiTokens {
+        control: generate_token()?,
+        read_only: generate_token()?,
+    };
+    write_token_file(&data_directory.join(CONTROL_TOKEN_FILE), &tokens.control)?;
+    write_token_file(
+        &data_directory.join(READ_ONLY_TOKEN_FILE),
+        &tokens.read_only,
+    )?;
+    Ok(tokens)
+}
+
+fn generate_token() -> io::Result<String> {
+    let mut rng = OsRng::new().map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    let mut bytes = [0u8; TOKEN_BYTES];
+    rng.fill_bytes(&mut bytes);
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+// The old file is removed rather than truncated, so that permissions somebody loosened on it
+// don't carry over to the new token.
+fn write_token_file(path: &Path, token: &str) -> io::Result<()> {
+    if let Err(e) = fs::remove_file(path) {
+        if e.kind() != io::ErrorKind::NotFound {
+            return Err(e);
+        }
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path)?;
+    file.write_all(token.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sub_lib::ui_gateway::UiClientRole;
+    use crate::test_utils::test_utils::ensure_node_home_directory_exists;
+
+    #[test]
+    fn make_ui_tokens_writes_distinct_random_tokens_to_the_data_directory() {
+        let data_directory = ensure_node_home_directory_exists(
+            "ui_tokens",
+            "make_ui_tokens_writes_distinct_random_tokens_to_the_data_directory",
+        );
+
+        let first = make_ui_tokens(&data_directory).unwrap();
+        let second = make_ui_tokens(&data_directory).unwrap();
+
+        assert_eq!(first.control.len(), TOKEN_BYTES * 2);
+        assert_ne!(first.control, first.read_only);
+        assert_ne!(first.control, second.control);
+        assert_eq!(
+            fs::read_to_string(data_directory.join(CONTROL_TOKEN_FILE)).unwrap(),
+            second.control
+        );
+        assert_eq!(
+            fs::read_to_string(data_directory.join(READ_ONLY_TOKEN_FILE)).unwrap(),
+            second.read_only
+        );
+        assert_eq!(
+            second.role_for(&second.read_only),
+            Some(UiClientRole::ReadOnly)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn token_files_are_readable_only_by_their_owner_even_if_they_were_loosened() {
+        use std::os::unix::fs::PermissionsExt;
+        let data_directory = ensure_node_home_directory_exists(
+            "ui_tokens",
+            "token_files_are_readable_only_by_their_owner_even_if_they_were_loosened",
+        );
+        let control_path = data_directory.join(CONTROL_TOKEN_FILE);
+        fs::write(&control_path, "stale").unwrap();
+        fs::set_permissions(&control_path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        make_ui_tokens(&data_directory).unwrap();
+
+        [CONTROL_TOKEN_FILE, READ_ONLY_TOKEN_FILE]
+            .iter()
+            .for_each(|file_name| {
+                let mode = fs::metadata(data_directory.join(file_name))
+                    .unwrap()
+                    .permissions()
+                    .mode();
+                assert_eq!(mode & 0o777, 0o600, "{}", file_name);
+            });
+    }
+}
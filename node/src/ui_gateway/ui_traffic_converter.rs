@@ -1,24 +1,64 @@
 // Copyright (c) 2017-2018, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 
+use crate::sub_lib::ui_gateway::UiEnvelope;
 use crate::sub_lib::ui_gateway::UiMessage;
+use crate::sub_lib::ui_gateway::UI_PROTOCOL_VERSION;
+use serde_json::Value;
 
-#[allow(dead_code)]
 pub const BROADCAST: u64 = 0xFFFFFFFFFFFFFFFF;
 
+#[derive(Debug, PartialEq)]
+pub struct UnmarshalError {
+    // Recovered from the message if possible, so the error can be matched to the request
+    pub correlation_id: Option<u64>,
+    pub message: String,
+}
+
 pub trait UiTrafficConverter {
-    fn marshal(&self, ui_message: UiMessage) -> Result<String, String>;
-    fn unmarshal(&self, json: &str) -> Result<UiMessage, String>;
+    fn marshal(&self, ui_message: UiMessage, correlation_id: Option<u64>)
+        -> Result<String, String>;
+    fn unmarshal(&self, json: &str) -> Result<(UiMessage, Option<u64>), UnmarshalError>;
 }
 
 pub struct UiTrafficConverterReal {}
 
 impl UiTrafficConverter for UiTrafficConverterReal {
-    fn marshal(&self, ui_message: UiMessage) -> Result<String, String> {
-        serde_json::to_string(&ui_message).map_err(|e| e.to_string())
+    fn marshal(
+        &self,
+        ui_message: UiMessage,
+        correlation_id: Option<u64>,
+    ) -> Result<String, String> {
+        match correlation_id {
+            Some(correlation_id) => serde_json::to_string(&UiEnvelope {
+                version: UI_PROTOCOL_VERSION,
+                correlation_id,
+                message: ui_message,
+            }),
+            None => serde_json::to_string(&ui_message),
+        }
+        .map_err(|e| e.to_string())
     }
 
-    fn unmarshal(&self, _json: &str) -> Result<UiMessage, String> {
-        serde_json::from_str(_json).map_err(|e| e.to_string())
+    fn unmarshal(&self, json: &str) -> Result<(UiMessage, Option<u64>), UnmarshalError> {
+        if let Ok(envelope) = serde_json::from_str::<UiEnvelope>(json) {
+            return if envelope.version == UI_PROTOCOL_VERSION {
+                Ok((envelope.message, Some(envelope.correlation_id)))
+            } else {
+                Err(UnmarshalError {
+                    correlation_id: Some(envelope.correlation_id),
+                    message: format!(
+                        "Unsupported protocol version {}; this Node speaks version {}",
+                        envelope.version, UI_PROTOCOL_VERSION
+                    ),
+                })
+            };
+        }
+        serde_json::from_str::<UiMessage>(json)
+            .map(|ui_message| (ui_message, None))
+            .map_err(|e| UnmarshalError {
+                correlation_id: UiTrafficConverterReal::find_correlation_id(json),
+                message: e.to_string(),
+            })
     }
 }
 
@@ -27,6 +67,12 @@ impl UiTrafficConverterReal {
     pub fn new() -> UiTrafficConverterReal {
         UiTrafficConverterReal {}
     }
+
+    fn find_correlation_id(json: &str) -> Option<u64> {
+        serde_json::from_str::<Value>(json)
+            .ok()
+            .and_then(|value| value.get("correlation_id").and_then(|id| id.as_u64()))
+    }
 }
 
 #[cfg(test)]
@@ -40,6 +86,61 @@ mod tests {
         let marshalled = serde_json::to_string(&UiMessage::ShutdownMessage).unwrap();
         let unmarshalled = subject.unmarshal(&marshalled);
 
-        assert_eq!(unmarshalled, Ok(UiMessage::ShutdownMessage));
+        assert_eq!(unmarshalled, Ok((UiMessage::ShutdownMessage, None)));
+    }
+
+    #[test]
+    fn messages_with_correlation_ids_are_marshalled_in_envelopes() {
+        let subject = UiTrafficConverterReal::new();
+
+        let bare = subject.marshal(UiMessage::NodeDescriptor("booga".to_string()), None);
+        let enveloped = subject.marshal(UiMessage::NodeDescriptor("booga".to_string()), Some(17));
+
+        assert_eq!(bare, Ok(r#"{"NodeDescriptor":"booga"}"#.to_string()));
+        assert_eq!(
+            enveloped,
+            Ok(
+                r#"{"version":1,"correlation_id":17,"message":{"NodeDescriptor":"booga"}}"#
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn enveloped_messages_are_unmarshalled_with_their_correlation_ids() {
+        let subject = UiTrafficConverterReal::new();
+
+        let result =
+            subject.unmarshal(r#"{"version":1,"correlation_id":17,"message":"GetNodeStatus"}"#);
+
+        assert_eq!(result, Ok((UiMessage::GetNodeStatus, Some(17))));
+    }
+
+    #[test]
+    fn envelopes_from_other_protocol_versions_are_rejected() {
+        let subject = UiTrafficConverterReal::new();
+
+        let result =
+            subject.unmarshal(r#"{"version":2,"correlation_id":17,"message":"GetNodeStatus"}"#);
+
+        assert_eq!(
+            result,
+            Err(UnmarshalError {
+                correlation_id: Some(17),
+                message: "Unsupported protocol version 2; this Node speaks version 1".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_messages_are_rejected_with_whatever_correlation_id_can_be_found() {
+        let subject = UiTrafficConverterReal::new();
+
+        let enveloped =
+            subject.unmarshal(r#"{"version":1,"correlation_id":17,"message":"GetBooga"}"#);
+        let bare = subject.unmarshal(r#""GetBooga""#);
+
+        assert_eq!(enveloped.err().unwrap().correlation_id, Some(17));
+        assert_eq!(bare.err().unwrap().correlation_id, None);
     }
 }
@@ -1,17 +1,25 @@
 // Copyright (c) 2017-2018, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 use crate::sub_lib::logger::Logger;
 use crate::sub_lib::ui_gateway::FromUiMessage;
+use crate::sub_lib::ui_gateway::UiClientRole;
+use crate::sub_lib::ui_gateway::UiMessage;
+use crate::sub_lib::ui_gateway::UiTokens;
+use crate::ui_gateway::ui_traffic_converter::UiTrafficConverter;
+use crate::ui_gateway::ui_traffic_converter::UiTrafficConverterReal;
+use crate::ui_gateway::ui_traffic_converter::BROADCAST;
 use actix::Recipient;
 use bytes::BytesMut;
 use futures::future::FutureResult;
 use futures::future::{err, ok};
-use futures::sink::Wait;
-use futures::stream::SplitSink;
+use futures::sync::mpsc;
+use futures::sync::mpsc::Sender;
+use futures::sync::oneshot;
 use futures::Future;
 use futures::Sink;
 use futures::Stream;
 use std::any::Any;
 use std::collections::HashMap;
+use std::io;
 use std::net::IpAddr;
 use std::net::Ipv4Addr;
 use std::net::SocketAddr;
@@ -26,14 +34,21 @@ use websocket::server::upgrade::WsUpgrade;
 use websocket::OwnedMessage;
 use websocket::WebSocketError;
 
+// Messages that can wait for a UI client's writer task. A client that falls this far behind is
+// dropped rather than allowed to hold up whoever is sending to it.
+const CLIENT_SEND_BUFFER_SIZE: usize = 1000;
+
 trait ClientWrapper: Send + Any {
     fn as_any(&self) -> &Any;
     fn send(&mut self, item: OwnedMessage) -> Result<(), WebSocketError>;
     fn flush(&mut self) -> Result<(), WebSocketError>;
 }
 
+// Hands messages to the client's writer task without waiting for them to be written.
 struct ClientWrapperReal {
-    delegate: Wait<SplitSink<Framed<TcpStream, MessageCodec<OwnedMessage>>>>,
+    delegate: Sender<OwnedMessage>,
+    // Dropped along with the client, which ends the task reading from it.
+    _reader_stopper: oneshot::Sender<()>,
 }
 
 impl ClientWrapper for ClientWrapperReal {
@@ -42,21 +57,32 @@ impl ClientWrapper for ClientWrapperReal {
     }
 
     fn send(&mut self, item: OwnedMessage) -> Result<(), WebSocketError> {
-        self.delegate.send(item)
+        self.delegate.try_send(item).map_err(|e| {
+            if e.is_full() {
+                WebSocketError::IoError(io::Error::from(io::ErrorKind::WouldBlock))
+            } else {
+                WebSocketError::IoError(io::Error::from(io::ErrorKind::BrokenPipe))
+            }
+        })
     }
 
     fn flush(&mut self) -> Result<(), WebSocketError> {
-        self.delegate.flush()
+        // The writer task flushes after every message it writes.
+        Ok(())
     }
 }
 
 pub trait WebSocketSupervisor {
+    // A client_id of BROADCAST sends to every connected client.
     fn send(&self, client_id: u64, message_json: &str);
+    // Like send, but a client that's gone or can't be written to is dropped instead of panicking.
+    fn send_if_connected(&self, client_id: u64, message_json: &str) -> bool;
 }
 
 pub struct WebSocketSupervisorReal {
     #[allow(dead_code)]
     inner: Arc<Mutex<WebSocketSupervisorInner>>,
+    logger: Logger,
 }
 
 struct WebSocketSupervisorInner {
@@ -64,31 +90,82 @@ struct WebSocketSupervisorInner {
     from_ui_message: Recipient<FromUiMessage>,
     client_id_by_socket_addr: HashMap<SocketAddr, u64>,
     client_by_id: HashMap<u64, Box<ClientWrapper>>,
+    tokens: UiTokens,
+    // Clients that haven't authenticated yet aren't in here, and get nothing but a chance to.
+    role_by_client_id: HashMap<u64, UiClientRole>,
+}
+
+enum UpgradeAuthentication {
+    Absent,
+    Valid(UiClientRole),
+    Invalid,
 }
 
 impl WebSocketSupervisor for WebSocketSupervisorReal {
     fn send(&self, client_id: u64, message_json: &str) {
+        if client_id == BROADCAST {
+            let mut client_ids = {
+                let locked_inner = self.inner.lock().expect("WebSocketSupervisor is poisoned");
+                locked_inner
+                    .role_by_client_id
+                    .keys()
+                    .cloned()
+                    .collect::<Vec<u64>>()
+            };
+            client_ids.sort();
+            client_ids.into_iter().for_each(|client_id| {
+                self.send_if_connected(client_id, message_json);
+            });
+            return;
+        }
+        // The client may have disconnected, or been dropped, before its answer was ready.
+        if !self.send_if_connected(client_id, message_json) {
+            self.logger.debug(format!(
+                "UI client {} is gone; its message wasn't sent",
+                client_id
+            ));
+        }
+    }
+
+    fn send_if_connected(&self, client_id: u64, message_json: &str) -> bool {
         let mut locked_inner = self.inner.lock().expect("WebSocketSupervisor is poisoned");
-        match locked_inner.client_by_id.get_mut(&client_id) {
-            Some(client) => match client.send(OwnedMessage::Text(message_json.to_string())) {
-                Ok(_) => client.flush().expect("Flush error"),
-                Err(e) => panic!("Send error: {:?}", e),
-            },
-            None => panic!("Tried to send to a nonexistent client"),
+        let result = match locked_inner.client_by_id.get_mut(&client_id) {
+            Some(client) => client
+                .send(OwnedMessage::Text(message_json.to_string()))
+                .and_then(|_| client.flush()),
+            None => return false,
         };
+        match result {
+            Ok(_) => true,
+            Err(e) => {
+                self.logger.warning(format!(
+                    "Couldn't send to UI client {}: {:?}; dropping it",
+                    client_id, e
+                ));
+                Self::remove_client(&mut locked_inner, client_id);
+                false
+            }
+        }
     }
 }
 
 impl WebSocketSupervisorReal {
-    pub fn new(port: u16, from_ui_message: Recipient<FromUiMessage>) -> WebSocketSupervisorReal {
+    pub fn new(
+        port: u16,
+        from_ui_message: Recipient<FromUiMessage>,
+        tokens: UiTokens,
+    ) -> WebSocketSupervisorReal {
         let inner = Arc::new(Mutex::new(WebSocketSupervisorInner {
             next_client_id: 0,
             from_ui_message,
             client_id_by_socket_addr: HashMap::new(),
             client_by_id: HashMap::new(),
+            tokens,
+            role_by_client_id: HashMap::new(),
         }));
         let logger = Logger::new("WebSocketSupervisor");
         let logger_1 = logger.clone();
+        let logger_2 = logger.clone();
         let server_address = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), port);
         let server = Server::bind(server_address, &Handle::default())
             .expect(format!("Could not start UI server at {}", server_address).as_str());
@@ -108,7 +185,10 @@ impl WebSocketSupervisorReal {
                 Err(())
             }
         }));
-        WebSocketSupervisorReal { inner }
+        WebSocketSupervisorReal {
+            inner,
+            logger: logger_2,
+        }
     }
 
     fn remove_failures<I, E>(
@@ -139,8 +219,52 @@ impl WebSocketSupervisorReal {
             .contains(&String::from("SubstratumNode-UI"))
         {
             Self::reject_upgrade_request(upgrade, &logger);
-        } else {
-            Self::accept_upgrade_request(upgrade, socket_addr, inner, logger);
+            return;
+        }
+        let authentication = {
+            let locked_inner = inner.lock().expect("WebSocketSupervisor is poisoned");
+            Self::authenticate_upgrade_request(&upgrade, &locked_inner.tokens)
+        };
+        match authentication {
+            UpgradeAuthentication::Absent => {
+                Self::accept_upgrade_request(upgrade, socket_addr, inner, logger, None)
+            }
+            UpgradeAuthentication::Valid(role) => {
+                Self::accept_upgrade_request(upgrade, socket_addr, inner, logger, Some(role))
+            }
+            UpgradeAuthentication::Invalid => {
+                logger.warning(format!(
+                    "UI at {} offered an invalid token; rejecting",
+                    socket_addr
+                ));
+                tokio::spawn(upgrade.reject().then(|_| ok::<(), ()>(())));
+            }
+        }
+    }
+
+    // UIs that can set headers may authenticate with "Authorization: Bearer <token>".
+    fn authenticate_upgrade_request(
+        upgrade: &WsUpgrade<TcpStream, BytesMut>,
+        tokens: &UiTokens,
+    ) -> UpgradeAuthentication {
+        let header_values = match upgrade.request.headers.get_raw("Authorization") {
+            None => return UpgradeAuthentication::Absent,
+            Some(values) => values,
+        };
+        let role_opt = header_values
+            .iter()
+            .filter_map(|value| String::from_utf8(value.clone()).ok())
+            .filter_map(|value| {
+                if value.starts_with("Bearer ") {
+                    tokens.role_for(value["Bearer ".len()..].trim())
+                } else {
+                    None
+                }
+            })
+            .next();
+        match role_opt {
+            Some(role) => UpgradeAuthentication::Valid(role),
+            None => UpgradeAuthentication::Invalid,
         }
     }
 
@@ -149,6 +273,7 @@ impl WebSocketSupervisorReal {
         socket_addr: SocketAddr,
         inner: Arc<Mutex<WebSocketSupervisorInner>>,
         logger: &Logger,
+        role_opt: Option<UiClientRole>,
     ) {
         let logger_clone = logger.clone();
         logger_clone.info(format!("UI connected at {}", socket_addr));
@@ -157,7 +282,7 @@ impl WebSocketSupervisorReal {
                 .use_protocol("SubstratumNode-UI")
                 .accept()
                 .map(move |(client, _)| {
-                    Self::handle_connection(client, &inner, &logger_clone, socket_addr);
+                    Self::handle_connection(client, &inner, &logger_clone, socket_addr, role_opt);
                 });
         tokio::spawn(upgrade_future.then(|result| {
             match result {
@@ -180,13 +305,22 @@ impl WebSocketSupervisorReal {
         inner: &Arc<Mutex<WebSocketSupervisorInner>>,
         logger: &Logger,
         socket_addr: SocketAddr,
+        role_opt: Option<UiClientRole>,
     ) {
         let logger_1 = logger.clone();
         let logger_2 = logger.clone();
         let inner_1 = inner.clone();
+        let logger_3 = logger.clone();
         let (outgoing, incoming) = client.split();
-        // "Going synchronous" here to avoid calling .send() on an async Sink, which consumes it
-        let sync_outgoing: Wait<SplitSink<_>> = outgoing.wait();
+        let (sender, receiver) = mpsc::channel(CLIENT_SEND_BUFFER_SIZE);
+        let (reader_stopper, reader_stopped) = oneshot::channel::<()>();
+        tokio::spawn(
+            receiver
+                .forward(outgoing.sink_map_err(move |e| {
+                    logger_3.warning(format!("Couldn't write to UI at {}: {:?}", socket_addr, e))
+                }))
+                .then(|_| ok::<(), ()>(())),
+        );
         let mut locked_inner = inner.lock().expect("WebSocketSupervisor is poisoned");
         let client_id = locked_inner.next_client_id;
         locked_inner.next_client_id += 1;
@@ -196,9 +330,13 @@ impl WebSocketSupervisorReal {
         locked_inner.client_by_id.insert(
             client_id,
             Box::new(ClientWrapperReal {
-                delegate: sync_outgoing,
+                delegate: sender,
+                _reader_stopper: reader_stopper,
             }),
         );
+        if let Some(role) = role_opt {
+            locked_inner.role_by_client_id.insert(client_id, role);
+        }
         let incoming_future = incoming
             .then(move |result| Self::handle_websocket_errors(result, &logger_2, socket_addr))
             .map(move |owned_message| match owned_message {
@@ -214,7 +352,9 @@ impl WebSocketSupervisorReal {
                 OwnedMessage::Ping(_) => Self::handle_other_message(&logger_1, socket_addr, "ping"),
                 OwnedMessage::Pong(_) => Self::handle_other_message(&logger_1, socket_addr, "pong"),
             })
-            .for_each(|_| ok::<(), ()>(()));
+            .for_each(|_| ok::<(), ()>(()))
+            .select(reader_stopped.then(|_| ok::<(), ()>(())))
+            .then(|_| ok::<(), ()>(()));
 
         tokio::spawn(incoming_future);
     }
@@ -225,26 +365,69 @@ impl WebSocketSupervisorReal {
         socket_addr: SocketAddr,
         message: &str,
     ) -> FutureResult<(), ()> {
-        let locked_inner = inner_arc.lock().expect("WebSocketSupervisor is poisoned");
-        match locked_inner.client_id_by_socket_addr.get(&socket_addr) {
+        let mut locked_inner = inner_arc.lock().expect("WebSocketSupervisor is poisoned");
+        let client_id = match locked_inner.client_id_by_socket_addr.get(&socket_addr) {
             None => {
-                logger.warning(
-                    "WebSocketSupervisor got a message from a client that never connected!"
-                        .to_string(),
-                );
-                err::<(), ()>(()) // end the stream
-            }
-            Some(client_id_ref) => {
-                locked_inner
-                    .from_ui_message
-                    .try_send(FromUiMessage {
-                        client_id: *client_id_ref,
-                        json: String::from(message),
-                    })
-                    .expect("UiGateway is dead");
-                ok::<(), ()>(())
+                logger.debug(format!(
+                    "UI at {} has been dropped; ignoring its message",
+                    socket_addr
+                ));
+                return err::<(), ()>(()); // end the stream
             }
+            Some(client_id_ref) => *client_id_ref,
+        };
+        let role = match locked_inner.role_by_client_id.get(&client_id) {
+            Some(role) => *role,
+            None => match Self::authenticate_first_message(&locked_inner.tokens, message) {
+                Some(role) => {
+                    locked_inner.role_by_client_id.insert(client_id, role);
+                    role
+                }
+                None => {
+                    logger.warning(format!(
+                        "UI at {} didn't authenticate; disconnecting",
+                        socket_addr
+                    ));
+                    Self::refuse_unauthenticated(&mut locked_inner, client_id, socket_addr, logger);
+                    return err::<(), ()>(()); // end the stream
+                }
+            },
+        };
+        locked_inner
+            .from_ui_message
+            .try_send(FromUiMessage {
+                client_id,
+                role,
+                json: String::from(message),
+            })
+            .expect("UiGateway is dead");
+        ok::<(), ()>(())
+    }
+
+    fn authenticate_first_message(tokens: &UiTokens, message: &str) -> Option<UiClientRole> {
+        match UiTrafficConverterReal::new().unmarshal(message) {
+            Ok((UiMessage::Authenticate { token }, _)) => tokens.role_for(&token),
+            _ => None,
+        }
+    }
+
+    fn refuse_unauthenticated(
+        locked_inner: &mut WebSocketSupervisorInner,
+        client_id: u64,
+        socket_addr: SocketAddr,
+        logger: &Logger,
+    ) {
+        let refusal = UiTrafficConverterReal::new()
+            .marshal(UiMessage::Error("Not authenticated".to_string()), None)
+            .expect("Internal error: failed to marshal UiMessage");
+        if let Some(client) = locked_inner.client_by_id.get_mut(&client_id) {
+            // It's being disconnected anyway; there's nothing to do if it doesn't hear why.
+            let _ = client
+                .send(OwnedMessage::Text(refusal))
+                .and_then(|_| client.flush());
         }
+        Self::close_connection(locked_inner, client_id, socket_addr, logger);
+        Self::remove_client(locked_inner, client_id);
     }
 
     fn handle_close_message(
@@ -254,13 +437,15 @@ impl WebSocketSupervisorReal {
     ) -> FutureResult<(), ()> {
         logger.info(format!("UI at {} disconnected", socket_addr));
         let mut locked_inner = inner_arc.lock().expect("WebSocketSupervisor is poisoned");
-        let client_id = match locked_inner.client_id_by_socket_addr.remove(&socket_addr) {
-            None => {
-                panic!("WebSocketSupervisor got a disconnect from a client that never connected!")
-            }
-            Some(client_id) => client_id,
-        };
-        Self::close_connection(&mut locked_inner, client_id, socket_addr, &logger);
+        // A client that was dropped has nothing left to close.
+        if let Some(client_id) = locked_inner
+            .client_id_by_socket_addr
+            .get(&socket_addr)
+            .cloned()
+        {
+            Self::close_connection(&mut locked_inner, client_id, socket_addr, &logger);
+            Self::remove_client(&mut locked_inner, client_id);
+        }
 
         err::<(), ()>(()) // end the stream
     }
@@ -301,19 +486,28 @@ impl WebSocketSupervisorReal {
         logger: &Logger,
     ) {
         let client = match locked_inner.client_by_id.get_mut(&client_id) {
-            None => panic!("WebSocketSupervisor got a disconnect from a client that has disappeared from the stable!"),
+            None => return,
             Some(client) => client,
         };
-        match client.send(OwnedMessage::Close(None)) {
-            Err(e) => logger.warning(format!(
+        if let Err(e) = client
+            .send(OwnedMessage::Close(None))
+            .and_then(|_| client.flush())
+        {
+            logger.warning(format!(
                 "Error acknowledging connection closure from UI at {}: {:?}",
                 socket_addr, e
-            )),
-            Ok(_) => client
-                .flush()
-                .expect(format!("Couldn't flush transmission to UI at {}", socket_addr).as_str()),
+            ))
         }
     }
+
+    // Dropping the client also ends its reader and, once it's written what it has, its writer.
+    fn remove_client(locked_inner: &mut WebSocketSupervisorInner, client_id: u64) {
+        locked_inner.client_by_id.remove(&client_id);
+        locked_inner.role_by_client_id.remove(&client_id);
+        locked_inner
+            .client_id_by_socket_addr
+            .retain(|_, existing_id| *existing_id != client_id);
+    }
 }
 
 #[cfg(test)]
@@ -324,6 +518,7 @@ mod tests {
     use crate::test_utils::logging::TestLogHandler;
     use crate::test_utils::recorder::make_recorder;
     use crate::test_utils::recorder::Recorder;
+    use crate::test_utils::recorder::Recording;
     use crate::test_utils::test_utils::find_free_port;
     use crate::test_utils::test_utils::wait_for;
     use actix::Actor;
@@ -332,13 +527,25 @@ mod tests {
     use futures::future::lazy;
     use std::collections::HashSet;
     use std::net::Shutdown;
+    use std::str::FromStr;
     use std::thread;
     use std::time::Duration;
     use websocket::client::sync::Client;
+    use websocket::header::Headers;
     use websocket::stream::sync::TcpStream;
     use websocket::ClientBuilder;
     use websocket::Message;
 
+    const CONTROL_TOKEN: &str = "control-token";
+    const READ_ONLY_TOKEN: &str = "read-only-token";
+
+    fn test_tokens() -> UiTokens {
+        UiTokens {
+            control: CONTROL_TOKEN.to_string(),
+            read_only: READ_ONLY_TOKEN.to_string(),
+        }
+    }
+
     impl WebSocketSupervisorReal {
         fn inject_mock_client(&self, mock_client: ClientWrapperMock) -> u64 {
             let mut locked_inner = self.inner.lock().unwrap();
@@ -347,6 +554,9 @@ mod tests {
             locked_inner
                 .client_by_id
                 .insert(client_id, Box::new(mock_client));
+            locked_inner
+                .role_by_client_id
+                .insert(client_id, UiClientRole::Control);
             client_id
         }
 
@@ -426,12 +636,45 @@ mod tests {
     }
 
     fn make_client(port: u16, protocol: &str) -> Result<Client<TcpStream>, WebSocketError> {
+        make_client_with_token(port, protocol, Some(CONTROL_TOKEN))
+    }
+
+    fn make_client_with_token(
+        port: u16,
+        protocol: &str,
+        token_opt: Option<&str>,
+    ) -> Result<Client<TcpStream>, WebSocketError> {
+        let mut headers = Headers::new();
+        if let Some(token) = token_opt {
+            headers.set_raw(
+                "Authorization",
+                vec![format!("Bearer {}", token).into_bytes()],
+            );
+        }
         ClientBuilder::new(format!("ws://127.0.0.1:{}", port).as_str())
             .expect("ClientBuilder could not be built")
             .add_protocol(protocol)
+            .custom_headers(&headers)
             .connect_insecure()
     }
 
+    fn start_subject(port: u16, ui_gateway: Recorder, name: &'static str) {
+        thread::spawn(move || {
+            let system = System::new(name);
+            let from_ui_message = {
+                let addr: Addr<Recorder> = ui_gateway.start();
+                addr.recipient::<FromUiMessage>()
+            };
+            let subject = lazy(move || {
+                let _subject = WebSocketSupervisorReal::new(port, from_ui_message, test_tokens());
+                Ok(())
+            });
+            actix::spawn(subject);
+            system.run();
+        });
+        wait_for_server(port);
+    }
+
     fn wait_for_client(port: u16, protocol: &str) -> Client<TcpStream> {
         let mut one_client_opt: Option<Client<TcpStream>> = None;
         wait_for(None, None, || match make_client(port, protocol) {
@@ -475,7 +718,7 @@ mod tests {
                 addr.recipient::<FromUiMessage>()
             };
             let subject = lazy(move || {
-                let _subject = WebSocketSupervisorReal::new(port, from_ui_message);
+                let _subject = WebSocketSupervisorReal::new(port, from_ui_message, test_tokens());
                 Ok(())
             });
             actix::spawn(subject);
@@ -500,7 +743,7 @@ mod tests {
                 addr.recipient::<FromUiMessage>()
             };
             let subject = lazy(move || {
-                let _subject = WebSocketSupervisorReal::new(port, from_ui_message);
+                let _subject = WebSocketSupervisorReal::new(port, from_ui_message, test_tokens());
                 Ok(())
             });
             actix::spawn(subject);
@@ -530,7 +773,7 @@ mod tests {
                 addr.recipient::<FromUiMessage>()
             };
             let subject = lazy(move || {
-                let _subject = WebSocketSupervisorReal::new(port, from_ui_message);
+                let _subject = WebSocketSupervisorReal::new(port, from_ui_message, test_tokens());
                 Ok(())
             });
             actix::spawn(subject);
@@ -577,7 +820,7 @@ mod tests {
                 addr.recipient::<FromUiMessage>()
             };
             let subject = lazy(move || {
-                let _subject = WebSocketSupervisorReal::new(port, from_ui_message);
+                let _subject = WebSocketSupervisorReal::new(port, from_ui_message, test_tokens());
                 Ok(())
             });
             actix::spawn(subject);
@@ -637,7 +880,7 @@ mod tests {
                 addr.recipient::<FromUiMessage>()
             };
             let subject = lazy(move || {
-                let _subject = WebSocketSupervisorReal::new(port, from_ui_message);
+                let _subject = WebSocketSupervisorReal::new(port, from_ui_message, test_tokens());
                 Ok(())
             });
             actix::spawn(subject);
@@ -658,6 +901,7 @@ mod tests {
             ui_gateway_recording.get_record::<FromUiMessage>(0),
             &FromUiMessage {
                 client_id: 0,
+                role: UiClientRole::Control,
                 json: String::from("One")
             }
         );
@@ -676,7 +920,7 @@ mod tests {
                 addr.recipient::<FromUiMessage>()
             };
             let subject = lazy(move || {
-                let _subject = WebSocketSupervisorReal::new(port, from_ui_message);
+                let _subject = WebSocketSupervisorReal::new(port, from_ui_message, test_tokens());
                 Ok(())
             });
             actix::spawn(subject);
@@ -698,6 +942,7 @@ mod tests {
             ui_gateway_recording.get_record::<FromUiMessage>(0),
             &FromUiMessage {
                 client_id: 0,
+                role: UiClientRole::Control,
                 json: String::from("One")
             }
         );
@@ -705,32 +950,164 @@ mod tests {
     }
 
     #[test]
-    fn send_sends_a_message_to_the_client() {
+    fn client_with_read_only_token_in_upgrade_request_is_marked_read_only() {
+        let port = find_free_port();
+        let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        start_subject(
+            port,
+            ui_gateway,
+            "client_with_read_only_token_in_upgrade_request_is_marked_read_only",
+        );
+        let mut client =
+            make_client_with_token(port, "SubstratumNode-UI", Some(READ_ONLY_TOKEN)).unwrap();
+
+        client.send_message(&Message::text("One")).unwrap();
+
+        ui_gateway_awaiter.await_message_count(1);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<FromUiMessage>(0),
+            &FromUiMessage {
+                client_id: 0,
+                role: UiClientRole::ReadOnly,
+                json: String::from("One")
+            }
+        );
+    }
+
+    #[test]
+    fn client_with_invalid_token_in_upgrade_request_is_rejected() {
+        init_test_logging();
         let port = find_free_port();
         let (ui_gateway, _, _) = make_recorder();
-        let ui_gateway_recipient = ui_gateway.start().recipient::<FromUiMessage>();
-        let system = System::new("receive_sends_a_message_to_the_client");
-        let mut client_id = 0;
-        let lazy_future = lazy(move || {
-            let subject = WebSocketSupervisorReal::new(port, ui_gateway_recipient);
-            let mut mock_client = ClientWrapperMock::new();
-            mock_client.send_results.push(Ok(()));
-            mock_client.flush_results.push(Ok(()));
-            client_id = subject.inject_mock_client(mock_client);
+        start_subject(
+            port,
+            ui_gateway,
+            "client_with_invalid_token_in_upgrade_request_is_rejected",
+        );
+
+        let result = make_client_with_token(port, "SubstratumNode-UI", Some("booga"));
 
-            let json_string = "{totally: 'valid'}";
+        assert_eq!(result.is_err(), true);
+        TestLogHandler::new().await_log_matching(
+            "UI at 127\\.0\\.0\\.1:\\d+ offered an invalid token; rejecting",
+            1000,
+        );
+    }
 
-            subject.send(client_id, json_string);
+    #[test]
+    fn client_without_token_can_authenticate_with_its_first_message() {
+        let port = find_free_port();
+        let (ui_gateway, ui_gateway_awaiter, ui_gateway_recording_arc) = make_recorder();
+        start_subject(
+            port,
+            ui_gateway,
+            "client_without_token_can_authenticate_with_its_first_message",
+        );
+        let mut client = make_client_with_token(port, "SubstratumNode-UI", None).unwrap();
+        let authenticate = format!(r#"{{"Authenticate":{{"token":"{}"}}}}"#, READ_ONLY_TOKEN);
+
+        client
+            .send_message(&Message::text(authenticate.as_str()))
+            .unwrap();
+        client.send_message(&Message::text("Two")).unwrap();
+
+        ui_gateway_awaiter.await_message_count(2);
+        let ui_gateway_recording = ui_gateway_recording_arc.lock().unwrap();
+        assert_eq!(
+            ui_gateway_recording.get_record::<FromUiMessage>(0),
+            &FromUiMessage {
+                client_id: 0,
+                role: UiClientRole::ReadOnly,
+                json: authenticate
+            }
+        );
+        assert_eq!(
+            ui_gateway_recording.get_record::<FromUiMessage>(1),
+            &FromUiMessage {
+                client_id: 0,
+                role: UiClientRole::ReadOnly,
+                json: String::from("Two")
+            }
+        );
+    }
+
+    #[test]
+    fn client_without_token_that_does_not_authenticate_is_refused_and_disconnected() {
+        init_test_logging();
+        let port = find_free_port();
+        let (ui_gateway, _, ui_gateway_recording_arc) = make_recorder();
+        start_subject(
+            port,
+            ui_gateway,
+            "client_without_token_that_does_not_authenticate_is_refused_and_disconnected",
+        );
+        let mut client = make_client_with_token(port, "SubstratumNode-UI", None).unwrap();
+
+        client
+            .send_message(&Message::text(r#"{"ShutdownMessage":null}"#))
+            .unwrap();
+
+        let refusal = client.recv_message().unwrap();
+        let close = client.recv_message().unwrap();
+        assert_eq!(
+            refusal,
+            OwnedMessage::Text(r#"{"Error":"Not authenticated"}"#.to_string())
+        );
+        assert_eq!(close, OwnedMessage::Close(None));
+        TestLogHandler::new().await_log_matching(
+            "UI at 127\\.0\\.0\\.1:\\d+ didn't authenticate; disconnecting",
+            1000,
+        );
+        assert_eq!(ui_gateway_recording_arc.lock().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn broadcast_goes_to_every_client_and_send_if_connected_drops_failed_clients() {
+        let port = find_free_port();
+        let (ui_gateway, _, _) = make_recorder();
+        let ui_gateway_recipient = ui_gateway.start().recipient::<FromUiMessage>();
+        let system = System::new(
+            "broadcast_goes_to_every_client_and_send_if_connected_drops_failed_clients",
+        );
+        let lazy_future = lazy(move || {
+            let subject = WebSocketSupervisorReal::new(port, ui_gateway_recipient, test_tokens());
+            let mut good_client = ClientWrapperMock::new();
+            good_client.send_results = vec![Ok(()), Ok(())];
+            good_client.flush_results = vec![Ok(()), Ok(())];
+            let good_client_id = subject.inject_mock_client(good_client);
+            let mut bad_client = ClientWrapperMock::new();
+            bad_client.send_results = vec![Err(WebSocketError::NoDataAvailable)];
+            let bad_client_id = subject.inject_mock_client(bad_client);
+
+            subject.send(BROADCAST, "broadcast");
+            let good_result = subject.send_if_connected(good_client_id, "unicast");
+            let bad_result = subject.send_if_connected(bad_client_id, "unicast");
+            let missing_result = subject.send_if_connected(12345, "unicast");
 
-            let mock_client_ref = subject.get_mock_client(client_id);
             assert_eq!(
-                &OwnedMessage::Text(String::from(json_string)),
-                mock_client_ref
+                *subject
+                    .get_mock_client(good_client_id)
                     .send_params
                     .lock()
+                    .unwrap(),
+                vec![
+                    OwnedMessage::Text("broadcast".to_string()),
+                    OwnedMessage::Text("unicast".to_string())
+                ]
+            );
+            assert_eq!(
+                subject
+                    .inner
+                    .lock()
                     .unwrap()
-                    .get(0)
-                    .expect("Send was not called")
+                    .client_by_id
+                    .contains_key(&bad_client_id),
+                false
+            );
+            assert_eq!(
+                (good_result, bad_result, missing_result),
+                (true, false, false)
             );
             Ok(())
         });
@@ -739,76 +1116,193 @@ mod tests {
         system.run();
     }
 
+    // No server, so no need to run the System; only for exercising the clients directly.
+    fn make_serverless_subject() -> (WebSocketSupervisorReal, Arc<Mutex<Recording>>) {
+        let (ui_gateway, _, ui_gateway_recording_arc) = make_recorder();
+        let subject = WebSocketSupervisorReal {
+            inner: Arc::new(Mutex::new(WebSocketSupervisorInner {
+                next_client_id: 0,
+                from_ui_message: ui_gateway.start().recipient::<FromUiMessage>(),
+                client_id_by_socket_addr: HashMap::new(),
+                client_by_id: HashMap::new(),
+                tokens: test_tokens(),
+                role_by_client_id: HashMap::new(),
+            })),
+            logger: Logger::new("WebSocketSupervisor"),
+        };
+        (subject, ui_gateway_recording_arc)
+    }
+
+    fn inject_real_client(
+        subject: &WebSocketSupervisorReal,
+        sender: Sender<OwnedMessage>,
+    ) -> (u64, oneshot::Receiver<()>) {
+        let (reader_stopper, reader_stopped) = oneshot::channel();
+        let mut locked_inner = subject.inner.lock().unwrap();
+        locked_inner
+            .client_id_by_socket_addr
+            .insert(client_socket_addr(), 42);
+        locked_inner.client_by_id.insert(
+            42,
+            Box::new(ClientWrapperReal {
+                delegate: sender,
+                _reader_stopper: reader_stopper,
+            }),
+        );
+        locked_inner
+            .role_by_client_id
+            .insert(42, UiClientRole::ReadOnly);
+        (42, reader_stopped)
+    }
+
+    fn client_socket_addr() -> SocketAddr {
+        SocketAddr::from_str("127.0.0.1:4242").unwrap()
+    }
+
+    fn assert_client_is_gone(subject: &WebSocketSupervisorReal, client_id: u64) {
+        let locked_inner = subject.inner.lock().unwrap();
+        assert_eq!(locked_inner.client_by_id.contains_key(&client_id), false);
+        assert_eq!(
+            locked_inner.role_by_client_id.contains_key(&client_id),
+            false
+        );
+        assert_eq!(
+            locked_inner
+                .client_id_by_socket_addr
+                .values()
+                .any(|id| *id == client_id),
+            false
+        );
+    }
+
     #[test]
-    #[should_panic(expected = "Flush error: NoDataAvailable")]
-    fn send_tries_to_send_message_and_panics_on_flush() {
-        let port = find_free_port();
-        let (ui_gateway, _, _) = make_recorder();
-        let ui_gateway_recipient = ui_gateway.start().recipient::<FromUiMessage>();
-        let system = System::new("receive_sends_a_message_and_errors_on_flush");
-        let mut client_id = 0;
-        let lazy_future = lazy(move || {
-            let subject = WebSocketSupervisorReal::new(port, ui_gateway_recipient);
-            let mut mock_client = ClientWrapperMock::new();
-            mock_client.send_results.push(Ok(()));
-            mock_client
-                .flush_results
-                .push(Err(WebSocketError::NoDataAvailable));
-            client_id = subject.inject_mock_client(mock_client);
+    fn send_sends_a_message_to_the_client() {
+        let _system = System::new("send_sends_a_message_to_the_client");
+        let (subject, _) = make_serverless_subject();
+        let mut mock_client = ClientWrapperMock::new();
+        mock_client.send_results.push(Ok(()));
+        mock_client.flush_results.push(Ok(()));
+        let client_id = subject.inject_mock_client(mock_client);
+        let json_string = "{totally: 'valid'}";
 
-            let json_string = "{totally: 'valid'}";
+        subject.send(client_id, json_string);
 
-            subject.send(client_id, json_string);
-            Ok(())
-        });
-        actix::spawn(lazy_future);
-        System::current().stop();
-        system.run();
+        assert_eq!(
+            *subject
+                .get_mock_client(client_id)
+                .send_params
+                .lock()
+                .unwrap(),
+            vec![OwnedMessage::Text(String::from(json_string))]
+        );
     }
 
     #[test]
-    #[should_panic(expected = "Send error: NoDataAvailable")]
-    fn send_tries_to_send_message_and_panics() {
-        let port = find_free_port();
-        let (ui_gateway, _, _) = make_recorder();
-        let ui_gateway_recipient = ui_gateway.start().recipient::<FromUiMessage>();
-        let system = System::new("receive_sends_a_message_and_errors_on_send");
-        let mut client_id = 0;
-        let lazy_future = lazy(move || {
-            let subject = WebSocketSupervisorReal::new(port, ui_gateway_recipient);
-            let mut mock_client = ClientWrapperMock::new();
-            mock_client
-                .send_results
-                .push(Err(WebSocketError::NoDataAvailable));
-            client_id = subject.inject_mock_client(mock_client);
+    fn send_drops_a_client_that_fails_to_send_or_flush_instead_of_panicking() {
+        init_test_logging();
+        let _system =
+            System::new("send_drops_a_client_that_fails_to_send_or_flush_instead_of_panicking");
+        let (subject, _) = make_serverless_subject();
+        let mut unsendable_client = ClientWrapperMock::new();
+        unsendable_client
+            .send_results
+            .push(Err(WebSocketError::NoDataAvailable));
+        let unsendable_client_id = subject.inject_mock_client(unsendable_client);
+        let mut unflushable_client = ClientWrapperMock::new();
+        unflushable_client.send_results.push(Ok(()));
+        unflushable_client
+            .flush_results
+            .push(Err(WebSocketError::NoDataAvailable));
+        let unflushable_client_id = subject.inject_mock_client(unflushable_client);
+
+        subject.send(unsendable_client_id, "{}");
+        subject.send(unflushable_client_id, "{}");
+
+        assert_client_is_gone(&subject, unsendable_client_id);
+        assert_client_is_gone(&subject, unflushable_client_id);
+        TestLogHandler::new().exists_log_containing(&format!(
+            "WARN: WebSocketSupervisor: Couldn't send to UI client {}: NoDataAvailable; dropping it",
+            unflushable_client_id
+        ));
+    }
 
-            let json_string = "{totally: 'valid'}";
+    #[test]
+    fn send_drops_a_client_whose_buffer_is_full_and_stops_its_reader() {
+        let _system = System::new("send_drops_a_client_whose_buffer_is_full_and_stops_its_reader");
+        let (subject, _) = make_serverless_subject();
+        // Nothing ever reads from the receiver, as if the UI had stopped reading.
+        let (sender, _receiver) = mpsc::channel(CLIENT_SEND_BUFFER_SIZE);
+        let (client_id, reader_stopped) = inject_real_client(&subject, sender);
 
-            subject.send(client_id, json_string);
-            Ok(())
-        });
-        actix::spawn(lazy_future);
-        System::current().stop();
-        system.run();
+        (0..(CLIENT_SEND_BUFFER_SIZE + 2)).for_each(|_| subject.send(client_id, "reply"));
+
+        assert_client_is_gone(&subject, client_id);
+        assert_eq!(reader_stopped.wait(), Err(oneshot::Canceled));
     }
 
     #[test]
-    #[should_panic(expected = "Tried to send to a nonexistent client")]
-    fn send_fails_to_look_up_client_to_send_to() {
-        let port = find_free_port();
-        let (ui_gateway, _, _) = make_recorder();
-        let ui_gateway_recipient = ui_gateway.start().recipient::<FromUiMessage>();
-        let system = System::new("receive_sends_a_message_and_errors_on_send");
-        let lazy_future = lazy(move || {
-            let subject = WebSocketSupervisorReal::new(port, ui_gateway_recipient);
+    fn send_if_connected_drops_a_client_whose_writer_has_fallen_behind_instead_of_waiting() {
+        let _system = System::new(
+            "send_if_connected_drops_a_client_whose_writer_has_fallen_behind_instead_of_waiting",
+        );
+        let (subject, _) = make_serverless_subject();
+        let (sender, _receiver) = mpsc::channel(CLIENT_SEND_BUFFER_SIZE);
+        let (client_id, _reader_stopped) = inject_real_client(&subject, sender);
+
+        let results = (0..(CLIENT_SEND_BUFFER_SIZE + 2))
+            .map(|_| subject.send_if_connected(client_id, "event"))
+            .collect::<Vec<bool>>();
+
+        let mut expected_results = vec![true; CLIENT_SEND_BUFFER_SIZE + 1];
+        expected_results.push(false);
+        assert_eq!(results, expected_results);
+        assert_client_is_gone(&subject, client_id);
+    }
 
-            let json_string = "{totally: 'valid'}";
+    #[test]
+    fn send_if_connected_drops_a_client_whose_writer_has_gone() {
+        let _system = System::new("send_if_connected_drops_a_client_whose_writer_has_gone");
+        let (subject, _) = make_serverless_subject();
+        let (sender, receiver) = mpsc::channel(CLIENT_SEND_BUFFER_SIZE);
+        drop(receiver);
+        let (client_id, _reader_stopped) = inject_real_client(&subject, sender);
 
-            subject.send(7, json_string);
-            Ok(())
-        });
-        actix::spawn(lazy_future);
+        let result = subject.send_if_connected(client_id, "event");
+
+        assert_eq!(result, false);
+        assert_client_is_gone(&subject, client_id);
+    }
+
+    #[test]
+    fn messages_from_and_to_an_already_dropped_client_are_ignored() {
+        let system = System::new("messages_from_and_to_an_already_dropped_client_are_ignored");
+        let (subject, ui_gateway_recording_arc) = make_serverless_subject();
+        let (sender, receiver) = mpsc::channel(CLIENT_SEND_BUFFER_SIZE);
+        drop(receiver);
+        let (client_id, _reader_stopped) = inject_real_client(&subject, sender);
+        subject.send_if_connected(client_id, "event");
+        let logger = Logger::new("WebSocketSupervisor");
+
+        let text_result = WebSocketSupervisorReal::handle_text_message(
+            &subject.inner,
+            &logger,
+            client_socket_addr(),
+            "{\"component\": \"NeighborhoodGraph\"}",
+        )
+        .wait();
+        let close_result = WebSocketSupervisorReal::handle_close_message(
+            &subject.inner,
+            &logger,
+            client_socket_addr(),
+        )
+        .wait();
+        subject.send(client_id, "late reply");
+
+        // Ending the stream stops the reader.
+        assert_eq!(text_result, Err(()));
+        assert_eq!(close_result, Err(()));
         System::current().stop();
         system.run();
+        assert_eq!(ui_gateway_recording_arc.lock().unwrap().len(), 0);
     }
 }
@@ -3,22 +3,42 @@
 mod utils;
 
 use futures::future::*;
+use node_lib::sub_lib::ui_gateway::CONTROL_TOKEN_FILE;
 use node_lib::sub_lib::ui_gateway::DEFAULT_UI_PORT;
+use std::fs;
 use std::time::Duration;
 use tokio::prelude::*;
 use tokio::runtime::Runtime;
+use websocket::header::Headers;
 use websocket::ClientBuilder;
 use websocket::OwnedMessage;
 
+fn control_headers() -> Headers {
+    // The Node writes its tokens to the default data directory, which is where it's running.
+    let token_path = dirs::data_dir()
+        .expect("No data directory")
+        .join(CONTROL_TOKEN_FILE);
+    let token = fs::read_to_string(&token_path)
+        .expect(&format!("Couldn't read UI token from {:?}", token_path));
+    let mut headers = Headers::new();
+    headers.set_raw(
+        "Authorization",
+        vec![format!("Bearer {}", token).into_bytes()],
+    );
+    headers
+}
+
 #[test]
 fn ui_gateway_message_integration() {
     fdlimit::raise_fd_limit();
     let mut node = utils::SubstratumNode::start(None);
+    let headers = control_headers();
 
     let descriptor_client =
         ClientBuilder::new(format!("ws://127.0.0.1:{}", DEFAULT_UI_PORT).as_str())
             .unwrap()
             .add_protocol("SubstratumNode-UI")
+            .custom_headers(&headers)
             .async_connect_insecure()
             .and_then(|(s, _)| {
                 s.send(OwnedMessage::Text(String::from(
@@ -39,6 +59,7 @@ fn ui_gateway_message_integration() {
         ClientBuilder::new(format!("ws://127.0.0.1:{}", DEFAULT_UI_PORT).as_str())
             .unwrap()
             .add_protocol("SubstratumNode-UI")
+            .custom_headers(&headers)
             .async_connect_insecure()
             .and_then(|(s, _)| {
                 s.send(OwnedMessage::Text(String::from(